@@ -172,7 +172,7 @@ const SellerDashboard: React.FC<SellerDashboardProps> = ({ onBack }) => {
       );
       if (response.ok) {
         const data = await response.json();
-        setProducts(data.products || []);
+        setProducts(data.items || []);
       } else if (response.status === 401) {
         console.log("User not authenticated for products, using mock data");
         setProducts(mockProducts.filter((p) => p.store_id === storeId));
@@ -215,8 +215,8 @@ const SellerDashboard: React.FC<SellerDashboardProps> = ({ onBack }) => {
       const data = await response.json();
       console.log("Fetched stores:", data);
 
-      // The API might return { stores: [...] } or just [...]
-      const storesList = data.stores || data || [];
+      // The API returns one page: { items: [...], total, ... }
+      const storesList = data.items || [];
       setStores(storesList);
     } catch (error) {
       console.error("Error fetching stores:", error);
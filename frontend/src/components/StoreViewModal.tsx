@@ -64,7 +64,7 @@ const StoreViewModal: React.FC<StoreViewModalProps> = ({
       });
       if (response.ok) {
         const data = await response.json();
-        setProducts((data.products || data || []) as ProductLite[]);
+        setProducts((data.items || []) as ProductLite[]);
       }
     } catch (error) {
       console.error("Error fetching store products:", error);
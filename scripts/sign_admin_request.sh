@@ -0,0 +1,39 @@
+#!/bin/bash
+
+# Send a signed request to an admin endpoint
+#
+# Usage: ADMIN_KEY_ID=... ADMIN_SECRET=... TOKEN=... \
+#        ./scripts/sign_admin_request.sh POST /api/v1/admin/maintenance '{"enabled":true}'
+#
+# Credentials come from `cargo run --bin admin_credential -- issue <relay_id> <label>`;
+# TOKEN is a bearer token for the same relay id. The signature is an HMAC-SHA256,
+# keyed with the hex SHA-256 of the secret, over:
+#
+#   METHOD \n path?query \n timestamp \n nonce \n hex SHA-256 of the body
+
+set -e
+
+METHOD=${1:?method required}
+REQUEST_PATH=${2:?path required}
+BODY=${3:-}
+BASE_URL=${BASE_URL:-http://localhost:3001}
+
+: "${ADMIN_KEY_ID:?ADMIN_KEY_ID required}"
+: "${ADMIN_SECRET:?ADMIN_SECRET required}"
+: "${TOKEN:?TOKEN required}"
+
+TIMESTAMP=$(date +%s)
+NONCE=$(openssl rand -hex 16)
+KEY=$(printf %s "$ADMIN_SECRET" | openssl dgst -sha256 -r | cut -d' ' -f1)
+BODY_HASH=$(printf %s "$BODY" | openssl dgst -sha256 -r | cut -d' ' -f1)
+SIGNATURE=$(printf '%s\n%s\n%s\n%s\n%s' "$METHOD" "$REQUEST_PATH" "$TIMESTAMP" "$NONCE" "$BODY_HASH" \
+    | openssl dgst -sha256 -hmac "$KEY" -r | cut -d' ' -f1)
+
+curl -sS -X "$METHOD" "$BASE_URL$REQUEST_PATH" \
+    -H "Authorization: Bearer $TOKEN" \
+    -H "Content-Type: application/json" \
+    -H "X-Admin-Key: $ADMIN_KEY_ID" \
+    -H "X-Timestamp: $TIMESTAMP" \
+    -H "X-Nonce: $NONCE" \
+    -H "X-Signature: $SIGNATURE" \
+    ${BODY:+--data "$BODY"}
@@ -0,0 +1,838 @@
+use crate::api::extract::UuidPath;
+use crate::auth::{claims_from_headers, Claims, ADMIN_ROLE};
+use crate::config::DatabaseConfig;
+use crate::db::analytics::{AdminSummary, Analytics};
+use crate::db::events::{EventLog, UNKNOWN_CURSOR};
+use crate::db::experiments::{self, VariantExposures};
+use crate::db::media_quota::{MediaLimits, MediaQuota, MediaQuotaOverrides, MediaUsage};
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::db::product_counts::RebuildSummary;
+use crate::db::product_counts::{ProductCounts, REBUILD_BATCH_SIZE};
+use crate::db::schema_migrations::{self, AppliedMigration, MigrationEntry, RunError};
+use crate::db::stores::Store;
+use crate::db::system_settings::SystemSetting;
+use crate::entity::event_record::Model as EventRecord;
+use crate::experiments::Experiments;
+use crate::features::{self, FeatureFlags, FeatureStatus, KNOWN_FEATURES};
+use crate::maintenance::{self, MaintenanceState, MAINTENANCE};
+use crate::migrator::Migrator;
+#[allow(unused_imports)]
+use crate::retention::PruneStats;
+use crate::retention::PRUNE_LOG;
+use crate::tenant::{validate_tenant_id, DEFAULT_TENANT};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// How long a computed summary is served before the aggregates are re-run
+const SUMMARY_TTL: Duration = Duration::from_secs(60);
+
+static SUMMARY_CACHE: SummaryCache = SummaryCache::new(SUMMARY_TTL);
+
+/// One entry per tenant so dashboard refreshes don't hammer the database
+struct SummaryCache {
+    ttl: Duration,
+    entries: Mutex<BTreeMap<String, (Instant, AdminSummary)>>,
+}
+
+impl SummaryCache {
+    const fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn get(&self, tenant_id: &str, now: Instant) -> Option<AdminSummary> {
+        let entries = self.entries.lock().ok()?;
+        match entries.get(tenant_id) {
+            Some((stored_at, summary)) if now.duration_since(*stored_at) < self.ttl => {
+                Some(summary.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn put(&self, now: Instant, summary: AdminSummary) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(summary.tenant_id.clone(), (now, summary));
+        }
+    }
+}
+
+/// Reject requests that don't carry an admin token
+pub fn require_admin(headers: &HeaderMap) -> Result<Claims, (StatusCode, &'static str)> {
+    match claims_from_headers(headers) {
+        Some(claims) if claims.role == ADMIN_ROLE => Ok(claims),
+        Some(_) => Err((StatusCode::FORBIDDEN, "Admin role required")),
+        None => Err((
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid Authorization token",
+        )),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AdminSummaryQuery {
+    /// Tenant to summarise; defaults to the default tenant
+    pub tenant: Option<String>,
+}
+
+/// Marketplace totals and 7-day deltas for the operations dashboard
+#[utoipa::path(
+    get,
+    operation_id = "adminSummary",
+    path = "/api/v1/admin/summary",
+    tag = "Admin",
+    params(AdminSummaryQuery),
+    responses(
+        (status = 200, description = "Dashboard summary, cached for up to a minute", body = AdminSummary),
+        (status = 400, description = "Malformed tenant"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn admin_summary(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<AdminSummaryQuery>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let tenant = query.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+    if let Err(err) = validate_tenant_id(tenant) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+
+    if let Some(summary) = SUMMARY_CACHE.get(tenant, Instant::now()) {
+        return Json(summary).into_response();
+    }
+
+    match Analytics::admin_summary(&db, tenant, Utc::now()).await {
+        Ok(summary) => {
+            SUMMARY_CACHE.put(Instant::now(), summary.clone());
+            Json(summary).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+const MAX_MAINTENANCE_MESSAGE_LEN: usize = 500;
+const MAX_RETRY_AFTER_SECS: u64 = 86_400;
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetMaintenanceRequest {
+    pub enabled: bool,
+    /// Reject GET requests as well as writes; defaults to false
+    pub include_reads: Option<bool>,
+    /// Shown to clients; defaults to a generic notice
+    pub message: Option<String>,
+    /// Sent as `Retry-After`; defaults to 300
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Turn maintenance mode on or off for every replica
+#[utoipa::path(
+    post,
+    operation_id = "setMaintenance",
+    path = "/api/v1/admin/maintenance",
+    tag = "Admin",
+    request_body = SetMaintenanceRequest,
+    responses(
+        (status = 200, description = "State in effect on this replica; others follow within one poll interval", body = MaintenanceState),
+        (status = 400, description = "Overlong message or retry interval out of range"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_maintenance(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<SetMaintenanceRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let message = request
+        .message
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| maintenance::DEFAULT_MESSAGE.to_string());
+    if message.len() > MAX_MAINTENANCE_MESSAGE_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("message must be at most {MAX_MAINTENANCE_MESSAGE_LEN} bytes"),
+        )
+            .into_response();
+    }
+    let retry_after_secs = request
+        .retry_after_secs
+        .unwrap_or(maintenance::DEFAULT_RETRY_AFTER_SECS);
+    if !(1..=MAX_RETRY_AFTER_SECS).contains(&retry_after_secs) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("retry_after_secs must be between 1 and {MAX_RETRY_AFTER_SECS}"),
+        )
+            .into_response();
+    }
+    let state = MaintenanceState {
+        enabled: request.enabled,
+        include_reads: request.include_reads.unwrap_or(false),
+        message,
+        retry_after_secs,
+    };
+
+    let value = serde_json::to_value(&state).unwrap_or_default();
+    match SystemSetting::put(&db, maintenance::SETTING_KEY, value, &admin.relay_id).await {
+        Ok(_) => {
+            MAINTENANCE.set(state.clone());
+            tracing::warn!(
+                enabled = state.enabled,
+                include_reads = state.include_reads,
+                admin = %admin.relay_id,
+                "Maintenance mode set"
+            );
+            Json(state).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// What each retention job did on its last run on this replica
+#[utoipa::path(
+    get,
+    operation_id = "retentionStats",
+    path = "/api/v1/admin/retention",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "One entry per pruned table", body = Vec<PruneStats>),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn retention_stats(headers: HeaderMap) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    Json(PRUNE_LOG.snapshot()).into_response()
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeaturesResponse {
+    /// Whether `PUT /admin/features/{name}` takes effect
+    pub runtime_overrides: bool,
+    pub features: Vec<FeatureStatus>,
+}
+
+fn features_response(flags: &FeatureFlags) -> FeaturesResponse {
+    FeaturesResponse {
+        runtime_overrides: flags.runtime_overrides(),
+        features: flags.snapshot(),
+    }
+}
+
+/// Feature flags in effect on this replica
+#[utoipa::path(
+    get,
+    operation_id = "listFeatures",
+    path = "/api/v1/admin/features",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Every known feature and whether it is on", body = FeaturesResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_features(
+    State(flags): State<Arc<FeatureFlags>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    Json(features_response(&flags)).into_response()
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetFeatureRequest {
+    /// `null` drops the override and falls back to `ENABLED_FEATURES`
+    pub enabled: Option<bool>,
+}
+
+/// Turn a feature on or off for every replica, without a restart
+#[utoipa::path(
+    put,
+    operation_id = "setFeature",
+    path = "/api/v1/admin/features/{name}",
+    tag = "Admin",
+    params(
+        ("name" = String, Path, description = "Feature name")
+    ),
+    request_body = SetFeatureRequest,
+    responses(
+        (status = 200, description = "Flags in effect on this replica; others follow within one refresh interval", body = FeaturesResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Unknown feature"),
+        (status = 409, description = "Runtime overrides are disabled"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_feature(
+    State(db): State<DatabaseConnection>,
+    State(flags): State<Arc<FeatureFlags>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<SetFeatureRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if !KNOWN_FEATURES.contains(&name.as_str()) {
+        return (StatusCode::NOT_FOUND, format!("Unknown feature '{name}'")).into_response();
+    }
+    if !flags.runtime_overrides() {
+        return (
+            StatusCode::CONFLICT,
+            "Runtime feature overrides are disabled; set ENABLED_FEATURES instead",
+        )
+            .into_response();
+    }
+
+    // Start from the stored overrides so a change made on another replica
+    // since the last refresh is kept
+    let mut overrides = match SystemSetting::get(&db, features::SETTING_KEY).await {
+        Ok(Some(setting)) => serde_json::from_value(setting.value).unwrap_or_default(),
+        Ok(None) => BTreeMap::new(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    match request.enabled {
+        Some(enabled) => overrides.insert(name.clone(), enabled),
+        None => overrides.remove(&name),
+    };
+    let value = serde_json::to_value(&overrides).unwrap_or_default();
+    match SystemSetting::put(&db, features::SETTING_KEY, value, &admin.relay_id).await {
+        Ok(_) => {
+            flags.set_overrides(overrides);
+            tracing::warn!(
+                feature = %name,
+                enabled = ?request.enabled,
+                admin = %admin.relay_id,
+                "Feature override set"
+            );
+            Json(features_response(&flags)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Per-store media limits; each `null` falls back to the configured default
+#[derive(Deserialize, ToSchema)]
+pub struct SetMediaQuotaRequest {
+    /// Bytes of original uploads the store may keep
+    pub store_bytes: Option<i64>,
+    /// Uploads per UTC day
+    pub daily_uploads: Option<i32>,
+    pub images_per_product: Option<i32>,
+}
+
+/// Raise (or lower) a store's media limits
+#[utoipa::path(
+    put,
+    operation_id = "setStoreMediaQuota",
+    path = "/api/v1/admin/stores/{id}/media-quota",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = SetMediaQuotaRequest,
+    responses(
+        (status = 200, description = "The store's usage against its new limits", body = MediaUsage),
+        (status = 400, description = "A limit is not positive"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_media_quota(
+    State(db): State<DatabaseConnection>,
+    State(limits): State<Arc<MediaLimits>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<SetMediaQuotaRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let positive = request.store_bytes.is_none_or(|n| n > 0)
+        && request.daily_uploads.is_none_or(|n| n > 0)
+        && request.images_per_product.is_none_or(|n| n > 0);
+    if !positive {
+        return (StatusCode::BAD_REQUEST, "Media limits must be positive").into_response();
+    }
+    let store = match Store::get(&db, id).await {
+        Ok(store) => store,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let overrides = MediaQuotaOverrides {
+        store_bytes: request.store_bytes,
+        daily_uploads: request.daily_uploads,
+        images_per_product: request.images_per_product,
+    };
+    match MediaQuota::set_overrides(&db, store, overrides).await {
+        Ok(store) => {
+            tracing::warn!(
+                store_id = %id,
+                ?overrides,
+                admin = %admin.relay_id,
+                "Store media quota set"
+            );
+            Json(MediaUsage::new(&store, &limits, Utc::now())).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Recount the product counts projection from the products table, in
+/// batches of stores
+#[utoipa::path(
+    post,
+    operation_id = "rebuildProductCounts",
+    path = "/api/v1/admin/product-counts/rebuild",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Stores recounted and rows written", body = RebuildSummary),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn rebuild_product_counts(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match ProductCounts::rebuild(&db, REBUILD_BATCH_SIZE).await {
+        Ok(summary) => {
+            tracing::warn!(
+                stores = summary.stores,
+                admin = %admin.relay_id,
+                "Product counts rebuilt"
+            );
+            Json(summary).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MigrationStatusResponse {
+    /// Every migration this build knows, oldest first
+    pub migrations: Vec<MigrationEntry>,
+    pub pending: usize,
+}
+
+/// Applied and pending schema migrations
+#[utoipa::path(
+    get,
+    operation_id = "listMigrations",
+    path = "/api/v1/admin/migrations",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Each migration and whether it has been applied", body = MigrationStatusResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_migrations(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match schema_migrations::status::<Migrator>(&db).await {
+        Ok(migrations) => Json(MigrationStatusResponse {
+            pending: migrations.iter().filter(|entry| !entry.applied).count(),
+            migrations,
+        })
+        .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MigrationRunResponse {
+    /// In the order they ran; empty when nothing was pending
+    pub applied: Vec<AppliedMigration>,
+}
+
+/// Apply pending schema migrations, one run at a time across replicas
+#[utoipa::path(
+    post,
+    operation_id = "runMigrations",
+    path = "/api/v1/admin/migrations/run",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Migrations applied by this run", body = MigrationRunResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Another run is in progress, or maintenance mode is required and off"),
+        (status = 500, description = "A migration failed; on Postgres the run was rolled back")
+    )
+)]
+pub async fn run_migrations(
+    State(db): State<DatabaseConnection>,
+    State(database): State<Arc<DatabaseConfig>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if database.migrations_require_maintenance && !MAINTENANCE.get().enabled {
+        return (
+            StatusCode::CONFLICT,
+            "Turn maintenance mode on before running migrations",
+        )
+            .into_response();
+    }
+    tracing::warn!(admin = %admin.relay_id, "Migration run started");
+    match schema_migrations::run_pending::<Migrator>(&db).await {
+        Ok(applied) => {
+            tracing::warn!(
+                applied = ?applied.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+                admin = %admin.relay_id,
+                "Migration run finished"
+            );
+            Json(MigrationRunResponse { applied }).into_response()
+        }
+        Err(RunError::Busy) => (
+            StatusCode::CONFLICT,
+            "Another migration run is in progress".to_string(),
+        )
+            .into_response(),
+        Err(RunError::Failed(err)) => {
+            tracing::warn!(admin = %admin.relay_id, error = %err, "Migration run failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, err).into_response()
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ExperimentSummary {
+    pub experiment: String,
+    /// The experiment as configured on this replica; null once it has been
+    /// taken out of the config
+    pub configured: Option<String>,
+    /// Configured variants first, in config order, then any others still in
+    /// the log
+    pub variants: Vec<VariantExposures>,
+}
+
+fn experiment_summary(
+    name: &str,
+    experiments: &Experiments,
+    logged: Vec<VariantExposures>,
+) -> ExperimentSummary {
+    let configured = experiments.get(name);
+    let mut variants: Vec<VariantExposures> = configured
+        .map(|experiment| {
+            experiment
+                .variants
+                .iter()
+                .map(|variant| VariantExposures {
+                    variant: variant.name.clone(),
+                    exposures: 0,
+                    subjects: 0,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    for counts in logged {
+        match variants.iter_mut().find(|v| v.variant == counts.variant) {
+            Some(known) => *known = counts,
+            None => variants.push(counts),
+        }
+    }
+    ExperimentSummary {
+        experiment: name.to_string(),
+        configured: configured.map(ToString::to_string),
+        variants,
+    }
+}
+
+/// Exposure counts of each variant of an experiment
+#[utoipa::path(
+    get,
+    operation_id = "experimentSummary",
+    path = "/api/v1/admin/experiments/{name}/summary",
+    tag = "Admin",
+    params(
+        ("name" = String, Path, description = "Experiment name")
+    ),
+    responses(
+        (status = 200, description = "Exposures per variant", body = ExperimentSummary),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Experiment neither configured nor logged"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_experiment_summary(
+    State(db): State<DatabaseConnection>,
+    State(experiments): State<Arc<Experiments>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let logged = match experiments::summary(&db, &name).await {
+        Ok(logged) => logged,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if logged.is_empty() && experiments.get(&name).is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Unknown experiment '{name}'"),
+        )
+            .into_response();
+    }
+    Json(experiment_summary(&name, &experiments, logged)).into_response()
+}
+
+/// Version of the [`ExportedEvent`] line format; bumped on breaking changes
+pub const EVENT_EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Most events a single export page may return, and the default
+pub const MAX_EVENT_EXPORT_LIMIT: u64 = 5000;
+
+#[derive(Deserialize, IntoParams)]
+pub struct EventExportQuery {
+    /// ID of the last event already exported; from the start of the log when absent
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub after: Option<Uuid>,
+    /// Events per page, 1 to 5000
+    pub limit: Option<u64>,
+}
+
+/// One line of the event export
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportedEvent {
+    pub schema_version: u32,
+    /// Position in the log; strictly increasing from line to line
+    pub seq: i64,
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub event_type: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub entity_id: Uuid,
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl From<EventRecord> for ExportedEvent {
+    fn from(record: EventRecord) -> Self {
+        Self {
+            schema_version: EVENT_EXPORT_SCHEMA_VERSION,
+            seq: record.seq,
+            id: record.id,
+            event_type: record.event_type,
+            entity_id: record.entity_id,
+            data: record.data,
+            occurred_at: record.occurred_at,
+        }
+    }
+}
+
+fn ndjson(events: Vec<EventRecord>) -> String {
+    let mut out = String::new();
+    for event in events {
+        if let Ok(line) = serde_json::to_string(&ExportedEvent::from(event)) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Page through the event log for the analytics warehouse. Pass the `id` of
+/// the last line received as `after` to get the next page; an empty body
+/// means the export has caught up. A page never skips or repeats an event,
+/// even while new ones are recorded.
+#[utoipa::path(
+    get,
+    operation_id = "exportEvents",
+    path = "/api/v1/admin/events/export",
+    tag = "Admin",
+    params(EventExportQuery),
+    responses(
+        (status = 200, description = "One ExportedEvent per line, in `seq` order", body = ExportedEvent, content_type = "application/x-ndjson"),
+        (status = 400, description = "Limit out of range, or `after` names no recorded event"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn export_events(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<EventExportQuery>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let limit = query.limit.unwrap_or(MAX_EVENT_EXPORT_LIMIT);
+    if !(1..=MAX_EVENT_EXPORT_LIMIT).contains(&limit) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {MAX_EVENT_EXPORT_LIMIT}"),
+        )
+            .into_response();
+    }
+    match EventLog::export(&db, query.after, limit).await {
+        Ok(events) => (
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+            ndjson(events),
+        )
+            .into_response(),
+        Err(err) if err == UNKNOWN_CURSOR => (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::analytics::CountWithDelta;
+
+    fn summary(stores: i64) -> AdminSummary {
+        AdminSummary {
+            tenant_id: DEFAULT_TENANT.to_string(),
+            sellers: CountWithDelta::default(),
+            stores: CountWithDelta {
+                total: stores,
+                last_7_days: 1,
+            },
+            products: CountWithDelta::default(),
+            media_files: CountWithDelta::default(),
+            media_storage_bytes: CountWithDelta::default(),
+            generated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn cache_serves_entry_until_ttl_expires() {
+        let cache = SummaryCache::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(cache.get(DEFAULT_TENANT, start).is_none());
+
+        cache.put(start, summary(3));
+        assert_eq!(
+            cache
+                .get(DEFAULT_TENANT, start + Duration::from_secs(59))
+                .unwrap()
+                .stores
+                .total,
+            3
+        );
+        assert!(cache
+            .get(DEFAULT_TENANT, start + Duration::from_secs(60))
+            .is_none());
+    }
+
+    #[test]
+    fn cache_keeps_tenants_apart() {
+        let cache = SummaryCache::new(Duration::from_secs(60));
+        let start = Instant::now();
+        cache.put(start, summary(3));
+        assert!(cache.get("acme", start).is_none());
+    }
+
+    #[test]
+    fn experiment_summary_lists_unseen_variants() {
+        let experiments = Experiments {
+            feed: Some("feed:newest=1,trust_score=1".parse().unwrap()),
+        };
+        let counts = |variant: &str, exposures| VariantExposures {
+            variant: variant.to_string(),
+            exposures,
+            subjects: exposures,
+        };
+        let summary = experiment_summary(
+            "feed",
+            &experiments,
+            vec![counts("retired", 4), counts("trust_score", 2)],
+        );
+        assert_eq!(
+            summary.variants,
+            [
+                counts("newest", 0),
+                counts("trust_score", 2),
+                counts("retired", 4)
+            ]
+        );
+        assert_eq!(
+            summary.configured.as_deref(),
+            Some("feed:newest=1,trust_score=1")
+        );
+        assert_eq!(
+            experiment_summary("old", &experiments, Vec::new()).configured,
+            None
+        );
+    }
+
+    #[test]
+    fn require_admin_rejects_missing_token() {
+        let (status, _) = require_admin(&HeaderMap::new()).unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_event_export_lines_carry_the_schema_version() {
+        let record = EventRecord {
+            seq: 42,
+            id: Uuid::new_v4(),
+            event_type: "ProductCreated".to_string(),
+            entity_id: Uuid::new_v4(),
+            data: serde_json::json!({ "name": "Ndole spice" }),
+            occurred_at: Utc::now(),
+        };
+        let body = ndjson(vec![record.clone(), record]);
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(body.ends_with('\n'));
+        assert_eq!(lines[0]["schema_version"], EVENT_EXPORT_SCHEMA_VERSION);
+        assert_eq!(lines[0]["seq"], 42);
+        assert_eq!(lines[0]["event_type"], "ProductCreated");
+        assert_eq!(lines[0]["data"]["name"], "Ndole spice");
+    }
+}
@@ -0,0 +1,383 @@
+//! Deleting many of a store's products in one call.
+//!
+//! Like `DELETE /stores/:id` it takes two calls: the first answers 409 with
+//! how many products would go and a confirmation token, the second repeats
+//! the request with the token in `X-Confirm-Token`. The token is bound to
+//! the exact selection, so one issued for two products can't delete two
+//! hundred.
+
+use crate::api::bundles::announce_deactivated;
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store;
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::auth::confirmation::ConfirmationResponse;
+use crate::auth::confirmation::{ConfirmationTokens, DeletionSummary, Target};
+use crate::auth::{authenticate, ApiScope};
+use crate::db::bulk_prices::BulkPrice;
+use crate::db::products::Product;
+use crate::events::{create_event, EventDispatcher, EventType};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most products a single request may delete
+pub const MAX_BULK_DELETE: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkDeleteRequest {
+    /// 1 to 1000 of the store's products
+    #[schema(value_type = Vec<String>)]
+    pub product_ids: Vec<Uuid>,
+    /// Remove the rows for good instead of archiving; defaults to false
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+impl BulkDeleteRequest {
+    fn check(&self) -> Result<(), String> {
+        if self.product_ids.is_empty() || self.product_ids.len() > MAX_BULK_DELETE {
+            return Err(format!(
+                "product_ids must list 1 to {MAX_BULK_DELETE} products"
+            ));
+        }
+        let mut seen = HashSet::new();
+        if let Some(dup) = self.product_ids.iter().find(|id| !seen.insert(**id)) {
+            return Err(format!("product {dup} appears more than once"));
+        }
+        Ok(())
+    }
+
+    /// What a confirmation token for this request stands for: the store,
+    /// the products in any order and whether they go for good
+    fn selection(&self, store_id: Uuid) -> Uuid {
+        let mut ids = self.product_ids.clone();
+        ids.sort_unstable();
+        let mut digest = Sha256::new()
+            .chain_update(store_id.as_bytes())
+            .chain_update([u8::from(self.permanent)]);
+        for id in &ids {
+            digest.update(id.as_bytes());
+        }
+        let digest = digest.finalize();
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        Uuid::from_bytes(bytes)
+    }
+}
+
+/// Delete many of a store's products at once.
+///
+/// They are archived, as by `DELETE /products/{id}`, or removed for good
+/// with `permanent`. Takes two calls: the first answers 409 with how many
+/// products would go and a confirmation token, the second sends the token
+/// in `X-Confirm-Token`.
+#[utoipa::path(
+    post,
+    operation_id = "bulkDeleteProducts",
+    path = "/api/v1/stores/{id}/products/bulk-delete",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("X-Confirm-Token" = Option<String>, Header, description = "Token from the first call's 409, valid for 5 minutes and one use")
+    ),
+    request_body = BulkDeleteRequest,
+    responses(
+        (status = 204, description = "Every product archived, or deleted for good with `permanent`"),
+        (status = 400, description = "Malformed selection, or the confirmation token is invalid, expired or already used"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found, or a product is not one of its products"),
+        (status = 409, description = "Confirmation required", body = ConfirmationResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn bulk_delete_products(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    State(confirmations): State<Arc<ConfirmationTokens>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<BulkDeleteRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = request.check() {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    if let Err(err) = owned_store(&db, &headers, store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    // The token only serves whoever asked for it
+    let caller = match authenticate(&db, &headers).await {
+        Ok(Some(principal)) => principal.actor(),
+        Ok(None) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token",
+            )
+                .into_response()
+        }
+        Err(err) => return err.into_response(),
+    };
+
+    let products = match BulkPrice::products(&db, &request.product_ids).await {
+        Ok(products) => products,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if let Some(missing) = request
+        .product_ids
+        .iter()
+        .find(|id| products.get(id).is_none_or(|p| p.store_id != store_id))
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("product {missing} not found in this store"),
+        )
+            .into_response();
+    }
+
+    let summary = DeletionSummary {
+        product_count: request.product_ids.len() as u64,
+        blockers: Vec::new(),
+    };
+    let target = Target {
+        action: "delete_products",
+        entity: request.selection(store_id),
+        caller: &caller,
+    };
+    if let Some(response) = confirmations.confirm(&headers, target, summary, Utc::now()) {
+        return response;
+    }
+
+    for id in &request.product_ids {
+        if request.permanent {
+            let deactivated = match Product::delete_permanently(&db, *id).await {
+                Ok(deactivated) => deactivated,
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            };
+            let event = create_event(
+                EventType::ProductDeleted,
+                *id,
+                serde_json::json!({ "product_id": id, "store_id": store_id }),
+            );
+            let _ = events.dispatch(event).await;
+            announce_deactivated(&events, &deactivated, *id).await;
+        } else if products[id].archived_at.is_none() {
+            let (archived, deactivated) = match Product::delete(&db, *id).await {
+                Ok(archived) => archived,
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            };
+            let event = create_event(
+                EventType::ProductArchived,
+                *id,
+                serde_json::json!({
+                    "product_id": id,
+                    "store_id": store_id,
+                    "archived_at": archived.archived_at,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+            announce_deactivated(&events, &deactivated, *id).await;
+        }
+    }
+    tracing::info!(store_id = %store_id, count = request.product_ids.len(), permanent = request.permanent, "Products deleted in bulk");
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::confirmation::CONFIRM_HEADER;
+    use crate::auth::JwtService;
+    use crate::db::testing::{seed_store, sqlite};
+    use axum::{
+        body::Body,
+        extract::FromRef,
+        http::{header, Request},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        events: Arc<EventDispatcher>,
+        confirmations: Arc<ConfirmationTokens>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<EventDispatcher> {
+        fn from_ref(state: &TestState) -> Self {
+            state.events.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<ConfirmationTokens> {
+        fn from_ref(state: &TestState) -> Self {
+            state.confirmations.clone()
+        }
+    }
+
+    async fn seed_products(db: &DatabaseConnection, store_id: Uuid, count: usize) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        for n in 0..count {
+            let product = Product::create(
+                db,
+                store_id,
+                None,
+                &format!("Okok leaves {n}"),
+                None,
+                1500.0,
+                "XAF",
+                4,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+            ids.push(product.id);
+        }
+        ids
+    }
+
+    async fn send(
+        app: &Router,
+        store_id: Uuid,
+        caller: &str,
+        body: serde_json::Value,
+        confirm: Option<&str>,
+    ) -> (StatusCode, serde_json::Value) {
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token(caller.into(), String::new(), "default".into())
+            .unwrap();
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/stores/{store_id}/products/bulk-delete"))
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(confirm) = confirm {
+            request = request.header(CONFIRM_HEADER, confirm);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_needs_a_token_for_that_selection() {
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        let ids = seed_products(&db, store_id, 3).await;
+        let app = Router::new()
+            .route(
+                "/stores/:id/products/bulk-delete",
+                post(bulk_delete_products),
+            )
+            .with_state(TestState {
+                db: db.clone(),
+                events: Arc::new(EventDispatcher::new()),
+                confirmations: Arc::new(ConfirmationTokens::new("secret")),
+            });
+        let two = serde_json::json!({ "product_ids": [ids[0], ids[1]] });
+
+        let (status, first) = send(&app, store_id, "seller-1", two.clone(), None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(first["summary"]["product_count"], 2);
+        let token = first["confirm_token"].as_str().unwrap();
+
+        // Not for more products, for good, or for another caller
+        let all = serde_json::json!({ "product_ids": ids });
+        let purge = serde_json::json!({ "product_ids": [ids[0], ids[1]], "permanent": true });
+        for (caller, body) in [
+            ("seller-1", all),
+            ("seller-1", purge),
+            ("seller-2", two.clone()),
+        ] {
+            let (status, _) = send(&app, store_id, caller, body, Some(token)).await;
+            assert_ne!(status, StatusCode::NO_CONTENT);
+        }
+        for id in &ids {
+            assert!(Product::get(&db, *id).await.unwrap().archived_at.is_none());
+        }
+
+        let reordered = serde_json::json!({ "product_ids": [ids[1], ids[0]] });
+        let (status, _) = send(&app, store_id, "seller-1", reordered, Some(token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(Product::get(&db, ids[0])
+            .await
+            .unwrap()
+            .archived_at
+            .is_some());
+        assert!(Product::get(&db, ids[1])
+            .await
+            .unwrap()
+            .archived_at
+            .is_some());
+        assert!(Product::get(&db, ids[2])
+            .await
+            .unwrap()
+            .archived_at
+            .is_none());
+
+        let (status, again) = send(&app, store_id, "seller-1", two, Some(token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(again["code"], "CONFIRM_TOKEN_USED");
+    }
+
+    #[tokio::test]
+    async fn test_bulk_delete_only_takes_the_stores_own_products() {
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        let other_store = seed_store(&db, "seller-1").await;
+        let ours = seed_products(&db, store_id, 1).await;
+        let theirs = seed_products(&db, other_store, 1).await;
+        let app = Router::new()
+            .route(
+                "/stores/:id/products/bulk-delete",
+                post(bulk_delete_products),
+            )
+            .with_state(TestState {
+                db,
+                events: Arc::new(EventDispatcher::new()),
+                confirmations: Arc::new(ConfirmationTokens::new("secret")),
+            });
+
+        let mixed = serde_json::json!({ "product_ids": [ours[0], theirs[0]] });
+        let (status, _) = send(&app, store_id, "seller-1", mixed, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let twice = serde_json::json!({ "product_ids": [ours[0], ours[0]] });
+        let (status, _) = send(&app, store_id, "seller-1", twice, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,461 @@
+//! Repricing a store's products in one go, e.g. after inflation.
+//!
+//! The body picks one of three adjustments: `{"percent": 10}`, `{"delta": 500}`
+//! or `{"prices": [{product_id, price}]}`. Products that would end up with a
+//! negative price, or at or below their running sale price, are left as is
+//! and reported; the rest change together.
+//!
+//! `PATCH /products/bulk` instead edits the price and/or stock of listed
+//! products, which may span several of the caller's stores. There a single
+//! bad entry rejects the whole batch, named by its index.
+
+use crate::api::extract::UuidPath;
+use crate::api::products::{announce_low_stock, changed_fields, ProductResponse};
+use crate::api::stores::owned_store;
+use crate::api::transaction::Tx;
+use crate::auth::{claims_from_headers, ApiScope};
+use crate::db::bulk_prices::{
+    check_price, BulkPrice, PriceAdjustment, PriceChange, PriceChangeStatus, ProductEdit,
+};
+use crate::db::history::ChangeOrigin;
+use crate::db::products::{price_error, quantity_error};
+use crate::db::snapshots::{SnapshotReason, StoreSnapshot};
+use crate::events::{create_event, EventDispatcher, EventType};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Most explicit prices a single request may carry
+pub const MAX_BULK_PRICES: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PriceAssignment {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub price: f64,
+}
+
+/// Exactly one of the fields must be set
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkPriceRequest {
+    /// Change every price by this percentage, e.g. 10 or -5; rounded to whole units
+    pub percent: Option<f64>,
+    /// Add this amount to every price; negative to lower them
+    pub delta: Option<f64>,
+    /// Set these products to these prices
+    pub prices: Option<Vec<PriceAssignment>>,
+}
+
+impl BulkPriceRequest {
+    pub fn adjustment(self) -> Result<PriceAdjustment, String> {
+        match (self.percent, self.delta, self.prices) {
+            (Some(percent), None, None) => Ok(PriceAdjustment::Percent(percent)),
+            (None, Some(delta), None) => Ok(PriceAdjustment::Delta(delta)),
+            (None, None, Some(prices)) => {
+                if prices.is_empty() || prices.len() > MAX_BULK_PRICES {
+                    return Err(format!("prices must list 1 to {MAX_BULK_PRICES} products"));
+                }
+                let mut seen = HashSet::new();
+                if let Some(dup) = prices.iter().find(|p| !seen.insert(p.product_id)) {
+                    return Err(format!("product {} appears more than once", dup.product_id));
+                }
+                Ok(PriceAdjustment::Set(
+                    prices
+                        .into_iter()
+                        .map(|p| (p.product_id, p.price))
+                        .collect(),
+                ))
+            }
+            _ => Err("Set exactly one of percent, delta or prices".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct BulkPriceQuery {
+    /// Report the would-be prices without saving them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkPriceSummary {
+    pub dry_run: bool,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub not_found: usize,
+    pub rejected: usize,
+    pub products: Vec<PriceChange>,
+}
+
+impl BulkPriceSummary {
+    fn new(dry_run: bool, products: Vec<PriceChange>) -> Self {
+        let count = |status| products.iter().filter(|c| c.status == status).count();
+        Self {
+            dry_run,
+            updated: count(PriceChangeStatus::Updated),
+            unchanged: count(PriceChangeStatus::Unchanged),
+            not_found: count(PriceChangeStatus::NotFound),
+            rejected: count(PriceChangeStatus::Rejected),
+            products,
+        }
+    }
+}
+
+/// Reprice many of a store's products at once
+#[utoipa::path(
+    post,
+    operation_id = "bulkUpdatePrices",
+    path = "/api/v1/stores/{id}/products/bulk-price",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        BulkPriceQuery
+    ),
+    request_body = BulkPriceRequest,
+    responses(
+        (status = 200, description = "What changed, or would change on a dry run", body = BulkPriceSummary),
+        (status = 400, description = "Not exactly one adjustment, or a malformed one"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn bulk_update_prices(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<BulkPriceQuery>,
+    headers: HeaderMap,
+    Json(request): Json<BulkPriceRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let adjustment = match request.adjustment() {
+        Ok(adjustment) => adjustment,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+
+    let now = Utc::now();
+    if !query.dry_run {
+        let created_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+        if let Err(err) = StoreSnapshot::capture(
+            &db,
+            id,
+            SnapshotReason::BulkPrice,
+            created_by.as_deref(),
+            now,
+        )
+        .await
+        {
+            return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+        }
+    }
+    let changes = match BulkPrice::apply(&db, id, &adjustment, query.dry_run, now).await {
+        Ok(changes) => changes,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let summary = BulkPriceSummary::new(query.dry_run, changes);
+    if !summary.dry_run && summary.updated > 0 {
+        let changed: Vec<_> = summary
+            .products
+            .iter()
+            .filter(|c| c.status == PriceChangeStatus::Updated)
+            .map(|c| {
+                serde_json::json!({
+                    "product_id": c.product_id,
+                    "previous_price": c.previous_price,
+                    "price": c.price,
+                })
+            })
+            .collect();
+        let _ = events
+            .dispatch(create_event(
+                EventType::ProductPricesBulkUpdated,
+                id,
+                serde_json::json!({ "store_id": id, "products": changed }),
+            ))
+            .await;
+    }
+    (StatusCode::OK, Json(summary)).into_response()
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkProductEntry {
+    #[schema(format = "uuid")]
+    pub id: String,
+    pub price: Option<f64>,
+    pub quantity_available: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkProductUpdateRequest {
+    /// At most 1000 entries, each setting a price, a stock level or both
+    pub products: Vec<BulkProductEntry>,
+}
+
+impl BulkProductUpdateRequest {
+    /// The edits, or why the batch is refused, naming the first bad entry
+    pub fn edits(&self) -> Result<Vec<ProductEdit>, String> {
+        if self.products.is_empty() || self.products.len() > MAX_BULK_PRICES {
+            return Err(format!("products must list 1 to {MAX_BULK_PRICES} entries"));
+        }
+        let mut seen = HashSet::new();
+        let mut edits = Vec::with_capacity(self.products.len());
+        for (index, entry) in self.products.iter().enumerate() {
+            let Ok(product_id) = Uuid::parse_str(&entry.id) else {
+                return Err(format!("products[{index}]: id is not a valid UUID"));
+            };
+            if !seen.insert(product_id) {
+                return Err(format!(
+                    "products[{index}]: product {product_id} appears more than once"
+                ));
+            }
+            if entry.price.is_none() && entry.quantity_available.is_none() {
+                return Err(format!(
+                    "products[{index}]: set price, quantity_available or both"
+                ));
+            }
+            if let Some(problem) = entry
+                .price
+                .and_then(|price| price_error("price", price))
+                .or_else(|| entry.quantity_available.and_then(quantity_error))
+            {
+                return Err(format!("products[{index}]: {problem}"));
+            }
+            edits.push(ProductEdit {
+                product_id,
+                price: entry.price,
+                quantity_available: entry.quantity_available,
+            });
+        }
+        Ok(edits)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkProductUpdateResponse {
+    /// The products after the update, in request order
+    pub products: Vec<ProductResponse>,
+}
+
+/// Set the price and/or stock of many products at once. Every product must
+/// belong to one of the caller's stores; if any entry is refused, nothing
+/// changes.
+#[utoipa::path(
+    patch,
+    operation_id = "bulkUpdateProducts",
+    path = "/api/v1/products/bulk",
+    tag = "Products",
+    request_body = BulkProductUpdateRequest,
+    responses(
+        (status = 200, description = "Every product updated", body = BulkProductUpdateResponse),
+        (status = 400, description = "An entry is malformed or sets a price it may not have; the message names its index"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "An entry's product belongs to another seller's store, or the key lacks products:write"),
+        (status = 404, description = "An entry's product does not exist"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn bulk_update_products(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    headers: HeaderMap,
+    tx: Tx,
+    Json(request): Json<BulkProductUpdateRequest>,
+) -> impl IntoResponse {
+    let edits = match request.edits() {
+        Ok(edits) => edits,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let ids: Vec<Uuid> = edits.iter().map(|edit| edit.product_id).collect();
+    let products = match BulkPrice::products(&*tx, &ids).await {
+        Ok(products) => products,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+
+    let now = Utc::now();
+    let mut owned_stores = HashSet::new();
+    for (index, edit) in edits.iter().enumerate() {
+        let Some(product) = products.get(&edit.product_id) else {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("products[{index}]: product {} not found", edit.product_id),
+            )
+                .into_response();
+        };
+        if !owned_stores.contains(&product.store_id) {
+            if let Err((status, err)) =
+                owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await
+            {
+                return (status, format!("products[{index}]: {err}")).into_response();
+            }
+            owned_stores.insert(product.store_id);
+        }
+        if let Some(price) = edit.price {
+            if let Err(err) = check_price(product, price, now) {
+                return (StatusCode::BAD_REQUEST, format!("products[{index}]: {err}"))
+                    .into_response();
+            }
+        }
+    }
+
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    for store_id in owned_stores {
+        if let Err(err) = StoreSnapshot::capture(
+            &*tx,
+            store_id,
+            SnapshotReason::BulkEdit,
+            changed_by.as_deref(),
+            now,
+        )
+        .await
+        {
+            return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+        }
+    }
+    let updated = match BulkPrice::edit(
+        &*tx,
+        &products,
+        &edits,
+        ChangeOrigin::edit(changed_by.as_deref()),
+        now,
+    )
+    .await
+    {
+        Ok(updated) => updated,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+
+    for product in &updated {
+        let before = &products[&product.id];
+        let changes = changed_fields(before, product);
+        if changes
+            .as_object()
+            .is_some_and(|changes| !changes.is_empty())
+        {
+            let mut payload = serde_json::json!({ "store_id": product.store_id });
+            payload["changes"] = changes;
+            let event = create_event(EventType::ProductUpdated, product.id, payload);
+            let _ = events.dispatch(event).await;
+        }
+        if before.price != product.price {
+            let event = create_event(
+                EventType::ProductPriceChanged,
+                product.id,
+                serde_json::json!({
+                    "store_id": product.store_id,
+                    "sku": product.sku,
+                    "previous_price": before.price,
+                    "price": product.price,
+                    "currency": product.currency,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+        }
+        announce_low_stock(&events, before.quantity_available, product).await;
+    }
+    Json(BulkProductUpdateResponse {
+        products: updated
+            .into_iter()
+            .map(|product| ProductResponse::new(product, now))
+            .collect(),
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_needs_exactly_one_adjustment() {
+        let parse = |body: &str| {
+            serde_json::from_str::<BulkPriceRequest>(body)
+                .unwrap()
+                .adjustment()
+        };
+        assert_eq!(
+            parse(r#"{"percent": 10}"#),
+            Ok(PriceAdjustment::Percent(10.0))
+        );
+        assert_eq!(
+            parse(r#"{"delta": -250}"#),
+            Ok(PriceAdjustment::Delta(-250.0))
+        );
+        let id = Uuid::new_v4();
+        assert_eq!(
+            parse(&format!(
+                r#"{{"prices": [{{"product_id": "{id}", "price": 1500}}]}}"#
+            )),
+            Ok(PriceAdjustment::Set(vec![(id, 1500.0)]))
+        );
+        assert!(parse("{}").is_err());
+        assert!(parse(r#"{"percent": 10, "delta": 5}"#).is_err());
+        assert!(parse(r#"{"prices": []}"#).is_err());
+        assert!(parse(&format!(
+            r#"{{"prices": [{{"product_id": "{id}", "price": 1}}, {{"product_id": "{id}", "price": 2}}]}}"#
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_bulk_edit_rejects_the_batch_naming_the_bad_entry() {
+        let parse = |body: String| {
+            serde_json::from_str::<BulkProductUpdateRequest>(&body)
+                .unwrap()
+                .edits()
+        };
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(
+            parse(format!(
+                r#"{{"products": [{{"id": "{a}", "price": 1500}}, {{"id": "{b}", "quantity_available": 3}}]}}"#
+            )),
+            Ok(vec![
+                ProductEdit {
+                    product_id: a,
+                    price: Some(1500.0),
+                    quantity_available: None,
+                },
+                ProductEdit {
+                    product_id: b,
+                    price: None,
+                    quantity_available: Some(3),
+                },
+            ])
+        );
+        assert_eq!(
+            parse(format!(
+                r#"{{"products": [{{"id": "{a}", "price": 1}}, {{"id": "nope", "price": 1}}]}}"#
+            )),
+            Err("products[1]: id is not a valid UUID".to_string())
+        );
+        assert_eq!(
+            parse(format!(
+                r#"{{"products": [{{"id": "{a}", "price": 1}}, {{"id": "{b}", "price": -1}}]}}"#
+            )),
+            Err("products[1]: price must not be negative".to_string())
+        );
+        assert_eq!(
+            parse(format!(r#"{{"products": [{{"id": "{a}"}}]}}"#)),
+            Err("products[0]: set price, quantity_available or both".to_string())
+        );
+        assert!(parse(format!(
+            r#"{{"products": [{{"id": "{a}", "price": 1}}, {{"id": "{a}", "price": 2}}]}}"#
+        ))
+        .is_err());
+        assert!(parse(r#"{"products": []}"#.to_string()).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::api::extract::UuidPath;
+use crate::api::products::announce_low_stock;
+use crate::api::stores::owned_store;
+use crate::api::validation::{validate_bundle, ValidationReport};
+use crate::auth::ApiScope;
+use crate::db::bundles::{Bundle, BundleComponent, BundleDetails, PurchaseOutcome};
+use crate::entity::product_bundle::Model as BundleModel;
+use crate::events::{create_event, EventDispatcher, EventType};
+use crate::tenant::tenant_from_headers;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct BundleItemRequest {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    /// Units of the product in one bundle
+    pub quantity: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateBundleRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Combined price of the bundle
+    pub price: f64,
+    pub items: Vec<BundleItemRequest>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateBundleRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub price: f64,
+    /// Reactivate or withdraw the bundle; unchanged when omitted
+    pub is_active: Option<bool>,
+    /// Replaces the current components
+    pub items: Vec<BundleItemRequest>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RecordBundleSaleRequest {
+    /// Bundles sold
+    pub quantity: i32,
+}
+
+fn components(items: &[BundleItemRequest]) -> Vec<BundleComponent> {
+    items
+        .iter()
+        .map(|item| BundleComponent {
+            product_id: item.product_id,
+            quantity: item.quantity,
+        })
+        .collect()
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BundleComponentResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub name: String,
+    /// Units of the product in one bundle
+    pub quantity: i32,
+    /// The product's own stock
+    pub quantity_available: i32,
+}
+
+/// Bundle with its components and how many can be sold
+#[derive(Serialize, ToSchema)]
+pub struct BundleResponse {
+    #[serde(flatten)]
+    pub bundle: BundleModel,
+    /// Bundles the component stock covers
+    pub quantity_available: i32,
+    pub components: Vec<BundleComponentResponse>,
+    /// The store is paused: the bundle can be viewed but not ordered
+    pub unavailable: bool,
+}
+
+impl BundleResponse {
+    pub fn new(details: BundleDetails) -> Self {
+        let quantity_available = details.availability();
+        Self {
+            components: details
+                .items
+                .into_iter()
+                .map(|(item, product)| BundleComponentResponse {
+                    product_id: product.id,
+                    name: product.name,
+                    quantity: item.quantity,
+                    quantity_available: product.quantity_available,
+                })
+                .collect(),
+            quantity_available,
+            bundle: details.bundle,
+            unavailable: false,
+        }
+    }
+
+    /// Flag the bundle as unavailable while its store is paused
+    pub fn store_paused(mut self, paused: bool) -> Self {
+        self.unavailable = paused;
+        self
+    }
+}
+
+/// One entry of a product listing, told apart by `type`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Listing<P> {
+    Product(P),
+    Bundle(BundleResponse),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BundlesListResponse {
+    pub bundles: Vec<BundleResponse>,
+}
+
+/// Tell sellers their bundles went inactive because a component was deleted
+pub async fn announce_deactivated(
+    dispatcher: &EventDispatcher,
+    bundles: &[BundleModel],
+    product_id: Uuid,
+) {
+    for bundle in bundles {
+        let event = create_event(
+            EventType::BundleDeactivated,
+            bundle.id,
+            serde_json::json!({
+                "store_id": bundle.store_id,
+                "name": bundle.name,
+                "removed_product_id": product_id,
+            }),
+        );
+        let _ = dispatcher.dispatch(event).await;
+    }
+}
+
+/// The bundle if it belongs to the store
+async fn store_bundle(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    bundle_id: Uuid,
+) -> Result<BundleDetails, (StatusCode, String)> {
+    match Bundle::get(db, bundle_id).await {
+        Ok(Some(details)) if details.bundle.store_id == store_id => Ok(details),
+        Ok(_) => Err((StatusCode::NOT_FOUND, "Bundle not found.".to_string())),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err)),
+    }
+}
+
+/// Create a bundle of the store's products
+#[utoipa::path(
+    post,
+    operation_id = "createBundle",
+    path = "/api/v1/stores/{id}/bundles",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = CreateBundleRequest,
+    responses(
+        (status = 201, description = "Bundle created", body = BundleResponse),
+        (status = 400, description = "Invalid bundle, or items that are not products of this store", body = ValidationReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn create_bundle(
+    State(db): State<DatabaseConnection>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<CreateBundleRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let components = components(&request.items);
+    if let Err(errors) =
+        validate_bundle(&db, store_id, &request.name, request.price, &components).await
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+    match Bundle::create(
+        &db,
+        store_id,
+        request.name.trim(),
+        request.description.as_deref(),
+        request.price,
+        &components,
+    )
+    .await
+    {
+        Ok(details) => (StatusCode::CREATED, Json(BundleResponse::new(details))).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List a store's bundles; the owner also sees inactive ones
+#[utoipa::path(
+    get,
+    operation_id = "listBundles",
+    path = "/api/v1/stores/{id}/bundles",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Bundles, newest first", body = BundlesListResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_bundles(
+    State(db): State<DatabaseConnection>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let is_owner = owned_store(&db, &headers, store_id, ApiScope::ProductsRead)
+        .await
+        .is_ok();
+    match Bundle::list_by_store(&db, &tenant, store_id, !is_owner).await {
+        Ok(bundles) => Json(BundlesListResponse {
+            bundles: bundles.into_iter().map(BundleResponse::new).collect(),
+        })
+        .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Get a bundle; inactive bundles are only shown to the owner
+#[utoipa::path(
+    get,
+    operation_id = "getBundle",
+    path = "/api/v1/stores/{id}/bundles/{bundle_id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("bundle_id" = String, Path, description = "Bundle ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Bundle found", body = BundleResponse),
+        (status = 404, description = "Bundle not found")
+    )
+)]
+pub async fn get_bundle(
+    State(db): State<DatabaseConnection>,
+    UuidPath((store_id, bundle_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let details = match store_bundle(&db, store_id, bundle_id).await {
+        Ok(details) => details,
+        Err(err) => return err.into_response(),
+    };
+    if !details.bundle.is_active
+        && owned_store(&db, &headers, store_id, ApiScope::ProductsRead)
+            .await
+            .is_err()
+    {
+        return (StatusCode::NOT_FOUND, "Bundle not found.").into_response();
+    }
+    Json(BundleResponse::new(details)).into_response()
+}
+
+/// Replace a bundle's details and components
+#[utoipa::path(
+    put,
+    operation_id = "updateBundle",
+    path = "/api/v1/stores/{id}/bundles/{bundle_id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("bundle_id" = String, Path, description = "Bundle ID", format = "uuid")
+    ),
+    request_body = UpdateBundleRequest,
+    responses(
+        (status = 200, description = "Bundle updated", body = BundleResponse),
+        (status = 400, description = "Invalid bundle, or items that are not products of this store", body = ValidationReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store or bundle not found")
+    )
+)]
+pub async fn update_bundle(
+    State(db): State<DatabaseConnection>,
+    UuidPath((store_id, bundle_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateBundleRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let existing = match store_bundle(&db, store_id, bundle_id).await {
+        Ok(details) => details.bundle,
+        Err(err) => return err.into_response(),
+    };
+    let components = components(&request.items);
+    if let Err(errors) =
+        validate_bundle(&db, store_id, &request.name, request.price, &components).await
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+    let is_active = request.is_active.unwrap_or(existing.is_active);
+    match Bundle::update(
+        &db,
+        existing,
+        request.name.trim(),
+        request.description.as_deref(),
+        request.price,
+        is_active,
+        &components,
+    )
+    .await
+    {
+        Ok(details) => Json(BundleResponse::new(details)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Delete a bundle; its component products are kept
+#[utoipa::path(
+    delete,
+    operation_id = "deleteBundle",
+    path = "/api/v1/stores/{id}/bundles/{bundle_id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("bundle_id" = String, Path, description = "Bundle ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Bundle deleted"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store or bundle not found")
+    )
+)]
+pub async fn delete_bundle(
+    State(db): State<DatabaseConnection>,
+    UuidPath((store_id, bundle_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    if let Err(err) = store_bundle(&db, store_id, bundle_id).await {
+        return err.into_response();
+    }
+    match Bundle::delete(&db, bundle_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Bundle not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Record bundles sold, taking the stock of every component at once
+#[utoipa::path(
+    post,
+    operation_id = "recordBundleSale",
+    path = "/api/v1/stores/{id}/bundles/{bundle_id}/sales",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("bundle_id" = String, Path, description = "Bundle ID", format = "uuid")
+    ),
+    request_body = RecordBundleSaleRequest,
+    responses(
+        (status = 200, description = "Stock taken from every component", body = BundleResponse),
+        (status = 400, description = "quantity must be 1 or more"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store or bundle not found, or bundle inactive"),
+        (status = 409, description = "A component has too little stock; nothing was taken")
+    )
+)]
+pub async fn record_bundle_sale(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath((store_id, bundle_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+    Json(request): Json<RecordBundleSaleRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    if request.quantity < 1 {
+        return (StatusCode::BAD_REQUEST, "quantity must be 1 or more").into_response();
+    }
+    let before = match store_bundle(&db, store_id, bundle_id).await {
+        Ok(details) => details,
+        Err(err) => return err.into_response(),
+    };
+    match Bundle::purchase(&db, bundle_id, request.quantity).await {
+        Ok(PurchaseOutcome::Purchased(products)) => {
+            for product in &products {
+                let previous = before
+                    .items
+                    .iter()
+                    .find(|(_, p)| p.id == product.id)
+                    .map(|(_, p)| p.quantity_available);
+                let event = create_event(
+                    EventType::ProductStockChanged,
+                    product.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "sku": product.sku,
+                        "previous_quantity": previous,
+                        "quantity_available": product.quantity_available,
+                        "bundle_id": bundle_id,
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+                if let Some(previous) = previous {
+                    announce_low_stock(&events, previous, product).await;
+                }
+            }
+            match Bundle::get(&db, bundle_id).await {
+                Ok(Some(details)) => Json(BundleResponse::new(details)).into_response(),
+                Ok(None) => (StatusCode::NOT_FOUND, "Bundle not found.").into_response(),
+                Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            }
+        }
+        Ok(PurchaseOutcome::OutOfStock(product_id)) => (
+            StatusCode::CONFLICT,
+            format!("Not enough stock of product {product_id} for this sale"),
+        )
+            .into_response(),
+        Ok(PurchaseOutcome::Unavailable) => {
+            (StatusCode::NOT_FOUND, "Bundle is not active.").into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
@@ -0,0 +1,275 @@
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::validation::MAX_ATTRIBUTE_TEXT_LEN;
+use crate::db::categories::Category;
+use crate::db::category_attributes::{
+    is_attribute_key, AttributeDefinition, AttributeType, CategoryAttributes, ATTRIBUTE_KEY_TAKEN,
+    MAX_ATTRIBUTE_KEY_LEN,
+};
+use crate::db::product_counts::ProductCounts;
+use crate::tenant::tenant_from_headers;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Deserialize, IntoParams)]
+pub struct CategoryQuery {
+    /// Count only this store's products
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CategoryResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    /// Published products in the category
+    pub product_count: i64,
+}
+
+/// List the product categories with how many published products each has
+#[utoipa::path(
+    get,
+    operation_id = "listCategories",
+    path = "/api/v1/categories",
+    tag = "Products",
+    params(CategoryQuery),
+    responses(
+        (status = 200, description = "Categories by name, with product counts", body = Vec<CategoryResponse>),
+        (status = 400, description = "Invalid X-Tenant-Id"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_categories(
+    State(db): State<DatabaseConnection>,
+    Query(query): Query<CategoryQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant_id = match tenant_from_headers(&headers) {
+        Ok(tenant_id) => tenant_id,
+        Err(err) => return err.into_response(),
+    };
+    let categories = match Category::list(&db).await {
+        Ok(categories) => categories,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let counts = match ProductCounts::published_by_category(&db, &tenant_id, query.store_id).await {
+        Ok(counts) => counts,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let categories: Vec<CategoryResponse> = categories
+        .into_iter()
+        .map(|category| CategoryResponse {
+            product_count: counts.get(&category.id).copied().unwrap_or(0),
+            id: category.id,
+            slug: category.slug,
+            name: category.name,
+        })
+        .collect();
+    Json(categories).into_response()
+}
+
+/// Most values an `enum` attribute may allow
+const MAX_ALLOWED_VALUES: usize = 100;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttributeDefinitionResponse {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub attribute_type: AttributeType,
+    /// Values an `enum` attribute may take; empty for other types
+    pub allowed_values: Vec<String>,
+    /// Products of the category must set the attribute
+    pub required: bool,
+}
+
+impl From<AttributeDefinition> for AttributeDefinitionResponse {
+    fn from(definition: AttributeDefinition) -> Self {
+        Self {
+            key: definition.key,
+            attribute_type: definition.attribute_type,
+            allowed_values: definition.allowed_values,
+            required: definition.required,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DefineAttributeRequest {
+    /// Lowercase letters, digits and underscores, e.g. `screen_size`
+    pub key: String,
+    #[serde(rename = "type")]
+    pub attribute_type: AttributeType,
+    /// Required for `enum`, rejected for other types
+    pub allowed_values: Option<Vec<String>>,
+    /// Defaults to false
+    pub required: Option<bool>,
+}
+
+impl DefineAttributeRequest {
+    fn definition(self) -> Result<AttributeDefinition, String> {
+        if !is_attribute_key(&self.key) {
+            return Err(format!(
+                "key must be at most {MAX_ATTRIBUTE_KEY_LEN} lowercase letters, digits or \
+                 underscores, start with a letter and not end in _gte, _lte, _gt or _lt"
+            ));
+        }
+        let allowed_values = match (self.attribute_type, self.allowed_values) {
+            (AttributeType::Enum, Some(values)) if !values.is_empty() => values,
+            (AttributeType::Enum, _) => {
+                return Err("allowed_values must list the values of an enum".to_string())
+            }
+            (_, Some(_)) => {
+                return Err("allowed_values only applies to enum attributes".to_string())
+            }
+            (_, None) => Vec::new(),
+        };
+        if allowed_values.len() > MAX_ALLOWED_VALUES {
+            return Err(format!(
+                "allowed_values may list at most {MAX_ALLOWED_VALUES} values"
+            ));
+        }
+        if let Some(value) = allowed_values
+            .iter()
+            .find(|value| value.trim().is_empty() || value.chars().count() > MAX_ATTRIBUTE_TEXT_LEN)
+        {
+            return Err(format!(
+                "allowed value '{value}' must be 1 to {MAX_ATTRIBUTE_TEXT_LEN} characters"
+            ));
+        }
+        Ok(AttributeDefinition {
+            key: self.key,
+            attribute_type: self.attribute_type,
+            allowed_values,
+            required: self.required.unwrap_or(false),
+        })
+    }
+}
+
+/// List the attributes products of a category carry
+#[utoipa::path(
+    get,
+    operation_id = "listCategoryAttributes",
+    path = "/api/v1/categories/{id}/attributes",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Category ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Attribute definitions by key", body = Vec<AttributeDefinitionResponse>),
+        (status = 404, description = "Category not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_category_attributes(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+) -> impl IntoResponse {
+    match Category::exists(&db, id).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::NOT_FOUND, "Category not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+    match CategoryAttributes::for_category(&db, id).await {
+        Ok(attributes) => {
+            let attributes: Vec<AttributeDefinitionResponse> = attributes
+                .iter()
+                .map(|attribute| AttributeDefinition::from(attribute).into())
+                .collect();
+            Json(attributes).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Define an attribute for a category's products. Existing products are
+/// checked against it on their next edit.
+#[utoipa::path(
+    post,
+    operation_id = "defineCategoryAttribute",
+    path = "/api/v1/admin/categories/{id}/attributes",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Category ID", format = "uuid")
+    ),
+    request_body = DefineAttributeRequest,
+    responses(
+        (status = 201, description = "Attribute defined", body = AttributeDefinitionResponse),
+        (status = 400, description = "Malformed key or allowed values"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Category not found"),
+        (status = 409, description = "The category already defines the key"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn define_category_attribute(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<DefineAttributeRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let definition = match request.definition() {
+        Ok(definition) => definition,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    match Category::exists(&db, id).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::NOT_FOUND, "Category not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+    match CategoryAttributes::define(&db, id, &definition).await {
+        Ok(attribute) => {
+            let response = AttributeDefinitionResponse::from(AttributeDefinition::from(&attribute));
+            (StatusCode::CREATED, Json(response)).into_response()
+        }
+        Err(err) if err == ATTRIBUTE_KEY_TAKEN => (StatusCode::CONFLICT, err).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Remove an attribute from a category. Products keep the value until
+/// their next edit, which must drop it.
+#[utoipa::path(
+    delete,
+    operation_id = "removeCategoryAttribute",
+    path = "/api/v1/admin/categories/{id}/attributes/{key}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Category ID", format = "uuid"),
+        ("key" = String, Path, description = "Attribute key")
+    ),
+    responses(
+        (status = 204, description = "Attribute removed"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "The category has no such attribute"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn remove_category_attribute(
+    State(db): State<DatabaseConnection>,
+    UuidPath((id, key)): UuidPath<(Uuid, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match CategoryAttributes::remove(&db, id, &key).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Category attribute not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
@@ -0,0 +1,392 @@
+//! Why the lines of a cart or order can't be bought as asked, with the
+//! quantity each one could be changed to, so the client can offer a fix
+//! instead of a bare refusal.
+
+use crate::db::products::PublicationStatus;
+use crate::db::stores::is_paused;
+use crate::entity::product::Model as ProductModel;
+use crate::entity::store::Model as StoreModel;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Some lines of the cart or order can't be bought as asked
+pub const ORDER_VALIDATION_FAILED: &str = "ORDER_VALIDATION_FAILED";
+
+/// One product and how many of it the buyer wants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+pub struct OrderLine {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub quantity: i32,
+}
+
+/// Why a line was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LineReason {
+    /// The product no longer exists
+    NotFound,
+    /// The store is paused and takes no orders
+    StorePaused,
+    /// A draft, or scheduled for later
+    ProductUnpublished,
+    /// Quantity below 1
+    InvalidQuantity,
+    OutOfStock,
+    /// Fewer in stock than asked; see `available`
+    InsufficientStock,
+}
+
+/// A refused line and how to fix it
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LineError {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub requested: i32,
+    pub reason: LineReason,
+    /// Units in stock, when stock is the problem
+    pub available: Option<i32>,
+    /// When a paused store opens again on its own
+    pub paused_until: Option<DateTime<Utc>>,
+    /// Most that can be bought right now; 0 means remove the line
+    pub max_purchasable: i32,
+}
+
+/// 422 listing every line of a cart or order that can't be bought as asked
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderValidationError {
+    pub code: &'static str,
+    pub message: String,
+    pub lines: Vec<LineError>,
+}
+
+/// The problem with one line, if any
+fn check_line(
+    line: &OrderLine,
+    product: Option<&ProductModel>,
+    store: Option<&StoreModel>,
+    now: DateTime<Utc>,
+) -> Option<LineError> {
+    let refuse = |reason, available: Option<i32>, max_purchasable| LineError {
+        product_id: line.product_id,
+        requested: line.quantity,
+        reason,
+        available,
+        paused_until: None,
+        max_purchasable,
+    };
+    let Some(product) = product else {
+        return Some(refuse(LineReason::NotFound, None, 0));
+    };
+    if let Some(store) = store.filter(|store| is_paused(store, now)) {
+        return Some(LineError {
+            paused_until: store.paused_until,
+            ..refuse(LineReason::StorePaused, None, 0)
+        });
+    }
+    if !product.is_published || PublicationStatus::of(product, now) != PublicationStatus::Published
+    {
+        return Some(refuse(LineReason::ProductUnpublished, None, 0));
+    }
+    let available = product.quantity_available.max(0);
+    if available == 0 {
+        return Some(refuse(LineReason::OutOfStock, Some(0), 0));
+    }
+    if line.quantity < 1 {
+        return Some(refuse(
+            LineReason::InvalidQuantity,
+            Some(available),
+            available,
+        ));
+    }
+    if line.quantity > available {
+        return Some(refuse(
+            LineReason::InsufficientStock,
+            Some(available),
+            available,
+        ));
+    }
+    None
+}
+
+// Cart checkout and order creation check their lines with this before
+// taking stock
+#[allow(dead_code)]
+impl OrderValidationError {
+    /// `Err` listing, in cart order, every line that can't be bought as
+    /// asked. `products` and `stores` are the rows the lines refer to; a
+    /// line whose product is missing is refused as not found.
+    pub fn check(
+        lines: &[OrderLine],
+        products: &[ProductModel],
+        stores: &[StoreModel],
+        now: DateTime<Utc>,
+    ) -> Result<(), Self> {
+        let lines: Vec<LineError> = lines
+            .iter()
+            .filter_map(|line| {
+                let product = products.iter().find(|p| p.id == line.product_id);
+                let store = product
+                    .and_then(|product| stores.iter().find(|store| store.id == product.store_id));
+                check_line(line, product, store, now)
+            })
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        Err(Self {
+            code: ORDER_VALIDATION_FAILED,
+            message: format!("{} item(s) can't be ordered as asked", lines.len()),
+            lines,
+        })
+    }
+}
+
+impl IntoResponse for OrderValidationError {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn store(paused_until: Option<Option<DateTime<Utc>>>) -> StoreModel {
+        let now = Utc::now();
+        StoreModel {
+            id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            name: "Mama Ngono".to_string(),
+            description: None,
+            logo_url: None,
+            location: None,
+            contact_phone: None,
+            contact_email: None,
+            contact_whatsapp: None,
+            show_phone: true,
+            show_whatsapp: true,
+            show_email: true,
+            embed_enabled: false,
+            media_bytes_used: 0,
+            media_uploads_today: 0,
+            media_uploads_day: None,
+            media_quota_bytes: None,
+            media_daily_uploads: None,
+            media_images_per_product: None,
+            owner_device_id: None,
+            is_verified: false,
+            rating: None,
+            store_rating: None,
+            store_review_count: 0,
+            total_products: 0,
+            default_return_policy: None,
+            default_pickup_available: true,
+            default_delivery_available: false,
+            default_delivery_fee: None,
+            default_delivery_estimated_days: None,
+            is_paused: paused_until.is_some(),
+            paused_until: paused_until.flatten(),
+            pause_message: None,
+            auto_response_enabled: false,
+            auto_response_message: None,
+            auto_response_trigger: None,
+            trust_score: None,
+            first_shared_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn product(store_id: Uuid, quantity: i32, is_published: bool) -> ProductModel {
+        let now = Utc::now();
+        ProductModel {
+            id: Uuid::new_v4(),
+            store_id,
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: "Ndole".to_string(),
+            description: None,
+            price: 1500.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: quantity,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn line(product: &ProductModel, quantity: i32) -> OrderLine {
+        OrderLine {
+            product_id: product.id,
+            quantity,
+        }
+    }
+
+    /// Reason, available and max purchasable of a single line
+    fn outcome(
+        product: &ProductModel,
+        store: &StoreModel,
+        quantity: i32,
+    ) -> Option<(LineReason, Option<i32>, i32)> {
+        let now = Utc::now();
+        match OrderValidationError::check(
+            &[line(product, quantity)],
+            std::slice::from_ref(product),
+            std::slice::from_ref(store),
+            now,
+        ) {
+            Ok(()) => None,
+            Err(err) => {
+                assert_eq!(err.code, ORDER_VALIDATION_FAILED);
+                let [line] = err.lines.as_slice() else {
+                    panic!("one line per refused line: {:?}", err.lines)
+                };
+                Some((line.reason, line.available, line.max_purchasable))
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_cart_state_gets_its_reason_and_fix() {
+        let now = Utc::now();
+        let open = store(None);
+        let paused = store(Some(Some(now + Duration::days(2))));
+        let lapsed_pause = store(Some(Some(now - Duration::hours(1))));
+        let mut scheduled = product(open.id, 5, true);
+        scheduled.publish_at = Some(now + Duration::days(1));
+
+        let cases = [
+            // (product, store, quantity, expected)
+            (product(open.id, 5, true), &open, 1, None),
+            (product(open.id, 5, true), &open, 5, None),
+            (
+                product(open.id, 5, true),
+                &open,
+                6,
+                Some((LineReason::InsufficientStock, Some(5), 5)),
+            ),
+            (
+                product(open.id, 0, true),
+                &open,
+                1,
+                Some((LineReason::OutOfStock, Some(0), 0)),
+            ),
+            // Oversold stock counts as none left
+            (
+                product(open.id, -2, true),
+                &open,
+                1,
+                Some((LineReason::OutOfStock, Some(0), 0)),
+            ),
+            (
+                product(open.id, 5, true),
+                &open,
+                0,
+                Some((LineReason::InvalidQuantity, Some(5), 5)),
+            ),
+            (
+                product(open.id, 5, false),
+                &open,
+                1,
+                Some((LineReason::ProductUnpublished, None, 0)),
+            ),
+            (
+                scheduled,
+                &open,
+                1,
+                Some((LineReason::ProductUnpublished, None, 0)),
+            ),
+            // The pause outranks every product-level problem
+            (
+                product(paused.id, 0, false),
+                &paused,
+                9,
+                Some((LineReason::StorePaused, None, 0)),
+            ),
+            (product(lapsed_pause.id, 5, true), &lapsed_pause, 2, None),
+        ];
+        for (index, (product, store, quantity, expected)) in cases.iter().enumerate() {
+            assert_eq!(
+                outcome(product, store, *quantity),
+                *expected,
+                "case {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_only_failing_lines_are_listed_in_cart_order() {
+        let now = Utc::now();
+        let open = store(None);
+        let paused = store(Some(None));
+        let fine = product(open.id, 3, true);
+        let short = product(open.id, 2, true);
+        let away = product(paused.id, 3, true);
+        let gone = product(open.id, 3, true);
+
+        let err = OrderValidationError::check(
+            &[
+                line(&short, 4),
+                line(&fine, 1),
+                line(&gone, 1),
+                line(&away, 1),
+            ],
+            &[fine.clone(), short.clone(), away.clone()],
+            &[open, paused],
+            now,
+        )
+        .unwrap_err();
+        let listed: Vec<(Uuid, LineReason)> = err
+            .lines
+            .iter()
+            .map(|line| (line.product_id, line.reason))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (short.id, LineReason::InsufficientStock),
+                (gone.id, LineReason::NotFound),
+                (away.id, LineReason::StorePaused),
+            ]
+        );
+        assert_eq!(err.lines[2].paused_until, None);
+        assert_eq!(err.message, "3 item(s) can't be ordered as asked");
+
+        let body = serde_json::to_value(&err).unwrap();
+        assert_eq!(body["lines"][0]["reason"], "insufficient_stock");
+        assert_eq!(body["lines"][0]["max_purchasable"], 2);
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        assert!(OrderValidationError::check(&[line(&fine, 3)], &[fine], &[], now).is_ok());
+    }
+}
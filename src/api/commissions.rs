@@ -0,0 +1,353 @@
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::db::categories::Category;
+use crate::db::commissions::{is_in_effect, CommissionRate, RateScope};
+use crate::db::stores::Store;
+use crate::entity::commission_rate::Model as RateModel;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateCommissionRateRequest {
+    /// Set exactly one of `category_id` and `store_id`
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Overrides the category rates for every product of the store
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+    /// Share of the sale price, 0 to 100
+    pub percent: f64,
+    /// Defaults to now; may not be in the past
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateCommissionRateRequest {
+    pub percent: f64,
+    pub effective_from: DateTime<Utc>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct CommissionRatesQuery {
+    /// Only this category's rates
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Only this store's overrides
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct EffectiveRateQuery {
+    #[param(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// Category of the product sold; without it only store overrides apply
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Defaults to now
+    pub at: Option<DateTime<Utc>>,
+}
+
+/// Rate with whether it already applies
+#[derive(Serialize, ToSchema)]
+pub struct CommissionRateResponse {
+    #[serde(flatten)]
+    pub rate: RateModel,
+    pub in_effect: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CommissionRatesListResponse {
+    pub rates: Vec<CommissionRateResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EffectiveRateResponse {
+    /// `None` when no rate covers the sale
+    pub rate: Option<RateModel>,
+}
+
+fn check_percent(percent: f64) -> Result<(), &'static str> {
+    if !percent.is_finite() || !(0.0..=100.0).contains(&percent) {
+        return Err("percent must be between 0 and 100");
+    }
+    Ok(())
+}
+
+fn scope_of(category_id: Option<Uuid>, store_id: Option<Uuid>) -> Result<RateScope, &'static str> {
+    match (category_id, store_id) {
+        (Some(category_id), None) => Ok(RateScope::Category(category_id)),
+        (None, Some(store_id)) => Ok(RateScope::Store(store_id)),
+        _ => Err("Set exactly one of category_id and store_id"),
+    }
+}
+
+/// Rates already applied to sales stay as they are
+fn ensure_pending(rate: &RateModel, now: DateTime<Utc>) -> Result<(), (StatusCode, &'static str)> {
+    if is_in_effect(rate, now) {
+        return Err((
+            StatusCode::CONFLICT,
+            "This rate is already in effect; add a new rate with a later effective_from instead",
+        ));
+    }
+    Ok(())
+}
+
+/// Set a commission rate for a category, or a store override
+#[utoipa::path(
+    post,
+    operation_id = "createCommissionRate",
+    path = "/api/v1/admin/commission-rates",
+    tag = "Admin",
+    request_body = CreateCommissionRateRequest,
+    responses(
+        (status = 201, description = "Rate created", body = RateModel),
+        (status = 400, description = "Invalid scope, percent or start"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Category or store not found")
+    )
+)]
+pub async fn create_commission_rate(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<CreateCommissionRateRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let scope = match scope_of(request.category_id, request.store_id) {
+        Ok(scope) => scope,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    if let Err(err) = check_percent(request.percent) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let now = Utc::now();
+    let effective_from = request.effective_from.unwrap_or(now);
+    if effective_from < now {
+        return (
+            StatusCode::BAD_REQUEST,
+            "effective_from must not be in the past",
+        )
+            .into_response();
+    }
+    match scope {
+        RateScope::Category(id) => match Category::exists(&db, id).await {
+            Ok(true) => {}
+            Ok(false) => return (StatusCode::NOT_FOUND, "Category not found.").into_response(),
+            Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        },
+        RateScope::Store(id) => {
+            if let Err(err) = Store::get(&db, id).await {
+                return (StatusCode::NOT_FOUND, err).into_response();
+            }
+        }
+    }
+
+    match CommissionRate::create(&db, scope, request.percent, effective_from, &admin.relay_id).await
+    {
+        Ok(rate) => (StatusCode::CREATED, Json(rate)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List commission rates, latest start first
+#[utoipa::path(
+    get,
+    operation_id = "listCommissionRates",
+    path = "/api/v1/admin/commission-rates",
+    tag = "Admin",
+    params(CommissionRatesQuery),
+    responses(
+        (status = 200, description = "Rates, past and upcoming", body = CommissionRatesListResponse),
+        (status = 400, description = "Both category_id and store_id given"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_commission_rates(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<CommissionRatesQuery>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let scope = match (query.category_id, query.store_id) {
+        (None, None) => None,
+        (category_id, store_id) => match scope_of(category_id, store_id) {
+            Ok(scope) => Some(scope),
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        },
+    };
+    match CommissionRate::list(&db, scope).await {
+        Ok(rates) => {
+            let now = Utc::now();
+            Json(CommissionRatesListResponse {
+                rates: rates
+                    .into_iter()
+                    .map(|rate| CommissionRateResponse {
+                        in_effect: is_in_effect(&rate, now),
+                        rate,
+                    })
+                    .collect(),
+            })
+            .into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// The rate a sale would pay, as order creation will look it up
+#[utoipa::path(
+    get,
+    operation_id = "getEffectiveCommissionRate",
+    path = "/api/v1/admin/commission-rates/effective",
+    tag = "Admin",
+    params(EffectiveRateQuery),
+    responses(
+        (status = 200, description = "Store override, else category rate, else none", body = EffectiveRateResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn effective_commission_rate(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Query(query): Query<EffectiveRateQuery>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let at = query.at.unwrap_or_else(Utc::now);
+    match CommissionRate::effective(&db, query.store_id, query.category_id, at).await {
+        Ok(rate) => Json(EffectiveRateResponse { rate }).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Reprice or reschedule a rate that is not in effect yet
+#[utoipa::path(
+    put,
+    operation_id = "updateCommissionRate",
+    path = "/api/v1/admin/commission-rates/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Commission rate ID", format = "uuid")
+    ),
+    request_body = UpdateCommissionRateRequest,
+    responses(
+        (status = 200, description = "Rate updated", body = RateModel),
+        (status = 400, description = "Invalid percent or start"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Rate not found"),
+        (status = 409, description = "Rate already in effect")
+    )
+)]
+pub async fn update_commission_rate(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateCommissionRateRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    if let Err(err) = check_percent(request.percent) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let now = Utc::now();
+    if request.effective_from < now {
+        return (
+            StatusCode::BAD_REQUEST,
+            "effective_from must not be in the past",
+        )
+            .into_response();
+    }
+    let rate = match CommissionRate::get(&db, id).await {
+        Ok(Some(rate)) => rate,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Commission rate not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if let Err(conflict) = ensure_pending(&rate, now) {
+        return conflict.into_response();
+    }
+
+    match CommissionRate::update(&db, rate, request.percent, request.effective_from).await {
+        Ok(rate) => Json(rate).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Withdraw a rate that is not in effect yet
+#[utoipa::path(
+    delete,
+    operation_id = "deleteCommissionRate",
+    path = "/api/v1/admin/commission-rates/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Commission rate ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Rate deleted"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Rate not found"),
+        (status = 409, description = "Rate already in effect")
+    )
+)]
+pub async fn delete_commission_rate(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let rate = match CommissionRate::get(&db, id).await {
+        Ok(Some(rate)) => rate,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Commission rate not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if let Err(conflict) = ensure_pending(&rate, Utc::now()) {
+        return conflict.into_response();
+    }
+    match CommissionRate::delete(&db, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Commission rate not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_needs_one_scope_and_a_sane_percent() {
+        let id = Uuid::new_v4();
+        assert_eq!(scope_of(Some(id), None), Ok(RateScope::Category(id)));
+        assert_eq!(scope_of(None, Some(id)), Ok(RateScope::Store(id)));
+        assert!(scope_of(None, None).is_err());
+        assert!(scope_of(Some(id), Some(id)).is_err());
+
+        assert!(check_percent(0.0).is_ok());
+        assert!(check_percent(12.5).is_ok());
+        assert!(check_percent(100.0).is_ok());
+        assert!(check_percent(-1.0).is_err());
+        assert!(check_percent(100.5).is_err());
+        assert!(check_percent(f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Delta-encoded list responses for repeat visitors.
+//!
+//! A client that already holds a list sends `known_ids=<id:version,...>`,
+//! where `version` is the `updated_at` it last saw in Unix milliseconds. The
+//! server answers with only the changed rows, the ids that left the list and
+//! a count of the rows it skipped. A delta covers the whole list; without
+//! `known_ids` the client gets one page of it.
+
+use crate::api::fields::{project_all, FieldSelection};
+use crate::api::pagination::PageRequest;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Most entries `known_ids` may carry; larger lists get a full response
+pub const MAX_KNOWN_IDS: usize = 500;
+
+/// Version of a row as clients quote it back in `known_ids`
+pub fn version(updated_at: DateTime<Utc>) -> i64 {
+    updated_at.timestamp_millis()
+}
+
+/// Rows a client already holds, by id
+#[derive(Debug, Default)]
+pub struct KnownVersions(HashMap<Uuid, i64>);
+
+impl KnownVersions {
+    /// Parse `id:version,...`. `Ok(None)` when the list is over
+    /// [`MAX_KNOWN_IDS`], in which case the caller sends the full list.
+    pub fn parse(raw: &str) -> Result<Option<Self>, String> {
+        let entries: Vec<&str> = raw
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .collect();
+        if entries.len() > MAX_KNOWN_IDS {
+            return Ok(None);
+        }
+        let mut known = HashMap::with_capacity(entries.len());
+        for entry in entries {
+            let parsed = entry.split_once(':').and_then(|(id, version)| {
+                Some((Uuid::parse_str(id).ok()?, version.parse::<i64>().ok()?))
+            });
+            match parsed {
+                Some((id, version)) => known.insert(id, version),
+                None => return Err(format!("known_ids entry '{entry}' must be <id>:<version>")),
+            };
+        }
+        Ok(Some(Self(known)))
+    }
+}
+
+/// How a current list differs from what the client holds
+#[derive(Debug, PartialEq)]
+pub struct DeltaPlan {
+    /// Positions in the current list the client must (re)load, ascending
+    pub changed: Vec<usize>,
+    pub removed: Vec<Uuid>,
+    pub unchanged_count: usize,
+}
+
+/// Compare the current `(id, version)` list against the client's copy
+pub fn plan(known: &KnownVersions, current: &[(Uuid, i64)]) -> DeltaPlan {
+    let mut changed = Vec::new();
+    let mut unchanged_count = 0;
+    for (index, (id, version)) in current.iter().enumerate() {
+        match known.0.get(id) {
+            Some(held) if held == version => unchanged_count += 1,
+            _ => changed.push(index),
+        }
+    }
+    let present: HashSet<&Uuid> = current.iter().map(|(id, _)| id).collect();
+    let mut removed: Vec<Uuid> = known
+        .0
+        .keys()
+        .filter(|id| !present.contains(id))
+        .copied()
+        .collect();
+    removed.sort();
+    DeltaPlan {
+        changed,
+        removed,
+        unchanged_count,
+    }
+}
+
+/// `page` of the list, or a delta body when the client sent its versions.
+/// `versions` lines up with `items`.
+pub fn list_body<T: Serialize>(
+    items: Vec<T>,
+    versions: &[(Uuid, i64)],
+    known: Option<&KnownVersions>,
+    fields: Option<&FieldSelection>,
+    page: &PageRequest,
+) -> Value {
+    let Some(known) = known else {
+        return serde_json::to_value(page.slice(items).project(fields)).unwrap_or(Value::Null);
+    };
+    let plan = plan(known, versions);
+    let changed: Vec<T> = items
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| plan.changed.binary_search(index).is_ok())
+        .map(|(_, item)| item)
+        .collect();
+    json!({
+        "changed": project_all(&changed, fields),
+        "removed": plan.removed,
+        "unchanged_count": plan.unchanged_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Item {
+        id: Uuid,
+        name: &'static str,
+    }
+
+    #[test]
+    fn test_one_round_trip_covers_every_kind_of_change() {
+        let (kept, edited, deleted, added) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        let raw = format!("{kept}:100,{edited}:100,{deleted}:100");
+        let known = KnownVersions::parse(&raw).unwrap().unwrap();
+
+        let items = vec![
+            Item {
+                id: added,
+                name: "new",
+            },
+            Item {
+                id: edited,
+                name: "edited",
+            },
+            Item {
+                id: kept,
+                name: "kept",
+            },
+        ];
+        let versions = [(added, 300), (edited, 200), (kept, 100)];
+        let body = list_body(
+            items,
+            &versions,
+            Some(&known),
+            None,
+            &PageRequest::default(),
+        );
+
+        assert_eq!(
+            body,
+            json!({
+                "changed": [
+                    { "id": added, "name": "new" },
+                    { "id": edited, "name": "edited" },
+                ],
+                "removed": [deleted],
+                "unchanged_count": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_without_known_ids_a_page_is_sent() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let page = PageRequest {
+            page: 2,
+            per_page: 1,
+            cursor: None,
+        };
+        let body = list_body(
+            vec![
+                Item {
+                    id: first,
+                    name: "a",
+                },
+                Item {
+                    id: second,
+                    name: "b",
+                },
+            ],
+            &[(first, 1), (second, 1)],
+            None,
+            None,
+            &page,
+        );
+        assert_eq!(
+            body,
+            json!({
+                "items": [{ "id": second, "name": "b" }],
+                "total": 2,
+                "page": 2,
+                "per_page": 1,
+                "next_cursor": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_oversized_known_ids_fall_back_to_full_response() {
+        let raw: Vec<String> = (0..=MAX_KNOWN_IDS)
+            .map(|i| format!("{}:{i}", Uuid::new_v4()))
+            .collect();
+        assert!(KnownVersions::parse(&raw.join(",")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_malformed_entries_are_rejected() {
+        assert!(KnownVersions::parse("not-a-uuid:1").is_err());
+        assert!(KnownVersions::parse(&Uuid::new_v4().to_string()).is_err());
+        assert!(KnownVersions::parse(&format!("{}:soon", Uuid::new_v4())).is_err());
+        assert!(KnownVersions::parse("").unwrap().is_some());
+    }
+}
@@ -0,0 +1,519 @@
+//! The storefront widget sellers embed on their own websites.
+//!
+//! Served without a token and with CORS open to any origin, on these routes
+//! only: the payload is a trimmed public view of the store, so there is
+//! nothing a foreign page could read that the store page doesn't already
+//! show. Stores opt in with `embed_enabled`. A `callback` query parameter
+//! wraps the JSON for JSONP, for pages that load it with a script tag.
+
+use crate::api::extract::UuidPath;
+use crate::api::products::media_url;
+use crate::config::SiteConfig;
+use crate::db::products::{active_sale, CatalogFilter, Product};
+use crate::db::stores::{is_paused, Store};
+use crate::entity::product::Model as ProductModel;
+use crate::entity::store::Model as StoreModel;
+use crate::links::{ProductLink, StoreLink};
+use crate::money::{Locale, LocaleParams, Money};
+use axum::{
+    extract::{FromRef, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_http::cors::{Any, CorsLayer};
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Products the widget shows, by name
+pub const EMBED_PRODUCTS: u64 = 50;
+const CACHE_CONTROL: &str = "public, max-age=3600";
+/// Longest JSONP callback name accepted
+const CALLBACK_MAX_LEN: usize = 64;
+/// Window over which embed requests are counted
+const EMBED_WINDOW: Duration = Duration::from_secs(60);
+
+/// Requests counted so far, by embedding origin and store
+type Counts = HashMap<(String, Uuid), u32>;
+
+/// Fixed-window request counter per embedding origin and store, so one busy
+/// site can't use up another's allowance for the same store
+pub struct EmbedLimiter {
+    per_window: u32,
+    inner: Mutex<(Instant, Counts)>,
+}
+
+impl EmbedLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_window: per_minute,
+            inner: Mutex::new((Instant::now(), HashMap::new())),
+        }
+    }
+
+    /// Count a request; false once the origin is over the limit for the store
+    pub fn allow(&self, origin: &str, store_id: Uuid, now: Instant) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return true;
+        };
+        let (started, counts) = &mut *inner;
+        if now.duration_since(*started) >= EMBED_WINDOW {
+            *started = now;
+            counts.clear();
+        }
+        let count = counts.entry((origin.to_string(), store_id)).or_insert(0);
+        *count += 1;
+        *count <= self.per_window
+    }
+}
+
+/// The embedding page's origin: `Origin` on fetches, else the referring
+/// page's, else empty for direct loads
+fn embedding_origin(headers: &HeaderMap) -> String {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(origin) = header(header::ORIGIN) {
+        return origin.to_string();
+    }
+    header(header::REFERER)
+        .and_then(|referer| url::Url::parse(referer).ok())
+        .map(|url| url.origin().ascii_serialization())
+        .unwrap_or_default()
+}
+
+/// A JSONP callback is a dotted JavaScript identifier path and nothing else,
+/// so it can't smuggle script into the response
+fn valid_callback(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= CALLBACK_MAX_LEN
+        && name.split('.').all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct EmbedQuery {
+    /// Wrap the JSON in a call to this function (JSONP)
+    pub callback: Option<String>,
+}
+
+/// The store as the widget shows it. Contact numbers and the email are
+/// only given when the seller publishes them; they are left out rather
+/// than masked.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbedStore {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub name: String,
+    pub logo_url: Option<String>,
+    pub location: Option<String>,
+    pub contact_phone: Option<String>,
+    pub contact_whatsapp: Option<String>,
+    pub contact_email: Option<String>,
+    /// Seller is away; buyers can browse but not order
+    pub is_paused: bool,
+    /// Store page on the marketplace
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbedProduct {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub name: String,
+    #[schema(deprecated)]
+    pub price: f64,
+    /// Price of the running sale, if any
+    #[schema(deprecated)]
+    pub sale_price: Option<f64>,
+    /// ISO 4217 code of both prices
+    pub currency: String,
+    pub price_money: Money,
+    pub sale_price_money: Option<Money>,
+    pub image_url: Option<String>,
+    /// Product page on the marketplace
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbedStoreResponse {
+    pub store: EmbedStore,
+    /// Published, in-stock products by name, at most 50
+    pub products: Vec<EmbedProduct>,
+}
+
+pub fn embed_store_view(store: &StoreModel, site: &SiteConfig, now: DateTime<Utc>) -> EmbedStore {
+    let shown = |show: bool, value: &Option<String>| value.clone().filter(|_| show);
+    EmbedStore {
+        id: store.id,
+        name: store.name.clone(),
+        logo_url: store.logo_url.clone(),
+        location: store.location.clone(),
+        contact_phone: shown(store.show_phone, &store.contact_phone),
+        contact_whatsapp: shown(store.show_whatsapp, &store.contact_whatsapp),
+        contact_email: shown(store.show_email, &store.contact_email),
+        is_paused: is_paused(store, now),
+        url: StoreLink::new(&site.public_base_url, store.id).to_string(),
+    }
+}
+
+pub fn embed_product_view(
+    product: &ProductModel,
+    site: &SiteConfig,
+    now: DateTime<Utc>,
+    locale: Locale,
+) -> EmbedProduct {
+    let sale_price = active_sale(product, now).map(|sale| sale.price);
+    EmbedProduct {
+        id: product.id,
+        name: product.name.clone(),
+        price: product.price,
+        sale_price,
+        currency: product.currency.clone(),
+        price_money: Money::new(product.price, &product.currency, locale),
+        sale_price_money: sale_price.map(|price| Money::new(price, &product.currency, locale)),
+        image_url: product
+            .image_id
+            .map(|id| format!("{}{}", site.public_base_url, media_url(id))),
+        url: ProductLink::new(&site.public_base_url, product.id).to_string(),
+    }
+}
+
+/// A store's products for the embed widget
+#[utoipa::path(
+    get,
+    operation_id = "embedStore",
+    path = "/api/v1/embed/stores/{id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        EmbedQuery,
+        LocaleParams
+    ),
+    responses(
+        (status = 200, description = "The store and its products; JavaScript calling `callback` when one is given", body = EmbedStoreResponse),
+        (status = 400, description = "callback is not a JavaScript function name"),
+        (status = 404, description = "Store not found, or it has not turned embedding on"),
+        (status = 429, description = "Too many requests from this origin for this store"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn embed_store(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    State(limiter): State<Arc<EmbedLimiter>>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<EmbedQuery>,
+    locale: Locale,
+    headers: HeaderMap,
+) -> Response {
+    let origin = embedding_origin(&headers);
+    if !limiter.allow(&origin, id, Instant::now()) {
+        warn!(store_id = %id, origin = %origin, "Embed rate limit exceeded");
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many requests.").into_response();
+    }
+    if let Some(callback) = query.callback.as_deref().filter(|c| !valid_callback(c)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("callback '{callback}' is not a JavaScript function name"),
+        )
+            .into_response();
+    }
+    let store = match Store::get(&db, id).await {
+        Ok(store) if store.embed_enabled => store,
+        _ => return (StatusCode::NOT_FOUND, "Store not found.").into_response(),
+    };
+
+    let now = Utc::now();
+    let products = match Product::catalog_page(
+        &db,
+        id,
+        CatalogFilter::default(),
+        now,
+        0,
+        EMBED_PRODUCTS,
+    )
+    .await
+    {
+        Ok(products) => products,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let body = EmbedStoreResponse {
+        store: embed_store_view(&store, &site, now),
+        products: products
+            .iter()
+            .map(|product| embed_product_view(product, &site, now, locale))
+            .collect(),
+    };
+
+    let cache = [(header::CACHE_CONTROL, CACHE_CONTROL)];
+    match query.callback {
+        Some(callback) => {
+            let json = match serde_json::to_string(&body) {
+                Ok(json) => json,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+                }
+            };
+            (
+                cache,
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        "application/javascript; charset=utf-8",
+                    ),
+                    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+                ],
+                // The comment keeps a leading byte order mark or stray text
+                // from joining the call
+                format!("/**/{callback}({json});"),
+            )
+                .into_response()
+        }
+        None => (cache, Json(body)).into_response(),
+    }
+}
+
+/// Any origin may read the widget, without credentials. Layered on the
+/// embed routes alone; the rest of the API keeps the app-wide policy.
+pub fn embed_cors() -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::GET, Method::HEAD])
+        .max_age(Duration::from_secs(86_400))
+}
+
+/// Embed routes. Merge them outside the token check: they serve anyone.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    DatabaseConnection: FromRef<S>,
+    Arc<SiteConfig>: FromRef<S>,
+    Arc<EmbedLimiter>: FromRef<S>,
+{
+    Router::new()
+        .route("/api/v1/embed/stores/:id", get(embed_store))
+        .layer(embed_cors())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::{product, store};
+    use axum::{body::Body, http::Request};
+    use sea_orm::{sea_query::Expr, EntityTrait};
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        site: Arc<SiteConfig>,
+        limiter: Arc<EmbedLimiter>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<SiteConfig> {
+        fn from_ref(state: &TestState) -> Self {
+            state.site.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<EmbedLimiter> {
+        fn from_ref(state: &TestState) -> Self {
+            state.limiter.clone()
+        }
+    }
+
+    /// The embed routes next to an ordinary one, as the app merges them
+    fn app(db: &DatabaseConnection, per_minute: u32) -> Router {
+        Router::new()
+            .route("/api/v1/stores/:id", get(|| async { "store" }))
+            .merge(router())
+            .with_state(TestState {
+                db: db.clone(),
+                site: Arc::new(SiteConfig {
+                    public_base_url: "https://transac.site".to_string(),
+                    currency: "XAF".to_string(),
+                }),
+                limiter: Arc::new(EmbedLimiter::new(per_minute)),
+            })
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str) -> (StatusCode, HeaderMap, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::ORIGIN, "https://mama-ngono.cm")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn embeddable_store(db: &DatabaseConnection) -> (Uuid, Uuid) {
+        let product_id = testing::seed_product(db, "seller-1").await;
+        let store_id = product::Entity::find_by_id(product_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .store_id;
+        let store = Store::get(db, store_id).await.unwrap();
+        Store::set_embed_enabled(db, store, true).await.unwrap();
+        (store_id, product_id)
+    }
+
+    #[tokio::test]
+    async fn test_embedding_is_off_until_the_seller_turns_it_on() {
+        let db = testing::sqlite().await;
+        let (store_id, product_id) = embeddable_store(&db).await;
+        let app = app(&db, 100);
+        let uri = format!("/api/v1/embed/stores/{store_id}");
+
+        let store = Store::get(&db, store_id).await.unwrap();
+        Store::set_embed_enabled(&db, store, false).await.unwrap();
+        assert_eq!(call(&app, Method::GET, &uri).await.0, StatusCode::NOT_FOUND);
+
+        let store = Store::get(&db, store_id).await.unwrap();
+        Store::set_embed_enabled(&db, store, true).await.unwrap();
+        let (status, headers, body) = call(&app, Method::GET, &uri).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(headers[header::CACHE_CONTROL], CACHE_CONTROL);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["products"][0]["id"], product_id.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_payload_is_projected_to_public_fields() {
+        let db = testing::sqlite().await;
+        let (store_id, product_id) = embeddable_store(&db).await;
+        store::Entity::update_many()
+            .col_expr(store::Column::ContactPhone, Expr::value("+237699000123"))
+            .col_expr(store::Column::ContactWhatsapp, Expr::value("+237677000456"))
+            .col_expr(store::Column::ContactEmail, Expr::value("ngono@example.cm"))
+            .col_expr(store::Column::ShowPhone, Expr::value(false))
+            .col_expr(store::Column::ShowEmail, Expr::value(false))
+            .exec(&db)
+            .await
+            .unwrap();
+
+        let (_, _, body) = call(
+            &app(&db, 100),
+            Method::GET,
+            &format!("/api/v1/embed/stores/{store_id}"),
+        )
+        .await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body["store"],
+            serde_json::json!({
+                "id": store_id,
+                "name": "Mama Ngono",
+                "logo_url": null,
+                "location": null,
+                "contact_phone": null,
+                "contact_whatsapp": "+237677000456",
+                "contact_email": null,
+                "is_paused": false,
+                "url": format!("https://transac.site/store/{store_id}"),
+            })
+        );
+        assert_eq!(
+            body["products"],
+            serde_json::json!([{
+                "id": product_id,
+                "name": "Wax print",
+                "price": 5000.0,
+                "sale_price": null,
+                "currency": "XAF",
+                "price_money": {
+                    "amount": 5000.0,
+                    "currency": "XAF",
+                    "formatted": "5,000\u{a0}FCFA",
+                },
+                "sale_price_money": null,
+                "image_url": null,
+                "url": format!("https://transac.site/product/{product_id}"),
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_is_opened_on_embed_routes_only() {
+        let db = testing::sqlite().await;
+        let (store_id, _) = embeddable_store(&db).await;
+        let app = app(&db, 100);
+        let embed = format!("/api/v1/embed/stores/{store_id}");
+
+        let (_, headers, _) = call(&app, Method::GET, &embed).await;
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        let (status, headers, _) = call(&app, Method::OPTIONS, &embed).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        let (status, headers, _) =
+            call(&app, Method::GET, &format!("/api/v1/stores/{store_id}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_jsonp_callback_is_checked() {
+        let db = testing::sqlite().await;
+        let (store_id, _) = embeddable_store(&db).await;
+        let app = app(&db, 100);
+        let uri = format!("/api/v1/embed/stores/{store_id}");
+
+        let (status, headers, body) =
+            call(&app, Method::GET, &format!("{uri}?callback=Transac.render")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("application/javascript"));
+        assert!(body.starts_with("/**/Transac.render({\"store\":"), "{body}");
+        assert!(body.ends_with("});"));
+
+        for callback in ["alert(1)", "a.", "1up", "x%3Bdocument.cookie"] {
+            let (status, _, _) =
+                call(&app, Method::GET, &format!("{uri}?callback={callback}")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{callback}");
+        }
+    }
+
+    #[test]
+    fn test_limit_is_per_origin_and_store() {
+        let limiter = EmbedLimiter::new(2);
+        let (store, other_store) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        assert!(limiter.allow("https://a.cm", store, now));
+        assert!(limiter.allow("https://a.cm", store, now));
+        assert!(!limiter.allow("https://a.cm", store, now));
+        assert!(limiter.allow("https://b.cm", store, now));
+        assert!(limiter.allow("https://a.cm", other_store, now));
+        assert!(limiter.allow("https://a.cm", store, now + EMBED_WINDOW));
+    }
+}
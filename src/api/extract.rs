@@ -0,0 +1,115 @@
+//! Extractors whose rejections use the API's JSON error bodies.
+
+use axum::{
+    async_trait,
+    extract::{rejection::PathRejection, FromRequestParts, Path, RawPathParams},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Error code of the 400 sent for a path id that is not a UUID
+pub const INVALID_ID: &str = "INVALID_ID";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InvalidIdResponse {
+    pub code: &'static str,
+    /// Names the offending parameter, e.g. "id must be a UUID, got 'abc'"
+    pub message: String,
+}
+
+impl IntoResponse for InvalidIdResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(self)).into_response()
+    }
+}
+
+/// `Path` for routes whose parameters are UUIDs. A malformed id gets an
+/// `INVALID_ID` JSON error instead of axum's plain-text rejection.
+pub struct UuidPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for UuidPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(UuidPath(value)),
+            Err(PathRejection::FailedToDeserializePathParams(rejection)) => {
+                // The uuid parse error doesn't say which parameter failed
+                let params = RawPathParams::from_request_parts(parts, state).await.ok();
+                let invalid = params.as_ref().and_then(|params| {
+                    params
+                        .iter()
+                        .find(|(_, value)| Uuid::parse_str(value).is_err())
+                        .map(|(key, value)| format!("{key} must be a UUID, got '{value}'"))
+                });
+                let message = invalid.unwrap_or_else(|| {
+                    warn!(error = %rejection.body_text(), "Unexpected path rejection");
+                    "Path ids must be UUIDs".to_string()
+                });
+                Err(InvalidIdResponse {
+                    code: INVALID_ID,
+                    message,
+                }
+                .into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{bundles, products, stores};
+    use crate::db::testing;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_malformed_ids_get_the_same_error_everywhere() {
+        let db = testing::sqlite().await;
+        let app = Router::new()
+            .route("/stores/:id", get(stores::get_store))
+            .route("/stores/:id/bundles/:bundle_id", get(bundles::get_bundle))
+            .with_state(db.clone())
+            .merge(products::router(db));
+        let valid = Uuid::new_v4();
+
+        let cases = [
+            ("/stores/abc".to_string(), "id must be a UUID, got 'abc'"),
+            ("/products/42".to_string(), "id must be a UUID, got '42'"),
+            (
+                "/products/xyz/media".to_string(),
+                "id must be a UUID, got 'xyz'",
+            ),
+            (
+                format!("/stores/{valid}/bundles/nope"),
+                "bundle_id must be a UUID, got 'nope'",
+            ),
+        ];
+        for (uri, message) in cases {
+            let request = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                body,
+                serde_json::json!({ "code": INVALID_ID, "message": message }),
+                "{uri}"
+            );
+        }
+    }
+}
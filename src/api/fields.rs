@@ -0,0 +1,269 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Fields a client may request from product list endpoints
+pub const PRODUCT_FIELDS: &[&str] = &[
+    "type",
+    "id",
+    "store_id",
+    "tenant_id",
+    "sku",
+    "name",
+    "description",
+    "price",
+    "currency",
+    "sale_price",
+    "sale_ends_at",
+    "quantity_available",
+    "low_stock_threshold",
+    "image_id",
+    "category_id",
+    "attributes",
+    "return_policy",
+    "return_policy_source",
+    "returns_accepted",
+    "return_window_days",
+    "return_conditions",
+    "pickup_available",
+    "delivery_available",
+    "delivery_fee_override",
+    "delivery_estimated_days",
+    "delivery_options_source",
+    "publish_at",
+    "is_published",
+    "archived_at",
+    "created_at",
+    "updated_at",
+    "effective_price",
+    "price_money",
+    "sale_price_money",
+    "effective_price_money",
+    "discount_percent",
+    "publication_status",
+    "unavailable",
+    "is_active",
+    "components",
+];
+
+/// Fields a client may request from store list endpoints
+pub const STORE_FIELDS: &[&str] = &[
+    "id",
+    "tenant_id",
+    "name",
+    "description",
+    "logo_url",
+    "location",
+    "contact_phone",
+    "contact_email",
+    "contact_whatsapp",
+    "owner_device_id",
+    "is_verified",
+    "rating",
+    "total_products",
+    "default_return_policy",
+    "default_pickup_available",
+    "default_delivery_available",
+    "default_delivery_fee",
+    "default_delivery_estimated_days",
+    "is_paused",
+    "paused_until",
+    "pause_message",
+    "trust_score",
+    "created_at",
+    "updated_at",
+];
+
+/// Sparse field selection from `?fields=a,b,c`, checked against a whitelist
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSelection {
+    fields: Vec<String>,
+}
+
+impl FieldSelection {
+    /// `Ok(None)` when the parameter is absent, meaning "all fields"
+    pub fn parse(raw: Option<&str>, allowed: &[&str]) -> Result<Option<Self>, String> {
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+        let mut fields: Vec<String> = Vec::new();
+        let mut unknown: Vec<&str> = Vec::new();
+        for field in raw.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if !allowed.contains(&field) {
+                unknown.push(field);
+            } else if !fields.iter().any(|f| f == field) {
+                fields.push(field.to_string());
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown field(s): {}. Allowed fields: {}",
+                unknown.join(", "),
+                allowed.join(", ")
+            ));
+        }
+        if fields.is_empty() {
+            return Err("fields must name at least one field".to_string());
+        }
+        Ok(Some(Self { fields }))
+    }
+
+    /// Keep only the selected top-level keys of `value`'s JSON form
+    pub fn project<T: Serialize>(&self, value: &T) -> Value {
+        match serde_json::to_value(value) {
+            Ok(Value::Object(mut object)) => {
+                let projected: Map<String, Value> = self
+                    .fields
+                    .iter()
+                    .filter_map(|f| object.remove_entry(f))
+                    .collect();
+                Value::Object(projected)
+            }
+            Ok(other) => other,
+            Err(_) => Value::Null,
+        }
+    }
+}
+
+/// Serialize `items`, projected when a selection was requested
+pub fn project_all<T: Serialize>(items: &[T], selection: Option<&FieldSelection>) -> Value {
+    match selection {
+        Some(selection) => Value::Array(items.iter().map(|i| selection.project(i)).collect()),
+        None => serde_json::to_value(items).unwrap_or(Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Item {
+        id: u32,
+        name: &'static str,
+        description: Option<&'static str>,
+        price: f64,
+    }
+
+    const ITEM_FIELDS: &[&str] = &["id", "name", "description", "price"];
+
+    #[test]
+    fn test_projection_keeps_only_requested_fields() {
+        let selection = FieldSelection::parse(Some("id, price,id"), ITEM_FIELDS)
+            .unwrap()
+            .unwrap();
+        let item = Item {
+            id: 7,
+            name: "Plantain chips",
+            description: Some("A long description nobody needs on 2G"),
+            price: 500.0,
+        };
+        assert_eq!(selection.project(&item), json!({ "id": 7, "price": 500.0 }));
+        // Null values are still returned when asked for
+        let selection = FieldSelection::parse(Some("description"), ITEM_FIELDS)
+            .unwrap()
+            .unwrap();
+        let item = Item {
+            description: None,
+            ..item
+        };
+        assert_eq!(selection.project(&item), json!({ "description": null }));
+    }
+
+    #[test]
+    fn test_absent_parameter_returns_everything() {
+        assert_eq!(FieldSelection::parse(None, ITEM_FIELDS), Ok(None));
+        let items = [Item {
+            id: 1,
+            name: "Kola",
+            description: None,
+            price: 100.0,
+        }];
+        assert_eq!(
+            project_all(&items, None),
+            json!([{ "id": 1, "name": "Kola", "description": null, "price": 100.0 }])
+        );
+    }
+
+    #[test]
+    fn test_unknown_or_empty_fields_are_rejected() {
+        let err = FieldSelection::parse(Some("id,owner_secret"), ITEM_FIELDS).unwrap_err();
+        assert!(err.contains("owner_secret"), "{err}");
+        assert!(FieldSelection::parse(Some(" , "), ITEM_FIELDS).is_err());
+    }
+
+    #[test]
+    fn test_whitelists_match_serialized_models() {
+        use crate::api::bundles::{BundleResponse, Listing};
+        use crate::api::products::SellerProductResponse;
+        use crate::db::bundles::BundleDetails;
+        use crate::entity::product::Model as ProductModel;
+        use crate::entity::product_bundle::Model as BundleModel;
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let product = ProductModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: "Wax print".to_string(),
+            description: None,
+            price: 5000.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: 3,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let bundle = BundleModel {
+            id: Uuid::new_v4(),
+            store_id: product.store_id,
+            name: "Wax print set".to_string(),
+            description: None,
+            price: 9000.0,
+            is_active: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        // Product listings mix products and bundles; every key of either is selectable
+        let entries = serde_json::to_value([
+            Listing::Product(SellerProductResponse::new(product, Utc::now())),
+            Listing::Bundle(BundleResponse::new(BundleDetails {
+                bundle,
+                items: Vec::new(),
+            })),
+        ])
+        .unwrap();
+        let mut keys: Vec<&str> = entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|entry| entry.as_object().unwrap().keys())
+            .map(|k| k.as_str())
+            .collect();
+        let mut allowed = PRODUCT_FIELDS.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+        allowed.sort_unstable();
+        assert_eq!(keys, allowed);
+    }
+}
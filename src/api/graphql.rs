@@ -0,0 +1,659 @@
+//! Read-only GraphQL view of the catalogue for the buyer app, built with the
+//! `graphql` feature.
+//!
+//! Nested fields resolve through one per-request [`DataLoader`], so a page of
+//! products with their stores and media costs one query per level rather
+//! than one per row. Mutations stay on the REST endpoints.
+
+use crate::api::media_storage::{BreakerState, S3_BREAKER};
+use crate::api::products::ProductMediaResponse;
+use crate::api::stores::mask_contact;
+use crate::db::product_media::ProductMedia;
+use crate::db::products::{discount_percent, effective_price, Product};
+use crate::db::stores::{is_paused, Store};
+use crate::entity::product::Model as ProductModel;
+use crate::entity::product_media::Model as ProductMediaModel;
+use crate::entity::store::Model as StoreModel;
+use crate::tenant::tenant_from_headers;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Deepest selection a query may nest
+const MAX_DEPTH: usize = 6;
+/// Upper bound on the number of fields a query may resolve
+const MAX_COMPLEXITY: usize = 500;
+/// Most products a single `products(ids:)` lookup may ask for
+const MAX_IDS: usize = 100;
+
+pub type CatalogSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Batched reads behind the schema; each call is one database query
+#[async_trait]
+pub trait CatalogSource: Send + Sync + 'static {
+    async fn products(&self, ids: &[Uuid]) -> Result<Vec<ProductModel>, String>;
+    async fn store_products(&self, store_ids: &[Uuid]) -> Result<Vec<ProductModel>, String>;
+    async fn stores(&self, ids: &[Uuid]) -> Result<Vec<StoreModel>, String>;
+    async fn media(&self, product_ids: &[Uuid]) -> Result<Vec<ProductMediaModel>, String>;
+}
+
+pub struct DbCatalog {
+    pub db: DatabaseConnection,
+    /// Products and stores of other tenants resolve as missing
+    pub tenant_id: String,
+}
+
+#[async_trait]
+impl CatalogSource for DbCatalog {
+    async fn products(&self, ids: &[Uuid]) -> Result<Vec<ProductModel>, String> {
+        Product::get_visible_many(&self.db, &self.tenant_id, ids).await
+    }
+
+    async fn store_products(&self, store_ids: &[Uuid]) -> Result<Vec<ProductModel>, String> {
+        Product::list_visible_by_stores(&self.db, &self.tenant_id, store_ids).await
+    }
+
+    async fn stores(&self, ids: &[Uuid]) -> Result<Vec<StoreModel>, String> {
+        Store::get_many(&self.db, &self.tenant_id, ids).await
+    }
+
+    async fn media(&self, product_ids: &[Uuid]) -> Result<Vec<ProductMediaModel>, String> {
+        ProductMedia::list_by_products(&self.db, product_ids).await
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ProductKey(Uuid);
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct StoreKey(Uuid);
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct StoreProductsKey(Uuid);
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct MediaKey(Uuid);
+
+pub struct CatalogLoader {
+    source: Arc<dyn CatalogSource>,
+}
+
+fn group_by<K, V>(items: Vec<V>, key: impl Fn(&V) -> K) -> HashMap<K, Vec<V>>
+where
+    K: std::hash::Hash + Eq,
+{
+    let mut grouped: HashMap<K, Vec<V>> = HashMap::new();
+    for item in items {
+        grouped.entry(key(&item)).or_default().push(item);
+    }
+    grouped
+}
+
+fn raw_ids<K: Copy>(keys: &[K], id: impl Fn(K) -> Uuid) -> Vec<Uuid> {
+    keys.iter().map(|k| id(*k)).collect()
+}
+
+impl Loader<ProductKey> for CatalogLoader {
+    type Value = ProductModel;
+    type Error = String;
+
+    async fn load(&self, keys: &[ProductKey]) -> Result<HashMap<ProductKey, ProductModel>, String> {
+        let products = self.source.products(&raw_ids(keys, |k| k.0)).await?;
+        Ok(products
+            .into_iter()
+            .map(|p| (ProductKey(p.id), p))
+            .collect())
+    }
+}
+
+impl Loader<StoreKey> for CatalogLoader {
+    type Value = StoreModel;
+    type Error = String;
+
+    async fn load(&self, keys: &[StoreKey]) -> Result<HashMap<StoreKey, StoreModel>, String> {
+        let stores = self.source.stores(&raw_ids(keys, |k| k.0)).await?;
+        Ok(stores.into_iter().map(|s| (StoreKey(s.id), s)).collect())
+    }
+}
+
+impl Loader<StoreProductsKey> for CatalogLoader {
+    type Value = Vec<ProductModel>;
+    type Error = String;
+
+    async fn load(
+        &self,
+        keys: &[StoreProductsKey],
+    ) -> Result<HashMap<StoreProductsKey, Vec<ProductModel>>, String> {
+        let products = self.source.store_products(&raw_ids(keys, |k| k.0)).await?;
+        Ok(group_by(products, |p| StoreProductsKey(p.store_id)))
+    }
+}
+
+impl Loader<MediaKey> for CatalogLoader {
+    type Value = Vec<ProductMediaModel>;
+    type Error = String;
+
+    async fn load(
+        &self,
+        keys: &[MediaKey],
+    ) -> Result<HashMap<MediaKey, Vec<ProductMediaModel>>, String> {
+        let media = self.source.media(&raw_ids(keys, |k| k.0)).await?;
+        Ok(group_by(media, |m| MediaKey(m.product_id)))
+    }
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<CatalogLoader> {
+    ctx.data_unchecked::<DataLoader<CatalogLoader>>()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A publicly visible product
+    async fn product(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> async_graphql::Result<Option<GqlProduct>> {
+        Ok(loader(ctx).load_one(ProductKey(id)).await?.map(GqlProduct))
+    }
+
+    /// Several products at once, in the order asked for; unknown ids are skipped
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<Uuid>,
+    ) -> async_graphql::Result<Vec<GqlProduct>> {
+        if ids.len() > MAX_IDS {
+            return Err(format!("At most {MAX_IDS} ids per query").into());
+        }
+        let mut found = loader(ctx)
+            .load_many(ids.iter().copied().map(ProductKey))
+            .await?;
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| found.remove(&ProductKey(id)).map(GqlProduct))
+            .collect())
+    }
+
+    async fn store(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<GqlStore>> {
+        Ok(loader(ctx).load_one(StoreKey(id)).await?.map(GqlStore))
+    }
+}
+
+pub struct GqlProduct(ProductModel);
+
+#[Object(name = "Product")]
+impl GqlProduct {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn store_id(&self) -> Uuid {
+        self.0.store_id
+    }
+
+    async fn sku(&self) -> Option<&str> {
+        self.0.sku.as_deref()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn price(&self) -> f64 {
+        self.0.price
+    }
+
+    /// ISO 4217 code of the prices
+    async fn currency(&self) -> &str {
+        &self.0.currency
+    }
+
+    /// `salePrice` while a sale is running, otherwise `price`
+    async fn effective_price(&self) -> f64 {
+        effective_price(&self.0, Utc::now())
+    }
+
+    async fn discount_percent(&self) -> Option<i32> {
+        discount_percent(&self.0, Utc::now())
+    }
+
+    async fn quantity_available(&self) -> i32 {
+        self.0.quantity_available
+    }
+
+    async fn category_id(&self) -> Option<Uuid> {
+        self.0.category_id
+    }
+
+    async fn returns_accepted(&self) -> bool {
+        self.0.returns_accepted
+    }
+
+    async fn return_window_days(&self) -> Option<i32> {
+        self.0.return_window_days
+    }
+
+    async fn return_conditions(&self) -> Option<&str> {
+        self.0.return_conditions.as_deref()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn store(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<GqlStore>> {
+        Ok(loader(ctx)
+            .load_one(StoreKey(self.0.store_id))
+            .await?
+            .map(GqlStore))
+    }
+
+    /// True while the store is paused: viewable, but not orderable
+    async fn unavailable(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        let store = loader(ctx).load_one(StoreKey(self.0.store_id)).await?;
+        Ok(store.is_some_and(|store| is_paused(&store, Utc::now())))
+    }
+
+    async fn media(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<GqlMedia>> {
+        let media = loader(ctx)
+            .load_one(MediaKey(self.0.id))
+            .await?
+            .unwrap_or_default();
+        let degraded = S3_BREAKER.state(Instant::now()) == BreakerState::Open;
+        Ok(media
+            .into_iter()
+            .map(|m| {
+                GqlMedia(if degraded {
+                    ProductMediaResponse::degraded(m)
+                } else {
+                    ProductMediaResponse::from(m)
+                })
+            })
+            .collect())
+    }
+}
+
+pub struct GqlStore(StoreModel);
+
+#[Object(name = "Store")]
+impl GqlStore {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn logo_url(&self) -> Option<&str> {
+        self.0.logo_url.as_deref()
+    }
+
+    async fn location(&self) -> Option<&str> {
+        self.0.location.as_deref()
+    }
+
+    /// Masked when the seller keeps the number private
+    async fn contact_whatsapp(&self) -> Option<String> {
+        let number = self.0.contact_whatsapp.as_deref()?;
+        Some(if self.0.show_whatsapp {
+            number.to_string()
+        } else {
+            mask_contact(number)
+        })
+    }
+
+    async fn is_verified(&self) -> bool {
+        self.0.is_verified
+    }
+
+    async fn rating(&self) -> Option<f32> {
+        self.0.rating
+    }
+
+    async fn store_rating(&self) -> Option<f32> {
+        self.0.store_rating
+    }
+
+    async fn store_review_count(&self) -> i32 {
+        self.0.store_review_count
+    }
+
+    async fn is_paused(&self) -> bool {
+        is_paused(&self.0, Utc::now())
+    }
+
+    async fn pause_message(&self) -> Option<&str> {
+        self.0.pause_message.as_deref()
+    }
+
+    async fn trust_score(&self) -> Option<f64> {
+        self.0.trust_score
+    }
+
+    /// The store's publicly visible products, newest first
+    async fn products(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<GqlProduct>> {
+        Ok(loader(ctx)
+            .load_one(StoreProductsKey(self.0.id))
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(GqlProduct)
+            .collect())
+    }
+}
+
+pub struct GqlMedia(ProductMediaResponse);
+
+#[Object(name = "Media")]
+impl GqlMedia {
+    async fn image_id(&self) -> Uuid {
+        self.0.image_id
+    }
+
+    /// Null while media storage is unavailable
+    async fn url(&self) -> Option<&str> {
+        self.0.url.as_deref()
+    }
+
+    async fn original_url(&self) -> Option<&str> {
+        self.0.original_url.as_deref()
+    }
+
+    async fn content_type(&self) -> &str {
+        &self.0.content_type
+    }
+
+    async fn size_bytes(&self) -> i64 {
+        self.0.size_bytes
+    }
+
+    async fn content_hash(&self) -> Option<&str> {
+        self.0.content_hash.as_deref()
+    }
+
+    async fn storage_degraded(&self) -> bool {
+        self.0.storage_degraded
+    }
+}
+
+/// The schema is static; per-request state travels as request data
+pub fn schema() -> &'static CatalogSchema {
+    static SCHEMA: OnceLock<CatalogSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .limit_depth(MAX_DEPTH)
+            .limit_complexity(MAX_COMPLEXITY)
+            .finish()
+    })
+}
+
+/// Run a query with a fresh loader, so batching and caching are per request
+pub async fn execute(
+    source: Arc<dyn CatalogSource>,
+    request: async_graphql::Request,
+) -> async_graphql::Response {
+    let loader = DataLoader::new(CatalogLoader { source }, tokio::spawn);
+    schema().execute(request.data(loader)).await
+}
+
+/// Read-only GraphQL endpoint over products, stores and media
+pub async fn graphql_endpoint(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Response {
+    let tenant_id = match tenant_from_headers(&headers) {
+        Ok(tenant_id) => tenant_id,
+        Err(err) => return err.into_response(),
+    };
+    Json(execute(Arc::new(DbCatalog { db, tenant_id }), request).await).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// In-memory catalogue that counts how many batched reads it served
+    #[derive(Default)]
+    struct CountingCatalog {
+        products: Vec<ProductModel>,
+        stores: Vec<StoreModel>,
+        media: Vec<ProductMediaModel>,
+        calls: AtomicUsize,
+    }
+
+    impl CountingCatalog {
+        fn hit(&self) {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl CatalogSource for CountingCatalog {
+        async fn products(&self, ids: &[Uuid]) -> Result<Vec<ProductModel>, String> {
+            self.hit();
+            Ok(self
+                .products
+                .iter()
+                .filter(|p| ids.contains(&p.id))
+                .cloned()
+                .collect())
+        }
+
+        async fn store_products(&self, store_ids: &[Uuid]) -> Result<Vec<ProductModel>, String> {
+            self.hit();
+            Ok(self
+                .products
+                .iter()
+                .filter(|p| store_ids.contains(&p.store_id))
+                .cloned()
+                .collect())
+        }
+
+        async fn stores(&self, ids: &[Uuid]) -> Result<Vec<StoreModel>, String> {
+            self.hit();
+            Ok(self
+                .stores
+                .iter()
+                .filter(|s| ids.contains(&s.id))
+                .cloned()
+                .collect())
+        }
+
+        async fn media(&self, product_ids: &[Uuid]) -> Result<Vec<ProductMediaModel>, String> {
+            self.hit();
+            Ok(self
+                .media
+                .iter()
+                .filter(|m| product_ids.contains(&m.product_id))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn store(name: &str) -> StoreModel {
+        let now = Utc::now();
+        StoreModel {
+            id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            name: name.to_string(),
+            description: None,
+            logo_url: None,
+            location: None,
+            contact_phone: None,
+            contact_email: None,
+            contact_whatsapp: None,
+            show_phone: true,
+            show_whatsapp: true,
+            show_email: true,
+            embed_enabled: false,
+            media_bytes_used: 0,
+            media_uploads_today: 0,
+            media_uploads_day: None,
+            media_quota_bytes: None,
+            media_daily_uploads: None,
+            media_images_per_product: None,
+            owner_device_id: None,
+            is_verified: false,
+            rating: None,
+            store_rating: None,
+            store_review_count: 0,
+            total_products: 0,
+            default_return_policy: None,
+            default_pickup_available: true,
+            default_delivery_available: false,
+            default_delivery_fee: None,
+            default_delivery_estimated_days: None,
+            is_paused: false,
+            paused_until: None,
+            pause_message: None,
+            auto_response_enabled: false,
+            auto_response_message: None,
+            auto_response_trigger: None,
+            trust_score: None,
+            first_shared_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn product(store_id: Uuid, name: &str) -> ProductModel {
+        let now = Utc::now();
+        ProductModel {
+            id: Uuid::new_v4(),
+            store_id,
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: name.to_string(),
+            description: None,
+            price: 2500.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: 4,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn media(product_id: Uuid) -> ProductMediaModel {
+        let id = Uuid::new_v4();
+        ProductMediaModel {
+            id,
+            product_id,
+            s3_key: format!("products/{product_id}/media/{id}.jpg"),
+            content_type: "image/jpeg".to_string(),
+            size_bytes: 1024,
+            webp_s3_key: None,
+            webp_size_bytes: None,
+            content_hash: None,
+            perceptual_hash: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn catalog(stores: usize, products_per_store: usize) -> CountingCatalog {
+        let mut catalog = CountingCatalog::default();
+        for s in 0..stores {
+            let store = store(&format!("Store {s}"));
+            for p in 0..products_per_store {
+                let product = product(store.id, &format!("Item {s}-{p}"));
+                catalog.media.push(media(product.id));
+                catalog.products.push(product);
+            }
+            catalog.stores.push(store);
+        }
+        catalog
+    }
+
+    #[tokio::test]
+    async fn test_nested_query_batches_each_level() {
+        let catalog = Arc::new(catalog(3, 4));
+        let ids: Vec<String> = catalog
+            .products
+            .iter()
+            .map(|p| format!("\"{}\"", p.id))
+            .collect();
+        let query = format!(
+            "{{ products(ids: [{}]) {{ name media {{ url }} store {{ name products {{ name }} }} }} }}",
+            ids.join(", ")
+        );
+
+        let response = execute(catalog.clone(), async_graphql::Request::new(query)).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let products = data["products"].as_array().unwrap();
+        assert_eq!(products.len(), 12);
+        assert_eq!(
+            products[0]["store"]["products"].as_array().unwrap().len(),
+            4
+        );
+        assert_eq!(products[0]["media"].as_array().unwrap().len(), 1);
+
+        // products, stores, media and store products: one batch each, not one per row
+        assert_eq!(catalog.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_query_depth_is_limited() {
+        let catalog = Arc::new(catalog(1, 1));
+        let id = catalog.products[0].id;
+        let nested = "store { products { store { products { store { products { name } } } } } }";
+        let query = format!("{{ product(id: \"{id}\") {{ {nested} }} }}");
+
+        let response = execute(catalog.clone(), async_graphql::Request::new(query)).await;
+        assert!(!response.errors.is_empty());
+        assert_eq!(catalog.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_mutations_are_not_exposed() {
+        let catalog = Arc::new(catalog(1, 1));
+        let response = execute(
+            catalog,
+            async_graphql::Request::new("mutation { deleteProduct(id: \"x\") }"),
+        )
+        .await;
+        assert!(!response.errors.is_empty());
+    }
+}
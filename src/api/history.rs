@@ -0,0 +1,123 @@
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::pagination::{HistoryPage, PageParams, PageRequest};
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::db::history::{history_page, HistoryCursor, HistoryScope};
+use crate::db::products::Product;
+use crate::db::stores::Store;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+/// Who changed what on a product
+#[utoipa::path(
+    get,
+    operation_id = "getProductHistory",
+    path = "/api/v1/products/{id}/history",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Edits and price changes with the fields they changed", body = HistoryPage),
+        (status = 400, description = "Invalid cursor, or both page and cursor sent"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Product not found")
+    )
+)]
+pub async fn product_history(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    page: PageRequest,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    if let Err(err) = check_access(&db, &headers, product.store_id).await {
+        return err.into_response();
+    }
+    history_response(&db, HistoryScope::Product(id), &page)
+        .await
+        .into_response()
+}
+
+/// Who changed what on a store and its products
+#[utoipa::path(
+    get,
+    operation_id = "getStoreHistory",
+    path = "/api/v1/stores/{id}/history",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Edits of the store and its products, with the fields they changed", body = HistoryPage),
+        (status = 400, description = "Invalid cursor, or both page and cursor sent"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn store_history(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    page: PageRequest,
+) -> impl IntoResponse {
+    if let Err(err) = check_access(&db, &headers, id).await {
+        return err.into_response();
+    }
+    history_response(&db, HistoryScope::Store(id), &page)
+        .await
+        .into_response()
+}
+
+/// Admins see any store's history, owners only their own
+async fn check_access(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+    store_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
+    if require_admin(headers).is_ok() {
+        Store::get(db, store_id)
+            .await
+            .map(|_| ())
+            .map_err(|err| (StatusCode::NOT_FOUND, err))
+    } else {
+        owned_store(db, headers, store_id, ApiScope::ProductsRead)
+            .await
+            .map(|_| ())
+    }
+}
+
+async fn history_response(
+    db: &DatabaseConnection,
+    scope: HistoryScope,
+    page: &PageRequest,
+) -> Result<Json<HistoryPage>, (StatusCode, String)> {
+    let cursor = page
+        .cursor
+        .as_deref()
+        .map(HistoryCursor::decode)
+        .transpose()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    let history = history_page(db, scope, cursor, page.per_page)
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    Ok(Json(page.by_cursor(
+        history.entries,
+        history.total,
+        history.next_cursor.map(|c| c.encode()),
+    )))
+}
@@ -1,4 +1,4 @@
-use axum::extract::Multipart;
+use crate::api::multipart::UploadedFile;
 use serde::{Deserialize, Serialize};
 
 /// Image analysis result
@@ -36,53 +36,23 @@ impl ImageAnalysisService {
         }
     }
 
-    #[allow(dead_code)]
-    pub async fn analyze_image(
-        &self,
-        multipart: &mut Multipart,
-    ) -> Result<ImageAnalysisResult, String> {
-        let mut file_data = Vec::new();
-        let mut content_type = String::new();
-        let mut file_name = None;
-
-        // Extract file from multipart
-        while let Some(mut field) = multipart
-            .next_field()
-            .await
-            .map_err(|e| format!("Failed to read multipart field: {e}"))?
-        {
-            if field.name() == Some("file") {
-                content_type = field
-                    .content_type()
-                    .unwrap_or("application/octet-stream")
-                    .to_string();
-
-                file_name = field.file_name().map(|s| s.to_string());
-
-                while let Some(chunk) = field
-                    .chunk()
-                    .await
-                    .map_err(|e| format!("Failed to read chunk: {e}"))?
-                {
-                    file_data.extend_from_slice(&chunk);
-                }
-                break;
-            }
-        }
-
-        if file_data.is_empty() {
-            return Ok(ImageAnalysisResult {
-                is_valid: false,
-                file_type: None,
-                file_size: 0,
-                width: None,
-                height: None,
-                violations: vec!["No file data found".to_string()],
-                file_name: None,
-                file_data: None,
-            });
-        }
+    /// Check a file read from an upload; see `crate::api::multipart`
+    pub async fn analyze_file(&self, file: &UploadedFile) -> ImageAnalysisResult {
+        self.analyze_bytes(
+            file.bytes.to_vec(),
+            file.content_type.clone(),
+            Some(file.name.clone()),
+        )
+        .await
+    }
 
+    /// Check an image already in memory, e.g. one fetched for a product import
+    pub async fn analyze_bytes(
+        &self,
+        file_data: Vec<u8>,
+        content_type: String,
+        file_name: Option<String>,
+    ) -> ImageAnalysisResult {
         let file_size = file_data.len() as u64;
         let mut violations = Vec::new();
 
@@ -121,7 +91,7 @@ impl ImageAnalysisService {
             }
         }
 
-        Ok(ImageAnalysisResult {
+        ImageAnalysisResult {
             is_valid: violations.is_empty(),
             file_type: Some(content_type),
             file_size,
@@ -130,7 +100,7 @@ impl ImageAnalysisService {
             violations,
             file_name,
             file_data: Some(file_data),
-        })
+        }
     }
 
     fn validate_image_format(&self, data: &[u8]) -> bool {
@@ -193,12 +163,9 @@ pub struct StubImageAnalysisService;
 
 impl StubImageAnalysisService {
     #[allow(dead_code)]
-    pub async fn analyze_image(
-        &self,
-        _multipart: &mut Multipart,
-    ) -> Result<ImageAnalysisResult, String> {
+    pub async fn analyze_file(&self, _file: &UploadedFile) -> ImageAnalysisResult {
         // Always return valid for stub implementation
-        Ok(ImageAnalysisResult {
+        ImageAnalysisResult {
             is_valid: true,
             file_type: Some("image/jpeg".to_string()),
             file_size: 1024,
@@ -207,6 +174,6 @@ impl StubImageAnalysisService {
             violations: vec![],
             file_name: Some("stub_image.jpg".to_string()),
             file_data: Some(vec![0; 1024]), // Dummy data
-        })
+        }
     }
 }
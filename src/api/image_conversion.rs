@@ -0,0 +1,271 @@
+use crate::api::media_storage::MediaStorage;
+use crate::db::product_media::WebpVariant;
+use crate::metrics;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use std::io::Cursor;
+use uuid::Uuid;
+
+/// Default WebP quality used when `WEBP_QUALITY` is not set
+const DEFAULT_WEBP_QUALITY: f32 = 80.0;
+
+/// Reason an upload was stored without a WebP variant
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    UnsupportedFormat,
+    Animated,
+    NotSmaller,
+    Failed(String),
+}
+
+/// Outcome of a WebP conversion attempt
+#[derive(Debug)]
+pub enum WebpConversion {
+    Converted(Vec<u8>),
+    Skipped(SkipReason),
+}
+
+/// Re-encodes JPEG/PNG (and single-frame GIF) uploads to lossy WebP
+#[derive(Debug, Clone)]
+pub struct WebpConverter {
+    quality: f32,
+}
+
+impl WebpConverter {
+    pub fn new(quality: f32) -> Self {
+        Self {
+            quality: quality.clamp(0.0, 100.0),
+        }
+    }
+
+    /// Convert on the blocking thread pool so encoding never stalls the runtime
+    pub async fn convert_async(&self, data: Vec<u8>) -> WebpConversion {
+        let converter = self.clone();
+        match tokio::task::spawn_blocking(move || converter.convert(&data)).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                WebpConversion::Skipped(SkipReason::Failed(format!("Conversion task failed: {e}")))
+            }
+        }
+    }
+
+    /// Convert image bytes to WebP (blocking)
+    pub fn convert(&self, data: &[u8]) -> WebpConversion {
+        let format = match image::guess_format(data) {
+            Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif)) => format,
+            _ => return WebpConversion::Skipped(SkipReason::UnsupportedFormat),
+        };
+
+        if format == ImageFormat::Gif && is_animated_gif(data) {
+            return WebpConversion::Skipped(SkipReason::Animated);
+        }
+
+        let decoded = match image::load_from_memory_with_format(data, format) {
+            Ok(img) => img,
+            Err(e) => {
+                return WebpConversion::Skipped(SkipReason::Failed(format!(
+                    "Failed to decode image: {e}"
+                )))
+            }
+        };
+
+        // The encoder only accepts 8-bit RGB/RGBA buffers
+        let normalized = if decoded.color().has_alpha() {
+            DynamicImage::ImageRgba8(decoded.to_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(decoded.to_rgb8())
+        };
+
+        let encoded = match webp::Encoder::from_image(&normalized) {
+            Ok(encoder) => encoder.encode(self.quality).to_vec(),
+            Err(e) => {
+                return WebpConversion::Skipped(SkipReason::Failed(format!(
+                    "Failed to encode WebP: {e}"
+                )))
+            }
+        };
+
+        if !is_worth_keeping(data.len(), encoded.len()) {
+            return WebpConversion::Skipped(SkipReason::NotSmaller);
+        }
+
+        WebpConversion::Converted(encoded)
+    }
+}
+
+impl Default for WebpConverter {
+    fn default() -> Self {
+        Self::new(DEFAULT_WEBP_QUALITY)
+    }
+}
+
+/// A variant is only worth storing when it is strictly smaller than the original
+fn is_worth_keeping(original_len: usize, converted_len: usize) -> bool {
+    converted_len < original_len
+}
+
+fn is_animated_gif(data: &[u8]) -> bool {
+    match GifDecoder::new(Cursor::new(data)) {
+        Ok(decoder) => decoder.into_frames().take(2).count() > 1,
+        // Undecodable GIFs are treated as animated so we never attempt a conversion
+        Err(_) => true,
+    }
+}
+
+/// Convert an upload and store its WebP variant next to the original.
+///
+/// Conversion problems never fail the upload: the original is always kept and
+/// the variant is simply omitted.
+pub async fn store_webp_variant<S: MediaStorage + Sync>(
+    storage: &S,
+    converter: &WebpConverter,
+    product_id: Uuid,
+    image_id: Uuid,
+    file_name: &str,
+    data: &[u8],
+) -> Option<WebpVariant> {
+    let webp_data = match converter.convert_async(data.to_vec()).await {
+        WebpConversion::Converted(bytes) => bytes,
+        WebpConversion::Skipped(reason) => {
+            tracing::debug!(image_id = %image_id, reason = ?reason, "Skipping WebP variant");
+            metrics::WEBP_CONVERSIONS_SKIPPED.inc();
+            return None;
+        }
+    };
+
+    let stem = file_name.split('.').next().unwrap_or("image");
+    let webp_name = format!("{stem}.webp");
+    match storage
+        .upload_media_data(
+            product_id,
+            &webp_name,
+            &webp_data,
+            "image/webp",
+            Some(image_id),
+        )
+        .await
+    {
+        Ok(s3_key) => {
+            let saved = data.len().saturating_sub(webp_data.len()) as u64;
+            metrics::WEBP_CONVERSIONS.inc();
+            metrics::WEBP_BYTES_SAVED.inc_by(saved);
+            tracing::info!(
+                image_id = %image_id,
+                original_bytes = data.len(),
+                webp_bytes = webp_data.len(),
+                "Stored WebP variant"
+            );
+            Some(WebpVariant {
+                s3_key,
+                size_bytes: webp_data.len() as i64,
+            })
+        }
+        Err(e) => {
+            tracing::warn!(image_id = %image_id, error = %e, "Failed to upload WebP variant");
+            metrics::WEBP_CONVERSIONS_SKIPPED.inc();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Frame, Rgb, RgbImage, RgbaImage};
+
+    /// Photo-like fixture: a gradient with deterministic noise so lossless
+    /// formats can't compress it away.
+    fn fixture(width: u32, height: u32) -> RgbImage {
+        let mut seed: u32 = 0x1234_5678;
+        RgbImage::from_fn(width, height, |x, y| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let noise = (seed >> 24) as u8 / 4;
+            Rgb([
+                (x * 255 / width) as u8 ^ noise,
+                (y * 255 / height) as u8 ^ noise,
+                ((x + y) % 256) as u8 ^ noise,
+            ])
+        })
+    }
+
+    fn encode(img: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut buf = Vec::new();
+        DynamicImage::ImageRgb8(img.clone())
+            .write_to(&mut Cursor::new(&mut buf), format)
+            .unwrap();
+        buf
+    }
+
+    fn assert_webp(outcome: WebpConversion) {
+        match outcome {
+            WebpConversion::Converted(bytes) => {
+                assert_eq!(&bytes[0..4], b"RIFF");
+                assert_eq!(&bytes[8..12], b"WEBP");
+            }
+            WebpConversion::Skipped(reason) => panic!("expected conversion, got {reason:?}"),
+        }
+    }
+
+    #[test]
+    fn converts_png() {
+        let png = encode(&fixture(128, 128), ImageFormat::Png);
+        assert_webp(WebpConverter::default().convert(&png));
+    }
+
+    #[test]
+    fn converts_jpeg() {
+        let jpeg = encode(&fixture(128, 128), ImageFormat::Jpeg);
+        assert_webp(WebpConverter::new(60.0).convert(&jpeg));
+    }
+
+    #[test]
+    fn converts_single_frame_gif() {
+        let gif = encode(&fixture(128, 128), ImageFormat::Gif);
+        assert_webp(WebpConverter::default().convert(&gif));
+    }
+
+    #[test]
+    fn skips_animated_gif() {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut buf);
+            encoder
+                .encode_frames(vec![
+                    Frame::new(RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]))),
+                    Frame::new(RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 255, 255]))),
+                ])
+                .unwrap();
+        }
+        match WebpConverter::default().convert(&buf) {
+            WebpConversion::Skipped(reason) => assert_eq!(reason, SkipReason::Animated),
+            WebpConversion::Converted(_) => panic!("animated GIF must not be converted"),
+        }
+    }
+
+    #[test]
+    fn skips_unsupported_formats() {
+        let png = encode(&fixture(128, 128), ImageFormat::Png);
+        let webp = match WebpConverter::default().convert(&png) {
+            WebpConversion::Converted(bytes) => bytes,
+            WebpConversion::Skipped(reason) => panic!("unexpected skip: {reason:?}"),
+        };
+        match WebpConverter::default().convert(&webp) {
+            WebpConversion::Skipped(reason) => assert_eq!(reason, SkipReason::UnsupportedFormat),
+            WebpConversion::Converted(_) => panic!("WebP input must not be re-encoded"),
+        }
+    }
+
+    #[test]
+    fn keeps_variant_only_when_smaller() {
+        assert!(is_worth_keeping(1000, 999));
+        assert!(!is_worth_keeping(1000, 1000));
+        assert!(!is_worth_keeping(1000, 1200));
+    }
+
+    #[test]
+    fn clamps_quality() {
+        assert_eq!(WebpConverter::new(150.0).quality, 100.0);
+        assert_eq!(WebpConverter::new(-3.0).quality, 0.0);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::api::image_analysis::ImageAnalysisService;
+use crate::api::products::CreateProductRequest;
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::import::extract::Extracted;
+use crate::import::fetch::{self, FetchError, MAX_IMAGE_BYTES};
+use crate::import::{import_page, ImportError};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest product name the draft carries; see `api::validation`
+const MAX_NAME_LEN: usize = 255;
+/// The only currency listings are priced in
+const LISTING_CURRENCY: &str = "XAF";
+
+#[derive(Deserialize, ToSchema)]
+pub struct ImportFromUrlRequest {
+    /// A shared WhatsApp catalog, Instagram or shop link
+    pub url: String,
+    /// Store the product will be listed in
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+}
+
+/// The page's image, checked like an upload but not stored
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportedImage {
+    pub url: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportDraftResponse {
+    /// Prefilled product for the seller to review and submit to
+    /// `POST /products`; it is saved as a draft
+    pub draft: CreateProductRequest,
+    /// Upload it to the product once created
+    pub image: Option<ImportedImage>,
+    /// Page the fields were read from, after redirects
+    pub source_url: String,
+    /// Site strategy that read the page
+    pub strategy: String,
+    /// Fields that could not be filled in, and why
+    pub warnings: Vec<String>,
+}
+
+/// Build the draft, dropping a price stated in another currency
+fn draft(store_id: Uuid, extracted: &mut Extracted) -> CreateProductRequest {
+    let name: String = extracted
+        .title
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_NAME_LEN)
+        .collect();
+    let mut price = extracted.price;
+    if let Some(currency) = extracted
+        .currency
+        .as_deref()
+        .filter(|currency| !currency.eq_ignore_ascii_case(LISTING_CURRENCY))
+    {
+        extracted.warnings.push(format!(
+            "The page's price is in {currency}; enter the price in {LISTING_CURRENCY}"
+        ));
+        price = None;
+    }
+    CreateProductRequest {
+        store_id: Some(store_id),
+        sku: None,
+        name,
+        description: extracted.description.clone(),
+        image_id: None,
+        category_id: None,
+        attributes: None,
+        price: price.unwrap_or_default(),
+        currency: None,
+        quantity_available: 1,
+        low_stock_threshold: None,
+        return_policy: None,
+        returns_accepted: None,
+        return_window_days: None,
+        return_conditions: None,
+        delivery_options: None,
+        publish_at: None,
+        draft: true,
+        sale_price: None,
+        sale_ends_at: None,
+    }
+}
+
+/// Fetch the page's image and put it through the upload checks
+async fn fetch_image(image_url: &str) -> Result<ImportedImage, String> {
+    let image = fetch::fetch(image_url, MAX_IMAGE_BYTES)
+        .await
+        .map_err(|e| format!("The image could not be downloaded: {e}"))?;
+    let content_type = image.content_type.unwrap_or_default();
+    let file_name = image
+        .url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(str::to_owned);
+    let analysis = ImageAnalysisService::new()
+        .analyze_bytes(image.body, content_type, file_name)
+        .await;
+    if !analysis.is_valid {
+        return Err(format!(
+            "The image can't be used: {}",
+            analysis.violations.join("; ")
+        ));
+    }
+    Ok(ImportedImage {
+        url: image.url.to_string(),
+        content_type: analysis.file_type.unwrap_or_default(),
+        size_bytes: analysis.file_size,
+    })
+}
+
+/// Prefill a product from a shared link. Nothing is saved; fields the page
+/// doesn't give are left empty and listed in `warnings`.
+#[utoipa::path(
+    post,
+    operation_id = "importProductFromUrl",
+    path = "/api/v1/products/import-from-url",
+    tag = "Products",
+    request_body = ImportFromUrlRequest,
+    responses(
+        (status = 200, description = "Draft to confirm, possibly partial", body = ImportDraftResponse),
+        (status = 400, description = "Invalid URL, or one that points at a private or reserved address"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found"),
+        (status = 422, description = "The link is not a web page"),
+        (status = 502, description = "The page could not be fetched")
+    )
+)]
+pub async fn import_product_from_url(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<ImportFromUrlRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, request.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let mut page = match import_page(&request.url).await {
+        Ok(page) => page,
+        Err(ImportError::Fetch(err @ (FetchError::InvalidUrl(_) | FetchError::Blocked(_)))) => {
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+        }
+        Err(ImportError::Fetch(err)) => {
+            return (StatusCode::BAD_GATEWAY, err.to_string()).into_response()
+        }
+        Err(ImportError::NotHtml(content_type)) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("The link leads to {content_type}, not a web page"),
+            )
+                .into_response()
+        }
+    };
+    let image = match page.extracted.image_url.as_deref() {
+        Some(image_url) => match fetch_image(image_url).await {
+            Ok(image) => Some(image),
+            Err(warning) => {
+                page.extracted.warnings.push(warning);
+                None
+            }
+        },
+        None => None,
+    };
+    let draft = draft(request.store_id, &mut page.extracted);
+    Json(ImportDraftResponse {
+        draft,
+        image,
+        source_url: page.url.to_string(),
+        strategy: page.strategy.to_string(),
+        warnings: page.extracted.warnings,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draft_keeps_only_listing_currency_prices() {
+        let mut extracted = Extracted {
+            title: Some("Robe".repeat(100)),
+            price: Some(18500.0),
+            currency: Some("xaf".to_string()),
+            ..Extracted::default()
+        };
+        let store_id = Uuid::new_v4();
+        let prefilled = draft(store_id, &mut extracted);
+        assert_eq!(prefilled.name.chars().count(), MAX_NAME_LEN);
+        assert_eq!(prefilled.price, 18500.0);
+        assert!(prefilled.draft);
+        assert!(extracted.warnings.is_empty());
+
+        let mut foreign = Extracted {
+            price: Some(30.0),
+            currency: Some("USD".to_string()),
+            ..Extracted::default()
+        };
+        assert_eq!(draft(store_id, &mut foreign).price, 0.0);
+        assert_eq!(foreign.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,586 @@
+//! Stock and price uploads from a seller's point-of-sale system.
+//!
+//! The body is either a JSON array of `{sku, quantity, price?}` or CSV with a
+//! `sku,quantity[,price]` header. Rows are matched to the store's products by
+//! SKU and applied in one transaction; a `sync_id` makes retries safe.
+
+use crate::api::extract::UuidPath;
+use crate::api::products::low_stock_event;
+use crate::api::stores::owned_store;
+use crate::auth::{claims_from_headers, ApiScope};
+use crate::db::inventory_sync::{DbInventoryLedger, InventoryLedger, InventoryUpdate, SkuOutcome};
+use crate::db::snapshots::{SnapshotReason, StoreSnapshot};
+use crate::entity::product::Model as ProductModel;
+use crate::events::{create_event, Event, EventDispatcher, EventType};
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Most rows a single upload may carry
+pub const MAX_SYNC_ROWS: usize = 1000;
+const MAX_SYNC_ID_LEN: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct InventorySyncQuery {
+    /// Client-chosen id for this upload; resending it returns the first report
+    pub sync_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Updated,
+    /// Quantity and price already matched
+    Unchanged,
+    /// No product in the store has this SKU
+    NotFound,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RowResult {
+    /// 1-based position in the upload, not counting a CSV header
+    pub row: usize,
+    pub sku: Option<String>,
+    pub status: RowStatus,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InventorySyncReport {
+    pub sync_id: String,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub not_found: usize,
+    pub invalid: usize,
+    pub rows: Vec<RowResult>,
+    /// True when this sync id had already been applied and nothing was changed now
+    #[serde(default)]
+    pub replayed: bool,
+}
+
+/// A row that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub sku: Option<String>,
+    pub message: String,
+}
+
+pub type ParsedRow = Result<InventoryUpdate, RowError>;
+
+fn row_error(sku: Option<&str>, message: &str) -> RowError {
+    RowError {
+        sku: sku.map(str::to_owned),
+        message: message.to_string(),
+    }
+}
+
+/// Shared checks for a row from either format
+fn build_row(
+    sku: Option<&str>,
+    quantity: Option<Option<i64>>,
+    price: Option<Option<f64>>,
+) -> ParsedRow {
+    let sku = sku.map(str::trim).filter(|s| !s.is_empty());
+    let Some(sku) = sku else {
+        return Err(row_error(None, "sku is required"));
+    };
+    let quantity = match quantity {
+        None => return Err(row_error(Some(sku), "quantity is required")),
+        Some(Some(q)) if (0..=i64::from(i32::MAX)).contains(&q) => q as i32,
+        Some(_) => {
+            return Err(row_error(
+                Some(sku),
+                "quantity must be a whole number of zero or more",
+            ))
+        }
+    };
+    let price = match price {
+        None => None,
+        Some(Some(p)) if p.is_finite() && p > 0.0 => Some(p),
+        Some(_) => return Err(row_error(Some(sku), "price must be a positive number")),
+    };
+    Ok(InventoryUpdate {
+        sku: sku.to_string(),
+        quantity,
+        price,
+    })
+}
+
+fn parse_json_row(row: &Value) -> ParsedRow {
+    let field = |name: &str| row.get(name).filter(|v| !v.is_null());
+    build_row(
+        field("sku").and_then(Value::as_str),
+        field("quantity").map(Value::as_i64),
+        field("price").map(Value::as_f64),
+    )
+}
+
+/// CSV without quoting: SKUs and numbers never need it
+fn parse_csv(body: &str) -> Result<Vec<ParsedRow>, String> {
+    let mut lines = body.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .ok_or("CSV body is empty")?
+        .split(',')
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(sku_col), Some(quantity_col)) = (column("sku"), column("quantity")) else {
+        return Err("CSV header must include sku and quantity".to_string());
+    };
+    let price_col = column("price");
+
+    Ok(lines
+        .map(|line| {
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let cell = |i: usize| cells.get(i).copied().filter(|c| !c.is_empty());
+            build_row(
+                cell(sku_col),
+                cell(quantity_col).map(|q| q.parse().ok()),
+                price_col.and_then(cell).map(|p| p.parse().ok()),
+            )
+        })
+        .collect())
+}
+
+/// Read the upload, by content type. Errors reject the whole upload.
+pub fn parse_upload(content_type: Option<&str>, body: &[u8]) -> Result<Vec<ParsedRow>, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "Body must be UTF-8".to_string())?;
+    let is_csv = content_type.is_some_and(|ct| ct.starts_with("text/csv"));
+    let mut rows = if is_csv {
+        parse_csv(body)?
+    } else {
+        serde_json::from_str::<Vec<Value>>(body)
+            .map_err(|_| "Body must be a JSON array of {sku, quantity, price?}".to_string())?
+            .iter()
+            .map(parse_json_row)
+            .collect()
+    };
+
+    // A SKU listed twice would make the result depend on row order
+    let mut seen = HashSet::new();
+    for row in &mut rows {
+        if let Ok(update) = row {
+            if !seen.insert(update.sku.clone()) {
+                *row = Err(row_error(Some(&update.sku), "sku appears more than once"));
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Combine row parse errors and applied outcomes into the report, in upload order
+fn build_report(sync_id: &str, rows: &[ParsedRow], outcomes: &[SkuOutcome]) -> InventorySyncReport {
+    let mut outcomes = outcomes.iter();
+    let rows: Vec<RowResult> = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let (sku, status, message) = match row {
+                Err(err) => (
+                    err.sku.clone(),
+                    RowStatus::Invalid,
+                    Some(err.message.clone()),
+                ),
+                Ok(update) => {
+                    let sku = Some(update.sku.clone());
+                    match outcomes.next() {
+                        Some(SkuOutcome::Updated { .. }) => (sku, RowStatus::Updated, None),
+                        Some(SkuOutcome::Unchanged) => (sku, RowStatus::Unchanged, None),
+                        Some(SkuOutcome::NotFound) | None => (sku, RowStatus::NotFound, None),
+                        Some(SkuOutcome::Rejected(reason)) => {
+                            (sku, RowStatus::Invalid, Some(reason.clone()))
+                        }
+                    }
+                }
+            };
+            RowResult {
+                row: index + 1,
+                sku,
+                status,
+                message,
+            }
+        })
+        .collect();
+    let count = |status| rows.iter().filter(|r| r.status == status).count();
+    InventorySyncReport {
+        sync_id: sync_id.to_string(),
+        updated: count(RowStatus::Updated),
+        unchanged: count(RowStatus::Unchanged),
+        not_found: count(RowStatus::NotFound),
+        invalid: count(RowStatus::Invalid),
+        rows,
+        replayed: false,
+    }
+}
+
+fn stored_report(report: Value) -> Result<InventorySyncReport, String> {
+    serde_json::from_value(report)
+        .map_err(|e| format!("Stored inventory sync report is unreadable: {e}"))
+}
+
+fn replay(report: Value) -> Result<InventorySyncReport, String> {
+    Ok(InventorySyncReport {
+        replayed: true,
+        ..stored_report(report)?
+    })
+}
+
+/// Apply an upload once per `sync_id`. Returns the report and the products
+/// that changed; a replayed upload changes nothing.
+pub async fn run_inventory_sync<L: InventoryLedger + Sync>(
+    ledger: &L,
+    store_id: Uuid,
+    sync_id: &str,
+    rows: &[ParsedRow],
+) -> Result<(InventorySyncReport, Vec<SkuOutcome>), String> {
+    if let Some(report) = ledger.recorded(store_id, sync_id).await? {
+        return Ok((replay(report)?, Vec::new()));
+    }
+    let updates: Vec<InventoryUpdate> = rows.iter().filter_map(|r| r.clone().ok()).collect();
+    let report = |outcomes: &[SkuOutcome]| {
+        serde_json::to_value(build_report(sync_id, rows, outcomes)).unwrap_or(Value::Null)
+    };
+    match ledger.apply(store_id, sync_id, &updates, &report).await {
+        Ok((outcomes, report)) => Ok((stored_report(report)?, outcomes)),
+        // Lost a race with a concurrent upload of the same sync id
+        Err(err) => match ledger.recorded(store_id, sync_id).await? {
+            Some(report) => Ok((replay(report)?, Vec::new())),
+            None => Err(err),
+        },
+    }
+}
+
+/// Stock and price events for the products an upload changed
+fn change_events(before: &ProductModel, after: &ProductModel) -> Vec<Event> {
+    let mut events = Vec::new();
+    if before.quantity_available != after.quantity_available {
+        events.push(create_event(
+            EventType::ProductStockChanged,
+            after.id,
+            serde_json::json!({
+                "store_id": after.store_id,
+                "sku": after.sku,
+                "previous_quantity": before.quantity_available,
+                "quantity_available": after.quantity_available,
+            }),
+        ));
+    }
+    events.extend(low_stock_event(before.quantity_available, after));
+    if before.price != after.price {
+        events.push(create_event(
+            EventType::ProductPriceChanged,
+            after.id,
+            serde_json::json!({
+                "store_id": after.store_id,
+                "sku": after.sku,
+                "previous_price": before.price,
+                "price": after.price,
+                "currency": after.currency,
+            }),
+        ));
+    }
+    events
+}
+
+/// Push stock and price changes from a POS system
+#[utoipa::path(
+    post,
+    operation_id = "inventorySync",
+    path = "/api/v1/stores/{id}/inventory-sync",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        InventorySyncQuery
+    ),
+    request_body(
+        content = String,
+        description = "JSON array of `{sku, quantity, price?}`, or `text/csv` with a `sku,quantity[,price]` header",
+    ),
+    responses(
+        (status = 200, description = "Per-row results", body = InventorySyncReport),
+        (status = 400, description = "Unreadable body or missing sync_id"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found"),
+        (status = 413, description = "More than 1000 rows"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn inventory_sync(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<InventorySyncQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let sync_id = query.sync_id.trim();
+    if sync_id.is_empty() || sync_id.len() > MAX_SYNC_ID_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("sync_id must be 1 to {MAX_SYNC_ID_LEN} characters"),
+        )
+            .into_response();
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    let rows = match parse_upload(content_type, &body) {
+        Ok(rows) if rows.len() > MAX_SYNC_ROWS => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("At most {MAX_SYNC_ROWS} rows per upload"),
+            )
+                .into_response()
+        }
+        Ok(rows) => rows,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+
+    let ledger = DbInventoryLedger { db: &db };
+    // A resent sync_id changes nothing, so needs no snapshot
+    let replay = match ledger.recorded(id, sync_id).await {
+        Ok(recorded) => recorded.is_some(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if !replay {
+        let created_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+        if let Err(err) = StoreSnapshot::capture(
+            &db,
+            id,
+            SnapshotReason::InventorySync,
+            created_by.as_deref(),
+            Utc::now(),
+        )
+        .await
+        {
+            return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+        }
+    }
+
+    match run_inventory_sync(&ledger, id, sync_id, &rows).await {
+        Ok((report, outcomes)) => {
+            for outcome in outcomes {
+                if let SkuOutcome::Updated { before, after } = outcome {
+                    for event in change_events(&before, &after) {
+                        let _ = events.dispatch(event).await;
+                    }
+                }
+            }
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::inventory_sync::ReportBuilder;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-memory ledger over a fixed set of SKUs
+    struct MemoryLedger {
+        products: Mutex<HashMap<String, (i32, f64)>>,
+        syncs: Mutex<HashMap<String, Value>>,
+    }
+
+    impl MemoryLedger {
+        fn new(products: &[(&str, i32, f64)]) -> Self {
+            Self {
+                products: Mutex::new(
+                    products
+                        .iter()
+                        .map(|(sku, q, p)| (sku.to_string(), (*q, *p)))
+                        .collect(),
+                ),
+                syncs: Mutex::default(),
+            }
+        }
+    }
+
+    fn model(sku: &str, quantity: i32, price: f64) -> Box<ProductModel> {
+        let now = chrono::Utc::now();
+        Box::new(ProductModel {
+            id: Uuid::nil(),
+            store_id: Uuid::nil(),
+            tenant_id: "default".to_string(),
+            sku: Some(sku.to_string()),
+            name: sku.to_string(),
+            description: None,
+            price,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: quantity,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    #[async_trait::async_trait]
+    impl InventoryLedger for MemoryLedger {
+        async fn recorded(&self, _: Uuid, sync_id: &str) -> Result<Option<Value>, String> {
+            Ok(self.syncs.lock().unwrap().get(sync_id).cloned())
+        }
+
+        async fn apply(
+            &self,
+            _: Uuid,
+            sync_id: &str,
+            updates: &[InventoryUpdate],
+            report: &ReportBuilder<'_>,
+        ) -> Result<(Vec<SkuOutcome>, Value), String> {
+            let mut products = self.products.lock().unwrap();
+            let outcomes: Vec<SkuOutcome> = updates
+                .iter()
+                .map(|u| match products.get_mut(&u.sku) {
+                    None => SkuOutcome::NotFound,
+                    Some(current) => {
+                        let next = (u.quantity, u.price.unwrap_or(current.1));
+                        if *current == next {
+                            return SkuOutcome::Unchanged;
+                        }
+                        let before = model(&u.sku, current.0, current.1);
+                        *current = next;
+                        SkuOutcome::Updated {
+                            before,
+                            after: model(&u.sku, next.0, next.1),
+                        }
+                    }
+                })
+                .collect();
+            let report = report(&outcomes);
+            self.syncs
+                .lock()
+                .unwrap()
+                .insert(sync_id.to_string(), report.clone());
+            Ok((outcomes, report))
+        }
+    }
+
+    #[test]
+    fn test_parses_json_and_csv_alike() {
+        let json = br#"[{"sku": "A", "quantity": 3}, {"sku": "B", "quantity": 0, "price": 1500}]"#;
+        let csv = b"SKU,Quantity,Price\nA,3,\nB,0,1500\n";
+        let expected = vec![
+            Ok(InventoryUpdate {
+                sku: "A".to_string(),
+                quantity: 3,
+                price: None,
+            }),
+            Ok(InventoryUpdate {
+                sku: "B".to_string(),
+                quantity: 0,
+                price: Some(1500.0),
+            }),
+        ];
+        assert_eq!(parse_upload(None, json).unwrap(), expected);
+        assert_eq!(parse_upload(Some("text/csv"), csv).unwrap(), expected);
+        assert!(parse_upload(Some("text/csv"), b"code,stock\nA,1").is_err());
+        assert!(parse_upload(Some("application/json"), b"{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mixed_rows_then_repeat_submission() {
+        let ledger = MemoryLedger::new(&[("A", 5, 1000.0), ("B", 2, 2000.0), ("C", 7, 500.0)]);
+        let body = br#"[
+            {"sku": "A", "quantity": 4},
+            {"sku": "B", "quantity": 2, "price": 2000},
+            {"sku": "Z", "quantity": 1},
+            {"sku": "C", "quantity": -1},
+            {"quantity": 3},
+            {"sku": "C", "quantity": 6, "price": 450},
+            {"sku": "A", "quantity": 9}
+        ]"#;
+        let rows = parse_upload(None, body).unwrap();
+
+        let (report, outcomes) = run_inventory_sync(&ledger, Uuid::nil(), "pos-1", &rows)
+            .await
+            .unwrap();
+        let statuses: Vec<RowStatus> = report.rows.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                RowStatus::Updated,
+                RowStatus::Unchanged,
+                RowStatus::NotFound,
+                RowStatus::Invalid,
+                RowStatus::Invalid,
+                RowStatus::Updated,
+                RowStatus::Invalid,
+            ]
+        );
+        assert_eq!(
+            (
+                report.updated,
+                report.unchanged,
+                report.not_found,
+                report.invalid
+            ),
+            (2, 1, 1, 3)
+        );
+        assert!(!report.replayed);
+        assert_eq!(ledger.products.lock().unwrap()["C"], (6, 450.0));
+
+        // Only the changed rows produce events: stock for A, stock and price for C
+        let events: Vec<_> = outcomes
+            .iter()
+            .filter_map(|o| match o {
+                SkuOutcome::Updated { before, after } => Some(change_events(before, after)),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(events.len(), 3);
+
+        // The same sync id again: nothing applied, the first report comes back
+        ledger
+            .products
+            .lock()
+            .unwrap()
+            .insert("A".to_string(), (50, 1000.0));
+        let (again, outcomes) = run_inventory_sync(&ledger, Uuid::nil(), "pos-1", &rows)
+            .await
+            .unwrap();
+        assert!(again.replayed);
+        assert!(outcomes.is_empty());
+        assert_eq!(again.rows, report.rows);
+        assert_eq!(ledger.products.lock().unwrap()["A"], (50, 1000.0));
+    }
+}
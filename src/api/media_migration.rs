@@ -0,0 +1,202 @@
+//! Copying all media to another storage backend; see `crate::media_migration`.
+
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::media_storage::{S3BackendConfig, S3MediaStorage};
+use crate::db::media_migrations::{MediaMigration, NewMediaMigration};
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::entity::media_migration::Model as MediaMigrationModel;
+use crate::media_migration::{
+    run_migration, MigrationOptions, DEFAULT_BATCH_SIZE, DEFAULT_CONCURRENCY, MAX_BATCH_SIZE,
+    MAX_CONCURRENCY, STALE_MIGRATION_MINUTES,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct StartMediaMigrationRequest {
+    /// Backend the media is read from
+    pub source: S3BackendConfig,
+    /// Backend the media is copied to
+    pub target: S3BackendConfig,
+    /// Only count the objects and bytes that would be copied
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Prepended to every key on the target; the stored keys are rewritten
+    /// to match. Required when source and target are the same bucket.
+    pub key_prefix: Option<String>,
+    /// Media rows per batch (default 100, max 1000)
+    pub batch_size: Option<u64>,
+    /// Media copied at once (default 4, max 16)
+    pub concurrency: Option<usize>,
+    /// Continue a failed or stalled run instead of starting a new one. Its
+    /// `dry_run` and `key_prefix` are kept; the backends must be the same.
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub resume_id: Option<Uuid>,
+}
+
+/// Start copying every media object to another backend
+#[utoipa::path(
+    post,
+    operation_id = "startMediaMigration",
+    path = "/api/v1/admin/media/migrate",
+    tag = "Admin",
+    request_body = StartMediaMigrationRequest,
+    responses(
+        (status = 202, description = "Migration started; poll it for progress", body = MediaMigrationModel),
+        (status = 400, description = "Source and target are the same bucket without a key prefix, or differ from the resumed run"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Migration to resume not found"),
+        (status = 409, description = "Another migration is running, or the one to resume is not stopped"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn start_media_migration(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(payload): Json<StartMediaMigrationRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let key_prefix = payload
+        .key_prefix
+        .as_deref()
+        .map(|prefix| prefix.trim_matches('/'))
+        .filter(|prefix| !prefix.is_empty())
+        .map(str::to_owned);
+    let source = payload.source.describe();
+    let target = payload.target.describe();
+    let stale_before = Utc::now() - Duration::minutes(STALE_MIGRATION_MINUTES);
+
+    let run = match payload.resume_id {
+        Some(id) => {
+            match MediaMigration::get(&db, id).await {
+                Ok(Some(run)) if run.source != source || run.target != target => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        "Source and target must match the migration being resumed",
+                    )
+                        .into_response()
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    return (StatusCode::NOT_FOUND, "Media migration not found").into_response()
+                }
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            }
+            match MediaMigration::resume(&db, id, stale_before).await {
+                Ok(Some(run)) => run,
+                Ok(None) => {
+                    return (
+                        StatusCode::CONFLICT,
+                        "Only failed or stalled migrations can be resumed",
+                    )
+                        .into_response()
+                }
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            }
+        }
+        None => {
+            if source == target && key_prefix.is_none() {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Copying a bucket onto itself needs a key_prefix",
+                )
+                    .into_response();
+            }
+            let request = NewMediaMigration {
+                requested_by: admin.relay_id.clone(),
+                source,
+                target,
+                key_prefix,
+                dry_run: payload.dry_run,
+            };
+            match MediaMigration::create(&db, &request, stale_before).await {
+                Ok(Some(run)) => run,
+                Ok(None) => {
+                    return (
+                        StatusCode::CONFLICT,
+                        "Another media migration is still running",
+                    )
+                        .into_response()
+                }
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            }
+        }
+    };
+
+    let options = MigrationOptions {
+        batch_size: payload
+            .batch_size
+            .unwrap_or(DEFAULT_BATCH_SIZE)
+            .clamp(1, MAX_BATCH_SIZE),
+        concurrency: payload
+            .concurrency
+            .unwrap_or(DEFAULT_CONCURRENCY)
+            .clamp(1, MAX_CONCURRENCY),
+        key_prefix: run.key_prefix.clone(),
+        dry_run: run.dry_run,
+    };
+    tracing::warn!(
+        migration_id = %run.id,
+        admin = %admin.relay_id,
+        source = %run.source,
+        target = %run.target,
+        dry_run = run.dry_run,
+        "Media migration started"
+    );
+    let source = S3MediaStorage::from_config(&payload.source).await;
+    let target = S3MediaStorage::from_config(&payload.target).await;
+    let job = run.clone();
+    tokio::spawn(async move {
+        if let Err(err) = run_migration(&db, &job, &source, &target, &options).await {
+            tracing::error!(migration_id = %job.id, "Media migration could not finish: {}", err);
+        }
+    });
+    (StatusCode::ACCEPTED, Json(run)).into_response()
+}
+
+/// Progress of a media migration
+#[utoipa::path(
+    get,
+    operation_id = "getMediaMigration",
+    path = "/api/v1/admin/media/migrations/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Migration ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Counts so far, the first failures, and the status", body = MediaMigrationModel),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Migration not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_media_migration(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match MediaMigration::get(&db, id).await {
+        Ok(Some(run)) => Json(run).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Media migration not found").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
@@ -1,21 +1,360 @@
+use crate::api::multipart::UploadedFile;
+use crate::metrics::{self, Counter, Gauge};
 use async_trait::async_trait;
-use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client as S3Client;
-use axum::extract::Multipart;
-use bytes::Bytes;
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
 use std::env;
-use std::time::Duration;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
 use tokio::time::sleep;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Error code returned while the storage circuit breaker is open
+pub const STORAGE_UNAVAILABLE: &str = "storage_unavailable";
+
+/// Consecutive S3 failures that open the breaker
+const BREAKER_FAILURE_THRESHOLD: u32 = 5;
+/// How long an open breaker rejects calls before letting a trial through
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Bucket checks made before the first upload is given up on
+const BUCKET_VERIFY_ATTEMPTS: u32 = 3;
+const BUCKET_VERIFY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Client shared by every S3 call in the process, built on first use
+static S3_STORAGE: OnceCell<S3MediaStorage> = OnceCell::const_new();
+
+/// Breaker shared by every S3 call in the process
+pub static S3_BREAKER: CircuitBreaker =
+    CircuitBreaker::new(BREAKER_FAILURE_THRESHOLD, BREAKER_COOLDOWN).with_metrics(
+        &metrics::MEDIA_STORAGE_BREAKER_OPENED,
+        &metrics::MEDIA_STORAGE_BREAKER_OPEN,
+    );
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls go through
+    Closed,
+    /// Recent calls kept failing; calls are rejected without trying
+    Open,
+    /// Cooldown is over; the next calls are trials that close or re-open it
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct BreakerInner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Stops hammering a dependency that keeps failing.
+///
+/// After `threshold` consecutive failures the breaker opens for `cooldown`;
+/// the first success afterwards closes it again.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+    metrics: Option<(&'static Counter, &'static Gauge)>,
+}
+
+impl CircuitBreaker {
+    pub const fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            inner: Mutex::new(BreakerInner {
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+            metrics: None,
+        }
+    }
+
+    const fn with_metrics(mut self, opened: &'static Counter, open: &'static Gauge) -> Self {
+        self.metrics = Some((opened, open));
+        self
+    }
+
+    pub fn state(&self, now: Instant) -> BreakerState {
+        let Ok(inner) = self.inner.lock() else {
+            return BreakerState::Closed;
+        };
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may be attempted right now
+    pub fn allows(&self, now: Instant) -> bool {
+        self.state(now) != BreakerState::Open
+    }
+
+    pub fn record_success(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if inner.opened_at.is_some() {
+                tracing::info!("Media storage recovered; closing circuit breaker");
+            }
+            *inner = BreakerInner::default();
+        }
+        if let Some((_, open)) = self.metrics {
+            open.set(0);
+        }
+    }
+
+    pub fn record_failure(&self, now: Instant) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        inner.consecutive_failures += 1;
+        let trips = match inner.opened_at {
+            // A failed trial re-opens for another full cooldown
+            Some(_) => true,
+            None => inner.consecutive_failures >= self.threshold,
+        };
+        if trips {
+            if inner.opened_at.is_none() {
+                tracing::warn!(
+                    failures = inner.consecutive_failures,
+                    "Media storage keeps failing; opening circuit breaker"
+                );
+                if let Some((opened, _)) = self.metrics {
+                    opened.inc();
+                }
+            }
+            inner.opened_at = Some(now);
+            if let Some((_, open)) = self.metrics {
+                open.set(1);
+            }
+        }
+    }
+
+    /// Record the outcome of a call made through the breaker
+    pub fn observe<T>(&self, result: Result<T, String>) -> Result<T, String> {
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(_) => self.record_failure(Instant::now()),
+        }
+        result
+    }
+}
+
+/// Body of the 503 returned while media storage is unavailable
+#[derive(Serialize, ToSchema)]
+pub struct StorageUnavailableResponse {
+    pub code: &'static str,
+    pub message: &'static str,
+}
+
+pub fn storage_unavailable_response() -> axum::response::Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(StorageUnavailableResponse {
+            code: STORAGE_UNAVAILABLE,
+            message: "Media storage is temporarily unavailable. Please retry in a minute.",
+        }),
+    )
+        .into_response()
+}
+
+/// Why media storage could not be reached
+#[derive(Debug)]
+pub enum StorageConnectError {
+    /// The breaker is open; no connection was attempted
+    Unavailable,
+    Failed(String),
+}
+
+/// The process-wide S3 client. Building it makes no network calls, so this
+/// only fails when credentials are missing.
+pub async fn shared_s3_storage() -> Result<S3MediaStorage, String> {
+    S3_STORAGE
+        .get_or_try_init(S3MediaStorage::new)
+        .await
+        .cloned()
+}
+
+/// Connect to S3 unless the breaker says it is down; the returned storage
+/// reports every call's outcome to [`S3_BREAKER`]
+pub async fn connect_s3_storage(
+) -> Result<BreakerStorage<'static, S3MediaStorage>, StorageConnectError> {
+    if !S3_BREAKER.allows(Instant::now()) {
+        return Err(StorageConnectError::Unavailable);
+    }
+    match shared_s3_storage().await {
+        Ok(storage) => Ok(BreakerStorage::new(storage, &S3_BREAKER)),
+        Err(err) => Err(StorageConnectError::Failed(err)),
+    }
+}
+
+/// Whether the media bucket is known to be usable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketStatus {
+    /// Storage credentials are not configured; uploads are disabled
+    Unconfigured,
+    /// Not checked yet; the first upload verifies it
+    Unverified,
+    /// Checked and reachable
+    Ready,
+}
+
+/// Status of the shared bucket, without touching the network
+pub fn bucket_status() -> BucketStatus {
+    match S3_STORAGE.get() {
+        Some(storage) if storage.bucket.is_verified() => BucketStatus::Ready,
+        Some(_) => BucketStatus::Unverified,
+        None => BucketStatus::Unconfigured,
+    }
+}
+
+/// Bucket verification run once per process, on first use.
+///
+/// A failed verification leaves the gate closed, so the next caller tries
+/// again; once one succeeds it is never repeated.
+#[derive(Default)]
+pub struct BucketGate {
+    verified: OnceCell<()>,
+}
+
+impl BucketGate {
+    pub fn is_verified(&self) -> bool {
+        self.verified.initialized()
+    }
+
+    /// Run `verify` up to `attempts` times, doubling `backoff` between
+    /// tries, unless an earlier call already succeeded
+    pub async fn ensure<F, Fut>(
+        &self,
+        attempts: u32,
+        backoff: Duration,
+        mut verify: F,
+    ) -> Result<(), String>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        self.verified
+            .get_or_try_init(|| async {
+                let mut delay = backoff;
+                let mut attempt = 1;
+                loop {
+                    match verify().await {
+                        Ok(()) => return Ok(()),
+                        Err(e) if attempt >= attempts => return Err(e),
+                        Err(e) => {
+                            tracing::warn!(
+                                attempt,
+                                error = %e,
+                                "Media bucket check failed; retrying in {:?}",
+                                delay
+                            );
+                            sleep(delay).await;
+                            delay *= 2;
+                            attempt += 1;
+                        }
+                    }
+                }
+            })
+            .await
+            .map(|_| ())
+    }
+}
+
+/// [`MediaStorage`] wrapper that short-circuits while its breaker is open
+pub struct BreakerStorage<'a, S> {
+    inner: S,
+    breaker: &'a CircuitBreaker,
+}
+
+impl<'a, S> BreakerStorage<'a, S> {
+    pub fn new(inner: S, breaker: &'a CircuitBreaker) -> Self {
+        Self { inner, breaker }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.breaker.allows(Instant::now()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{STORAGE_UNAVAILABLE}: media storage circuit breaker is open"
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl<S: MediaStorage + Send + Sync> MediaStorage for BreakerStorage<'_, S> {
+    async fn upload_media_data(
+        &self,
+        product_id: Uuid,
+        file_name: &str,
+        file_data: &[u8],
+        content_type: &str,
+        image_id: Option<Uuid>,
+    ) -> Result<String, String> {
+        self.check()?;
+        self.breaker.observe(
+            self.inner
+                .upload_media_data(product_id, file_name, file_data, content_type, image_id)
+                .await,
+        )
+    }
+
+    async fn delete_media(&self, media_key: &str) -> Result<(), String> {
+        self.check()?;
+        self.breaker
+            .observe(self.inner.delete_media(media_key).await)
+    }
+
+    async fn put_object(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), String> {
+        self.check()?;
+        self.breaker
+            .observe(self.inner.put_object(key, data, content_type).await)
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, String> {
+        self.check()?;
+        self.breaker
+            .observe(self.inner.presigned_url(key, expires_in).await)
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, String> {
+        self.check()?;
+        self.breaker.observe(self.inner.get_object(key).await)
+    }
+}
+
 #[async_trait]
 pub trait MediaStorage {
-    #[allow(dead_code)]
+    /// Store an uploaded file; its key is made from `image_id`, or a new
+    /// id, and the file name
     async fn upload_media(
         &self,
         product_id: Uuid,
-        multipart: &mut Multipart,
-    ) -> Result<String, String>;
+        file: &UploadedFile,
+        image_id: Option<Uuid>,
+    ) -> Result<String, String> {
+        self.upload_media_data(
+            product_id,
+            &file.name,
+            &file.bytes,
+            &file.content_type,
+            image_id,
+        )
+        .await
+    }
 
     #[allow(dead_code)]
     async fn upload_media_data(
@@ -29,45 +368,95 @@ pub trait MediaStorage {
 
     #[allow(dead_code)]
     async fn delete_media(&self, media_key: &str) -> Result<(), String>;
+
+    /// Store `data` under an exact key, replacing what was there
+    async fn put_object(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), String>;
+
+    /// A time-limited download link for `key`
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, String>;
+
+    /// The bytes stored under `key`
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Where an S3-compatible bucket lives and how to reach it. Deliberately
+/// not `Debug`, so the secret never ends up in a log line.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct S3BackendConfig {
+    /// e.g. `http://minio:9000` or `https://<account>.r2.cloudflarestorage.com`
+    pub endpoint_url: String,
+    /// Defaults to `us-east-1`
+    pub region: Option<String>,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3BackendConfig {
+    /// The backend from `AWS_*` and `S3_BUCKET_NAME`, as used for uploads
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            access_key_id: env::var("AWS_ACCESS_KEY_ID")
+                .map_err(|_| "AWS_ACCESS_KEY_ID environment variable not set".to_string())?,
+            secret_access_key: env::var("AWS_SECRET_ACCESS_KEY")
+                .map_err(|_| "AWS_SECRET_ACCESS_KEY environment variable not set".to_string())?,
+            // Defaults to a local MinIO
+            endpoint_url: env::var("AWS_ENDPOINT_URL")
+                .unwrap_or_else(|_| "http://localhost:9000".to_string()),
+            region: env::var("AWS_REGION").ok(),
+            bucket: env::var("S3_BUCKET_NAME").unwrap_or_else(|_| "transac-media".to_string()),
+        })
+    }
+
+    /// Endpoint and bucket, safe to store and show
+    pub fn describe(&self) -> String {
+        format!(
+            "{}/{}",
+            self.endpoint_url.trim_end_matches('/'),
+            self.bucket
+        )
+    }
 }
 
 // S3/MinIO implementation
+#[derive(Clone)]
 pub struct S3MediaStorage {
     client: S3Client,
     bucket_name: String,
+    /// Shared by clones, so the bucket is verified once per process
+    bucket: Arc<BucketGate>,
 }
 
 #[allow(dead_code)]
 impl S3MediaStorage {
+    /// Build the client from the environment. The bucket is not checked
+    /// here; [`Self::ensure_bucket`] does that on first upload.
     pub async fn new() -> Result<Self, String> {
-        // Get credentials from environment variables
-        let access_key = env::var("AWS_ACCESS_KEY_ID")
-            .map_err(|_| "AWS_ACCESS_KEY_ID environment variable not set".to_string())?;
-        let secret_key = env::var("AWS_SECRET_ACCESS_KEY")
-            .map_err(|_| "AWS_SECRET_ACCESS_KEY environment variable not set".to_string())?;
-
-        // Get endpoint URL from environment variable (for MinIO)
-        let endpoint_url =
-            env::var("AWS_ENDPOINT_URL").unwrap_or_else(|_| "http://localhost:9000".to_string());
-
-        // Get region from environment variable or use default
-        let region_name = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
-
-        let region = RegionProviderChain::default_provider()
-            .or_else(aws_config::Region::new(region_name.clone()))
-            .region()
-            .await;
+        Ok(Self::from_config(&S3BackendConfig::from_env()?).await)
+    }
+
+    /// Build a client for any S3-compatible backend
+    pub async fn from_config(backend: &S3BackendConfig) -> Self {
+        let region_name = backend
+            .region
+            .clone()
+            .unwrap_or_else(|| "us-east-1".to_string());
+
+        // An explicit region keeps the provider chain from probing instance metadata
+        let region = aws_config::Region::new(region_name.clone());
 
         // Build AWS config with explicit credentials and endpoint
         let credentials = aws_sdk_s3::config::Credentials::new(
-            access_key, secret_key, None,     // session_token
+            backend.access_key_id.clone(),
+            backend.secret_access_key.clone(),
+            None,     // session_token
             None,     // expiry
             "static", // provider_name
         );
 
         let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
             .region(region)
-            .endpoint_url(&endpoint_url)
+            .endpoint_url(&backend.endpoint_url)
             .credentials_provider(credentials)
             .load()
             .await;
@@ -79,43 +468,29 @@ impl S3MediaStorage {
 
         let client = S3Client::from_conf(s3_config);
 
-        // Get bucket name from environment variable or use default
-        let bucket_name =
-            env::var("S3_BUCKET_NAME").unwrap_or_else(|_| "transac-media".to_string());
-
         // Log connection details (without sensitive info)
         tracing::info!(
             "Initializing S3 media storage with bucket: {}, region: {}, endpoint: {}",
-            bucket_name,
+            backend.bucket,
             region_name,
-            &endpoint_url
+            &backend.endpoint_url
         );
 
-        // One-time diagnostic: list buckets visible to the client
-        match client.list_buckets().send().await {
-            Ok(output) => {
-                let names: Vec<String> = output
-                    .buckets()
-                    .iter()
-                    .filter_map(|b| b.name().map(|s| s.to_string()))
-                    .collect();
-                tracing::info!("S3 list_buckets returned: {:?}", names);
-            }
-            Err(e) => {
-                tracing::warn!("S3 list_buckets failed: {:?}", e);
-            }
-        }
-
-        // Create a new instance
-        let storage = Self {
+        Self {
             client,
-            bucket_name,
-        };
-
-        // Verify bucket exists and is accessible
-        storage.ensure_bucket_exists().await?;
+            bucket_name: backend.bucket.clone(),
+            bucket: Arc::default(),
+        }
+    }
 
-        Ok(storage)
+    /// Verify the bucket exists, creating it if needed, the first time this
+    /// is called in the process
+    pub async fn ensure_bucket(&self) -> Result<(), String> {
+        self.bucket
+            .ensure(BUCKET_VERIFY_ATTEMPTS, BUCKET_VERIFY_BACKOFF, || {
+                self.ensure_bucket_exists()
+            })
+            .await
     }
 
     /// Ensures the configured bucket exists and is accessible
@@ -227,62 +602,10 @@ impl S3MediaStorage {
             }
         }
     }
-
-    async fn extract_file_from_multipart(
-        &self,
-        multipart: &mut Multipart,
-    ) -> Result<(String, Bytes), String> {
-        let mut file_data = Vec::new();
-        let mut filename = String::new();
-
-        while let Some(mut field) = multipart
-            .next_field()
-            .await
-            .map_err(|e| format!("Failed to read multipart field: {e}"))?
-        {
-            if field.name() == Some("file") {
-                filename = field.file_name().unwrap_or("unknown").to_string();
-
-                while let Some(chunk) = field
-                    .chunk()
-                    .await
-                    .map_err(|e| format!("Failed to read chunk: {e}"))?
-                {
-                    file_data.extend_from_slice(&chunk);
-                }
-                break;
-            }
-        }
-
-        if file_data.is_empty() {
-            return Err("No file data found in multipart".to_string());
-        }
-
-        Ok((filename, Bytes::from(file_data)))
-    }
 }
 
 #[async_trait]
 impl MediaStorage for S3MediaStorage {
-    async fn upload_media(
-        &self,
-        product_id: Uuid,
-        multipart: &mut Multipart,
-    ) -> Result<String, String> {
-        // Extract file from multipart
-        let (filename, file_data) = self.extract_file_from_multipart(multipart).await?;
-
-        // Use the common implementation
-        self.upload_media_data(
-            product_id,
-            &filename,
-            &file_data,
-            "application/octet-stream",
-            None,
-        )
-        .await
-    }
-
     async fn upload_media_data(
         &self,
         product_id: Uuid,
@@ -294,6 +617,7 @@ impl MediaStorage for S3MediaStorage {
         if file_data.is_empty() {
             return Err("Cannot upload empty file data".to_string());
         }
+        self.ensure_bucket().await?;
 
         // Generate S3 key with organized folder structure
         let file_extension = file_name.split('.').next_back().unwrap_or("bin");
@@ -374,6 +698,57 @@ impl MediaStorage for S3MediaStorage {
             }
         }
     }
+
+    async fn put_object(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), String> {
+        self.ensure_bucket().await?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(data.to_vec().into())
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!("S3 put_object failed for '{}': {:?}", key, e);
+                format!("Failed to upload to S3: {e}")
+            })?;
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, String> {
+        let config = aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)
+            .map_err(|e| format!("Invalid presigning expiry: {e}"))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .presigned(config)
+            .await
+            .map_err(|e| format!("Failed to presign S3 download: {e}"))?;
+        Ok(request.uri().to_string())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, String> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!("S3 get_object failed for '{}': {:?}", key, e);
+                format!("Failed to download from S3: {e}")
+            })?;
+        let body = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| format!("Failed to read S3 object '{key}': {e}"))?;
+        Ok(body.into_bytes().to_vec())
+    }
 }
 
 // Stub implementation for development/testing
@@ -381,19 +756,6 @@ pub struct StubMediaStorage;
 
 #[async_trait]
 impl MediaStorage for StubMediaStorage {
-    async fn upload_media(
-        &self,
-        product_id: Uuid,
-        _multipart: &mut Multipart,
-    ) -> Result<String, String> {
-        // Return a stubbed S3 key for development
-        Ok(format!(
-            "products/{}/media_stub_{}.jpg",
-            product_id,
-            Uuid::new_v4()
-        ))
-    }
-
     async fn upload_media_data(
         &self,
         product_id: Uuid,
@@ -414,4 +776,191 @@ impl MediaStorage for StubMediaStorage {
         // Stub implementation - always succeeds
         Ok(())
     }
+
+    async fn put_object(
+        &self,
+        _key: &str,
+        _data: &[u8],
+        _content_type: &str,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, String> {
+        Ok(format!(
+            "https://stub.invalid/{key}?expires_in={}",
+            expires_in.as_secs()
+        ))
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, String> {
+        // Uploads to the stub are never kept
+        Err(format!("Object '{key}' not found in stub storage"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Storage whose availability can be toggled, counting calls that reach it
+    #[derive(Default)]
+    struct FlakyStorage {
+        down: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl MediaStorage for FlakyStorage {
+        async fn upload_media_data(
+            &self,
+            product_id: Uuid,
+            file_name: &str,
+            _file_data: &[u8],
+            _content_type: &str,
+            _image_id: Option<Uuid>,
+        ) -> Result<String, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.down.load(Ordering::SeqCst) {
+                Err("connection refused".to_string())
+            } else {
+                Ok(format!("products/{product_id}/media/{file_name}"))
+            }
+        }
+
+        async fn delete_media(&self, _media_key: &str) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn put_object(
+            &self,
+            _key: &str,
+            _data: &[u8],
+            _content_type: &str,
+        ) -> Result<(), String> {
+            unreachable!("tests upload media")
+        }
+
+        async fn presigned_url(&self, _key: &str, _expires_in: Duration) -> Result<String, String> {
+            unreachable!("tests upload media")
+        }
+
+        async fn get_object(&self, _key: &str) -> Result<Vec<u8>, String> {
+            unreachable!("tests upload media")
+        }
+    }
+
+    async fn upload<S: MediaStorage + Sync>(storage: &S) -> Result<String, String> {
+        storage
+            .upload_media_data(Uuid::new_v4(), "a.jpg", b"data", "image/jpeg", None)
+            .await
+    }
+
+    #[test]
+    fn test_breaker_opens_after_threshold_and_half_opens_after_cooldown() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        let start = Instant::now();
+
+        breaker.record_failure(start);
+        breaker.record_failure(start);
+        assert_eq!(breaker.state(start), BreakerState::Closed);
+
+        breaker.record_failure(start);
+        assert_eq!(breaker.state(start), BreakerState::Open);
+        assert!(!breaker.allows(start + Duration::from_secs(29)));
+
+        let later = start + Duration::from_secs(30);
+        assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+
+        // A failed trial re-opens for a full cooldown
+        breaker.record_failure(later);
+        assert_eq!(
+            breaker.state(later + Duration::from_secs(29)),
+            BreakerState::Open
+        );
+
+        breaker.record_success();
+        assert_eq!(breaker.state(later), BreakerState::Closed);
+    }
+
+    #[test]
+    fn test_success_resets_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure(now);
+        breaker.record_success();
+        breaker.record_failure(now);
+        assert_eq!(breaker.state(now), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_storage_short_circuits_while_open() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let storage = BreakerStorage::new(FlakyStorage::default(), &breaker);
+        storage.inner().down.store(true, Ordering::SeqCst);
+
+        assert!(upload(&storage).await.is_err());
+        assert!(upload(&storage).await.is_err());
+        assert_eq!(breaker.state(Instant::now()), BreakerState::Open);
+
+        // Further calls fail fast without touching the backend
+        let err = upload(&storage).await.unwrap_err();
+        assert!(err.starts_with(STORAGE_UNAVAILABLE));
+        assert_eq!(storage.inner().calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_gate_retries_after_a_failed_verification() {
+        let gate = BucketGate::default();
+        let checks = AtomicUsize::new(0);
+        // Fails the first check, then succeeds
+        let verify = || async {
+            match checks.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("connection refused".to_string()),
+                _ => Ok(()),
+            }
+        };
+
+        assert!(gate.ensure(1, Duration::ZERO, verify).await.is_err());
+        assert!(!gate.is_verified());
+
+        assert!(gate.ensure(1, Duration::ZERO, verify).await.is_ok());
+        assert!(gate.is_verified());
+
+        // Memoized: later uploads don't check again
+        assert!(gate.ensure(1, Duration::ZERO, verify).await.is_ok());
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_gate_backs_off_within_one_call() {
+        let gate = BucketGate::default();
+        let checks = AtomicUsize::new(0);
+        let verify = || async {
+            match checks.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("bucket not ready".to_string()),
+                _ => Ok(()),
+            }
+        };
+
+        assert!(gate
+            .ensure(3, Duration::from_millis(1), verify)
+            .await
+            .is_ok());
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_storage_recovers_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        let storage = BreakerStorage::new(FlakyStorage::default(), &breaker);
+        storage.inner().down.store(true, Ordering::SeqCst);
+        assert!(upload(&storage).await.is_err());
+
+        // With no cooldown the next call is a trial; once S3 is back it closes
+        storage.inner().down.store(false, Ordering::SeqCst);
+        assert!(upload(&storage).await.is_ok());
+        assert_eq!(breaker.state(Instant::now()), BreakerState::Closed);
+    }
 }
@@ -1,7 +1,48 @@
+pub mod admin;
+pub mod bulk_delete;
+pub mod bulk_prices;
+pub mod bundles;
+pub mod categories;
+pub mod checkout;
+pub mod commissions;
+pub mod delta;
+pub mod embed;
+pub mod extract;
+pub mod fields;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod history;
 pub mod image_analysis;
+pub mod image_conversion;
+pub mod imports;
+pub mod inventory_sync;
+pub mod media_migration;
 pub mod media_storage;
+pub mod moderation;
+pub mod multipart;
+pub mod notification_preferences;
+pub mod onboarding;
+pub mod pagination;
+pub mod payout_accounts;
+pub mod product_views;
 pub mod products;
+pub mod promotions;
+pub mod public_stats;
+pub mod questions;
+pub mod referrals;
+pub mod reports;
+pub mod return_policies;
+pub mod seo;
+pub mod snapshots;
+pub mod store_api_keys;
+pub mod store_reviews;
 pub mod stores;
+pub mod sync;
+pub mod transaction;
+pub mod user_refs;
+pub mod validation;
+pub mod watches;
+pub mod whatsapp_catalog;
 
 use axum::Router;
 use sea_orm::DatabaseConnection;
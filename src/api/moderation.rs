@@ -0,0 +1,762 @@
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::config::SiteConfig;
+use crate::db::media_similarity::{FlagStatus, MediaSimilarity};
+use crate::db::moderation::{
+    profanity_rule, term_rule, ModerationStatus, ProductModeration, ProfanityTerm, ProhibitedTerm,
+};
+use crate::db::review_anomalies::{AnomalyStatus, ReviewAnomaly};
+use crate::db::store_reviews::StoreReview;
+use crate::entity::media_similarity_flag::Model as FlagModel;
+use crate::entity::product::Model as ProductModel;
+use crate::entity::product_moderation::Model as ModerationModel;
+use crate::entity::profanity_term::Model as ProfanityModel;
+use crate::entity::prohibited_term::Model as TermModel;
+use crate::entity::review_anomaly::Model as AnomalyModel;
+use crate::events::{create_event, EventDispatcher, EventType};
+use crate::links::ProductLink;
+use crate::moderation::text::{normalize_text, TextField, TEXT_MODERATION};
+use crate::moderation::{normalize, TermAction, TermRule, Verdict, PROHIBITED_TERMS};
+use crate::tenant::{validate_tenant_id, DEFAULT_TENANT};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const MAX_TERM_LEN: usize = 200;
+const MAX_CATEGORY_LEN: usize = 50;
+
+/// Body of the 422 returned when a listing uses a blocked term
+#[derive(Serialize, ToSchema)]
+pub struct ProhibitedTermRejection {
+    pub message: String,
+    /// The blocked term, normalized
+    pub term: String,
+    pub category: String,
+}
+
+impl IntoResponse for ProhibitedTermRejection {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// Screen a listing's name and description against the prohibited terms.
+/// `Ok(Some(terms))` when flagged terms matched and the listing must be held
+/// for review.
+pub fn screen_listing(
+    name: &str,
+    description: Option<&str>,
+) -> Result<Option<Vec<TermRule>>, ProhibitedTermRejection> {
+    screen("Listing", &[name, description.unwrap_or_default()])
+}
+
+/// Screen free text such as a product question; `subject` names it in the
+/// rejection message
+pub fn screen(
+    subject: &str,
+    texts: &[&str],
+) -> Result<Option<Vec<TermRule>>, ProhibitedTermRejection> {
+    match PROHIBITED_TERMS.verdict(texts) {
+        Verdict::Clear => Ok(None),
+        Verdict::Flag(rules) => Ok(Some(rules)),
+        Verdict::Block(rule) => Err(ProhibitedTermRejection {
+            message: format!(
+                "{subject} uses the prohibited term '{}' ({})",
+                rule.term, rule.category
+            ),
+            term: rule.term,
+            category: rule.category,
+        }),
+    }
+}
+
+/// Unpublish a just-saved product that matched flagged terms and queue it
+/// for review
+pub async fn hold_listing(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+    product: ProductModel,
+    matched: &[TermRule],
+) -> Result<ProductModel, String> {
+    let mut product = product;
+    let entry = ProductModeration::hold(db, product.clone(), matched).await?;
+    product.is_published = false;
+    let event = create_event(
+        EventType::ProductHeldForReview,
+        product.id,
+        serde_json::json!({
+            "store_id": product.store_id,
+            "matched_terms": entry.matched_terms,
+        }),
+    );
+    let _ = dispatcher.dispatch(event).await;
+    Ok(product)
+}
+
+/// Load the stored terms into the running matcher
+pub async fn load_prohibited_terms(db: &DatabaseConnection) -> Result<usize, String> {
+    let rules = ProhibitedTerm::list(db)
+        .await?
+        .iter()
+        .map(term_rule)
+        .collect::<Result<Vec<_>, _>>()?;
+    let count = rules.len();
+    PROHIBITED_TERMS.load(rules)?;
+    Ok(count)
+}
+
+/// Body of the 422 returned when user-written text is refused. The matched
+/// phrase is left out on purpose.
+#[derive(Serialize, ToSchema)]
+pub struct TextRejectionResponse {
+    pub message: String,
+    pub field: TextField,
+    pub category: String,
+}
+
+impl IntoResponse for TextRejectionResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// Screen user-written text against the profanity list, in the mode
+/// configured for `field`. Returns the text to save, masked if need be.
+pub fn moderate_text(field: TextField, text: &str) -> Result<String, TextRejectionResponse> {
+    TEXT_MODERATION
+        .screen(field, text)
+        .map_err(|rejection| TextRejectionResponse {
+            message: format!(
+                "{} contains {} language",
+                rejection.field.as_str(),
+                rejection.category
+            ),
+            field: rejection.field,
+            category: rejection.category,
+        })
+}
+
+/// [`moderate_text`] for an optional field
+pub fn moderate_optional_text(
+    field: TextField,
+    text: Option<&str>,
+) -> Result<Option<String>, TextRejectionResponse> {
+    text.map(|text| moderate_text(field, text)).transpose()
+}
+
+/// Either 422 body of an endpoint screened for both prohibited terms and
+/// profanity; only used to document those endpoints
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum ContentRejection {
+    ProhibitedTerm(ProhibitedTermRejection),
+    Language(TextRejectionResponse),
+}
+
+/// Load the stored profanity list into the running matcher
+pub async fn load_profanity_terms(db: &DatabaseConnection) -> Result<usize, String> {
+    let rules: Vec<_> = ProfanityTerm::list(db)
+        .await?
+        .iter()
+        .map(profanity_rule)
+        .collect();
+    let count = rules.len();
+    TEXT_MODERATION.load(rules)?;
+    Ok(count)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateProhibitedTermRequest {
+    /// Word or phrase; matched ignoring case, diacritics and punctuation
+    pub term: String,
+    /// Reason given to sellers, e.g. "counterfeit"
+    pub category: String,
+    pub action: TermAction,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProhibitedTermsResponse {
+    pub terms: Vec<TermModel>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateProfanityTermRequest {
+    /// Word or phrase; matched ignoring case, diacritics, punctuation and
+    /// basic leetspeak
+    pub phrase: String,
+    /// Reason given when text is refused, e.g. "insult"
+    pub category: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProfanityTermsResponse {
+    #[schema(inline)]
+    pub terms: Vec<ProfanityModel>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ModerationQueueQuery {
+    /// Defaults to `pending`
+    pub status: Option<ModerationStatus>,
+    /// Defaults to the default tenant
+    pub tenant: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModerationQueueResponse {
+    pub entries: Vec<ModerationModel>,
+    /// Uploads that look like another store's photos; only listed with the
+    /// pending queue
+    pub image_flags: Vec<ImageFlagResponse>,
+    /// Stores whose recent reviews look farmed, most suspicious first; only
+    /// listed with the pending queue
+    pub review_anomalies: Vec<AnomalyModel>,
+}
+
+/// A possibly copied photo, with links to both listings
+#[derive(Serialize, ToSchema)]
+pub struct ImageFlagResponse {
+    #[serde(flatten)]
+    pub flag: FlagModel,
+    /// Listing that uploaded the image
+    pub listing_url: String,
+    /// Listing that had it first
+    pub matched_listing_url: String,
+}
+
+impl ImageFlagResponse {
+    fn new(flag: FlagModel, site: &SiteConfig) -> Self {
+        Self {
+            listing_url: ProductLink::new(&site.public_base_url, flag.product_id).to_string(),
+            matched_listing_url: ProductLink::new(&site.public_base_url, flag.matched_product_id)
+                .to_string(),
+            flag,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReviewImageFlagRequest {
+    /// `dismissed` closes the flag, `held` sends the uploading listing to the
+    /// moderation queue
+    pub decision: FlagStatus,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReviewAnomalyRequest {
+    /// `dismissed` closes the finding, `hidden` hides its reviews and takes
+    /// them out of the store's rating
+    pub decision: AnomalyStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecomputeStoreRatingsResponse {
+    /// Stores whose rating was recomputed
+    pub stores: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReviewProductRequest {
+    /// `approved` publishes the product, `rejected` keeps it hidden
+    pub decision: ModerationStatus,
+}
+
+/// Add a prohibited term
+#[utoipa::path(
+    post,
+    operation_id = "createProhibitedTerm",
+    path = "/api/v1/admin/prohibited-terms",
+    tag = "Admin",
+    request_body = CreateProhibitedTermRequest,
+    responses(
+        (status = 201, description = "Term added and applied to new listings", body = TermModel),
+        (status = 400, description = "Empty or overlong term or category"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Term already listed")
+    )
+)]
+pub async fn create_prohibited_term(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateProhibitedTermRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let term = normalize(&request.term);
+    let category = request.category.trim();
+    if term.is_empty() || term.len() > MAX_TERM_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("term must contain letters or digits and be at most {MAX_TERM_LEN} bytes"),
+        )
+            .into_response();
+    }
+    if category.is_empty() || category.len() > MAX_CATEGORY_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("category must be 1 to {MAX_CATEGORY_LEN} bytes"),
+        )
+            .into_response();
+    }
+    match ProhibitedTerm::find(&db, &term).await {
+        Ok(None) => {}
+        Ok(Some(existing)) => {
+            return (
+                StatusCode::CONFLICT,
+                format!("'{}' is already listed ({})", existing.term, existing.id),
+            )
+                .into_response()
+        }
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+
+    match ProhibitedTerm::create(&db, &term, category, request.action, &admin.relay_id).await {
+        Ok(created) => {
+            let rule = TermRule {
+                id: created.id,
+                term: created.term.clone(),
+                category: created.category.clone(),
+                action: request.action,
+            };
+            let event = create_event(
+                EventType::ProhibitedTermAdded,
+                created.id,
+                serde_json::to_value(&rule).unwrap_or_default(),
+            );
+            let _ = events.dispatch(event).await;
+            (StatusCode::CREATED, Json(created)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List prohibited terms
+#[utoipa::path(
+    get,
+    operation_id = "listProhibitedTerms",
+    path = "/api/v1/admin/prohibited-terms",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Terms in alphabetical order", body = ProhibitedTermsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_prohibited_terms(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match ProhibitedTerm::list(&db).await {
+        Ok(terms) => Json(ProhibitedTermsResponse { terms }).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Remove a prohibited term
+#[utoipa::path(
+    delete,
+    operation_id = "deleteProhibitedTerm",
+    path = "/api/v1/admin/prohibited-terms/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Term ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Term removed"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Term not found")
+    )
+)]
+pub async fn delete_prohibited_term(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match ProhibitedTerm::delete(&db, id).await {
+        Ok(true) => {
+            let event = create_event(EventType::ProhibitedTermRemoved, id, serde_json::json!({}));
+            let _ = events.dispatch(event).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Prohibited term not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Add a word or phrase to the profanity list
+#[utoipa::path(
+    post,
+    operation_id = "createProfanityTerm",
+    path = "/api/v1/admin/profanity-terms",
+    tag = "Admin",
+    request_body = CreateProfanityTermRequest,
+    responses(
+        (status = 201, description = "Phrase added and applied to new text", body = inline(ProfanityModel)),
+        (status = 400, description = "Empty or overlong phrase or category"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Phrase already listed")
+    )
+)]
+pub async fn create_profanity_term(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateProfanityTermRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let phrase = normalize_text(&request.phrase);
+    let category = request.category.trim();
+    if phrase.is_empty() || phrase.len() > MAX_TERM_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("phrase must contain letters or digits and be at most {MAX_TERM_LEN} bytes"),
+        )
+            .into_response();
+    }
+    if category.is_empty() || category.len() > MAX_CATEGORY_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("category must be 1 to {MAX_CATEGORY_LEN} bytes"),
+        )
+            .into_response();
+    }
+    match ProfanityTerm::find(&db, &phrase).await {
+        Ok(None) => {}
+        Ok(Some(existing)) => {
+            return (
+                StatusCode::CONFLICT,
+                format!("'{}' is already listed ({})", existing.phrase, existing.id),
+            )
+                .into_response()
+        }
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+
+    match ProfanityTerm::create(&db, &phrase, category, &admin.relay_id).await {
+        Ok(created) => {
+            let event = create_event(
+                EventType::ProfanityTermAdded,
+                created.id,
+                serde_json::to_value(profanity_rule(&created)).unwrap_or_default(),
+            );
+            let _ = events.dispatch(event).await;
+            (StatusCode::CREATED, Json(created)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List profanity terms
+#[utoipa::path(
+    get,
+    operation_id = "listProfanityTerms",
+    path = "/api/v1/admin/profanity-terms",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Phrases in alphabetical order", body = ProfanityTermsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_profanity_terms(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match ProfanityTerm::list(&db).await {
+        Ok(terms) => Json(ProfanityTermsResponse { terms }).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Remove a word or phrase from the profanity list
+#[utoipa::path(
+    delete,
+    operation_id = "deleteProfanityTerm",
+    path = "/api/v1/admin/profanity-terms/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Profanity term ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Phrase removed"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Phrase not found")
+    )
+)]
+pub async fn delete_profanity_term(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match ProfanityTerm::delete(&db, id).await {
+        Ok(true) => {
+            let event = create_event(EventType::ProfanityTermRemoved, id, serde_json::json!({}));
+            let _ = events.dispatch(event).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Profanity term not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Listings held by flagged terms
+#[utoipa::path(
+    get,
+    operation_id = "listModerationQueue",
+    path = "/api/v1/admin/moderation",
+    tag = "Admin",
+    params(
+        ("status" = Option<String>, Query, description = "pending (default), approved or rejected"),
+        ("tenant" = Option<String>, Query, description = "Tenant whose listings to show; defaults to the default tenant")
+    ),
+    responses(
+        (status = 200, description = "Queue entries and, for the pending queue, image flags and review anomalies", body = ModerationQueueResponse),
+        (status = 400, description = "Malformed tenant"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_moderation_queue(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    headers: HeaderMap,
+    Query(query): Query<ModerationQueueQuery>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    let tenant = query.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+    if let Err(err) = validate_tenant_id(tenant) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let status = query.status.unwrap_or(ModerationStatus::Pending);
+    let entries = match ProductModeration::list(&db, tenant, status).await {
+        Ok(entries) => entries,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let (image_flags, review_anomalies) = if status == ModerationStatus::Pending {
+        let flags = match MediaSimilarity::list(&db, tenant, FlagStatus::Pending).await {
+            Ok(flags) => flags,
+            Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        };
+        match ReviewAnomaly::list(&db, tenant, AnomalyStatus::Pending).await {
+            Ok(anomalies) => (flags, anomalies),
+            Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        }
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    Json(ModerationQueueResponse {
+        entries,
+        image_flags: image_flags
+            .into_iter()
+            .map(|flag| ImageFlagResponse::new(flag, &site))
+            .collect(),
+        review_anomalies,
+    })
+    .into_response()
+}
+
+/// Dismiss an image flag, or hold the listing that uploaded the image
+#[utoipa::path(
+    post,
+    operation_id = "reviewImageFlag",
+    path = "/api/v1/admin/moderation/image-flags/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Image flag ID", format = "uuid")
+    ),
+    request_body = ReviewImageFlagRequest,
+    responses(
+        (status = 200, description = "Decision recorded", body = ImageFlagResponse),
+        (status = 400, description = "Decision must be dismissed or held"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Image flag not found")
+    )
+)]
+pub async fn review_image_flag(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<ReviewImageFlagRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if request.decision == FlagStatus::Pending {
+        return (
+            StatusCode::BAD_REQUEST,
+            "decision must be dismissed or held",
+        )
+            .into_response();
+    }
+    match MediaSimilarity::review(&db, id, request.decision, &admin.relay_id).await {
+        Ok(Some(flag)) => Json(ImageFlagResponse::new(flag, &site)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Image flag not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Dismiss a review anomaly, or hide the reviews behind it
+#[utoipa::path(
+    post,
+    operation_id = "reviewReviewAnomaly",
+    path = "/api/v1/admin/moderation/review-anomalies/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Review anomaly ID", format = "uuid")
+    ),
+    request_body = ReviewAnomalyRequest,
+    responses(
+        (status = 200, description = "Decision recorded", body = AnomalyModel),
+        (status = 400, description = "Decision must be dismissed or hidden"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Review anomaly not found")
+    )
+)]
+pub async fn review_review_anomaly(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<ReviewAnomalyRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if request.decision == AnomalyStatus::Pending {
+        return (
+            StatusCode::BAD_REQUEST,
+            "decision must be dismissed or hidden",
+        )
+            .into_response();
+    }
+    match ReviewAnomaly::review(&db, id, request.decision, &admin.relay_id).await {
+        Ok(Some(anomaly)) => Json(anomaly).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Review anomaly not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Recompute every store's rating from its published reviews
+#[utoipa::path(
+    post,
+    operation_id = "recomputeStoreRatings",
+    path = "/api/v1/admin/store-ratings/recompute",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Ratings recomputed", body = RecomputeStoreRatingsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn recompute_store_ratings(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match StoreReview::recompute_ratings(&db).await {
+        Ok(stores) => Json(RecomputeStoreRatingsResponse { stores }).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Approve or reject a held listing
+#[utoipa::path(
+    post,
+    operation_id = "reviewProduct",
+    path = "/api/v1/admin/moderation/{product_id}",
+    tag = "Admin",
+    params(
+        ("product_id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    request_body = ReviewProductRequest,
+    responses(
+        (status = 200, description = "Decision recorded", body = ModerationModel),
+        (status = 400, description = "Decision must be approved or rejected"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Product is not in the moderation queue")
+    )
+)]
+pub async fn review_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(product_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<ReviewProductRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if request.decision == ModerationStatus::Pending {
+        return (
+            StatusCode::BAD_REQUEST,
+            "decision must be approved or rejected",
+        )
+            .into_response();
+    }
+    match ProductModeration::review(&db, product_id, request.decision, &admin.relay_id).await {
+        Ok(Some((entry, product))) => {
+            if product.is_published {
+                let event = create_event(
+                    EventType::ProductPublished,
+                    product.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "name": product.name,
+                        "price": product.price
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+            }
+            Json(entry).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            "Product is not in the moderation queue.",
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
@@ -0,0 +1,361 @@
+//! File uploads from `multipart/form-data` bodies, read once and within
+//! limits.
+//!
+//! Upload handlers take [`MultipartFile`] instead of axum's `Multipart`. The
+//! body is read field by field and refused as soon as it sends more fields
+//! than [`UploadLimits::max_fields`], a file larger than
+//! [`UploadLimits::max_file_bytes`], or a field its [`UploadForm`] doesn't
+//! name, so image analysis and storage only ever see the typed
+//! [`UploadedFile`].
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequest, Multipart, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bytes::{Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Field the product image uploads send their file in
+pub const FILE_FIELD: &str = "file";
+
+/// Room left in a request body for the part headers around the file
+const PART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// How much of a multipart body a handler reads before refusing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct UploadLimits {
+    /// Fields per request, files or not
+    pub max_fields: usize,
+    /// Bytes per file
+    pub max_file_bytes: usize,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            max_fields: 8,
+            max_file_bytes: 6 * 1024 * 1024,
+        }
+    }
+}
+
+impl UploadLimits {
+    /// Body limit for upload routes: one file of the largest size plus its
+    /// part headers. Bigger bodies are cut off before a field is parsed.
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_file_bytes.saturating_add(PART_OVERHEAD_BYTES)
+    }
+}
+
+/// The fields one upload endpoint accepts
+pub trait UploadForm: Send {
+    /// Names of the file fields; any other field is refused
+    const FILE_FIELDS: &'static [&'static str];
+    /// Files per request
+    const MAX_FILES: usize = 1;
+}
+
+/// A product image, sent in [`FILE_FIELD`]
+pub struct ProductImageForm;
+
+impl UploadForm for ProductImageForm {
+    const FILE_FIELDS: &'static [&'static str] = &[FILE_FIELD];
+}
+
+/// A file read from a multipart body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedFile {
+    /// File name the client sent, "unknown" when it sent none
+    pub name: String,
+    /// As declared by the client; image analysis checks it against the bytes
+    pub content_type: String,
+    pub bytes: Bytes,
+}
+
+/// Error codes of [`UploadRejection`]
+pub const INVALID_MULTIPART: &str = "INVALID_MULTIPART";
+pub const TOO_MANY_FIELDS: &str = "TOO_MANY_FIELDS";
+pub const FILE_TOO_LARGE: &str = "FILE_TOO_LARGE";
+pub const UNEXPECTED_FIELD: &str = "UNEXPECTED_FIELD";
+pub const TOO_MANY_FILES: &str = "TOO_MANY_FILES";
+pub const MISSING_FILE: &str = "MISSING_FILE";
+
+/// Why an upload was refused: 413 for the size limits, 422 for fields the
+/// form doesn't take or a missing file, 400 for a body that isn't multipart
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadRejection {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl UploadRejection {
+    fn new(status: StatusCode, code: &'static str, message: String) -> Self {
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+
+    fn malformed(err: axum::extract::multipart::MultipartError) -> Self {
+        // The body limit surfaces here, as a read error with status 413
+        let status = err.status();
+        let code = if status == StatusCode::PAYLOAD_TOO_LARGE {
+            FILE_TOO_LARGE
+        } else {
+            INVALID_MULTIPART
+        };
+        Self::new(status, code, err.body_text())
+    }
+}
+
+impl IntoResponse for UploadRejection {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+/// Read every field of `multipart` as a file of the form `F`, stopping at the
+/// first one that breaks a limit
+pub async fn read_files<F: UploadForm>(
+    multipart: &mut Multipart,
+    limits: &UploadLimits,
+) -> Result<Vec<UploadedFile>, UploadRejection> {
+    let mut files = Vec::new();
+    let mut fields = 0;
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(UploadRejection::malformed)?
+    {
+        fields += 1;
+        if fields > limits.max_fields {
+            return Err(UploadRejection::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                TOO_MANY_FIELDS,
+                format!(
+                    "At most {} multipart fields are accepted",
+                    limits.max_fields
+                ),
+            ));
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        if !F::FILE_FIELDS.contains(&name.as_str()) {
+            return Err(UploadRejection::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                UNEXPECTED_FIELD,
+                format!(
+                    "Unexpected field '{name}'; send the file in {}",
+                    F::FILE_FIELDS.join(" or ")
+                ),
+            ));
+        }
+        if files.len() == F::MAX_FILES {
+            return Err(UploadRejection::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                TOO_MANY_FILES,
+                format!("At most {} file(s) per upload", F::MAX_FILES),
+            ));
+        }
+        let file_name = field.file_name().unwrap_or("unknown").to_string();
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let mut bytes = BytesMut::new();
+        while let Some(chunk) = field.chunk().await.map_err(UploadRejection::malformed)? {
+            if bytes.len() + chunk.len() > limits.max_file_bytes {
+                return Err(UploadRejection::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    FILE_TOO_LARGE,
+                    format!(
+                        "'{file_name}' is larger than {} bytes",
+                        limits.max_file_bytes
+                    ),
+                ));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        // An empty part is a file input left blank
+        if !bytes.is_empty() {
+            files.push(UploadedFile {
+                name: file_name,
+                content_type,
+                bytes: bytes.freeze(),
+            });
+        }
+    }
+    if files.is_empty() {
+        return Err(UploadRejection::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            MISSING_FILE,
+            format!("No file uploaded in {}", F::FILE_FIELDS.join(" or ")),
+        ));
+    }
+    Ok(files)
+}
+
+/// The single file of an `F` upload, read within the state's
+/// [`UploadLimits`]
+pub struct MultipartFile<F = ProductImageForm> {
+    pub file: UploadedFile,
+    form: PhantomData<F>,
+}
+
+#[async_trait]
+impl<S, F> FromRequest<S> for MultipartFile<F>
+where
+    Arc<UploadLimits>: FromRef<S>,
+    S: Send + Sync,
+    F: UploadForm,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let limits = Arc::<UploadLimits>::from_ref(state);
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut files = read_files::<F>(&mut multipart, &limits)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Self {
+            file: files.remove(0),
+            form: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::header;
+
+    const BOUNDARY: &str = "X-UPLOAD-BOUNDARY";
+
+    /// Two images at most, to test forms taking several files
+    struct GalleryForm;
+
+    impl UploadForm for GalleryForm {
+        const FILE_FIELDS: &'static [&'static str] = &["file"];
+        const MAX_FILES: usize = 2;
+    }
+
+    /// A multipart body of `(field, file name, bytes)` parts
+    fn body(parts: &[(&str, &str, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (field, file_name, bytes) in parts {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{field}\"; filename=\"{file_name}\"\r\nContent-Type: image/png\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(bytes);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    async fn read<F: UploadForm>(
+        body: Vec<u8>,
+        limits: UploadLimits,
+    ) -> Result<Vec<UploadedFile>, UploadRejection> {
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+        read_files::<F>(&mut multipart, &limits).await
+    }
+
+    fn refused(result: Result<Vec<UploadedFile>, UploadRejection>) -> (StatusCode, &'static str) {
+        let rejection = result.unwrap_err();
+        (rejection.status, rejection.code)
+    }
+
+    #[tokio::test]
+    async fn test_one_file_is_read_with_its_name_and_type() {
+        let files = read::<ProductImageForm>(
+            body(&[("file", "mask.png", b"\x89PNG")]),
+            UploadLimits::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            files,
+            [UploadedFile {
+                name: "mask.png".to_string(),
+                content_type: "image/png".to_string(),
+                bytes: Bytes::from_static(b"\x89PNG"),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_field_flooding_is_cut_off_at_the_limit() {
+        // Counted before the files, so the limit holds for any form
+        let limits = UploadLimits {
+            max_fields: 2,
+            ..UploadLimits::default()
+        };
+        let parts = vec![("file", "a.png", &b"x"[..]); 5_000];
+        assert_eq!(
+            refused(read::<GalleryForm>(body(&parts), limits).await),
+            (StatusCode::PAYLOAD_TOO_LARGE, TOO_MANY_FIELDS)
+        );
+        // Fields the form doesn't take are refused at the first one
+        let parts = vec![("x", "a.png", &b"x"[..]); 5_000];
+        assert_eq!(
+            refused(read::<ProductImageForm>(body(&parts), limits).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, UNEXPECTED_FIELD)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_missing_or_empty_file_is_refused() {
+        let limits = UploadLimits::default();
+        assert_eq!(
+            refused(read::<ProductImageForm>(body(&[]), limits).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, MISSING_FILE)
+        );
+        assert_eq!(
+            refused(read::<ProductImageForm>(body(&[("file", "a.png", b"")]), limits).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, MISSING_FILE)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_files_beyond_the_form_or_size_limit_are_refused() {
+        let limits = UploadLimits {
+            max_fields: 8,
+            max_file_bytes: 4,
+        };
+        let two = body(&[("file", "a.png", b"aa"), ("file", "b.png", b"bb")]);
+        assert_eq!(
+            refused(read::<ProductImageForm>(two.clone(), limits).await),
+            (StatusCode::UNPROCESSABLE_ENTITY, TOO_MANY_FILES)
+        );
+        let files = read::<GalleryForm>(two, limits).await.unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a.png", "b.png"]);
+
+        assert_eq!(
+            refused(read::<ProductImageForm>(body(&[("file", "a.png", b"12345")]), limits).await),
+            (StatusCode::PAYLOAD_TOO_LARGE, FILE_TOO_LARGE)
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! The caller's notification delivery preferences; see `crate::notifications`
+
+use crate::auth::claims_from_headers;
+use crate::db::notifications::NotificationPreferences;
+use crate::notifications::{DeliveryMode, NotificationKind};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct NotificationPreference {
+    pub kind: NotificationKind,
+    pub mode: DeliveryMode,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateNotificationPreferencesRequest {
+    /// Kinds left out keep their current mode
+    pub preferences: Vec<NotificationPreference>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NotificationPreferencesResponse {
+    /// Every kind, `immediate` unless set otherwise
+    pub preferences: Vec<NotificationPreference>,
+}
+
+impl From<BTreeMap<NotificationKind, DeliveryMode>> for NotificationPreferencesResponse {
+    fn from(modes: BTreeMap<NotificationKind, DeliveryMode>) -> Self {
+        Self {
+            preferences: modes
+                .into_iter()
+                .map(|(kind, mode)| NotificationPreference { kind, mode })
+                .collect(),
+        }
+    }
+}
+
+fn user_id(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    claims_from_headers(headers)
+        .map(|claims| claims.relay_id)
+        .ok_or_else(|| {
+            (
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token".to_string(),
+            )
+        })
+}
+
+/// How the caller's notifications are delivered, per kind
+#[utoipa::path(
+    get,
+    operation_id = "getNotificationPreferences",
+    path = "/api/v1/users/me/notification-preferences",
+    tag = "Users",
+    responses(
+        (status = 200, description = "The mode of every notification kind", body = NotificationPreferencesResponse),
+        (status = 401, description = "Missing or invalid Authorization token")
+    )
+)]
+pub async fn get_notification_preferences(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let user = match user_id(&headers) {
+        Ok(user) => user,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match NotificationPreferences::for_user(&db, &user).await {
+        Ok(modes) => Json(NotificationPreferencesResponse::from(modes)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Choose, per kind, between immediate notifications and an hourly or
+/// daily digest. Notifications already queued keep the mode they were
+/// queued with.
+#[utoipa::path(
+    put,
+    operation_id = "updateNotificationPreferences",
+    path = "/api/v1/users/me/notification-preferences",
+    tag = "Users",
+    request_body = UpdateNotificationPreferencesRequest,
+    responses(
+        (status = 200, description = "Saved; the mode of every notification kind", body = NotificationPreferencesResponse),
+        (status = 400, description = "A kind is listed more than once"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 422, description = "Unknown kind or mode")
+    )
+)]
+pub async fn update_notification_preferences(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateNotificationPreferencesRequest>,
+) -> impl IntoResponse {
+    let user = match user_id(&headers) {
+        Ok(user) => user,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut seen = BTreeSet::new();
+    if let Some(repeated) = request
+        .preferences
+        .iter()
+        .find(|preference| !seen.insert(preference.kind))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("{} is listed more than once", repeated.kind.as_str()),
+        )
+            .into_response();
+    }
+    let modes: Vec<(NotificationKind, DeliveryMode)> = request
+        .preferences
+        .iter()
+        .map(|preference| (preference.kind, preference.mode))
+        .collect();
+    if let Err(e) = NotificationPreferences::set(&db, &user, &modes).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+    match NotificationPreferences::for_user(&db, &user).await {
+        Ok(modes) => Json(NotificationPreferencesResponse::from(modes)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
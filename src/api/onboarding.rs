@@ -0,0 +1,220 @@
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::db::onboarding::{progress, OnboardingStep, StepStatus};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, ToSchema)]
+pub struct OnboardingResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// In the order the seller is asked to do them; skip unknown steps
+    pub steps: Vec<StepStatus>,
+    /// Share of the steps completed, 0 to 100
+    pub percent_complete: u8,
+    /// First step still to do; null once every step is done
+    pub next_step: Option<OnboardingStep>,
+    /// What to tell the seller about `next_step`
+    pub next_action: Option<String>,
+}
+
+impl OnboardingResponse {
+    fn new(store_id: Uuid, steps: Vec<StepStatus>) -> Self {
+        let completed = steps.iter().filter(|status| status.completed).count();
+        let percent_complete = (completed * 100).checked_div(steps.len()).unwrap_or(100) as u8;
+        let next_step = steps
+            .iter()
+            .find(|status| !status.completed)
+            .map(|status| status.step);
+        Self {
+            store_id,
+            steps,
+            percent_complete,
+            next_step,
+            next_action: next_step.map(|step| step.action().to_string()),
+        }
+    }
+}
+
+/// The store owner's setup checklist
+#[utoipa::path(
+    get,
+    operation_id = "getStoreOnboarding",
+    path = "/api/v1/stores/{id}/onboarding",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Each step's status and the next one to do", body = OnboardingResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn store_onboarding(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    match progress(&db, &store).await {
+        Ok(steps) => Json(OnboardingResponse::new(store.id, steps)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{products, stores};
+    use crate::auth::JwtService;
+    use crate::db::testing::{seed_store, sqlite};
+    use axum::{
+        body::Body,
+        http::{header, Request},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn app(db: DatabaseConnection) -> Router {
+        Router::new()
+            .route("/stores/:id/onboarding", get(store_onboarding))
+            .with_state(db.clone())
+            .merge(stores::router(db.clone()))
+            .merge(products::router(db))
+    }
+
+    fn token(relay_id: &str) -> String {
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token(relay_id.into(), String::new(), "default".into())
+            .unwrap();
+        format!("Bearer {token}")
+    }
+
+    async fn send(
+        db: &DatabaseConnection,
+        method: &str,
+        uri: &str,
+        caller: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, token(caller))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app(db.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    async fn done_steps(db: &DatabaseConnection, store_id: Uuid) -> (Vec<String>, u64) {
+        let (status, body) = send(
+            db,
+            "GET",
+            &format!("/stores/{store_id}/onboarding"),
+            "seller-1",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let done = body["steps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|status| status["completed"] == true)
+            .map(|status| status["step"].as_str().unwrap().to_string())
+            .collect();
+        (done, body["percent_complete"].as_u64().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_steps_complete_as_the_seller_sets_up() {
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        assert_eq!(
+            done_steps(&db, store_id).await,
+            (vec!["store_created".into()], 25)
+        );
+        let (status, _) = send(
+            &db,
+            "GET",
+            &format!("/stores/{store_id}/onboarding"),
+            "seller-2",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(
+            &db,
+            "PUT",
+            &format!("/stores/{store_id}"),
+            "seller-1",
+            serde_json::json!({
+                "name": "Mama Ngono",
+                "logo_url": "https://cdn.example.com/logo.png"
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(done_steps(&db, store_id).await.1, 50);
+
+        let (status, body) = send(
+            &db,
+            "POST",
+            "/products",
+            "seller-1",
+            serde_json::json!({
+                "store_id": store_id,
+                "name": "Ndole",
+                "price": 1500.0,
+                "quantity_available": 3,
+                "draft": true
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(done_steps(&db, store_id).await.1, 75);
+
+        // Only the owner's share counts
+        let share = format!("/stores/{store_id}/share");
+        let (status, _) = send(&db, "GET", &share, "buyer-1", serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(done_steps(&db, store_id).await.1, 75);
+        send(&db, "GET", &share, "seller-1", serde_json::Value::Null).await;
+        let (status, body) = send(
+            &db,
+            "GET",
+            &format!("/stores/{store_id}/onboarding"),
+            "seller-1",
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["percent_complete"], 100);
+        assert_eq!(body["steps"].as_array().unwrap().len(), 4);
+        assert!(body["next_step"].is_null());
+    }
+}
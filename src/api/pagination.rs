@@ -0,0 +1,248 @@
+//! One page of a list, and the query parameters that pick it.
+//!
+//! Numbered lists are paged with `page` and `per_page`; lists read newest
+//! first by cursor, like change history, take `cursor` instead and answer
+//! with the next one. Both are clamped here, so handlers never see a page
+//! size outside `1..=MAX_PER_PAGE`.
+
+use crate::api::fields::FieldSelection;
+use crate::api::products::ProductResponse;
+use crate::api::questions::QuestionResponse;
+use crate::api::store_reviews::StoreReviewResponse;
+use crate::db::history::HistoryEntry;
+use crate::entity::store::Model as StoreModel;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_PER_PAGE: u64 = 20;
+pub const MAX_PER_PAGE: u64 = 100;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// Page number, starting at 1
+    pub page: Option<u64>,
+    /// Items per page (default 20, max 100)
+    pub per_page: Option<u64>,
+    /// `next_cursor` from the previous page, on lists paged by cursor
+    pub cursor: Option<String>,
+}
+
+/// [`PageParams`] once checked and clamped; extract it next to the
+/// handler's own `Query`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+    pub page: u64,
+    pub per_page: u64,
+    pub cursor: Option<String>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+            cursor: None,
+        }
+    }
+}
+
+impl TryFrom<PageParams> for PageRequest {
+    type Error = String;
+
+    fn try_from(params: PageParams) -> Result<Self, Self::Error> {
+        if params.cursor.is_some() && params.page.is_some() {
+            return Err("Send either page or cursor, not both".to_string());
+        }
+        Ok(Self {
+            page: params.page.unwrap_or(1).max(1),
+            per_page: params
+                .per_page
+                .unwrap_or(DEFAULT_PER_PAGE)
+                .clamp(1, MAX_PER_PAGE),
+            cursor: params.cursor,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PageRequest {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<PageParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| (StatusCode::BAD_REQUEST, rejection.body_text()))?;
+        PageRequest::try_from(params).map_err(|err| (StatusCode::BAD_REQUEST, err))
+    }
+}
+
+impl PageRequest {
+    /// Rows to skip before this page
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// This page of a numbered list already read whole
+    pub fn slice<T>(&self, items: Vec<T>) -> Paginated<T> {
+        let total = items.len() as u64;
+        let items = items
+            .into_iter()
+            .skip(usize::try_from(self.offset()).unwrap_or(usize::MAX))
+            .take(self.per_page as usize)
+            .collect();
+        self.numbered(items, total)
+    }
+
+    /// A page of a numbered list the database already cut
+    pub fn numbered<T>(&self, items: Vec<T>, total: u64) -> Paginated<T> {
+        Paginated {
+            items,
+            total,
+            page: Some(self.page),
+            per_page: self.per_page,
+            next_cursor: None,
+        }
+    }
+
+    /// This page of `first` followed by `rest`, where the database already
+    /// cut `first` to this page of a list `first_total` long and `rest` is
+    /// read whole
+    pub fn numbered_then<T>(&self, first: Vec<T>, first_total: u64, rest: Vec<T>) -> Paginated<T> {
+        let total = first_total + rest.len() as u64;
+        let skip = self.offset().saturating_sub(first_total);
+        let room = (self.per_page as usize).saturating_sub(first.len());
+        let mut items = first;
+        items.extend(
+            rest.into_iter()
+                .skip(usize::try_from(skip).unwrap_or(usize::MAX))
+                .take(room),
+        );
+        self.numbered(items, total)
+    }
+
+    /// A page of a list paged by cursor
+    pub fn by_cursor<T>(
+        &self,
+        items: Vec<T>,
+        total: u64,
+        next_cursor: Option<String>,
+    ) -> Paginated<T> {
+        Paginated {
+            items,
+            total,
+            page: None,
+            per_page: self.per_page,
+            next_cursor,
+        }
+    }
+}
+
+/// One page of a list
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(
+    ProductsPage = Paginated<ProductResponse>,
+    StoresPage = Paginated<StoreModel>,
+    QuestionsPage = Paginated<QuestionResponse>,
+    StoreReviewsPage = Paginated<StoreReviewResponse>,
+    HistoryPage = Paginated<HistoryEntry>
+)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Items in the whole list
+    pub total: u64,
+    /// Page number; null on lists paged by cursor
+    pub page: Option<u64>,
+    pub per_page: u64,
+    /// Pass back as `cursor` for the next page; null on the last page and on
+    /// numbered lists
+    pub next_cursor: Option<String>,
+}
+
+impl<T: Serialize> Paginated<T> {
+    /// The same page with only `fields` of each item
+    pub fn project(self, fields: Option<&FieldSelection>) -> Paginated<Value> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| match fields {
+                Some(fields) => fields.project(item),
+                None => serde_json::to_value(item).unwrap_or(Value::Null),
+            })
+            .collect();
+        Paginated {
+            items,
+            total: self.total,
+            page: self.page,
+            per_page: self.per_page,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(
+        page: Option<u64>,
+        per_page: Option<u64>,
+        cursor: Option<&str>,
+    ) -> Result<PageRequest, String> {
+        PageRequest::try_from(PageParams {
+            page,
+            per_page,
+            cursor: cursor.map(str::to_owned),
+        })
+    }
+
+    #[test]
+    fn test_params_are_clamped() {
+        assert_eq!(request(None, None, None), Ok(PageRequest::default()));
+        let clamped = request(Some(0), Some(10_000), None).unwrap();
+        assert_eq!((clamped.page, clamped.per_page), (1, MAX_PER_PAGE));
+        assert_eq!(request(Some(3), Some(0), None).unwrap().per_page, 1);
+        assert!(request(Some(2), None, Some("abc")).is_err());
+    }
+
+    #[test]
+    fn test_slice_reports_the_whole_list() {
+        let page = request(Some(2), Some(2), None)
+            .unwrap()
+            .slice(vec![1, 2, 3, 4, 5]);
+        assert_eq!(page.items, vec![3, 4]);
+        assert_eq!((page.total, page.page), (5, Some(2)));
+        let past_end = request(Some(u64::MAX), Some(2), None)
+            .unwrap()
+            .slice(vec![1]);
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.total, 1);
+    }
+
+    #[test]
+    fn test_numbered_then_matches_slicing_the_whole_list() {
+        let (first, rest) = ((0..5).collect::<Vec<u32>>(), vec![100, 101, 102]);
+        let whole: Vec<u32> = first.iter().chain(&rest).copied().collect();
+        for per_page in 1..=10 {
+            for number in 1..=10 {
+                let page = request(Some(number), Some(per_page), None).unwrap();
+                let cut: Vec<u32> = first
+                    .iter()
+                    .skip(page.offset() as usize)
+                    .take(per_page as usize)
+                    .copied()
+                    .collect();
+                let paged = page.numbered_then(cut, first.len() as u64, rest.clone());
+                let sliced = page.slice(whole.clone());
+                assert_eq!(paged.items, sliced.items, "page {number} of {per_page}");
+                assert_eq!(paged.total, 8);
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Where a store gets paid. Owners set and read their own account; admins
+//! verify it. Account numbers only ever leave the server masked.
+
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store_by_token;
+use crate::crypto::field::FieldCipher;
+use crate::db::history::ChangeOrigin;
+use crate::db::payout_accounts::{
+    mask_account_number, PayoutAccount, PayoutAccountInput, PayoutProvider,
+};
+use crate::entity::store_payout_account::Model as PayoutAccountModel;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const MIN_ACCOUNT_DIGITS: usize = 8;
+const MAX_ACCOUNT_DIGITS: usize = 15;
+const MAX_ACCOUNT_NAME_LEN: usize = 100;
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetPayoutAccountRequest {
+    pub provider: PayoutProvider,
+    /// Mobile-money number, 8 to 15 digits; spaces and a leading `+` are dropped
+    pub account_number: String,
+    /// Name registered with the provider
+    pub account_name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyPayoutAccountRequest {
+    /// `false` withdraws an earlier verification
+    pub verified: bool,
+}
+
+/// A payout account with its number masked
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PayoutAccountResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    pub provider: String,
+    /// All but the last four digits hidden, e.g. `*****3456`
+    pub account_number: String,
+    pub account_name: String,
+    pub verified: bool,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PayoutAccountResponse {
+    fn new(
+        cipher: &FieldCipher,
+        account: PayoutAccountModel,
+    ) -> Result<Self, (StatusCode, String)> {
+        let number = cipher
+            .decrypt(&account.account_number_encrypted)
+            .map_err(|err| {
+                tracing::error!(
+                    store_id = %account.store_id,
+                    "Payout account number could not be decrypted: {}",
+                    err
+                );
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read payout account".to_string(),
+                )
+            })?;
+        Ok(Self {
+            store_id: account.store_id,
+            provider: account.provider,
+            account_number: mask_account_number(&number),
+            account_name: account.account_name,
+            verified: account.verified,
+            verified_at: account.verified_at,
+            updated_at: account.updated_at,
+        })
+    }
+}
+
+/// Digits of a mobile-money number, or why it is not one
+fn normalize_account_number(raw: &str) -> Result<String, String> {
+    let number: String = raw
+        .trim()
+        .trim_start_matches('+')
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if !number.chars().all(|c| c.is_ascii_digit())
+        || !(MIN_ACCOUNT_DIGITS..=MAX_ACCOUNT_DIGITS).contains(&number.len())
+    {
+        return Err(format!(
+            "account_number must be {MIN_ACCOUNT_DIGITS} to {MAX_ACCOUNT_DIGITS} digits"
+        ));
+    }
+    Ok(number)
+}
+
+fn require_cipher(cipher: &FieldCipher) -> Result<(), (StatusCode, String)> {
+    if cipher.is_configured() {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Payout accounts are not available".to_string(),
+        ))
+    }
+}
+
+/// Set the account a store is paid out to
+#[utoipa::path(
+    put,
+    operation_id = "setPayoutAccount",
+    path = "/api/v1/stores/{id}/payout-account",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = SetPayoutAccountRequest,
+    responses(
+        (status = 200, description = "Account saved; any change clears its verification", body = PayoutAccountResponse),
+        (status = 400, description = "Malformed account number or name"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found"),
+        (status = 503, description = "Field encryption is not configured")
+    )
+)]
+pub async fn set_payout_account(
+    State(db): State<DatabaseConnection>,
+    State(cipher): State<Arc<FieldCipher>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<SetPayoutAccountRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_cipher(&cipher) {
+        return rejection.into_response();
+    }
+    let owner = match owned_store_by_token(&db, &headers, store_id).await {
+        Ok((_, claims)) => claims.relay_id,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let account_number = match normalize_account_number(&payload.account_number) {
+        Ok(number) => number,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let account_name = payload.account_name.trim();
+    if account_name.is_empty() || account_name.chars().count() > MAX_ACCOUNT_NAME_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("account_name must be 1 to {MAX_ACCOUNT_NAME_LEN} characters"),
+        )
+            .into_response();
+    }
+    let input = PayoutAccountInput {
+        provider: payload.provider,
+        account_number,
+        account_name: account_name.to_string(),
+    };
+    let origin = ChangeOrigin::edit(Some(owner.as_str()));
+    match PayoutAccount::set(&db, &cipher, store_id, &input, origin).await {
+        Ok(account) => match PayoutAccountResponse::new(&cipher, account) {
+            Ok(response) => Json(response).into_response(),
+            Err(rejection) => rejection.into_response(),
+        },
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// A store's payout account, masked
+#[utoipa::path(
+    get,
+    operation_id = "getPayoutAccount",
+    path = "/api/v1/stores/{id}/payout-account",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "The account with all but the last four digits hidden", body = PayoutAccountResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found, or no account set"),
+        (status = 503, description = "Field encryption is not configured")
+    )
+)]
+pub async fn get_payout_account(
+    State(db): State<DatabaseConnection>,
+    State(cipher): State<Arc<FieldCipher>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_cipher(&cipher) {
+        return rejection.into_response();
+    }
+    if let Err(rejection) = owned_store_by_token(&db, &headers, store_id).await {
+        return rejection.into_response();
+    }
+    masked_account(&db, &cipher, store_id).await
+}
+
+/// A store's payout account, for review before verifying it
+#[utoipa::path(
+    get,
+    operation_id = "adminGetPayoutAccount",
+    path = "/api/v1/admin/stores/{id}/payout-account",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "The account with all but the last four digits hidden", body = PayoutAccountResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "No account set"),
+        (status = 503, description = "Field encryption is not configured")
+    )
+)]
+pub async fn admin_get_payout_account(
+    State(db): State<DatabaseConnection>,
+    State(cipher): State<Arc<FieldCipher>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    if let Err(rejection) = require_cipher(&cipher) {
+        return rejection.into_response();
+    }
+    masked_account(&db, &cipher, store_id).await
+}
+
+/// Mark a store's payout account verified, or withdraw the verification
+#[utoipa::path(
+    post,
+    operation_id = "verifyPayoutAccount",
+    path = "/api/v1/admin/stores/{id}/payout-account/verify",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = VerifyPayoutAccountRequest,
+    responses(
+        (status = 200, description = "Verification updated", body = PayoutAccountResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "No account set"),
+        (status = 503, description = "Field encryption is not configured")
+    )
+)]
+pub async fn verify_payout_account(
+    State(db): State<DatabaseConnection>,
+    State(cipher): State<Arc<FieldCipher>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<VerifyPayoutAccountRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(rejection) = require_cipher(&cipher) {
+        return rejection.into_response();
+    }
+    match PayoutAccount::set_verified(&db, store_id, payload.verified, &admin.relay_id).await {
+        Ok(Some(account)) => {
+            tracing::warn!(
+                store_id = %store_id,
+                admin = %admin.relay_id,
+                verified = payload.verified,
+                "Payout account verification changed"
+            );
+            match PayoutAccountResponse::new(&cipher, account) {
+                Ok(response) => Json(response).into_response(),
+                Err(rejection) => rejection.into_response(),
+            }
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Payout account not found").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+async fn masked_account(
+    db: &DatabaseConnection,
+    cipher: &FieldCipher,
+    store_id: Uuid,
+) -> axum::response::Response {
+    match PayoutAccount::get(db, store_id).await {
+        Ok(Some(account)) => match PayoutAccountResponse::new(cipher, account) {
+            Ok(response) => Json(response).into_response(),
+            Err(rejection) => rejection.into_response(),
+        },
+        Ok(None) => (StatusCode::NOT_FOUND, "Payout account not found").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::field::{FieldKey, KEY_LEN};
+
+    fn account(number_encrypted: String) -> PayoutAccountModel {
+        let now = Utc::now();
+        PayoutAccountModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            provider: "orange_money".to_string(),
+            account_number_encrypted: number_encrypted,
+            account_name: "Ngono Marie".to_string(),
+            verified: false,
+            verified_by: None,
+            verified_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_response_masks_the_number() {
+        let cipher = FieldCipher::new(vec![FieldKey::new("k1", [5; KEY_LEN])]);
+        let sealed = cipher.encrypt("699887766").unwrap();
+        let response = PayoutAccountResponse::new(&cipher, account(sealed.clone())).unwrap();
+        assert_eq!(response.account_number, "*****7766");
+        let body = serde_json::to_string(&response).unwrap();
+        assert!(!body.contains("699887766"));
+        assert!(!body.contains(&sealed));
+
+        // Another deployment's key can't open it
+        let stranger = FieldCipher::new(vec![FieldKey::new("k1", [6; KEY_LEN])]);
+        let (status, _) = PayoutAccountResponse::new(&stranger, account(sealed)).unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_account_numbers_are_normalized() {
+        assert_eq!(
+            normalize_account_number(" +237 677 12 34 56 ").unwrap(),
+            "237677123456"
+        );
+        assert!(normalize_account_number("6771").is_err());
+        assert!(normalize_account_number("677-123-456").is_err());
+        assert!(normalize_account_number("1234567890123456").is_err());
+    }
+}
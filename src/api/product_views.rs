@@ -0,0 +1,207 @@
+//! Product views and the figures sellers see about them; see
+//! `crate::product_views`.
+
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::db::product_views::{ProductViews, ViewCounts};
+use crate::db::products::Product;
+use crate::db::stores::Store;
+use crate::product_views::{viewer_hash, ViewRecorder};
+use axum::{
+    extract::{FromRef, Request, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::debug;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductStatsResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    /// Viewers counted once per dedup window
+    pub views: ViewCounts,
+    /// Visible reviews of the product's store; products aren't reviewed on
+    /// their own
+    pub review_count: i32,
+}
+
+/// Count a view of a product page. Always accepted: the view is only
+/// queued, and dropped when it repeats a recent one or the queue is full.
+#[utoipa::path(
+    post,
+    operation_id = "recordProductView",
+    path = "/api/v1/products/{id}/view",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 202, description = "View queued, or dropped as a repeat"),
+        (status = 400, description = "Invalid product ID")
+    )
+)]
+pub async fn record_product_view(
+    State(recorder): State<Arc<ViewRecorder>>,
+    UuidPath(id): UuidPath<Uuid>,
+    request: Request,
+) -> StatusCode {
+    if let Some(viewer) = viewer_hash(&request) {
+        if !recorder.record(id, viewer, Utc::now()) {
+            debug!(product_id = %id, "View queue full, view dropped");
+        }
+    }
+    StatusCode::ACCEPTED
+}
+
+/// Views and reviews of a product, for its store owner and admins
+#[utoipa::path(
+    get,
+    operation_id = "getProductStats",
+    path = "/api/v1/products/{id}/stats",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "View counts and review count", body = ProductStatsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Product not found")
+    )
+)]
+pub async fn product_stats(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let store = if require_admin(&headers).is_ok() {
+        Store::get(&db, product.store_id)
+            .await
+            .map_err(|err| (StatusCode::NOT_FOUND, err))
+    } else {
+        owned_store(&db, &headers, product.store_id, ApiScope::ProductsRead).await
+    };
+    let store = match store {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let views = match ProductViews::counts(&db, id, Utc::now()).await {
+        Ok(views) => views,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    Json(ProductStatsResponse {
+        product_id: id,
+        views,
+        review_count: store.store_review_count,
+    })
+    .into_response()
+}
+
+/// The view route. Merge it behind the token check: anyone may view a
+/// product, but anonymous viewers go through the public-path policy and its
+/// per-address limit like any other public call.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Arc<ViewRecorder>: FromRef<S>,
+{
+    Router::new().route("/api/v1/products/:id/view", post(record_product_view))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::JwtService;
+    use crate::db::product_views::NewProductView;
+    use crate::db::testing::{seed_product, sqlite};
+    use axum::{body::Body, http::header, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        recorder: Arc<ViewRecorder>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<ViewRecorder> {
+        fn from_ref(state: &TestState) -> Self {
+            state.recorder.clone()
+        }
+    }
+
+    fn as_seller(uri: &str, seller: &str) -> Request<Body> {
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token(seller.into(), String::new(), "default".into())
+            .unwrap();
+        Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_views_are_queued_and_counted_for_the_owner_only() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller-1").await;
+        let (recorder, mut queue) = ViewRecorder::new();
+        let app = Router::new()
+            .route("/api/v1/products/:id/stats", get(product_stats))
+            .merge(router())
+            .with_state(TestState {
+                db: db.clone(),
+                recorder: Arc::new(recorder),
+            });
+
+        let view = Request::builder()
+            .method("POST")
+            .uri(format!("/api/v1/products/{product_id}/view"))
+            .header("x-forwarded-for", "203.0.113.7")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(view).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let queued: NewProductView = queue.try_recv().unwrap();
+        assert_eq!(queued.product_id, product_id);
+        ProductViews::insert_batch(&db, &[queued]).await.unwrap();
+
+        let uri = format!("/api/v1/products/{product_id}/stats");
+        let response = app
+            .clone()
+            .oneshot(as_seller(&uri, "seller-2"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app.oneshot(as_seller(&uri, "seller-1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats["views"]["total"], 1);
+        assert_eq!(stats["views"]["last_7_days"], 1);
+        assert_eq!(stats["review_count"], 0);
+    }
+}
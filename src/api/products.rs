@@ -1,37 +1,113 @@
+use crate::api::bundles::announce_deactivated;
+use crate::api::extract::UuidPath;
+use crate::api::fields::{FieldSelection, PRODUCT_FIELDS};
 use crate::api::image_analysis::ImageAnalysisService;
-use crate::api::media_storage::{MediaStorage, S3MediaStorage, StubMediaStorage};
-use crate::auth::JwtService;
-use crate::db::products::Product;
+use crate::api::image_conversion::{store_webp_variant, WebpConverter};
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::api::media_storage::StorageUnavailableResponse;
+use crate::api::media_storage::{
+    connect_s3_storage, storage_unavailable_response, BreakerState, MediaStorage,
+    StorageConnectError, StubMediaStorage, S3_BREAKER,
+};
+use crate::api::moderation::ProhibitedTermRejection;
+use crate::api::moderation::{hold_listing, screen_listing};
+#[allow(unused_imports)]
+use crate::api::multipart::UploadRejection;
+use crate::api::multipart::{MultipartFile, UploadLimits};
+use crate::api::pagination::{PageParams, PageRequest};
+use crate::api::stores::owned_store;
+use crate::api::transaction::{transaction_middleware, Tx};
+use crate::api::validation::{validate_product, ProductInput, ValidationReport, MAX_NAME_LEN};
+use crate::auth::{authenticate, claims_from_headers, ApiScope, JwtService};
+use crate::currency::DEFAULT_CURRENCY;
+use crate::db::category_attributes::AttributeFilter;
+use crate::db::delivery::{unavailable_items, DeliveryMethod, DeliveryOptionsInput};
+use crate::db::history::ChangeOrigin;
+use crate::db::media_quota::{MediaCharge, MediaLimits, MediaQuota};
+use crate::db::media_similarity::MediaSimilarity;
+use crate::db::product_media::{content_hash, ProductMedia};
+use crate::db::products::{
+    active_sale, currency_error, discount_percent, effective_price, low_stock_crossing,
+    PriceFilter, Product, ProductPatch, ProductSort, PublicationStatus, PublishOutcome,
+    ReturnPolicySource, Sale, SKU_TAKEN,
+};
+use crate::db::return_policy::ReturnTerms;
+use crate::db::stores::{is_paused, Store};
+use crate::db::watches::ProductWatch;
 use crate::entity::product::Model as ProductModel;
+use crate::entity::product_media::Model as ProductMediaModel;
+use crate::entity::store::Model as StoreModel;
+use crate::error::AppError;
 use crate::events::{
-    create_event, EventDispatcher, EventType, LoggingEventHandler, WebSocketEventHandler,
+    create_event, Event, EventDispatcher, EventType, LoggingEventHandler, WebSocketEventHandler,
 };
+use crate::moderation::image_hash::dhash_async;
+use crate::money::{Locale, LocaleParams, Money};
+use crate::tenant::tenant_from_headers;
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{DefaultBodyLimit, FromRef, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use tracing::error;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Deserialize, ToSchema)]
+#[derive(Deserialize, Serialize, ToSchema)]
 #[allow(dead_code)]
 pub struct CreateProductRequest {
-    #[schema(value_type = String, format = "uuid")]
-    pub store_id: Uuid,
+    /// Store to list the product in; may be omitted by a seller who owns
+    /// exactly one store, and by an API key, which uses its own store
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
     pub sku: Option<String>,
     pub name: String,
     pub description: Option<String>,
     #[schema(value_type = String, format = "uuid")]
     pub image_id: Option<Uuid>,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Attributes defined for the category, e.g. `{"condition": "used"}`
+    #[schema(value_type = Option<Object>)]
+    pub attributes: Option<serde_json::Value>,
     pub price: f64,
+    /// ISO 4217 code of the prices, e.g. `NGN`; the deployment's default
+    /// currency when left out
+    pub currency: Option<String>,
     pub quantity_available: i32,
+    /// Get a `ProductLowStock` event when stock falls to this level
+    pub low_stock_threshold: Option<i32>,
+    /// Legacy free-text policy, read into the structured fields when they are
+    /// omitted. Falls back to the store's default when this is empty too
+    pub return_policy: Option<String>,
+    /// Set this to describe the policy with structured fields
+    pub returns_accepted: Option<bool>,
+    /// 0–90 days; requires `returns_accepted: true`
+    pub return_window_days: Option<i32>,
+    pub return_conditions: Option<String>,
+    /// How the product reaches the buyer; omitted fields, or the whole object,
+    /// take the store's defaults
+    pub delivery_options: Option<DeliveryOptionsInput>,
+    /// Keep the product hidden from public listings until this time (must be in the future)
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Save without publishing, e.g. to upload images first; publish with
+    /// `POST /products/{id}/publish`
+    #[serde(default)]
+    pub draft: bool,
+    /// Promotional price; must be lower than `price` and set together with `sale_ends_at`
+    pub sale_price: Option<f64>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub sale_ends_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -42,8 +118,355 @@ pub struct UpdateProductRequest {
     pub description: Option<String>,
     #[schema(value_type = String, format = "uuid")]
     pub image_id: Option<Uuid>,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Attributes defined for the category; left out, the current ones are
+    /// kept and checked against `category_id`
+    #[schema(value_type = Option<Object>)]
+    pub attributes: Option<serde_json::Value>,
     pub price: f64,
+    /// ISO 4217 code of the prices; left out, the current one is kept
+    pub currency: Option<String>,
     pub quantity_available: i32,
+    /// Left out, the threshold is cleared
+    pub low_stock_threshold: Option<i32>,
+    /// Legacy free-text policy, read into the structured fields when they are
+    /// omitted. Falls back to the store's default when this is empty too
+    pub return_policy: Option<String>,
+    /// Set this to describe the policy with structured fields
+    pub returns_accepted: Option<bool>,
+    /// 0–90 days; requires `returns_accepted: true`
+    pub return_window_days: Option<i32>,
+    pub return_conditions: Option<String>,
+    /// How the product reaches the buyer; omitted fields, or the whole object,
+    /// take the store's defaults
+    pub delivery_options: Option<DeliveryOptionsInput>,
+    /// Omit both sale fields to end a running sale
+    pub sale_price: Option<f64>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub sale_ends_at: Option<DateTime<Utc>>,
+}
+
+/// Partial edit: only the fields sent are changed. For the nullable ones,
+/// `null` clears the field while leaving it out keeps it.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateProductPatch {
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub sku: Option<Option<String>>,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub image_id: Option<Option<Uuid>>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Option<Uuid>>,
+    /// Replaces all attributes. Left out, the current ones are kept, so a
+    /// new `category_id` must come with attributes it defines.
+    #[schema(value_type = Option<Object>)]
+    pub attributes: Option<serde_json::Value>,
+    pub price: Option<f64>,
+    /// ISO 4217 code of the prices
+    pub currency: Option<String>,
+    pub quantity_available: Option<i32>,
+    /// `null` stops low-stock events
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i32>)]
+    pub low_stock_threshold: Option<Option<i32>>,
+    /// Replaces the return terms; as on `PUT`, the structured fields win
+    /// over this text
+    pub return_policy: Option<String>,
+    pub returns_accepted: Option<bool>,
+    /// 0–90 days; requires `returns_accepted: true`
+    pub return_window_days: Option<i32>,
+    pub return_conditions: Option<String>,
+    /// Replaces the delivery options; omitted fields take the store's
+    /// defaults
+    pub delivery_options: Option<DeliveryOptionsInput>,
+    /// Send both sale fields as `null` to end a running sale
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<f64>)]
+    pub sale_price: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub sale_ends_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// Tells a field sent as `null` (`Some(None)`) from one left out (`None`,
+/// through `#[serde(default)]`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl CreateProductRequest {
+    pub fn as_input(&self) -> ProductInput<'_> {
+        ProductInput {
+            store_id: self.store_id,
+            product_id: None,
+            sku: self.sku.as_deref(),
+            name: &self.name,
+            price: self.price,
+            currency: self.currency.as_deref(),
+            quantity_available: self.quantity_available,
+            low_stock_threshold: self.low_stock_threshold,
+            category_id: self.category_id,
+            publish_at: self.publish_at,
+            sale_price: self.sale_price,
+            sale_ends_at: self.sale_ends_at,
+            returns_accepted: self.returns_accepted,
+            return_window_days: self.return_window_days,
+            return_conditions: self.return_conditions.as_deref(),
+            delivery_options: self.delivery_options,
+            attributes: self.attributes.as_ref(),
+        }
+    }
+
+    pub fn return_terms(&self) -> Option<ReturnTerms> {
+        ReturnTerms::from_request(
+            self.returns_accepted,
+            self.return_window_days,
+            self.return_conditions.as_deref(),
+            self.return_policy.as_deref(),
+        )
+    }
+}
+
+impl UpdateProductRequest {
+    pub fn as_input(&self, store_id: Uuid, product_id: Uuid) -> ProductInput<'_> {
+        ProductInput {
+            store_id: Some(store_id),
+            product_id: Some(product_id),
+            sku: self.sku.as_deref(),
+            name: &self.name,
+            price: self.price,
+            currency: self.currency.as_deref(),
+            quantity_available: self.quantity_available,
+            low_stock_threshold: self.low_stock_threshold,
+            category_id: self.category_id,
+            publish_at: None,
+            sale_price: self.sale_price,
+            sale_ends_at: self.sale_ends_at,
+            returns_accepted: self.returns_accepted,
+            return_window_days: self.return_window_days,
+            return_conditions: self.return_conditions.as_deref(),
+            delivery_options: self.delivery_options,
+            attributes: self.attributes.as_ref(),
+        }
+    }
+
+    pub fn return_terms(&self) -> Option<ReturnTerms> {
+        ReturnTerms::from_request(
+            self.returns_accepted,
+            self.return_window_days,
+            self.return_conditions.as_deref(),
+            self.return_policy.as_deref(),
+        )
+    }
+}
+
+impl UpdateProductPatch {
+    /// The product as it would be after the patch, for validation; an
+    /// untouched sale is checked only while it runs
+    pub fn as_input<'a>(
+        &'a self,
+        existing: &'a ProductModel,
+        now: DateTime<Utc>,
+    ) -> ProductInput<'a> {
+        let (sale_price, sale_ends_at) = if self.touches_sale() {
+            (
+                self.sale_price.unwrap_or(existing.sale_price),
+                self.sale_ends_at.unwrap_or(existing.sale_ends_at),
+            )
+        } else {
+            let sale = active_sale(existing, now);
+            (sale.map(|s| s.price), sale.map(|s| s.ends_at))
+        };
+        ProductInput {
+            store_id: Some(existing.store_id),
+            product_id: Some(existing.id),
+            sku: match &self.sku {
+                Some(sku) => sku.as_deref(),
+                None => existing.sku.as_deref(),
+            },
+            name: self.name.as_deref().unwrap_or(&existing.name),
+            price: self.price.unwrap_or(existing.price),
+            currency: self.currency.as_deref(),
+            quantity_available: self
+                .quantity_available
+                .unwrap_or(existing.quantity_available),
+            low_stock_threshold: self
+                .low_stock_threshold
+                .unwrap_or(existing.low_stock_threshold),
+            category_id: self.category_id.unwrap_or(existing.category_id),
+            publish_at: None,
+            sale_price,
+            sale_ends_at,
+            returns_accepted: self.returns_accepted,
+            return_window_days: self.return_window_days,
+            return_conditions: self.return_conditions.as_deref(),
+            delivery_options: self.delivery_options,
+            attributes: Some(self.attributes.as_ref().unwrap_or(&existing.attributes)),
+        }
+    }
+
+    fn touches_sale(&self) -> bool {
+        self.sale_price.is_some() || self.sale_ends_at.is_some()
+    }
+
+    /// The changes to save, given the sale validation settled on
+    pub fn into_patch(self, sale: Option<Sale>) -> ProductPatch {
+        let return_policy = ReturnTerms::from_request(
+            self.returns_accepted,
+            self.return_window_days,
+            self.return_conditions.as_deref(),
+            self.return_policy.as_deref(),
+        );
+        ProductPatch {
+            sale: self.touches_sale().then_some(sale),
+            sku: self.sku,
+            name: self.name,
+            description: self.description,
+            price: self.price,
+            currency: self.currency,
+            quantity_available: self.quantity_available,
+            low_stock_threshold: self.low_stock_threshold,
+            image_id: self.image_id,
+            category_id: self.category_id,
+            attributes: self.attributes.map(stored_attributes),
+            return_policy,
+            delivery: self.delivery_options,
+        }
+    }
+}
+
+/// Attributes as saved: an object, empty when none were sent
+pub fn stored_attributes(attributes: serde_json::Value) -> serde_json::Value {
+    match attributes {
+        serde_json::Value::Null => serde_json::json!({}),
+        attributes => attributes,
+    }
+}
+
+/// Top-level fields of `after` that differ from `before`, with their new
+/// values; `updated_at` always moves and is left out
+pub fn changed_fields(before: &ProductModel, after: &ProductModel) -> serde_json::Value {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return serde_json::json!({});
+    };
+    let changed: serde_json::Map<String, serde_json::Value> = after
+        .into_iter()
+        .filter(|(field, value)| field != "updated_at" && before.get(field) != Some(value))
+        .collect();
+    serde_json::Value::Object(changed)
+}
+
+/// Dry-run body: a create form, or an edit form when `product_id` is set
+#[derive(Deserialize, ToSchema)]
+pub struct ValidateProductRequest {
+    /// Product being edited, so its own SKU isn't reported as taken
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub product_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub product: CreateProductRequest,
+}
+
+/// Product with the price a buyer pays right now. The bare `price`,
+/// `sale_price` and `effective_price` numbers are deprecated in favour of
+/// their `_money` counterparts; see `crate::money`.
+#[derive(Serialize, ToSchema)]
+pub struct ProductResponse {
+    #[serde(flatten)]
+    #[schema(inline)]
+    pub product: ProductModel,
+    /// `sale_price` while the sale is running, otherwise `price`
+    #[schema(deprecated)]
+    pub effective_price: f64,
+    pub price_money: Money,
+    /// Set whenever `sale_price` is, even once the sale has ended
+    pub sale_price_money: Option<Money>,
+    pub effective_price_money: Money,
+    pub discount_percent: Option<i32>,
+    /// The store is paused: the product can be viewed but not ordered
+    pub unavailable: bool,
+    /// The caller watches the product for price drops; only on product
+    /// detail, for token holders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_watched: Option<bool>,
+}
+
+impl ProductResponse {
+    /// Prices are formatted in English until [`Self::localized`]
+    pub fn new(product: ProductModel, now: DateTime<Utc>) -> Self {
+        let effective_price = effective_price(&product, now);
+        let locale = Locale::default();
+        Self {
+            effective_price,
+            price_money: Money::new(product.price, &product.currency, locale),
+            sale_price_money: product
+                .sale_price
+                .map(|sale_price| Money::new(sale_price, &product.currency, locale)),
+            effective_price_money: Money::new(effective_price, &product.currency, locale),
+            discount_percent: discount_percent(&product, now),
+            unavailable: false,
+            is_watched: None,
+            product,
+        }
+    }
+
+    /// Format the prices for `locale`
+    pub fn localized(mut self, locale: Locale) -> Self {
+        let currency = &self.product.currency;
+        self.price_money = Money::new(self.product.price, currency, locale);
+        self.sale_price_money = self
+            .product
+            .sale_price
+            .map(|sale_price| Money::new(sale_price, currency, locale));
+        self.effective_price_money = Money::new(self.effective_price, currency, locale);
+        self
+    }
+
+    /// Flag the product as unavailable while its store is paused
+    pub fn store_paused(mut self, paused: bool) -> Self {
+        self.unavailable = paused;
+        self
+    }
+
+    pub fn watched(mut self, watched: bool) -> Self {
+        self.is_watched = Some(watched);
+        self
+    }
+}
+
+/// Product as seen by its seller, including whether it is live yet
+#[derive(Serialize, ToSchema)]
+pub struct SellerProductResponse {
+    #[serde(flatten)]
+    pub product: ProductResponse,
+    pub publication_status: PublicationStatus,
+}
+
+impl SellerProductResponse {
+    pub fn new(product: ProductModel, now: DateTime<Utc>) -> Self {
+        let publication_status = PublicationStatus::of(&product, now);
+        Self {
+            product: ProductResponse::new(product, now),
+            publication_status,
+        }
+    }
+
+    pub fn localized(mut self, locale: Locale) -> Self {
+        self.product = self.product.localized(locale);
+        self
+    }
 }
 
 #[allow(dead_code)]
@@ -51,6 +474,199 @@ pub struct UpdateProductRequest {
 pub struct ListProductsQuery {
     #[schema(value_type = String, format = "uuid")]
     pub store_id: Uuid,
+    /// Slug of the category to list, e.g. `shoes`
+    pub category: Option<String>,
+    /// Lower bound on the effective price
+    pub min_price: Option<f64>,
+    /// Upper bound on the effective price
+    pub max_price: Option<f64>,
+    /// Only products that do (true) or don't (false) take returns
+    pub returns_accepted: Option<bool>,
+    /// Only products that can (true) or can't (false) be delivered
+    pub delivery_available: Option<bool>,
+    /// Only products priced in this ISO 4217 currency, e.g. `NGN`
+    pub currency: Option<String>,
+    pub sort: Option<ProductSort>,
+    /// Comma-separated subset of fields to return, e.g. `id,name,price`
+    pub fields: Option<String>,
+}
+
+impl ListProductsQuery {
+    /// The filter to list with; a negative or inverted price range is refused
+    pub fn price_filter(&self) -> Result<PriceFilter, AppError> {
+        for (name, price) in [("min_price", self.min_price), ("max_price", self.max_price)] {
+            if price.is_some_and(|price| price < 0.0 || !price.is_finite()) {
+                return Err(AppError::Validation(format!(
+                    "{name} must be a non-negative number"
+                )));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if min > max {
+                return Err(AppError::Validation(
+                    "min_price must not be greater than max_price".to_string(),
+                ));
+            }
+        }
+        if let Some(problem) = self
+            .currency
+            .as_deref()
+            .and_then(|code| currency_error("currency", code))
+        {
+            return Err(AppError::Validation(problem));
+        }
+        Ok(PriceFilter {
+            min_price: self.min_price,
+            max_price: self.max_price,
+            returns_accepted: self.returns_accepted,
+            delivery_available: self.delivery_available,
+            currency: self.currency.clone(),
+            attributes: Vec::new(),
+            include_archived: false,
+            sort: self.sort.unwrap_or_default(),
+        })
+    }
+}
+
+/// Longest search text accepted, in characters
+pub const MAX_SEARCH_LEN: usize = 100;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SearchProductsQuery {
+    /// Text to find in product names and descriptions, ignoring case
+    pub q: Option<String>,
+    /// Only products of this store
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+    /// Only products in this category
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    /// Only products priced in this ISO 4217 currency
+    pub currency: Option<String>,
+    pub sort: Option<ProductSort>,
+}
+
+impl SearchProductsQuery {
+    /// The search text, trimmed; blank or overlong text is refused rather
+    /// than scanning every product
+    pub fn text(&self) -> Result<&str, String> {
+        let text = self.q.as_deref().map(str::trim).unwrap_or_default();
+        if text.is_empty() {
+            return Err("q must not be empty".to_string());
+        }
+        if text.chars().count() > MAX_SEARCH_LEN {
+            return Err(format!("q must be at most {MAX_SEARCH_LEN} characters"));
+        }
+        Ok(text)
+    }
+
+    /// The filter to search with; an unknown currency is refused
+    pub fn price_filter(&self) -> Result<PriceFilter, String> {
+        if let Some(problem) = self
+            .currency
+            .as_deref()
+            .and_then(|code| currency_error("currency", code))
+        {
+            return Err(problem);
+        }
+        Ok(PriceFilter {
+            min_price: self.min_price,
+            max_price: self.max_price,
+            currency: self.currency.clone(),
+            sort: self.sort.unwrap_or_default(),
+            ..PriceFilter::default()
+        })
+    }
+}
+
+/// Some cart items cannot be handed over the way the buyer asked
+pub const DELIVERY_METHOD_UNAVAILABLE: &str = "DELIVERY_METHOD_UNAVAILABLE";
+
+/// 422 listing the cart items that rule out the chosen delivery method
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeliveryMethodUnavailable {
+    pub code: &'static str,
+    pub message: String,
+    pub delivery_method: DeliveryMethod,
+    #[schema(value_type = Vec<String>)]
+    pub product_ids: Vec<Uuid>,
+}
+
+// Order creation checks its cart with this before taking stock
+#[allow(dead_code)]
+impl DeliveryMethodUnavailable {
+    /// `Err` when any product in the cart does not offer `method`
+    pub fn check<'a>(
+        items: impl IntoIterator<Item = &'a ProductModel>,
+        method: DeliveryMethod,
+    ) -> Result<(), Self> {
+        let product_ids = unavailable_items(items, method);
+        if product_ids.is_empty() {
+            return Ok(());
+        }
+        Err(Self {
+            code: DELIVERY_METHOD_UNAVAILABLE,
+            message: format!(
+                "{} item(s) in the cart are not available for {}",
+                product_ids.len(),
+                method.as_str()
+            ),
+            delivery_method: method,
+            product_ids,
+        })
+    }
+}
+
+impl IntoResponse for DeliveryMethodUnavailable {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// Let store followers know a product just went on sale
+pub async fn announce_sale(dispatcher: &EventDispatcher, product: &ProductModel) {
+    let event = create_event(
+        EventType::ProductOnSale,
+        product.id,
+        serde_json::json!({
+            "store_id": product.store_id,
+            "name": product.name,
+            "price": product.price,
+            "currency": product.currency,
+            "sale_price": product.sale_price,
+            "sale_ends_at": product.sale_ends_at,
+        }),
+    );
+    let _ = dispatcher.dispatch(event).await;
+}
+
+/// A `ProductLowStock` event if a change from `previous_quantity` took
+/// `product` to its low-stock threshold; see [`low_stock_crossing`]
+pub fn low_stock_event(previous_quantity: i32, product: &ProductModel) -> Option<Event> {
+    let threshold = low_stock_crossing(previous_quantity, product)?;
+    Some(create_event(
+        EventType::ProductLowStock,
+        product.id,
+        serde_json::json!({
+            "store_id": product.store_id,
+            "sku": product.sku,
+            "quantity_available": product.quantity_available,
+            "low_stock_threshold": threshold,
+        }),
+    ))
+}
+
+/// Warn the seller when an edit took a product's stock to its threshold
+pub async fn announce_low_stock(
+    dispatcher: &EventDispatcher,
+    previous_quantity: i32,
+    product: &ProductModel,
+) {
+    if let Some(event) = low_stock_event(previous_quantity, product) {
+        let _ = dispatcher.dispatch(event).await;
+    }
 }
 #[allow(dead_code)]
 pub fn router(db: DatabaseConnection) -> Router<()> {
@@ -65,23 +681,46 @@ pub fn router(db: DatabaseConnection) -> Router<()> {
     // Initialize image analysis service
     let image_analysis = Arc::new(ImageAnalysisService::new());
 
+    // Initialize WebP conversion for uploaded images
+    let webp_converter = Arc::new(WebpConverter::default());
+
+    let media_limits = Arc::new(MediaLimits::default());
+    let upload_limits = UploadLimits::default();
+
     Router::new()
         .route("/products", post(create_product).get(list_products))
+        .route("/products/validate", post(validate_product_form))
+        .route("/products/search", get(search_products))
         .route(
             "/products/:id",
-            get(get_product).put(update_product).delete(delete_product),
+            get(get_product)
+                .put(update_product)
+                .patch(patch_product)
+                .delete(delete_product),
         )
+        .route("/products/:id/restore", post(restore_product))
+        .route("/products/:id/duplicate", post(duplicate_product))
         .route(
             "/products/:id/media",
-            post(upload_product_media)
+            get(list_product_media)
+                .post(upload_product_media)
                 .put(edit_product_media)
-                .delete(delete_product_media),
+                .delete(delete_product_media)
+                .layer(DefaultBodyLimit::max(upload_limits.max_body_bytes())),
         )
+        .layer(middleware::from_fn_with_state(
+            db.clone(),
+            transaction_middleware,
+        ))
         .with_state(ProductApiState {
             db,
             event_dispatcher: Arc::new(event_dispatcher),
             jwt_service,
             image_analysis,
+            webp_converter,
+            media_limits,
+            default_currency: DEFAULT_CURRENCY.to_string(),
+            upload_limits: Arc::new(upload_limits),
         })
 }
 
@@ -92,120 +731,474 @@ pub struct ProductApiState {
     pub event_dispatcher: Arc<EventDispatcher>,
     pub jwt_service: Arc<JwtService>,
     pub image_analysis: Arc<ImageAnalysisService>,
+    pub webp_converter: Arc<WebpConverter>,
+    pub media_limits: Arc<MediaLimits>,
+    /// ISO 4217 code of products created without one
+    pub default_currency: String,
+    pub upload_limits: Arc<UploadLimits>,
+}
+
+impl FromRef<ProductApiState> for DatabaseConnection {
+    fn from_ref(state: &ProductApiState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<ProductApiState> for Arc<UploadLimits> {
+    fn from_ref(state: &ProductApiState) -> Self {
+        state.upload_limits.clone()
+    }
+}
+
+impl FromRef<ProductApiState> for Arc<EventDispatcher> {
+    fn from_ref(state: &ProductApiState) -> Self {
+        state.event_dispatcher.clone()
+    }
+}
+
+impl FromRef<ProductApiState> for Arc<MediaLimits> {
+    fn from_ref(state: &ProductApiState) -> Self {
+        state.media_limits.clone()
+    }
+}
+
+/// The store a new product goes in: `store_id` when given, else the API
+/// key's store or the seller's only store. The caller must be allowed to add
+/// products to it.
+pub(crate) async fn product_store(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+    store_id: Option<Uuid>,
+) -> Result<StoreModel, (StatusCode, String)> {
+    let store_id = match store_id {
+        Some(store_id) => store_id,
+        None => default_store_id(db, headers).await?,
+    };
+    owned_store(db, headers, store_id, ApiScope::ProductsWrite).await
+}
+
+async fn default_store_id(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+) -> Result<Uuid, (StatusCode, String)> {
+    let principal = match authenticate(db, headers).await {
+        Ok(Some(principal)) => principal,
+        Ok(None) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token".to_string(),
+            ))
+        }
+        Err((status, msg)) => return Err((status, msg.to_string())),
+    };
+    if let Some(grant) = &principal.api_key {
+        return Ok(grant.store_id);
+    }
+    let stores = Store::list_by_owner(db, &principal.claims.tenant_id, &principal.claims.relay_id)
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    match stores.as_slice() {
+        [store] => Ok(store.id),
+        [] => Err((
+            StatusCode::NOT_FOUND,
+            "You have no store yet; create one before adding products".to_string(),
+        )),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "store_id is required when you own more than one store".to_string(),
+        )),
+    }
 }
 
 /// Create a new product
 #[utoipa::path(
     post,
-    path = "/products",
+    operation_id = "createProduct",
+    path = "/api/v1/products",
     request_body = CreateProductRequest,
     responses(
-        (status = 201, description = "Product created successfully", body = Model),
-        (status = 400, description = "Bad request - invalid data")
+        (status = 201, description = "Product created successfully; unpublished if it matched a flagged term", body = ProductResponse),
+        (status = 400, description = "Bad request - invalid data, or no store_id from a seller with several stores", body = ValidationReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner, or an API key without products:write"),
+        (status = 404, description = "Store not found, or the seller has no store yet"),
+        (status = 409, description = "Another product in the store was just given this SKU"),
+        (status = 422, description = "Name or description uses a blocked term, or `INSUFFICIENT_MEDIA` to publish right away; create it as a draft instead", body = CreateProductRejection)
     ),
     tag = "Products"
 )]
 async fn create_product(
     State(state): State<ProductApiState>,
+    headers: HeaderMap,
     Json(payload): Json<CreateProductRequest>,
 ) -> impl IntoResponse {
+    let now = Utc::now();
+    let store = match product_store(&state.db, &headers, payload.store_id).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let input = ProductInput {
+        store_id: Some(store.id),
+        ..payload.as_input()
+    };
+    let sale = match validate_product(&state.db, &input, now).await {
+        Ok(sale) => sale,
+        Err(errors) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(ValidationReport::from(errors)),
+            )
+                .into_response()
+        }
+    };
+
+    let held_terms = match screen_listing(&payload.name, payload.description.as_deref()) {
+        Ok(terms) => terms,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if publishes_on_create(payload.draft, payload.publish_at) {
+        // A new product has no media yet
+        if let Some(refused) = InsufficientMedia::check(state.media_limits.min_images_to_publish, 0)
+        {
+            return refused.into_response();
+        }
+    }
+
     match Product::create(
         &state.db,
-        payload.store_id,
+        store.id,
         payload.sku.as_deref(),
         &payload.name,
         payload.description.as_deref(),
         payload.price,
+        payload
+            .currency
+            .as_deref()
+            .unwrap_or(&state.default_currency),
         payload.quantity_available,
+        payload.low_stock_threshold,
         payload.image_id,
+        payload.return_terms(),
+        payload.delivery_options,
+        payload.publish_at,
+        payload.draft,
+        sale,
+        payload.category_id,
+        stored_attributes(payload.attributes.clone().unwrap_or_default()),
     )
     .await
     {
         Ok(product) => {
-            // Trigger real-time event: product created
-            let event = create_event(
-                EventType::ProductCreated,
-                product.id,
-                serde_json::json!({
-                    "store_id": product.store_id,
-                    "name": product.name,
-                    "price": product.price
-                }),
-            );
-            let _ = state.event_dispatcher.dispatch(event).await;
+            let product = match held_terms {
+                Some(terms) => {
+                    match hold_listing(&state.db, &state.event_dispatcher, product.clone(), &terms)
+                        .await
+                    {
+                        Ok(product) => product,
+                        Err(e) => {
+                            // Never leave a flagged listing live
+                            let _ = Product::delete_permanently(&state.db, product.id).await;
+                            return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e)
+                                .into_response();
+                        }
+                    }
+                }
+                None => product,
+            };
+            // Trigger real-time event: product created. Scheduled products are
+            // announced by the publish scheduler once they go live instead.
+            if product.is_published {
+                let event = create_event(
+                    EventType::ProductCreated,
+                    product.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "name": product.name,
+                        "price": product.price,
+                        "currency": product.currency
+                    }),
+                );
+                let _ = state.event_dispatcher.dispatch(event).await;
+                if sale.is_some() {
+                    announce_sale(&state.event_dispatcher, &product).await;
+                }
+            }
 
-            (axum::http::StatusCode::CREATED, Json(product)).into_response()
+            (
+                axum::http::StatusCode::CREATED,
+                Json(ProductResponse::new(product, now)),
+            )
+                .into_response()
         }
+        Err(e) if e == SKU_TAKEN => (axum::http::StatusCode::CONFLICT, e).into_response(),
         Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
     }
 }
 
+/// Check a product form without saving it
+#[utoipa::path(
+    post,
+    operation_id = "validateProductForm",
+    path = "/api/v1/products/validate",
+    request_body = ValidateProductRequest,
+    responses(
+        (status = 200, description = "Validation result; `valid` is false when `errors` is non-empty", body = ValidationReport)
+    ),
+    tag = "Products"
+)]
+pub async fn validate_product_form(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<ValidateProductRequest>,
+) -> impl IntoResponse {
+    let mut input = payload.product.as_input();
+    input.product_id = payload.product_id;
+    let errors = validate_product(&db, &input, Utc::now())
+        .await
+        .err()
+        .unwrap_or_default();
+    Json(ValidationReport::from(errors))
+}
+
 /// Get a product by ID
 #[utoipa::path(
     get,
-    path = "/products/{id}",
+    operation_id = "getProduct",
+    path = "/api/v1/products/{id}",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        LocaleParams
     ),
     responses(
-        (status = 200, description = "Product found", body = Model),
+        (status = 200, description = "Product found", body = ProductResponse),
         (status = 404, description = "Product not found")
     ),
     tag = "Products"
 )]
 async fn get_product(
     State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
+    locale: Locale,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    match Product::get(&state.db, id).await {
-        Ok(product) => Json::<ProductModel>(product).into_response(),
-        Err(e) => (axum::http::StatusCode::NOT_FOUND, e).into_response(),
+    let product = match Product::get_visible(&state.db, id).await {
+        Ok(product) => product,
+        Err(e) => return (axum::http::StatusCode::NOT_FOUND, e).into_response(),
+    };
+    let now = Utc::now();
+    let paused = match Store::get(&state.db, product.store_id).await {
+        Ok(store) => is_paused(&store, now),
+        Err(e) => return (axum::http::StatusCode::NOT_FOUND, e).into_response(),
+    };
+    let mut response = ProductResponse::new(product, now)
+        .store_paused(paused)
+        .localized(locale);
+    if let Some(claims) = claims_from_headers(&headers) {
+        match ProductWatch::is_watching(&state.db, id, &claims.relay_id).await {
+            Ok(watched) => response = response.watched(watched),
+            Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        }
     }
+    Json(response).into_response()
 }
 
 /// List products by store ID
 #[utoipa::path(
     get,
-    path = "/products",
+    operation_id = "listProducts",
+    path = "/api/v1/products",
     params(
-        ("store_id" = UuidSchema, Query, description = "Store ID to filter products")
+        ("store_id" = String, Query, description = "Store ID to filter products", format = "uuid"),
+        ("category" = Option<String>, Query, description = "Only products in the category with this slug"),
+        ("min_price" = Option<f64>, Query, description = "Minimum effective price, at least 0"),
+        ("max_price" = Option<f64>, Query, description = "Maximum effective price, at least min_price"),
+        ("returns_accepted" = Option<bool>, Query, description = "Only products that do (true) or don't (false) accept returns"),
+        ("delivery_available" = Option<bool>, Query, description = "Only products that can (true) or can't (false) be delivered"),
+        ("currency" = Option<String>, Query, description = "Only products priced in this ISO 4217 currency, e.g. `NGN`"),
+        ("include_archived" = Option<bool>, Query, description = "For the store owner, also list archived products; ignored for anyone else"),
+        ("attr.{key}" = Option<String>, Query, description = "Only products whose category attribute `key` has this value, e.g. `attr.condition=used`; add `_gte`, `_lte`, `_gt` or `_lt` to the key to compare a number, e.g. `attr.ram_gte=8`"),
+        ("sort" = Option<ProductSort>, Query, description = "Sort order, by effective price or newest first"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return, e.g. `id,name,price,effective_price`; unknown fields are rejected"),
+        PageParams,
+        LocaleParams
     ),
     responses(
-        (status = 200, description = "Products found", body = Vec<Model>),
-        (status = 400, description = "Bad request - invalid store ID")
+        (status = 200, description = "One page of products", body = ProductsPage),
+        (status = 400, description = "Bad request - invalid store ID, unknown field, invalid price range, unknown currency, malformed attribute filter or invalid page parameters")
     ),
     tag = "Products"
 )]
 async fn list_products(
     State(state): State<ProductApiState>,
     Query(query): Query<ListProductsQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+    page: PageRequest,
+    locale: Locale,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    match Product::list_by_store(&state.db, query.store_id).await {
-        Ok(products) => Json::<Vec<ProductModel>>(products).into_response(),
+    let fields = match FieldSelection::parse(query.fields.as_deref(), PRODUCT_FIELDS) {
+        Ok(fields) => fields,
+        Err(err) => return (axum::http::StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let mut price_filter = match query.price_filter() {
+        Ok(filter) => filter,
+        Err(err) => return err.into_response(),
+    };
+    price_filter.attributes = match AttributeFilter::from_query(
+        params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+    ) {
+        Ok(filters) => filters,
+        Err(err) => return AppError::Validation(err).into_response(),
+    };
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now();
+    let paused = Store::get(&state.db, query.store_id)
+        .await
+        .is_ok_and(|store| is_paused(&store, now));
+    match Product::list_filtered(
+        &state.db,
+        &tenant,
+        query.store_id,
+        query.category.as_deref(),
+        price_filter,
+        page.page,
+        page.per_page,
+    )
+    .await
+    {
+        Ok((products, total)) => {
+            let products: Vec<ProductResponse> = products
+                .into_iter()
+                .map(|product| {
+                    ProductResponse::new(product, now)
+                        .store_paused(paused)
+                        .localized(locale)
+                })
+                .collect();
+            Json(page.numbered(products, total).project(fields.as_ref())).into_response()
+        }
         Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
     }
 }
 
+/// Search public products by name and description
+#[utoipa::path(
+    get,
+    operation_id = "searchProducts",
+    path = "/api/v1/products/search",
+    params(
+        ("q" = String, Query, description = "Text to find in names and descriptions, ignoring case"),
+        ("store_id" = Option<String>, Query, description = "Only products of this store", format = "uuid"),
+        ("category_id" = Option<String>, Query, description = "Only products in this category", format = "uuid"),
+        ("min_price" = Option<f64>, Query, description = "Minimum effective price"),
+        ("max_price" = Option<f64>, Query, description = "Maximum effective price"),
+        ("currency" = Option<String>, Query, description = "Only products priced in this ISO 4217 currency, e.g. `NGN`"),
+        ("sort" = Option<ProductSort>, Query, description = "Sort order, by effective price or newest first"),
+        PageParams,
+        LocaleParams
+    ),
+    responses(
+        (status = 200, description = "One page of matching products", body = ProductsPage),
+        (status = 400, description = "Empty or overlong q, unknown currency, or invalid page parameters")
+    ),
+    tag = "Products"
+)]
+pub async fn search_products(
+    State(db): State<DatabaseConnection>,
+    Query(query): Query<SearchProductsQuery>,
+    page: PageRequest,
+    locale: Locale,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let text = match query.text() {
+        Ok(text) => text,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let price_filter = match query.price_filter() {
+        Ok(filter) => filter,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now();
+    match Product::search(
+        &db,
+        &tenant,
+        text,
+        query.store_id,
+        query.category_id,
+        price_filter,
+        page.page,
+        page.per_page,
+    )
+    .await
+    {
+        Ok((products, total)) => {
+            let products: Vec<ProductResponse> = products
+                .into_iter()
+                .map(|product| ProductResponse::new(product, now).localized(locale))
+                .collect();
+            Json(page.numbered(products, total)).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
 /// Update a product by ID
 #[utoipa::path(
     put,
-    path = "/products/{id}",
+    operation_id = "updateProduct",
+    path = "/api/v1/products/{id}",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid")
     ),
     request_body = UpdateProductRequest,
     responses(
-        (status = 200, description = "Product updated successfully", body = Model),
-        (status = 400, description = "Bad request - invalid data"),
-        (status = 404, description = "Product not found")
+        (status = 200, description = "Product updated successfully; unpublished if it matched a flagged term", body = ProductResponse),
+        (status = 400, description = "Bad request - invalid data", body = ValidationReport),
+        (status = 404, description = "Product not found"),
+        (status = 422, description = "Name or description uses a blocked term", body = ProhibitedTermRejection)
     ),
     tag = "Products"
 )]
 async fn update_product(
     State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateProductRequest>,
 ) -> impl IntoResponse {
+    let now = Utc::now();
+    let existing = match Product::get(&state.db, id).await {
+        Ok(product) => product,
+        Err(e) => return (axum::http::StatusCode::NOT_FOUND, e).into_response(),
+    };
+    let input = ProductInput {
+        attributes: Some(payload.attributes.as_ref().unwrap_or(&existing.attributes)),
+        ..payload.as_input(existing.store_id, id)
+    };
+    let sale = match validate_product(&state.db, &input, now).await {
+        Ok(sale) => sale,
+        Err(errors) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(ValidationReport::from(errors)),
+            )
+                .into_response()
+        }
+    };
+
+    let held_terms = match screen_listing(&payload.name, payload.description.as_deref()) {
+        Ok(terms) => terms,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
     match Product::update(
         &state.db,
         id,
@@ -213,12 +1206,35 @@ async fn update_product(
         &payload.name,
         payload.description.as_deref(),
         payload.price,
+        payload.currency.as_deref(),
         payload.quantity_available,
+        payload.low_stock_threshold,
         payload.image_id,
+        payload.return_terms(),
+        payload.delivery_options,
+        sale,
+        payload.category_id,
+        payload
+            .attributes
+            .clone()
+            .map_or_else(|| existing.attributes.clone(), stored_attributes),
+        ChangeOrigin::edit(changed_by.as_deref()),
     )
     .await
     {
         Ok(product) => {
+            let product = match held_terms {
+                Some(terms) => {
+                    match hold_listing(&state.db, &state.event_dispatcher, product, &terms).await {
+                        Ok(product) => product,
+                        Err(e) => {
+                            return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e)
+                                .into_response()
+                        }
+                    }
+                }
+                None => product,
+            };
             // Trigger real-time event: product updated
             let event = create_event(
                 EventType::ProductUpdated,
@@ -226,94 +1242,864 @@ async fn update_product(
                 serde_json::json!({
                     "store_id": product.store_id,
                     "name": product.name,
-                    "price": product.price
+                    "price": product.price,
+                    "currency": product.currency
                 }),
             );
             let _ = state.event_dispatcher.dispatch(event).await;
+            if existing.price != product.price {
+                let event = create_event(
+                    EventType::ProductPriceChanged,
+                    product.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "sku": product.sku,
+                        "previous_price": existing.price,
+                        "price": product.price,
+                        "currency": product.currency,
+                    }),
+                );
+                let _ = state.event_dispatcher.dispatch(event).await;
+            }
+            if sale.is_some() && product.is_published {
+                announce_sale(&state.event_dispatcher, &product).await;
+            }
+            announce_low_stock(
+                &state.event_dispatcher,
+                existing.quantity_available,
+                &product,
+            )
+            .await;
+
+            Json(ProductResponse::new(product, now)).into_response()
+        }
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+/// Change some fields of a product, leaving the rest as they are
+#[utoipa::path(
+    patch,
+    operation_id = "patchProduct",
+    path = "/api/v1/products/{id}",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    request_body = UpdateProductPatch,
+    responses(
+        (status = 200, description = "Product updated; unpublished if it now matches a flagged term", body = ProductResponse),
+        (status = 400, description = "Bad request - invalid data", body = ValidationReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the owner of the product's store, or an API key without products:write"),
+        (status = 404, description = "Product not found"),
+        (status = 409, description = "Another product of the store has this SKU"),
+        (status = 422, description = "Name or description uses a blocked term", body = ProhibitedTermRejection)
+    ),
+    tag = "Products"
+)]
+pub async fn patch_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateProductPatch>,
+) -> impl IntoResponse {
+    let now = Utc::now();
+    let existing = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(e) => return (axum::http::StatusCode::NOT_FOUND, e).into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, existing.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let sale = match validate_product(&db, &payload.as_input(&existing, now), now).await {
+        Ok(sale) => sale,
+        Err(errors) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(ValidationReport::from(errors)),
+            )
+                .into_response()
+        }
+    };
+
+    let held_terms = if payload.name.is_some() || payload.description.is_some() {
+        let description = match &payload.description {
+            Some(description) => description.as_deref(),
+            None => existing.description.as_deref(),
+        };
+        match screen_listing(
+            payload.name.as_deref().unwrap_or(&existing.name),
+            description,
+        ) {
+            Ok(terms) => terms,
+            Err(rejection) => return rejection.into_response(),
+        }
+    } else {
+        None
+    };
+
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    let patch = payload.into_patch(sale);
+    let sale_changed = patch.sale.is_some_and(|sale| sale.is_some());
+    let product = match Product::patch(&db, id, patch, ChangeOrigin::edit(changed_by.as_deref()))
+        .await
+    {
+        Ok(product) => product,
+        Err(e) if e == SKU_TAKEN => return (axum::http::StatusCode::CONFLICT, e).into_response(),
+        Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let product = match held_terms {
+        Some(terms) => match hold_listing(&db, &events, product, &terms).await {
+            Ok(product) => product,
+            Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        },
+        None => product,
+    };
+
+    let changes = changed_fields(&existing, &product);
+    if changes
+        .as_object()
+        .is_some_and(|changes| !changes.is_empty())
+    {
+        let mut payload = serde_json::json!({ "store_id": product.store_id });
+        payload["changes"] = changes;
+        let event = create_event(EventType::ProductUpdated, product.id, payload);
+        let _ = events.dispatch(event).await;
+    }
+    if existing.price != product.price {
+        let event = create_event(
+            EventType::ProductPriceChanged,
+            product.id,
+            serde_json::json!({
+                "store_id": product.store_id,
+                "sku": product.sku,
+                "previous_price": existing.price,
+                "price": product.price,
+                "currency": product.currency,
+            }),
+        );
+        let _ = events.dispatch(event).await;
+    }
+    if sale_changed && product.is_published {
+        announce_sale(&events, &product).await;
+    }
+    announce_low_stock(&events, existing.quantity_available, &product).await;
+
+    Json(ProductResponse::new(product, now)).into_response()
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DeleteProductQuery {
+    /// Remove the row for good instead of archiving; defaults to false
+    pub permanent: Option<bool>,
+}
+
+/// Delete a product by ID. It is archived: gone from every listing but
+/// kept, with its reviews and history, until restored. `permanent=true`
+/// removes it for good.
+#[utoipa::path(
+    delete,
+    operation_id = "deleteProduct",
+    path = "/api/v1/products/{id}",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        DeleteProductQuery
+    ),
+    responses(
+        (status = 204, description = "Product archived, or deleted for good with `permanent=true`"),
+        (status = 400, description = "Bad request - invalid product ID"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the owner of the product's store"),
+        (status = 404, description = "Product not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Products"
+)]
+pub async fn delete_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<DeleteProductQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(e) => return (StatusCode::NOT_FOUND, e).into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+
+    if query.permanent.unwrap_or(false) {
+        return match Product::delete_permanently(&db, id).await {
+            Ok(deactivated_bundles) => {
+                let event = create_event(
+                    EventType::ProductDeleted,
+                    id,
+                    serde_json::json!({
+                        "product_id": id,
+                        "store_id": product.store_id,
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+                announce_deactivated(&events, &deactivated_bundles, id).await;
+                StatusCode::NO_CONTENT.into_response()
+            }
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        };
+    }
+    if product.archived_at.is_some() {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    match Product::delete(&db, id).await {
+        Ok((archived, deactivated_bundles)) => {
+            let event = create_event(
+                EventType::ProductArchived,
+                id,
+                serde_json::json!({
+                    "product_id": id,
+                    "store_id": archived.store_id,
+                    "archived_at": archived.archived_at,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+            announce_deactivated(&events, &deactivated_bundles, id).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Bring an archived product back into listings
+#[utoipa::path(
+    post,
+    operation_id = "restoreProduct",
+    path = "/api/v1/products/{id}/restore",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Product restored; restoring a product that isn't archived changes nothing", body = SellerProductResponse),
+        (status = 400, description = "Bad request - invalid product ID"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the owner of the product's store"),
+        (status = 404, description = "Product not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Products"
+)]
+pub async fn restore_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(e) => return (StatusCode::NOT_FOUND, e).into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let was_archived = product.archived_at.is_some();
+    match Product::restore(&db, id).await {
+        Ok(product) => {
+            if was_archived {
+                let event = create_event(
+                    EventType::ProductRestored,
+                    id,
+                    serde_json::json!({
+                        "product_id": id,
+                        "store_id": product.store_id,
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+            }
+            Json(SellerProductResponse::new(product, Utc::now())).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Suffix marking a duplicated product's name
+pub const COPY_SUFFIX: &str = " (copy)";
+
+/// `name` with [`COPY_SUFFIX`], shortened first when the result would be
+/// longer than a product name may be
+pub fn copy_name(name: &str) -> String {
+    let keep = MAX_NAME_LEN - COPY_SUFFIX.chars().count();
+    let name: String = name.chars().take(keep).collect();
+    format!("{}{COPY_SUFFIX}", name.trim_end())
+}
+
+/// Start a new product from an existing one. The copy takes the name (with
+/// " (copy)"), description, category, attributes, price and return policy,
+/// but no SKU, sale, images or stock. It goes live only when the original
+/// is live and a product without images may be published; otherwise it is
+/// a draft.
+#[utoipa::path(
+    post,
+    operation_id = "duplicateProduct",
+    path = "/api/v1/products/{id}/duplicate",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 201, description = "Copy created in the original's store", body = SellerProductResponse),
+        (status = 400, description = "Bad request - invalid product ID"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the owner of the product's store, or an API key without products:write"),
+        (status = 404, description = "Product not found"),
+        (status = 422, description = "The name or description uses a term blocked since the original was listed", body = ProhibitedTermRejection),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Products"
+)]
+pub async fn duplicate_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    State(limits): State<Arc<MediaLimits>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let original = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(e) => return (StatusCode::NOT_FOUND, e).into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, original.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let name = copy_name(&original.name);
+    let held_terms = match screen_listing(&name, original.description.as_deref()) {
+        Ok(terms) => terms,
+        Err(rejection) => return rejection.into_response(),
+    };
+    // Inherited policies are left to inherit, so they follow the store's
+    // default as the original's do
+    let return_terms = (original.return_policy_source == ReturnPolicySource::Product.as_str())
+        .then(|| ReturnTerms {
+            returns_accepted: original.returns_accepted,
+            window_days: original.return_window_days,
+            conditions: original.return_conditions.clone(),
+        });
+    // The copy has no images yet
+    let draft = !original.is_published
+        || InsufficientMedia::check(limits.min_images_to_publish, 0).is_some();
+
+    let copy = match Product::create(
+        &db,
+        original.store_id,
+        None,
+        &name,
+        original.description.as_deref(),
+        original.price,
+        &original.currency,
+        0,
+        None,
+        None,
+        return_terms,
+        None,
+        None,
+        draft,
+        None,
+        original.category_id,
+        original.attributes.clone(),
+    )
+    .await
+    {
+        Ok(copy) => copy,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let copy = match held_terms {
+        Some(terms) => match hold_listing(&db, &events, copy.clone(), &terms).await {
+            Ok(copy) => copy,
+            Err(e) => {
+                // Never leave a flagged listing live
+                let _ = Product::delete_permanently(&db, copy.id).await;
+                return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+            }
+        },
+        None => copy,
+    };
+    if copy.is_published {
+        let event = create_event(
+            EventType::ProductCreated,
+            copy.id,
+            serde_json::json!({
+                "store_id": copy.store_id,
+                "name": copy.name,
+                "price": copy.price,
+                "currency": copy.currency,
+                "duplicated_from": original.id,
+            }),
+        );
+        let _ = events.dispatch(event).await;
+    }
+    (
+        StatusCode::CREATED,
+        Json(SellerProductResponse::new(copy, Utc::now())),
+    )
+        .into_response()
+}
+
+/// Publish a draft once it has its images
+#[utoipa::path(
+    post,
+    operation_id = "publishProduct",
+    path = "/api/v1/products/{id}/publish",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Product is live; publishing a live product changes nothing", body = ProductResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner, or the key lacks products:write"),
+        (status = 404, description = "Product not found"),
+        (status = 409, description = "Held for moderation review"),
+        (status = 422, description = "`INSUFFICIENT_MEDIA`: upload more images first", body = InsufficientMedia),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Products"
+)]
+pub async fn publish_product(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    State(limits): State<Arc<MediaLimits>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(e) => return (StatusCode::NOT_FOUND, e).into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    let now = Utc::now();
+    match Product::publish(
+        &db,
+        id,
+        limits.min_images_to_publish,
+        ChangeOrigin::edit(changed_by.as_deref()),
+    )
+    .await
+    {
+        Ok(PublishOutcome::Published(product)) => {
+            let event = create_event(
+                EventType::ProductPublished,
+                product.id,
+                serde_json::json!({
+                    "store_id": product.store_id,
+                    "name": product.name,
+                    "price": product.price,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+            Json(ProductResponse::new(product, now)).into_response()
+        }
+        Ok(PublishOutcome::AlreadyPublished(product)) => {
+            Json(ProductResponse::new(product, now)).into_response()
+        }
+        Ok(PublishOutcome::Held) => (
+            StatusCode::CONFLICT,
+            "Product is held for moderation review",
+        )
+            .into_response(),
+        Ok(PublishOutcome::MissingMedia { required, found }) => {
+            InsufficientMedia::new(required, found).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+// --- Media upload/edit/delete endpoints for products ---
+
+#[derive(Serialize, ToSchema)]
+pub struct MediaUploadResponse {
+    #[schema(value_type = String, format = "uuid")]
+    image_id: Uuid,
+    s3_key: String,
+}
+
+/// Media entry returned by the product media listing
+#[derive(Serialize, ToSchema)]
+pub struct ProductMediaResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub image_id: Uuid,
+    /// Preferred URL: the WebP variant when one was stored, otherwise the original.
+    /// Null while media storage is unavailable
+    pub url: Option<String>,
+    /// URL of the upload exactly as it was received; null while media storage is unavailable
+    pub original_url: Option<String>,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub webp_size_bytes: Option<i64>,
+    /// Hex SHA-256 of the original upload; null for uploads made before hashing
+    pub content_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// True when media storage is down and the URLs were withheld
+    pub storage_degraded: bool,
+}
+
+/// The product already has as many images as its store allows
+pub const MEDIA_IMAGE_LIMIT: &str = "MEDIA_IMAGE_LIMIT";
+/// The upload would take the store past its byte quota
+pub const MEDIA_STORAGE_QUOTA: &str = "MEDIA_STORAGE_QUOTA";
+/// The store used up today's uploads
+pub const MEDIA_DAILY_LIMIT: &str = "MEDIA_DAILY_LIMIT";
+
+/// A refused upload: 403 for the image and byte quotas, 429 for the daily limit
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MediaQuotaExceeded {
+    pub code: &'static str,
+    pub message: String,
+    pub limit: i64,
+    /// Bytes already used, for `MEDIA_STORAGE_QUOTA`
+    pub used: Option<i64>,
+}
+
+impl MediaQuotaExceeded {
+    /// `None` when the upload was charged
+    pub fn from_charge(charge: MediaCharge) -> Option<Self> {
+        let (code, message, limit, used) = match charge {
+            MediaCharge::Charged => return None,
+            MediaCharge::TooManyImages { limit } => (
+                MEDIA_IMAGE_LIMIT,
+                format!("A product can have at most {limit} images"),
+                limit.into(),
+                None,
+            ),
+            MediaCharge::StoreFull { limit, used } => (
+                MEDIA_STORAGE_QUOTA,
+                "The store's media storage is full; delete some images first".to_string(),
+                limit,
+                Some(used),
+            ),
+            MediaCharge::DailyLimit { limit } => (
+                MEDIA_DAILY_LIMIT,
+                format!("A store can upload at most {limit} images a day"),
+                limit.into(),
+                None,
+            ),
+        };
+        Some(Self {
+            code,
+            message,
+            limit,
+            used,
+        })
+    }
+}
+
+impl IntoResponse for MediaQuotaExceeded {
+    fn into_response(self) -> Response {
+        let status = if self.code == MEDIA_DAILY_LIMIT {
+            StatusCode::TOO_MANY_REQUESTS
+        } else {
+            StatusCode::FORBIDDEN
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// The product has fewer images than publishing requires
+pub const INSUFFICIENT_MEDIA: &str = "INSUFFICIENT_MEDIA";
+
+/// 422 refusing to publish a product without enough images
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InsufficientMedia {
+    pub code: &'static str,
+    pub message: String,
+    /// Images a product needs, `MIN_IMAGES_TO_PUBLISH`
+    pub required: u32,
+    /// Images the product has
+    pub found: u64,
+    /// Images still to upload
+    pub missing: u64,
+}
+
+impl InsufficientMedia {
+    pub fn new(required: u32, found: u64) -> Self {
+        let missing = u64::from(required).saturating_sub(found);
+        Self {
+            code: INSUFFICIENT_MEDIA,
+            message: format!(
+                "A product needs at least {required} image(s) to be published; upload {missing} more or save it as a draft"
+            ),
+            required,
+            found,
+            missing,
+        }
+    }
+
+    /// `None` when `found` images are enough
+    pub fn check(required: u32, found: u64) -> Option<Self> {
+        (found < u64::from(required)).then(|| Self::new(required, found))
+    }
+}
+
+/// Either 422 body of [`create_product`]; only used to document it
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum CreateProductRejection {
+    ProhibitedTerm(ProhibitedTermRejection),
+    InsufficientMedia(InsufficientMedia),
+}
+
+impl IntoResponse for InsufficientMedia {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// Whether a product created with these settings goes live straight away.
+/// Scheduled products are checked by the scheduler when they come due.
+pub fn publishes_on_create(draft: bool, publish_at: Option<DateTime<Utc>>) -> bool {
+    !draft && publish_at.is_none()
+}
+
+/// Count an upload against its store's quotas before it reaches storage.
+/// The charge is part of the request transaction, so it is undone when the
+/// upload fails.
+pub async fn charge_media_upload(
+    tx: &Tx,
+    limits: &MediaLimits,
+    product_id: Uuid,
+    bytes: usize,
+) -> Result<(), Response> {
+    let bytes = i64::try_from(bytes).unwrap_or(i64::MAX);
+    match MediaQuota::charge_upload(&**tx, limits, product_id, bytes, Utc::now()).await {
+        Ok(charge) => match MediaQuotaExceeded::from_charge(charge) {
+            Some(refused) => Err(refused.into_response()),
+            None => Ok(()),
+        },
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e).into_response()),
+    }
+}
+
+/// URL serving the original upload for an image_id
+pub fn media_url(image_id: Uuid) -> String {
+    format!("/api/v1/media/{image_id}")
+}
 
-            Json(product).into_response()
+/// URL serving the WebP variant for an image_id
+pub fn webp_media_url(image_id: Uuid) -> String {
+    format!("/api/v1/media/{image_id}.webp")
+}
+
+impl From<ProductMediaModel> for ProductMediaResponse {
+    fn from(media: ProductMediaModel) -> Self {
+        let original_url = media_url(media.id);
+        let url = if media.webp_s3_key.is_some() {
+            webp_media_url(media.id)
+        } else {
+            original_url.clone()
+        };
+        Self {
+            image_id: media.id,
+            url: Some(url),
+            original_url: Some(original_url),
+            content_type: media.content_type,
+            size_bytes: media.size_bytes,
+            webp_size_bytes: media.webp_size_bytes,
+            content_hash: media.content_hash,
+            created_at: media.created_at,
+            updated_at: media.updated_at,
+            storage_degraded: false,
         }
-        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
     }
 }
 
-/// Delete a product by ID
+impl ProductMediaResponse {
+    /// Listing entry without URLs, for when media storage cannot serve them
+    pub(crate) fn degraded(media: ProductMediaModel) -> Self {
+        Self {
+            url: None,
+            original_url: None,
+            storage_degraded: true,
+            ..Self::from(media)
+        }
+    }
+}
+
+/// ETag over everything the media listing exposes, so any upload, replacement
+/// or deletion yields a new value
+pub fn media_etag(media: &[ProductMediaModel]) -> String {
+    let mut hasher = Sha256::new();
+    for item in media {
+        hasher.update(item.id.as_bytes());
+        // Older rows have no content hash; their S3 key is unique per upload
+        hasher.update(item.content_hash.as_deref().unwrap_or(&item.s3_key));
+        hasher.update(item.webp_s3_key.as_deref().unwrap_or(""));
+        hasher.update(item.updated_at.timestamp_micros().to_le_bytes());
+    }
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .take(16)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("\"{digest}\"")
+}
+
+/// Whether `If-None-Match` names `etag` (weak comparison, `*` matches anything)
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// 304 when the client's copy is current, otherwise the listing with its ETag.
+///
+/// While storage is degraded the listing carries no URLs and no ETag, so
+/// clients neither cache it nor keep serving URLs that would fail.
+fn media_listing_response(
+    headers: &HeaderMap,
+    media: Vec<ProductMediaModel>,
+    storage: BreakerState,
+) -> Response {
+    if storage == BreakerState::Open {
+        return Json(
+            media
+                .into_iter()
+                .map(ProductMediaResponse::degraded)
+                .collect::<Vec<_>>(),
+        )
+        .into_response();
+    }
+    let etag = media_etag(&media);
+    if if_none_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        [(header::ETAG, etag)],
+        Json(
+            media
+                .into_iter()
+                .map(ProductMediaResponse::from)
+                .collect::<Vec<_>>(),
+        ),
+    )
+        .into_response()
+}
+
+/// List media for a product
 #[utoipa::path(
-    delete,
-    path = "/products/{id}",
+    get,
+    operation_id = "listProductMedia",
+    path = "/api/v1/products/{id}/media",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous listing")
     ),
     responses(
-        (status = 204, description = "Product deleted successfully"),
-        (status = 400, description = "Bad request - invalid data"),
-        (status = 404, description = "Product not found")
+        (status = 200, description = "Media found", body = Vec<ProductMediaResponse>,
+            headers(("ETag" = String, description = "Changes whenever any listed media changes"))),
+        (status = 304, description = "Media unchanged since the given ETag"),
+        (status = 404, description = "Product not found"),
+        (status = 500, description = "Internal server error")
     ),
     tag = "Products"
 )]
-async fn delete_product(
-    State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
+pub async fn list_product_media(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    match Product::delete(&state.db, id).await {
-        Ok(_) => {
-            // Trigger real-time event: product deleted
-            let event = create_event(
-                EventType::ProductDeleted,
-                id,
-                serde_json::json!({
-                    "product_id": id
-                }),
-            );
-            let _ = state.event_dispatcher.dispatch(event).await;
+    if let Err(e) = Product::get(&db, id).await {
+        return (StatusCode::NOT_FOUND, e).into_response();
+    }
 
-            axum::http::StatusCode::NO_CONTENT.into_response()
+    match ProductMedia::list_by_product(&db, id).await {
+        Ok(media) => {
+            media_listing_response(&headers, media, S3_BREAKER.state(std::time::Instant::now()))
         }
-        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     }
 }
-// --- Media upload/edit/delete endpoints for products ---
 
-#[derive(Serialize, ToSchema)]
-pub struct MediaUploadResponse {
-    #[schema(value_type = String, format = "uuid")]
+/// Store the WebP variant of a fresh upload, record both objects and flag
+/// the upload if it looks like another store's image
+#[allow(clippy::too_many_arguments)]
+async fn record_uploaded_media<S: MediaStorage + Sync>(
+    state: &ProductApiState,
+    tx: &Tx,
+    storage: &S,
+    product_id: Uuid,
     image_id: Uuid,
-    s3_key: String,
+    s3_key: &str,
+    file_name: &str,
+    content_type: &str,
+    file_data: &[u8],
+) -> Result<(), String> {
+    let webp = store_webp_variant(
+        storage,
+        &state.webp_converter,
+        product_id,
+        image_id,
+        file_name,
+        file_data,
+    )
+    .await;
+    let perceptual_hash = dhash_async(file_data.to_vec()).await;
+
+    let media = ProductMedia::create(
+        &**tx,
+        image_id,
+        product_id,
+        s3_key,
+        content_type,
+        file_data.len() as i64,
+        &content_hash(file_data),
+        perceptual_hash,
+        webp,
+    )
+    .await?;
+    MediaSimilarity::flag_matches(&**tx, &media).await?;
+    Ok(())
 }
 
 /// Upload media for a product
 #[utoipa::path(
     post,
-    path = "/products/{id}/media",
+    operation_id = "uploadProductMedia",
+    path = "/api/v1/products/{id}/media",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid")
     ),
     request_body = String,
     responses(
         (status = 200, description = "Media uploaded successfully", body = MediaUploadResponse),
-        (status = 400, description = "Bad request - invalid image or analysis failed"),
+        (status = 400, description = "Bad request - not multipart, or the image failed analysis"),
+        (status = 403, description = "`MEDIA_IMAGE_LIMIT` or `MEDIA_STORAGE_QUOTA` reached", body = MediaQuotaExceeded),
         (status = 404, description = "Product not found"),
-        (status = 500, description = "Internal server error - upload failed")
+        (status = 413, description = "File larger than `UPLOAD_MAX_FILE_BYTES`, or more than `UPLOAD_MAX_FIELDS` fields", body = UploadRejection),
+        (status = 422, description = "No file in `file`, a second file, or another field", body = UploadRejection),
+        (status = 429, description = "`MEDIA_DAILY_LIMIT` reached", body = MediaQuotaExceeded),
+        (status = 500, description = "Internal server error - upload failed"),
+        (status = 503, description = "Media storage temporarily unavailable", body = StorageUnavailableResponse)
     ),
     tag = "Products"
 )]
 pub async fn upload_product_media(
     State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
-    mut multipart: Multipart,
+    UuidPath(id): UuidPath<Uuid>,
+    tx: Tx,
+    upload: MultipartFile,
 ) -> impl IntoResponse {
+    let file = upload.file;
     // 1. Analyze image using the image analysis service
-    let analysis_result = match state.image_analysis.analyze_image(&mut multipart).await {
-        Ok(result) => result,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                format!("Image analysis error: {e}"),
-            )
-                .into_response()
-        }
-    };
-
+    let analysis_result = state.image_analysis.analyze_file(&file).await;
     if !analysis_result.is_valid {
         return (
             StatusCode::BAD_REQUEST,
@@ -321,39 +2107,20 @@ pub async fn upload_product_media(
         )
             .into_response();
     }
-    // 3. Generate new image_id
+    // 2. Generate new image_id
     let image_id = Uuid::new_v4();
-    // 4. Upload to S3/Minio
-    let s3 = match S3MediaStorage::new().await {
+    // 3. Upload to S3/Minio
+    let s3 = match connect_s3_storage().await {
         Ok(s3) => s3,
-        Err(_) => {
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(_)) => {
             // Fallback to stub implementation if S3 initialization fails
-            let stub = StubMediaStorage;
-
-            // Use the file data from analysis result
-            let file_name = analysis_result
-                .file_name
-                .as_deref()
-                .unwrap_or("unknown.jpg");
-            let content_type = analysis_result
-                .file_type
-                .as_deref()
-                .unwrap_or("application/octet-stream");
-
-            // If we have file data, use it, otherwise fall back to the old method
-            let s3_key = if let Some(file_data) = &analysis_result.file_data {
-                match stub
-                    .upload_media_data(id, file_name, file_data, content_type, Some(image_id))
-                    .await
-                {
-                    Ok(key) => key,
-                    Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-                }
-            } else {
-                match stub.upload_media(id, &mut multipart).await {
-                    Ok(key) => key,
-                    Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-                }
+            let s3_key = match StubMediaStorage
+                .upload_media(id, &file, Some(image_id))
+                .await
+            {
+                Ok(key) => key,
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
             };
 
             // Continue with stub result
@@ -365,40 +2132,38 @@ pub async fn upload_product_media(
         }
     };
 
-    // Use the file data from analysis result
-    let file_name = analysis_result
-        .file_name
-        .as_deref()
-        .unwrap_or("unknown.jpg");
-    let content_type = analysis_result
-        .file_type
-        .as_deref()
-        .unwrap_or("application/octet-stream");
-
-    // If we have file data, use it, otherwise fall back to the old method
-    let s3_key = if let Some(file_data) = &analysis_result.file_data {
-        match s3
-            .upload_media_data(id, file_name, file_data, content_type, Some(image_id))
-            .await
-        {
-            Ok(key) => key,
-            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-        }
-    } else {
-        match s3.upload_media(id, &mut multipart).await {
-            Ok(key) => key,
-            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-        }
+    if let Err(refused) = charge_media_upload(&tx, &state.media_limits, id, file.bytes.len()).await
+    {
+        return refused;
+    }
+    let s3_key = match s3.upload_media(id, &file, Some(image_id)).await {
+        Ok(key) => key,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     };
-    // Image handling will be implemented separately
+    if let Err(e) = record_uploaded_media(
+        &state,
+        &tx,
+        &s3,
+        id,
+        image_id,
+        &s3_key,
+        &file.name,
+        &file.content_type,
+        &file.bytes,
+    )
+    .await
+    {
+        error!(s3_key = %s3_key, "Failed to record product media: {:?}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
 
-    // 6. Update the product with the new image_id
-    if let Err(e) = Product::update_image(&state.db, id, Some(image_id)).await {
+    // 4. Update the product with the new image_id
+    if let Err(e) = Product::update_image(&*tx, id, Some(image_id)).await {
         error!("Failed to update product with image_id: {:?}", e);
-        // Continue anyway, as the image was uploaded successfully
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
     }
 
-    // 7. Trigger real-time events
+    // 5. Trigger real-time events
     let event = create_event(
         EventType::ProductMediaUploaded,
         id,
@@ -422,36 +2187,34 @@ pub async fn upload_product_media(
 /// Replace media for a product
 #[utoipa::path(
     put,
-    path = "/products/{id}/media",
+    operation_id = "editProductMedia",
+    path = "/api/v1/products/{id}/media",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid")
     ),
     request_body = String,
     responses(
         (status = 200, description = "Media replaced successfully", body = MediaUploadResponse),
-        (status = 400, description = "Bad request - invalid image or analysis failed"),
+        (status = 400, description = "Bad request - not multipart, or the image failed analysis"),
+        (status = 403, description = "`MEDIA_IMAGE_LIMIT` or `MEDIA_STORAGE_QUOTA` reached", body = MediaQuotaExceeded),
         (status = 404, description = "Product not found"),
-        (status = 500, description = "Internal server error - upload failed")
+        (status = 413, description = "File larger than `UPLOAD_MAX_FILE_BYTES`, or more than `UPLOAD_MAX_FIELDS` fields", body = UploadRejection),
+        (status = 422, description = "No file in `file`, a second file, or another field", body = UploadRejection),
+        (status = 429, description = "`MEDIA_DAILY_LIMIT` reached", body = MediaQuotaExceeded),
+        (status = 500, description = "Internal server error - upload failed"),
+        (status = 503, description = "Media storage temporarily unavailable", body = StorageUnavailableResponse)
     ),
     tag = "Products"
 )]
 pub async fn edit_product_media(
     State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
-    mut multipart: Multipart,
+    UuidPath(id): UuidPath<Uuid>,
+    tx: Tx,
+    upload: MultipartFile,
 ) -> impl IntoResponse {
     // Same as upload, but replace existing media
-    let analysis_result = match state.image_analysis.analyze_image(&mut multipart).await {
-        Ok(result) => result,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                format!("Image analysis error: {e}"),
-            )
-                .into_response()
-        }
-    };
-
+    let file = upload.file;
+    let analysis_result = state.image_analysis.analyze_file(&file).await;
     if !analysis_result.is_valid {
         return (
             StatusCode::BAD_REQUEST,
@@ -461,40 +2224,19 @@ pub async fn edit_product_media(
     }
 
     let image_id = Uuid::new_v4();
-    let s3 = match S3MediaStorage::new().await {
+    let s3 = match connect_s3_storage().await {
         Ok(s3) => s3,
-        Err(_) => {
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(_)) => {
             // Fallback to stub implementation if S3 initialization fails
-            let stub = StubMediaStorage;
-
-            // Use the file data from analysis result
-            let file_name = analysis_result
-                .file_name
-                .as_deref()
-                .unwrap_or("unknown.jpg");
-            let content_type = analysis_result
-                .file_type
-                .as_deref()
-                .unwrap_or("application/octet-stream");
-
-            // If we have file data, use it, otherwise fall back to the old method
-            let s3_key = if let Some(file_data) = &analysis_result.file_data {
-                match stub
-                    .upload_media_data(id, file_name, file_data, content_type, Some(image_id))
-                    .await
-                {
-                    Ok(key) => key,
-                    Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-                }
-            } else {
-                match stub.upload_media(id, &mut multipart).await {
-                    Ok(key) => key,
-                    Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-                }
+            let s3_key = match StubMediaStorage
+                .upload_media(id, &file, Some(image_id))
+                .await
+            {
+                Ok(key) => key,
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
             };
 
-            // Image handling will be implemented separately
-
             // Trigger event for media replacement
             let event = create_event(
                 EventType::ProductMediaReplaced,
@@ -517,37 +2259,35 @@ pub async fn edit_product_media(
         }
     };
 
-    // Use the file data from analysis result
-    let file_name = analysis_result
-        .file_name
-        .as_deref()
-        .unwrap_or("unknown.jpg");
-    let content_type = analysis_result
-        .file_type
-        .as_deref()
-        .unwrap_or("application/octet-stream");
-
-    // If we have file data, use it, otherwise fall back to the old method
-    let s3_key = if let Some(file_data) = &analysis_result.file_data {
-        match s3
-            .upload_media_data(id, file_name, file_data, content_type, Some(image_id))
-            .await
-        {
-            Ok(key) => key,
-            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-        }
-    } else {
-        match s3.upload_media(id, &mut multipart).await {
-            Ok(key) => key,
-            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-        }
+    if let Err(refused) = charge_media_upload(&tx, &state.media_limits, id, file.bytes.len()).await
+    {
+        return refused;
+    }
+    let s3_key = match s3.upload_media(id, &file, Some(image_id)).await {
+        Ok(key) => key,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     };
-    // Image handling will be implemented separately
+    if let Err(e) = record_uploaded_media(
+        &state,
+        &tx,
+        &s3,
+        id,
+        image_id,
+        &s3_key,
+        &file.name,
+        &file.content_type,
+        &file.bytes,
+    )
+    .await
+    {
+        error!(s3_key = %s3_key, "Failed to record product media: {:?}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
 
     // Update the product with the new image_id
-    if let Err(e) = Product::update_image(&state.db, id, Some(image_id)).await {
+    if let Err(e) = Product::update_image(&*tx, id, Some(image_id)).await {
         error!("Failed to update product with image_id: {:?}", e);
-        // Continue anyway, as the image was uploaded successfully
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
     }
 
     // Trigger event for media replacement
@@ -571,34 +2311,137 @@ pub async fn edit_product_media(
         .into_response()
 }
 
+/// Remove an upload and its WebP variant from storage
+async fn delete_media_objects<S: MediaStorage + Sync>(
+    storage: &S,
+    media: &ProductMediaModel,
+) -> Result<(), String> {
+    storage.delete_media(&media.s3_key).await?;
+    if let Some(webp_key) = &media.webp_s3_key {
+        storage.delete_media(webp_key).await?;
+    }
+    Ok(())
+}
+
+/// Delete one image of a product, freeing its bytes in the store's quota
+#[utoipa::path(
+    delete,
+    operation_id = "deleteProductMediaItem",
+    path = "/api/v1/products/{id}/media/{image_id}",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        ("image_id" = String, Path, description = "Image ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Image deleted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Product or image not found"),
+        (status = 500, description = "Internal server error - deletion failed"),
+        (status = 503, description = "Media storage temporarily unavailable", body = StorageUnavailableResponse)
+    ),
+    tag = "Products"
+)]
+pub async fn delete_product_media_item(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath((id, image_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+    tx: Tx,
+) -> impl IntoResponse {
+    let product = match Product::get(&db, id).await {
+        Ok(product) => product,
+        Err(_) => return (StatusCode::NOT_FOUND, "Product not found").into_response(),
+    };
+    if let Err(err) = owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let media = match ProductMedia::get(&db, image_id).await {
+        Ok(Some(media)) if media.product_id == id => media,
+        Ok(_) => return (StatusCode::NOT_FOUND, "Media not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let s3 = match connect_s3_storage().await {
+        Ok(s3) => s3,
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(e)) => {
+            error!(error = %e, "Failed to initialize S3 storage");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to initialize storage",
+            )
+                .into_response();
+        }
+    };
+
+    // Rows first: if storage then fails, the transaction puts them back
+    if let Err(e) = ProductMedia::delete(&*tx, product.store_id, &media).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+    if product.image_id == Some(image_id) {
+        if let Err(e) = Product::update_image(&*tx, id, None).await {
+            error!("Failed to clear product image_id: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    }
+    if let Err(e) = delete_media_objects(&s3, &media).await {
+        error!(s3_key = %media.s3_key, "Failed to delete media object: {:?}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+
+    let event = create_event(
+        EventType::ProductMediaDeleted,
+        id,
+        serde_json::json!({
+            "product_id": id,
+            "previous_image_id": image_id
+        }),
+    );
+    let _ = events.dispatch(event).await;
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
 /// Delete media for a product
 #[utoipa::path(
     delete,
-    path = "/products/{id}/media",
+    operation_id = "deleteProductMedia",
+    path = "/api/v1/products/{id}/media",
     params(
-        ("id" = UuidSchema, Path, description = "Product ID")
+        ("id" = String, Path, description = "Product ID", format = "uuid")
     ),
     responses(
         (status = 200, description = "Media deleted successfully"),
         (status = 404, description = "Product not found"),
-        (status = 500, description = "Internal server error - deletion failed")
+        (status = 500, description = "Internal server error - deletion failed"),
+        (status = 503, description = "Media storage temporarily unavailable", body = StorageUnavailableResponse)
     ),
     tag = "Products"
 )]
 pub async fn delete_product_media(
     State(state): State<ProductApiState>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
+    tx: Tx,
 ) -> impl IntoResponse {
     // 1. Get product to find current image_id
-    let _product = match Product::get(&state.db, id).await {
+    let product = match Product::get(&state.db, id).await {
         Ok(product) => product,
         Err(_) => return (StatusCode::NOT_FOUND, "Product not found").into_response(),
     };
+    // Media row of the current image; older uploads were never recorded
+    let media = match product.image_id {
+        Some(image_id) => match ProductMedia::get(&state.db, image_id).await {
+            Ok(media) => media,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        },
+        None => None,
+    };
 
     // 2. Delete from S3/Minio
-    let s3 = match S3MediaStorage::new().await {
+    let s3 = match connect_s3_storage().await {
         Ok(s3) => s3,
-        Err(_) => {
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(_)) => {
             // Fallback to stub implementation
             let stub = StubMediaStorage;
             if let Err(e) = stub.delete_media(&format!("products/{id}/media")).await {
@@ -621,21 +2464,33 @@ pub async fn delete_product_media(
         }
     };
 
-    // Delete from S3 using the stored s3_key (this would need to be stored in the product model)
-    // For now, we'll use a placeholder key
-    let s3_key = format!("products/{id}/media");
-    if let Err(e) = s3.delete_media(&s3_key).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    match &media {
+        Some(media) => {
+            // Frees the bytes in the store's quota along with the row
+            if let Err(e) = ProductMedia::delete(&*tx, product.store_id, media).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+            }
+            if let Err(e) = delete_media_objects(&s3, media).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+            }
+        }
+        None => {
+            // Unrecorded upload: fall back to the legacy placeholder key
+            let s3_key = format!("products/{id}/media");
+            if let Err(e) = s3.delete_media(&s3_key).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+            }
+        }
     }
 
     // Clear the image_id in the product record
     if let Err(e) = Product::update_image(
-        &state.db, id, None, // Set to None to clear the image_id
+        &*tx, id, None, // Set to None to clear the image_id
     )
     .await
     {
         error!("Failed to clear product image_id: {:?}", e);
-        // Continue anyway, as the image was deleted successfully
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
     }
 
     // Trigger event for media deletion
@@ -651,3 +2506,569 @@ pub async fn delete_product_media(
 
     (StatusCode::OK, "Media deleted").into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn media(product_id: Uuid, bytes: &[u8]) -> ProductMediaModel {
+        let image_id = Uuid::new_v4();
+        ProductMediaModel {
+            id: image_id,
+            product_id,
+            s3_key: format!("products/{product_id}/media/{image_id}.jpg"),
+            content_type: "image/jpeg".to_string(),
+            size_bytes: bytes.len() as i64,
+            webp_s3_key: None,
+            webp_size_bytes: None,
+            content_hash: Some(content_hash(bytes)),
+            perceptual_hash: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn conditional(etag: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(etag).unwrap());
+        headers
+    }
+
+    fn etag_of(response: &Response) -> String {
+        response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_media_listing_200_then_304_then_200_after_replacement() {
+        let product_id = Uuid::new_v4();
+        let mut listing = vec![media(product_id, b"first photo")];
+
+        let first =
+            media_listing_response(&HeaderMap::new(), listing.clone(), BreakerState::Closed);
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = etag_of(&first);
+
+        let cached =
+            media_listing_response(&conditional(&etag), listing.clone(), BreakerState::Closed);
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(etag_of(&cached), etag);
+
+        // PUT /products/:id/media records the replacement as a new media row
+        listing.push(media(product_id, b"second photo"));
+        let changed = media_listing_response(&conditional(&etag), listing, BreakerState::Closed);
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(etag_of(&changed), etag);
+    }
+
+    #[tokio::test]
+    async fn test_media_listing_withholds_urls_while_storage_is_down() {
+        let listing = vec![media(Uuid::new_v4(), b"photo")];
+        let etag = media_etag(&listing);
+
+        // A cached ETag must not turn into a 304 for a degraded listing
+        let response = media_listing_response(&conditional(&etag), listing, BreakerState::Open);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let entries: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entries[0]["url"], serde_json::Value::Null);
+        assert_eq!(entries[0]["original_url"], serde_json::Value::Null);
+        assert_eq!(entries[0]["storage_degraded"], true);
+        assert_eq!(entries[0]["size_bytes"], 5);
+    }
+
+    #[test]
+    fn test_media_etag_tracks_content_not_just_ids() {
+        let original = media(Uuid::new_v4(), b"photo");
+        let mut reencoded = original.clone();
+        reencoded.content_hash = Some(content_hash(b"same id, new bytes"));
+        let etag = media_etag(std::slice::from_ref(&original));
+        assert_ne!(etag, media_etag(&[reencoded]));
+        assert_eq!(etag, media_etag(&[original]));
+    }
+
+    #[test]
+    fn test_publishing_on_create_needs_images() {
+        // Creating a published product means creating it without images
+        assert!(publishes_on_create(false, None));
+        let refused = InsufficientMedia::check(1, 0).unwrap();
+        assert_eq!((refused.code, refused.missing), (INSUFFICIENT_MEDIA, 1));
+        assert_eq!(
+            refused.into_response().status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        // Drafts and scheduled products are not checked until they go live
+        assert!(!publishes_on_create(true, None));
+        assert!(!publishes_on_create(false, Some(Utc::now())));
+        assert!(InsufficientMedia::check(0, 0).is_none());
+        assert!(InsufficientMedia::check(2, 3).is_none());
+    }
+
+    #[test]
+    fn test_if_none_match_accepts_lists_and_weak_tags() {
+        let etag = media_etag(&[]);
+        assert!(if_none_match(
+            &conditional(&format!("\"other\", W/{etag}")),
+            &etag
+        ));
+        assert!(if_none_match(&conditional("*"), &etag));
+        assert!(!if_none_match(&conditional("\"other\""), &etag));
+        assert!(!if_none_match(&HeaderMap::new(), &etag));
+    }
+
+    async fn send(
+        db: &DatabaseConnection,
+        method: &str,
+        uri: &str,
+        caller: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        use tower::ServiceExt;
+
+        let mut request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(caller) = caller {
+            let token = JwtService::new()
+                .unwrap()
+                .generate_token(caller.into(), String::new(), "default".into())
+                .unwrap();
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = request
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        let response = router(db.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_products_are_created_in_the_callers_store() {
+        use crate::db::testing::{seed_store, sqlite};
+        use crate::entity::product;
+        use sea_orm::{ActiveModelTrait, Set};
+
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        let ndole = |store_id: Option<Uuid>| {
+            serde_json::json!({
+                "store_id": store_id,
+                "name": "Ndole",
+                "price": 1500.0,
+                "quantity_available": 3,
+                "draft": true
+            })
+        };
+
+        // Without store_id, a seller's only store is used
+        let (status, body) = send(&db, "POST", "/products", Some("seller-1"), ndole(None)).await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(body["store_id"], store_id.to_string());
+        let product_id = Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+
+        // Listed once it goes live, as if its photo had been uploaded and it
+        // was published
+        product::ActiveModel {
+            id: Set(product_id),
+            is_published: Set(true),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        let (status, body) = send(
+            &db,
+            "GET",
+            &format!("/products?store_id={store_id}"),
+            None,
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["items"][0]["id"], product_id.to_string());
+        assert_eq!(body["total"], 1);
+
+        let cases = [
+            (None, ndole(Some(store_id)), StatusCode::UNAUTHORIZED),
+            (
+                Some("seller-2"),
+                ndole(Some(store_id)),
+                StatusCode::FORBIDDEN,
+            ),
+            (Some("seller-2"), ndole(None), StatusCode::NOT_FOUND),
+            (
+                Some("seller-1"),
+                ndole(Some(Uuid::new_v4())),
+                StatusCode::NOT_FOUND,
+            ),
+        ];
+        for (caller, body, expected) in cases {
+            let (status, body) = send(&db, "POST", "/products", caller, body).await;
+            assert_eq!(status, expected, "{caller:?}: {body}");
+        }
+
+        seed_store(&db, "seller-1").await;
+        let (status, _) = send(&db, "POST", "/products", Some("seller-1"), ndole(None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_delete_archives_until_restored_or_deleted_for_good() {
+        use crate::db::testing::{seed_product, sqlite};
+
+        let db = sqlite().await;
+        let id = seed_product(&db, "seller-1").await;
+        let store_id = Product::get(&db, id).await.unwrap().store_id;
+        let product_uri = format!("/products/{id}");
+        let listed = |db: DatabaseConnection| async move {
+            let uri = format!("/products?store_id={store_id}");
+            let (_, body) = send(&db, "GET", &uri, None, serde_json::Value::Null).await;
+            body["total"].as_u64().unwrap()
+        };
+        let null = serde_json::Value::Null;
+
+        let (status, _) = send(&db, "DELETE", &product_uri, None, null.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&db, "DELETE", &product_uri, Some("seller-2"), null.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = send(&db, "DELETE", &product_uri, Some("seller-1"), null.clone()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        // Gone from buyers, but the row is kept
+        let (status, _) = send(&db, "GET", &product_uri, None, null.clone()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(listed(db.clone()).await, 0);
+        assert!(Product::get(&db, id).await.unwrap().archived_at.is_some());
+
+        let restore_uri = format!("/products/{id}/restore");
+        let (status, _) = send(&db, "POST", &restore_uri, Some("seller-2"), null.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&db, "POST", &restore_uri, Some("seller-1"), null.clone()).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["publication_status"], "published");
+        assert_eq!(body["archived_at"], serde_json::Value::Null);
+        let (status, _) = send(&db, "GET", &product_uri, None, null.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed(db.clone()).await, 1);
+
+        let permanent = format!("{product_uri}?permanent=true");
+        let (status, _) = send(&db, "DELETE", &permanent, Some("seller-2"), null.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&db, "DELETE", &permanent, Some("seller-1"), null.clone()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(Product::get(&db, id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_copy_the_listing_but_not_its_stock_or_images() {
+        use crate::db::testing::{seed_product, sqlite};
+        use crate::entity::product;
+        use sea_orm::{ActiveModelTrait, Set};
+
+        let db = sqlite().await;
+        let id = seed_product(&db, "seller-1").await;
+        let original = product::ActiveModel {
+            id: Set(id),
+            sku: Set(Some("WAX-01".to_string())),
+            description: Set(Some("Six yards, blue".to_string())),
+            image_id: Set(Some(Uuid::new_v4())),
+            attributes: Set(serde_json::json!({"color": "blue"})),
+            return_policy_source: Set("product".to_string()),
+            returns_accepted: Set(true),
+            return_window_days: Set(Some(14)),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        let uri = format!("/products/{id}/duplicate");
+        let null = serde_json::Value::Null;
+
+        let (status, _) = send(&db, "POST", &uri, None, null.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&db, "POST", &uri, Some("seller-2"), null.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let missing = format!("/products/{}/duplicate", Uuid::new_v4());
+        let (status, _) = send(&db, "POST", &missing, Some("seller-1"), null.clone()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, copy) = send(&db, "POST", &uri, Some("seller-1"), null).await;
+        assert_eq!(status, StatusCode::CREATED, "{copy}");
+        assert_ne!(copy["id"], id.to_string());
+        assert_eq!(copy["store_id"], original.store_id.to_string());
+        assert_eq!(copy["name"], "Wax print (copy)");
+        assert_eq!(copy["description"], "Six yards, blue");
+        assert_eq!(copy["price"], 5000.0);
+        assert_eq!(copy["attributes"]["color"], "blue");
+        assert_eq!(copy["returns_accepted"], true);
+        assert_eq!(copy["return_window_days"], 14);
+        assert_eq!(copy["quantity_available"], 0);
+        assert_eq!(copy["sku"], serde_json::Value::Null);
+        assert_eq!(copy["image_id"], serde_json::Value::Null);
+        // A live original, but the copy has none of the images it needs
+        assert_eq!(copy["publication_status"], "draft");
+
+        let original_now = Product::get(&db, id).await.unwrap();
+        assert_eq!(original_now.quantity_available, 3);
+        assert_eq!(original_now.image_id, original.image_id);
+    }
+
+    #[test]
+    fn test_copy_names_stay_within_the_name_limit() {
+        assert_eq!(copy_name("Phone"), "Phone (copy)");
+        let long = "é".repeat(MAX_NAME_LEN);
+        let copied = copy_name(&long);
+        assert_eq!(copied.chars().count(), MAX_NAME_LEN);
+        assert!(copied.ends_with(COPY_SUFFIX));
+    }
+
+    #[test]
+    fn test_patch_tells_null_from_missing() {
+        let patch: UpdateProductPatch =
+            serde_json::from_str(r#"{"description": null, "price": 4500}"#).unwrap();
+        assert_eq!(patch.description, Some(None));
+        assert_eq!(patch.sku, None);
+        assert_eq!(patch.price, Some(4500.0));
+        let patch: UpdateProductPatch =
+            serde_json::from_str(r#"{"description": "Cotton", "sale_price": null}"#).unwrap();
+        assert_eq!(patch.description, Some(Some("Cotton".to_string())));
+        assert_eq!(patch.sale_price, Some(None));
+        assert_eq!(patch.sale_ends_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_patch_changes_only_the_fields_sent() {
+        use crate::db::testing;
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let db = testing::sqlite().await;
+        let id = testing::seed_product(&db, "seller-1").await;
+        let app = router(db.clone());
+        let token = |relay_id: &str| {
+            let token = JwtService::new()
+                .unwrap()
+                .generate_token(relay_id.into(), String::new(), "default".into())
+                .unwrap();
+            format!("Bearer {token}")
+        };
+        let patch_as = |caller: &str, body: &str| {
+            let request = axum::http::Request::builder()
+                .method("PATCH")
+                .uri(format!("/products/{id}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, token(caller))
+                .body(Body::from(body.to_owned()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        let patch = |body: &str| patch_as("seller-1", body);
+
+        let before = Product::get(&db, id).await.unwrap();
+        let response = patch_as("seller-2", r#"{"description": "Not mine"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(Product::get(&db, id).await.unwrap(), before);
+
+        let response = patch(r#"{"description": "Hand-dyed cotton"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let after = Product::get(&db, id).await.unwrap();
+        assert_eq!(after.description.as_deref(), Some("Hand-dyed cotton"));
+        assert_eq!(
+            changed_fields(&before, &after),
+            serde_json::json!({ "description": "Hand-dyed cotton" })
+        );
+
+        let response = patch(r#"{"price": 4000, "quantity_available": 7}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let product = Product::get(&db, id).await.unwrap();
+        assert_eq!((product.price, product.quantity_available), (4000.0, 7));
+        assert_eq!(product.description.as_deref(), Some("Hand-dyed cotton"));
+        assert_eq!(product.name, before.name);
+        assert_eq!(product.return_policy_source, before.return_policy_source);
+
+        let response = patch(r#"{"description": null}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(Product::get(&db, id).await.unwrap().description, None);
+
+        // The patched product is validated as a whole: a sale above the
+        // price is refused, and nothing is saved
+        let ends = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+        let response = patch(&format!(
+            r#"{{"sale_price": 4500, "sale_ends_at": "{ends}"}}"#
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = patch(r#"{"name": "", "price": 3000}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let product = Product::get(&db, id).await.unwrap();
+        assert_eq!((product.price, product.sale_price), (4000.0, None));
+
+        let missing = axum::http::Request::builder()
+            .method("PATCH")
+            .uri(format!("/products/{}", Uuid::new_v4()))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, token("seller-1"))
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = app.clone().oneshot(missing).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Keeps the low-stock warnings sent
+    struct LowStockInbox(Arc<std::sync::Mutex<Vec<serde_json::Value>>>);
+
+    #[async_trait::async_trait]
+    impl crate::events::EventHandler for LowStockInbox {
+        async fn handle_event(&self, event: &Event) -> Result<(), String> {
+            if matches!(event.event_type, EventType::ProductLowStock) {
+                self.0.lock().unwrap().push(event.data.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_low_stock_is_announced_once_per_crossing() {
+        use crate::db::testing;
+
+        let db = testing::sqlite().await;
+        let id = testing::seed_product(&db, "seller-1").await;
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_handler(Box::new(LowStockInbox(sent.clone())));
+        let state = ProductApiState {
+            db: db.clone(),
+            event_dispatcher: Arc::new(dispatcher),
+            jwt_service: Arc::new(JwtService::new().unwrap_or_default()),
+            image_analysis: Arc::new(ImageAnalysisService::new()),
+            webp_converter: Arc::new(WebpConverter::default()),
+            media_limits: Arc::new(MediaLimits::default()),
+            default_currency: DEFAULT_CURRENCY.to_string(),
+            upload_limits: Arc::default(),
+        };
+        let token = state
+            .jwt_service
+            .generate_token("seller-1".into(), String::new(), "default".into())
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        let patch = |body: serde_json::Value| {
+            let (db, events, headers) = (
+                state.db.clone(),
+                state.event_dispatcher.clone(),
+                headers.clone(),
+            );
+            async move {
+                let payload = serde_json::from_value(body).unwrap();
+                patch_product(
+                    State(db),
+                    State(events),
+                    UuidPath(id),
+                    headers,
+                    Json(payload),
+                )
+                .await
+                .into_response()
+                .status()
+            }
+        };
+
+        let status =
+            patch(serde_json::json!({ "quantity_available": 5, "low_stock_threshold": 2 }));
+        assert_eq!(status.await, StatusCode::OK);
+        // Down to the threshold warns; further sales stay quiet until a
+        // restock lifts the stock back above it
+        for quantity in [3, 2, 1, 0, 6, 1] {
+            let status = patch(serde_json::json!({ "quantity_available": quantity }));
+            assert_eq!(status.await, StatusCode::OK);
+        }
+
+        // Without a threshold nothing is sent
+        let status =
+            patch(serde_json::json!({ "quantity_available": 9, "low_stock_threshold": null }));
+        assert_eq!(status.await, StatusCode::OK);
+        let status = patch(serde_json::json!({ "quantity_available": 0 }));
+        assert_eq!(status.await, StatusCode::OK);
+
+        let product = Product::get(&db, id).await.unwrap();
+        let sent = sent.lock().unwrap();
+        let remaining: Vec<_> = sent
+            .iter()
+            .map(|data| &data["quantity_available"])
+            .collect();
+        assert_eq!(remaining, [2, 1]);
+        assert_eq!(sent[0]["low_stock_threshold"], 2);
+        assert_eq!(sent[0]["store_id"], product.store_id.to_string());
+    }
+
+    #[test]
+    fn test_search_text_is_required() {
+        let query = |q: Option<&str>| SearchProductsQuery {
+            q: q.map(str::to_owned),
+            store_id: None,
+            category_id: None,
+            min_price: None,
+            max_price: None,
+            currency: None,
+            sort: None,
+        };
+        assert_eq!(query(Some("  wax print ")).text(), Ok("wax print"));
+        for blank in [None, Some(""), Some(" \t ")] {
+            assert_eq!(query(blank).text(), Err("q must not be empty".to_string()));
+        }
+        let long = "a".repeat(MAX_SEARCH_LEN + 1);
+        assert!(query(Some(&long)).text().is_err());
+    }
+
+    #[test]
+    fn test_list_refuses_negative_or_inverted_price_ranges() {
+        let query = |min_price, max_price| ListProductsQuery {
+            store_id: Uuid::new_v4(),
+            category: None,
+            min_price,
+            max_price,
+            returns_accepted: None,
+            delivery_available: None,
+            currency: None,
+            sort: None,
+            fields: None,
+        };
+        for (min, max) in [(None, None), (Some(0.0), None), (Some(500.0), Some(500.0))] {
+            assert!(query(min, max).price_filter().is_ok());
+        }
+        for (min, max) in [
+            (Some(-1.0), None),
+            (None, Some(-0.5)),
+            (Some(f64::NAN), None),
+            (Some(900.0), Some(100.0)),
+        ] {
+            assert!(matches!(
+                query(min, max).price_filter(),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
+}
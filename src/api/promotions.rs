@@ -0,0 +1,335 @@
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::stores::public_store;
+use crate::db::promotions::{is_active, Promotion};
+use crate::db::stores::Store;
+use crate::entity::store::Model as StoreModel;
+use crate::entity::store_promotion::Model as PromotionModel;
+use crate::tenant::tenant_from_headers;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreatePromotionRequest {
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// Slot on the landing page, starting at 1
+    pub position: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdatePromotionRequest {
+    pub position: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+/// Promotion with whether it is showing right now
+#[derive(Serialize, ToSchema)]
+pub struct PromotionResponse {
+    #[serde(flatten)]
+    pub promotion: PromotionModel,
+    pub active: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PromotionsListResponse {
+    pub promotions: Vec<PromotionResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeaturedStore {
+    pub position: i32,
+    pub ends_at: DateTime<Utc>,
+    pub store: StoreModel,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeaturedStoresResponse {
+    pub stores: Vec<FeaturedStore>,
+}
+
+fn check_slot(
+    position: i32,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+) -> Result<(), &'static str> {
+    if position < 1 {
+        return Err("position must be 1 or greater");
+    }
+    if ends_at <= starts_at {
+        return Err("ends_at must be after starts_at");
+    }
+    Ok(())
+}
+
+/// 409 if the slot is already booked for any part of the window
+async fn ensure_slot_free(
+    db: &DatabaseConnection,
+    position: i32,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    exclude_id: Option<Uuid>,
+) -> Result<(), axum::response::Response> {
+    match Promotion::find_overlapping(db, position, starts_at, ends_at, exclude_id).await {
+        Ok(None) => Ok(()),
+        Ok(Some(existing)) => Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Position {} is already booked from {} to {} (promotion {})",
+                position, existing.starts_at, existing.ends_at, existing.id
+            ),
+        )
+            .into_response()),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err).into_response()),
+    }
+}
+
+/// Book a homepage slot for a store
+#[utoipa::path(
+    post,
+    operation_id = "createPromotion",
+    path = "/api/v1/admin/promotions",
+    tag = "Admin",
+    request_body = CreatePromotionRequest,
+    responses(
+        (status = 201, description = "Promotion created", body = PromotionModel),
+        (status = 400, description = "Invalid position or window"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Store not found"),
+        (status = 409, description = "Position already booked for an overlapping window")
+    )
+)]
+pub async fn create_promotion(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(request): Json<CreatePromotionRequest>,
+) -> impl IntoResponse {
+    let admin = match require_admin(&headers) {
+        Ok(claims) => claims,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(err) = check_slot(request.position, request.starts_at, request.ends_at) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    if let Err(err) = Store::get(&db, request.store_id).await {
+        return (StatusCode::NOT_FOUND, err).into_response();
+    }
+    if let Err(conflict) = ensure_slot_free(
+        &db,
+        request.position,
+        request.starts_at,
+        request.ends_at,
+        None,
+    )
+    .await
+    {
+        return conflict;
+    }
+
+    match Promotion::create(
+        &db,
+        request.store_id,
+        request.position,
+        request.starts_at,
+        request.ends_at,
+        &admin.relay_id,
+    )
+    .await
+    {
+        Ok(promotion) => (StatusCode::CREATED, Json(promotion)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List all promotions, past and upcoming
+#[utoipa::path(
+    get,
+    operation_id = "listPromotions",
+    path = "/api/v1/admin/promotions",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Promotions by position and start time", body = PromotionsListResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+pub async fn list_promotions(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match Promotion::list(&db).await {
+        Ok(promotions) => {
+            let now = Utc::now();
+            Json(PromotionsListResponse {
+                promotions: promotions
+                    .into_iter()
+                    .map(|promotion| PromotionResponse {
+                        active: is_active(&promotion, now),
+                        promotion,
+                    })
+                    .collect(),
+            })
+            .into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Move or reschedule a promotion
+#[utoipa::path(
+    put,
+    operation_id = "updatePromotion",
+    path = "/api/v1/admin/promotions/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Promotion ID", format = "uuid")
+    ),
+    request_body = UpdatePromotionRequest,
+    responses(
+        (status = 200, description = "Promotion updated", body = PromotionModel),
+        (status = 400, description = "Invalid position or window"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Promotion not found"),
+        (status = 409, description = "Position already booked for an overlapping window")
+    )
+)]
+pub async fn update_promotion(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<UpdatePromotionRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    if let Err(err) = check_slot(request.position, request.starts_at, request.ends_at) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let promotion = match Promotion::get(&db, id).await {
+        Ok(Some(promotion)) => promotion,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Promotion not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if let Err(conflict) = ensure_slot_free(
+        &db,
+        request.position,
+        request.starts_at,
+        request.ends_at,
+        Some(id),
+    )
+    .await
+    {
+        return conflict;
+    }
+
+    match Promotion::update(
+        &db,
+        promotion,
+        request.position,
+        request.starts_at,
+        request.ends_at,
+    )
+    .await
+    {
+        Ok(promotion) => Json(promotion).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Cancel a promotion
+#[utoipa::path(
+    delete,
+    operation_id = "deletePromotion",
+    path = "/api/v1/admin/promotions/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Promotion ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "Promotion deleted"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Promotion not found")
+    )
+)]
+pub async fn delete_promotion(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection.into_response();
+    }
+    match Promotion::delete(&db, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Promotion not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Stores currently promoted on the landing page, in slot order
+#[utoipa::path(
+    get,
+    operation_id = "listFeaturedStores",
+    path = "/api/v1/featured-stores",
+    tag = "Stores",
+    responses(
+        (status = 200, description = "Promoted stores in position order", body = FeaturedStoresResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_featured_stores(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    match Promotion::featured_stores(&db, &tenant, Utc::now()).await {
+        Ok(rows) => Json(FeaturedStoresResponse {
+            stores: rows
+                .into_iter()
+                .map(|(promotion, store)| FeaturedStore {
+                    position: promotion.position,
+                    ends_at: promotion.ends_at,
+                    store: public_store(store),
+                })
+                .collect(),
+        })
+        .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_check_slot() {
+        let now = Utc::now();
+        assert!(check_slot(1, now, now + Duration::hours(1)).is_ok());
+        assert!(check_slot(0, now, now + Duration::hours(1)).is_err());
+        // An empty window can never be shown
+        assert!(check_slot(1, now, now).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::db::public_stats::PublicStats;
+use crate::tenant::tenant_from_headers;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// The figures change only when the job reruns, hourly by default
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// Rounded marketplace figures for public display; see `crate::public_stats`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PublicStatsResponse {
+    /// Visible products, rounded down: show as "at least"
+    pub products: i64,
+    /// Stores, rounded down like `products`
+    pub stores: i64,
+    /// Cities with enough stores to be named
+    pub city_count: i64,
+    /// The busiest of those cities
+    pub cities: Vec<String>,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Marketplace size for public widgets
+///
+/// Counts are rounded and small cities left out, so no exact internal
+/// figure is exposed. Served from figures a background job computes; no
+/// token needed.
+#[utoipa::path(
+    get,
+    operation_id = "getPublicStats",
+    path = "/api/v1/public-stats",
+    tag = "System",
+    responses(
+        (status = 200, description = "Rounded product, store and city figures", body = PublicStatsResponse),
+        (status = 400, description = "Invalid X-Tenant-Id"),
+        (status = 429, description = "Too many requests without a token"),
+        (status = 503, description = "Figures not computed yet"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn public_stats(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let stats = match PublicStats::get(&db, &tenant).await {
+        Ok(Some(stats)) => stats,
+        Ok(None) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Public stats are not computed yet.",
+            )
+                .into_response()
+        }
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let response = PublicStatsResponse {
+        products: stats.products,
+        stores: stats.stores,
+        city_count: stats.city_count,
+        cities: serde_json::from_value(stats.cities).unwrap_or_default(),
+        computed_at: stats.computed_at,
+    };
+    ([(header::CACHE_CONTROL, CACHE_CONTROL)], Json(response)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{seed_product, sqlite};
+    use crate::public_stats::PublicStatsRules;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_serves_the_stored_figures_with_a_long_cache() {
+        let db = sqlite().await;
+        seed_product(&db, "seller-1").await;
+        let app = Router::new()
+            .route("/api/v1/public-stats", get(public_stats))
+            .with_state(db.clone());
+        let call = || async {
+            let request = Request::builder()
+                .uri("/api/v1/public-stats")
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request).await.unwrap()
+        };
+
+        assert_eq!(call().await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let rules = PublicStatsRules {
+            granularity: 1,
+            min_stores_per_city: 1,
+        };
+        PublicStats::refresh_all(&db, &rules, Utc::now())
+            .await
+            .unwrap();
+        let response = call().await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], CACHE_CONTROL);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["products"], 1);
+        assert_eq!(body["stores"], 1);
+        assert_eq!(body["cities"], serde_json::json!([]));
+    }
+}
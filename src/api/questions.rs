@@ -0,0 +1,558 @@
+use crate::api::extract::UuidPath;
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::api::moderation::ContentRejection;
+use crate::api::moderation::{moderate_text, screen};
+use crate::api::pagination::{PageParams, PageRequest};
+use crate::api::stores::owned_store;
+use crate::api::user_refs::{PublicUserRef, UserRefBuilder};
+use crate::auth::{claims_from_headers, ApiScope};
+use crate::db::products::Product;
+use crate::db::questions::{ProductQuestion, QuestionFilter};
+use crate::db::stores::{is_paused, AutoResponse, AutoResponseTrigger, Store};
+use crate::entity::product_question::Model as QuestionModel;
+use crate::events::{create_event, EventDispatcher, EventType};
+use crate::moderation::text::TextField;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const MIN_QUESTION_LEN: usize = 3;
+const MAX_QUESTION_LEN: usize = 1000;
+const MAX_ANSWER_LEN: usize = 2000;
+
+#[derive(Deserialize, ToSchema)]
+pub struct AskQuestionRequest {
+    pub question: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AnswerQuestionRequest {
+    /// Replaces any earlier answer
+    pub answer: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ListQuestionsQuery {
+    /// Only questions the seller has answered
+    #[serde(default)]
+    pub answered_only: bool,
+}
+
+/// A product question as the caller may see it: the asker's relay ID
+/// only for the asker and admins
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuestionResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub asked_by: PublicUserRef,
+    pub question: String,
+    pub answer: Option<String>,
+    /// Device ID of the store owner who answered
+    pub answered_by: Option<String>,
+    /// Matched a flagged term; shown to the store owner only
+    pub is_held: bool,
+    pub created_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+    /// The store's automatic reply, shown apart from the seller's answer
+    pub auto_reply: Option<AutomatedReply>,
+}
+
+/// A reply the store sent on its own, not written by the seller
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AutomatedReply {
+    pub message: String,
+    /// Always true, so clients can style these apart from answers
+    pub automated: bool,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl QuestionResponse {
+    pub fn new(question: QuestionModel, users: &UserRefBuilder) -> Self {
+        Self {
+            id: question.id,
+            product_id: question.product_id,
+            asked_by: users.build(&question.asked_by),
+            question: question.question,
+            answer: question.answer,
+            answered_by: question.answered_by,
+            is_held: question.is_held,
+            created_at: question.created_at,
+            answered_at: question.answered_at,
+            auto_reply: question.auto_reply.zip(question.auto_replied_at).map(
+                |(message, sent_at)| AutomatedReply {
+                    message,
+                    automated: true,
+                    sent_at,
+                },
+            ),
+        }
+    }
+}
+
+/// Trimmed text if its length is within bounds
+pub(crate) fn bounded<'a>(
+    field: &str,
+    text: &'a str,
+    min: usize,
+    max: usize,
+) -> Result<&'a str, String> {
+    let text = text.trim();
+    let len = text.chars().count();
+    if len < min || len > max {
+        return Err(format!("{field} must be {min} to {max} characters"));
+    }
+    Ok(text)
+}
+
+/// Record the paused store's automatic reply to a question just asked;
+/// the reply, if one went out. A failure only costs the buyer the reply.
+async fn paused_auto_reply(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    question: &QuestionModel,
+) -> Option<String> {
+    // Held questions wait for the seller, like the delayed replies do
+    if question.is_held {
+        return None;
+    }
+    let store = Store::get(db, store_id).await.ok()?;
+    if !is_paused(&store, question.created_at) {
+        return None;
+    }
+    let auto_response = AutoResponse::of(&store);
+    let message = auto_response.message_for(AutoResponseTrigger::Paused)?;
+    match ProductQuestion::record_auto_reply(db, question.id, message, question.created_at).await {
+        Ok(true) => Some(message.to_string()),
+        Ok(false) => None,
+        Err(err) => {
+            warn!(question_id = %question.id, error = %err, "Question asked without its automatic reply");
+            None
+        }
+    }
+}
+
+/// Ask a public question about a product
+#[utoipa::path(
+    post,
+    operation_id = "askQuestion",
+    path = "/api/v1/products/{id}/questions",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    request_body = AskQuestionRequest,
+    responses(
+        (status = 201, description = "Question posted; held from the public list if it matched a flagged term. Carries the store's automatic reply when it is paused and set to send one", body = QuestionResponse),
+        (status = 400, description = "Question too short or too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 404, description = "Product not found"),
+        (status = 422, description = "Question uses a blocked term, or offensive language when questions are set to reject", body = ContentRejection)
+    )
+)]
+pub async fn ask_question(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(product_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<AskQuestionRequest>,
+) -> impl IntoResponse {
+    let Some(claims) = claims_from_headers(&headers) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid Authorization token",
+        )
+            .into_response();
+    };
+    let question = match bounded(
+        "question",
+        &request.question,
+        MIN_QUESTION_LEN,
+        MAX_QUESTION_LEN,
+    ) {
+        Ok(question) => question,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let product = match Product::get_visible(&db, product_id).await {
+        Ok(product) => product,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let held = match screen("Question", &[question]) {
+        Ok(flagged) => flagged.is_some(),
+        Err(rejection) => return rejection.into_response(),
+    };
+    let question = match moderate_text(TextField::Question, question) {
+        Ok(question) => question,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match ProductQuestion::create(&db, product_id, &claims.relay_id, &question, held).await {
+        Ok(mut question) => {
+            if let Some(reply) = paused_auto_reply(&db, product.store_id, &question).await {
+                question.auto_reply = Some(reply);
+                question.auto_replied_at = Some(question.created_at);
+            }
+            if !held {
+                let event = create_event(
+                    EventType::ProductQuestionAsked,
+                    question.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "product_id": product_id,
+                        "question": question.question,
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+            }
+            let question =
+                QuestionResponse::new(question, &PublicUserRef::viewed_by(Some(&claims)));
+            (StatusCode::CREATED, Json(question)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Answer a question on one of the caller's products
+#[utoipa::path(
+    post,
+    operation_id = "answerQuestion",
+    path = "/api/v1/questions/{id}/answer",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Question ID", format = "uuid")
+    ),
+    request_body = AnswerQuestionRequest,
+    responses(
+        (status = 200, description = "Answer saved", body = QuestionResponse),
+        (status = 400, description = "Answer empty or too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the product's store"),
+        (status = 404, description = "Question not found"),
+        (status = 422, description = "Answer uses a blocked term, or offensive language when answers are set to reject", body = ContentRejection)
+    )
+)]
+pub async fn answer_question(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(question_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<AnswerQuestionRequest>,
+) -> impl IntoResponse {
+    let question = match ProductQuestion::get(&db, question_id).await {
+        Ok(Some(question)) => question,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Question not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let product = match Product::get(&db, question.product_id).await {
+        Ok(product) => product,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let store = match owned_store(&db, &headers, product.store_id, ApiScope::ProductsWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let answer = match bounded("answer", &request.answer, 1, MAX_ANSWER_LEN) {
+        Ok(answer) => answer,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    // Flagged terms are left to the question's own hold; only blocks apply here
+    if let Err(rejection) = screen("Answer", &[answer]) {
+        return rejection.into_response();
+    }
+    let answer = match moderate_text(TextField::Answer, answer) {
+        Ok(answer) => answer,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let answered_by = store.owner_device_id.unwrap_or_default();
+    match ProductQuestion::answer(&db, question, &answer, &answered_by).await {
+        Ok(question) => {
+            let event = create_event(
+                EventType::ProductQuestionAnswered,
+                question.id,
+                serde_json::json!({
+                    "product_id": question.product_id,
+                    "asked_by": question.asked_by,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+            let viewer = claims_from_headers(&headers);
+            Json(QuestionResponse::new(
+                question,
+                &PublicUserRef::viewed_by(viewer.as_ref()),
+            ))
+            .into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// A product's public questions, newest first; the store owner also sees
+/// held ones
+#[utoipa::path(
+    get,
+    operation_id = "listQuestions",
+    path = "/api/v1/products/{id}/questions",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid"),
+        ListQuestionsQuery,
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "One page of questions", body = QuestionsPage),
+        (status = 400, description = "Invalid page parameters"),
+        (status = 404, description = "Product not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_questions(
+    State(db): State<DatabaseConnection>,
+    UuidPath(product_id): UuidPath<Uuid>,
+    Query(query): Query<ListQuestionsQuery>,
+    page: PageRequest,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let product = match Product::get_visible(&db, product_id).await {
+        Ok(product) => product,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let is_owner = owned_store(&db, &headers, product.store_id, ApiScope::ProductsRead)
+        .await
+        .is_ok();
+    let filter = QuestionFilter {
+        answered_only: query.answered_only,
+        include_held: is_owner,
+    };
+    match ProductQuestion::list(&db, product_id, filter, page.page, page.per_page).await {
+        Ok((questions, total)) => {
+            let viewer = claims_from_headers(&headers);
+            let users = PublicUserRef::viewed_by(viewer.as_ref());
+            let questions = questions
+                .into_iter()
+                .map(|question| QuestionResponse::new(question, &users))
+                .collect();
+            Json(page.numbered(questions, total)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::JwtService;
+    use crate::db::testing::{seed_product, sqlite};
+    use axum::{
+        body::Body,
+        extract::FromRef,
+        http::{header, Request},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        events: Arc<EventDispatcher>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<EventDispatcher> {
+        fn from_ref(state: &TestState) -> Self {
+            state.events.clone()
+        }
+    }
+
+    fn app(db: DatabaseConnection) -> Router {
+        Router::new()
+            .route(
+                "/products/:id/questions",
+                post(ask_question).get(list_questions),
+            )
+            .route("/questions/:id/answer", post(answer_question))
+            .with_state(TestState {
+                db,
+                events: Arc::new(EventDispatcher::new()),
+            })
+    }
+
+    fn token(relay_id: &str) -> String {
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token(relay_id.into(), String::new(), "default".into())
+            .unwrap();
+        format!("Bearer {token}")
+    }
+
+    async fn send(
+        db: &DatabaseConnection,
+        method: &str,
+        uri: &str,
+        caller: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(caller) = caller {
+            request = request.header(header::AUTHORIZATION, token(caller));
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let response = app(db.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    async fn ask(db: &DatabaseConnection, product_id: Uuid, question: &str) -> Uuid {
+        let (status, body) = send(
+            db,
+            "POST",
+            &format!("/products/{product_id}/questions"),
+            Some("buyer-1"),
+            serde_json::json!({ "question": question }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_only_the_store_owner_may_answer() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller-1").await;
+        let question_id = ask(&db, product_id, "Does it come in blue?").await;
+        let uri = format!("/questions/{question_id}/answer");
+        let answer = serde_json::json!({ "answer": "Yes, and in green." });
+
+        let (status, _) = send(&db, "POST", &uri, None, answer.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&db, "POST", &uri, Some("buyer-1"), answer.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&db, "POST", &uri, Some("seller-2"), answer.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(&db, "POST", &uri, Some("seller-1"), answer).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["answer"], "Yes, and in green.");
+        assert_eq!(body["answered_by"], "seller-1");
+    }
+
+    #[tokio::test]
+    async fn test_answered_only_lists_answered_questions() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller-1").await;
+        let answered = ask(&db, product_id, "Is it waterproof?").await;
+        ask(&db, product_id, "How long is delivery?").await;
+        send(
+            &db,
+            "POST",
+            &format!("/questions/{answered}/answer"),
+            Some("seller-1"),
+            serde_json::json!({ "answer": "Splash-proof only." }),
+        )
+        .await;
+
+        let uri = format!("/products/{product_id}/questions");
+        let (_, all) = send(&db, "GET", &uri, None, serde_json::Value::Null).await;
+        assert_eq!(all["items"].as_array().unwrap().len(), 2);
+        // Visitors see a handle, not who asked
+        assert_eq!(
+            all["items"][0]["asked_by"]["relay_id"],
+            serde_json::Value::Null
+        );
+        assert!(!all.to_string().contains("buyer-1"), "{all}");
+        let (_, mine) = send(&db, "GET", &uri, Some("buyer-1"), serde_json::Value::Null).await;
+        assert_eq!(mine["items"][0]["asked_by"]["relay_id"], "buyer-1");
+
+        let (status, only) = send(
+            &db,
+            "GET",
+            &format!("{uri}?answered_only=true"),
+            None,
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let questions = only["items"].as_array().unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0]["id"], answered.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_paused_stores_auto_reply_without_answering() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller-1").await;
+        let store_id = Product::get(&db, product_id).await.unwrap().store_id;
+        let store = Store::get(&db, store_id).await.unwrap();
+        let auto_response = AutoResponse {
+            enabled: true,
+            message: Some("On holiday until Monday, I'll answer then.".to_string()),
+            trigger: Some(AutoResponseTrigger::Paused),
+        };
+        let store = Store::set_auto_response(&db, store, &auto_response)
+            .await
+            .unwrap();
+        let uri = format!("/products/{product_id}/questions");
+        let question = serde_json::json!({ "question": "Is it still available?" });
+
+        // Open stores don't auto-reply
+        let (_, body) = send(&db, "POST", &uri, Some("buyer-1"), question.clone()).await;
+        assert_eq!(body["auto_reply"], serde_json::Value::Null);
+
+        Store::pause(&db, store, None, None).await.unwrap();
+        let (status, body) = send(&db, "POST", &uri, Some("buyer-1"), question.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            body["auto_reply"]["message"],
+            "On holiday until Monday, I'll answer then."
+        );
+        assert_eq!(body["auto_reply"]["automated"], true);
+        assert_eq!(body["answer"], serde_json::Value::Null);
+
+        // Listed as automated, and still open for the answered_only filter
+        let (_, listed) = send(&db, "GET", &uri, None, serde_json::Value::Null).await;
+        assert_eq!(listed["items"][0]["auto_reply"]["automated"], true);
+        let (_, answered) = send(
+            &db,
+            "GET",
+            &format!("{uri}?answered_only=true"),
+            None,
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(answered["items"], serde_json::json!([]));
+
+        // A store set to reply late stays quiet while paused
+        let store = Store::get(&db, store_id).await.unwrap();
+        let delayed = AutoResponse {
+            trigger: Some(AutoResponseTrigger::DelayedReply),
+            ..auto_response
+        };
+        Store::set_auto_response(&db, store, &delayed)
+            .await
+            .unwrap();
+        let (_, body) = send(&db, "POST", &uri, Some("buyer-1"), question).await;
+        assert_eq!(body["auto_reply"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,420 @@
+//! Referral links sellers share to send buyers to another store, and get
+//! credit for it.
+//!
+//! Following a link is public: `GET /api/v1/referrals/{code}` counts the
+//! visit of a caller it can tell apart (a token or an `X-Session-Id`) and
+//! answers with the target, or redirects browsers straight to it. The
+//! referring store sees the visits and sales its links brought in its
+//! stats and link list.
+
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::config::{ReferralConfig, SiteConfig};
+use crate::db::products::Product;
+use crate::db::referrals::{ReferralLink, ReferralTotals, CODE_LEN};
+use crate::db::stores::Store;
+use crate::entity::referral_link::Model as ReferralLinkModel;
+use crate::experiments;
+use crate::links::{ProductLink, StoreLink};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateReferralLinkRequest {
+    /// Store to send buyers to; taken from `product_id` when left out
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub target_store_id: Option<Uuid>,
+    /// One of the target store's products, to link to instead of its
+    /// storefront
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub product_id: Option<Uuid>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReferralLinkResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub code: String,
+    /// Where the link resolves, relative to the API
+    pub path: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub target_store_id: Uuid,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub target_product_id: Option<Uuid>,
+    /// Viewer-days: each viewer counts once per day they followed the link
+    pub click_count: i64,
+    /// Orders attributed to the link
+    pub sale_count: i64,
+    pub last_clicked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ReferralLinkModel> for ReferralLinkResponse {
+    fn from(link: ReferralLinkModel) -> Self {
+        Self {
+            path: format!("/api/v1/referrals/{}", link.code),
+            id: link.id,
+            code: link.code,
+            target_store_id: link.target_store_id,
+            target_product_id: link.target_product_id,
+            click_count: link.click_count,
+            sale_count: link.sale_count,
+            last_clicked_at: link.last_clicked_at,
+            created_at: link.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReferralLinkListResponse {
+    pub referral_links: Vec<ReferralLinkResponse>,
+    pub totals: ReferralTotals,
+    /// Days after following a link that the viewer's orders are credited to it
+    pub attribution_window_days: u32,
+}
+
+/// Where a referral link leads
+#[derive(Serialize, ToSchema)]
+pub struct ReferralTargetResponse {
+    pub code: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub product_id: Option<Uuid>,
+    /// The product or store page on the public website
+    pub url: String,
+}
+
+/// Whether the caller is a browser asking for a page rather than an app
+/// asking for JSON
+fn wants_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Create a referral link to another store or one of its products
+#[utoipa::path(
+    post,
+    operation_id = "createReferralLink",
+    path = "/api/v1/stores/{id}/referral-links",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "ID of the referring store", format = "uuid")
+    ),
+    request_body = CreateReferralLinkRequest,
+    responses(
+        (status = 201, description = "Referral link created", body = ReferralLinkResponse),
+        (status = 400, description = "No target, a target owned by the referring store's owner, or a product of another store than the one named"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store, target store or product not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_referral_link(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<CreateReferralLinkRequest>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let product = match request.product_id {
+        Some(product_id) => match Product::get(&db, product_id).await {
+            Ok(product) if product.archived_at.is_none() => Some(product),
+            Ok(_) => return (StatusCode::NOT_FOUND, "Product not found.").into_response(),
+            Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+        },
+        None => None,
+    };
+    let target_store_id = match (request.target_store_id, &product) {
+        (Some(target), Some(product)) if product.store_id != target => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "product_id belongs to another store than target_store_id",
+            )
+                .into_response()
+        }
+        (Some(target), _) => target,
+        (None, Some(product)) => product.store_id,
+        (None, None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "target_store_id or product_id is required",
+            )
+                .into_response()
+        }
+    };
+    let target = match Store::get(&db, target_store_id).await {
+        Ok(target) => target,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    // Sellers with several stores can't refer buyers between them
+    if target.id == store.id || target.owner_device_id == store.owner_device_id {
+        return (
+            StatusCode::BAD_REQUEST,
+            "A store can't refer buyers to itself or its owner's other stores",
+        )
+            .into_response();
+    }
+
+    match ReferralLink::create(
+        &db,
+        store.id,
+        target_store_id,
+        request.product_id,
+        Utc::now(),
+    )
+    .await
+    {
+        Ok(link) => (StatusCode::CREATED, Json(ReferralLinkResponse::from(link))).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List a store's referral links with what each brought in
+#[utoipa::path(
+    get,
+    operation_id = "listReferralLinks",
+    path = "/api/v1/stores/{id}/referral-links",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "ID of the referring store", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Referral links, newest first, and their totals", body = ReferralLinkListResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_referral_links(
+    State(db): State<DatabaseConnection>,
+    State(referrals): State<Arc<ReferralConfig>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    match ReferralLink::list_by_store(&db, store.id).await {
+        Ok(links) => Json(ReferralLinkListResponse {
+            totals: ReferralTotals::of(&links),
+            referral_links: links.into_iter().map(ReferralLinkResponse::from).collect(),
+            attribution_window_days: referrals.attribution_days,
+        })
+        .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Follow a referral link
+///
+/// Counts the visit for the referring store, once per viewer per day, and
+/// answers with the target. Browsers asking for `text/html` are redirected
+/// to the target's page instead.
+#[utoipa::path(
+    get,
+    operation_id = "resolveReferralLink",
+    path = "/api/v1/referrals/{code}",
+    tag = "Stores",
+    params(
+        ("code" = String, Path, description = "Referral link code")
+    ),
+    responses(
+        (status = 200, description = "The link's target", body = ReferralTargetResponse),
+        (status = 303, description = "Browsers are sent to the target's page"),
+        (status = 404, description = "No such referral link"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn resolve_referral_link(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if code.len() != CODE_LEN {
+        return (StatusCode::NOT_FOUND, "Referral link not found.").into_response();
+    }
+    let link = match ReferralLink::find_by_code(&db, &code).await {
+        Ok(Some(link)) => link,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Referral link not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    // Viewers are told apart as experiment subjects are
+    if let Some(viewer) = experiments::subject(&headers) {
+        if let Err(err) = ReferralLink::record_click(&db, &link, &viewer, Utc::now()).await {
+            warn!(code = %link.code, error = %err, "Referral followed without counting it");
+        }
+    }
+
+    let url = match link.target_product_id {
+        Some(product_id) => ProductLink::new(&site.public_base_url, product_id).to_string(),
+        None => StoreLink::new(&site.public_base_url, link.target_store_id).to_string(),
+    };
+    if wants_html(&headers) {
+        return Redirect::to(&url).into_response();
+    }
+    Json(ReferralTargetResponse {
+        code: link.code,
+        store_id: link.target_store_id,
+        product_id: link.target_product_id,
+        url,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::JwtService;
+    use crate::db::testing::{seed_product, seed_store, sqlite};
+    use axum::{body::Body, extract::FromRef, http::Request, routing::get, routing::post, Router};
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        site: Arc<SiteConfig>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<SiteConfig> {
+        fn from_ref(state: &TestState) -> Self {
+            state.site.clone()
+        }
+    }
+
+    fn app(db: &DatabaseConnection) -> Router {
+        Router::new()
+            .route(
+                "/api/v1/stores/:id/referral-links",
+                post(create_referral_link),
+            )
+            .route("/api/v1/referrals/:code", get(resolve_referral_link))
+            .with_state(TestState {
+                db: db.clone(),
+                site: Arc::new(SiteConfig {
+                    public_base_url: "https://transac.site".to_string(),
+                    currency: "XAF".to_string(),
+                }),
+            })
+    }
+
+    async fn call(
+        app: &Router,
+        request: axum::http::request::Builder,
+        body: serde_json::Value,
+    ) -> (StatusCode, HeaderMap, serde_json::Value) {
+        let request = request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            headers,
+            serde_json::from_slice(&body).unwrap_or_default(),
+        )
+    }
+
+    fn as_seller(method: &str, uri: &str, seller: &str) -> axum::http::request::Builder {
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token(seller.into(), String::new(), "default".into())
+            .unwrap();
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+    }
+
+    #[tokio::test]
+    async fn test_links_point_at_another_store_and_resolve_to_it() {
+        let db = sqlite().await;
+        let referrer = seed_store(&db, "seller-1").await;
+        let product_id = seed_product(&db, "seller-2").await;
+        let own_product = seed_product(&db, "seller-1").await;
+        let app = app(&db);
+        let uri = format!("/api/v1/stores/{referrer}/referral-links");
+        let create = |body| call(&app, as_seller("POST", &uri, "seller-1"), body);
+
+        // Self-referral, to the same store or another of the seller's, is
+        // refused
+        let (status, _, _) = create(serde_json::json!({ "target_store_id": referrer })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = create(serde_json::json!({ "product_id": own_product })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = create(serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, link) = create(serde_json::json!({ "product_id": product_id })).await;
+        assert_eq!(status, StatusCode::CREATED, "{link}");
+        let path = link["path"].as_str().unwrap().to_string();
+
+        let follow = || {
+            Request::builder()
+                .uri(&path)
+                .header(experiments::SESSION_HEADER, "session-1")
+        };
+        let (status, _, target) = call(&app, follow(), serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(target["product_id"], product_id.to_string());
+        assert_eq!(
+            target["url"],
+            format!("https://transac.site/product/{product_id}")
+        );
+        let (status, headers, _) = call(
+            &app,
+            follow().header(header::ACCEPT, "text/html,*/*;q=0.8"),
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(headers[header::LOCATION], target["url"].as_str().unwrap());
+
+        // The same viewer on the same day counts once
+        let code = link["code"].as_str().unwrap();
+        let counted = ReferralLink::find_by_code(&db, code)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(counted.click_count, 1);
+
+        let (status, _, _) = call(
+            &app,
+            Request::builder().uri("/api/v1/referrals/unknown1"),
+            serde_json::Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
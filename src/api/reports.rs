@@ -0,0 +1,220 @@
+//! Seller reports that take too long for a single request. A report is
+//! queued here, generated by the report runner, and fetched through a
+//! presigned link once ready; see `crate::reports`.
+
+use crate::api::extract::UuidPath;
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::api::media_storage::StorageUnavailableResponse;
+use crate::api::media_storage::{
+    connect_s3_storage, storage_unavailable_response, MediaStorage, StorageConnectError,
+    StubMediaStorage,
+};
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::db::reports::{CreateReport, ReportJob};
+use crate::entity::report_job::Model as ReportJobModel;
+use crate::reports::{check_range, ReportFormat, ReportLimits, ReportStatus, ReportType};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// The store already has as many reports in flight as it may
+pub const REPORT_LIMIT: &str = "REPORT_LIMIT";
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReportRequest {
+    pub report_type: ReportType,
+    /// Defaults to CSV
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Start of the range, inclusive
+    #[schema(value_type = String, format = "date-time")]
+    pub from: DateTime<Utc>,
+    /// End of the range, exclusive
+    #[schema(value_type = String, format = "date-time")]
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReportResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    pub report_type: String,
+    pub format: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub status: ReportStatus,
+    /// Why generation failed
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// When the file is deleted
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Presigned link, set while the report is ready
+    pub download_url: Option<String>,
+}
+
+impl ReportResponse {
+    fn new(job: ReportJobModel, download_url: Option<String>) -> Self {
+        Self {
+            id: job.id,
+            store_id: job.store_id,
+            // Rows are only written by `ReportJob`, so the status always parses
+            status: ReportStatus::parse(&job.status).unwrap_or(ReportStatus::Failed),
+            report_type: job.report_type,
+            format: job.format,
+            from: job.range_start,
+            to: job.range_end,
+            error: job.error,
+            created_at: job.created_at,
+            finished_at: job.finished_at,
+            expires_at: job.expires_at,
+            download_url,
+        }
+    }
+}
+
+/// 429 when the store has too many reports queued or running
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReportLimitResponse {
+    pub code: &'static str,
+    pub message: String,
+    pub limit: u64,
+}
+
+impl IntoResponse for ReportLimitResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::TOO_MANY_REQUESTS, Json(self)).into_response()
+    }
+}
+
+/// Queue a report over a date range; poll it with `getReport`
+#[utoipa::path(
+    post,
+    operation_id = "createReport",
+    path = "/api/v1/stores/{id}/reports",
+    tag = "Stores",
+    params(("id" = String, Path, description = "Store ID", format = "uuid")),
+    request_body = CreateReportRequest,
+    responses(
+        (status = 202, description = "Report queued", body = ReportResponse),
+        (status = 400, description = "Invalid date range"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:read"),
+        (status = 404, description = "Store not found"),
+        (status = 429, description = "Too many reports in flight for this store", body = ReportLimitResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_report(
+    State(db): State<DatabaseConnection>,
+    State(limits): State<Arc<ReportLimits>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<CreateReportRequest>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(err) = check_range(request.from, request.to, Utc::now()) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let create = CreateReport {
+        store_id: id,
+        requested_by: store.owner_device_id.unwrap_or_default(),
+        report_type: request.report_type,
+        format: request.format,
+        range_start: request.from,
+        range_end: request.to,
+    };
+    match ReportJob::create(&db, &create, limits.max_per_store).await {
+        Ok(Some(job)) => {
+            (StatusCode::ACCEPTED, Json(ReportResponse::new(job, None))).into_response()
+        }
+        Ok(None) => ReportLimitResponse {
+            code: REPORT_LIMIT,
+            message: format!(
+                "A store can have at most {} reports in progress; wait for one to finish",
+                limits.max_per_store
+            ),
+            limit: limits.max_per_store,
+        }
+        .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// A report's status, with a download link once it is ready
+#[utoipa::path(
+    get,
+    operation_id = "getReport",
+    path = "/api/v1/stores/{id}/reports/{report_id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("report_id" = String, Path, description = "Report ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "The report", body = ReportResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:read"),
+        (status = 404, description = "Store or report not found"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Media storage unavailable", body = StorageUnavailableResponse)
+    )
+)]
+pub async fn get_report(
+    State(db): State<DatabaseConnection>,
+    UuidPath((id, report_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        return err.into_response();
+    }
+    let job = match ReportJob::get(&db, id, report_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Report not found").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let now = Utc::now();
+    let download = match (&job.media_key, job.expires_at) {
+        (Some(key), Some(expires_at)) if job.status == ReportStatus::Ready.as_str() => {
+            match (expires_at - now).to_std() {
+                Ok(left) => Some((key.clone(), left)),
+                // Past its expiry; the runner deletes it shortly
+                Err(_) => None,
+            }
+        }
+        _ => None,
+    };
+    let download_url = match download {
+        None => None,
+        Some((key, left)) => {
+            let url = match connect_s3_storage().await {
+                Ok(storage) => storage.presigned_url(&key, left).await,
+                Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+                Err(StorageConnectError::Failed(_)) => {
+                    StubMediaStorage.presigned_url(&key, left).await
+                }
+            };
+            match url {
+                Ok(url) => Some(url),
+                Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+            }
+        }
+    };
+    Json(ReportResponse::new(job, download_url)).into_response()
+}
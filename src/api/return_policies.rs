@@ -0,0 +1,58 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Ready-made return policy text sellers can start from
+#[derive(Serialize, ToSchema, Clone, Copy)]
+pub struct ReturnPolicyTemplate {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub text: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReturnPolicyTemplatesResponse {
+    pub templates: Vec<ReturnPolicyTemplate>,
+}
+
+pub const RETURN_POLICY_TEMPLATES: &[ReturnPolicyTemplate] = &[
+    ReturnPolicyTemplate {
+        id: "no_returns",
+        title: "No returns",
+        text: "All sales are final. Items cannot be returned or exchanged.",
+    },
+    ReturnPolicyTemplate {
+        id: "exchange_7_days",
+        title: "Exchange within 7 days",
+        text: "Items can be exchanged within 7 days of purchase if unused and in their original packaging. Refunds are not offered.",
+    },
+    ReturnPolicyTemplate {
+        id: "refund_14_days",
+        title: "Refund within 14 days",
+        text: "Items can be returned within 14 days of purchase for a full refund if unused and in their original packaging. The buyer covers return delivery costs.",
+    },
+    ReturnPolicyTemplate {
+        id: "defective_only",
+        title: "Defective items only",
+        text: "Returns are accepted only for items that arrive damaged or defective. Contact the store within 48 hours of delivery with a photo of the issue.",
+    },
+];
+
+/// List the server-provided return policy templates
+#[utoipa::path(
+    get,
+    operation_id = "listReturnPolicyTemplates",
+    path = "/api/v1/return-policy-templates",
+    tag = "Stores",
+    responses(
+        (status = 200, description = "Available return policy templates", body = ReturnPolicyTemplatesResponse)
+    )
+)]
+pub async fn list_return_policy_templates() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(ReturnPolicyTemplatesResponse {
+            templates: RETURN_POLICY_TEMPLATES.to_vec(),
+        }),
+    )
+}
@@ -0,0 +1,478 @@
+//! `sitemap.xml` and the Google Merchant product feed.
+//!
+//! Both are streamed: rows are fetched and rendered a chunk at a time, so a
+//! large catalogue never sits in memory. Paused stores, their products and
+//! products held by moderation are left out.
+
+use crate::api::products::media_url;
+use crate::config::SiteConfig;
+use crate::db::products::active_sale;
+use crate::db::seo::Seo;
+use crate::entity::product::Model as ProductModel;
+use crate::links::{ProductLink, StoreLink};
+use crate::tenant::tenant_from_headers;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use sea_orm::DatabaseConnection;
+use std::fmt::Write;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Sitemap protocol limit on URLs per file
+pub const URLS_PER_SITEMAP: u64 = 50_000;
+/// Rows fetched per query while streaming
+const CHUNK: u64 = 1000;
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+const SITEMAP_NS: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Escape text for an XML element or attribute
+pub fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn w3c_date(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Number of sitemap files needed for `total` URLs; one file needs no index
+pub fn sitemap_pages(total: u64) -> u64 {
+    total.div_ceil(URLS_PER_SITEMAP).max(1)
+}
+
+fn xml_response(content_type: &'static str, body: Body) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+type Chunk = Result<String, std::io::Error>;
+
+/// `<url>` entries `[start, end)` of the listing: stores first, then
+/// products, each ordered by id
+fn url_entries(
+    db: DatabaseConnection,
+    tenant_id: String,
+    base_url: String,
+    now: DateTime<Utc>,
+    store_count: u64,
+    start: u64,
+    end: u64,
+) -> impl Stream<Item = Chunk> {
+    stream::unfold(Some(start), move |pos| {
+        let (db, tenant_id, base_url) = (db.clone(), tenant_id.clone(), base_url.clone());
+        async move {
+            let pos = pos.filter(|pos| *pos < end)?;
+            let mut out = String::new();
+            let fetched = if pos < store_count {
+                let limit = CHUNK.min(store_count - pos).min(end - pos);
+                Seo::stores(&db, &tenant_id, now, pos, limit)
+                    .await
+                    .map(|stores| {
+                        for store in &stores {
+                            let loc = StoreLink::new(&base_url, store.id).to_string();
+                            url_entry(&mut out, &loc, store.updated_at);
+                        }
+                        stores.len() as u64
+                    })
+            } else {
+                let limit = CHUNK.min(end - pos);
+                Seo::products(&db, &tenant_id, now, pos - store_count, limit)
+                    .await
+                    .map(|products| {
+                        for product in &products {
+                            let loc = ProductLink::new(&base_url, product.id).to_string();
+                            url_entry(&mut out, &loc, product.updated_at);
+                        }
+                        products.len() as u64
+                    })
+            };
+            match fetched {
+                // Rows vanished since counting; end the file early
+                Ok(0) => None,
+                Ok(n) => Some((Ok(out), Some(pos + n))),
+                Err(e) => Some((Err(std::io::Error::other(e)), None)),
+            }
+        }
+    })
+}
+
+fn url_entry(out: &mut String, loc: &str, lastmod: DateTime<Utc>) {
+    let _ = writeln!(
+        out,
+        "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+        xml_escape(loc),
+        w3c_date(lastmod)
+    );
+}
+
+/// `<urlset>` covering URLs `[start, end)`
+fn urlset(
+    db: DatabaseConnection,
+    tenant_id: String,
+    base_url: String,
+    now: DateTime<Utc>,
+    store_count: u64,
+    start: u64,
+    end: u64,
+) -> Response {
+    let head =
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"{SITEMAP_NS}\">\n");
+    let body = stream::once(async { Ok(head) })
+        .chain(url_entries(
+            db,
+            tenant_id,
+            base_url,
+            now,
+            store_count,
+            start,
+            end,
+        ))
+        .chain(stream::once(async { Ok("</urlset>\n".to_string()) }));
+    xml_response("application/xml; charset=utf-8", Body::from_stream(body))
+}
+
+/// Store and product pages for crawlers
+#[utoipa::path(
+    get,
+    operation_id = "sitemap",
+    path = "/sitemap.xml",
+    tag = "Seo",
+    responses(
+        (status = 200, description = "A `<urlset>`, or a `<sitemapindex>` of `/sitemaps/{page}.xml` files past 50,000 URLs", content_type = "application/xml"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn sitemap(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    headers: HeaderMap,
+) -> Response {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now();
+    let (stores, products) = match Seo::count_listed(&db, &tenant, now).await {
+        Ok(counts) => counts,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let total = stores + products;
+    let pages = sitemap_pages(total);
+    if pages == 1 {
+        return urlset(
+            db,
+            tenant,
+            site.public_base_url.clone(),
+            now,
+            stores,
+            0,
+            total,
+        );
+    }
+
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"{SITEMAP_NS}\">\n"
+    );
+    for page in 1..=pages {
+        let _ = writeln!(
+            out,
+            "<sitemap><loc>{}</loc><lastmod>{}</lastmod></sitemap>",
+            xml_escape(&format!("{}/sitemaps/{page}.xml", site.public_base_url)),
+            w3c_date(now)
+        );
+    }
+    out.push_str("</sitemapindex>\n");
+    xml_response("application/xml; charset=utf-8", Body::from(out))
+}
+
+/// One file of a sitemap too large for a single `<urlset>`
+#[utoipa::path(
+    get,
+    operation_id = "sitemapPage",
+    path = "/sitemaps/{page}.xml",
+    tag = "Seo",
+    params(
+        ("page" = u64, Path, description = "1-based file number from the sitemap index")
+    ),
+    responses(
+        (status = 200, description = "Up to 50,000 URLs", content_type = "application/xml"),
+        (status = 404, description = "No such sitemap file"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn sitemap_page(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    Path(file): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(page) = file
+        .strip_suffix(".xml")
+        .and_then(|page| page.parse::<u64>().ok())
+        .filter(|page| *page >= 1)
+    else {
+        return (StatusCode::NOT_FOUND, "Sitemap not found").into_response();
+    };
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now();
+    let (stores, products) = match Seo::count_listed(&db, &tenant, now).await {
+        Ok(counts) => counts,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let total = stores + products;
+    if page > sitemap_pages(total) {
+        return (StatusCode::NOT_FOUND, "Sitemap not found").into_response();
+    }
+    let start = (page - 1) * URLS_PER_SITEMAP;
+    let end = (start + URLS_PER_SITEMAP).min(total);
+    urlset(
+        db,
+        tenant,
+        site.public_base_url.clone(),
+        now,
+        stores,
+        start,
+        end,
+    )
+}
+
+/// `<item>` of the Merchant feed
+fn feed_item(out: &mut String, site: &SiteConfig, product: &ProductModel, now: DateTime<Utc>) {
+    let link = ProductLink::new(&site.public_base_url, product.id).to_string();
+    let _ = write!(
+        out,
+        "<item><g:id>{}</g:id><title>{}</title><description>{}</description><link>{}</link>",
+        product.id,
+        xml_escape(&product.name),
+        xml_escape(product.description.as_deref().unwrap_or(&product.name)),
+        xml_escape(&link),
+    );
+    if let Some(image_id) = product.image_id {
+        let image = format!("{}{}", site.public_base_url, media_url(image_id));
+        let _ = write!(out, "<g:image_link>{}</g:image_link>", xml_escape(&image));
+    }
+    let _ = write!(
+        out,
+        "<g:price>{:.2} {}</g:price>",
+        product.price, product.currency
+    );
+    if let Some(sale) = active_sale(product, now) {
+        let _ = write!(
+            out,
+            "<g:sale_price>{:.2} {}</g:sale_price>",
+            sale.price, product.currency
+        );
+    }
+    out.push_str(
+        "<g:availability>in_stock</g:availability><g:condition>new</g:condition></item>\n",
+    );
+}
+
+/// Published, in-stock products in RSS 2.0 with Google Merchant fields
+#[utoipa::path(
+    get,
+    operation_id = "productFeed",
+    path = "/feeds/products.xml",
+    tag = "Seo",
+    responses(
+        (status = 200, description = "RSS 2.0 feed with the `g:` Merchant namespace", content_type = "application/rss+xml")
+    )
+)]
+pub async fn product_feed(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    headers: HeaderMap,
+) -> Response {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now();
+    let head = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:g=\"http://base.google.com/ns/1.0\">\n\
+         <channel><title>Transac products</title><link>{}</link>\
+         <description>Products available on Transac</description>\n",
+        xml_escape(&site.public_base_url)
+    );
+    let items = stream::unfold(Some(None), move |cursor: Option<Option<Uuid>>| {
+        let (db, tenant, site) = (db.clone(), tenant.clone(), site.clone());
+        async move {
+            let after = cursor?;
+            match Seo::feed_products(&db, &tenant, now, after, CHUNK).await {
+                Ok(products) if products.is_empty() => None,
+                Ok(products) => {
+                    let mut out = String::new();
+                    for product in &products {
+                        feed_item(&mut out, &site, product, now);
+                    }
+                    let next = products.last().map(|product| product.id);
+                    Some((Ok(out), Some(next)))
+                }
+                Err(e) => Some((Err(std::io::Error::other(e)), None)),
+            }
+        }
+    });
+    let body = stream::once(async { Ok::<_, std::io::Error>(head) })
+        .chain(items)
+        .chain(stream::once(async { Ok("</channel></rss>\n".to_string()) }));
+    xml_response(
+        "application/rss+xml; charset=utf-8",
+        Body::from_stream(body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::{product, store};
+    use axum::{extract::FromRef, http::Request, routing::get, Router};
+    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        site: Arc<SiteConfig>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<SiteConfig> {
+        fn from_ref(state: &TestState) -> Self {
+            state.site.clone()
+        }
+    }
+
+    fn app(db: DatabaseConnection) -> Router {
+        Router::new()
+            .route("/sitemap.xml", get(sitemap))
+            .route("/feeds/products.xml", get(product_feed))
+            .with_state(TestState {
+                db,
+                site: Arc::new(SiteConfig {
+                    public_base_url: "https://transac.site".to_string(),
+                    currency: "XAF".to_string(),
+                }),
+            })
+    }
+
+    async fn fetch(db: &DatabaseConnection, path: &str) -> (HeaderMap, String) {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app(db.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// A live product, a sold-out one, and a product in a paused store
+    async fn seed(db: &DatabaseConnection) -> (Uuid, Uuid, Uuid) {
+        let live = testing::seed_product(db, "seller-1").await;
+        let sold_out = testing::seed_product(db, "seller-2").await;
+        let mut sold_out_model: product::ActiveModel = product::Entity::find_by_id(sold_out)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .into();
+        sold_out_model.quantity_available = Set(0);
+        sold_out_model.update(db).await.unwrap();
+
+        let paused = testing::seed_product(db, "seller-3").await;
+        let paused_store = product::Entity::find_by_id(paused)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .store_id;
+        let mut store_model: store::ActiveModel = store::Entity::find_by_id(paused_store)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .into();
+        store_model.is_paused = Set(true);
+        store_model.update(db).await.unwrap();
+        (live, sold_out, paused)
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_lists_open_stores_and_their_products() {
+        let db = testing::sqlite().await;
+        let (live, sold_out, paused) = seed(&db).await;
+
+        let (headers, xml) = fetch(&db, "/sitemap.xml").await;
+        assert_eq!(headers[header::CACHE_CONTROL], CACHE_CONTROL);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset"));
+        assert!(xml.ends_with("</urlset>\n"));
+        // Two open stores and their two products
+        assert_eq!(xml.matches("<url>").count(), 4);
+        assert_eq!(xml.matches("</url>").count(), 4);
+        assert!(xml.contains(&format!("<loc>https://transac.site/product/{live}</loc>")));
+        assert!(xml.contains(&format!("/product/{sold_out}</loc>")));
+        assert!(!xml.contains(&paused.to_string()));
+        assert!(xml.contains("<lastmod>"));
+    }
+
+    #[tokio::test]
+    async fn test_feed_has_only_in_stock_products_with_merchant_fields() {
+        let db = testing::sqlite().await;
+        let (live, sold_out, paused) = seed(&db).await;
+
+        let (headers, xml) = fetch(&db, "/feeds/products.xml").await;
+        assert!(headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("application/rss+xml"));
+        assert!(xml.contains("xmlns:g=\"http://base.google.com/ns/1.0\""));
+        assert!(xml.ends_with("</channel></rss>\n"));
+        assert_eq!(xml.matches("<item>").count(), 1);
+        assert!(xml.contains(&format!("<g:id>{live}</g:id>")));
+        assert!(xml.contains("<g:price>5000.00 XAF</g:price>"));
+        assert!(xml.contains("<g:availability>in_stock</g:availability>"));
+        assert!(!xml.contains(&sold_out.to_string()));
+        assert!(!xml.contains(&paused.to_string()));
+    }
+
+    #[test]
+    fn test_large_sitemaps_split_into_pages() {
+        assert_eq!(sitemap_pages(0), 1);
+        assert_eq!(sitemap_pages(URLS_PER_SITEMAP), 1);
+        assert_eq!(sitemap_pages(URLS_PER_SITEMAP + 1), 2);
+        assert_eq!(xml_escape("Kaba & <scarf>"), "Kaba &amp; &lt;scarf&gt;");
+    }
+}
@@ -0,0 +1,170 @@
+//! Store snapshots: take one by hand, list them, and restore one after a bulk
+//! edit or POS upload went wrong. Bulk operations take their own snapshot
+//! before changing anything, see [`crate::db::snapshots`].
+
+use crate::api::extract::UuidPath;
+use crate::api::products::changed_fields;
+use crate::api::stores::owned_store;
+use crate::auth::{claims_from_headers, ApiScope};
+use crate::db::products::SKU_TAKEN;
+use crate::db::snapshots::{SnapshotReason, StoreSnapshot};
+use crate::entity::store_snapshot::Model as SnapshotModel;
+use crate::events::{create_event, EventDispatcher, EventType};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, ToSchema)]
+pub struct StoreSnapshotsResponse {
+    /// Newest first
+    #[schema(inline)]
+    pub snapshots: Vec<SnapshotModel>,
+}
+
+/// Take a snapshot of a store's products
+#[utoipa::path(
+    post,
+    operation_id = "createStoreSnapshot",
+    path = "/api/v1/stores/{id}/snapshots",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 201, description = "Snapshot taken; the store's oldest is dropped past 20", body = inline(SnapshotModel)),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_store_snapshot(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let created_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    match StoreSnapshot::capture(
+        &db,
+        id,
+        SnapshotReason::Manual,
+        created_by.as_deref(),
+        Utc::now(),
+    )
+    .await
+    {
+        Ok(snapshot) => (StatusCode::CREATED, Json(snapshot)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List a store's snapshots
+#[utoipa::path(
+    get,
+    operation_id = "listStoreSnapshots",
+    path = "/api/v1/stores/{id}/snapshots",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Snapshots, newest first", body = StoreSnapshotsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_store_snapshots(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    match StoreSnapshot::list(&db, id).await {
+        Ok(snapshots) => {
+            (StatusCode::OK, Json(StoreSnapshotsResponse { snapshots })).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Put a store's products back as they were in a snapshot. Catalog fields
+/// are restored; stock, visibility and archive state are not. Products
+/// deleted for good since are reported as missing.
+#[utoipa::path(
+    post,
+    operation_id = "restoreStoreSnapshot",
+    path = "/api/v1/stores/{id}/snapshots/{snapshot_id}/restore",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("snapshot_id" = String, Path, description = "Snapshot ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "What the restore changed", body = RestoreReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:write"),
+        (status = 404, description = "Store or snapshot not found"),
+        (status = 409, description = "Another product has since taken a snapshotted SKU; nothing was restored"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn restore_store_snapshot(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath((id, snapshot_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store(&db, &headers, id, ApiScope::ProductsWrite).await {
+        return err.into_response();
+    }
+    let snapshot = match StoreSnapshot::get(&db, id, snapshot_id).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Snapshot not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    let restore =
+        match StoreSnapshot::restore(&db, &snapshot, changed_by.as_deref(), Utc::now()).await {
+            Ok(restore) => restore,
+            Err(err) if err == SKU_TAKEN => return (StatusCode::CONFLICT, err).into_response(),
+            Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        };
+
+    for (before, after) in &restore.changed {
+        let mut payload = serde_json::json!({ "store_id": after.store_id });
+        payload["changes"] = changed_fields(before, after);
+        let event = create_event(EventType::ProductUpdated, after.id, payload);
+        let _ = events.dispatch(event).await;
+        if before.price != after.price {
+            let event = create_event(
+                EventType::ProductPriceChanged,
+                after.id,
+                serde_json::json!({
+                    "store_id": after.store_id,
+                    "sku": after.sku,
+                    "previous_price": before.price,
+                    "price": after.price,
+                    "currency": after.currency,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+        }
+    }
+    (StatusCode::OK, Json(restore.report)).into_response()
+}
@@ -0,0 +1,186 @@
+use crate::api::extract::UuidPath;
+use crate::api::stores::owned_store_by_token;
+use crate::auth::ApiScope;
+use crate::db::api_keys::ApiKey;
+use crate::entity::store_api_key::Model as ApiKeyModel;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Name to recognise the key by, e.g. "Shop POS"
+    pub label: String,
+    pub scopes: Vec<ApiScope>,
+}
+
+/// API key metadata; the secret itself is never returned after creation
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub label: String,
+    pub key_prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ApiKeyModel> for ApiKeyResponse {
+    fn from(key: ApiKeyModel) -> Self {
+        Self {
+            scopes: key
+                .scopes
+                .split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .collect(),
+            id: key.id,
+            label: key.label,
+            key_prefix: key.key_prefix,
+            last_used_at: key.last_used_at,
+            revoked: key.revoked,
+            created_at: key.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedApiKeyResponse {
+    #[serde(flatten)]
+    pub key: ApiKeyResponse,
+    /// Plaintext key for the `X-Api-Key` header; shown only in this response
+    pub secret: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyListResponse {
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+/// Create an API key for a store
+#[utoipa::path(
+    post,
+    operation_id = "createApiKey",
+    path = "/api/v1/stores/{id}/api-keys",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created; `secret` is not shown again", body = CreatedApiKeyResponse),
+        (status = 400, description = "Missing label or scopes"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn create_api_key(
+    State(db): State<DatabaseConnection>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store_by_token(&db, &headers, store_id).await {
+        return err.into_response();
+    }
+    let label = request.label.trim();
+    if label.is_empty() || label.chars().count() > 100 {
+        return (StatusCode::BAD_REQUEST, "label must be 1 to 100 characters").into_response();
+    }
+    if request.scopes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "At least one scope is required").into_response();
+    }
+    let mut scopes = request.scopes;
+    scopes.sort_by_key(|s| s.as_str());
+    scopes.dedup();
+
+    match ApiKey::create(&db, store_id, label, &scopes).await {
+        Ok((key, secret)) => (
+            StatusCode::CREATED,
+            Json(CreatedApiKeyResponse {
+                key: key.into(),
+                secret,
+            }),
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// List a store's API keys
+#[utoipa::path(
+    get,
+    operation_id = "listApiKeys",
+    path = "/api/v1/stores/{id}/api-keys",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "API keys, newest first", body = ApiKeyListResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn list_api_keys(
+    State(db): State<DatabaseConnection>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store_by_token(&db, &headers, store_id).await {
+        return err.into_response();
+    }
+    match ApiKey::list_by_store(&db, store_id).await {
+        Ok(keys) => (
+            StatusCode::OK,
+            Json(ApiKeyListResponse {
+                api_keys: keys.into_iter().map(ApiKeyResponse::from).collect(),
+            }),
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Revoke a store API key
+#[utoipa::path(
+    delete,
+    operation_id = "revokeApiKey",
+    path = "/api/v1/stores/{id}/api-keys/{key_id}",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("key_id" = String, Path, description = "API key ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store or API key not found")
+    )
+)]
+pub async fn revoke_api_key(
+    State(db): State<DatabaseConnection>,
+    UuidPath((store_id, key_id)): UuidPath<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = owned_store_by_token(&db, &headers, store_id).await {
+        return err.into_response();
+    }
+    match ApiKey::revoke(&db, store_id, key_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "API key not found.").into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
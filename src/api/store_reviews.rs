@@ -0,0 +1,505 @@
+use crate::api::extract::UuidPath;
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::api::moderation::ContentRejection;
+use crate::api::moderation::{moderate_optional_text, moderate_text, screen};
+use crate::api::pagination::{PageParams, PageRequest, StoreReviewsPage};
+use crate::api::questions::bounded;
+use crate::api::stores::owned_store;
+use crate::api::user_refs::{PublicUserRef, UserRefBuilder};
+use crate::auth::{claims_from_headers, ApiScope};
+use crate::db::store_reviews::{
+    NewStoreReview, PostedReview, ReviewSort, StoreReview, MAX_REVIEWS_PER_DAY,
+};
+use crate::db::stores::Store;
+use crate::entity::store_review::Model as StoreReviewModel;
+use crate::events::{create_event, EventDispatcher, EventType};
+use crate::moderation::text::TextField;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const MAX_COMMENT_LEN: usize = 2000;
+const MAX_REPLY_LEN: usize = 2000;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateStoreReviewRequest {
+    /// 1 to 5 stars
+    pub rating: i32,
+    pub comment: Option<String>,
+    /// Order the review is about, for the verified badge
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub order_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReplyStoreReviewRequest {
+    /// Replaces any earlier reply
+    pub reply: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ListStoreReviewsQuery {
+    /// `newest` (default), `highest` or `lowest` rating first
+    #[param(value_type = Option<String>)]
+    #[serde(default)]
+    pub sort: ReviewSort,
+}
+
+/// A store review as the caller may see it: the reviewer's relay ID only
+/// for the reviewer and admins
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StoreReviewResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    pub reviewer: PublicUserRef,
+    /// 1 to 5 stars
+    pub rating: i32,
+    pub comment: Option<String>,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub order_id: Option<Uuid>,
+    pub is_verified: bool,
+    /// The store owner's public reply
+    pub reply: Option<String>,
+    /// Device ID of the store owner who replied
+    pub replied_by: Option<String>,
+    pub replied_at: Option<DateTime<Utc>>,
+    /// Shown to the store owner only and left out of the store's rating
+    pub is_held: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoreReviewResponse {
+    pub fn new(review: StoreReviewModel, users: &UserRefBuilder) -> Self {
+        Self {
+            id: review.id,
+            store_id: review.store_id,
+            reviewer: users.build(&review.reviewer_id),
+            rating: review.rating,
+            comment: review.comment,
+            order_id: review.order_id,
+            is_verified: review.is_verified,
+            reply: review.reply,
+            replied_by: review.replied_by,
+            replied_at: review.replied_at,
+            is_held: review.is_held,
+            created_at: review.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StoreReviewsListResponse {
+    #[serde(flatten)]
+    pub reviews: StoreReviewsPage,
+    /// Average of the store's reviews; the store's `rating` rates its products
+    pub store_rating: Option<f32>,
+    pub store_review_count: i32,
+}
+
+/// Review a store as a whole; one review per buyer and store
+#[utoipa::path(
+    post,
+    operation_id = "createStoreReview",
+    path = "/api/v1/stores/{id}/reviews",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = CreateStoreReviewRequest,
+    responses(
+        (status = 201, description = "Review posted; held from the public list if it matched a flagged term", body = StoreReviewResponse),
+        (status = 400, description = "Rating outside 1 to 5, or comment too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Store owners can't review their own store"),
+        (status = 404, description = "Store not found"),
+        (status = 409, description = "Caller already reviewed this store"),
+        (status = 422, description = "Comment uses a blocked term, or offensive language when review comments are set to reject", body = ContentRejection),
+        (status = 429, description = "Caller posted too many store reviews today")
+    )
+)]
+pub async fn create_store_review(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<CreateStoreReviewRequest>,
+) -> impl IntoResponse {
+    let Some(claims) = claims_from_headers(&headers) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid Authorization token",
+        )
+            .into_response();
+    };
+    if !(1..=5).contains(&request.rating) {
+        return (StatusCode::BAD_REQUEST, "rating must be 1 to 5").into_response();
+    }
+    let comment = match request.comment.as_deref() {
+        Some(comment) => match bounded("comment", comment, 0, MAX_COMMENT_LEN) {
+            Ok(comment) => Some(comment).filter(|comment| !comment.is_empty()),
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        },
+        None => None,
+    };
+    let store = match Store::get(&db, store_id).await {
+        Ok(store) => store,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    if store.owner_device_id.as_deref() == Some(claims.relay_id.as_str()) {
+        return (
+            StatusCode::FORBIDDEN,
+            "Store owners can't review their own store",
+        )
+            .into_response();
+    }
+    let held = match screen("Review", &[comment.unwrap_or_default()]) {
+        Ok(flagged) => flagged.is_some(),
+        Err(rejection) => return rejection.into_response(),
+    };
+    let comment = match moderate_optional_text(TextField::ReviewComment, comment) {
+        Ok(comment) => comment,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let review = NewStoreReview {
+        store_id,
+        reviewer_id: claims.relay_id.clone(),
+        rating: request.rating,
+        comment,
+        order_id: request.order_id,
+        is_held: held,
+    };
+    match StoreReview::create(&db, review, Utc::now()).await {
+        Ok(PostedReview::Posted(review)) => {
+            if !held {
+                let event = create_event(
+                    EventType::StoreReviewPosted,
+                    review.id,
+                    serde_json::json!({
+                        "store_id": store_id,
+                        "rating": review.rating,
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+            }
+            let review = StoreReviewResponse::new(review, &PublicUserRef::viewed_by(Some(&claims)));
+            (StatusCode::CREATED, Json(review)).into_response()
+        }
+        Ok(PostedReview::AlreadyReviewed) => {
+            (StatusCode::CONFLICT, "You have already reviewed this store").into_response()
+        }
+        Ok(PostedReview::DailyLimit) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("You can review at most {MAX_REVIEWS_PER_DAY} stores a day"),
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Reply to a review of the caller's store
+#[utoipa::path(
+    post,
+    operation_id = "replyStoreReview",
+    path = "/api/v1/store-reviews/{id}/reply",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store review ID", format = "uuid")
+    ),
+    request_body = ReplyStoreReviewRequest,
+    responses(
+        (status = 200, description = "Reply saved", body = StoreReviewResponse),
+        (status = 400, description = "Reply empty or too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Review not found"),
+        (status = 422, description = "Reply uses a blocked term, or offensive language when replies are set to reject", body = ContentRejection)
+    )
+)]
+pub async fn reply_store_review(
+    State(db): State<DatabaseConnection>,
+    State(events): State<Arc<EventDispatcher>>,
+    UuidPath(review_id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<ReplyStoreReviewRequest>,
+) -> impl IntoResponse {
+    let review = match StoreReview::get(&db, review_id).await {
+        Ok(Some(review)) => review,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Review not found.").into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let store = match owned_store(&db, &headers, review.store_id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let reply = match bounded("reply", &request.reply, 1, MAX_REPLY_LEN) {
+        Ok(reply) => reply,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    // Flagged terms are left to the review's own hold; only blocks apply here
+    if let Err(rejection) = screen("Reply", &[reply]) {
+        return rejection.into_response();
+    }
+    let reply = match moderate_text(TextField::ReviewReply, reply) {
+        Ok(reply) => reply,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let replied_by = store.owner_device_id.unwrap_or_default();
+    match StoreReview::reply(&db, review, &reply, &replied_by).await {
+        Ok(review) => {
+            let event = create_event(
+                EventType::StoreReviewReplied,
+                review.id,
+                serde_json::json!({
+                    "store_id": review.store_id,
+                    "reviewer_id": review.reviewer_id,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+            let viewer = claims_from_headers(&headers);
+            Json(StoreReviewResponse::new(
+                review,
+                &PublicUserRef::viewed_by(viewer.as_ref()),
+            ))
+            .into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// A store's reviews with its review rating; the store owner also sees
+/// held ones
+#[utoipa::path(
+    get,
+    operation_id = "listStoreReviews",
+    path = "/api/v1/stores/{id}/reviews",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ListStoreReviewsQuery,
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "One page of reviews", body = StoreReviewsListResponse),
+        (status = 400, description = "Invalid page parameters"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_store_reviews(
+    State(db): State<DatabaseConnection>,
+    UuidPath(store_id): UuidPath<Uuid>,
+    Query(query): Query<ListStoreReviewsQuery>,
+    page: PageRequest,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let store = match Store::get(&db, store_id).await {
+        Ok(store) => store,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+    let is_owner = owned_store(&db, &headers, store_id, ApiScope::StoresWrite)
+        .await
+        .is_ok();
+    match StoreReview::list(
+        &db,
+        store_id,
+        query.sort,
+        is_owner,
+        page.page,
+        page.per_page,
+    )
+    .await
+    {
+        Ok((reviews, total)) => {
+            let viewer = claims_from_headers(&headers);
+            let users = PublicUserRef::viewed_by(viewer.as_ref());
+            let reviews = reviews
+                .into_iter()
+                .map(|review| StoreReviewResponse::new(review, &users))
+                .collect();
+            Json(StoreReviewsListResponse {
+                reviews: page.numbered(reviews, total),
+                store_rating: store.store_rating,
+                store_review_count: store.store_review_count,
+            })
+            .into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{JwtService, ADMIN_ROLE};
+    use crate::db::testing::{seed_store, sqlite};
+    use axum::{
+        body::Body,
+        extract::FromRef,
+        http::{header, Request},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        events: Arc<EventDispatcher>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<EventDispatcher> {
+        fn from_ref(state: &TestState) -> Self {
+            state.events.clone()
+        }
+    }
+
+    fn app(db: DatabaseConnection) -> Router {
+        Router::new()
+            .route(
+                "/stores/:id/reviews",
+                post(create_store_review).get(list_store_reviews),
+            )
+            .route("/store-reviews/:id/reply", post(reply_store_review))
+            .with_state(TestState {
+                db,
+                events: Arc::new(EventDispatcher::new()),
+            })
+    }
+
+    /// `ops-` callers get an admin token
+    fn token(relay_id: &str) -> String {
+        let token = if relay_id.starts_with("ops-") {
+            JwtService::new().unwrap().generate_token_with_role(
+                relay_id.into(),
+                String::new(),
+                ADMIN_ROLE.into(),
+            )
+        } else {
+            JwtService::new().unwrap().generate_token(
+                relay_id.into(),
+                String::new(),
+                "default".into(),
+            )
+        };
+        format!("Bearer {}", token.unwrap())
+    }
+
+    async fn send(
+        db: &DatabaseConnection,
+        method: &str,
+        uri: &str,
+        caller: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(caller) = caller {
+            request = request.header(header::AUTHORIZATION, token(caller));
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let response = app(db.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_review_reply_and_list() {
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        let uri = format!("/stores/{store_id}/reviews");
+        let review = serde_json::json!({ "rating": 4, "comment": "Quick replies, neat packaging" });
+
+        let (status, _) = send(&db, "POST", &uri, None, review.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&db, "POST", &uri, Some("seller-1"), review.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(
+            &db,
+            "POST",
+            &uri,
+            Some("buyer-1"),
+            serde_json::json!({ "rating": 6 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, posted) = send(&db, "POST", &uri, Some("buyer-1"), review.clone()).await;
+        assert_eq!(status, StatusCode::CREATED, "{posted}");
+        assert_eq!(posted["is_verified"], false);
+        let (status, _) = send(&db, "POST", &uri, Some("buyer-1"), review).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let reply_uri = format!("/store-reviews/{}/reply", posted["id"].as_str().unwrap());
+        let reply = serde_json::json!({ "reply": "Thank you, come again!" });
+        let (status, _) = send(&db, "POST", &reply_uri, Some("seller-2"), reply.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&db, "POST", &reply_uri, Some("seller-1"), reply).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["replied_by"], "seller-1");
+
+        let (status, list) = send(&db, "GET", &uri, None, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(list["store_rating"], 4.0);
+        assert_eq!(list["store_review_count"], 1);
+        assert_eq!(list["items"][0]["reply"], "Thank you, come again!");
+    }
+
+    #[tokio::test]
+    async fn test_only_the_reviewer_and_admins_see_who_wrote_a_review() {
+        let db = sqlite().await;
+        let store_id = seed_store(&db, "seller-1").await;
+        let uri = format!("/stores/{store_id}/reviews");
+        let reviewer = "npub1-buyer-7a3f";
+        let review = serde_json::json!({ "rating": 5 });
+        let (status, posted) = send(&db, "POST", &uri, Some(reviewer), review).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(posted["reviewer"]["relay_id"], reviewer);
+        assert!(posted.get("reviewer_id").is_none());
+
+        for (viewer, sees_id) in [
+            (None, false),
+            (Some("buyer-2"), false),
+            (Some("seller-1"), false),
+            (Some(reviewer), true),
+            (Some("ops-1"), true),
+        ] {
+            let (status, list) = send(&db, "GET", &uri, viewer, serde_json::Value::Null).await;
+            assert_eq!(status, StatusCode::OK);
+            let shown = &list["items"][0]["reviewer"];
+            assert_eq!(shown["handle"], "Buyer •••a3f", "{viewer:?}");
+            assert_eq!(
+                shown["relay_id"].as_str(),
+                sees_id.then_some(reviewer),
+                "{viewer:?}"
+            );
+            assert_eq!(
+                list.to_string().contains(reviewer),
+                sees_id,
+                "{viewer:?}: {list}"
+            );
+        }
+    }
+}
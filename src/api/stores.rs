@@ -1,14 +1,44 @@
-use crate::db::stores::Store;
+use crate::api::admin::require_admin;
+use crate::api::extract::UuidPath;
+use crate::api::fields::{FieldSelection, STORE_FIELDS};
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::api::moderation::TextRejectionResponse;
+use crate::api::pagination::{PageParams, PageRequest};
+use crate::api::return_policies;
+use crate::api::validation::{
+    delivery_option_errors, validate_store, FieldError, StoreInput, ValidationReport,
+};
+#[allow(unused_imports)]
+use crate::auth::confirmation::ConfirmationResponse;
+use crate::auth::confirmation::DeletionSummary;
+use crate::auth::{authenticate, claims_from_headers, ApiScope, Claims, ADMIN_ROLE};
+use crate::config::SiteConfig;
+use crate::db::delivery::{DeliveryOptions, DeliveryOptionsInput};
+use crate::db::history::ChangeOrigin;
+use crate::db::media_quota::{MediaLimits, MediaUsage};
+use crate::db::onboarding;
+use crate::db::product_counts::{ProductCounts, StoreProductCounts};
+use crate::db::referrals::{ReferralLink, ReferralTotals};
+use crate::db::reports::ReportJob;
+use crate::db::stores::{
+    is_paused, AutoResponse, AutoResponseTrigger, ContactVisibility, Store, StoreSort,
+};
 use crate::entity::store::Model as StoreModel;
+use crate::links::{SmsShare, StoreLink, TelegramShare, WhatsAppShare};
+use crate::tenant::tenant_from_headers;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{FromRef, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
 use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -23,6 +53,7 @@ pub struct CreateStoreRequest {
     pub contact_email: Option<String>,
     pub contact_whatsapp: Option<String>,
     pub owner_device_id: Option<String>,
+    pub default_return_policy: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -35,18 +66,118 @@ pub struct UpdateStoreRequest {
     pub contact_phone: Option<String>,
     pub contact_email: Option<String>,
     pub contact_whatsapp: Option<String>,
+    pub default_return_policy: Option<String>,
+    /// Publish the phone number; unchanged when omitted
+    pub show_phone: Option<bool>,
+    /// Publish the WhatsApp number; unchanged when omitted
+    pub show_whatsapp: Option<bool>,
+    /// Publish the email address; unchanged when omitted
+    pub show_email: Option<bool>,
+}
+
+impl CreateStoreRequest {
+    pub fn as_input(&self) -> StoreInput<'_> {
+        StoreInput {
+            name: &self.name,
+            logo_url: self.logo_url.as_deref(),
+            location: self.location.as_deref(),
+            contact_phone: self.contact_phone.as_deref(),
+            contact_email: self.contact_email.as_deref(),
+            contact_whatsapp: self.contact_whatsapp.as_deref(),
+        }
+    }
+}
+
+impl UpdateStoreRequest {
+    pub fn as_input(&self) -> StoreInput<'_> {
+        StoreInput {
+            name: &self.name,
+            logo_url: self.logo_url.as_deref(),
+            location: self.location.as_deref(),
+            contact_phone: self.contact_phone.as_deref(),
+            contact_email: self.contact_email.as_deref(),
+            contact_whatsapp: self.contact_whatsapp.as_deref(),
+        }
+    }
+
+    pub fn visibility(&self) -> ContactVisibility {
+        ContactVisibility {
+            show_phone: self.show_phone,
+            show_whatsapp: self.show_whatsapp,
+            show_email: self.show_email,
+        }
+    }
+}
+
+/// Stands in for the hidden digits of a private number
+const CONTACT_MASK: &str = "•••••";
+
+/// `+237699000123` becomes `+237•••••123`: enough for a buyer to recognise
+/// the number, not to dial it
+pub fn mask_contact(value: &str) -> String {
+    let digits: Vec<char> = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect();
+    if digits.len() <= 7 {
+        return CONTACT_MASK.to_string();
+    }
+    let head: String = digits[..4].iter().collect();
+    let tail: String = digits[digits.len() - 3..].iter().collect();
+    format!("{head}{CONTACT_MASK}{tail}")
+}
+
+/// The store as buyers see it: private numbers masked, a private email
+/// omitted
+pub fn public_store(mut store: StoreModel) -> StoreModel {
+    if !store.show_phone {
+        store.contact_phone = store.contact_phone.as_deref().map(mask_contact);
+    }
+    if !store.show_whatsapp {
+        store.contact_whatsapp = store.contact_whatsapp.as_deref().map(mask_contact);
+    }
+    if !store.show_email {
+        store.contact_email = None;
+    }
+    store
+}
+
+/// The owner and admins always see every contact field
+pub fn sees_full_contacts(store: &StoreModel, claims: Option<&Claims>) -> bool {
+    claims.is_some_and(|claims| {
+        claims.role == ADMIN_ROLE
+            || store.owner_device_id.as_deref() == Some(claims.relay_id.as_str())
+    })
+}
+
+/// The store as the caller may see it
+pub fn store_for_viewer(store: StoreModel, claims: Option<&Claims>) -> StoreModel {
+    if sees_full_contacts(&store, claims) {
+        store
+    } else {
+        public_store(store)
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Serialize, ToSchema)]
-pub struct StoreResponse {
-    pub store: StoreModel,
+#[derive(Deserialize)]
+pub struct UpdateStoreQuery {
+    /// Also update products that inherit the store's default return policy
+    pub apply_to_products: Option<bool>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+pub struct ListStoresQuery {
+    /// Comma-separated subset of fields to return
+    pub fields: Option<String>,
+    pub sort: Option<StoreSort>,
 }
 
 #[allow(dead_code)]
 #[derive(Serialize, ToSchema)]
-pub struct StoresListResponse {
-    pub stores: Vec<StoreModel>,
+pub struct StoreResponse {
+    pub store: StoreModel,
 }
 
 #[allow(dead_code)]
@@ -55,25 +186,474 @@ pub struct StoreShareResponse {
     pub store_id: String,
     pub share_url: String,
     pub whatsapp_share_url: String,
+    /// Opens the messaging app with the link as the body of one SMS
+    pub sms_share_url: String,
+    pub telegram_share_url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StoreStatsResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    pub total_products: i32,
+    pub product_counts: StoreProductCounts,
+    pub media: MediaUsage,
+    /// Visits and sales the store's referral links brought other stores
+    pub referrals: ReferralTotals,
+}
+
+/// Error code for actions a paused store cannot take
+pub const STORE_PAUSED: &str = "STORE_PAUSED";
+
+/// Longest pause banner a seller may set
+const PAUSE_MESSAGE_MAX_LEN: usize = 280;
+
+/// Longest automatic reply a seller may set
+const AUTO_RESPONSE_MAX_LEN: usize = 500;
+
+#[derive(Deserialize, ToSchema)]
+pub struct PauseStoreRequest {
+    /// Resume automatically at this time; stays paused until resumed when omitted
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub paused_until: Option<DateTime<Utc>>,
+    /// Banner shown to buyers, e.g. "Back on the 3rd!"
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EmbedSettingsRequest {
+    /// Let other websites show the store's products with the embed widget
+    pub enabled: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EmbedSettingsResponse {
+    pub enabled: bool,
+    /// Path of the widget's data, to load from the embedding page
+    pub path: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AutoResponseRequest {
+    pub enabled: bool,
+    /// Sent to buyers as is; required while enabled
+    pub message: Option<String>,
+    /// Required while enabled
+    pub trigger: Option<AutoResponseTrigger>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StorePausedResponse {
+    pub code: &'static str,
+    pub message: String,
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+impl IntoResponse for StorePausedResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::CONFLICT, Json(self)).into_response()
+    }
+}
+
+/// `STORE_PAUSED` (409) when the store is paused; new orders must go through this
+#[allow(dead_code)]
+pub fn ensure_accepting_orders(
+    store: &StoreModel,
+    now: DateTime<Utc>,
+) -> Result<(), StorePausedResponse> {
+    if !is_paused(store, now) {
+        return Ok(());
+    }
+    Err(StorePausedResponse {
+        code: STORE_PAUSED,
+        message: store
+            .pause_message
+            .clone()
+            .unwrap_or_else(|| "This store is not taking orders right now".to_string()),
+        paused_until: store.paused_until,
+    })
+}
+
+/// Store-management actions take the seller's token, or an API key for the
+/// store carrying `scope`
+pub(crate) async fn owned_store(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+    store_id: Uuid,
+    scope: ApiScope,
+) -> Result<StoreModel, (StatusCode, String)> {
+    let principal = match authenticate(db, headers).await {
+        Ok(Some(p)) if p.claims.role == "seller" => p,
+        Ok(Some(_)) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Insufficient role for managing a store".to_string(),
+            ))
+        }
+        Ok(None) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token".to_string(),
+            ))
+        }
+        Err((status, msg)) => return Err((status, msg.to_string())),
+    };
+    if !principal.allows(scope, store_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("API key lacks {} for this store", scope.as_str()),
+        ));
+    }
+    let store = Store::get(db, store_id)
+        .await
+        .map_err(|err| (StatusCode::NOT_FOUND, err))?;
+    if store.owner_device_id.as_deref() != Some(principal.claims.relay_id.as_str()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Not allowed to manage this store".to_string(),
+        ));
+    }
+    Ok(store)
+}
+
+/// Like [`owned_store`] for actions an API key must never take, such as
+/// minting keys or changing payout details: the seller's bearer token only.
+/// Returns the store with the seller's claims.
+pub(crate) async fn owned_store_by_token(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+    store_id: Uuid,
+) -> Result<(StoreModel, Claims), (StatusCode, String)> {
+    let claims = match claims_from_headers(headers) {
+        Some(c) if c.role == "seller" => c,
+        Some(_) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Insufficient role for managing a store".to_string(),
+            ))
+        }
+        None => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token".to_string(),
+            ))
+        }
+    };
+    let store = Store::get(db, store_id)
+        .await
+        .map_err(|err| (StatusCode::NOT_FOUND, err))?;
+    if store.owner_device_id.as_deref() != Some(claims.relay_id.as_str()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Not allowed to manage this store".to_string(),
+        ));
+    }
+    Ok((store, claims))
+}
+
+fn check_pause_request(request: &PauseStoreRequest, now: DateTime<Utc>) -> Result<(), String> {
+    if request.paused_until.is_some_and(|until| until <= now) {
+        return Err("paused_until must be in the future".to_string());
+    }
+    if request
+        .message
+        .as_deref()
+        .is_some_and(|m| m.chars().count() > PAUSE_MESSAGE_MAX_LEN)
+    {
+        return Err(format!(
+            "message must be at most {PAUSE_MESSAGE_MAX_LEN} characters"
+        ));
+    }
+    Ok(())
+}
+
+/// Pause a store (vacation mode)
+#[utoipa::path(
+    post,
+    operation_id = "pauseStore",
+    path = "/api/v1/stores/{id}/pause",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = PauseStoreRequest,
+    responses(
+        (status = 200, description = "Store paused; it stays visible but takes no orders", body = StoreResponse),
+        (status = 400, description = "paused_until is not in the future, or message is too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn pause_store(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<PauseStoreRequest>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(err) = check_pause_request(&request, Utc::now()) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    match Store::pause(&db, store, request.paused_until, request.message.as_deref()).await {
+        Ok(store) => (StatusCode::OK, Json(StoreResponse { store })).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Resume a paused store
+#[utoipa::path(
+    post,
+    operation_id = "resumeStore",
+    path = "/api/v1/stores/{id}/resume",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Store resumed", body = StoreResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn resume_store(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    match Store::resume(&db, store).await {
+        Ok(store) => (StatusCode::OK, Json(StoreResponse { store })).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Set the delivery options the store's products inherit
+///
+/// Omitted fields keep their current value. Products that set no options of
+/// their own pick up the change right away.
+#[utoipa::path(
+    put,
+    operation_id = "setStoreDeliveryOptions",
+    path = "/api/v1/stores/{id}/delivery-options",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = DeliveryOptionsInput,
+    responses(
+        (status = 200, description = "Delivery defaults updated", body = StoreResponse),
+        (status = 400, description = "Invalid options, or neither pickup nor delivery left on", body = ValidationReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn set_delivery_options(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<DeliveryOptionsInput>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let mut errors = delivery_option_errors("delivery_options", &request);
+    let defaults = request.fill(DeliveryOptions::store_default(&store));
+    if errors.is_empty() && !defaults.pickup_available && !defaults.delivery_available {
+        errors.push(FieldError::new(
+            "delivery_options",
+            "invalid",
+            "Offer pickup, delivery or both.",
+        ));
+    }
+    if !errors.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+    match Store::set_delivery_defaults(&db, store, defaults).await {
+        Ok(store) => (StatusCode::OK, Json(StoreResponse { store })).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+/// Turn the storefront embed widget on or off
+///
+/// While on, `GET /api/v1/embed/stores/{id}` serves the store's public
+/// products to any website.
+#[utoipa::path(
+    put,
+    operation_id = "setStoreEmbed",
+    path = "/api/v1/stores/{id}/embed",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = EmbedSettingsRequest,
+    responses(
+        (status = 200, description = "Embed setting updated", body = EmbedSettingsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_embed(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<EmbedSettingsRequest>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    match Store::set_embed_enabled(&db, store, request.enabled).await {
+        Ok(store) => {
+            let settings = EmbedSettingsResponse {
+                enabled: store.embed_enabled,
+                path: format!("/api/v1/embed/stores/{}", store.id),
+            };
+            (StatusCode::OK, Json(settings)).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+fn check_auto_response_request(request: &AutoResponseRequest) -> Result<(), String> {
+    let message = request.message.as_deref().map(str::trim).unwrap_or("");
+    if message.chars().count() > AUTO_RESPONSE_MAX_LEN {
+        return Err(format!(
+            "message must be at most {AUTO_RESPONSE_MAX_LEN} characters"
+        ));
+    }
+    if request.enabled && message.is_empty() {
+        return Err("message is required to enable the auto-response".to_string());
+    }
+    if request.enabled && request.trigger.is_none() {
+        return Err("trigger is required to enable the auto-response".to_string());
+    }
+    Ok(())
+}
+
+/// The store's automatic reply to buyers' questions
+#[utoipa::path(
+    get,
+    operation_id = "getStoreAutoResponse",
+    path = "/api/v1/stores/{id}/auto-response",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Auto-response settings", body = AutoResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn get_auto_response(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        Ok(store) => Json(AutoResponse::of(&store)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Set the store's automatic reply to buyers' questions
+///
+/// With the `paused` trigger it answers questions asked while the store is
+/// paused, right away. With `delayed_reply` it answers questions still
+/// unanswered after 48 hours, once. Either way the question stays
+/// unanswered for the seller.
+#[utoipa::path(
+    put,
+    operation_id = "setStoreAutoResponse",
+    path = "/api/v1/stores/{id}/auto-response",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    request_body = AutoResponseRequest,
+    responses(
+        (status = 200, description = "Auto-response updated", body = AutoResponse),
+        (status = 400, description = "Message or trigger missing while enabled, or message too long"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store"),
+        (status = 404, description = "Store not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_auto_response(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<AutoResponseRequest>,
+) -> impl IntoResponse {
+    let store = match owned_store(&db, &headers, id, ApiScope::StoresWrite).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(err) = check_auto_response_request(&request) {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let auto_response = AutoResponse {
+        enabled: request.enabled,
+        message: request.message,
+        trigger: request.trigger,
+    };
+    match Store::set_auto_response(&db, store, &auto_response).await {
+        Ok(store) => Json(AutoResponse::of(&store)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
 }
 
 /// Create a new store
 #[utoipa::path(
     post,
-    path = "/stores",
+    operation_id = "createStore",
+    path = "/api/v1/stores",
     tag = "Stores",
     request_body = CreateStoreRequest,
     responses(
         (status = 201, description = "Store created successfully", body = StoreResponse),
-        (status = 400, description = "Bad request - invalid input"),
+        (status = 400, description = "Bad request - invalid input", body = ValidationReport),
+        (status = 422, description = "Name or description uses offensive language", body = TextRejectionResponse),
         (status = 500, description = "Internal server error")
     )
 )]
 #[allow(dead_code)]
 pub async fn create_store(
     State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
     Json(request): Json<CreateStoreRequest>,
 ) -> impl IntoResponse {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    if let Err(errors) = validate_store(&request.as_input()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+
     match Store::create(
         &db,
         &request.name,
@@ -84,6 +664,8 @@ pub async fn create_store(
         request.contact_email.as_deref(),
         request.contact_whatsapp.as_deref(),
         request.owner_device_id.as_deref(),
+        request.default_return_policy.as_deref(),
+        &tenant,
     )
     .await
     {
@@ -92,10 +674,29 @@ pub async fn create_store(
     }
 }
 
+/// Check a store form without saving it
+#[utoipa::path(
+    post,
+    operation_id = "validateStoreForm",
+    path = "/api/v1/stores/validate",
+    tag = "Stores",
+    request_body = CreateStoreRequest,
+    responses(
+        (status = 200, description = "Validation result; `valid` is false when `errors` is non-empty", body = ValidationReport)
+    )
+)]
+pub async fn validate_store_form(Json(request): Json<CreateStoreRequest>) -> impl IntoResponse {
+    let errors = validate_store(&request.as_input())
+        .err()
+        .unwrap_or_default();
+    Json(ValidationReport::from(errors))
+}
+
 /// Get a store by ID
 #[utoipa::path(
     get,
-    path = "/stores/{id}",
+    operation_id = "getStore",
+    path = "/api/v1/stores/{id}",
     tag = "Stores",
     params(
         ("id" = String, Path, description = "Store ID", format = "uuid")
@@ -109,10 +710,14 @@ pub async fn create_store(
 #[allow(dead_code)]
 pub async fn get_store(
     State(db): State<DatabaseConnection>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     match Store::get(&db, id).await {
-        Ok(store) => (StatusCode::OK, Json(StoreResponse { store })).into_response(),
+        Ok(store) => {
+            let store = store_for_viewer(store, claims_from_headers(&headers).as_ref());
+            (StatusCode::OK, Json(StoreResponse { store })).into_response()
+        }
         Err(err) => (StatusCode::NOT_FOUND, err).into_response(),
     }
 }
@@ -120,17 +725,56 @@ pub async fn get_store(
 /// List all stores
 #[utoipa::path(
     get,
-    path = "/stores",
+    operation_id = "listStores",
+    path = "/api/v1/stores",
     tag = "Stores",
+    params(
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return, e.g. `id,name,logo_url`; unknown fields are rejected"),
+        ("sort" = Option<String>, Query, description = "`newest` (default) or `trust_score`, most trusted first"),
+        PageParams
+    ),
     responses(
-        (status = 200, description = "List of stores", body = StoresListResponse),
+        (status = 200, description = "One page of stores", body = StoresPage),
+        (status = 400, description = "Unknown field or sort requested, or invalid page parameters"),
         (status = 500, description = "Internal server error")
     )
 )]
 #[allow(dead_code)]
-pub async fn list_stores(State(db): State<DatabaseConnection>) -> impl IntoResponse {
-    match Store::list(&db).await {
-        Ok(stores) => (StatusCode::OK, Json(StoresListResponse { stores })).into_response(),
+pub async fn list_stores(
+    State(db): State<DatabaseConnection>,
+    Query(query): Query<ListStoresQuery>,
+    page: PageRequest,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let fields = match FieldSelection::parse(query.fields.as_deref(), STORE_FIELDS) {
+        Ok(fields) => fields,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let claims = claims_from_headers(&headers);
+    let stores = Store::list(
+        &db,
+        &tenant,
+        query.sort.unwrap_or_default(),
+        page.page,
+        page.per_page,
+    )
+    .await
+    .map(|(stores, total)| {
+        let stores = stores
+            .into_iter()
+            .map(|store| store_for_viewer(store, claims.as_ref()))
+            .collect();
+        page.numbered(stores, total)
+    });
+    match stores {
+        Ok(stores) if fields.is_some() => {
+            (StatusCode::OK, Json(stores.project(fields.as_ref()))).into_response()
+        }
+        Ok(stores) => (StatusCode::OK, Json(stores)).into_response(),
         Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
     }
 }
@@ -138,26 +782,40 @@ pub async fn list_stores(State(db): State<DatabaseConnection>) -> impl IntoRespo
 /// Update a store
 #[utoipa::path(
     put,
-    path = "/stores/{id}",
+    operation_id = "updateStore",
+    path = "/api/v1/stores/{id}",
     tag = "Stores",
     params(
-        ("id" = String, Path, description = "Store ID", format = "uuid")
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("apply_to_products" = Option<bool>, Query, description = "Cascade the default return policy to products that inherit it")
     ),
     request_body = UpdateStoreRequest,
     responses(
         (status = 200, description = "Store updated successfully", body = StoreResponse),
         (status = 404, description = "Store not found"),
-        (status = 400, description = "Bad request - invalid input"),
+        (status = 400, description = "Bad request - invalid input", body = ValidationReport),
+        (status = 422, description = "Name or description uses offensive language", body = TextRejectionResponse),
         (status = 500, description = "Internal server error")
     )
 )]
 #[allow(dead_code)]
 pub async fn update_store(
     State(db): State<DatabaseConnection>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<UpdateStoreQuery>,
+    headers: HeaderMap,
     Json(request): Json<UpdateStoreRequest>,
 ) -> impl IntoResponse {
-    match Store::update(
+    if let Err(errors) = validate_store(&request.as_input()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+
+    let changed_by = claims_from_headers(&headers).map(|claims| claims.relay_id);
+    let store = match Store::update(
         &db,
         id,
         &request.name,
@@ -167,32 +825,69 @@ pub async fn update_store(
         request.contact_phone.as_deref(),
         request.contact_email.as_deref(),
         request.contact_whatsapp.as_deref(),
+        request.default_return_policy.as_deref(),
+        request.visibility(),
+        ChangeOrigin::edit(changed_by.as_deref()),
     )
     .await
     {
-        Ok(store) => (StatusCode::OK, Json(StoreResponse { store })).into_response(),
-        Err(err) => (StatusCode::NOT_FOUND, err).into_response(),
+        Ok(store) => store,
+        Err(err) => return (StatusCode::NOT_FOUND, err).into_response(),
+    };
+
+    if query.apply_to_products.unwrap_or(false) {
+        if let Err(err) = Store::apply_default_return_policy(&db, &store).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+        }
+    }
+
+    (StatusCode::OK, Json(StoreResponse { store })).into_response()
+}
+
+/// What deleting a store would remove, and what stops it for now
+pub async fn deletion_summary(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+) -> Result<DeletionSummary, String> {
+    let counts = ProductCounts::for_store(db, store_id).await?;
+    let mut blockers = Vec::new();
+    // The runner would write the file for a store that no longer exists
+    if ReportJob::active_count(db, store_id).await? > 0 {
+        blockers.push(
+            "A report is being generated for this store; try again once it is ready".to_string(),
+        );
     }
+    Ok(DeletionSummary {
+        product_count: (counts.published + counts.unpublished) as u64,
+        blockers,
+    })
 }
 
-/// Delete a store
+/// Delete a store.
+///
+/// Takes two calls: the first answers 409 with what would be deleted and a
+/// confirmation token, the second sends the token in `X-Confirm-Token`.
 #[utoipa::path(
     delete,
-    path = "/stores/{id}",
+    operation_id = "deleteStore",
+    path = "/api/v1/stores/{id}",
     tag = "Stores",
     params(
-        ("id" = String, Path, description = "Store ID", format = "uuid")
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        ("X-Confirm-Token" = Option<String>, Header, description = "Token from the first call's 409, valid for 5 minutes and one use")
     ),
     responses(
         (status = 204, description = "Store deleted successfully"),
+        (status = 400, description = "Confirmation token invalid, expired or already used"),
         (status = 404, description = "Store not found"),
+        (status = 409, description = "Confirmation required, or the deletion is blocked", body = ConfirmationResponse),
         (status = 500, description = "Internal server error")
     )
 )]
 #[allow(dead_code)]
 pub async fn delete_store(
     State(db): State<DatabaseConnection>,
-    Path(id): Path<Uuid>,
+    UuidPath(id): UuidPath<Uuid>,
 ) -> impl IntoResponse {
     match Store::delete(&db, id).await {
         Ok(()) => StatusCode::NO_CONTENT.into_response(),
@@ -200,46 +895,100 @@ pub async fn delete_store(
     }
 }
 
+/// Usage figures of a store, for its owner and admins
+#[utoipa::path(
+    get,
+    operation_id = "getStoreStats",
+    path = "/api/v1/stores/{id}/stats",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid")
+    ),
+    responses(
+        (status = 200, description = "Product counts and usage against quotas", body = StoreStatsResponse),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Not the store owner"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn store_stats(
+    State(db): State<DatabaseConnection>,
+    State(limits): State<Arc<MediaLimits>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let store = if require_admin(&headers).is_ok() {
+        Store::get(&db, id)
+            .await
+            .map_err(|err| (StatusCode::NOT_FOUND, err))
+    } else {
+        owned_store(&db, &headers, id, ApiScope::ProductsRead).await
+    };
+    let store = match store {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let product_counts = match ProductCounts::for_store(&db, store.id).await {
+        Ok(counts) => counts,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let referrals = match ReferralLink::totals(&db, store.id).await {
+        Ok(totals) => totals,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    Json(StoreStatsResponse {
+        store_id: store.id,
+        total_products: (product_counts.published + product_counts.unpublished) as i32,
+        product_counts,
+        media: MediaUsage::new(&store, &limits, Utc::now()),
+        referrals,
+    })
+    .into_response()
+}
+
 /// Generate store sharing links
 #[utoipa::path(
     get,
-    path = "/stores/{id}/share",
+    operation_id = "getStoreShareLinks",
+    path = "/api/v1/stores/{id}/share",
     tag = "Stores",
     params(
         ("id" = String, Path, description = "Store ID", format = "uuid")
     ),
     responses(
-        (status = 200, description = "Store sharing links generated", body = StoreShareResponse),
+        (status = 200, description = "Store sharing links generated; the owner's first request completes the share onboarding step", body = StoreShareResponse),
         (status = 404, description = "Store not found"),
         (status = 500, description = "Internal server error")
     )
 )]
-#[allow(dead_code)]
 pub async fn get_store_share_links(
     State(db): State<DatabaseConnection>,
-    Path(id): Path<Uuid>,
+    State(site): State<Arc<SiteConfig>>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     // First verify the store exists
     match Store::get(&db, id).await {
         Ok(store) => {
-            let store_id = id.to_string();
-            let base_url = "https://transac.site"; // This should come from config
-            let share_url = format!("{base_url}/store/{store_id}");
-            let whatsapp_message = format!(
-                "Check out my store '{}' on Transac: {}",
-                store.name, share_url
-            );
-            let whatsapp_share_url = format!(
-                "https://wa.me/?text={}",
-                urlencoding::encode(&whatsapp_message)
-            );
+            let is_owner = claims_from_headers(&headers).is_some_and(|claims| {
+                store.owner_device_id.as_deref() == Some(claims.relay_id.as_str())
+            });
+            if is_owner && store.first_shared_at.is_none() {
+                if let Err(err) = onboarding::record_share(&db, id, Utc::now()).await {
+                    warn!(store_id = %id, error = %err, "Share links sent without recording them");
+                }
+            }
+            let share_url = StoreLink::new(&site.public_base_url, id).to_string();
+            let message = format!("Check out my store '{}' on Transac:", store.name);
 
             (
                 StatusCode::OK,
                 Json(StoreShareResponse {
-                    store_id,
+                    store_id: id.to_string(),
+                    whatsapp_share_url: WhatsAppShare::new(&message, &share_url).to_string(),
+                    sms_share_url: SmsShare::new(&message, &share_url).to_string(),
+                    telegram_share_url: TelegramShare::new(&message, &share_url).to_string(),
                     share_url,
-                    whatsapp_share_url,
                 }),
             )
                 .into_response()
@@ -248,14 +997,309 @@ pub async fn get_store_share_links(
     }
 }
 
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct StoreApiState {
+    pub db: DatabaseConnection,
+    pub site: Arc<SiteConfig>,
+}
+
+impl FromRef<StoreApiState> for DatabaseConnection {
+    fn from_ref(state: &StoreApiState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<StoreApiState> for Arc<SiteConfig> {
+    fn from_ref(state: &StoreApiState) -> Self {
+        state.site.clone()
+    }
+}
+
 #[allow(dead_code)]
 pub fn router(db: DatabaseConnection) -> Router<()> {
     Router::new()
         .route("/stores", post(create_store))
         .route("/stores", get(list_stores))
+        .route("/stores/validate", post(validate_store_form))
         .route("/stores/:id", get(get_store))
         .route("/stores/:id", put(update_store))
         .route("/stores/:id", delete(delete_store))
         .route("/stores/:id/share", get(get_store_share_links))
-        .with_state(db)
+        .route("/stores/:id/pause", post(pause_store))
+        .route("/stores/:id/resume", post(resume_store))
+        .route("/stores/:id/delivery-options", put(set_delivery_options))
+        .route(
+            "/return-policy-templates",
+            get(return_policies::list_return_policy_templates),
+        )
+        .with_state(StoreApiState {
+            db,
+            site: Arc::new(SiteConfig::default()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn store(is_paused: bool, paused_until: Option<DateTime<Utc>>) -> StoreModel {
+        let now = Utc::now();
+        StoreModel {
+            id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            name: "Mama Ngono".to_string(),
+            description: None,
+            logo_url: None,
+            location: None,
+            contact_phone: None,
+            contact_email: None,
+            contact_whatsapp: None,
+            show_phone: true,
+            show_whatsapp: true,
+            show_email: true,
+            embed_enabled: false,
+            media_bytes_used: 0,
+            media_uploads_today: 0,
+            media_uploads_day: None,
+            media_quota_bytes: None,
+            media_daily_uploads: None,
+            media_images_per_product: None,
+            owner_device_id: None,
+            is_verified: false,
+            rating: None,
+            store_rating: None,
+            store_review_count: 0,
+            total_products: 0,
+            default_return_policy: None,
+            default_pickup_available: true,
+            default_delivery_available: false,
+            default_delivery_fee: None,
+            default_delivery_estimated_days: None,
+            is_paused,
+            paused_until,
+            pause_message: Some("On holiday until the 3rd".to_string()),
+            auto_response_enabled: false,
+            auto_response_message: None,
+            auto_response_trigger: None,
+            trust_score: None,
+            first_shared_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_orders_against_paused_store_are_rejected() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 24, 9, 0, 0).unwrap();
+        let back = now + Duration::days(10);
+
+        let response = ensure_accepting_orders(&store(true, Some(back)), now)
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], STORE_PAUSED);
+        assert_eq!(body["message"], "On holiday until the 3rd");
+    }
+
+    #[test]
+    fn test_orders_accepted_when_open_or_pause_elapsed() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 24, 9, 0, 0).unwrap();
+        assert!(ensure_accepting_orders(&store(false, None), now).is_ok());
+        // Past paused_until but not yet swept by the resume job
+        let elapsed = store(true, Some(now - Duration::minutes(1)));
+        assert!(ensure_accepting_orders(&elapsed, now).is_ok());
+    }
+
+    #[test]
+    fn test_pause_request_checks() {
+        let now = Utc::now();
+        let request = |paused_until, message: &str| PauseStoreRequest {
+            paused_until,
+            message: Some(message.to_string()),
+        };
+        assert!(check_pause_request(&request(None, "Away"), now).is_ok());
+        assert!(check_pause_request(&request(Some(now + Duration::days(1)), "Away"), now).is_ok());
+        assert!(check_pause_request(&request(Some(now), "Away"), now).is_err());
+        let long = "a".repeat(PAUSE_MESSAGE_MAX_LEN + 1);
+        assert!(check_pause_request(&request(None, &long), now).is_err());
+    }
+
+    #[test]
+    fn test_auto_response_request_checks() {
+        let request = |enabled, message: Option<&str>, trigger| AutoResponseRequest {
+            enabled,
+            message: message.map(str::to_string),
+            trigger,
+        };
+        let paused = Some(AutoResponseTrigger::Paused);
+        assert!(check_auto_response_request(&request(true, Some("Away"), paused)).is_ok());
+        assert!(check_auto_response_request(&request(false, None, None)).is_ok());
+        assert!(check_auto_response_request(&request(true, Some("  "), paused)).is_err());
+        assert!(check_auto_response_request(&request(true, Some("Away"), None)).is_err());
+        let long = "a".repeat(AUTO_RESPONSE_MAX_LEN + 1);
+        assert!(check_auto_response_request(&request(false, Some(&long), paused)).is_err());
+    }
+
+    #[test]
+    fn test_mask_contact() {
+        assert_eq!(mask_contact("+237 699 000 123"), "+237•••••123");
+        assert_eq!(mask_contact("+237-6"), "•••••");
+    }
+
+    async fn get_as(db: &DatabaseConnection, id: Uuid, caller: Option<(&str, &str)>) -> StoreModel {
+        use axum::body::Body;
+        use axum::http::{header, Request};
+        use tower::ServiceExt;
+
+        let mut request = Request::builder().uri(format!("/stores/{id}"));
+        if let Some((relay_id, role)) = caller {
+            let token = crate::auth::JwtService::new()
+                .unwrap()
+                .generate_token_with_role(relay_id.into(), String::new(), role.into())
+                .unwrap();
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = router(db.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: StoreResponseBody = serde_json::from_slice(&body).unwrap();
+        body.store
+    }
+
+    #[derive(Deserialize)]
+    struct StoreResponseBody {
+        store: StoreModel,
+    }
+
+    #[tokio::test]
+    async fn test_private_contacts_hidden_from_buyers_only() {
+        let db = crate::db::testing::sqlite().await;
+        let id = crate::db::testing::seed_store(&db, "seller-1").await;
+        Store::update(
+            &db,
+            id,
+            "Mama Ngono",
+            None,
+            None,
+            None,
+            Some("+237699000123"),
+            Some("mama@example.cm"),
+            Some("+237699000456"),
+            None,
+            ContactVisibility {
+                show_phone: Some(false),
+                show_whatsapp: None,
+                show_email: Some(false),
+            },
+            ChangeOrigin::edit(None),
+        )
+        .await
+        .unwrap();
+
+        for caller in [None, Some(("buyer-1", "buyer"))] {
+            let store = get_as(&db, id, caller).await;
+            assert_eq!(store.contact_phone.as_deref(), Some("+237•••••123"));
+            assert_eq!(store.contact_email, None);
+            // Left untouched, so still public
+            assert_eq!(store.contact_whatsapp.as_deref(), Some("+237699000456"));
+        }
+        for caller in [("seller-1", "seller"), ("ops-1", ADMIN_ROLE)] {
+            let store = get_as(&db, id, Some(caller)).await;
+            assert_eq!(store.contact_phone.as_deref(), Some("+237699000123"));
+            assert_eq!(store.contact_email.as_deref(), Some("mama@example.cm"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_share_links_encode_the_store_name() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let db = crate::db::testing::sqlite().await;
+        let id = crate::db::testing::seed_store(&db, "seller-1").await;
+        Store::update(
+            &db,
+            id,
+            "Fish & Chips + Co 🐟",
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            ContactVisibility::default(),
+            ChangeOrigin::edit(None),
+        )
+        .await
+        .unwrap();
+
+        let response = router(db.clone())
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/stores/{id}/share"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let links: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let share_url = format!("https://transac.site/store/{id}");
+        assert_eq!(links["share_url"], share_url);
+
+        let whatsapp = links["whatsapp_share_url"].as_str().unwrap();
+        let text = whatsapp.strip_prefix("https://wa.me/?text=").unwrap();
+        assert!(text.is_ascii() && !text.contains('&') && !text.contains('+'));
+        assert_eq!(
+            urlencoding::decode(text).unwrap(),
+            format!("Check out my store 'Fish & Chips + Co 🐟' on Transac: {share_url}")
+        );
+        assert!(links["sms_share_url"]
+            .as_str()
+            .unwrap()
+            .starts_with("sms:?&body=Check%20out%20my%20store%20%27Fish%20%26%20Chips%20%2B%20Co"));
+        assert!(links["telegram_share_url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://t.me/share/url?url=https%3A%2F%2Ftransac.site%2Fstore%2F"));
+    }
+
+    #[tokio::test]
+    async fn test_store_deletion_waits_for_running_reports() {
+        use crate::db::reports::CreateReport;
+        use crate::reports::{ReportFormat, ReportType};
+
+        let db = crate::db::testing::sqlite().await;
+        let id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let summary = deletion_summary(&db, id).await.unwrap();
+        assert!(summary.blockers.is_empty());
+
+        let request = CreateReport {
+            store_id: id,
+            requested_by: "seller-1".to_string(),
+            report_type: ReportType::NewProducts,
+            format: ReportFormat::Csv,
+            range_start: Utc::now() - chrono::Duration::days(30),
+            range_end: Utc::now(),
+        };
+        ReportJob::create(&db, &request, 5).await.unwrap().unwrap();
+        let summary = deletion_summary(&db, id).await.unwrap();
+        assert_eq!(summary.blockers.len(), 1);
+    }
 }
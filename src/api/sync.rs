@@ -0,0 +1,128 @@
+use crate::api::products::ProductResponse;
+use crate::api::stores::public_store;
+use crate::db::retention::PrunableTable;
+use crate::db::sync::{sync_page, DbChangeSource, SyncCursor};
+use crate::entity::store::Model as StoreModel;
+use crate::entity::tombstone::Model as TombstoneModel;
+use crate::retention::PRUNE_LOG;
+use crate::tenant::tenant_from_headers;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_SYNC_LIMIT: u64 = 200;
+const MAX_SYNC_LIMIT: u64 = 500;
+
+#[derive(Deserialize, IntoParams)]
+pub struct SyncQuery {
+    /// `server_time` from the previous completed sync; omit for a full sync
+    pub since: Option<String>,
+    #[param(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+    /// Maximum number of records per page (default 200, max 500)
+    pub limit: Option<u64>,
+    /// `next_cursor` from the previous page of the same sync
+    pub cursor: Option<String>,
+}
+
+/// Everything that changed since the client's last sync
+#[derive(Serialize, ToSchema)]
+pub struct SyncResponse {
+    pub stores: Vec<StoreModel>,
+    pub products: Vec<ProductResponse>,
+    /// Rows deleted since `since`; clients drop their local copies
+    pub tombstones: Vec<TombstoneModel>,
+    /// Store this and send it as `since` once `next_cursor` is null
+    pub server_time: DateTime<Utc>,
+    /// Pass back as `cursor` to fetch the rest of this sync
+    pub next_cursor: Option<String>,
+}
+
+/// Pull changed stores and products, plus deletions, since a watermark
+#[utoipa::path(
+    get,
+    operation_id = "syncChanges",
+    path = "/api/v1/sync",
+    params(SyncQuery),
+    responses(
+        (status = 200, description = "Changes since `since`", body = SyncResponse),
+        (status = 400, description = "Invalid `since` or `cursor`"),
+        (status = 410, description = "`since` predates the deletions still kept; start a full sync"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Sync"
+)]
+pub async fn sync_changes(
+    State(db): State<DatabaseConnection>,
+    Query(query): Query<SyncQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant = match tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    let since = match query.since.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(since)) => Some(since.with_timezone(&Utc)),
+        Some(Err(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "since must be an RFC 3339 timestamp".to_string(),
+            )
+                .into_response()
+        }
+        None => None,
+    };
+    let now = Utc::now();
+    // Tombstones older than the retention window are gone, so deletions
+    // before it can no longer be reported
+    if let (Some(since), Some(horizon)) = (since, PRUNE_LOG.horizon(PrunableTable::Tombstones, now))
+    {
+        if since < horizon {
+            return (
+                StatusCode::GONE,
+                "since is older than the kept deletion history; start a full sync".to_string(),
+            )
+                .into_response();
+        }
+    }
+    let cursor = match query.cursor.as_deref().map(SyncCursor::decode) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(err)) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        None => None,
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SYNC_LIMIT)
+        .clamp(1, MAX_SYNC_LIMIT);
+
+    let source = DbChangeSource {
+        db: &db,
+        tenant_id: &tenant,
+    };
+    match sync_page(&source, since, query.store_id, cursor, limit, now).await {
+        Ok(page) => (
+            StatusCode::OK,
+            Json(SyncResponse {
+                stores: page.stores.into_iter().map(public_store).collect(),
+                products: page
+                    .products
+                    .into_iter()
+                    .map(|p| ProductResponse::new(p, now))
+                    .collect(),
+                tombstones: page.tombstones,
+                server_time: page.server_time,
+                next_cursor: page.next_cursor.map(|c| c.encode()),
+            }),
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
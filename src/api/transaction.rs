@@ -0,0 +1,204 @@
+//! Request-scoped database transactions.
+//!
+//! [`transaction_middleware`] gives every mutating request room for one
+//! transaction. A handler opts in by taking a [`Tx`] extractor, which begins
+//! the transaction; the middleware commits it when the handler answers 2xx
+//! and rolls it back on anything else, so a handler that fails halfway leaves
+//! no partial rows. Handlers that manage their own transactions simply don't
+//! take `Tx`, and nothing is begun for them.
+//!
+//! The transaction is settled before the response is returned, so a
+//! streaming body never holds it open. A `Tx` kept alive past the handler is
+//! a bug: it is rolled back and the response becomes a 500.
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, State},
+    http::{request::Parts, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, TransactionTrait};
+use std::ops::Deref;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::error;
+
+/// Where a request's transaction lives between the extractor and the middleware
+#[derive(Clone)]
+struct TxSlot {
+    db: DatabaseConnection,
+    txn: Arc<Mutex<Option<Arc<DatabaseTransaction>>>>,
+}
+
+/// The request's transaction; extracting it twice yields the same one
+pub struct Tx(Arc<DatabaseTransaction>);
+
+impl Deref for Tx {
+    type Target = DatabaseTransaction;
+
+    fn deref(&self) -> &DatabaseTransaction {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Tx {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(slot) = parts.extensions.get::<TxSlot>().cloned() else {
+            error!("Tx extracted on a route without transaction_middleware");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Request transaction unavailable",
+            ));
+        };
+        let mut txn = slot.txn.lock().await;
+        if let Some(txn) = txn.as_ref() {
+            return Ok(Tx(txn.clone()));
+        }
+        let begun = Arc::new(slot.db.begin().await.map_err(|e| {
+            error!("Failed to begin request transaction: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction. Please try again later.",
+            )
+        })?);
+        *txn = Some(begun.clone());
+        Ok(Tx(begun))
+    }
+}
+
+/// Reads never get a transaction
+fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Commit the request's transaction on 2xx, roll it back otherwise
+pub async fn transaction_middleware(
+    State(db): State<DatabaseConnection>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    if !is_mutating(request.method()) {
+        return next.run(request).await;
+    }
+    let slot = TxSlot {
+        db,
+        txn: Arc::default(),
+    };
+    request.extensions_mut().insert(slot.clone());
+    let response = next.run(request).await;
+
+    let Some(txn) = slot.txn.lock().await.take() else {
+        return response;
+    };
+    // Dropping the last handle rolls the transaction back
+    let Ok(txn) = Arc::try_unwrap(txn) else {
+        error!("Request transaction outlived its handler; rolled back");
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to save changes. Please try again later.",
+        )
+            .into_response();
+    };
+    if response.status().is_success() {
+        if let Err(e) = txn.commit().await {
+            error!("Failed to commit request transaction: {:?}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to save changes. Please try again later.",
+            )
+                .into_response();
+        }
+    } else if let Err(e) = txn.rollback().await {
+        error!("Failed to roll back request transaction: {:?}", e);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use sea_orm::{ConnectOptions, ConnectionTrait, Database, DbBackend, Statement};
+    use tower::ServiceExt;
+
+    async fn database() -> DatabaseConnection {
+        // One connection, so every query sees the same in-memory database
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1).sqlx_logging(false);
+        let db = Database::connect(options).await.unwrap();
+        db.execute_unprepared("CREATE TABLE rows (name TEXT NOT NULL)")
+            .await
+            .unwrap();
+        db
+    }
+
+    async fn insert(tx: &Tx, name: &str) {
+        tx.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            "INSERT INTO rows (name) VALUES (?)",
+            [name.into()],
+        ))
+        .await
+        .unwrap();
+    }
+
+    async fn rows(db: &DatabaseConnection) -> i64 {
+        let row = db
+            .query_one(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT COUNT(*) AS n FROM rows",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        row.try_get("", "n").unwrap()
+    }
+
+    fn app(db: DatabaseConnection) -> Router {
+        Router::new()
+            .route(
+                "/ok",
+                post(|tx: Tx| async move {
+                    insert(&tx, "first").await;
+                    insert(&tx, "second").await;
+                    StatusCode::CREATED
+                }),
+            )
+            .route(
+                "/fail",
+                post(|tx: Tx| async move {
+                    insert(&tx, "first").await;
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }),
+            )
+            .layer(middleware::from_fn_with_state(db, transaction_middleware))
+    }
+
+    async fn send(db: &DatabaseConnection, path: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        app(db.clone()).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_failed_handler_leaves_no_partial_rows() {
+        let db = database().await;
+        assert_eq!(send(&db, "/fail").await, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(rows(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_successful_handler_commits_every_write() {
+        let db = database().await;
+        assert_eq!(send(&db, "/ok").await, StatusCode::CREATED);
+        assert_eq!(rows(&db).await, 2);
+    }
+}
@@ -0,0 +1,107 @@
+//! Buyers as other people see them. Reviews and product questions name
+//! their author with a handle derived from the relay ID, never the ID
+//! itself, which would let anyone link a buyer's posts across stores. The
+//! author and admins still get the ID back.
+
+use crate::auth::{Claims, ADMIN_ROLE};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Characters of the relay ID kept at the end of a handle
+const HANDLE_TAIL_LEN: usize = 3;
+
+/// Shorter relay IDs get no tail, which would give away too much of them
+const MIN_ID_LEN_FOR_TAIL: usize = 8;
+
+/// Author of public content, as the viewer may see them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PublicUserRef {
+    /// Shown in place of a name, e.g. `Buyer •••a3f`; the same for every
+    /// post by the same buyer
+    pub handle: String,
+    /// Profile picture; null until buyers have profiles
+    pub avatar_url: Option<String>,
+    /// The author's relay ID, for the author themselves and admins only
+    pub relay_id: Option<String>,
+}
+
+/// Builds [`PublicUserRef`]s for one viewer
+#[derive(Debug, Clone, Copy)]
+pub struct UserRefBuilder<'a> {
+    viewer: Option<&'a Claims>,
+}
+
+impl PublicUserRef {
+    /// References as `viewer` (`None` for visitors) may see them
+    pub fn viewed_by(viewer: Option<&Claims>) -> UserRefBuilder<'_> {
+        UserRefBuilder { viewer }
+    }
+}
+
+impl UserRefBuilder<'_> {
+    /// The user behind `relay_id`
+    pub fn build(&self, relay_id: &str) -> PublicUserRef {
+        let sees_id = self
+            .viewer
+            .is_some_and(|viewer| viewer.role == ADMIN_ROLE || viewer.relay_id == relay_id);
+        PublicUserRef {
+            handle: handle(relay_id),
+            avatar_url: None,
+            relay_id: sees_id.then(|| relay_id.to_owned()),
+        }
+    }
+}
+
+/// `Buyer •••` and the last few alphanumerics of the relay ID: enough to
+/// tell two buyers apart on a page, not to find either elsewhere
+fn handle(relay_id: &str) -> String {
+    let chars: Vec<char> = relay_id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    if chars.len() < MIN_ID_LEN_FOR_TAIL {
+        return "Buyer •••".to_string();
+    }
+    let tail: String = chars[chars.len().saturating_sub(HANDLE_TAIL_LEN)..]
+        .iter()
+        .collect();
+    format!("Buyer •••{tail}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(relay_id: &str, role: &str) -> Claims {
+        Claims::new(relay_id.into(), String::new(), 1, role.into())
+    }
+
+    #[test]
+    fn test_only_the_author_and_admins_see_the_relay_id() {
+        let author = "npub1buyer7a3f";
+        let buyer = claims(author, "buyer");
+        let other_buyer = claims("npub1buyer0000", "buyer");
+        let store_owner = claims("seller-1", "seller");
+        let admin = claims("ops-1", ADMIN_ROLE);
+
+        for (viewer, sees_id) in [
+            (None, false),
+            (Some(&other_buyer), false),
+            (Some(&store_owner), false),
+            (Some(&buyer), true),
+            (Some(&admin), true),
+        ] {
+            let user = PublicUserRef::viewed_by(viewer).build(author);
+            assert_eq!(user.handle, "Buyer •••a3f");
+            assert_eq!(user.avatar_url, None);
+            assert_eq!(user.relay_id.as_deref(), sees_id.then_some(author));
+        }
+    }
+
+    #[test]
+    fn test_handle_never_spells_out_a_short_id() {
+        assert_eq!(handle("npub1-buyer-7a3f"), "Buyer •••a3f");
+        assert_eq!(handle("buyer-1"), "Buyer •••");
+        assert_eq!(handle(""), "Buyer •••");
+    }
+}
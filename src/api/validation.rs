@@ -0,0 +1,993 @@
+//! Form validation shared by the create/update handlers and the dry-run
+//! `/validate` endpoints, so both always report the same errors.
+
+use crate::db::bundles::{Bundle, BundleComponent};
+use crate::db::categories::Category;
+use crate::db::category_attributes::{AttributeDefinition, AttributeType, CategoryAttributes};
+use crate::db::delivery::{DeliveryOptions, DeliveryOptionsInput, MAX_ESTIMATED_DAYS};
+use crate::db::products::{
+    currency_error, low_stock_threshold_error, price_error, quantity_error, Product, Sale,
+    SKU_TAKEN,
+};
+use crate::db::return_policy::MAX_RETURN_WINDOW_DAYS;
+use crate::db::stores::Store;
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::collections::HashSet;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest product, store or bundle name
+pub const MAX_NAME_LEN: usize = 255;
+const MAX_SKU_LEN: usize = 100;
+const MAX_URL_LEN: usize = 500;
+const MAX_LOCATION_LEN: usize = 255;
+const MAX_EMAIL_LEN: usize = 255;
+const MAX_PHONE_LEN: usize = 50;
+const MAX_RETURN_CONDITIONS_LEN: usize = 1000;
+const MAX_BUNDLE_ITEMS: usize = 20;
+/// Longest text or enum value an attribute may hold
+pub const MAX_ATTRIBUTE_TEXT_LEN: usize = 255;
+
+/// A single problem with one form field
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine-readable reason: required, too_long, invalid, out_of_range, taken, not_found,
+    /// unknown
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_owned(),
+            code: code.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Body returned by the dry-run endpoints, and by create/update on a 400
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<FieldError>,
+}
+
+impl From<Vec<FieldError>> for ValidationReport {
+    fn from(errors: Vec<FieldError>) -> Self {
+        Self {
+            valid: errors.is_empty(),
+            errors,
+        }
+    }
+}
+
+/// Product fields as submitted, independent of how the handler parsed them
+#[derive(Debug, Clone, Default)]
+pub struct ProductInput<'a> {
+    pub store_id: Option<Uuid>,
+    /// Set when validating an edit so the product's own SKU isn't a conflict
+    pub product_id: Option<Uuid>,
+    pub sku: Option<&'a str>,
+    pub name: &'a str,
+    pub price: f64,
+    /// `None` takes the default currency, or keeps the current one on an edit
+    pub currency: Option<&'a str>,
+    pub quantity_available: i32,
+    pub low_stock_threshold: Option<i32>,
+    pub category_id: Option<Uuid>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub sale_price: Option<f64>,
+    pub sale_ends_at: Option<DateTime<Utc>>,
+    pub returns_accepted: Option<bool>,
+    pub return_window_days: Option<i32>,
+    pub return_conditions: Option<&'a str>,
+    pub delivery_options: Option<DeliveryOptionsInput>,
+    /// Category attributes; `None` means the product has none
+    pub attributes: Option<&'a serde_json::Value>,
+}
+
+/// Store fields as submitted
+#[derive(Debug, Clone, Default)]
+pub struct StoreInput<'a> {
+    pub name: &'a str,
+    pub logo_url: Option<&'a str>,
+    pub location: Option<&'a str>,
+    pub contact_phone: Option<&'a str>,
+    pub contact_email: Option<&'a str>,
+    pub contact_whatsapp: Option<&'a str>,
+}
+
+/// `field` of a JSON body as a number; `None` when absent or null
+pub fn number_field(body: &serde_json::Value, field: &str) -> Result<Option<f64>, FieldError> {
+    match body.get(field) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| FieldError::new(field, "invalid", format!("{field} must be a number."))),
+    }
+}
+
+/// `field` of a JSON body as a whole number its column can hold; `None`
+/// when absent or null
+pub fn integer_field(body: &serde_json::Value, field: &str) -> Result<Option<i32>, FieldError> {
+    let out_of_range = || {
+        FieldError::new(
+            field,
+            "out_of_range",
+            format!("{field} must be between {} and {}.", i32::MIN, i32::MAX),
+        )
+    };
+    match body.get(field) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => match value.as_i64() {
+            Some(n) => i32::try_from(n).map(Some).map_err(|_| out_of_range()),
+            None if value.is_u64() => Err(out_of_range()),
+            None => Err(FieldError::new(
+                field,
+                "invalid",
+                format!("{field} must be a whole number."),
+            )),
+        },
+    }
+}
+
+/// A scheduled publish time must not already have passed
+pub fn publish_at_error(
+    publish_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<FieldError> {
+    match publish_at {
+        Some(publish_at) if publish_at <= now => Some(FieldError::new(
+            "publish_at",
+            "out_of_range",
+            "publish_at must be in the future.",
+        )),
+        _ => None,
+    }
+}
+
+/// Build a sale from optional request fields; both must be given together
+pub fn sale(
+    price: f64,
+    sale_price: Option<f64>,
+    sale_ends_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<Option<Sale>, FieldError> {
+    match (sale_price, sale_ends_at) {
+        (None, None) => Ok(None),
+        (Some(sale_price), Some(ends_at)) => {
+            if !(0.0..price).contains(&sale_price) {
+                return Err(FieldError::new(
+                    "sale_price",
+                    "out_of_range",
+                    "sale_price must be lower than price.",
+                ));
+            }
+            if ends_at <= now {
+                return Err(FieldError::new(
+                    "sale_ends_at",
+                    "out_of_range",
+                    "sale_ends_at must be in the future.",
+                ));
+            }
+            Ok(Some(Sale {
+                price: sale_price,
+                ends_at,
+            }))
+        }
+        (None, Some(_)) => Err(FieldError::new(
+            "sale_price",
+            "required",
+            "sale_price and sale_ends_at must be set together.",
+        )),
+        (Some(_), None) => Err(FieldError::new(
+            "sale_ends_at",
+            "required",
+            "sale_price and sale_ends_at must be set together.",
+        )),
+    }
+}
+
+/// Structured return policy rules: a window only makes sense when returns
+/// are accepted, and must fit within the maximum
+pub fn return_policy_errors(
+    returns_accepted: Option<bool>,
+    window_days: Option<i32>,
+    conditions: Option<&str>,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(days) = window_days {
+        if !(0..=MAX_RETURN_WINDOW_DAYS).contains(&days) {
+            errors.push(FieldError::new(
+                "return_window_days",
+                "out_of_range",
+                format!("return_window_days must be between 0 and {MAX_RETURN_WINDOW_DAYS}."),
+            ));
+        } else if returns_accepted != Some(true) {
+            errors.push(FieldError::new(
+                "return_window_days",
+                "invalid",
+                "return_window_days needs returns_accepted set to true.",
+            ));
+        }
+    }
+    if let Some(conditions) = conditions {
+        if returns_accepted.is_none() {
+            errors.push(FieldError::new(
+                "returns_accepted",
+                "required",
+                "returns_accepted is required with return_conditions.",
+            ));
+        }
+        max_len(
+            &mut errors,
+            "return_conditions",
+            conditions,
+            MAX_RETURN_CONDITIONS_LEN,
+        );
+    }
+    errors
+}
+
+/// Delivery option rules; `field` names the object the options came in
+pub fn delivery_option_errors(field: &str, options: &DeliveryOptionsInput) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if options.pickup_available == Some(false) && options.delivery_available == Some(false) {
+        errors.push(FieldError::new(
+            field,
+            "invalid",
+            "Offer pickup, delivery or both.",
+        ));
+    }
+    if let Some(fee) = options.delivery_fee_override {
+        if !fee.is_finite() || fee < 0.0 {
+            errors.push(FieldError::new(
+                &format!("{field}.delivery_fee_override"),
+                "out_of_range",
+                "delivery_fee_override must be zero or more.",
+            ));
+        }
+    }
+    if let Some(days) = options.estimated_days {
+        if !(0..=MAX_ESTIMATED_DAYS).contains(&days) {
+            errors.push(FieldError::new(
+                &format!("{field}.estimated_days"),
+                "out_of_range",
+                format!("estimated_days must be between 0 and {MAX_ESTIMATED_DAYS}."),
+            ));
+        }
+    }
+    errors
+}
+
+/// Field rules that need no database access
+pub fn product_field_errors(input: &ProductInput<'_>, now: DateTime<Utc>) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    required_text(&mut errors, "name", input.name, MAX_NAME_LEN);
+    if let Some(sku) = input.sku {
+        max_len(&mut errors, "sku", sku, MAX_SKU_LEN);
+    }
+    if let Some(problem) = price_error("price", input.price) {
+        errors.push(FieldError::new("price", "out_of_range", problem));
+    }
+    if let Some(problem) = input
+        .currency
+        .and_then(|code| currency_error("currency", code))
+    {
+        errors.push(FieldError::new("currency", "invalid", problem));
+    }
+    if let Some(problem) = quantity_error(input.quantity_available) {
+        errors.push(FieldError::new(
+            "quantity_available",
+            "out_of_range",
+            problem,
+        ));
+    }
+    if let Some(problem) = input
+        .low_stock_threshold
+        .and_then(low_stock_threshold_error)
+    {
+        errors.push(FieldError::new(
+            "low_stock_threshold",
+            "out_of_range",
+            problem,
+        ));
+    }
+    errors.extend(publish_at_error(input.publish_at, now));
+    if let Err(e) = sale(input.price, input.sale_price, input.sale_ends_at, now) {
+        errors.push(e);
+    }
+    errors.extend(return_policy_errors(
+        input.returns_accepted,
+        input.return_window_days,
+        input.return_conditions,
+    ));
+    if let Some(options) = &input.delivery_options {
+        errors.extend(delivery_option_errors("delivery_options", options));
+    }
+    errors
+}
+
+/// Category attribute rules: every key must be defined for the category,
+/// required ones present, and each value of its definition's type
+pub fn attribute_errors(
+    definitions: &[AttributeDefinition],
+    attributes: Option<&serde_json::Value>,
+) -> Vec<FieldError> {
+    let empty = serde_json::Map::new();
+    let attributes = match attributes {
+        None | Some(serde_json::Value::Null) => &empty,
+        Some(serde_json::Value::Object(attributes)) => attributes,
+        Some(_) => {
+            return vec![FieldError::new(
+                "attributes",
+                "invalid",
+                "attributes must be an object.",
+            )]
+        }
+    };
+    let mut errors = Vec::new();
+    for key in attributes.keys() {
+        if !definitions.iter().any(|definition| &definition.key == key) {
+            errors.push(FieldError::new(
+                &format!("attributes.{key}"),
+                "unknown",
+                format!("{key} is not an attribute of the product's category."),
+            ));
+        }
+    }
+    for definition in definitions {
+        let field = format!("attributes.{}", definition.key);
+        let value = match attributes.get(&definition.key) {
+            None | Some(serde_json::Value::Null) => {
+                if definition.required {
+                    errors.push(FieldError::new(
+                        &field,
+                        "required",
+                        format!("{} is required in this category.", definition.key),
+                    ));
+                }
+                continue;
+            }
+            Some(value) => value,
+        };
+        match (definition.attribute_type, value) {
+            (AttributeType::Number, serde_json::Value::Number(_)) => {}
+            (AttributeType::Number, _) => errors.push(FieldError::new(
+                &field,
+                "invalid",
+                format!("{} must be a number.", definition.key),
+            )),
+            (AttributeType::Text, serde_json::Value::String(text)) => {
+                max_len(&mut errors, &field, text, MAX_ATTRIBUTE_TEXT_LEN)
+            }
+            (AttributeType::Enum, serde_json::Value::String(text))
+                if definition.allowed_values.contains(text) => {}
+            (AttributeType::Enum, _) => errors.push(FieldError::new(
+                &field,
+                "invalid",
+                format!(
+                    "{} must be one of: {}.",
+                    definition.key,
+                    definition.allowed_values.join(", ")
+                ),
+            )),
+            (AttributeType::Text, _) => errors.push(FieldError::new(
+                &field,
+                "invalid",
+                format!("{} must be text.", definition.key),
+            )),
+        }
+    }
+    errors
+}
+
+/// Full product pipeline: field rules, SKU uniqueness within the store,
+/// category existence and the category's attributes. Returns the validated
+/// sale on success.
+pub async fn validate_product(
+    db: &DatabaseConnection,
+    input: &ProductInput<'_>,
+    now: DateTime<Utc>,
+) -> Result<Option<Sale>, Vec<FieldError>> {
+    let mut errors = product_field_errors(input, now);
+
+    if let (Some(store_id), Some(sku)) = (input.store_id, non_blank(input.sku)) {
+        match Product::sku_taken(db, store_id, sku, input.product_id).await {
+            Ok(true) => errors.push(FieldError::new("sku", "taken", SKU_TAKEN)),
+            Ok(false) => {}
+            Err(e) => errors.push(FieldError::new("sku", "invalid", e)),
+        }
+    }
+
+    match input.category_id {
+        Some(category_id) => match Category::exists(db, category_id).await {
+            Ok(true) => match CategoryAttributes::for_category(db, category_id).await {
+                Ok(definitions) => {
+                    let definitions: Vec<AttributeDefinition> =
+                        definitions.iter().map(AttributeDefinition::from).collect();
+                    errors.extend(attribute_errors(&definitions, input.attributes));
+                }
+                Err(e) => errors.push(FieldError::new("attributes", "invalid", e)),
+            },
+            Ok(false) => errors.push(FieldError::new(
+                "category_id",
+                "not_found",
+                "Category does not exist.",
+            )),
+            Err(e) => errors.push(FieldError::new("category_id", "invalid", e)),
+        },
+        None => errors.extend(attribute_errors(&[], input.attributes)),
+    }
+
+    // Options left out come from the store, which may leave no way to get
+    // the product to the buyer
+    if let (Some(store_id), Some(options), true) =
+        (input.store_id, input.delivery_options, errors.is_empty())
+    {
+        match Store::get(db, store_id).await {
+            Ok(store) => {
+                let filled = options.fill(DeliveryOptions::store_default(&store));
+                if !filled.pickup_available && !filled.delivery_available {
+                    errors.push(FieldError::new(
+                        "delivery_options",
+                        "invalid",
+                        "The store offers neither pickup nor delivery by default; enable one for this product.",
+                    ));
+                }
+            }
+            Err(e) => errors.push(FieldError::new("store_id", "not_found", e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    sale(input.price, input.sale_price, input.sale_ends_at, now).map_err(|e| vec![e])
+}
+
+/// Store field rules
+pub fn store_field_errors(input: &StoreInput<'_>) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    required_text(&mut errors, "name", input.name, MAX_NAME_LEN);
+    if let Some(logo_url) = non_blank(input.logo_url) {
+        max_len(&mut errors, "logo_url", logo_url, MAX_URL_LEN);
+        if !["https://", "http://", "/"]
+            .iter()
+            .any(|prefix| logo_url.starts_with(prefix))
+        {
+            errors.push(FieldError::new(
+                "logo_url",
+                "invalid",
+                "logo_url must be an http(s) URL or a path on this server.",
+            ));
+        }
+    }
+    if let Some(location) = input.location {
+        max_len(&mut errors, "location", location, MAX_LOCATION_LEN);
+    }
+    if let Some(email) = non_blank(input.contact_email) {
+        max_len(&mut errors, "contact_email", email, MAX_EMAIL_LEN);
+        if !is_plausible_email(email) {
+            errors.push(FieldError::new(
+                "contact_email",
+                "invalid",
+                "contact_email is not a valid email address.",
+            ));
+        }
+    }
+    for (field, value) in [
+        ("contact_phone", input.contact_phone),
+        ("contact_whatsapp", input.contact_whatsapp),
+    ] {
+        if let Some(phone) = non_blank(value) {
+            max_len(&mut errors, field, phone, MAX_PHONE_LEN);
+            if !is_plausible_phone(phone) {
+                errors.push(FieldError::new(
+                    field,
+                    "invalid",
+                    format!("{field} must be a phone number, e.g. +237 6 12 34 56 78."),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Full store pipeline; stores have no cross-row rules yet
+pub fn validate_store(input: &StoreInput<'_>) -> Result<(), Vec<FieldError>> {
+    let errors = store_field_errors(input);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Bundle field rules: a name, a price and 1 to 20 distinct components
+pub fn bundle_field_errors(
+    name: &str,
+    price: f64,
+    components: &[BundleComponent],
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    required_text(&mut errors, "name", name, MAX_NAME_LEN);
+    if let Some(problem) = price_error("price", price) {
+        errors.push(FieldError::new("price", "out_of_range", problem));
+    }
+    if !(1..=MAX_BUNDLE_ITEMS).contains(&components.len()) {
+        errors.push(FieldError::new(
+            "items",
+            "out_of_range",
+            format!("A bundle needs between 1 and {MAX_BUNDLE_ITEMS} items."),
+        ));
+    }
+    if components.iter().any(|c| c.quantity < 1) {
+        errors.push(FieldError::new(
+            "items",
+            "out_of_range",
+            "Each item quantity must be 1 or more.",
+        ));
+    }
+    let mut seen = HashSet::new();
+    if !components.iter().all(|c| seen.insert(c.product_id)) {
+        errors.push(FieldError::new(
+            "items",
+            "invalid",
+            "A product can appear only once; raise its quantity instead.",
+        ));
+    }
+    errors
+}
+
+/// Full bundle pipeline: field rules, and every component must be a product
+/// of the same store
+pub async fn validate_bundle(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    name: &str,
+    price: f64,
+    components: &[BundleComponent],
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = bundle_field_errors(name, price, components);
+    let ids: Vec<Uuid> = components.iter().map(|c| c.product_id).collect();
+    match Bundle::foreign_products(db, store_id, &ids).await {
+        Ok(foreign) if foreign.is_empty() => {}
+        Ok(foreign) => errors.push(FieldError::new(
+            "items",
+            "not_found",
+            format!(
+                "Not products of this store: {}",
+                foreign
+                    .iter()
+                    .map(Uuid::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )),
+        Err(e) => errors.push(FieldError::new("items", "invalid", e)),
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn non_blank(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn required_text(errors: &mut Vec<FieldError>, field: &str, value: &str, max: usize) {
+    if value.trim().is_empty() {
+        errors.push(FieldError::new(
+            field,
+            "required",
+            format!("{field} is required."),
+        ));
+    } else {
+        max_len(errors, field, value, max);
+    }
+}
+
+fn max_len(errors: &mut Vec<FieldError>, field: &str, value: &str, max: usize) {
+    if value.chars().count() > max {
+        errors.push(FieldError::new(
+            field,
+            "too_long",
+            format!("{field} must be at most {max} characters."),
+        ));
+    }
+}
+
+fn is_plausible_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+fn is_plausible_phone(phone: &str) -> bool {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    let allowed = phone.chars().enumerate().all(|(i, c)| {
+        c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')') || (c == '+' && i == 0)
+    });
+    allowed && (6..=15).contains(&digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn product<'a>() -> ProductInput<'a> {
+        ProductInput {
+            name: "Ndole spice mix",
+            price: 1500.0,
+            quantity_available: 10,
+            ..Default::default()
+        }
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_product_has_no_field_errors() {
+        assert!(product_field_errors(&product(), Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn numeric_fields_are_refused_not_coerced() {
+        let body = serde_json::json!({
+            "price": "1500",
+            "quantity_available": 4_294_967_293_i64,
+            "low_stock_threshold": u64::MAX,
+            "return_window_days": 7.5,
+            "sale_price": null,
+            "ok": 12,
+        });
+        let refused = |field: &str| match field {
+            "price" => number_field(&body, field).unwrap_err(),
+            _ => integer_field(&body, field).unwrap_err(),
+        };
+        for (field, code) in [
+            ("price", "invalid"),
+            ("quantity_available", "out_of_range"),
+            ("low_stock_threshold", "out_of_range"),
+            ("return_window_days", "invalid"),
+        ] {
+            let error = refused(field);
+            assert_eq!((error.field.as_str(), error.code.as_str()), (field, code));
+        }
+        assert_eq!(number_field(&body, "sale_price"), Ok(None));
+        assert_eq!(number_field(&body, "missing"), Ok(None));
+        assert_eq!(integer_field(&body, "ok"), Ok(Some(12)));
+        assert_eq!(integer_field(&body, "price").unwrap_err().field, "price");
+    }
+
+    #[test]
+    fn bundle_field_rules() {
+        let phone = Uuid::new_v4();
+        let item = |quantity| BundleComponent {
+            product_id: phone,
+            quantity,
+        };
+        assert!(bundle_field_errors("Phone + case", 50000.0, &[item(1)]).is_empty());
+
+        let errors = bundle_field_errors(" ", -1.0, &[]);
+        assert_eq!(fields(&errors), vec!["name", "price", "items"]);
+        let errors = bundle_field_errors("Phone + case", 50000.0, &[item(1), item(0)]);
+        let codes: Vec<&str> = errors.iter().map(|e| e.code.as_str()).collect();
+        assert_eq!(codes, vec!["out_of_range", "invalid"]);
+    }
+
+    #[test]
+    fn product_field_rules() {
+        let now = Utc::now();
+        let long_sku = "x".repeat(MAX_SKU_LEN + 1);
+        let input = ProductInput {
+            name: "  ",
+            sku: Some(&long_sku),
+            price: -1.0,
+            quantity_available: -3,
+            low_stock_threshold: Some(-1),
+            publish_at: Some(now - Duration::minutes(5)),
+            ..product()
+        };
+        let errors = product_field_errors(&input, now);
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "name",
+                "sku",
+                "price",
+                "quantity_available",
+                "low_stock_threshold",
+                "publish_at"
+            ]
+        );
+        assert_eq!(errors[0].code, "required");
+        assert_eq!(errors[1].code, "too_long");
+        assert_eq!(errors[2].message, "price must not be negative");
+        assert_eq!(errors[3].message, "quantity_available must not be negative");
+    }
+
+    #[test]
+    fn price_must_be_finite_and_below_the_cap() {
+        let message = |price| {
+            let input = ProductInput { price, ..product() };
+            let errors = product_field_errors(&input, Utc::now());
+            assert_eq!(fields(&errors), vec!["price"]);
+            errors[0].message.clone()
+        };
+        assert_eq!(message(f64::NAN), "price must be a finite number");
+        assert_eq!(message(f64::NEG_INFINITY), "price must be a finite number");
+        assert_eq!(message(100_000_001.0), "price must be at most 100000000");
+        let at_cap = ProductInput {
+            price: 100_000_000.0,
+            ..product()
+        };
+        assert!(product_field_errors(&at_cap, Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn currency_must_be_iso_4217() {
+        let errors = |currency| {
+            let input = ProductInput {
+                currency,
+                ..product()
+            };
+            product_field_errors(&input, Utc::now())
+        };
+        assert!(errors(None).is_empty());
+        assert!(errors(Some("NGN")).is_empty());
+        for code in ["ngn", "FCFA", "ABC"] {
+            let errors = errors(Some(code));
+            assert_eq!(fields(&errors), vec!["currency"], "{code}");
+            assert_eq!(
+                errors[0].message,
+                "currency must be an ISO 4217 code such as XAF, NGN or USD"
+            );
+        }
+    }
+
+    #[test]
+    fn delivery_option_rules() {
+        let none = DeliveryOptionsInput {
+            pickup_available: Some(false),
+            delivery_available: Some(false),
+            delivery_fee_override: Some(-500.0),
+            estimated_days: Some(MAX_ESTIMATED_DAYS + 1),
+        };
+        let errors = delivery_option_errors("delivery_options", &none);
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "delivery_options",
+                "delivery_options.delivery_fee_override",
+                "delivery_options.estimated_days"
+            ]
+        );
+        let partial = DeliveryOptionsInput {
+            delivery_available: Some(true),
+            estimated_days: Some(3),
+            ..Default::default()
+        };
+        assert!(delivery_option_errors("delivery_options", &partial).is_empty());
+    }
+
+    #[test]
+    fn sale_rules() {
+        let now = Utc::now();
+        let later = now + Duration::days(2);
+        assert_eq!(sale(100.0, None, None, now), Ok(None));
+        assert_eq!(
+            sale(100.0, Some(80.0), Some(later), now),
+            Ok(Some(Sale {
+                price: 80.0,
+                ends_at: later
+            }))
+        );
+        let field = |r: Result<Option<Sale>, FieldError>| r.unwrap_err().field;
+        assert_eq!(
+            field(sale(100.0, Some(100.0), Some(later), now)),
+            "sale_price"
+        );
+        assert_eq!(
+            field(sale(100.0, Some(-1.0), Some(later), now)),
+            "sale_price"
+        );
+        assert_eq!(
+            field(sale(100.0, Some(80.0), Some(now - Duration::hours(1)), now)),
+            "sale_ends_at"
+        );
+        assert_eq!(field(sale(100.0, Some(80.0), None, now)), "sale_ends_at");
+        assert_eq!(field(sale(100.0, None, Some(later), now)), "sale_price");
+    }
+
+    #[test]
+    fn return_policy_rules() {
+        assert!(return_policy_errors(Some(true), Some(0), Some("Unused only")).is_empty());
+        assert!(return_policy_errors(Some(true), Some(90), None).is_empty());
+        assert!(return_policy_errors(None, None, None).is_empty());
+
+        let codes = |errors: Vec<FieldError>| -> Vec<(String, String)> {
+            errors.into_iter().map(|e| (e.field, e.code)).collect()
+        };
+        let pair = |field: &str, code: &str| (field.to_string(), code.to_string());
+        assert_eq!(
+            codes(return_policy_errors(Some(true), Some(91), None)),
+            vec![pair("return_window_days", "out_of_range")]
+        );
+        assert_eq!(
+            codes(return_policy_errors(Some(true), Some(-1), None)),
+            vec![pair("return_window_days", "out_of_range")]
+        );
+        assert_eq!(
+            codes(return_policy_errors(Some(false), Some(7), None)),
+            vec![pair("return_window_days", "invalid")]
+        );
+        let long = "x".repeat(MAX_RETURN_CONDITIONS_LEN + 1);
+        assert_eq!(
+            codes(return_policy_errors(None, None, Some(&long))),
+            vec![
+                pair("returns_accepted", "required"),
+                pair("return_conditions", "too_long")
+            ]
+        );
+    }
+
+    #[test]
+    fn store_field_rules() {
+        let ok = StoreInput {
+            name: "Mama Ngono",
+            logo_url: Some("https://cdn.example.com/logo.png"),
+            contact_email: Some("shop@example.cm"),
+            contact_whatsapp: Some("+237 6 12 34 56 78"),
+            ..Default::default()
+        };
+        assert!(validate_store(&ok).is_ok());
+
+        let bad = StoreInput {
+            name: "",
+            logo_url: Some("ftp://example.com/logo.png"),
+            contact_email: Some("not-an-email"),
+            contact_phone: Some("call me"),
+            contact_whatsapp: Some("+237-6"),
+            ..Default::default()
+        };
+        let errors = validate_store(&bad).unwrap_err();
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "name",
+                "logo_url",
+                "contact_email",
+                "contact_phone",
+                "contact_whatsapp"
+            ]
+        );
+    }
+
+    #[test]
+    fn report_valid_flag_follows_errors() {
+        assert!(ValidationReport::from(vec![]).valid);
+        let report = ValidationReport::from(vec![FieldError::new("name", "required", "x")]);
+        assert!(!report.valid);
+    }
+
+    fn definition(key: &str, attribute_type: AttributeType, required: bool) -> AttributeDefinition {
+        AttributeDefinition {
+            key: key.to_string(),
+            attribute_type,
+            allowed_values: match attribute_type {
+                AttributeType::Enum => vec!["new".to_string(), "used".to_string()],
+                _ => Vec::new(),
+            },
+            required,
+        }
+    }
+
+    #[test]
+    fn attribute_rules_per_type() {
+        let definitions = [
+            definition("brand", AttributeType::Text, false),
+            definition("condition", AttributeType::Enum, true),
+            definition("ram", AttributeType::Number, false),
+        ];
+        let ok = serde_json::json!({"brand": "Tecno", "condition": "used", "ram": 8});
+        assert!(attribute_errors(&definitions, Some(&ok)).is_empty());
+        // Optional attributes may be left out or null
+        let ok = serde_json::json!({"condition": "new", "ram": null});
+        assert!(attribute_errors(&definitions, Some(&ok)).is_empty());
+
+        let bad = serde_json::json!({
+            "brand": "x".repeat(MAX_ATTRIBUTE_TEXT_LEN + 1),
+            "condition": "refurbished",
+            "ram": "8",
+            "colour": "red"
+        });
+        let errors = attribute_errors(&definitions, Some(&bad));
+        let codes: Vec<(&str, &str)> = errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.code.as_str()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("attributes.colour", "unknown"),
+                ("attributes.brand", "too_long"),
+                ("attributes.condition", "invalid"),
+                ("attributes.ram", "invalid"),
+            ]
+        );
+
+        let errors = attribute_errors(&definitions, None);
+        assert_eq!(fields(&errors), vec!["attributes.condition"]);
+        assert_eq!(errors[0].code, "required");
+        let errors = attribute_errors(&definitions, Some(&serde_json::json!(["used"])));
+        assert_eq!(fields(&errors), vec!["attributes"]);
+    }
+
+    #[tokio::test]
+    async fn category_change_invalidates_stale_attributes() {
+        let db = crate::db::testing::sqlite().await;
+        let phones = crate::db::testing::seed_category(&db, "phones").await;
+        let fabrics = crate::db::testing::seed_category(&db, "fabrics").await;
+        CategoryAttributes::define(&db, phones, &definition("ram", AttributeType::Number, true))
+            .await
+            .unwrap();
+        CategoryAttributes::define(
+            &db,
+            fabrics,
+            &definition("length_m", AttributeType::Number, true),
+        )
+        .await
+        .unwrap();
+
+        let attributes = serde_json::json!({"ram": 8});
+        let phone = ProductInput {
+            category_id: Some(phones),
+            attributes: Some(&attributes),
+            ..product()
+        };
+        assert!(validate_product(&db, &phone, Utc::now()).await.is_ok());
+
+        // Moved to another category, the phone's attributes no longer fit
+        let moved = ProductInput {
+            category_id: Some(fabrics),
+            ..phone.clone()
+        };
+        let errors = validate_product(&db, &moved, Utc::now()).await.unwrap_err();
+        assert_eq!(
+            fields(&errors),
+            vec!["attributes.ram", "attributes.length_m"]
+        );
+        assert_eq!(errors[0].code, "unknown");
+        assert_eq!(errors[1].code, "required");
+
+        // Without a category no attributes are allowed
+        let uncategorized = ProductInput {
+            category_id: None,
+            ..phone
+        };
+        let errors = validate_product(&db, &uncategorized, Utc::now())
+            .await
+            .unwrap_err();
+        assert_eq!(fields(&errors), vec!["attributes.ram"]);
+    }
+}
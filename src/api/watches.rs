@@ -0,0 +1,192 @@
+//! Buyers watching products for price drops; see `crate::price_alerts`
+
+use crate::api::extract::UuidPath;
+use crate::auth::claims_from_headers;
+use crate::db::products::Product;
+use crate::db::watches::ProductWatch;
+use crate::entity::product_watch::Model as WatchModel;
+use crate::money::{Locale, LocaleParams, Money};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct WatchProductRequest {
+    /// Only alert at or below this price; any drop when omitted
+    pub target_price: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WatchResponse {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub target_price: Option<f64>,
+    pub last_notified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<WatchModel> for WatchResponse {
+    fn from(watch: WatchModel) -> Self {
+        Self {
+            product_id: watch.product_id,
+            target_price: watch.target_price,
+            last_notified_at: watch.last_notified_at,
+            created_at: watch.created_at,
+        }
+    }
+}
+
+/// A watch with the product's current name and price
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WatchedProduct {
+    #[serde(flatten)]
+    pub watch: WatchResponse,
+    pub name: String,
+    #[schema(deprecated)]
+    pub price: f64,
+    pub price_money: Money,
+    /// `target_price` in the product's currency
+    pub target_price_money: Option<Money>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WatchListResponse {
+    pub watches: Vec<WatchedProduct>,
+}
+
+fn watcher_id(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    claims_from_headers(headers)
+        .map(|claims| claims.relay_id)
+        .ok_or_else(|| {
+            (
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid Authorization token".to_string(),
+            )
+        })
+}
+
+/// Watch a product for price drops
+#[utoipa::path(
+    post,
+    operation_id = "watchProduct",
+    path = "/api/v1/products/{id}/watch",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    request_body = WatchProductRequest,
+    responses(
+        (status = 200, description = "Watching; a repeat call replaces the target", body = WatchResponse),
+        (status = 400, description = "Target price is not positive"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 404, description = "Product not found")
+    )
+)]
+pub async fn watch_product(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<WatchProductRequest>,
+) -> impl IntoResponse {
+    let watcher = match watcher_id(&headers) {
+        Ok(watcher) => watcher,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if payload
+        .target_price
+        .is_some_and(|target| !target.is_finite() || target <= 0.0)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            "target_price must be greater than zero",
+        )
+            .into_response();
+    }
+    if let Err(e) = Product::get_visible(&db, id).await {
+        return (StatusCode::NOT_FOUND, e).into_response();
+    }
+    match ProductWatch::watch(&db, id, &watcher, payload.target_price).await {
+        Ok(watch) => Json(WatchResponse::from(watch)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Stop watching a product
+#[utoipa::path(
+    delete,
+    operation_id = "unwatchProduct",
+    path = "/api/v1/products/{id}/watch",
+    tag = "Products",
+    params(
+        ("id" = String, Path, description = "Product ID", format = "uuid")
+    ),
+    responses(
+        (status = 204, description = "No longer watching"),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 404, description = "Not watching this product")
+    )
+)]
+pub async fn unwatch_product(
+    State(db): State<DatabaseConnection>,
+    UuidPath(id): UuidPath<Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let watcher = match watcher_id(&headers) {
+        Ok(watcher) => watcher,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match ProductWatch::unwatch(&db, id, &watcher).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Not watching this product").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// Products the caller watches, newest first
+#[utoipa::path(
+    get,
+    operation_id = "listMyWatches",
+    path = "/api/v1/users/me/watches",
+    tag = "Products",
+    params(LocaleParams),
+    responses(
+        (status = 200, description = "Watched products with their current prices", body = WatchListResponse),
+        (status = 401, description = "Missing or invalid Authorization token")
+    )
+)]
+pub async fn list_my_watches(
+    State(db): State<DatabaseConnection>,
+    locale: Locale,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let watcher = match watcher_id(&headers) {
+        Ok(watcher) => watcher,
+        Err(rejection) => return rejection.into_response(),
+    };
+    match ProductWatch::list_for_watcher(&db, &watcher).await {
+        Ok(rows) => Json(WatchListResponse {
+            watches: rows
+                .into_iter()
+                .map(|(watch, product)| WatchedProduct {
+                    price_money: Money::new(product.price, &product.currency, locale),
+                    target_price_money: watch
+                        .target_price
+                        .map(|target| Money::new(target, &product.currency, locale)),
+                    watch: watch.into(),
+                    name: product.name,
+                    price: product.price,
+                })
+                .collect(),
+        })
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
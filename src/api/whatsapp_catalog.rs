@@ -0,0 +1,459 @@
+//! A store's catalog for sharing on WhatsApp, where many sellers really sell.
+//!
+//! The text format is a block to paste into a chat or status: a store header,
+//! then one `name – price – link` line per product. The CSV format follows the
+//! WhatsApp Business catalog import. Both are streamed a page at a time.
+
+use crate::api::extract::UuidPath;
+use crate::api::products::media_url;
+use crate::api::stores::owned_store;
+use crate::auth::ApiScope;
+use crate::config::SiteConfig;
+use crate::db::products::{active_sale, CatalogFilter, Product};
+use crate::entity::product::Model as ProductModel;
+use crate::links::{ProductLink, StoreLink};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Rows fetched per query while streaming
+const CHUNK: u64 = 500;
+/// WhatsApp Business catalog limits on item names and descriptions, in characters
+pub const NAME_LIMIT: usize = 150;
+pub const DESCRIPTION_LIMIT: usize = 5000;
+
+const CSV_HEADER: &str = "id,name,description,price,sale_price,currency,image_url,link\n";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogFormat {
+    /// Text block to paste into a chat
+    #[default]
+    Text,
+    /// WhatsApp Business catalog import
+    Csv,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct WhatsappCatalogQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub format: CatalogFormat,
+    /// Also list scheduled and unpublished products
+    #[serde(default)]
+    pub include_unpublished: bool,
+    #[serde(default)]
+    pub include_out_of_stock: bool,
+}
+
+/// Whole amounts without decimals, as XAF has no minor unit
+pub fn format_amount(amount: f64) -> String {
+    if amount.fract() == 0.0 {
+        format!("{amount:.0}")
+    } else {
+        format!("{amount:.2}")
+    }
+}
+
+/// First `limit` characters of `text`
+pub fn truncate_chars(text: &str, limit: usize) -> &str {
+    match text.char_indices().nth(limit) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Quote a CSV field when it holds a separator, a quote or a line break
+pub fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+fn text_header(name: &str, description: Option<&str>, link: &str) -> String {
+    let mut out = format!("*{}*\n", name.trim());
+    if let Some(description) = description.map(str::trim).filter(|d| !d.is_empty()) {
+        let _ = writeln!(out, "{description}");
+    }
+    let _ = writeln!(out, "{link}\n");
+    out
+}
+
+/// `name – price – link`, with a running sale shown as WhatsApp strikethrough
+fn text_line(out: &mut String, site: &SiteConfig, product: &ProductModel, now: DateTime<Utc>) {
+    let price = match active_sale(product, now) {
+        Some(sale) => format!(
+            "~{}~ {} {}",
+            format_amount(product.price),
+            format_amount(sale.price),
+            product.currency
+        ),
+        None => format!("{} {}", format_amount(product.price), product.currency),
+    };
+    let _ = writeln!(
+        out,
+        "{} – {} – {}",
+        product.name.trim(),
+        price,
+        ProductLink::new(&site.public_base_url, product.id)
+    );
+}
+
+fn csv_row(out: &mut String, site: &SiteConfig, product: &ProductModel, now: DateTime<Utc>) {
+    let image = product
+        .image_id
+        .map(|id| format!("{}{}", site.public_base_url, media_url(id)))
+        .unwrap_or_default();
+    let sale = active_sale(product, now)
+        .map(|sale| format_amount(sale.price))
+        .unwrap_or_default();
+    let description = product.description.as_deref().unwrap_or_default();
+    let _ = writeln!(
+        out,
+        "{},{},{},{},{},{},{},{}",
+        product.id,
+        csv_field(truncate_chars(product.name.trim(), NAME_LIMIT)),
+        csv_field(truncate_chars(description.trim(), DESCRIPTION_LIMIT)),
+        format_amount(product.price),
+        sale,
+        product.currency,
+        csv_field(&image),
+        csv_field(&ProductLink::new(&site.public_base_url, product.id).to_string()),
+    );
+}
+
+/// Export a store's products for WhatsApp
+#[utoipa::path(
+    get,
+    operation_id = "whatsappCatalog",
+    path = "/api/v1/stores/{id}/whatsapp-catalog",
+    tag = "Stores",
+    params(
+        ("id" = String, Path, description = "Store ID", format = "uuid"),
+        WhatsappCatalogQuery
+    ),
+    responses(
+        (status = 200, description = "Text block, or CSV for WhatsApp Business catalog import", content_type = ["text/plain", "text/csv"]),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Caller does not own the store or the key lacks products:read"),
+        (status = 404, description = "Store not found")
+    )
+)]
+pub async fn whatsapp_catalog(
+    State(db): State<DatabaseConnection>,
+    State(site): State<Arc<SiteConfig>>,
+    UuidPath(id): UuidPath<Uuid>,
+    Query(query): Query<WhatsappCatalogQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let store = match owned_store(&db, &headers, id, ApiScope::ProductsRead).await {
+        Ok(store) => store,
+        Err(err) => return err.into_response(),
+    };
+    let filter = CatalogFilter {
+        include_unpublished: query.include_unpublished,
+        include_out_of_stock: query.include_out_of_stock,
+    };
+    let format = query.format;
+    let head = match format {
+        CatalogFormat::Text => text_header(
+            &store.name,
+            store.description.as_deref(),
+            &StoreLink::new(&site.public_base_url, store.id).to_string(),
+        ),
+        CatalogFormat::Csv => CSV_HEADER.to_string(),
+    };
+
+    let now = Utc::now();
+    let rows = stream::unfold(Some(0), move |offset: Option<u64>| {
+        let (db, site) = (db.clone(), site.clone());
+        async move {
+            let offset = offset?;
+            match Product::catalog_page(&db, id, filter, now, offset, CHUNK).await {
+                Ok(products) if products.is_empty() => None,
+                Ok(products) => {
+                    let mut out = String::new();
+                    for product in &products {
+                        match format {
+                            CatalogFormat::Text => text_line(&mut out, &site, product, now),
+                            CatalogFormat::Csv => csv_row(&mut out, &site, product, now),
+                        }
+                    }
+                    Some((Ok(out), Some(offset + products.len() as u64)))
+                }
+                Err(e) => Some((Err(std::io::Error::other(e)), None)),
+            }
+        }
+    });
+    let body = Body::from_stream(stream::once(async { Ok(head) }).chain(rows));
+    match format {
+        CatalogFormat::Text => {
+            ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+        }
+        CatalogFormat::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"whatsapp-catalog.csv\"",
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::{product, store};
+    use axum::{
+        extract::FromRef,
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use chrono::Duration;
+    use sea_orm::{EntityTrait, Set};
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct TestState {
+        db: DatabaseConnection,
+        site: Arc<SiteConfig>,
+    }
+
+    impl FromRef<TestState> for DatabaseConnection {
+        fn from_ref(state: &TestState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<TestState> for Arc<SiteConfig> {
+        fn from_ref(state: &TestState) -> Self {
+            state.site.clone()
+        }
+    }
+
+    async fn fetch(db: &DatabaseConnection, path: &str, caller: &str) -> (HeaderMap, String) {
+        let app = Router::new()
+            .route("/stores/:id/whatsapp-catalog", get(whatsapp_catalog))
+            .with_state(TestState {
+                db: db.clone(),
+                site: Arc::new(SiteConfig {
+                    public_base_url: "https://transac.site".to_string(),
+                    currency: "XAF".to_string(),
+                }),
+            });
+        let token = crate::auth::JwtService::new()
+            .unwrap()
+            .generate_token_with_role(caller.into(), String::new(), "seller".into())
+            .unwrap();
+        let request = Request::builder()
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        (headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    struct Seeded {
+        store: Uuid,
+        kaba: Uuid,
+        wax: Uuid,
+        sold_out: Uuid,
+        image: Uuid,
+    }
+
+    async fn seed_item(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+        price: f64,
+        quantity: i32,
+        tweak: impl FnOnce(&mut product::ActiveModel),
+    ) -> Uuid {
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let mut item = product::ActiveModel {
+            id: Set(id),
+            store_id: Set(store_id),
+            tenant_id: Set("default".to_string()),
+            sku: Set(None),
+            name: Set(name.to_string()),
+            description: Set(description.map(str::to_string)),
+            price: Set(price),
+            currency: Set("XAF".to_string()),
+            sale_price: Set(None),
+            sale_ends_at: Set(None),
+            quantity_available: Set(quantity),
+            low_stock_threshold: Set(None),
+            image_id: Set(None),
+            category_id: Set(None),
+            attributes: Set(serde_json::json!({})),
+            return_policy: Set(None),
+            return_policy_source: Set("none".to_string()),
+            returns_accepted: Set(false),
+            return_window_days: Set(None),
+            return_conditions: Set(None),
+            pickup_available: Set(true),
+            delivery_available: Set(false),
+            delivery_fee_override: Set(None),
+            delivery_estimated_days: Set(None),
+            delivery_options_source: Set("store_default".to_string()),
+            publish_at: Set(None),
+            is_published: Set(true),
+            archived_at: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        tweak(&mut item);
+        product::Entity::insert(item)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        id
+    }
+
+    async fn seed(db: &DatabaseConnection) -> Seeded {
+        let store_id = testing::seed_store(db, "seller-1").await;
+        store::Entity::update_many()
+            .col_expr(
+                store::Column::Description,
+                sea_orm::sea_query::Expr::value("Wax prints from Douala"),
+            )
+            .exec(db)
+            .await
+            .unwrap();
+        let image = Uuid::new_v4();
+        let kaba = seed_item(
+            db,
+            store_id,
+            "Kaba, long",
+            Some("Cotton \"kaba\" dress\nhand-sewn"),
+            12500.5,
+            2,
+            |item| item.image_id = Set(Some(image)),
+        )
+        .await;
+        let wax = seed_item(db, store_id, "Wax print", None, 5000.0, 3, |item| {
+            item.sale_price = Set(Some(4000.0));
+            item.sale_ends_at = Set(Some(Utc::now() + Duration::days(2)));
+        })
+        .await;
+        let sold_out = seed_item(db, store_id, "Headwrap", None, 1500.0, 0, |_| {}).await;
+        seed_item(db, store_id, "Next season", None, 9000.0, 5, |item| {
+            item.publish_at = Set(Some(Utc::now() + Duration::days(7)));
+            item.is_published = Set(false);
+        })
+        .await;
+        Seeded {
+            store: store_id,
+            kaba,
+            wax,
+            sold_out,
+            image,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_block_lists_published_in_stock_products() {
+        let db = testing::sqlite().await;
+        let s = seed(&db).await;
+
+        let (headers, text) = fetch(
+            &db,
+            &format!("/stores/{}/whatsapp-catalog", s.store),
+            "seller-1",
+        )
+        .await;
+        assert!(headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        assert_eq!(
+            text,
+            format!(
+                "*Mama Ngono*\n\
+                 Wax prints from Douala\n\
+                 https://transac.site/store/{}\n\
+                 \n\
+                 Kaba, long – 12500.50 XAF – https://transac.site/product/{}\n\
+                 Wax print – ~5000~ 4000 XAF – https://transac.site/product/{}\n",
+                s.store, s.kaba, s.wax
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_csv_export_with_out_of_stock_override() {
+        let db = testing::sqlite().await;
+        let s = seed(&db).await;
+
+        let (headers, csv) = fetch(
+            &db,
+            &format!(
+                "/stores/{}/whatsapp-catalog?format=csv&include_out_of_stock=true",
+                s.store
+            ),
+            "seller-1",
+        )
+        .await;
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"whatsapp-catalog.csv\""
+        );
+        assert_eq!(
+            csv,
+            format!(
+                "{CSV_HEADER}\
+                 {sold_out},Headwrap,,1500,,XAF,,https://transac.site/product/{sold_out}\n\
+                 {kaba},\"Kaba, long\",\"Cotton \"\"kaba\"\" dress\nhand-sewn\",12500.50,,XAF,\
+                 https://transac.site/api/v1/media/{image},https://transac.site/product/{kaba}\n\
+                 {wax},Wax print,,5000,4000,XAF,,https://transac.site/product/{wax}\n",
+                sold_out = s.sold_out,
+                kaba = s.kaba,
+                wax = s.wax,
+                image = s.image,
+            )
+        );
+    }
+
+    #[test]
+    fn test_fields_fit_whatsapp_limits() {
+        let long = "é".repeat(NAME_LIMIT + 10);
+        assert_eq!(
+            truncate_chars(&long, NAME_LIMIT).chars().count(),
+            NAME_LIMIT
+        );
+        assert_eq!(truncate_chars("Kaba", NAME_LIMIT), "Kaba");
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(format_amount(5000.0), "5000");
+        assert_eq!(format_amount(99.5), "99.50");
+    }
+}
@@ -0,0 +1,256 @@
+use super::Claims;
+use crate::db::api_keys::ApiKey;
+use crate::db::stores::Store;
+use crate::entity::store_api_key::Model as ApiKeyModel;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::Rng;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Header carrying a store API key, accepted instead of a bearer JWT
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+const API_KEY_PREFIX: &str = "tk_";
+
+/// What an API key may do on its store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ApiScope {
+    #[serde(rename = "products:read")]
+    ProductsRead,
+    #[serde(rename = "products:write")]
+    ProductsWrite,
+    #[serde(rename = "stores:write")]
+    StoresWrite,
+}
+
+impl ApiScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::ProductsRead => "products:read",
+            ApiScope::ProductsWrite => "products:write",
+            ApiScope::StoresWrite => "stores:write",
+        }
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "products:read" => Ok(ApiScope::ProductsRead),
+            "products:write" => Ok(ApiScope::ProductsWrite),
+            "stores:write" => Ok(ApiScope::StoresWrite),
+            other => Err(format!("Unknown API key scope: {other}")),
+        }
+    }
+}
+
+/// Fresh plaintext key; shown to the seller once and never stored
+pub fn generate_api_key() -> String {
+    let mut rng = rand::rng();
+    let random_bytes: Vec<u8> = (0..32).map(|_| rng.random::<u8>()).collect();
+    format!("{API_KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(random_bytes))
+}
+
+/// Hex SHA-256 of a key, the only form kept at rest
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// What a valid, non-revoked key grants
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyGrant {
+    pub key_id: Uuid,
+    pub store_id: Uuid,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKeyGrant {
+    /// `None` for revoked keys
+    pub fn from_key(key: &ApiKeyModel) -> Option<Self> {
+        if key.revoked {
+            return None;
+        }
+        Some(Self {
+            key_id: key.id,
+            store_id: key.store_id,
+            // Scopes were validated on creation; skip anything unknown
+            scopes: key
+                .scopes
+                .split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .collect(),
+        })
+    }
+
+    pub fn allows(&self, scope: ApiScope, store_id: Uuid) -> bool {
+        self.store_id == store_id && self.scopes.contains(&scope)
+    }
+}
+
+/// Authenticated caller, from a bearer JWT or a store API key
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub claims: Claims,
+    /// Set when the caller used an API key; limits what `claims` may do
+    pub api_key: Option<ApiKeyGrant>,
+}
+
+impl Principal {
+    /// Bearer tokens carry the owner's full rights; API keys only their
+    /// scopes, and only on their own store
+    pub fn allows(&self, scope: ApiScope, store_id: Uuid) -> bool {
+        self.api_key
+            .as_ref()
+            .is_none_or(|grant| grant.allows(scope, store_id))
+    }
+
+    /// Who to credit a change to: the device, or the API key it went through
+    pub fn actor(&self) -> String {
+        match &self.api_key {
+            Some(grant) => format!("api_key:{}", grant.key_id),
+            None => self.claims.relay_id.clone(),
+        }
+    }
+}
+
+/// Look up a presented key; unknown and revoked keys are rejected
+pub async fn verify_api_key(
+    db: &DatabaseConnection,
+    raw_key: &HeaderValue,
+) -> Result<ApiKeyGrant, (StatusCode, &'static str)> {
+    let invalid = (StatusCode::UNAUTHORIZED, "Invalid or revoked API key");
+    let key = raw_key.to_str().map_err(|_| invalid)?;
+    ApiKey::find_by_hash(db, &hash_api_key(key))
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to verify API key",
+            )
+        })?
+        .as_ref()
+        .and_then(ApiKeyGrant::from_key)
+        .ok_or(invalid)
+}
+
+/// Resolve the caller from `X-Api-Key` or `Authorization: Bearer`.
+///
+/// An API key acts as the owner of its store with the key's scopes. A key that
+/// is present but unknown or revoked is rejected outright rather than treated
+/// as anonymous.
+pub async fn authenticate(
+    db: &DatabaseConnection,
+    headers: &HeaderMap,
+) -> Result<Option<Principal>, (StatusCode, &'static str)> {
+    let Some(raw_key) = headers.get(API_KEY_HEADER) else {
+        return Ok(super::claims_from_headers(headers).map(|claims| Principal {
+            claims,
+            api_key: None,
+        }));
+    };
+
+    let invalid = (StatusCode::UNAUTHORIZED, "Invalid or revoked API key");
+    let grant = verify_api_key(db, raw_key).await?;
+    let store = Store::get(db, grant.store_id).await.map_err(|_| invalid)?;
+    let owner = store.owner_device_id.ok_or(invalid)?;
+
+    // Recording usage must not slow down or fail the request
+    let touch_db = db.clone();
+    let key_id = grant.key_id;
+    tokio::spawn(async move {
+        if let Err(err) = ApiKey::touch(&touch_db, key_id, chrono::Utc::now()).await {
+            tracing::warn!(key_id = %key_id, error = %err, "Failed to record API key usage");
+        }
+    });
+
+    let mut claims = Claims::new(owner, String::new(), 0, "seller".to_string());
+    claims.tenant_id = store.tenant_id;
+    Ok(Some(Principal {
+        claims,
+        api_key: Some(grant),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn key(scopes: &str, revoked: bool) -> ApiKeyModel {
+        ApiKeyModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            key_hash: hash_api_key("tk_test"),
+            key_prefix: "tk_test".to_string(),
+            label: "POS".to_string(),
+            scopes: scopes.to_string(),
+            last_used_at: None,
+            revoked,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn principal(grant: Option<ApiKeyGrant>) -> Principal {
+        Principal {
+            claims: Claims::new("device".into(), String::new(), 1, "seller".into()),
+            api_key: grant,
+        }
+    }
+
+    #[test]
+    fn test_scopes_limit_api_key_principal() {
+        let model = key("products:write", false);
+        let store_id = model.store_id;
+        let principal = principal(ApiKeyGrant::from_key(&model));
+
+        assert!(principal.allows(ApiScope::ProductsWrite, store_id));
+        assert!(!principal.allows(ApiScope::ProductsRead, store_id));
+        assert!(!principal.allows(ApiScope::StoresWrite, store_id));
+        // A key never reaches another seller's store
+        assert!(!principal.allows(ApiScope::ProductsWrite, Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_bearer_principal_is_not_scope_limited() {
+        let principal = principal(None);
+        assert!(principal.allows(ApiScope::StoresWrite, Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_revoked_key_grants_nothing() {
+        assert!(ApiKeyGrant::from_key(&key("products:read products:write", true)).is_none());
+        assert!(ApiKeyGrant::from_key(&key("products:read products:write", false)).is_some());
+    }
+
+    #[test]
+    fn test_generated_keys_hash_deterministically() {
+        let key = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_ne!(key, generate_api_key());
+        assert_eq!(hash_api_key(&key), hash_api_key(&key));
+        assert_eq!(hash_api_key(&key).len(), 64);
+        assert_ne!(hash_api_key(&key), key);
+    }
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in [
+            ApiScope::ProductsRead,
+            ApiScope::ProductsWrite,
+            ApiScope::StoresWrite,
+        ] {
+            assert_eq!(scope.as_str().parse::<ApiScope>(), Ok(scope));
+        }
+        assert!("admin".parse::<ApiScope>().is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Two-step confirmation for destructive calls such as `DELETE /stores/:id`.
+//!
+//! The first call, without `X-Confirm-Token`, changes nothing: it answers
+//! 409 with what the call would remove and a token. Repeating the call with
+//! that token carries it out. A token is an HMAC over the action, the
+//! entity, the caller, an expiry and a nonce, so it only confirms that one
+//! call, for [`CONFIRMATION_TTL_SECS`], once. When something blocks the
+//! action the 409 lists the reasons instead and no token is issued.
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const CONFIRM_HEADER: &str = "x-confirm-token";
+
+/// How long a confirmation token stays valid
+pub const CONFIRMATION_TTL_SECS: i64 = 300;
+
+/// Error codes of the handshake
+pub const CONFIRMATION_REQUIRED: &str = "CONFIRMATION_REQUIRED";
+pub const ACTION_BLOCKED: &str = "ACTION_BLOCKED";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The one call a token confirms
+#[derive(Debug, Clone, Copy)]
+pub struct Target<'a> {
+    /// e.g. `delete_store`
+    pub action: &'static str,
+    pub entity: Uuid,
+    /// Who may use the token, see `Principal::actor`
+    pub caller: &'a str,
+}
+
+/// What a destructive call would take with it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DeletionSummary {
+    pub product_count: u64,
+    /// Why the call cannot go ahead now; empty when it can
+    pub blockers: Vec<String>,
+}
+
+/// 409 answer to an unconfirmed or blocked destructive call
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConfirmationResponse {
+    /// `CONFIRMATION_REQUIRED` or `ACTION_BLOCKED`
+    pub code: &'static str,
+    pub message: String,
+    /// Repeat the call with this in `X-Confirm-Token`; absent when blocked
+    pub confirm_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub summary: DeletionSummary,
+}
+
+/// Why a confirmation token was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationError {
+    /// Not a token, or issued for another action, entity or caller
+    Invalid,
+    Expired,
+    /// Already used
+    Reused,
+}
+
+impl ConfirmationError {
+    pub fn code(self) -> &'static str {
+        match self {
+            ConfirmationError::Invalid => "CONFIRM_TOKEN_INVALID",
+            ConfirmationError::Expired => "CONFIRM_TOKEN_EXPIRED",
+            ConfirmationError::Reused => "CONFIRM_TOKEN_USED",
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            ConfirmationError::Invalid => "Confirmation token does not match this request",
+            ConfirmationError::Expired => "Confirmation token has expired; ask for a new one",
+            ConfirmationError::Reused => "Confirmation token was already used",
+        }
+    }
+}
+
+impl IntoResponse for ConfirmationError {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "code": self.code(), "message": self.message() })),
+        )
+            .into_response()
+    }
+}
+
+/// Issues and redeems confirmation tokens. Used tokens are remembered per
+/// process, so with several replicas a token could be used once on each.
+pub struct ConfirmationTokens {
+    key: Vec<u8>,
+    /// Nonces of redeemed tokens, with their expiry
+    used: Mutex<HashMap<String, i64>>,
+}
+
+impl ConfirmationTokens {
+    /// Tokens keyed off the JWT secret, so no new setting is needed and a
+    /// rotated secret voids outstanding tokens along with sessions
+    pub fn new(jwt_secret: &str) -> Self {
+        let key = Sha256::new()
+            .chain_update(b"confirmation-token:")
+            .chain_update(jwt_secret.as_bytes())
+            .finalize()
+            .to_vec();
+        Self {
+            key,
+            used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A token for `target`, valid until `now` plus the TTL
+    pub fn issue(&self, target: Target<'_>, now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+        let expires = now.timestamp() + CONFIRMATION_TTL_SECS;
+        let nonce = format!("{:016x}", rand::rng().random::<u64>());
+        let signature = hex(&self.sign(target, expires, &nonce));
+        let expires_at = Utc.timestamp_opt(expires, 0).single().unwrap_or(now);
+        (format!("{expires}.{nonce}.{signature}"), expires_at)
+    }
+
+    /// Accept `token` for `target`, once
+    pub fn redeem(
+        &self,
+        token: &str,
+        target: Target<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<(), ConfirmationError> {
+        let mut parts = token.trim().splitn(3, '.');
+        let (Some(expires), Some(nonce), Some(signature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ConfirmationError::Invalid);
+        };
+        let expires: i64 = expires.parse().map_err(|_| ConfirmationError::Invalid)?;
+        let signature = decode_hex(signature).ok_or(ConfirmationError::Invalid)?;
+        self.mac(target, expires, nonce)
+            .verify_slice(&signature)
+            .map_err(|_| ConfirmationError::Invalid)?;
+        let now = now.timestamp();
+        if now > expires {
+            return Err(ConfirmationError::Expired);
+        }
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        used.retain(|_, until| *until >= now);
+        if used.insert(nonce.to_owned(), expires).is_some() {
+            return Err(ConfirmationError::Reused);
+        }
+        Ok(())
+    }
+
+    /// Let `target` go ahead only with a valid token in the headers. `Some`
+    /// is the response to send instead: the reasons if `summary` has
+    /// blockers, a fresh token if none was sent, or why the one sent failed.
+    pub fn confirm(
+        &self,
+        headers: &HeaderMap,
+        target: Target<'_>,
+        summary: DeletionSummary,
+        now: DateTime<Utc>,
+    ) -> Option<Response> {
+        if !summary.blockers.is_empty() {
+            return Some(conflict(ConfirmationResponse {
+                code: ACTION_BLOCKED,
+                message: "This can't be done yet; see blockers".to_string(),
+                confirm_token: None,
+                expires_at: None,
+                summary,
+            }));
+        }
+        let Some(token) = headers.get(CONFIRM_HEADER) else {
+            let (token, expires_at) = self.issue(target, now);
+            return Some(conflict(ConfirmationResponse {
+                code: CONFIRMATION_REQUIRED,
+                message: "Repeat the request with X-Confirm-Token to confirm".to_string(),
+                confirm_token: Some(token),
+                expires_at: Some(expires_at),
+                summary,
+            }));
+        };
+        let redeemed = match token.to_str() {
+            Ok(token) => self.redeem(token, target, now),
+            Err(_) => Err(ConfirmationError::Invalid),
+        };
+        redeemed.err().map(IntoResponse::into_response)
+    }
+
+    fn mac(&self, target: Target<'_>, expires: i64, nonce: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes any key length");
+        let payload = format!(
+            "{}\n{}\n{}\n{expires}\n{nonce}",
+            target.action, target.entity, target.caller
+        );
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, target: Target<'_>, expires: i64, nonce: &str) -> Vec<u8> {
+        self.mac(target, expires, nonce)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+}
+
+fn conflict(body: ConfirmationResponse) -> Response {
+    (StatusCode::CONFLICT, Json(body)).into_response()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use chrono::Duration;
+
+    fn target(entity: Uuid, caller: &str) -> Target<'_> {
+        Target {
+            action: "delete_store",
+            entity,
+            caller,
+        }
+    }
+
+    async fn body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_tokens_confirm_one_call_once() {
+        let tokens = ConfirmationTokens::new("secret");
+        let store = Uuid::new_v4();
+        let now = Utc::now();
+        let (token, expires_at) = tokens.issue(target(store, "seller-1"), now);
+        assert_eq!(
+            expires_at.timestamp(),
+            now.timestamp() + CONFIRMATION_TTL_SECS
+        );
+
+        // Another store, another caller, another action or another key
+        let elsewhere = Target {
+            action: "delete_products",
+            ..target(store, "seller-1")
+        };
+        for (tokens, target) in [
+            (&tokens, target(Uuid::new_v4(), "seller-1")),
+            (&tokens, target(store, "seller-2")),
+            (&tokens, elsewhere),
+            (&ConfirmationTokens::new("other"), target(store, "seller-1")),
+        ] {
+            assert_eq!(
+                tokens.redeem(&token, target, now),
+                Err(ConfirmationError::Invalid)
+            );
+        }
+        for garbage in ["", "abc", "1.2.zz", "1.2"] {
+            assert_eq!(
+                tokens.redeem(garbage, target(store, "seller-1"), now),
+                Err(ConfirmationError::Invalid)
+            );
+        }
+
+        let later = now + Duration::seconds(CONFIRMATION_TTL_SECS);
+        assert_eq!(
+            tokens.redeem(&token, target(store, "seller-1"), later),
+            Ok(())
+        );
+        assert_eq!(
+            tokens.redeem(&token, target(store, "seller-1"), later),
+            Err(ConfirmationError::Reused)
+        );
+    }
+
+    #[test]
+    fn test_expired_tokens_are_refused() {
+        let tokens = ConfirmationTokens::new("secret");
+        let store = Uuid::new_v4();
+        let now = Utc::now();
+        let (token, _) = tokens.issue(target(store, "seller-1"), now);
+        let too_late = now + Duration::seconds(CONFIRMATION_TTL_SECS + 1);
+        assert_eq!(
+            tokens.redeem(&token, target(store, "seller-1"), too_late),
+            Err(ConfirmationError::Expired)
+        );
+        // Refusing it did not use it up
+        assert_eq!(
+            tokens.redeem(&token, target(store, "seller-1"), now),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_issues_then_accepts_a_token() {
+        let tokens = ConfirmationTokens::new("secret");
+        let store = Uuid::new_v4();
+        let summary = DeletionSummary {
+            product_count: 240,
+            blockers: Vec::new(),
+        };
+        let now = Utc::now();
+
+        let first = tokens
+            .confirm(
+                &HeaderMap::new(),
+                target(store, "seller-1"),
+                summary.clone(),
+                now,
+            )
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::CONFLICT);
+        let first = body(first).await;
+        assert_eq!(first["code"], CONFIRMATION_REQUIRED);
+        assert_eq!(first["summary"]["product_count"], 240);
+
+        let mut headers = HeaderMap::new();
+        let token = first["confirm_token"].as_str().unwrap();
+        headers.insert(CONFIRM_HEADER, HeaderValue::from_str(token).unwrap());
+        assert!(tokens
+            .confirm(&headers, target(store, "seller-1"), summary.clone(), now)
+            .is_none());
+        let again = tokens
+            .confirm(&headers, target(store, "seller-1"), summary, now)
+            .unwrap();
+        assert_eq!(again.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body(again).await["code"], "CONFIRM_TOKEN_USED");
+    }
+
+    #[tokio::test]
+    async fn test_blocked_actions_get_reasons_not_a_token() {
+        let tokens = ConfirmationTokens::new("secret");
+        let store = Uuid::new_v4();
+        let now = Utc::now();
+        let (token, _) = tokens.issue(target(store, "seller-1"), now);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONFIRM_HEADER, HeaderValue::from_str(&token).unwrap());
+        let summary = DeletionSummary {
+            product_count: 3,
+            blockers: vec!["A report is being generated for this store".to_string()],
+        };
+
+        // Even a valid token doesn't get past a blocker
+        let response = tokens
+            .confirm(&headers, target(store, "seller-1"), summary, now)
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = body(response).await;
+        assert_eq!(body["code"], ACTION_BLOCKED);
+        assert!(body["confirm_token"].is_null());
+        assert_eq!(body["summary"]["blockers"].as_array().unwrap().len(), 1);
+    }
+}
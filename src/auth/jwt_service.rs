@@ -3,6 +3,11 @@ use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header,
 use serde::{Deserialize, Serialize};
 use std::env;
 
+use crate::config::DEFAULT_JWT_SECRET;
+
+/// Seconds past `exp` a token is still accepted, for clock skew
+pub const LEEWAY_SECS: u64 = 60;
+
 /// JWT claims structure
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -12,6 +17,8 @@ pub struct Claims {
     pub relay_id: String,   // Relay identifier
     pub public_key: String, // Public key used for authentication
     pub role: String,       // Role of the token bearer (e.g., "seller", "buyer")
+    #[serde(default = "crate::tenant::default_tenant")]
+    pub tenant_id: String, // Marketplace the bearer acts in
 }
 
 impl Claims {
@@ -24,6 +31,7 @@ impl Claims {
             relay_id,
             public_key,
             role,
+            tenant_id: crate::tenant::default_tenant(),
         }
     }
 }
@@ -38,25 +46,37 @@ pub struct JwtService {
 
 impl JwtService {
     pub fn new() -> Result<Self, String> {
-        let secret = env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string());
+        let secret = env::var("JWT_SECRET").unwrap_or_else(|_| DEFAULT_JWT_SECRET.to_string());
+        Ok(Self::with_secret(&secret))
+    }
 
+    /// Service signing with `secret`, e.g. `Config::auth.jwt_secret`
+    pub fn with_secret(secret: &str) -> Self {
         let encoding_key = EncodingKey::from_secret(secret.as_ref());
         let decoding_key = DecodingKey::from_secret(secret.as_ref());
 
         let mut validation = Validation::new(Algorithm::HS256);
         validation.set_required_spec_claims(&["sub", "exp", "iat"]);
+        validation.leeway = LEEWAY_SECS;
 
-        Ok(Self {
+        Self {
             encoding_key,
             decoding_key,
             validation,
-        })
+        }
     }
 
-    pub fn generate_token(&self, relay_id: String, public_key: String) -> Result<String, String> {
+    /// `relay_id` must already carry the tenant prefix, see
+    /// `tenant::resolve_identity`
+    pub fn generate_token(
+        &self,
+        relay_id: String,
+        public_key: String,
+        tenant_id: String,
+    ) -> Result<String, String> {
         // Backward-compatible: default role to "seller" for existing callers
-        let claims = Claims::new(relay_id, public_key, 24, "seller".to_string()); // 24 hours expiration
+        let mut claims = Claims::new(relay_id, public_key, 24, "seller".to_string()); // 24 hours expiration
+        claims.tenant_id = tenant_id;
 
         encode(&Header::default(), &claims, &self.encoding_key)
             .map_err(|e| format!("Failed to generate token: {e}"))
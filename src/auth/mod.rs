@@ -1,3 +1,56 @@
+pub mod api_key;
+pub mod confirmation;
 pub mod jwt_service;
+pub mod signing;
+pub mod token_cache;
 
+pub use api_key::{authenticate, ApiKeyGrant, ApiScope};
 pub use jwt_service::{Claims, JwtService};
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+
+/// Role carried by operator tokens allowed on `/admin` endpoints
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Who is calling, as established by the request gate and stored in the
+/// request extensions of every request it lets through
+#[derive(Debug, Clone)]
+pub enum RequestPrincipal {
+    /// No usable token, on a route open to visitors
+    Anonymous,
+    Token(Claims),
+    /// A known, unrevoked store API key; the handler still checks its store
+    /// and scopes
+    ApiKey(ApiKeyGrant),
+}
+
+#[allow(dead_code)] // for handlers that personalise public reads
+impl RequestPrincipal {
+    pub fn is_anonymous(&self) -> bool {
+        matches!(self, RequestPrincipal::Anonymous)
+    }
+
+    /// Claims of a bearer token; API keys carry none
+    pub fn claims(&self) -> Option<&Claims> {
+        match self {
+            RequestPrincipal::Token(claims) => Some(claims),
+            RequestPrincipal::Anonymous | RequestPrincipal::ApiKey(_) => None,
+        }
+    }
+
+    /// Grant of a store API key; bearer tokens carry none
+    pub fn api_key(&self) -> Option<&ApiKeyGrant> {
+        match self {
+            RequestPrincipal::ApiKey(grant) => Some(grant),
+            RequestPrincipal::Anonymous | RequestPrincipal::Token(_) => None,
+        }
+    }
+}
+
+/// Claims of the `Authorization: Bearer <token>` token, checked by the shared
+/// [`token_cache::TokenGate`], so revoked tokens get nothing here either
+pub fn claims_from_headers(headers: &HeaderMap) -> Option<Claims> {
+    let auth_str = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let token = auth_str.strip_prefix("Bearer ")?;
+    token_cache::shared().authenticate(token).ok()
+}
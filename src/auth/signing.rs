@@ -0,0 +1,520 @@
+//! HMAC request signing for `/admin` routes, on top of the admin bearer token.
+//!
+//! A signed request carries four headers:
+//! - `X-Admin-Key`: id of the caller's admin credential
+//! - `X-Timestamp`: unix seconds, within five minutes of the server clock
+//! - `X-Nonce`: random string, never reused within that window
+//! - `X-Signature`: hex HMAC-SHA256 of the canonical request
+//!
+//! The canonical request is the lines `METHOD`, `path?query`, timestamp,
+//! nonce and the hex SHA-256 of the body, joined by `\n`. The HMAC key is the
+//! hex SHA-256 of the credential secret. `admin_credentials` keeps that key
+//! sealed with the [`FieldCipher`], so issuing and checking credentials needs
+//! `FIELD_ENCRYPTION_KEYS`; the plaintext secret lives only with the admin.
+//! See `scripts/sign_admin_request.sh` for signing from a shell.
+//!
+//! Used nonces are remembered in memory by each process, so with several
+//! replicas a captured request can be replayed once against every instance
+//! that hasn't seen its nonce, until the timestamp goes stale.
+//!
+//! With `ADMIN_REQUIRE_SIGNATURES` off, unsigned admin requests still pass,
+//! but a request that carries a signature must verify.
+
+use crate::auth::claims_from_headers;
+use crate::crypto::field::FieldCipher;
+use crate::db::admin_credentials::AdminCredential;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const KEY_HEADER: &str = "x-admin-key";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub const NONCE_HEADER: &str = "x-nonce";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Allowed clock difference between the admin and the server, either way
+pub const MAX_SKEW_SECS: i64 = 300;
+/// Largest admin request body read for hashing
+const MAX_SIGNED_BODY: usize = 1024 * 1024;
+const NONCE_LEN: std::ops::RangeInclusive<usize> = 8..=128;
+const SECRET_PREFIX: &str = "tas_";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a signature was refused; each gets its own error code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    Malformed,
+    Expired,
+    UnknownKey,
+    /// The credential belongs to another admin than the bearer token
+    KeyMismatch,
+    Invalid,
+    Replayed,
+}
+
+impl SignatureError {
+    pub fn code(self) -> &'static str {
+        match self {
+            SignatureError::Missing => "SIGNATURE_MISSING",
+            SignatureError::Malformed => "SIGNATURE_MALFORMED",
+            SignatureError::Expired => "SIGNATURE_EXPIRED",
+            SignatureError::UnknownKey => "SIGNATURE_UNKNOWN_KEY",
+            SignatureError::KeyMismatch => "SIGNATURE_KEY_MISMATCH",
+            SignatureError::Invalid => "SIGNATURE_INVALID",
+            SignatureError::Replayed => "SIGNATURE_REPLAYED",
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            SignatureError::Missing => "Admin requests must be signed",
+            SignatureError::Malformed => {
+                "X-Admin-Key, X-Timestamp, X-Nonce and a hex X-Signature are required"
+            }
+            SignatureError::Expired => "X-Timestamp is more than five minutes off",
+            SignatureError::UnknownKey => "Admin credential not found or revoked",
+            SignatureError::KeyMismatch => "Admin credential belongs to another admin",
+            SignatureError::Invalid => "Signature does not match the request",
+            SignatureError::Replayed => "Nonce already used",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SignatureRejection {
+    pub code: &'static str,
+    pub message: &'static str,
+}
+
+impl IntoResponse for SignatureError {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(SignatureRejection {
+                code: self.code(),
+                message: self.message(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Fresh plaintext secret; shown to the admin once and never stored
+pub fn generate_admin_secret() -> String {
+    let mut rng = rand::rng();
+    let random_bytes: Vec<u8> = (0..32).map(|_| rng.random::<u8>()).collect();
+    format!("{SECRET_PREFIX}{}", URL_SAFE_NO_PAD.encode(random_bytes))
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// HMAC key of a secret, its hex SHA-256; stored sealed, never as is
+pub fn signing_key(secret: &str) -> String {
+    hex_sha256(secret.as_bytes())
+}
+
+pub fn canonical_request(
+    method: &str,
+    path_and_query: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_ascii_uppercase(),
+        path_and_query,
+        timestamp,
+        nonce,
+        hex_sha256(body)
+    )
+}
+
+fn mac(key: &str, canonical: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC takes any key length");
+    mac.update(canonical.as_bytes());
+    mac
+}
+
+/// `X-Signature` value for a request, for tests and admin tooling
+#[allow(dead_code)] // used by tests; scripts/sign_admin_request.sh mirrors it
+pub fn sign_request(
+    secret: &str,
+    method: &str,
+    path_and_query: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    let canonical = canonical_request(method, path_and_query, timestamp, nonce, body);
+    mac(&signing_key(secret), &canonical)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Nonces seen within the freshness window. Kept per process, so with
+/// several replicas a nonce could be replayed once against each of them.
+#[derive(Default)]
+pub struct NonceCache {
+    seen: Mutex<HashMap<(Uuid, String), i64>>,
+}
+
+impl NonceCache {
+    /// Remember a nonce; false if the credential already used it
+    fn first_use(&self, key_id: Uuid, nonce: &str, now: i64) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        // A nonce older than twice the skew can only come with an expired timestamp
+        seen.retain(|_, at| now - *at <= 2 * MAX_SKEW_SECS);
+        seen.insert((key_id, nonce.to_owned()), now).is_none()
+    }
+}
+
+/// State of [`admin_signature_middleware`]
+#[derive(Clone)]
+pub struct AdminSigning {
+    pub db: DatabaseConnection,
+    /// Reject unsigned admin requests
+    pub required: bool,
+    /// Opens the stored signing keys
+    pub cipher: Arc<FieldCipher>,
+    pub nonces: Arc<NonceCache>,
+}
+
+struct SignedHeaders {
+    key_id: Uuid,
+    timestamp: i64,
+    nonce: String,
+    signature: Vec<u8>,
+}
+
+fn signed_headers(headers: &HeaderMap) -> Result<SignedHeaders, SignatureError> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(key_id), Some(timestamp), Some(nonce), Some(signature)) = (
+        header(KEY_HEADER),
+        header(TIMESTAMP_HEADER),
+        header(NONCE_HEADER),
+        header(SIGNATURE_HEADER),
+    ) else {
+        return Err(SignatureError::Malformed);
+    };
+    Ok(SignedHeaders {
+        key_id: key_id.parse().map_err(|_| SignatureError::Malformed)?,
+        timestamp: timestamp.parse().map_err(|_| SignatureError::Malformed)?,
+        nonce: Some(nonce)
+            .filter(|n| NONCE_LEN.contains(&n.len()))
+            .ok_or(SignatureError::Malformed)?
+            .to_owned(),
+        signature: decode_hex(signature).ok_or(SignatureError::Malformed)?,
+    })
+}
+
+/// Check the signature of an `/admin` request before its handler runs
+pub async fn admin_signature_middleware(
+    State(signing): State<AdminSigning>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !request.headers().contains_key(SIGNATURE_HEADER) {
+        if signing.required {
+            return SignatureError::Missing.into_response();
+        }
+        return next.run(request).await;
+    }
+    let signed = match signed_headers(request.headers()) {
+        Ok(signed) => signed,
+        Err(err) => return err.into_response(),
+    };
+    let now = Utc::now().timestamp();
+    if (now - signed.timestamp).abs() > MAX_SKEW_SECS {
+        return SignatureError::Expired.into_response();
+    }
+    let credential = match AdminCredential::find_active(&signing.db, signed.key_id).await {
+        Ok(Some(credential)) => credential,
+        Ok(None) => return SignatureError::UnknownKey.into_response(),
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    if claims_from_headers(request.headers())
+        .is_some_and(|claims| claims.relay_id != credential.relay_id)
+    {
+        return SignatureError::KeyMismatch.into_response();
+    }
+
+    let key = match signing.cipher.decrypt(&credential.signing_key_encrypted) {
+        Ok(key) => key,
+        Err(err) => {
+            tracing::error!(key_id = %credential.id, error = %err, "Failed to open admin signing key");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to verify request signature",
+            )
+                .into_response();
+        }
+    };
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_SIGNED_BODY).await else {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Admin request body too large",
+        )
+            .into_response();
+    };
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |pq| pq.as_str());
+    let canonical = canonical_request(
+        parts.method.as_str(),
+        path_and_query,
+        signed.timestamp,
+        &signed.nonce,
+        &body,
+    );
+    // verify_slice compares in constant time
+    if mac(&key, &canonical)
+        .verify_slice(&signed.signature)
+        .is_err()
+    {
+        return SignatureError::Invalid.into_response();
+    }
+    if !signing.nonces.first_use(credential.id, &signed.nonce, now) {
+        return SignatureError::Replayed.into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{JwtService, ADMIN_ROLE};
+    use crate::crypto::field::{FieldKey, KEY_LEN};
+    use crate::db::testing;
+    use axum::{http::header::AUTHORIZATION, middleware, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn cipher() -> FieldCipher {
+        FieldCipher::new(vec![FieldKey::new("k1", [4; KEY_LEN])])
+    }
+
+    fn app(db: DatabaseConnection, required: bool) -> Router {
+        Router::new()
+            .route("/api/v1/admin/maintenance", post(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(
+                AdminSigning {
+                    db,
+                    required,
+                    cipher: Arc::new(cipher()),
+                    nonces: Arc::new(NonceCache::default()),
+                },
+                admin_signature_middleware,
+            ))
+    }
+
+    fn token(relay_id: &str) -> String {
+        JwtService::new()
+            .unwrap()
+            .generate_token_with_role(relay_id.into(), String::new(), ADMIN_ROLE.into())
+            .unwrap()
+    }
+
+    struct Signed<'a> {
+        key_id: Uuid,
+        secret: &'a str,
+        timestamp: i64,
+        nonce: &'a str,
+        body: &'a str,
+    }
+
+    fn signed_request(signed: &Signed, sent_body: &str, relay_id: &str) -> Request {
+        let path = "/api/v1/admin/maintenance?dry_run=1";
+        let signature = sign_request(
+            signed.secret,
+            "POST",
+            path,
+            signed.timestamp,
+            signed.nonce,
+            signed.body.as_bytes(),
+        );
+        Request::builder()
+            .method("POST")
+            .uri(path)
+            .header(AUTHORIZATION, format!("Bearer {}", token(relay_id)))
+            .header(KEY_HEADER, signed.key_id.to_string())
+            .header(TIMESTAMP_HEADER, signed.timestamp.to_string())
+            .header(NONCE_HEADER, signed.nonce)
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::from(sent_body.to_owned()))
+            .unwrap()
+    }
+
+    async fn code(app: &Router, request: Request) -> (StatusCode, Option<String>) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let code = serde_json::from_slice::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v["code"].as_str().map(str::to_owned));
+        (status, code)
+    }
+
+    #[tokio::test]
+    async fn test_signed_admin_requests() {
+        let db = testing::sqlite().await;
+        let (credential, secret) = AdminCredential::create(&db, &cipher(), "admin-1", "laptop")
+            .await
+            .unwrap();
+        // Only the sealed key reaches the database
+        assert!(!credential
+            .signing_key_encrypted
+            .contains(&signing_key(&secret)));
+        let app = app(db.clone(), true);
+        let now = Utc::now().timestamp();
+        let body = r#"{"enabled": true}"#;
+        let good = Signed {
+            key_id: credential.id,
+            secret: &secret,
+            timestamp: now,
+            nonce: "nonce-0001",
+            body,
+        };
+
+        assert_eq!(
+            code(&app, signed_request(&good, body, "admin-1")).await,
+            (StatusCode::OK, None)
+        );
+        let rejected = |code: &str| (StatusCode::UNAUTHORIZED, Some(code.to_string()));
+        assert_eq!(
+            code(&app, signed_request(&good, body, "admin-1")).await,
+            rejected("SIGNATURE_REPLAYED")
+        );
+
+        let fresh = |nonce| Signed { nonce, ..good };
+        assert_eq!(
+            code(
+                &app,
+                signed_request(&fresh("nonce-0002"), r#"{"enabled": false}"#, "admin-1")
+            )
+            .await,
+            rejected("SIGNATURE_INVALID")
+        );
+        let wrong_secret = Signed {
+            secret: "tas_guess",
+            ..fresh("nonce-0003")
+        };
+        assert_eq!(
+            code(&app, signed_request(&wrong_secret, body, "admin-1")).await,
+            rejected("SIGNATURE_INVALID")
+        );
+        let stale = Signed {
+            timestamp: now - MAX_SKEW_SECS - 1,
+            ..fresh("nonce-0004")
+        };
+        assert_eq!(
+            code(&app, signed_request(&stale, body, "admin-1")).await,
+            rejected("SIGNATURE_EXPIRED")
+        );
+        assert_eq!(
+            code(&app, signed_request(&fresh("nonce-0005"), body, "admin-2")).await,
+            rejected("SIGNATURE_KEY_MISMATCH")
+        );
+        let unknown = Signed {
+            key_id: Uuid::new_v4(),
+            ..fresh("nonce-0006")
+        };
+        assert_eq!(
+            code(&app, signed_request(&unknown, body, "admin-1")).await,
+            rejected("SIGNATURE_UNKNOWN_KEY")
+        );
+        assert_eq!(
+            code(&app, signed_request(&fresh("short"), body, "admin-1")).await,
+            rejected("SIGNATURE_MALFORMED")
+        );
+
+        let mut unsigned = signed_request(&fresh("nonce-0007"), body, "admin-1");
+        unsigned.headers_mut().remove(SIGNATURE_HEADER);
+        assert_eq!(code(&app, unsigned).await, rejected("SIGNATURE_MISSING"));
+        // Optional signing lets unsigned requests through
+        let mut unsigned = signed_request(&fresh("nonce-0008"), body, "admin-1");
+        unsigned.headers_mut().remove(SIGNATURE_HEADER);
+        assert_eq!(
+            code(&self::app(db.clone(), false), unsigned).await,
+            (StatusCode::OK, None)
+        );
+
+        assert!(AdminCredential::revoke(&db, credential.id).await.unwrap());
+        assert_eq!(
+            code(&app, signed_request(&fresh("nonce-0009"), body, "admin-1")).await,
+            rejected("SIGNATURE_UNKNOWN_KEY")
+        );
+    }
+
+    #[test]
+    fn test_signature_matches_the_shell_recipe() {
+        // printf 'POST\n/api/v1/admin/summary\n1700000000\nabcdefgh\n<sha256 of "">' |
+        //   openssl dgst -sha256 -hmac "$(printf %s tas_secret | sha256sum | cut -d' ' -f1)"
+        let canonical = canonical_request(
+            "post",
+            "/api/v1/admin/summary",
+            1_700_000_000,
+            "abcdefgh",
+            b"",
+        );
+        assert_eq!(
+            canonical,
+            "POST\n/api/v1/admin/summary\n1700000000\nabcdefgh\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let signature = sign_request(
+            "tas_secret",
+            "POST",
+            "/api/v1/admin/summary",
+            1_700_000_000,
+            "abcdefgh",
+            b"",
+        );
+        assert_eq!(
+            signature,
+            "e66be52a817ac35c30551c0e54fbb138119bf6bff839c2510c503ad2bb3489b0"
+        );
+        assert_eq!(decode_hex(&signature).map(|b| b.len()), Some(32));
+        assert!(decode_hex("zz").is_none());
+    }
+}
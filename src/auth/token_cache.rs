@@ -0,0 +1,363 @@
+//! Bearer-token checks for the request gate, with recently verified tokens
+//! remembered so a device's next requests skip the signature check.
+//!
+//! Tokens are keyed by their SHA-256, never stored as is. A cached entry
+//! lives at most [`MAX_CACHE_TTL_SECS`] and never past the token's own
+//! `exp`; after that the token is verified again from scratch, so the
+//! cache can only ever repeat a decision the verifier just made.
+
+use crate::auth::jwt_service::LEEWAY_SECS;
+use crate::auth::{Claims, JwtService};
+use crate::metrics;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Verified tokens kept at once; the least recently used goes first
+pub const CACHE_CAPACITY: usize = 10_000;
+/// How long a verified token is trusted without checking it again
+pub const MAX_CACHE_TTL_SECS: i64 = 300;
+
+type TokenHash = [u8; 32];
+
+static SHARED_GATE: OnceLock<Arc<TokenGate>> = OnceLock::new();
+
+/// Make `gate` the one [`shared`] returns; only the first call counts, so
+/// install it before serving
+pub fn install(gate: Arc<TokenGate>) {
+    if SHARED_GATE.set(gate).is_err() {
+        tracing::warn!("Token gate already installed; keeping the first");
+    }
+}
+
+/// The request gate's [`TokenGate`], for handlers that read the caller
+/// from the headers themselves. Before [`install`], e.g. in tests, a gate
+/// over [`JwtService::new`].
+pub fn shared() -> &'static TokenGate {
+    SHARED_GATE.get_or_init(|| Arc::new(TokenGate::new(Arc::new(JwtService::default()))))
+}
+
+fn hash(token: &str) -> TokenHash {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+struct Entry {
+    claims: Claims,
+    /// Unix time after which the entry is ignored
+    valid_until: i64,
+    /// Position in [`Lru::order`]
+    used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<TokenHash, Entry>,
+    /// Use counter to key, oldest first
+    order: BTreeMap<u64, TokenHash>,
+    next_use: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, key: TokenHash) -> u64 {
+        let used = self.next_use;
+        self.next_use += 1;
+        self.order.insert(used, key);
+        used
+    }
+
+    fn remove(&mut self, key: &TokenHash) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.used);
+        Some(entry)
+    }
+}
+
+/// Bounded LRU of verified claims by token hash
+pub struct TokenCache {
+    capacity: usize,
+    inner: Mutex<Lru>,
+}
+
+impl TokenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(Lru::default()),
+        }
+    }
+
+    fn get(&self, key: &TokenHash, now: i64) -> Option<Claims> {
+        let mut lru = self.inner.lock().ok()?;
+        let valid_until = lru.entries.get(key)?.valid_until;
+        if now >= valid_until {
+            lru.remove(key);
+            metrics::TOKEN_CACHE_ENTRIES.set(lru.entries.len() as u64);
+            return None;
+        }
+        let old = lru.entries.get(key)?.used;
+        lru.order.remove(&old);
+        let used = lru.touch(*key);
+        let entry = lru.entries.get_mut(key)?;
+        entry.used = used;
+        Some(entry.claims.clone())
+    }
+
+    fn insert(&self, key: TokenHash, claims: Claims, now: i64) {
+        let valid_until = claims.exp.min(now + MAX_CACHE_TTL_SECS);
+        if valid_until <= now {
+            return;
+        }
+        let Ok(mut lru) = self.inner.lock() else {
+            return;
+        };
+        lru.remove(&key);
+        while lru.entries.len() >= self.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+        let used = lru.touch(key);
+        lru.entries.insert(
+            key,
+            Entry {
+                claims,
+                valid_until,
+                used,
+            },
+        );
+        metrics::TOKEN_CACHE_ENTRIES.set(lru.entries.len() as u64);
+    }
+
+    /// Forget one token
+    pub fn invalidate(&self, token: &str) {
+        if let Ok(mut lru) = self.inner.lock() {
+            lru.remove(&hash(token));
+            metrics::TOKEN_CACHE_ENTRIES.set(lru.entries.len() as u64);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|lru| lru.entries.len()).unwrap_or(0)
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Verifies bearer tokens with the shared [`JwtService`], consulting the
+/// revocation list first and the cache second
+pub struct TokenGate {
+    jwt: Arc<JwtService>,
+    cache: Option<TokenCache>,
+    /// Revoked token hashes, until the token would have expired anyway
+    revoked: Mutex<HashMap<TokenHash, i64>>,
+}
+
+impl TokenGate {
+    pub fn new(jwt: Arc<JwtService>) -> Self {
+        Self::with_cache(jwt, Some(TokenCache::new(CACHE_CAPACITY)))
+    }
+
+    /// `None` verifies every token in full
+    pub fn with_cache(jwt: Arc<JwtService>, cache: Option<TokenCache>) -> Self {
+        Self {
+            jwt,
+            cache,
+            revoked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn authenticate(&self, token: &str) -> Result<Claims, String> {
+        self.authenticate_at(token, Utc::now().timestamp())
+    }
+
+    fn authenticate_at(&self, token: &str, now: i64) -> Result<Claims, String> {
+        let key = hash(token);
+        if self
+            .revoked
+            .lock()
+            .is_ok_and(|revoked| revoked.contains_key(&key))
+        {
+            return Err("Token has been revoked".to_string());
+        }
+        if let Some(cache) = &self.cache {
+            if let Some(claims) = cache.get(&key, now) {
+                metrics::TOKEN_CACHE_HITS.inc();
+                return Ok(claims);
+            }
+            metrics::TOKEN_CACHE_MISSES.inc();
+        }
+        let claims = self.jwt.validate_token(token)?;
+        if let Some(cache) = &self.cache {
+            cache.insert(key, claims.clone(), now);
+        }
+        Ok(claims)
+    }
+
+    /// Refuse `token` from now on, cached or not. Tokens that no longer
+    /// verify are already refused and are not recorded. The list is kept in
+    /// memory, so other instances keep accepting the token until it expires.
+    pub fn revoke(&self, token: &str) {
+        let key = hash(token);
+        if let Some(cache) = &self.cache {
+            cache.invalidate(token);
+        }
+        let Ok(claims) = self.jwt.validate_token(token) else {
+            return;
+        };
+        if let Ok(mut revoked) = self.revoked.lock() {
+            let now = Utc::now().timestamp();
+            // Entries are only needed while the verifier would still accept them
+            revoked.retain(|_, until| *until > now);
+            revoked.insert(key, claims.exp + LEEWAY_SECS as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "token-cache-test-secret-of-decent-length";
+
+    fn jwt(secret: &str) -> Arc<JwtService> {
+        Arc::new(JwtService::with_secret(secret))
+    }
+
+    fn token(relay_id: &str) -> String {
+        jwt(SECRET)
+            .generate_token_with_role(relay_id.to_string(), "pk".to_string(), "seller".to_string())
+            .unwrap()
+    }
+
+    fn claims(relay_id: &str, exp: i64) -> Claims {
+        let mut claims = Claims::new(
+            relay_id.to_string(),
+            "pk".to_string(),
+            1,
+            "seller".to_string(),
+        );
+        claims.exp = exp;
+        claims
+    }
+
+    /// Relay on success, or just "denied"
+    fn decision(gate: &TokenGate, token: &str) -> Result<String, ()> {
+        gate.authenticate(token)
+            .map(|claims| claims.relay_id)
+            .map_err(|_| ())
+    }
+
+    #[test]
+    fn test_cached_and_uncached_gates_decide_alike() {
+        let cached = TokenGate::new(jwt(SECRET));
+        let uncached = TokenGate::with_cache(jwt(SECRET), None);
+        let valid = token("device-1");
+        let other = token("device-2");
+        // device-1's header and claims under device-2's signature
+        let (signed, _) = valid.rsplit_once('.').unwrap();
+        let (_, signature) = other.rsplit_once('.').unwrap();
+        let tampered = format!("{signed}.{signature}");
+        let foreign = jwt("some-other-secret-entirely-0123456789")
+            .generate_token_with_role(
+                "device-1".to_string(),
+                "pk".to_string(),
+                "seller".to_string(),
+            )
+            .unwrap();
+        let tokens = [valid.as_str(), &other, &tampered, &foreign, "not-a-jwt", ""];
+
+        let hits = metrics::TOKEN_CACHE_HITS.get();
+        // Twice, so the second round is served from the cache where it can be
+        for _ in 0..2 {
+            for token in tokens {
+                assert_eq!(
+                    decision(&cached, token),
+                    decision(&uncached, token),
+                    "{token}"
+                );
+            }
+        }
+        assert_eq!(decision(&cached, &valid), Ok("device-1".to_string()));
+        assert!(metrics::TOKEN_CACHE_HITS.get() >= hits + 2);
+        // Only tokens that verified were cached
+        assert_eq!(cached.cache.as_ref().unwrap().len(), 2);
+
+        // Revoking takes effect on the very next request, cached or not
+        cached.revoke(&valid);
+        uncached.revoke(&valid);
+        for token in tokens {
+            assert_eq!(
+                decision(&cached, token),
+                decision(&uncached, token),
+                "{token}"
+            );
+        }
+        assert!(decision(&cached, &valid).is_err());
+        assert_eq!(decision(&cached, &other), Ok("device-2".to_string()));
+    }
+
+    #[test]
+    fn test_handlers_see_revocations_through_the_shared_gate() {
+        use axum::http::{header::AUTHORIZATION, HeaderMap};
+
+        let token = JwtService::default()
+            .generate_token_with_role(
+                "revoked-device".to_string(),
+                "pk".to_string(),
+                "seller".to_string(),
+            )
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        let relay =
+            |headers: &HeaderMap| crate::auth::claims_from_headers(headers).map(|c| c.relay_id);
+
+        assert_eq!(relay(&headers), Some("revoked-device".to_string()));
+        shared().revoke(&token);
+        assert_eq!(relay(&headers), None);
+    }
+
+    #[test]
+    fn test_entries_end_at_token_expiry_or_ttl() {
+        let cache = TokenCache::new(10);
+        let now = 1_700_000_000;
+        let soon = hash("expires-soon");
+        let later = hash("expires-later");
+        cache.insert(soon, claims("a", now + 30), now);
+        cache.insert(later, claims("b", now + 86_400), now);
+        // Already expired tokens are never cached
+        cache.insert(hash("expired"), claims("c", now - 1), now);
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.get(&soon, now + 29).is_some());
+        assert!(cache.get(&soon, now + 30).is_none());
+        assert!(cache.get(&later, now + MAX_CACHE_TTL_SECS - 1).is_some());
+        assert!(cache.get(&later, now + MAX_CACHE_TTL_SECS).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted_first() {
+        let cache = TokenCache::new(2);
+        let now = 1_700_000_000;
+        let exp = now + 3_600;
+        cache.insert(hash("t1"), claims("a", exp), now);
+        cache.insert(hash("t2"), claims("b", exp), now);
+        // Using t1 makes t2 the oldest
+        assert!(cache.get(&hash("t1"), now).is_some());
+        cache.insert(hash("t3"), claims("c", exp), now);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&hash("t2"), now).is_none());
+        assert!(cache.get(&hash("t1"), now).is_some());
+        assert!(cache.get(&hash("t3"), now).is_some());
+
+        cache.invalidate("t1");
+        assert_eq!(cache.len(), 1);
+    }
+}
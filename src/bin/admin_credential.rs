@@ -0,0 +1,56 @@
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use transac::config::Config;
+use transac::crypto::field::FieldCipher;
+use transac::db::admin_credentials::AdminCredential;
+use transac::db::create_connection;
+use uuid::Uuid;
+
+const USAGE: &str = "usage: admin_credential issue <relay_id> <label>\n       admin_credential revoke <credential_id>";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "transac=warn".into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = Config::from_env()?;
+    let conn = create_connection(&config).await?;
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["issue", relay_id, label] => {
+            let cipher = FieldCipher::new(config.field_encryption_keys.clone());
+            if !cipher.is_configured() {
+                anyhow::bail!("FIELD_ENCRYPTION_KEYS must be set to issue admin credentials");
+            }
+            let (credential, secret) = AdminCredential::create(&conn, &cipher, relay_id, label)
+                .await
+                .map_err(anyhow::Error::msg)?;
+            // The secret is only ever shown here; the database keeps a sealed
+            // key derived from it
+            println!("ADMIN_KEY_ID={}", credential.id);
+            println!("ADMIN_SECRET={secret}");
+        }
+        ["revoke", id] => {
+            let id: Uuid = id.parse()?;
+            if !AdminCredential::revoke(&conn, id)
+                .await
+                .map_err(anyhow::Error::msg)?
+            {
+                anyhow::bail!("No active admin credential {id}");
+            }
+            println!("Revoked admin credential {id}");
+        }
+        _ => anyhow::bail!(USAGE),
+    }
+    Ok(())
+}
@@ -32,7 +32,7 @@ async fn main() -> anyhow::Result<()> {
             let msg = format!("{e}");
             if msg.contains("does not exist") {
                 // Attempt to create the database by connecting to the 'postgres' DB
-                let (admin_url, db_name) = build_admin_url_and_db_name(&config.database_url)
+                let (admin_url, db_name) = build_admin_url_and_db_name(&config.database.url)
                     .ok_or_else(|| {
                         anyhow::anyhow!("Failed to parse DATABASE_URL for admin creation")
                     })?;
@@ -0,0 +1,28 @@
+use std::time::Duration;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use transac::health::preflight::preflight;
+
+/// How long any one network check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Checks a deployment before it takes traffic. Prints a JSON report on
+/// stdout and exits 1 if any check failed.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Logs go to stderr so stdout stays parseable
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "transac=warn".into()),
+        )
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .init();
+
+    dotenvy::dotenv().ok();
+    let report = preflight(|name| std::env::var(name).ok(), CHECK_TIMEOUT).await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -1,40 +1,1289 @@
+use crate::api::multipart::UploadLimits;
+use crate::crypto::field::FieldKey;
+use crate::currency::{is_iso_4217, DEFAULT_CURRENCY};
+use crate::db::media_quota::MediaLimits;
+use crate::db::stores::StoreSort;
+use crate::experiments::Experiment;
+use crate::features::KNOWN_FEATURES;
+use crate::moderation::text::TextModes;
+use crate::product_views::ViewSettings;
+use crate::public_stats::PublicStatsRules;
+use crate::reports::ReportLimits;
+use crate::trust::TrustWeights;
 use dotenvy::dotenv;
 use serde::Deserialize;
 use std::env;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Site `PUBLIC_BASE_URL` falls back to when unset
+pub const DEFAULT_PUBLIC_BASE_URL: &str = "https://transac.site";
+
+/// Secret `JwtService` falls back to when `JWT_SECRET` is unset
+pub const DEFAULT_JWT_SECRET: &str = "your-secret-key-change-in-production";
+
+/// Placeholder secrets shipped in `.env.example` and the local MinIO setup
+const PLACEHOLDER_SECRETS: &[&str] = &[
+    DEFAULT_JWT_SECRET,
+    "change-me-in-production-please",
+    "minioadmin",
+];
+
+/// Sync clients offline longer than this must start over with a full sync
+const MIN_TOMBSTONE_RETENTION_DAYS: u32 = 30;
+/// POS clients retry an upload within this window
+const MIN_INVENTORY_SYNC_RETENTION_DAYS: u32 = 7;
+/// Report rows outlive their files, which expire after a week
+const MIN_REPORT_RETENTION_DAYS: u32 = 7;
+/// A bad bulk edit may go unnoticed for a few days
+const MIN_SNAPSHOT_RETENTION_DAYS: u32 = 7;
+const MAX_RETENTION_DAYS: u32 = 3650;
+
+/// Routes open to visitors without a token, until `PUBLIC_PATHS` says otherwise.
+/// A prefix covers itself and everything below it, `*` stands for any one
+/// segment; GET rules also cover HEAD.
+pub const DEFAULT_PUBLIC_PATHS: &[(&str, &str)] = &[
+    ("GET", "/healthz"),
+    ("POST", "/api/v1/pow"),
+    ("GET", "/api/v1/products"),
+    ("POST", "/api/v1/products/*/view"),
+    ("GET", "/api/v1/stores"),
+    ("GET", "/api/v1/categories"),
+    ("GET", "/api/v1/search"),
+    ("GET", "/api/v1/feed"),
+    ("GET", "/api/v1/featured-stores"),
+    ("GET", "/api/v1/referrals"),
+    ("GET", "/api/v1/public-stats"),
+    ("GET", "/api/v1/return-policy-templates"),
+    ("GET", "/api/v1/media"),
+    ("GET", "/api/v1/sync"),
+    // Read-only catalog queries; mutations stay on REST
+    ("POST", "/api/v1/graphql"),
+    ("GET", "/sitemap.xml"),
+    ("GET", "/sitemaps"),
+    ("GET", "/feeds"),
+];
+const PUBLIC_PATH_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+const MAX_POW_DIFFICULTY: u32 = 32;
+const MIN_PRODUCTION_JWT_SECRET_LEN: usize = 32;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Environment {
+    Development,
+    Staging,
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Environment::Development),
+            "staging" => Ok(Environment::Staging),
+            "production" | "prod" => Ok(Environment::Production),
+            _ => Err("must be development, staging or production".to_string()),
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Clone)]
-pub struct Config {
-    pub database_url: String,
-    pub pow_difficulty: u32,
-    pub pow_timeout_minutes: i64,
+pub struct DatabaseConfig {
+    pub url: String,
     pub run_migrations_on_start: bool,
+    /// Refuse `POST /admin/migrations/run` unless maintenance mode is on
+    pub migrations_require_maintenance: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PowConfig {
+    pub difficulty: u32,
+    pub timeout_minutes: i64,
+}
+
+/// Object storage and image processing. Unset credentials leave media
+/// uploads disabled.
+#[derive(Deserialize, Clone)]
+pub struct MediaConfig {
+    pub endpoint_url: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub webp_quality: f32,
+}
+
+/// Certificate and key for serving HTTPS directly, both PEM. Requires the
+/// `tls` feature.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// How long append-only tables keep their rows, and how the pruning jobs
+/// pace their deletes
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionConfig {
+    pub tombstone_days: u32,
+    pub inventory_sync_days: u32,
+    /// Days `report_jobs` rows are kept; their files expire sooner
+    pub report_days: u32,
+    /// Days store snapshots are kept, on top of the per-store cap
+    pub snapshot_days: u32,
+    /// Rows deleted per statement
+    pub batch_size: u64,
+    /// Pause between batches so other writers get the table
+    pub batch_pause_ms: u64,
+    pub interval_secs: u64,
+}
+
+/// One `METHOD /prefix` entry of `PUBLIC_PATHS`
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct PublicPathRule {
+    /// Upper-case HTTP method
+    pub method: String,
+    /// Starts with `/`; matched on whole path segments, `*` matching any one
+    pub prefix: String,
+}
+
+/// Who may call the API without a token; see `crate::crypto::middleware`
+#[derive(Debug, Deserialize, Clone)]
+pub struct PublicAccessConfig {
+    pub rules: Vec<PublicPathRule>,
+    /// Requests a minute one address may make without a token
+    pub anonymous_requests_per_minute: u32,
+    /// Requests a minute one embedding site may make for one store's widget;
+    /// see `crate::api::embed`
+    pub embed_requests_per_minute: u32,
+}
+
+/// Public website the API serves, for links in sitemaps, feeds and shares;
+/// see `crate::links`
+#[derive(Debug, Deserialize, Clone)]
+pub struct SiteConfig {
+    /// No trailing slash
+    pub public_base_url: String,
+    /// ISO 4217 code of products created without one
+    pub currency: String,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            public_base_url: DEFAULT_PUBLIC_BASE_URL.to_string(),
+            currency: DEFAULT_CURRENCY.to_string(),
+        }
+    }
+}
+
+/// Store-to-store referral links; see `crate::api::referrals`
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReferralConfig {
+    /// Days after following a link that the viewer's orders are credited to it
+    pub attribution_days: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeatureConfig {
+    /// Features on at boot
+    pub enabled: Vec<String>,
+    /// Let admins flip features at runtime through `system_settings`
+    pub runtime_overrides: bool,
+    /// How often replicas re-read the runtime overrides
+    pub refresh_interval_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    /// Reject `/admin` requests without a valid HMAC signature; see `crate::auth::signing`
+    pub admin_signatures_required: bool,
 }
 
+// Secrets stay out of logs and panic messages
+impl fmt::Debug for MediaConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaConfig")
+            .field("endpoint_url", &self.endpoint_url)
+            .field("region", &self.region)
+            .field("bucket", &self.bucket)
+            .field("access_key_id", &self.access_key_id)
+            .field(
+                "secret_access_key",
+                &self.secret_access_key.as_ref().map(|_| "***"),
+            )
+            .field("webp_quality", &self.webp_quality)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &"***")
+            .field("admin_signatures_required", &self.admin_signatures_required)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Config {
+    pub environment: Environment,
+    pub database: DatabaseConfig,
+    pub pow: PowConfig,
+    pub media: MediaConfig,
+    /// Default per-store upload limits; admins can override them per store
+    pub media_limits: MediaLimits,
+    /// How much of a multipart upload is read before it is refused
+    pub upload_limits: UploadLimits,
+    pub auth: AuthConfig,
+    /// `None` serves plain HTTP
+    pub tls: Option<TlsConfig>,
+    pub retention: RetentionConfig,
+    pub public_access: PublicAccessConfig,
+    pub features: FeatureConfig,
+    pub site: SiteConfig,
+    pub referrals: ReferralConfig,
+    pub publish_scheduler_interval_secs: u64,
+    pub sale_cleanup_interval_secs: u64,
+    pub promotion_expiry_interval_secs: u64,
+    pub store_resume_interval_secs: u64,
+    pub trust_score_interval_secs: u64,
+    /// How often recent store reviews are scanned for farming
+    pub review_anomaly_interval_secs: u64,
+    /// Rounding and suppression of the public marketplace figures
+    pub public_stats: PublicStatsRules,
+    /// How often the public marketplace figures are recomputed
+    pub public_stats_interval_secs: u64,
+    /// Dedup window and flush interval of product view counting
+    pub product_views: ViewSettings,
+    /// How often replicas re-read the maintenance switch
+    pub maintenance_poll_interval_secs: u64,
+    /// Time jobs and event delivery get to finish after SIGTERM, once the
+    /// server has stopped accepting
+    pub shutdown_grace_secs: u64,
+    pub question_reminder_interval_secs: u64,
+    /// How often closed notification digest windows are checked and sent
+    pub notification_digest_interval_secs: u64,
+    /// Caps on queued and running report jobs; see `crate::reports`
+    pub reports: ReportLimits,
+    pub report_runner_interval_secs: u64,
+    /// Age at which an unanswered product question is sent to the seller
+    pub question_reminder_after_days: u32,
+    /// How often questions are checked for `delayed_reply` auto-responses
+    pub auto_reply_interval_secs: u64,
+    pub trust_weights: TrustWeights,
+    /// Score change, in points, that raises a trust event
+    pub trust_alert_threshold: f64,
+    /// Keys sealing sensitive columns, current first; empty leaves payout
+    /// accounts and admin request signing disabled
+    pub field_encryption_keys: Vec<FieldKey>,
+    /// A/B test of the public store list's ordering; variants are sort names
+    pub feed_experiment: Option<Experiment>,
+    /// Whether profanity in each kind of user-written text is refused or masked
+    pub text_moderation: TextModes,
+}
+
+/// Every problem found in the configuration, reported together so a
+/// misconfigured deployment can be fixed in one pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid configuration ({} problems):",
+            self.problems.len()
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Config {
     /// Loads configuration from environment variables, using dotenvy to load from .env if present.
-    pub fn from_env() -> anyhow::Result<Self> {
+    pub fn from_env() -> Result<Self, ConfigError> {
         dotenv().ok();
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("DATABASE_URL must be set in environment"))?;
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Builds the configuration from `lookup`, collecting every problem
+    /// instead of stopping at the first
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut vars = Vars {
+            lookup,
+            problems: Vec::new(),
+        };
+
+        let environment = vars.parse("ENVIRONMENT", Environment::Development);
+
+        let database = DatabaseConfig {
+            url: vars.url("DATABASE_URL", None, &["postgres", "postgresql"]),
+            run_migrations_on_start: vars.flag("RUN_MIGRATIONS_ON_START", false),
+            migrations_require_maintenance: vars.flag("MIGRATIONS_REQUIRE_MAINTENANCE", false),
+        };
+
+        let pow = PowConfig {
+            difficulty: vars.in_range("POW_DIFFICULTY", 4, 0..=MAX_POW_DIFFICULTY),
+            timeout_minutes: vars.at_least("POW_TIMEOUT_MINUTES", 10, 1),
+        };
 
-        let pow_difficulty = env::var("POW_DIFFICULTY")
-            .unwrap_or_else(|_| "4".to_string())
-            .parse::<u32>()?;
+        let media = MediaConfig {
+            endpoint_url: vars.url(
+                "AWS_ENDPOINT_URL",
+                Some("http://localhost:9000"),
+                &["http", "https"],
+            ),
+            region: vars.string("AWS_REGION", "us-east-1"),
+            bucket: vars.string("S3_BUCKET_NAME", "transac-media"),
+            access_key_id: vars.optional("AWS_ACCESS_KEY_ID"),
+            secret_access_key: vars.optional("AWS_SECRET_ACCESS_KEY"),
+            webp_quality: vars.in_range("WEBP_QUALITY", 80.0, 0.0..=100.0),
+        };
 
-        let pow_timeout_minutes = env::var("POW_TIMEOUT_MINUTES")
-            .unwrap_or_else(|_| "10".to_string())
-            .parse::<i64>()?;
+        let default_limits = MediaLimits::default();
+        let media_limits = MediaLimits {
+            images_per_product: vars.in_range(
+                "MEDIA_MAX_IMAGES_PER_PRODUCT",
+                default_limits.images_per_product,
+                1..=100,
+            ),
+            store_bytes: vars.at_least(
+                "MEDIA_STORE_QUOTA_BYTES",
+                default_limits.store_bytes,
+                1024 * 1024,
+            ),
+            daily_uploads: vars.at_least("MEDIA_DAILY_UPLOADS", default_limits.daily_uploads, 1),
+            min_images_to_publish: vars.in_range(
+                "MIN_IMAGES_TO_PUBLISH",
+                default_limits.min_images_to_publish,
+                0..=100,
+            ),
+        };
 
-        let run_migrations_on_start = env::var("RUN_MIGRATIONS_ON_START")
-            .unwrap_or_else(|_| "false".to_string())
-            .parse::<bool>()
-            .unwrap_or(true);
+        let default_upload = UploadLimits::default();
+        let upload_limits = UploadLimits {
+            max_fields: vars.in_range("UPLOAD_MAX_FIELDS", default_upload.max_fields, 1..=100),
+            max_file_bytes: vars.in_range(
+                "UPLOAD_MAX_FILE_BYTES",
+                default_upload.max_file_bytes,
+                1024..=100 * 1024 * 1024,
+            ),
+        };
+
+        let auth = AuthConfig {
+            jwt_secret: vars.string("JWT_SECRET", DEFAULT_JWT_SECRET),
+            admin_signatures_required: vars.flag("ADMIN_REQUIRE_SIGNATURES", false),
+        };
+
+        let tls = vars.tls("TLS_CERT_PATH", "TLS_KEY_PATH");
+
+        let retention = RetentionConfig {
+            tombstone_days: vars.in_range(
+                "RETENTION_TOMBSTONE_DAYS",
+                90,
+                MIN_TOMBSTONE_RETENTION_DAYS..=MAX_RETENTION_DAYS,
+            ),
+            inventory_sync_days: vars.in_range(
+                "RETENTION_INVENTORY_SYNC_DAYS",
+                30,
+                MIN_INVENTORY_SYNC_RETENTION_DAYS..=MAX_RETENTION_DAYS,
+            ),
+            report_days: vars.in_range(
+                "RETENTION_REPORT_DAYS",
+                30,
+                MIN_REPORT_RETENTION_DAYS..=MAX_RETENTION_DAYS,
+            ),
+            snapshot_days: vars.in_range(
+                "RETENTION_SNAPSHOT_DAYS",
+                90,
+                MIN_SNAPSHOT_RETENTION_DAYS..=MAX_RETENTION_DAYS,
+            ),
+            batch_size: vars.in_range("RETENTION_BATCH_SIZE", 5000, 100..=50_000),
+            batch_pause_ms: vars.in_range("RETENTION_BATCH_PAUSE_MS", 200, 0..=60_000),
+            interval_secs: vars.interval("RETENTION_INTERVAL_SECS", 3600),
+        };
+
+        let public_access = PublicAccessConfig {
+            rules: vars.public_paths("PUBLIC_PATHS"),
+            anonymous_requests_per_minute: vars.in_range(
+                "ANONYMOUS_REQUESTS_PER_MINUTE",
+                120,
+                1..=100_000,
+            ),
+            embed_requests_per_minute: vars.in_range("EMBED_REQUESTS_PER_MINUTE", 300, 1..=100_000),
+        };
+
+        let features = FeatureConfig {
+            enabled: vars.features("ENABLED_FEATURES"),
+            runtime_overrides: vars.flag("FEATURE_RUNTIME_OVERRIDES", true),
+            refresh_interval_secs: vars.interval("FEATURE_REFRESH_INTERVAL_SECS", 30),
+        };
+
+        // Named `CURRENCY` before products carried their own
+        let legacy_currency = vars.currency("CURRENCY", DEFAULT_CURRENCY);
+        let site = SiteConfig {
+            public_base_url: vars
+                .url(
+                    "PUBLIC_BASE_URL",
+                    Some(DEFAULT_PUBLIC_BASE_URL),
+                    &["http", "https"],
+                )
+                .trim_end_matches('/')
+                .to_string(),
+            currency: vars.currency("DEFAULT_CURRENCY", &legacy_currency),
+        };
+
+        let referrals = ReferralConfig {
+            attribution_days: vars.in_range("REFERRAL_ATTRIBUTION_DAYS", 7, 1..=90),
+        };
+
+        let publish_scheduler_interval_secs = vars.interval("PUBLISH_SCHEDULER_INTERVAL_SECS", 60);
+        let sale_cleanup_interval_secs = vars.interval("SALE_CLEANUP_INTERVAL_SECS", 3600);
+        let promotion_expiry_interval_secs = vars.interval("PROMOTION_EXPIRY_INTERVAL_SECS", 300);
+        let store_resume_interval_secs = vars.interval("STORE_RESUME_INTERVAL_SECS", 300);
+        let trust_score_interval_secs = vars.interval("TRUST_SCORE_INTERVAL_SECS", 3600);
+        let review_anomaly_interval_secs = vars.interval("REVIEW_ANOMALY_INTERVAL_SECS", 3600);
+        let default_public_stats = PublicStatsRules::default();
+        let public_stats = PublicStatsRules {
+            granularity: vars.in_range(
+                "PUBLIC_STATS_GRANULARITY",
+                default_public_stats.granularity,
+                1..=1_000_000,
+            ),
+            min_stores_per_city: vars.in_range(
+                "PUBLIC_STATS_MIN_STORES_PER_CITY",
+                default_public_stats.min_stores_per_city,
+                1..=10_000,
+            ),
+        };
+        let public_stats_interval_secs = vars.interval("PUBLIC_STATS_INTERVAL_SECS", 3600);
+        let default_views = ViewSettings::default();
+        let product_views = ViewSettings {
+            dedup_window_secs: vars.in_range(
+                "PRODUCT_VIEW_DEDUP_SECS",
+                default_views.dedup_window_secs,
+                60..=86_400,
+            ),
+            flush_interval_secs: vars.in_range(
+                "PRODUCT_VIEW_FLUSH_SECS",
+                default_views.flush_interval_secs,
+                1..=300,
+            ),
+        };
+        let maintenance_poll_interval_secs = vars.interval("MAINTENANCE_POLL_INTERVAL_SECS", 10);
+        let shutdown_grace_secs = vars.in_range("SHUTDOWN_GRACE_SECS", 20, 1..=300);
+        let question_reminder_interval_secs =
+            vars.interval("QUESTION_REMINDER_INTERVAL_SECS", 3600);
+        let question_reminder_after_days = vars.in_range("QUESTION_REMINDER_AFTER_DAYS", 3, 1..=90);
+        let auto_reply_interval_secs = vars.interval("AUTO_REPLY_INTERVAL_SECS", 600);
+        let notification_digest_interval_secs =
+            vars.interval("NOTIFICATION_DIGEST_INTERVAL_SECS", 300);
+
+        let default_reports = ReportLimits::default();
+        let reports = ReportLimits {
+            max_per_store: vars.in_range(
+                "REPORTS_MAX_PER_STORE",
+                default_reports.max_per_store,
+                1..=20,
+            ),
+            max_running: vars.in_range("REPORTS_MAX_RUNNING", default_reports.max_running, 1..=64),
+        };
+        let report_runner_interval_secs = vars.interval("REPORT_RUNNER_INTERVAL_SECS", 10);
+
+        let defaults = TrustWeights::default();
+        let trust_weights = TrustWeights {
+            verification: vars.weight("TRUST_WEIGHT_VERIFICATION", defaults.verification),
+            rating: vars.weight("TRUST_WEIGHT_RATING", defaults.rating),
+            fulfillment: vars.weight("TRUST_WEIGHT_FULFILLMENT", defaults.fulfillment),
+            disputes: vars.weight("TRUST_WEIGHT_DISPUTES", defaults.disputes),
+            account_age: vars.weight("TRUST_WEIGHT_ACCOUNT_AGE", defaults.account_age),
+        };
+
+        let trust_alert_threshold = vars.weight("TRUST_ALERT_THRESHOLD", 15.0);
+
+        let field_encryption_keys = vars.field_keys("FIELD_ENCRYPTION_KEYS");
+        let feed_experiment = vars.feed_experiment("FEED_EXPERIMENT");
+        let text_moderation = vars.text_modes("TEXT_MODERATION_MODES");
+
+        if !vars.problems.is_empty() {
+            return Err(ConfigError {
+                problems: vars.problems,
+            });
+        }
 
         Ok(Config {
-            database_url,
-            pow_difficulty,
-            pow_timeout_minutes,
-            run_migrations_on_start,
+            environment,
+            database,
+            pow,
+            media,
+            media_limits,
+            upload_limits,
+            auth,
+            tls,
+            retention,
+            public_access,
+            features,
+            site,
+            referrals,
+            publish_scheduler_interval_secs,
+            sale_cleanup_interval_secs,
+            promotion_expiry_interval_secs,
+            store_resume_interval_secs,
+            trust_score_interval_secs,
+            review_anomaly_interval_secs,
+            public_stats,
+            public_stats_interval_secs,
+            product_views,
+            maintenance_poll_interval_secs,
+            shutdown_grace_secs,
+            question_reminder_interval_secs,
+            question_reminder_after_days,
+            auto_reply_interval_secs,
+            notification_digest_interval_secs,
+            reports,
+            report_runner_interval_secs,
+            trust_weights,
+            trust_alert_threshold,
+            field_encryption_keys,
+            feed_experiment,
+            text_moderation,
         })
     }
+
+    /// Extra checks for `ENVIRONMENT=production`: no default or placeholder
+    /// secrets, and a JWT secret long enough to resist brute force
+    pub fn validate_for_production(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        problems.extend(jwt_secret_problem(&self.auth.jwt_secret));
+        for (name, value) in [
+            ("AWS_ACCESS_KEY_ID", &self.media.access_key_id),
+            ("AWS_SECRET_ACCESS_KEY", &self.media.secret_access_key),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|value| PLACEHOLDER_SECRETS.contains(&value))
+            {
+                problems.push(format!("{name} must not use the local MinIO default"));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+}
+
+/// Why `secret` is unfit to sign production tokens, if it is
+pub fn jwt_secret_problem(secret: &str) -> Option<String> {
+    if PLACEHOLDER_SECRETS.contains(&secret) {
+        Some("JWT_SECRET must be changed from its default value".to_string())
+    } else if secret.len() < MIN_PRODUCTION_JWT_SECRET_LEN {
+        Some(format!(
+            "JWT_SECRET must be at least {MIN_PRODUCTION_JWT_SECRET_LEN} bytes in production"
+        ))
+    } else {
+        None
+    }
+}
+
+/// Reads variables through `lookup`, recording problems as it goes
+struct Vars<F> {
+    lookup: F,
+    problems: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Vars<F> {
+    /// Set and non-blank value of `name`
+    fn optional(&self, name: &str) -> Option<String> {
+        (self.lookup)(name)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn string(&self, name: &str, default: &str) -> String {
+        self.optional(name).unwrap_or_else(|| default.to_string())
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str, default: T) -> T
+    where
+        T::Err: fmt::Display,
+    {
+        match self.optional(name) {
+            None => default,
+            Some(raw) => raw.parse().unwrap_or_else(|e| {
+                self.problems.push(format!("{name}: '{raw}' {e}"));
+                default
+            }),
+        }
+    }
+
+    /// Number that passes `admits`, described by `expected` when it does not
+    fn number<T>(
+        &mut self,
+        name: &str,
+        default: T,
+        admits: impl Fn(&T) -> bool,
+        expected: String,
+    ) -> T
+    where
+        T: FromStr,
+    {
+        let Some(raw) = self.optional(name) else {
+            return default;
+        };
+        match raw.parse::<T>() {
+            Ok(value) if admits(&value) => value,
+            Ok(_) => {
+                self.problems
+                    .push(format!("{name} must be {expected}, got {raw}"));
+                default
+            }
+            Err(_) => {
+                self.problems
+                    .push(format!("{name} must be a number, got '{raw}'"));
+                default
+            }
+        }
+    }
+
+    fn in_range<T>(&mut self, name: &str, default: T, range: RangeInclusive<T>) -> T
+    where
+        T: FromStr + PartialOrd + fmt::Display,
+    {
+        let expected = format!("between {} and {}", range.start(), range.end());
+        self.number(name, default, |value| range.contains(value), expected)
+    }
+
+    fn at_least<T>(&mut self, name: &str, default: T, min: T) -> T
+    where
+        T: FromStr + PartialOrd + fmt::Display,
+    {
+        let expected = format!("at least {min}");
+        self.number(name, default, |value| *value >= min, expected)
+    }
+
+    /// Job interval in seconds
+    fn interval(&mut self, name: &str, default: u64) -> u64 {
+        self.at_least(name, default, 1)
+    }
+
+    /// Non-negative number, or `default` when unset
+    fn weight(&mut self, name: &str, default: f64) -> f64 {
+        self.at_least(name, default, 0.0)
+    }
+
+    fn flag(&mut self, name: &str, default: bool) -> bool {
+        let Some(raw) = self.optional(name) else {
+            return default;
+        };
+        match raw.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => true,
+            "false" | "0" | "no" | "off" => false,
+            _ => {
+                self.problems
+                    .push(format!("{name} must be true or false, got '{raw}'"));
+                default
+            }
+        }
+    }
+
+    /// Comma-separated feature names, each one of [`KNOWN_FEATURES`]; the
+    /// defaults when unset
+    fn features(&mut self, name: &str) -> Vec<String> {
+        let Some(raw) = (self.lookup)(name) else {
+            return crate::features::DEFAULT_FEATURES
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect();
+        };
+        let mut enabled = Vec::new();
+        for feature in raw.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if KNOWN_FEATURES.contains(&feature) {
+                enabled.push(feature.to_string());
+            } else {
+                self.problems.push(format!(
+                    "{name}: unknown feature '{feature}', expected one of {}",
+                    KNOWN_FEATURES.join(", ")
+                ));
+            }
+        }
+        enabled
+    }
+
+    /// Comma-separated `METHOD /prefix` rules, e.g. `GET /api/v1/products`
+    fn public_paths(&mut self, name: &str) -> Vec<PublicPathRule> {
+        let Some(raw) = (self.lookup)(name) else {
+            return DEFAULT_PUBLIC_PATHS
+                .iter()
+                .map(|(method, prefix)| PublicPathRule {
+                    method: method.to_string(),
+                    prefix: prefix.to_string(),
+                })
+                .collect();
+        };
+        let mut rules = Vec::new();
+        for rule in raw.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            match rule
+                .split_once(' ')
+                .map(|(m, p)| (m.to_ascii_uppercase(), p.trim()))
+            {
+                Some((method, prefix))
+                    if PUBLIC_PATH_METHODS.contains(&method.as_str())
+                        && prefix.starts_with('/') =>
+                {
+                    rules.push(PublicPathRule {
+                        method,
+                        prefix: prefix.to_string(),
+                    });
+                }
+                _ => self.problems.push(format!(
+                    "{name}: '{rule}' must be a method ({}) and a path starting with /",
+                    PUBLIC_PATH_METHODS.join(", ")
+                )),
+            }
+        }
+        rules
+    }
+
+    /// Comma-separated `<id>:<base64 key>` entries with distinct ids; none
+    /// when unset
+    fn field_keys(&mut self, name: &str) -> Vec<FieldKey> {
+        let Some(raw) = self.optional(name) else {
+            return Vec::new();
+        };
+        let mut keys: Vec<FieldKey> = Vec::new();
+        // Entries are reported by position so key material never reaches the logs
+        for (position, entry) in raw.split(',').map(str::trim).enumerate() {
+            match FieldKey::parse(entry) {
+                Ok(key) if keys.iter().any(|k| k.id == key.id) => self
+                    .problems
+                    .push(format!("{name}: key id '{}' is listed twice", key.id)),
+                Ok(key) => keys.push(key),
+                Err(e) => self
+                    .problems
+                    .push(format!("{name}: entry {}: {e}", position + 1)),
+            }
+        }
+        keys
+    }
+
+    /// `name:sort=weight,...`, each variant a store list sort; none when
+    /// unset
+    fn feed_experiment(&mut self, name: &str) -> Option<Experiment> {
+        let raw = self.optional(name)?;
+        let experiment = match raw.parse::<Experiment>() {
+            Ok(experiment) => experiment,
+            Err(e) => {
+                self.problems.push(format!("{name}: {e}"));
+                return None;
+            }
+        };
+        for variant in &experiment.variants {
+            if let Err(e) = variant.name.parse::<StoreSort>() {
+                self.problems
+                    .push(format!("{name}: variant '{}': {e}", variant.name));
+                return None;
+            }
+        }
+        Some(experiment)
+    }
+
+    /// Comma-separated `field=mode` overrides; the defaults when unset
+    fn text_modes(&mut self, name: &str) -> TextModes {
+        let Some(raw) = self.optional(name) else {
+            return TextModes::default();
+        };
+        match raw.parse() {
+            Ok(modes) => modes,
+            Err(e) => {
+                self.problems.push(format!("{name}: {e}"));
+                TextModes::default()
+            }
+        }
+    }
+
+    /// Three-letter ISO 4217 code, upper-cased
+    fn currency(&mut self, name: &str, default: &str) -> String {
+        let code = self.string(name, default).to_ascii_uppercase();
+        if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_uppercase()) {
+            self.problems.push(format!(
+                "{name} must be a three-letter currency code, got '{code}'"
+            ));
+            return default.to_string();
+        }
+        if !is_iso_4217(&code) {
+            self.problems.push(format!(
+                "{name} must be an ISO 4217 currency code, got '{code}'"
+            ));
+            return default.to_string();
+        }
+        code
+    }
+
+    /// Both paths or neither
+    fn tls(&mut self, cert: &str, key: &str) -> Option<TlsConfig> {
+        match (self.optional(cert), self.optional(key)) {
+            (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+                cert_path: cert_path.into(),
+                key_path: key_path.into(),
+            }),
+            (None, None) => None,
+            (Some(_), None) => {
+                self.problems.push(format!("{key} must be set with {cert}"));
+                None
+            }
+            (None, Some(_)) => {
+                self.problems.push(format!("{cert} must be set with {key}"));
+                None
+            }
+        }
+    }
+
+    /// `scheme://host...` with one of `schemes`. Required when `default` is
+    /// `None`.
+    fn url(&mut self, name: &str, default: Option<&str>, schemes: &[&str]) -> String {
+        let raw = match (self.optional(name), default) {
+            (Some(raw), _) => raw,
+            (None, Some(default)) => return default.to_string(),
+            (None, None) => {
+                self.problems.push(format!("{name} must be set"));
+                return String::new();
+            }
+        };
+        let well_formed = raw.split_once("://").is_some_and(|(scheme, rest)| {
+            schemes.contains(&scheme.to_ascii_lowercase().as_str())
+                && rest
+                    .rsplit('@')
+                    .next()
+                    .and_then(|host| host.split(['/', '?', '#']).next())
+                    .is_some_and(|host| !host.is_empty())
+        });
+        if !well_formed {
+            self.problems.push(format!(
+                "{name} must be a {} URL with a host",
+                schemes.join("/")
+            ));
+        }
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    const DATABASE_URL: (&str, &str) =
+        ("DATABASE_URL", "postgres://user:pw@localhost:5432/transac");
+
+    #[test]
+    fn test_defaults_apply_when_only_database_url_is_set() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.environment, Environment::Development);
+        assert!(!config.database.run_migrations_on_start);
+        assert!(!config.database.migrations_require_maintenance);
+        assert_eq!(config.pow.difficulty, 4);
+        assert_eq!(config.pow.timeout_minutes, 10);
+        assert_eq!(config.media.endpoint_url, "http://localhost:9000");
+        assert_eq!(config.media.bucket, "transac-media");
+        assert_eq!(config.media.webp_quality, 80.0);
+        assert_eq!(config.media_limits, MediaLimits::default());
+        assert_eq!(config.upload_limits, UploadLimits::default());
+        assert_eq!(config.reports, ReportLimits::default());
+        assert_eq!(config.auth.jwt_secret, DEFAULT_JWT_SECRET);
+        assert!(!config.auth.admin_signatures_required);
+        assert_eq!(config.publish_scheduler_interval_secs, 60);
+        assert_eq!(config.tls, None);
+    }
+
+    #[test]
+    fn test_tls_needs_both_cert_and_key() {
+        let config = load(&[
+            DATABASE_URL,
+            ("TLS_CERT_PATH", "/etc/transac/fullchain.pem"),
+            ("TLS_KEY_PATH", "/etc/transac/privkey.pem"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.tls,
+            Some(TlsConfig {
+                cert_path: "/etc/transac/fullchain.pem".into(),
+                key_path: "/etc/transac/privkey.pem".into(),
+            })
+        );
+        let err = load(&[
+            DATABASE_URL,
+            ("TLS_CERT_PATH", "/etc/transac/fullchain.pem"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["TLS_KEY_PATH must be set with TLS_CERT_PATH"]
+        );
+    }
+
+    #[test]
+    fn test_all_problems_are_reported_together() {
+        let err = load(&[
+            ("POW_DIFFICULTY", "33"),
+            ("POW_TIMEOUT_MINUTES", "ten"),
+            ("RUN_MIGRATIONS_ON_START", "maybe"),
+            ("AWS_ENDPOINT_URL", "localhost:9000"),
+            ("WEBP_QUALITY", "120"),
+            ("SALE_CLEANUP_INTERVAL_SECS", "0"),
+            ("TRUST_WEIGHT_RATING", "-1"),
+            ("ENVIRONMENT", "qa"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                "ENVIRONMENT: 'qa' must be development, staging or production",
+                "DATABASE_URL must be set",
+                "RUN_MIGRATIONS_ON_START must be true or false, got 'maybe'",
+                "POW_DIFFICULTY must be between 0 and 32, got 33",
+                "POW_TIMEOUT_MINUTES must be a number, got 'ten'",
+                "AWS_ENDPOINT_URL must be a http/https URL with a host",
+                "WEBP_QUALITY must be between 0 and 100, got 120",
+                "SALE_CLEANUP_INTERVAL_SECS must be at least 1, got 0",
+                "TRUST_WEIGHT_RATING must be at least 0, got -1",
+            ]
+        );
+        let report = err.to_string();
+        assert!(report.starts_with("invalid configuration (9 problems):"));
+        assert!(report.contains("\n  - POW_DIFFICULTY must be between 0 and 32, got 33"));
+    }
+
+    #[test]
+    fn test_public_paths_parse_method_and_prefix() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.public_access.rules.len(), DEFAULT_PUBLIC_PATHS.len());
+        let config = load(&[
+            DATABASE_URL,
+            ("PUBLIC_PATHS", "get /api/v1/products, POST /api/v1/pow"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.public_access.rules,
+            [
+                PublicPathRule {
+                    method: "GET".to_string(),
+                    prefix: "/api/v1/products".to_string(),
+                },
+                PublicPathRule {
+                    method: "POST".to_string(),
+                    prefix: "/api/v1/pow".to_string(),
+                },
+            ]
+        );
+        let err = load(&[DATABASE_URL, ("PUBLIC_PATHS", "/api/v1/products,FETCH /x")]).unwrap_err();
+        assert_eq!(err.problems.len(), 2);
+    }
+
+    #[test]
+    fn test_retention_cannot_go_below_its_floor() {
+        let err = load(&[
+            DATABASE_URL,
+            ("RETENTION_TOMBSTONE_DAYS", "1"),
+            ("RETENTION_INVENTORY_SYNC_DAYS", "0"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                "RETENTION_TOMBSTONE_DAYS must be between 30 and 3650, got 1",
+                "RETENTION_INVENTORY_SYNC_DAYS must be between 7 and 3650, got 0",
+            ]
+        );
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.retention.tombstone_days, 90);
+        assert_eq!(config.retention.report_days, 30);
+        assert_eq!(config.retention.snapshot_days, 90);
+        assert_eq!(config.retention.batch_size, 5000);
+    }
+
+    #[test]
+    fn test_enabled_features_are_checked_against_known_ones() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.features.enabled, ["questions"]);
+        assert!(config.features.runtime_overrides);
+
+        // Set but empty turns every feature off
+        let config = load(&[DATABASE_URL, ("ENABLED_FEATURES", "")]).unwrap();
+        assert!(config.features.enabled.is_empty());
+
+        let err = load(&[DATABASE_URL, ("ENABLED_FEATURES", "questions, orders")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["ENABLED_FEATURES: unknown feature 'orders', expected one of questions"]
+        );
+    }
+
+    #[test]
+    fn test_feed_experiment_variants_are_store_sorts() {
+        assert_eq!(load(&[DATABASE_URL]).unwrap().feed_experiment, None);
+        let config = load(&[
+            DATABASE_URL,
+            ("FEED_EXPERIMENT", "feed_ranking:newest=50,trust_score=50"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.feed_experiment.unwrap().to_string(),
+            "feed_ranking:newest=50,trust_score=50"
+        );
+
+        let err = load(&[DATABASE_URL, ("FEED_EXPERIMENT", "feed:newest=1,random=1")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["FEED_EXPERIMENT: variant 'random': sort must be one of newest, trust_score"]
+        );
+    }
+
+    #[test]
+    fn test_text_moderation_modes_override_defaults() {
+        use crate::moderation::text::{TextField, TextMode};
+
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(
+            config.text_moderation.get(TextField::StoreName),
+            TextMode::Reject
+        );
+        assert_eq!(
+            config.text_moderation.get(TextField::ReviewComment),
+            TextMode::Mask
+        );
+        let config = load(&[
+            DATABASE_URL,
+            ("TEXT_MODERATION_MODES", "review_comment=reject"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.text_moderation.get(TextField::ReviewComment),
+            TextMode::Reject
+        );
+
+        let err = load(&[DATABASE_URL, ("TEXT_MODERATION_MODES", "answer=hide")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["TEXT_MODERATION_MODES: mode must be reject or mask, got 'hide'"]
+        );
+    }
+
+    #[test]
+    fn test_site_base_url_and_currency() {
+        let config = load(&[
+            DATABASE_URL,
+            ("PUBLIC_BASE_URL", "https://shop.example.cm/"),
+            ("CURRENCY", "xaf"),
+        ])
+        .unwrap();
+        assert_eq!(config.site.public_base_url, "https://shop.example.cm");
+        assert_eq!(config.site.currency, "XAF");
+        assert_eq!(config.referrals.attribution_days, 7);
+
+        let err = load(&[DATABASE_URL, ("CURRENCY", "CFA franc")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["CURRENCY must be a three-letter currency code, got 'CFA FRANC'"]
+        );
+    }
+
+    #[test]
+    fn test_public_stats_rules() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.public_stats, PublicStatsRules::default());
+        let config = load(&[
+            DATABASE_URL,
+            ("PUBLIC_STATS_GRANULARITY", "1000"),
+            ("PUBLIC_STATS_MIN_STORES_PER_CITY", "10"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.public_stats,
+            PublicStatsRules {
+                granularity: 1000,
+                min_stores_per_city: 10,
+            }
+        );
+        let err = load(&[DATABASE_URL, ("PUBLIC_STATS_GRANULARITY", "0")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["PUBLIC_STATS_GRANULARITY must be between 1 and 1000000, got 0"]
+        );
+    }
+
+    #[test]
+    fn test_product_view_settings() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert_eq!(config.product_views, ViewSettings::default());
+        let config = load(&[
+            DATABASE_URL,
+            ("PRODUCT_VIEW_DEDUP_SECS", "600"),
+            ("PRODUCT_VIEW_FLUSH_SECS", "30"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.product_views,
+            ViewSettings {
+                dedup_window_secs: 600,
+                flush_interval_secs: 30,
+            }
+        );
+        let err = load(&[DATABASE_URL, ("PRODUCT_VIEW_FLUSH_SECS", "0")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["PRODUCT_VIEW_FLUSH_SECS must be between 1 and 300, got 0"]
+        );
+    }
+
+    #[test]
+    fn test_upload_limits() {
+        let config = load(&[
+            DATABASE_URL,
+            ("UPLOAD_MAX_FIELDS", "4"),
+            ("UPLOAD_MAX_FILE_BYTES", "2097152"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.upload_limits,
+            UploadLimits {
+                max_fields: 4,
+                max_file_bytes: 2 * 1024 * 1024,
+            }
+        );
+        let err = load(&[DATABASE_URL, ("UPLOAD_MAX_FIELDS", "0")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["UPLOAD_MAX_FIELDS must be between 1 and 100, got 0"]
+        );
+    }
+
+    #[test]
+    fn test_default_currency_wins_and_must_be_iso_4217() {
+        let config = load(&[
+            DATABASE_URL,
+            ("CURRENCY", "XAF"),
+            ("DEFAULT_CURRENCY", "ngn"),
+        ])
+        .unwrap();
+        assert_eq!(config.site.currency, "NGN");
+
+        let err = load(&[DATABASE_URL, ("DEFAULT_CURRENCY", "ABC")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["DEFAULT_CURRENCY must be an ISO 4217 currency code, got 'ABC'"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_database_url_does_not_echo_credentials() {
+        let err = load(&[("DATABASE_URL", "mysql://root:hunter2@db/transac")]).unwrap_err();
+        assert_eq!(
+            err.problems,
+            ["DATABASE_URL must be a postgres/postgresql URL with a host"]
+        );
+        let err = load(&[("DATABASE_URL", "postgres:///transac")]).unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+    }
+
+    #[test]
+    fn test_run_migrations_flag_parses_both_ways() {
+        for (raw, expected) in [("true", true), ("1", true), ("FALSE", false), ("no", false)] {
+            let config = load(&[DATABASE_URL, ("RUN_MIGRATIONS_ON_START", raw)]).unwrap();
+            assert_eq!(config.database.run_migrations_on_start, expected, "{raw}");
+        }
+    }
+
+    #[test]
+    fn test_field_encryption_keys_keep_their_order() {
+        let config = load(&[DATABASE_URL]).unwrap();
+        assert!(config.field_encryption_keys.is_empty());
+
+        let key = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+        let config = load(&[
+            DATABASE_URL,
+            ("FIELD_ENCRYPTION_KEYS", &format!("k2:{key}, k1:{key}")),
+        ])
+        .unwrap();
+        let ids: Vec<&str> = config
+            .field_encryption_keys
+            .iter()
+            .map(|key| key.id.as_str())
+            .collect();
+        assert_eq!(ids, ["k2", "k1"]);
+
+        let err = load(&[
+            DATABASE_URL,
+            (
+                "FIELD_ENCRYPTION_KEYS",
+                &format!("k1:{key},k1:{key},k3:c2hvcnQ="),
+            ),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                "FIELD_ENCRYPTION_KEYS: key id 'k1' is listed twice",
+                "FIELD_ENCRYPTION_KEYS: entry 3: key 'k3' must be 32 bytes of base64",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_production_rejects_default_secrets() {
+        let config = load(&[
+            DATABASE_URL,
+            ("ENVIRONMENT", "production"),
+            ("AWS_ACCESS_KEY_ID", "minioadmin"),
+            ("AWS_SECRET_ACCESS_KEY", "minioadmin"),
+        ])
+        .unwrap();
+        assert_eq!(config.environment, Environment::Production);
+        let err = config.validate_for_production().unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                "JWT_SECRET must be changed from its default value",
+                "AWS_ACCESS_KEY_ID must not use the local MinIO default",
+                "AWS_SECRET_ACCESS_KEY must not use the local MinIO default",
+            ]
+        );
+
+        let short = load(&[DATABASE_URL, ("JWT_SECRET", "s3cret")]).unwrap();
+        assert_eq!(
+            short.validate_for_production().unwrap_err().problems,
+            ["JWT_SECRET must be at least 32 bytes in production"]
+        );
+
+        let hardened = load(&[
+            DATABASE_URL,
+            ("JWT_SECRET", "8c1f0e5a9b7d4c2e6f3a1b0d9e8c7f6a5b4c3d2e"),
+            ("AWS_ACCESS_KEY_ID", "AKIAEXAMPLE"),
+            ("AWS_SECRET_ACCESS_KEY", "wJalrXUtnFEMI"),
+        ])
+        .unwrap();
+        assert_eq!(hardened.validate_for_production(), Ok(()));
+    }
 }
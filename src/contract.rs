@@ -0,0 +1,631 @@
+//! Contract tests driven by the OpenAPI document.
+//!
+//! Every operation in [`ApiDoc`] is called against the app on an in-memory
+//! database, with the smallest inputs its schemas allow. The response must
+//! carry one of the documented statuses and, where a JSON body is
+//! documented for that status, a body that validates against its schema.
+//! Failures are reported by operation id.
+//!
+//! Path ids come from a seeded store, product and category; operations that
+//! need other rows register a [`Hook`] in [`hooks`]. Operations that still
+//! break their contract are listed in [`KNOWN_VIOLATIONS`] until fixed;
+//! the suite also fails when one of them starts passing, so the list only
+//! shrinks.
+
+use crate::auth::{JwtService, ADMIN_ROLE};
+use crate::config::Config;
+use crate::crypto::PowService;
+use crate::db::testing;
+use crate::entity::{product, product_bundle, product_question};
+use crate::{
+    context_router, events, experiments, features, shutdown, stores_router, ApiContext, ApiDoc,
+    AppState,
+};
+use axum::body::Body;
+use axum::http::{header, Method, Request};
+use futures::future::BoxFuture;
+use jsonschema::{Draft, JSONSchema};
+use sea_orm::{DatabaseConnection, EntityTrait, Set};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+/// Owner of the seeded store; every non-admin call is made as them
+const SELLER: &str = "contract-seller";
+
+/// Operations whose current behaviour breaks the document. Fix the handler
+/// or the annotation, then drop the id from here.
+const KNOWN_VIOLATIONS: &[&str] = &[
+    // Follow-up: serve the product routes from `stores_router`. Documented,
+    // but only `api::products::router` serves them, which needs the JWT and
+    // image-analysis state `AppState` doesn't carry
+    "getProduct",
+    "updateProduct",
+    "deleteProductMedia",
+    "editProductMedia",
+    // Follow-up: read through the request transaction. The handler reads
+    // through the pool while holding it, which the single test connection
+    // can't serve
+    "deleteProductMediaItem",
+];
+
+/// Longest a single call may take before it counts as hung
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Rows every operation starts with
+pub struct Fixtures {
+    pub store_id: Uuid,
+    pub product_id: Uuid,
+    pub category_id: Uuid,
+}
+
+impl Fixtures {
+    async fn seed(db: &DatabaseConnection) -> Self {
+        let product_id = testing::seed_product(db, SELLER).await;
+        let store_id = product::Entity::find_by_id(product_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .store_id;
+        let category_id = testing::seed_category(db, "phones").await;
+        Self {
+            store_id,
+            product_id,
+            category_id,
+        }
+    }
+
+    /// The seeded id a parameter or body field of this name refers to
+    fn id_for(&self, name: &str, path: &str) -> Option<Uuid> {
+        match name {
+            "store_id" => Some(self.store_id),
+            "product_id" => Some(self.product_id),
+            "category_id" => Some(self.category_id),
+            "id" if path.contains("/stores/") => Some(self.store_id),
+            "id" if path.contains("/products/") => Some(self.product_id),
+            "id" if path.contains("/categories/") => Some(self.category_id),
+            _ => None,
+        }
+    }
+}
+
+/// Seeds whatever an operation needs beyond [`Fixtures`] and returns values
+/// for its path parameters, by name
+pub type Hook =
+    for<'a> fn(&'a DatabaseConnection, &'a Fixtures) -> BoxFuture<'a, Vec<(&'static str, String)>>;
+
+/// Seeding hooks, by operation id
+fn hooks() -> HashMap<&'static str, Hook> {
+    let mut hooks: HashMap<&'static str, Hook> = HashMap::new();
+    for id in [
+        "getBundle",
+        "updateBundle",
+        "deleteBundle",
+        "recordBundleSale",
+    ] {
+        hooks.insert(id, seed_bundle);
+    }
+    hooks.insert("answerQuestion", seed_question);
+    hooks
+}
+
+fn seed_bundle<'a>(
+    db: &'a DatabaseConnection,
+    fixtures: &'a Fixtures,
+) -> BoxFuture<'a, Vec<(&'static str, String)>> {
+    Box::pin(async move {
+        let now = chrono::Utc::now();
+        let bundle = product_bundle::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            store_id: Set(fixtures.store_id),
+            name: Set("Kitchen starter".to_string()),
+            description: Set(None),
+            price: Set(5000.0),
+            is_active: Set(true),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        let id = bundle.id.clone().unwrap();
+        product_bundle::Entity::insert(bundle)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        vec![("bundle_id", id.to_string())]
+    })
+}
+
+fn seed_question<'a>(
+    db: &'a DatabaseConnection,
+    fixtures: &'a Fixtures,
+) -> BoxFuture<'a, Vec<(&'static str, String)>> {
+    Box::pin(async move {
+        let question = product_question::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(fixtures.product_id),
+            asked_by: Set("contract-buyer".to_string()),
+            question: Set("Is it still available?".to_string()),
+            answer: Set(None),
+            answered_by: Set(None),
+            is_held: Set(false),
+            created_at: Set(chrono::Utc::now()),
+            answered_at: Set(None),
+            auto_reply: Set(None),
+            auto_replied_at: Set(None),
+        };
+        let id = question.id.clone().unwrap();
+        product_question::Entity::insert(question)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        vec![("id", id.to_string())]
+    })
+}
+
+fn app(db: &DatabaseConnection) -> axum::Router {
+    let config = Config::from_lookup(|name| match name {
+        "DATABASE_URL" => Some("postgres://localhost/transac".to_string()),
+        "ENABLED_FEATURES" => Some(features::KNOWN_FEATURES.join(",")),
+        _ => None,
+    })
+    .unwrap();
+    let jwt_service = Arc::new(JwtService::with_secret(&config.auth.jwt_secret));
+    let tokens = Arc::new(crate::auth::token_cache::TokenGate::new(
+        jwt_service.clone(),
+    ));
+    let public_access = Arc::new(
+        crate::PublicAccess::from_config(&config.public_access, tokens.clone())
+            .with_api_keys(db.clone()),
+    );
+    let pow_service = Arc::new(PowService::new(
+        config.pow.difficulty,
+        config.pow.timeout_minutes,
+    ));
+    let context = ApiContext {
+        pow_service: pow_service.clone(),
+        jwt_service,
+        tokens,
+        shutdown: shutdown::ShutdownCoordinator::new(),
+    };
+    let state = AppState {
+        db: db.clone(),
+        events: Arc::new(events::EventDispatcher::new()),
+        features: Arc::new(features::FeatureFlags::new(
+            config.features.enabled.clone(),
+            false,
+        )),
+        site: Arc::new(config.site.clone()),
+        media_limits: Arc::new(config.media_limits),
+        upload_limits: Arc::new(config.upload_limits),
+        report_limits: Arc::new(config.reports),
+        field_cipher: Arc::new(crate::crypto::field::FieldCipher::new(
+            config.field_encryption_keys,
+        )),
+        database: Arc::new(config.database.clone()),
+        experiments: Arc::new(experiments::Experiments {
+            feed: config.feed_experiment.clone(),
+        }),
+        confirmations: Arc::new(crate::auth::confirmation::ConfirmationTokens::new(
+            &config.auth.jwt_secret,
+        )),
+        embed_limiter: Arc::new(crate::api::embed::EmbedLimiter::new(
+            config.public_access.embed_requests_per_minute,
+        )),
+        referrals: Arc::new(config.referrals.clone()),
+        views: Arc::new(crate::product_views::ViewRecorder::new().0),
+        pow_service,
+        webp: Arc::new(crate::api::image_conversion::WebpConverter::new(
+            config.media.webp_quality,
+        )),
+    };
+    context_router(public_access.clone())
+        .merge(stores_router(state, public_access, false))
+        .with_state(context)
+}
+
+/// The document with OpenAPI 3.0 `nullable` rewritten as JSON Schema
+fn spec() -> Value {
+    let mut spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    nullable_to_json_schema(&mut spec);
+    spec
+}
+
+fn nullable_to_json_schema(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for child in map.values_mut() {
+                nullable_to_json_schema(child);
+            }
+            if map.remove("nullable") != Some(Value::Bool(true)) {
+                return;
+            }
+            if let Some(Value::Array(options)) = map.get_mut("enum") {
+                options.push(Value::Null);
+            }
+            match map.get("type").cloned() {
+                Some(Value::String(ty)) => {
+                    map.insert("type".into(), json!([ty, "null"]));
+                }
+                _ => {
+                    let schema = Value::Object(std::mem::take(map));
+                    map.insert("anyOf".into(), json!([schema, { "type": "null" }]));
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(nullable_to_json_schema),
+        _ => {}
+    }
+}
+
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    match value.get("$ref").and_then(Value::as_str) {
+        Some(pointer) => resolve(spec, spec.pointer(&pointer[1..]).unwrap_or(&Value::Null)),
+        None => value,
+    }
+}
+
+/// The smallest value `schema` accepts; `name` is the field or parameter
+/// it is for, so ids can point at seeded rows
+fn example(spec: &Value, schema: &Value, name: &str, path: &str, fixtures: &Fixtures) -> Value {
+    let schema = resolve(spec, schema);
+    if let Some(value) = schema.get("example").or_else(|| schema.get("default")) {
+        return value.clone();
+    }
+    if let Some(first) = schema.get("enum").and_then(|e| e.get(0)) {
+        return first.clone();
+    }
+    for key in ["allOf", "oneOf", "anyOf"] {
+        let Some(options) = schema.get(key).and_then(Value::as_array) else {
+            continue;
+        };
+        if key != "allOf" {
+            let option = options
+                .iter()
+                .find(|o| o.get("type") != Some(&json!("null")));
+            return example(spec, option.unwrap_or(&Value::Null), name, path, fixtures);
+        }
+        let mut merged = Map::new();
+        for option in options {
+            match example(spec, option, name, path, fixtures) {
+                Value::Object(fields) => merged.extend(fields),
+                other => return other,
+            }
+        }
+        return Value::Object(merged);
+    }
+    let ty = match &schema["type"] {
+        Value::Array(types) => types.iter().find(|t| *t != "null").unwrap_or(&Value::Null),
+        ty => ty,
+    };
+    match ty.as_str() {
+        Some("object") => {
+            let required: BTreeSet<&str> = schema["required"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            let mut object = Map::new();
+            if let Some(properties) = schema["properties"].as_object() {
+                for (field, property) in properties {
+                    if required.contains(field.as_str()) {
+                        let value = example(spec, property, field, path, fixtures);
+                        object.insert(field.clone(), value);
+                    }
+                }
+            }
+            Value::Object(object)
+        }
+        Some("array") => match schema["minItems"].as_u64() {
+            Some(n) if n > 0 => {
+                let item = example(spec, &schema["items"], name, path, fixtures);
+                Value::Array(vec![item; n as usize])
+            }
+            _ => json!([]),
+        },
+        Some("integer") => json!(schema["minimum"].as_i64().unwrap_or(1).max(1)),
+        Some("number") => json!(schema["minimum"].as_f64().unwrap_or(1.0).max(1.0)),
+        Some("boolean") => json!(false),
+        _ => match schema["format"].as_str() {
+            _ if fixtures.id_for(name, path).is_some() => {
+                json!(fixtures.id_for(name, path).unwrap().to_string())
+            }
+            Some("uuid") => json!(Uuid::new_v4().to_string()),
+            Some("date-time") => json!(chrono::Utc::now().to_rfc3339()),
+            Some("date") => json!(chrono::Utc::now().date_naive().to_string()),
+            Some("email") => json!("seller@example.com"),
+            Some("uri") | Some("url") => json!("https://example.com"),
+            _ => {
+                let len = schema["minLength"].as_u64().unwrap_or(1).max(1);
+                json!("x".repeat(len as usize))
+            }
+        },
+    }
+}
+
+fn as_param(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// One documented operation
+struct Operation<'a> {
+    id: String,
+    method: Method,
+    path: &'a str,
+    spec: &'a Value,
+}
+
+impl Operation<'_> {
+    fn request(
+        &self,
+        spec: &Value,
+        fixtures: &Fixtures,
+        hooked: &[(&str, String)],
+    ) -> Request<Body> {
+        let mut path = self.path.to_string();
+        let mut query = Vec::new();
+        let parameters = self.spec["parameters"].as_array().into_iter().flatten();
+        for parameter in parameters.map(|p| resolve(spec, p)) {
+            let name = parameter["name"].as_str().unwrap_or_default();
+            let value = match hooked.iter().find(|(hooked, _)| *hooked == name) {
+                Some((_, value)) => value.clone(),
+                None => as_param(&example(
+                    spec,
+                    &parameter["schema"],
+                    name,
+                    self.path,
+                    fixtures,
+                )),
+            };
+            match parameter["in"].as_str() {
+                Some("path") => path = path.replace(&format!("{{{name}}}"), &value),
+                Some("query") if parameter["required"] == json!(true) => {
+                    query.push(format!("{name}={}", urlencoding::encode(&value)));
+                }
+                _ => {}
+            }
+        }
+        if !query.is_empty() {
+            path = format!("{path}?{}", query.join("&"));
+        }
+
+        let role = if self.path.contains("/admin/") {
+            ADMIN_ROLE
+        } else {
+            "seller"
+        };
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token_with_role(SELLER.into(), String::new(), role.into())
+            .unwrap();
+        let request = Request::builder()
+            .method(self.method.clone())
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"));
+        let content = &resolve(spec, &self.spec["requestBody"])["content"];
+        match content.as_object().and_then(|c| c.iter().next()) {
+            Some((media_type, body)) if media_type == "application/json" => {
+                let body = example(spec, &body["schema"], "", self.path, fixtures);
+                request
+                    .header(header::CONTENT_TYPE, media_type)
+                    .body(Body::from(body.to_string()))
+            }
+            Some((media_type, _)) => request
+                .header(header::CONTENT_TYPE, media_type)
+                .body(Body::empty()),
+            None => request.body(Body::empty()),
+        }
+        .unwrap()
+    }
+
+    /// Call the operation on a fresh database; `Err` describes how it broke
+    /// the document
+    async fn check(&self, spec: &Value, hooks: &HashMap<&str, Hook>) -> Result<(), String> {
+        let db = testing::sqlite().await;
+        let fixtures = Fixtures::seed(&db).await;
+        let hooked = match hooks.get(self.id.as_str()) {
+            Some(hook) => hook(&db, &fixtures).await,
+            None => Vec::new(),
+        };
+        let request = self.request(spec, &fixtures, &hooked);
+        let uri = request.uri().to_string();
+        let response = tokio::time::timeout(CALL_TIMEOUT, app(&db).oneshot(request))
+            .await
+            .map_err(|_| format!("{} {uri} timed out", self.method))?
+            .unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let responses = &self.spec["responses"];
+        let documented = responses
+            .get(status.as_str())
+            .or_else(|| responses.get("default"))
+            .ok_or_else(|| {
+                format!(
+                    "{} {uri} answered undocumented {status}: {}",
+                    self.method,
+                    String::from_utf8_lossy(&body)
+                )
+            })?;
+        let Some(schema) = resolve(spec, documented)["content"]
+            .get("application/json")
+            .map(|content| &content["schema"])
+        else {
+            return Ok(());
+        };
+        if !content_type.starts_with("application/json") {
+            return Err(format!(
+                "{} {uri} answered {status} with {content_type:?} instead of JSON: {}",
+                self.method,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        let instance: Value = serde_json::from_slice(&body).map_err(|e| {
+            format!(
+                "{} {uri} answered {status} with invalid JSON: {e}",
+                self.method
+            )
+        })?;
+        let root = json!({ "allOf": [schema], "components": spec["components"] });
+        let validator = JSONSchema::options()
+            .with_draft(Draft::Draft7)
+            .compile(&root)
+            .map_err(|e| format!("schema for {status} does not compile: {e}"))?;
+        if let Err(errors) = validator.validate(&instance) {
+            let errors: Vec<String> = errors
+                .map(|e| format!("{} at {}", e, e.instance_path))
+                .collect();
+            return Err(format!(
+                "{} {uri} answered {status} with a body off its schema: {}",
+                self.method,
+                errors.join("; ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn operations(spec: &Value) -> Vec<Operation<'_>> {
+    let mut operations = Vec::new();
+    for (path, item) in spec["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in item.as_object().into_iter().flatten() {
+            let Ok(method) = Method::from_bytes(method.to_uppercase().as_bytes()) else {
+                continue;
+            };
+            let Some(id) = operation["operationId"].as_str() else {
+                continue;
+            };
+            operations.push(Operation {
+                id: id.to_string(),
+                method,
+                path,
+                spec: operation,
+            });
+        }
+    }
+    operations
+}
+
+#[tokio::test]
+async fn test_documented_operations_honour_their_contract() {
+    let spec = spec();
+    let hooks = hooks();
+    let known: BTreeSet<&str> = KNOWN_VIOLATIONS.iter().copied().collect();
+    let mut failures = Vec::new();
+    for operation in operations(&spec) {
+        let outcome = operation.check(&spec, &hooks).await;
+        match (outcome, known.contains(operation.id.as_str())) {
+            (Err(e), false) => failures.push(format!("{}: {e}", operation.id)),
+            (Ok(()), true) => failures.push(format!(
+                "{}: now honours its contract; remove it from KNOWN_VIOLATIONS",
+                operation.id
+            )),
+            _ => {}
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_known_violations_name_documented_operations() {
+    let spec = spec();
+    let ids: BTreeSet<String> = operations(&spec).into_iter().map(|o| o.id).collect();
+    for id in KNOWN_VIOLATIONS {
+        assert!(ids.contains(*id), "{id} is not a documented operation");
+    }
+}
+
+#[test]
+fn test_examples_follow_schemas() {
+    let spec = json!({ "components": { "schemas": { "Store": {
+        "type": "object",
+        "required": ["store_id", "name", "tags"],
+        "properties": {
+            "store_id": { "type": "string", "format": "uuid" },
+            "name": { "type": "string", "minLength": 3 },
+            "tags": { "type": "array", "minItems": 1, "items": { "type": "integer", "minimum": 5 } },
+            "note": { "type": "string" }
+        }
+    } } } });
+    let fixtures = Fixtures {
+        store_id: Uuid::new_v4(),
+        product_id: Uuid::new_v4(),
+        category_id: Uuid::new_v4(),
+    };
+    let store = example(
+        &spec,
+        &json!({ "$ref": "#/components/schemas/Store" }),
+        "",
+        "/api/v1/stores",
+        &fixtures,
+    );
+    assert_eq!(
+        store,
+        json!({ "store_id": fixtures.store_id.to_string(), "name": "xxx", "tags": [5] })
+    );
+}
+
+#[test]
+fn test_nullable_becomes_a_null_type() {
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "note": { "type": "string", "nullable": true },
+            "store": { "allOf": [{ "$ref": "#/components/schemas/Store" }], "nullable": true }
+        }
+    });
+    nullable_to_json_schema(&mut schema);
+    assert_eq!(
+        schema["properties"]["note"]["type"],
+        json!(["string", "null"])
+    );
+    assert_eq!(
+        schema["properties"]["store"]["anyOf"][1],
+        json!({ "type": "null" })
+    );
+}
+
+#[test]
+fn test_every_schema_reference_resolves() {
+    fn refs(value: &Value, out: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(target)) => {
+                            out.insert(target.clone());
+                        }
+                        _ => refs(value, out),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+            _ => {}
+        }
+    }
+    let spec = spec();
+    let mut targets = BTreeSet::new();
+    refs(&spec, &mut targets);
+    let dangling: Vec<_> = targets
+        .iter()
+        .filter(|target| {
+            let name = target.trim_start_matches("#/components/schemas/");
+            spec["components"]["schemas"].get(name).is_none()
+        })
+        .collect();
+    assert!(dangling.is_empty(), "unresolved references: {dangling:?}");
+}
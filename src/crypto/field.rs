@@ -0,0 +1,204 @@
+//! AES-256-GCM encryption of single sensitive columns, such as payout
+//! account numbers.
+//!
+//! Ciphertexts are stored as `<key id>:<base64 of nonce and sealed bytes>`.
+//! New values are sealed with the first configured key; the others are kept
+//! only to open values written before a rotation.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rand::Rng;
+use serde::Deserialize;
+use std::fmt;
+
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// One entry of `FIELD_ENCRYPTION_KEYS`
+#[derive(Clone, Deserialize)]
+pub struct FieldKey {
+    /// Short label written in front of every ciphertext sealed with the key
+    pub id: String,
+    key: [u8; KEY_LEN],
+}
+
+impl FieldKey {
+    pub fn new(id: impl Into<String>, key: [u8; KEY_LEN]) -> Self {
+        Self { id: id.into(), key }
+    }
+
+    /// `<id>:<base64 key>`; the id is non-empty and has no `:` or `,`, and
+    /// the key decodes to 32 bytes
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let (id, key) = raw
+            .split_once(':')
+            .ok_or_else(|| "must be <id>:<base64 key>".to_string())?;
+        let id = id.trim();
+        if id.is_empty() {
+            return Err("key id must not be empty".to_string());
+        }
+        let key = STANDARD
+            .decode(key.trim())
+            .ok()
+            .and_then(|bytes| <[u8; KEY_LEN]>::try_from(bytes).ok())
+            .ok_or_else(|| format!("key '{id}' must be {KEY_LEN} bytes of base64"))?;
+        Ok(Self::new(id, key))
+    }
+}
+
+// Key material stays out of logs and panic messages
+impl fmt::Debug for FieldKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldKey")
+            .field("id", &self.id)
+            .field("key", &"***")
+            .finish()
+    }
+}
+
+/// Seals and opens column values with the configured keys
+#[derive(Debug, Clone)]
+pub struct FieldCipher {
+    keys: Vec<FieldKey>,
+}
+
+impl FieldCipher {
+    /// The first key seals new values. With no keys at all every call fails,
+    /// which callers report as the feature being unavailable.
+    pub fn new(keys: Vec<FieldKey>) -> Self {
+        Self { keys }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Seal `plaintext` with the current key under a fresh random nonce
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let current = self
+            .keys
+            .first()
+            .ok_or_else(|| "Field encryption is not configured".to_string())?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rng().fill(&mut nonce);
+        let sealed = Aes256Gcm::new(&current.key.into())
+            .encrypt(&Nonce::from(nonce), plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt field".to_string())?;
+        let mut payload = nonce.to_vec();
+        payload.extend(sealed);
+        Ok(format!("{}:{}", current.id, STANDARD.encode(payload)))
+    }
+
+    /// Open a value sealed by [`FieldCipher::encrypt`] with any configured
+    /// key, current or retired
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String, String> {
+        let (id, payload) = ciphertext
+            .split_once(':')
+            .ok_or_else(|| "Encrypted field has no key id".to_string())?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == id)
+            .ok_or_else(|| format!("Encryption key '{id}' is not configured"))?;
+        let payload = STANDARD
+            .decode(payload)
+            .map_err(|_| "Encrypted field is not valid base64".to_string())?;
+        if payload.len() < NONCE_LEN {
+            return Err("Encrypted field is truncated".to_string());
+        }
+        let (nonce, sealed) = payload.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("split at the nonce length");
+        let plaintext = Aes256Gcm::new(&key.key.into())
+            .decrypt(&Nonce::from(nonce), sealed)
+            .map_err(|_| "Encrypted field failed authentication".to_string())?;
+        String::from_utf8(plaintext).map_err(|_| "Decrypted field is not UTF-8".to_string())
+    }
+
+    /// Whether `ciphertext` was sealed with an older key and should be
+    /// rewritten with the current one
+    pub fn needs_rotation(&self, ciphertext: &str) -> bool {
+        let current = self.keys.first().map(|key| key.id.as_str());
+        ciphertext.split_once(':').map(|(id, _)| id) != current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> FieldKey {
+        FieldKey::new(id, [byte; KEY_LEN])
+    }
+
+    #[test]
+    fn test_round_trip_uses_fresh_nonces() {
+        let cipher = FieldCipher::new(vec![key("k1", 7)]);
+        let first = cipher.encrypt("677123456").unwrap();
+        let second = cipher.encrypt("677123456").unwrap();
+        assert!(first.starts_with("k1:"));
+        assert_ne!(first, second);
+        assert!(!first.contains("677123456"));
+        assert_eq!(cipher.decrypt(&first).unwrap(), "677123456");
+        assert_eq!(cipher.decrypt(&second).unwrap(), "677123456");
+    }
+
+    #[test]
+    fn test_old_values_open_after_rotation() {
+        let old = FieldCipher::new(vec![key("k1", 1)]);
+        let sealed = old.encrypt("699000111").unwrap();
+
+        let rotated = FieldCipher::new(vec![key("k2", 2), key("k1", 1)]);
+        assert_eq!(rotated.decrypt(&sealed).unwrap(), "699000111");
+        assert!(rotated.needs_rotation(&sealed));
+        let resealed = rotated.encrypt("699000111").unwrap();
+        assert!(resealed.starts_with("k2:"));
+        assert!(!rotated.needs_rotation(&resealed));
+
+        // Once the old key is dropped its values can no longer be read
+        let retired = FieldCipher::new(vec![key("k2", 2)]);
+        assert_eq!(
+            retired.decrypt(&sealed).unwrap_err(),
+            "Encryption key 'k1' is not configured"
+        );
+    }
+
+    #[test]
+    fn test_tampering_and_wrong_keys_are_rejected() {
+        let cipher = FieldCipher::new(vec![key("k1", 1)]);
+        let sealed = cipher.encrypt("655443322").unwrap();
+        let (id, payload) = sealed.split_once(':').unwrap();
+        let mut bytes = STANDARD.decode(payload).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        let tampered = format!("{id}:{}", STANDARD.encode(bytes));
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        // Same id, different key material
+        let impostor = FieldCipher::new(vec![key("k1", 9)]);
+        assert!(impostor.decrypt(&sealed).is_err());
+        assert!(cipher.decrypt("k1:AAAA").is_err());
+        assert!(cipher.decrypt("no key id").is_err());
+    }
+
+    #[test]
+    fn test_unconfigured_cipher_refuses_to_encrypt() {
+        let cipher = FieldCipher::new(Vec::new());
+        assert!(!cipher.is_configured());
+        assert!(cipher.encrypt("677123456").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_and_hide_it_from_debug() {
+        let raw = format!("2025-10:{}", STANDARD.encode([3u8; KEY_LEN]));
+        let parsed = FieldKey::parse(&raw).unwrap();
+        assert_eq!(parsed.id, "2025-10");
+        assert_eq!(
+            format!("{parsed:?}"),
+            r#"FieldKey { id: "2025-10", key: "***" }"#
+        );
+
+        assert!(FieldKey::parse("no-separator").is_err());
+        assert!(FieldKey::parse(&format!(":{}", STANDARD.encode([3u8; KEY_LEN]))).is_err());
+        assert!(FieldKey::parse(&format!("k1:{}", STANDARD.encode([3u8; 16]))).is_err());
+    }
+}
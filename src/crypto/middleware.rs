@@ -1,26 +1,152 @@
-use crate::auth::JwtService;
+use crate::auth::api_key::{verify_api_key, API_KEY_HEADER};
+use crate::auth::token_cache::TokenGate;
+use crate::auth::RequestPrincipal;
+use crate::config::{PublicAccessConfig, PublicPathRule};
+use crate::request_middleware::get_client_ip;
 use axum::{
-    extract::Request,
-    http::{HeaderMap, StatusCode},
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
-/// Determine if cryptographic validation should be skipped for a given path
-pub fn should_skip_validation(path: &str) -> bool {
-    // Public endpoints that don't require authentication
-    let public_paths = [
-        "/healthz",
-        // PoW challenge endpoint for obtaining challenges
-        "/api/v1/pow/challenge",
-        // PoW verification endpoint for obtaining certificates
-        "/api/v1/pow/verify",
-    ];
-
-    public_paths
-        .iter()
-        .any(|&public_path| path == public_path || path.starts_with(&format!("{public_path}/")))
+/// Window over which anonymous requests are counted
+const ANONYMOUS_WINDOW: Duration = Duration::from_secs(60);
+
+/// Rule segment standing for any one path segment, e.g. a product ID
+const WILDCARD: &str = "*";
+
+#[derive(Debug, Default)]
+struct PrefixNode {
+    children: HashMap<String, PrefixNode>,
+    /// A rule ends here, so this path and everything below it is public
+    terminal: bool,
+}
+
+/// `PUBLIC_PATHS` compiled into one segment trie per method, so a lookup
+/// costs one step per path segment however many rules there are
+#[derive(Debug, Default)]
+pub struct PublicPathPolicy {
+    methods: HashMap<Method, PrefixNode>,
+}
+
+impl PublicPathPolicy {
+    pub fn new(rules: &[PublicPathRule]) -> Self {
+        let mut policy = Self::default();
+        for rule in rules {
+            let Ok(method) = Method::from_bytes(rule.method.as_bytes()) else {
+                continue;
+            };
+            let mut node = policy.methods.entry(method).or_default();
+            for segment in segments(&rule.prefix) {
+                node = node.children.entry(segment.to_string()).or_default();
+            }
+            node.terminal = true;
+        }
+        policy
+    }
+
+    /// Whether `method` on `path` may be called without a token
+    pub fn is_public(&self, method: &Method, path: &str) -> bool {
+        let method = if method == Method::HEAD {
+            &Method::GET
+        } else {
+            method
+        };
+        let Some(node) = self.methods.get(method) else {
+            return false;
+        };
+        let segments: Vec<&str> = segments(path).collect();
+        node.covers(&segments)
+    }
+}
+
+impl PrefixNode {
+    /// Whether a rule through this node covers the rest of a path, trying
+    /// the literal segment before `*`
+    fn covers(&self, rest: &[&str]) -> bool {
+        if self.terminal {
+            return true;
+        }
+        let Some((segment, rest)) = rest.split_first() else {
+            return false;
+        };
+        [*segment, WILDCARD]
+            .iter()
+            .filter_map(|key| self.children.get(*key))
+            .any(|child| child.covers(rest))
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Fixed-window request counter per client address
+pub struct AnonymousLimiter {
+    per_window: u32,
+    window: Duration,
+    inner: Mutex<(Instant, HashMap<String, u32>)>,
+}
+
+impl AnonymousLimiter {
+    pub fn new(per_window: u32, window: Duration) -> Self {
+        Self {
+            per_window,
+            window,
+            inner: Mutex::new((Instant::now(), HashMap::new())),
+        }
+    }
+
+    /// Count a request from `client`; false once it is over the limit
+    pub fn allow(&self, client: &str, now: Instant) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return true;
+        };
+        let (started, counts) = &mut *inner;
+        if now.duration_since(*started) >= self.window {
+            // Starting over drops every address, so the map stays small
+            *started = now;
+            counts.clear();
+        }
+        let count = counts.entry(client.to_string()).or_insert(0);
+        *count += 1;
+        *count <= self.per_window
+    }
+}
+
+/// State of [`crypto_validation_middleware`]
+pub struct PublicAccess {
+    pub policy: PublicPathPolicy,
+    pub limiter: AnonymousLimiter,
+    /// Also [`crate::auth::token_cache::install`]ed for the handlers
+    pub tokens: Arc<TokenGate>,
+    /// Where `X-Api-Key` headers are checked; without it they count for
+    /// nothing and the request needs a token like any other
+    pub api_keys: Option<DatabaseConnection>,
+}
+
+impl PublicAccess {
+    /// `tokens` is the gate shared with the rest of the app, e.g. logout
+    pub fn from_config(config: &PublicAccessConfig, tokens: Arc<TokenGate>) -> Self {
+        Self {
+            policy: PublicPathPolicy::new(&config.rules),
+            limiter: AnonymousLimiter::new(config.anonymous_requests_per_minute, ANONYMOUS_WINDOW),
+            tokens,
+            api_keys: None,
+        }
+    }
+
+    /// Accept store API keys in place of a token, checked against `db`
+    pub fn with_api_keys(mut self, db: DatabaseConnection) -> Self {
+        self.api_keys = Some(db);
+        self
+    }
 }
 
 /// Extract token from Authorization header
@@ -37,64 +163,336 @@ fn extract_token(headers: &HeaderMap) -> Option<String> {
 }
 
 /// Cryptographic validation middleware
-/// This middleware ensures all incoming requests are properly authenticated
+///
+/// Requests need a valid token unless the public-path policy opens their
+/// method and path; those go through as [`RequestPrincipal::Anonymous`],
+/// rate limited per address. A known, unrevoked API key stands in for the
+/// token; the handler then checks the key's store and scopes. Every request
+/// let through carries its [`RequestPrincipal`] in the extensions.
 pub async fn crypto_validation_middleware(
-    request: Request,
+    State(access): State<Arc<PublicAccess>>,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let path = request.uri().path();
-
-    // Skip validation for public endpoints
-    if should_skip_validation(path) {
-        debug!(path = %path, "Skipping crypto validation for public endpoint");
-        return Ok(next.run(request).await);
-    }
+    let path = request.uri().path().to_string();
+    let public = access.policy.is_public(request.method(), &path);
 
-    debug!(path = %path, "Applying cryptographic validation");
-
-    // Extract headers for token check
-    let headers = request.headers().clone();
-
-    // Check for token authentication
-    if let Some(token) = extract_token(&headers) {
+    if let Some(token) = extract_token(request.headers()) {
         debug!(path = %path, "Detected bearer token, validating");
 
-        // Validate JWT using JwtService (env-based secret)
-        let jwt = JwtService::new()
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
-            .unwrap();
-        match jwt.validate_token(&token) {
+        match access.tokens.authenticate(&token) {
             Ok(claims) => {
                 info!(path = %path, relay_id = %claims.relay_id, "Authenticated request");
+                request
+                    .extensions_mut()
+                    .insert(RequestPrincipal::Token(claims));
                 return Ok(next.run(request).await);
             }
+            // A stale token should not lock a visitor out of public pages
+            Err(e) if public => {
+                debug!(path = %path, error = %e, "Ignoring invalid token on public route");
+            }
             Err(e) => {
                 warn!(path = %path, error = %e, "Invalid JWT token");
                 return Err(StatusCode::UNAUTHORIZED);
             }
         }
+    } else if let (Some(db), Some(raw_key)) =
+        (&access.api_keys, request.headers().get(API_KEY_HEADER))
+    {
+        let grant = match verify_api_key(db, raw_key).await {
+            Ok(grant) => grant,
+            Err((status, error)) => {
+                warn!(path = %path, error, "Rejected API key");
+                return Err(status);
+            }
+        };
+        request
+            .extensions_mut()
+            .insert(RequestPrincipal::ApiKey(grant));
+        return Ok(next.run(request).await);
+    }
+
+    if !public {
+        warn!(
+            path = %path,
+            "Request missing authentication token in Authorization header"
+        );
+        return Err(StatusCode::UNAUTHORIZED);
     }
 
-    // No token found - authentication required
-    warn!(
-        path = %path,
-        "Request missing authentication token in Authorization header"
-    );
-    Err(StatusCode::UNAUTHORIZED)
+    let client = get_client_ip(&request);
+    if !access.limiter.allow(&client, Instant::now()) {
+        warn!(path = %path, client = %client, "Anonymous rate limit exceeded");
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    debug!(path = %path, "Serving public route anonymously");
+    request.extensions_mut().insert(RequestPrincipal::Anonymous);
+    Ok(next.run(request).await)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::JwtService;
+    use crate::config::DEFAULT_PUBLIC_PATHS;
+
+    fn default_policy() -> PublicPathPolicy {
+        let rules: Vec<PublicPathRule> = DEFAULT_PUBLIC_PATHS
+            .iter()
+            .map(|(method, prefix)| PublicPathRule {
+                method: method.to_string(),
+                prefix: prefix.to_string(),
+            })
+            .collect();
+        PublicPathPolicy::new(&rules)
+    }
 
     #[test]
-    fn test_should_skip_validation() {
-        assert!(should_skip_validation("/healthz"));
-        assert!(should_skip_validation("/api/v1/pow/challenge"));
-        assert!(should_skip_validation("/api/v1/pow/verify"));
+    fn test_public_reads_are_open() {
+        let policy = default_policy();
+        assert!(policy.is_public(&Method::GET, "/healthz"));
+        assert!(policy.is_public(&Method::POST, "/api/v1/pow/challenge"));
+        assert!(policy.is_public(&Method::POST, "/api/v1/pow/verify"));
+        assert!(policy.is_public(&Method::GET, "/api/v1/products"));
+        assert!(policy.is_public(&Method::GET, "/api/v1/products/123/media"));
+        assert!(policy.is_public(&Method::HEAD, "/api/v1/stores/"));
+        assert!(policy.is_public(&Method::GET, "/api/v1/categories"));
+        assert!(policy.is_public(&Method::POST, "/api/v1/products/123/view"));
+    }
+
+    #[test]
+    fn test_wildcards_stand_for_one_segment() {
+        let policy = PublicPathPolicy::new(&[
+            PublicPathRule {
+                method: "POST".to_string(),
+                prefix: "/api/v1/products/*/view".to_string(),
+            },
+            PublicPathRule {
+                method: "POST".to_string(),
+                prefix: "/api/v1/products/featured".to_string(),
+            },
+        ]);
+        assert!(policy.is_public(&Method::POST, "/api/v1/products/123/view"));
+        assert!(policy.is_public(&Method::POST, "/api/v1/products/featured/view"));
+        assert!(policy.is_public(&Method::POST, "/api/v1/products/featured"));
+        assert!(!policy.is_public(&Method::POST, "/api/v1/products/123"));
+        assert!(!policy.is_public(&Method::POST, "/api/v1/products/123/stats"));
+        assert!(!policy.is_public(&Method::POST, "/api/v1/products/view"));
+    }
+
+    #[test]
+    fn test_writes_and_other_paths_are_protected() {
+        let policy = default_policy();
+        assert!(!policy.is_public(&Method::POST, "/api/v1/products"));
+        assert!(!policy.is_public(&Method::PUT, "/api/v1/stores/123"));
+        assert!(!policy.is_public(&Method::DELETE, "/api/v1/products/123"));
+        assert!(!policy.is_public(&Method::POST, "/api/v1/products/123/restore"));
+        assert!(!policy.is_public(&Method::GET, "/api/v1/pow/challenge"));
+        assert!(!policy.is_public(&Method::GET, "/api/v1/events"));
+        assert!(!policy.is_public(&Method::GET, "/api/v1/admin/summary"));
+        assert!(!policy.is_public(&Method::GET, "/some/other/path"));
+        // Prefixes match whole segments only
+        assert!(!policy.is_public(&Method::GET, "/api/v1/products-export"));
+        assert!(!policy.is_public(&Method::GET, "/api/v1"));
+    }
+
+    #[test]
+    fn test_anonymous_limiter_resets_each_window() {
+        let limiter = AnonymousLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(limiter.allow("10.0.0.1", start));
+        assert!(limiter.allow("10.0.0.1", start));
+        assert!(!limiter.allow("10.0.0.1", start));
+        assert!(limiter.allow("10.0.0.2", start));
+        assert!(limiter.allow("10.0.0.1", start + Duration::from_secs(60)));
+    }
+
+    const SECRET: &str = "middleware-test-secret-of-decent-length";
+
+    /// Plaintext of a new `products:write` key on `store_id`
+    async fn seed_api_key(db: &DatabaseConnection, store_id: uuid::Uuid, revoked: bool) -> String {
+        use crate::auth::api_key::{generate_api_key, hash_api_key};
+        use crate::entity::store_api_key;
+        use sea_orm::{EntityTrait, Set};
+
+        let secret = generate_api_key();
+        let key = store_api_key::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            store_id: Set(store_id),
+            key_hash: Set(hash_api_key(&secret)),
+            key_prefix: Set(secret[..10].to_string()),
+            label: Set("POS".to_string()),
+            scopes: Set("products:write".to_string()),
+            last_used_at: Set(None),
+            revoked: Set(revoked),
+            created_at: Set(chrono::Utc::now()),
+        };
+        // Inserted without RETURNING, which SQLite can't do for UUID keys
+        store_api_key::Entity::insert(key)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        secret
+    }
 
-        assert!(!should_skip_validation("/api/v1/events"));
-        assert!(!should_skip_validation("/some/other/path"));
+    fn access(rules: &[(&str, &str)]) -> PublicAccess {
+        PublicAccess::from_config(
+            &PublicAccessConfig {
+                rules: rules
+                    .iter()
+                    .map(|(method, prefix)| PublicPathRule {
+                        method: method.to_string(),
+                        prefix: prefix.to_string(),
+                    })
+                    .collect(),
+                anonymous_requests_per_minute: 10,
+                embed_requests_per_minute: 10,
+            },
+            Arc::new(TokenGate::new(Arc::new(JwtService::with_secret(SECRET)))),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_handlers_see_who_is_calling() {
+        use crate::db::testing;
+        use axum::{body::Body, routing::get, Extension, Router};
+        use tower::ServiceExt;
+
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let key = seed_api_key(&db, store_id, false).await;
+        let token = JwtService::with_secret(SECRET)
+            .generate_token("seller-1".into(), String::new(), "default".into())
+            .unwrap();
+
+        let app = Router::new()
+            .route(
+                "/api/v1/products",
+                get(
+                    |Extension(principal): Extension<RequestPrincipal>| async move {
+                        match principal {
+                            RequestPrincipal::Anonymous => "anonymous".to_string(),
+                            RequestPrincipal::Token(claims) => format!("token {}", claims.relay_id),
+                            RequestPrincipal::ApiKey(grant) => format!("key {}", grant.store_id),
+                        }
+                    },
+                ),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(access(&[("GET", "/api/v1/products")]).with_api_keys(db)),
+                crypto_validation_middleware,
+            ));
+        let caller = |header: Option<(&str, String)>| {
+            let mut request = Request::builder().uri("/api/v1/products");
+            if let Some((name, value)) = header {
+                request = request.header(name, value);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+
+        assert_eq!(caller(None).await, "anonymous");
+        assert_eq!(
+            caller(Some(("Authorization", format!("Bearer {token}")))).await,
+            "token seller-1"
+        );
+        assert_eq!(
+            caller(Some((API_KEY_HEADER, key))).await,
+            format!("key {store_id}")
+        );
+        // A stale token on a public route falls back to anonymous
+        assert_eq!(
+            caller(Some(("Authorization", "Bearer stale".to_string()))).await,
+            "anonymous"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_known_api_keys_stand_in_for_a_token() {
+        use crate::db::testing;
+        use axum::{body::Body, routing::post, Router};
+        use tower::ServiceExt;
+
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let key = seed_api_key(&db, store_id, false).await;
+        let revoked_key = seed_api_key(&db, store_id, true).await;
+
+        let status = |access: Arc<PublicAccess>, key: &str| {
+            let app = Router::new()
+                .route("/api/v1/products", post(|| async { "ok" }))
+                .layer(axum::middleware::from_fn_with_state(
+                    access,
+                    crypto_validation_middleware,
+                ));
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/api/v1/products")
+                .header(API_KEY_HEADER, key)
+                .body(Body::empty())
+                .unwrap();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        // Where keys can't be checked they don't open anything
+        assert_eq!(
+            status(Arc::new(access(&[])), &key).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        let access = Arc::new(access(&[]).with_api_keys(db));
+        assert_eq!(status(access.clone(), &key).await, StatusCode::OK);
+        for rejected in ["tk_made_up", revoked_key.as_str(), ""] {
+            assert_eq!(
+                status(access.clone(), rejected).await,
+                StatusCode::UNAUTHORIZED,
+                "{rejected}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_views_are_rate_limited() {
+        use crate::product_views::ViewRecorder;
+        use axum::{body::Body, Router};
+        use tower::ServiceExt;
+
+        let (recorder, mut queue) = ViewRecorder::new();
+        let app: Router = crate::api::product_views::router()
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(access(&[("POST", "/api/v1/products/*/view")])),
+                crypto_validation_middleware,
+            ))
+            .with_state(Arc::new(recorder));
+        let view = |ip: &str| {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/v1/products/{}/view", uuid::Uuid::new_v4()))
+                .header("x-forwarded-for", ip)
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        for _ in 0..10 {
+            assert_eq!(view("203.0.113.7").await, StatusCode::ACCEPTED);
+        }
+        assert_eq!(view("203.0.113.7").await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(view("203.0.113.8").await, StatusCode::ACCEPTED);
+        let mut queued = 0;
+        while queue.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert_eq!(queued, 11);
     }
 
     #[test]
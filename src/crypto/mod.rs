@@ -4,7 +4,9 @@
 //! - POW challenge generation and verification
 //! - Cryptographic middleware for request validation
 //! - Certificate-based authentication
+//! - Encryption of sensitive database fields
 
+pub mod field;
 pub mod middleware;
 pub mod pow;
 
@@ -32,6 +34,9 @@ pub mod types {
         pub challenge_id: String,
         pub nonce: u64,
         pub hash: String, // Base64 encoded hash result
+        /// How long the client took to solve it, for tuning the difficulty
+        #[serde(default)]
+        pub solve_time_ms: Option<u64>,
     }
 
     /// Proof of Work request for certificate issuance
@@ -61,6 +66,10 @@ pub mod types {
     pub struct VerificationRequest {
         pub solution: PowSolution,
         pub public_key: String,
+        /// May carry a `<tenant>:` prefix
         pub relay_id: String,
+        /// Tenant to join; defaults to the relay_id prefix, else `default`
+        #[serde(default)]
+        pub tenant_id: Option<String>,
     }
 }
@@ -1,18 +1,72 @@
 use base64::Engine;
 use chrono::{Duration, Utc};
 use rand::Rng;
+use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
 
 use super::types::{PowChallenge, PowSolution};
 use crate::error::AppError;
+use crate::metrics;
+
+/// Accepted solve times kept for [`PowService::difficulty_report`]
+const RECENT_SOLVES: usize = 1000;
+
+/// Why a solution was turned down
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rejection {
+    UnknownChallenge(String),
+    Expired,
+    InvalidHash,
+    InsufficientDifficulty(u32),
+}
+
+impl Rejection {
+    /// Outcome label on `transac_pow_verifications_total`
+    fn outcome(&self) -> &'static str {
+        match self {
+            Rejection::UnknownChallenge(_) => "unknown_challenge",
+            Rejection::Expired => "expired",
+            Rejection::InvalidHash => "invalid_hash",
+            Rejection::InsufficientDifficulty(_) => "insufficient_difficulty",
+        }
+    }
+}
+
+impl From<Rejection> for AppError {
+    fn from(rejection: Rejection) -> Self {
+        AppError::Validation(match rejection {
+            Rejection::UnknownChallenge(id) => format!("Challenge not found: {id}"),
+            Rejection::Expired => "Challenge has expired".to_string(),
+            Rejection::InvalidHash => "Invalid hash in solution".to_string(),
+            Rejection::InsufficientDifficulty(difficulty) => {
+                format!("Hash does not meet difficulty requirement of {difficulty} leading zeros")
+            }
+        })
+    }
+}
+
+/// What ops need to tune `POW_DIFFICULTY`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PowDifficultyReport {
+    /// Leading zero bits new challenges require
+    pub effective_difficulty: u32,
+    /// Accepted solutions that reported a solve time, up to the last 1000
+    pub recent_solves: usize,
+    pub p50_solve_ms: Option<u64>,
+    pub p90_solve_ms: Option<u64>,
+    pub p99_solve_ms: Option<u64>,
+}
 
 #[derive(Debug, Clone)]
 pub struct PowService {
     challenges: Arc<Mutex<HashMap<String, PowChallenge>>>,
     default_difficulty: u32,
     challenge_lifetime: Duration,
+    /// Client-reported solve times of accepted solutions, oldest first
+    recent_solves: Arc<Mutex<VecDeque<u64>>>,
 }
 
 impl PowService {
@@ -21,6 +75,7 @@ impl PowService {
             challenges: Arc::new(Mutex::new(HashMap::new())),
             default_difficulty: difficulty,
             challenge_lifetime: Duration::minutes(timeout_minutes),
+            recent_solves: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_SOLVES))),
         }
     }
 
@@ -41,39 +96,57 @@ impl PowService {
             .lock()
             .unwrap()
             .insert(challenge_id, challenge.clone());
+        metrics::POW_CHALLENGES_ISSUED.inc();
+        metrics::POW_DIFFICULTY_ISSUED.observe(f64::from(challenge.difficulty));
 
         Ok(challenge)
     }
 
     pub fn verify_solution(&self, solution: &PowSolution) -> Result<(), AppError> {
+        match self.check(solution) {
+            Ok(()) => {
+                metrics::POW_VERIFICATIONS.inc("success");
+                if let Some(solve_time_ms) = solution.solve_time_ms {
+                    self.record_solve(solve_time_ms);
+                }
+                Ok(())
+            }
+            Err(rejection) => {
+                metrics::POW_VERIFICATIONS.inc(rejection.outcome());
+                Err(rejection.into())
+            }
+        }
+    }
+
+    fn check(&self, solution: &PowSolution) -> Result<(), Rejection> {
         let challenge = self
             .challenges
             .lock()
             .unwrap()
             .get(&solution.challenge_id)
             .cloned()
-            .ok_or_else(|| {
-                AppError::Validation(format!("Challenge not found: {}", solution.challenge_id))
-            })?;
+            .ok_or_else(|| Rejection::UnknownChallenge(solution.challenge_id.clone()))?;
 
         if Utc::now() > challenge.expires_at {
             self.challenges
                 .lock()
                 .unwrap()
                 .remove(&solution.challenge_id);
-            return Err(AppError::Validation("Challenge has expired".to_string()));
+            return Err(Rejection::Expired);
         }
 
-        let computed_hash = self.compute_hash(&challenge.challenge_data, solution.nonce)?;
+        let computed_hash = self
+            .compute_hash(&challenge.challenge_data, solution.nonce)
+            .map_err(|_| Rejection::InvalidHash)?;
         if computed_hash != solution.hash {
-            return Err(AppError::Validation("Invalid hash in solution".to_string()));
+            return Err(Rejection::InvalidHash);
         }
 
-        if !self.meets_difficulty(&computed_hash, challenge.difficulty)? {
-            return Err(AppError::Validation(format!(
-                "Hash does not meet difficulty requirement of {} leading zeros",
-                challenge.difficulty
-            )));
+        if !self
+            .meets_difficulty(&computed_hash, challenge.difficulty)
+            .map_err(|_| Rejection::InvalidHash)?
+        {
+            return Err(Rejection::InsufficientDifficulty(challenge.difficulty));
         }
 
         self.challenges
@@ -84,6 +157,33 @@ impl PowService {
         Ok(())
     }
 
+    fn record_solve(&self, solve_time_ms: u64) {
+        metrics::POW_SOLVE_SECONDS.observe(solve_time_ms as f64 / 1000.0);
+        let mut recent = self.recent_solves.lock().unwrap();
+        if recent.len() == RECENT_SOLVES {
+            recent.pop_front();
+        }
+        recent.push_back(solve_time_ms);
+    }
+
+    /// Current difficulty and how long recent accepted solutions took
+    pub fn difficulty_report(&self) -> PowDifficultyReport {
+        let mut solves: Vec<u64> = self.recent_solves.lock().unwrap().iter().copied().collect();
+        solves.sort_unstable();
+        // Nearest-rank percentile
+        let percentile = |p: usize| {
+            let rank = (p * solves.len()).div_ceil(100);
+            solves.get(rank.saturating_sub(1)).copied()
+        };
+        PowDifficultyReport {
+            effective_difficulty: self.default_difficulty,
+            recent_solves: solves.len(),
+            p50_solve_ms: percentile(50),
+            p90_solve_ms: percentile(90),
+            p99_solve_ms: percentile(99),
+        }
+    }
+
     fn compute_hash(&self, challenge_data: &str, nonce: u64) -> Result<String, AppError> {
         let mut hasher = Sha256::new();
         hasher.update(challenge_data.as_bytes());
@@ -118,3 +218,110 @@ impl PowService {
         base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&random_bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{
+        POW_CHALLENGES_ISSUED, POW_DIFFICULTY_ISSUED, POW_SOLVE_SECONDS, POW_VERIFICATIONS,
+    };
+
+    /// A nonce whose hash does (or, with `meets` false, doesn't) clear the
+    /// challenge's difficulty, and that hash
+    fn solve(service: &PowService, challenge: &PowChallenge, meets: bool) -> (u64, String) {
+        (0..)
+            .map(|nonce| {
+                let hash = service
+                    .compute_hash(&challenge.challenge_data, nonce)
+                    .unwrap();
+                (nonce, hash)
+            })
+            .find(|(_, hash)| {
+                service
+                    .meets_difficulty(hash, challenge.difficulty)
+                    .unwrap()
+                    == meets
+            })
+            .unwrap()
+    }
+
+    fn solution(challenge: &PowChallenge, (nonce, hash): (u64, String)) -> PowSolution {
+        PowSolution {
+            challenge_id: challenge.challenge_id.clone(),
+            nonce,
+            hash,
+            solve_time_ms: Some(1200),
+        }
+    }
+
+    #[test]
+    fn test_metrics_count_issuance_and_every_outcome() {
+        let service = PowService::new(4, 10);
+        let issued = POW_CHALLENGES_ISSUED.get();
+        let difficulties = POW_DIFFICULTY_ISSUED.count();
+        let challenge = service.generate_challenge().unwrap();
+        assert_eq!(POW_CHALLENGES_ISSUED.get(), issued + 1);
+        assert_eq!(POW_DIFFICULTY_ISSUED.count(), difficulties + 1);
+
+        let cases = [
+            ("unknown_challenge", {
+                let mut unknown = solution(&challenge, solve(&service, &challenge, true));
+                unknown.challenge_id = "nope".to_string();
+                unknown
+            }),
+            ("invalid_hash", {
+                let (nonce, _) = solve(&service, &challenge, true);
+                solution(&challenge, (nonce, "AAAA".to_string()))
+            }),
+            (
+                "insufficient_difficulty",
+                solution(&challenge, solve(&service, &challenge, false)),
+            ),
+        ];
+        for (outcome, attempt) in cases {
+            let before = POW_VERIFICATIONS.get(outcome);
+            assert!(service.verify_solution(&attempt).is_err(), "{outcome}");
+            assert_eq!(POW_VERIFICATIONS.get(outcome), before + 1, "{outcome}");
+        }
+
+        let expiring = PowService::new(4, -1);
+        let stale = expiring.generate_challenge().unwrap();
+        let before = POW_VERIFICATIONS.get("expired");
+        let attempt = solution(&stale, solve(&expiring, &stale, true));
+        assert!(expiring.verify_solution(&attempt).is_err());
+        assert_eq!(POW_VERIFICATIONS.get("expired"), before + 1);
+
+        let (successes, solves) = (POW_VERIFICATIONS.get("success"), POW_SOLVE_SECONDS.count());
+        let accepted = solution(&challenge, solve(&service, &challenge, true));
+        service.verify_solution(&accepted).unwrap();
+        assert_eq!(POW_VERIFICATIONS.get("success"), successes + 1);
+        assert_eq!(POW_SOLVE_SECONDS.count(), solves + 1);
+        assert_eq!(service.difficulty_report().p50_solve_ms, Some(1200));
+
+        let rendered = metrics::render();
+        assert!(rendered.contains("transac_pow_verifications_total{outcome=\"expired\"}"));
+        assert!(rendered.contains("transac_pow_solve_seconds_bucket{le=\"+Inf\"}"));
+    }
+
+    #[test]
+    fn test_report_gives_nearest_rank_percentiles() {
+        let service = PowService::new(12, 10);
+        assert_eq!(service.difficulty_report().p50_solve_ms, None);
+        service
+            .recent_solves
+            .lock()
+            .unwrap()
+            .extend((1..=RECENT_SOLVES as u64).map(|ms| ms * 10));
+        let report = service.difficulty_report();
+        assert_eq!(report.effective_difficulty, 12);
+        assert_eq!(report.recent_solves, RECENT_SOLVES);
+        assert_eq!(
+            (
+                report.p50_solve_ms,
+                report.p90_solve_ms,
+                report.p99_solve_ms
+            ),
+            (Some(5000), Some(9000), Some(9900))
+        );
+    }
+}
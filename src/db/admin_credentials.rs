@@ -0,0 +1,85 @@
+use crate::auth::signing::{generate_admin_secret, signing_key};
+use crate::crypto::field::FieldCipher;
+use crate::entity::admin_credential::{
+    self, ActiveModel as AdminCredentialActiveModel, Entity as AdminCredentialEntity,
+    Model as AdminCredentialModel,
+};
+use chrono::Utc;
+use sea_orm::{sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+pub struct AdminCredential;
+
+impl AdminCredential {
+    /// Issue a signing credential for an admin device, returning it alongside
+    /// the plaintext secret. The secret cannot be recovered afterwards; its
+    /// signing key is stored sealed with `cipher`.
+    #[allow(dead_code)] // used by the admin_credential binary
+    pub async fn create(
+        db: &DatabaseConnection,
+        cipher: &FieldCipher,
+        relay_id: &str,
+        label: &str,
+    ) -> Result<(AdminCredentialModel, String), String> {
+        let secret = generate_admin_secret();
+        let credential = AdminCredentialModel {
+            id: Uuid::new_v4(),
+            relay_id: relay_id.to_owned(),
+            label: label.to_owned(),
+            signing_key_encrypted: cipher.encrypt(&signing_key(&secret))?,
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+        let active: AdminCredentialActiveModel = credential.clone().into();
+        AdminCredentialEntity::insert(active)
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to create admin credential for {}: {:?}",
+                    relay_id, e
+                );
+                "Failed to create admin credential. Please try again later.".to_string()
+            })?;
+        debug!(
+            "Admin credential {} created for {}",
+            credential.id, relay_id
+        );
+        Ok((credential, secret))
+    }
+
+    /// The credential with this id, unless it was revoked
+    pub async fn find_active(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<AdminCredentialModel>, String> {
+        AdminCredentialEntity::find_by_id(id)
+            .filter(admin_credential::Column::RevokedAt.is_null())
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up admin credential {}: {:?}", id, e);
+                "Failed to verify request signature. Please try again later.".to_string()
+            })
+    }
+
+    /// Returns false if no active credential has this id
+    #[allow(dead_code)] // used by the admin_credential binary
+    pub async fn revoke(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let result = AdminCredentialEntity::update_many()
+            .col_expr(
+                admin_credential::Column::RevokedAt,
+                Expr::value(Some(Utc::now())),
+            )
+            .filter(admin_credential::Column::Id.eq(id))
+            .filter(admin_credential::Column::RevokedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to revoke admin credential {}: {:?}", id, e);
+                "Failed to revoke admin credential. Please try again later.".to_string()
+            })?;
+        Ok(result.rows_affected > 0)
+    }
+}
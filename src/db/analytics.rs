@@ -0,0 +1,112 @@
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, FromQueryResult, Statement};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// Window used for the "recent" half of every count
+pub const DELTA_WINDOW_DAYS: i64 = 7;
+
+/// A running total and how much of it was added in the last 7 days
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema, FromQueryResult)]
+pub struct CountWithDelta {
+    pub total: i64,
+    pub last_7_days: i64,
+}
+
+/// One tenant's totals for the internal dashboard
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct AdminSummary {
+    pub tenant_id: String,
+    /// Distinct devices owning at least one store
+    pub sellers: CountWithDelta,
+    pub stores: CountWithDelta,
+    pub products: CountWithDelta,
+    pub media_files: CountWithDelta,
+    /// Stored bytes, originals plus WebP variants
+    pub media_storage_bytes: CountWithDelta,
+    pub generated_at: DateTime<Utc>,
+}
+
+const SELLERS_SQL: &str = r#"
+    SELECT COUNT(DISTINCT owner_device_id)::BIGINT AS total,
+           COUNT(DISTINCT owner_device_id) FILTER (WHERE first_store_at >= $1)::BIGINT AS last_7_days
+    FROM (
+        SELECT owner_device_id, MIN(created_at) AS first_store_at
+        FROM stores
+        WHERE owner_device_id IS NOT NULL AND tenant_id = $2
+        GROUP BY owner_device_id
+    ) owners
+"#;
+
+const STORES_SQL: &str = r#"
+    SELECT COUNT(*)::BIGINT AS total,
+           COUNT(*) FILTER (WHERE created_at >= $1)::BIGINT AS last_7_days
+    FROM stores
+    WHERE tenant_id = $2
+"#;
+
+const PRODUCTS_SQL: &str = r#"
+    SELECT COUNT(*)::BIGINT AS total,
+           COUNT(*) FILTER (WHERE created_at >= $1)::BIGINT AS last_7_days
+    FROM products
+    WHERE tenant_id = $2
+"#;
+
+const MEDIA_FILES_SQL: &str = r#"
+    SELECT COUNT(*)::BIGINT AS total,
+           COUNT(*) FILTER (WHERE created_at >= $1)::BIGINT AS last_7_days
+    FROM product_media
+    WHERE product_id IN (SELECT id FROM products WHERE tenant_id = $2)
+"#;
+
+const MEDIA_BYTES_SQL: &str = r#"
+    SELECT COALESCE(SUM(size_bytes + COALESCE(webp_size_bytes, 0)), 0)::BIGINT AS total,
+           COALESCE(SUM(size_bytes + COALESCE(webp_size_bytes, 0))
+               FILTER (WHERE created_at >= $1), 0)::BIGINT AS last_7_days
+    FROM product_media
+    WHERE product_id IN (SELECT id FROM products WHERE tenant_id = $2)
+"#;
+
+pub struct Analytics;
+
+impl Analytics {
+    /// Compute a tenant's dashboard summary with one aggregate query per metric
+    pub async fn admin_summary(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<AdminSummary, String> {
+        let since = now - Duration::days(DELTA_WINDOW_DAYS);
+        Ok(AdminSummary {
+            tenant_id: tenant_id.to_string(),
+            sellers: count_with_delta(db, SELLERS_SQL, tenant_id, since).await?,
+            stores: count_with_delta(db, STORES_SQL, tenant_id, since).await?,
+            products: count_with_delta(db, PRODUCTS_SQL, tenant_id, since).await?,
+            media_files: count_with_delta(db, MEDIA_FILES_SQL, tenant_id, since).await?,
+            media_storage_bytes: count_with_delta(db, MEDIA_BYTES_SQL, tenant_id, since).await?,
+            generated_at: now,
+        })
+    }
+}
+
+async fn count_with_delta(
+    db: &DatabaseConnection,
+    sql: &str,
+    tenant_id: &str,
+    since: DateTime<Utc>,
+) -> Result<CountWithDelta, String> {
+    let stmt =
+        Statement::from_sql_and_values(DbBackend::Postgres, sql, [since.into(), tenant_id.into()]);
+    let row = db.query_one(stmt).await.map_err(|e| {
+        error!("Failed to compute admin summary: {:?}", e);
+        "Failed to compute summary. Please try again later.".to_string()
+    })?;
+    match row {
+        Some(row) => CountWithDelta::from_query_result(&row, "").map_err(|e| {
+            error!("Failed to read admin summary row: {:?}", e);
+            "Failed to compute summary. Please try again later.".to_string()
+        }),
+        None => Ok(CountWithDelta::default()),
+    }
+}
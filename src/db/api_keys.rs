@@ -0,0 +1,106 @@
+use crate::auth::api_key::{generate_api_key, hash_api_key, ApiScope};
+use crate::entity::store_api_key::{
+    self, ActiveModel as ApiKeyActiveModel, Entity as ApiKeyEntity, Model as ApiKeyModel,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Characters of the plaintext key kept for display
+const KEY_PREFIX_LEN: usize = 10;
+
+pub struct ApiKey;
+
+impl ApiKey {
+    /// Create a key for a store, returning it alongside the plaintext secret.
+    /// The secret cannot be recovered afterwards.
+    pub async fn create(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        label: &str,
+        scopes: &[ApiScope],
+    ) -> Result<(ApiKeyModel, String), String> {
+        let secret = generate_api_key();
+        let scopes: Vec<&str> = scopes.iter().map(|s| s.as_str()).collect();
+        let key = ApiKeyActiveModel {
+            id: Set(Uuid::new_v4()),
+            store_id: Set(store_id),
+            key_hash: Set(hash_api_key(&secret)),
+            key_prefix: Set(secret.chars().take(KEY_PREFIX_LEN).collect()),
+            label: Set(label.to_owned()),
+            scopes: Set(scopes.join(" ")),
+            last_used_at: Set(None),
+            revoked: Set(false),
+            created_at: Set(Utc::now()),
+        };
+        let res = key.insert(db).await.map_err(|e| {
+            error!("Failed to create API key for store {}: {:?}", store_id, e);
+            "Failed to create API key. Please try again later.".to_string()
+        })?;
+        debug!("API key {} created for store {}", res.id, store_id);
+        Ok((res, secret))
+    }
+
+    pub async fn list_by_store(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+    ) -> Result<Vec<ApiKeyModel>, String> {
+        ApiKeyEntity::find()
+            .filter(store_api_key::Column::StoreId.eq(store_id))
+            .order_by_desc(store_api_key::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list API keys for store {}: {:?}", store_id, e);
+                "Failed to list API keys. Please try again later.".to_string()
+            })
+    }
+
+    pub async fn find_by_hash(
+        db: &DatabaseConnection,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyModel>, String> {
+        ApiKeyEntity::find()
+            .filter(store_api_key::Column::KeyHash.eq(key_hash))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up API key: {:?}", e);
+                "Failed to verify API key.".to_string()
+            })
+    }
+
+    /// Revoke a store's key; revoked keys stay listed but stop authenticating.
+    /// Returns false when the store has no such key.
+    pub async fn revoke(db: &DatabaseConnection, store_id: Uuid, id: Uuid) -> Result<bool, String> {
+        let res = ApiKeyEntity::update_many()
+            .col_expr(store_api_key::Column::Revoked, Expr::value(true))
+            .filter(store_api_key::Column::Id.eq(id))
+            .filter(store_api_key::Column::StoreId.eq(store_id))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to revoke API key {}: {:?}", id, e);
+                "Failed to revoke API key. Please try again later.".to_string()
+            })?;
+        debug!("API key {} revoked: {}", id, res.rows_affected > 0);
+        Ok(res.rows_affected > 0)
+    }
+
+    pub async fn touch(db: &DatabaseConnection, id: Uuid, at: DateTime<Utc>) -> Result<(), String> {
+        ApiKeyEntity::update_many()
+            .col_expr(store_api_key::Column::LastUsedAt, Expr::value(at))
+            .filter(store_api_key::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to record use of API key {}: {:?}", id, e);
+                "Failed to record API key usage.".to_string()
+            })?;
+        Ok(())
+    }
+}
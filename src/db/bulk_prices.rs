@@ -0,0 +1,497 @@
+//! Store-wide price changes, applied in one transaction with a price-history
+//! row for every product that changed, and per-product price and stock edits
+//! across a seller's listings.
+
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::db::product_counts::ProductCounts;
+use crate::db::products::{active_sale, price_error};
+use crate::entity::product::{
+    self, ActiveModel as ProductActiveModel, Entity as ProductEntity, Model as ProductModel,
+};
+use crate::entity::product_price_history::{
+    ActiveModel as PriceHistoryActiveModel, Entity as PriceHistoryEntity,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// `source` of the price-history rows written by a bulk update
+pub const BULK_PRICE_SOURCE: &str = "bulk";
+
+/// How a bulk update sets prices
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceAdjustment {
+    /// Every product of the store by this percentage, rounded to whole units
+    Percent(f64),
+    /// Every product of the store by a fixed amount
+    Delta(f64),
+    /// Explicit prices for the listed products
+    Set(Vec<(Uuid, f64)>),
+}
+
+/// Percentage results are rounded half away from zero to whole units, as XAF
+/// has no minor unit
+pub fn adjust_by_percent(price: f64, percent: f64) -> f64 {
+    (price * (100.0 + percent) / 100.0).round()
+}
+
+impl PriceAdjustment {
+    fn new_price(&self, product: &ProductModel, explicit: &HashMap<Uuid, f64>) -> f64 {
+        match self {
+            PriceAdjustment::Percent(percent) => adjust_by_percent(product.price, *percent),
+            PriceAdjustment::Delta(delta) => product.price + delta,
+            PriceAdjustment::Set(_) => explicit[&product.id],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceChangeStatus {
+    Updated,
+    /// The product already had this price
+    Unchanged,
+    /// Not a product of this store
+    NotFound,
+    /// The new price would break the product; it was left as is
+    Rejected,
+}
+
+/// What a bulk update did, or would do, to one product
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PriceChange {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub status: PriceChangeStatus,
+    pub previous_price: Option<f64>,
+    /// The price after the update; the refused price when rejected
+    pub price: Option<f64>,
+    pub message: Option<String>,
+}
+
+/// Check a new regular price against the product it targets. Ok(false)
+/// means the price is unchanged.
+pub fn check_price(product: &ProductModel, price: f64, now: DateTime<Utc>) -> Result<bool, String> {
+    if let Some(problem) = price_error("price", price) {
+        return Err(problem);
+    }
+    if active_sale(product, now).is_some_and(|sale| sale.price >= price) {
+        return Err("price must stay above the current sale price".to_string());
+    }
+    Ok(product.price != price)
+}
+
+/// A new price and/or stock level for one product
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProductEdit {
+    pub product_id: Uuid,
+    pub price: Option<f64>,
+    pub quantity_available: Option<i32>,
+}
+
+pub struct BulkPrice;
+
+impl BulkPrice {
+    /// Apply `adjustment` to the store's products in one transaction. With
+    /// `dry_run` the same writes are made and then rolled back, so the
+    /// result is exactly what a real run would return.
+    ///
+    /// For explicit prices the changes follow the request order; otherwise
+    /// they follow product creation order.
+    pub async fn apply(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        adjustment: &PriceAdjustment,
+        dry_run: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<PriceChange>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Bulk price update for store {} failed: {:?}", store_id, e);
+            "Failed to update prices. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+
+        let explicit: HashMap<Uuid, f64> = match adjustment {
+            PriceAdjustment::Set(prices) => prices.iter().copied().collect(),
+            _ => HashMap::new(),
+        };
+        let mut query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .filter(product::Column::ArchivedAt.is_null());
+        if let PriceAdjustment::Set(_) = adjustment {
+            query = query.filter(product::Column::Id.is_in(explicit.keys().copied()));
+        }
+        let products = query
+            .order_by_asc(product::Column::CreatedAt)
+            .order_by_asc(product::Column::Id)
+            .all(&txn)
+            .await
+            .map_err(fail)?;
+
+        let mut changes = HashMap::with_capacity(products.len());
+        let mut order = Vec::with_capacity(products.len());
+        let mut history = Vec::new();
+        for product in products {
+            let price = adjustment.new_price(&product, &explicit);
+            let previous_price = product.price;
+            let (status, message) = match check_price(&product, price, now) {
+                Err(reason) => (PriceChangeStatus::Rejected, Some(reason)),
+                Ok(false) => (PriceChangeStatus::Unchanged, None),
+                Ok(true) => {
+                    history.push(PriceHistoryActiveModel {
+                        id: Set(Uuid::new_v4()),
+                        product_id: Set(product.id),
+                        store_id: Set(store_id),
+                        previous_price: Set(previous_price),
+                        price: Set(price),
+                        source: Set(BULK_PRICE_SOURCE.to_string()),
+                        created_at: Set(now),
+                    });
+                    let mut active: ProductActiveModel = product.clone().into();
+                    active.price = Set(price);
+                    active.updated_at = Set(now);
+                    active.update(&txn).await.map_err(fail)?;
+                    (PriceChangeStatus::Updated, None)
+                }
+            };
+            order.push(product.id);
+            changes.insert(
+                product.id,
+                PriceChange {
+                    product_id: product.id,
+                    status,
+                    previous_price: Some(previous_price),
+                    price: Some(price),
+                    message,
+                },
+            );
+        }
+        if !history.is_empty() {
+            PriceHistoryEntity::insert_many(history)
+                .exec_without_returning(&txn)
+                .await
+                .map_err(fail)?;
+        }
+
+        if dry_run {
+            txn.rollback().await.map_err(fail)?;
+        } else {
+            txn.commit().await.map_err(fail)?;
+            debug!(store_id = %store_id, "Bulk price update applied");
+        }
+
+        Ok(match adjustment {
+            PriceAdjustment::Set(prices) => prices
+                .iter()
+                .map(|(product_id, price)| {
+                    changes.remove(product_id).unwrap_or(PriceChange {
+                        product_id: *product_id,
+                        status: PriceChangeStatus::NotFound,
+                        previous_price: None,
+                        price: Some(*price),
+                        message: None,
+                    })
+                })
+                .collect(),
+            _ => order.iter().filter_map(|id| changes.remove(id)).collect(),
+        })
+    }
+
+    /// The products with these IDs, whichever store they belong to
+    pub async fn products<C: ConnectionTrait>(
+        conn: &C,
+        ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, ProductModel>, String> {
+        let products = ProductEntity::find()
+            .filter(product::Column::Id.is_in(ids.iter().copied()))
+            .all(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to load products for a bulk edit: {:?}", e);
+                "Failed to load products. Please try again later.".to_string()
+            })?;
+        Ok(products.into_iter().map(|p| (p.id, p)).collect())
+    }
+
+    /// Save `edits` to the `products` they target. The caller owns the
+    /// transaction `conn` belongs to. Price changes get a price-history row
+    /// and changed products an audit entry. Returns the products after the
+    /// edit, in `edits` order.
+    pub async fn edit<C: ConnectionTrait>(
+        conn: &C,
+        products: &HashMap<Uuid, ProductModel>,
+        edits: &[ProductEdit],
+        origin: ChangeOrigin<'_>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<ProductModel>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Bulk product edit failed: {:?}", e);
+            "Failed to update products. Please try again later.".to_string()
+        };
+        let mut updated = Vec::with_capacity(edits.len());
+        let mut history = Vec::new();
+        for edit in edits {
+            let product = products
+                .get(&edit.product_id)
+                .ok_or_else(|| "Product not found.".to_string())?;
+            let mut active: ProductActiveModel = product.clone().into();
+            if let Some(price) = edit.price.filter(|price| *price != product.price) {
+                active.price = Set(price);
+                history.push(PriceHistoryActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    product_id: Set(product.id),
+                    store_id: Set(product.store_id),
+                    previous_price: Set(product.price),
+                    price: Set(price),
+                    source: Set(BULK_PRICE_SOURCE.to_string()),
+                    created_at: Set(now),
+                });
+            }
+            if let Some(quantity) = edit
+                .quantity_available
+                .filter(|quantity| *quantity != product.quantity_available)
+            {
+                active.quantity_available = Set(quantity);
+            }
+            if !active.is_changed() {
+                updated.push(product.clone());
+                continue;
+            }
+            active.updated_at = Set(now);
+            let after = active.update(conn).await.map_err(fail)?;
+            ProductCounts::record(conn, Some(product), Some(&after)).await?;
+            AuditLog::record_product(conn, product, &after, origin).await?;
+            updated.push(after);
+        }
+        if !history.is_empty() {
+            PriceHistoryEntity::insert_many(history)
+                .exec_without_returning(conn)
+                .await
+                .map_err(fail)?;
+        }
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::product_price_history;
+    use sea_orm::PaginatorTrait;
+
+    async fn seed_priced_product(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        price: f64,
+        sale_price: Option<f64>,
+    ) -> Uuid {
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let product = ProductActiveModel {
+            id: Set(id),
+            store_id: Set(store_id),
+            tenant_id: Set("default".to_string()),
+            sku: Set(None),
+            name: Set("Ndole spice".to_string()),
+            description: Set(None),
+            price: Set(price),
+            currency: Set("XAF".to_string()),
+            sale_price: Set(sale_price),
+            sale_ends_at: Set(sale_price.map(|_| now + chrono::Duration::days(1))),
+            quantity_available: Set(1),
+            low_stock_threshold: Set(None),
+            image_id: Set(None),
+            category_id: Set(None),
+            attributes: Set(serde_json::json!({})),
+            return_policy: Set(None),
+            return_policy_source: Set("none".to_string()),
+            returns_accepted: Set(false),
+            return_window_days: Set(None),
+            return_conditions: Set(None),
+            pickup_available: Set(true),
+            delivery_available: Set(false),
+            delivery_fee_override: Set(None),
+            delivery_estimated_days: Set(None),
+            delivery_options_source: Set("store_default".to_string()),
+            publish_at: Set(None),
+            is_published: Set(true),
+            archived_at: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        ProductEntity::insert(product)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        id
+    }
+
+    #[test]
+    fn test_percent_rounds_to_whole_units() {
+        assert_eq!(adjust_by_percent(1250.0, 10.0), 1375.0);
+        assert_eq!(adjust_by_percent(999.0, 5.0), 1049.0);
+        // Halves round away from zero
+        assert_eq!(adjust_by_percent(10.0, 5.0), 11.0);
+        assert_eq!(adjust_by_percent(1000.0, -12.5), 875.0);
+        assert_eq!(adjust_by_percent(1000.0, 7.0), 1070.0);
+        assert_eq!(adjust_by_percent(3.0, -50.0), 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_matches_the_real_run() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let plain = seed_priced_product(&db, store_id, 999.0, None).await;
+        // 15% off would undercut the running sale, so the product is rejected
+        let on_sale = seed_priced_product(&db, store_id, 1000.0, Some(900.0)).await;
+        let free = seed_priced_product(&db, store_id, 0.0, None).await;
+        let other_store = testing::seed_product(&db, "seller-2").await;
+
+        let adjustment = PriceAdjustment::Percent(-15.0);
+        let preview = BulkPrice::apply(&db, store_id, &adjustment, true, Utc::now())
+            .await
+            .unwrap();
+        let statuses: Vec<_> = preview.iter().map(|c| (c.product_id, c.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (plain, PriceChangeStatus::Updated),
+                (on_sale, PriceChangeStatus::Rejected),
+                (free, PriceChangeStatus::Unchanged),
+            ]
+        );
+        assert_eq!(preview[0].price, Some(849.0));
+        let price_of = |id| {
+            let db = db.clone();
+            async move {
+                ProductEntity::find_by_id(id)
+                    .one(&db)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .price
+            }
+        };
+        assert_eq!(price_of(plain).await, 999.0);
+        assert_eq!(
+            product_price_history::Entity::find()
+                .count(&db)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let applied = BulkPrice::apply(&db, store_id, &adjustment, false, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(applied, preview);
+        assert_eq!(price_of(plain).await, 849.0);
+        assert_eq!(price_of(on_sale).await, 1000.0);
+        assert_eq!(price_of(other_store).await, 5000.0);
+        let history = product_price_history::Entity::find()
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            (
+                history[0].product_id,
+                history[0].previous_price,
+                history[0].price
+            ),
+            (plain, 999.0, 849.0)
+        );
+
+        // Explicit prices follow the request; other stores' products are not found
+        let missing = Uuid::new_v4();
+        let set = PriceAdjustment::Set(vec![(other_store, 1.0), (free, -5.0), (missing, 10.0)]);
+        let changes = BulkPrice::apply(&db, store_id, &set, false, Utc::now())
+            .await
+            .unwrap();
+        let statuses: Vec<_> = changes.iter().map(|c| c.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                PriceChangeStatus::NotFound,
+                PriceChangeStatus::Rejected,
+                PriceChangeStatus::NotFound,
+            ]
+        );
+        assert_eq!(price_of(other_store).await, 5000.0);
+    }
+
+    #[tokio::test]
+    async fn test_edit_writes_history_for_changed_prices_only() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let repriced = seed_priced_product(&db, store_id, 1000.0, None).await;
+        let restocked = seed_priced_product(&db, store_id, 500.0, None).await;
+        let untouched = seed_priced_product(&db, store_id, 750.0, None).await;
+
+        let edits = [
+            ProductEdit {
+                product_id: repriced,
+                price: Some(1200.0),
+                quantity_available: None,
+            },
+            ProductEdit {
+                product_id: restocked,
+                price: Some(500.0),
+                quantity_available: Some(40),
+            },
+            ProductEdit {
+                product_id: untouched,
+                price: None,
+                quantity_available: Some(1),
+            },
+        ];
+        let txn = db.begin().await.unwrap();
+        let products = BulkPrice::products(&txn, &[repriced, restocked, untouched])
+            .await
+            .unwrap();
+        let updated = BulkPrice::edit(
+            &txn,
+            &products,
+            &edits,
+            ChangeOrigin::edit(Some("device-1")),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        txn.commit().await.unwrap();
+
+        let saved: Vec<_> = updated
+            .iter()
+            .map(|p| (p.id, p.price, p.quantity_available))
+            .collect();
+        assert_eq!(
+            saved,
+            vec![
+                (repriced, 1200.0, 1),
+                (restocked, 500.0, 40),
+                (untouched, 750.0, 1),
+            ]
+        );
+        let history = product_price_history::Entity::find()
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            (
+                history[0].product_id,
+                history[0].price,
+                history[0].source.as_str()
+            ),
+            (repriced, 1200.0, BULK_PRICE_SOURCE)
+        );
+    }
+}
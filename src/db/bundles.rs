@@ -0,0 +1,442 @@
+//! Product bundles: several of a store's products sold together at one price.
+//! A bundle has no stock of its own; it is available as long as every
+//! component is.
+
+use crate::entity::bundle_item::{
+    self, ActiveModel as BundleItemActiveModel, Entity as BundleItemEntity,
+    Model as BundleItemModel,
+};
+use crate::entity::product::{self, Entity as ProductEntity, Model as ProductModel};
+use crate::entity::product_bundle::{
+    self, ActiveModel as BundleActiveModel, Entity as BundleEntity, Model as BundleModel,
+};
+use crate::entity::store;
+use crate::tenant::tenant_condition;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::{Expr, Query},
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait, UpdateMany,
+};
+use std::collections::HashMap;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// A component as sellers specify it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BundleComponent {
+    pub product_id: Uuid,
+    /// Units of the product in one bundle
+    pub quantity: i32,
+}
+
+/// A bundle with its components and their products
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleDetails {
+    pub bundle: BundleModel,
+    pub items: Vec<(BundleItemModel, ProductModel)>,
+}
+
+impl BundleDetails {
+    /// Bundles that can be sold right now, see [`availability`]
+    pub fn availability(&self) -> i32 {
+        let components: Vec<(i32, i32)> = self
+            .items
+            .iter()
+            .map(|(item, product)| (item.quantity, product.quantity_available))
+            .collect();
+        availability(&components)
+    }
+}
+
+/// How many bundles the component stock covers: the minimum over components
+/// of `stock / required`. `components` holds `(required, stock)` pairs; a
+/// bundle without components is never available.
+pub fn availability(components: &[(i32, i32)]) -> i32 {
+    components
+        .iter()
+        .map(|&(required, stock)| {
+            if required <= 0 {
+                0
+            } else {
+                stock.max(0) / required
+            }
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// Units to take from each component when `bundles` bundles are sold
+pub fn stock_decrements(components: &[BundleComponent], bundles: i32) -> Vec<(Uuid, i32)> {
+    components
+        .iter()
+        .map(|c| (c.product_id, c.quantity.saturating_mul(bundles)))
+        .collect()
+}
+
+/// Take `units` from a product's stock, only if it has that many left
+fn decrement_query(product_id: Uuid, units: i32, now: DateTime<Utc>) -> UpdateMany<ProductEntity> {
+    ProductEntity::update_many()
+        .col_expr(
+            product::Column::QuantityAvailable,
+            Expr::col(product::Column::QuantityAvailable).sub(units),
+        )
+        .col_expr(product::Column::UpdatedAt, Expr::value(now))
+        .filter(product::Column::Id.eq(product_id))
+        .filter(product::Column::QuantityAvailable.gte(units))
+}
+
+/// Result of selling bundles
+#[derive(Debug, Clone, PartialEq)]
+pub enum PurchaseOutcome {
+    /// Stock taken; the components after the decrement
+    Purchased(Vec<ProductModel>),
+    /// This component has too little stock; nothing was taken
+    OutOfStock(Uuid),
+    /// The bundle does not exist or is inactive
+    Unavailable,
+}
+
+pub struct Bundle;
+
+impl Bundle {
+    async fn details<C: ConnectionTrait>(
+        conn: &C,
+        bundles: Vec<BundleModel>,
+    ) -> Result<Vec<BundleDetails>, sea_orm::DbErr> {
+        let ids: Vec<Uuid> = bundles.iter().map(|b| b.id).collect();
+        let mut items: HashMap<Uuid, Vec<(BundleItemModel, ProductModel)>> = HashMap::new();
+        for (item, product) in BundleItemEntity::find()
+            .filter(bundle_item::Column::BundleId.is_in(ids))
+            .find_also_related(ProductEntity)
+            .order_by_asc(bundle_item::Column::Id)
+            .all(conn)
+            .await?
+        {
+            if let Some(product) = product {
+                items
+                    .entry(item.bundle_id)
+                    .or_default()
+                    .push((item, product));
+            }
+        }
+        Ok(bundles
+            .into_iter()
+            .map(|bundle| BundleDetails {
+                items: items.remove(&bundle.id).unwrap_or_default(),
+                bundle,
+            })
+            .collect())
+    }
+
+    async fn replace_items<C: ConnectionTrait>(
+        conn: &C,
+        bundle_id: Uuid,
+        components: &[BundleComponent],
+    ) -> Result<(), sea_orm::DbErr> {
+        BundleItemEntity::delete_many()
+            .filter(bundle_item::Column::BundleId.eq(bundle_id))
+            .exec(conn)
+            .await?;
+        for component in components {
+            BundleItemActiveModel {
+                id: Set(Uuid::new_v4()),
+                bundle_id: Set(bundle_id),
+                product_id: Set(component.product_id),
+                quantity: Set(component.quantity),
+            }
+            .insert(conn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Those of `product_ids` that are not live products of the store;
+    /// archived products count as gone
+    pub async fn foreign_products(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        product_ids: &[Uuid],
+    ) -> Result<Vec<Uuid>, String> {
+        let owned: Vec<Uuid> = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .filter(product::Column::ArchivedAt.is_null())
+            .filter(product::Column::Id.is_in(product_ids.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to check bundle products: {:?}", e);
+                "Failed to check bundle products. Please try again later.".to_string()
+            })?
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        Ok(product_ids
+            .iter()
+            .filter(|id| !owned.contains(id))
+            .copied()
+            .collect())
+    }
+
+    pub async fn create(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+        price: f64,
+        components: &[BundleComponent],
+    ) -> Result<BundleDetails, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to create bundle for store {}: {:?}", store_id, e);
+            "Failed to create bundle. Please try again later.".to_string()
+        };
+        let now = Utc::now();
+        let txn = db.begin().await.map_err(fail)?;
+        let bundle = BundleActiveModel {
+            id: Set(Uuid::new_v4()),
+            store_id: Set(store_id),
+            name: Set(name.to_owned()),
+            description: Set(description.map(|d| d.to_owned())),
+            price: Set(price),
+            is_active: Set(true),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(&txn)
+        .await
+        .map_err(fail)?;
+        Self::replace_items(&txn, bundle.id, components)
+            .await
+            .map_err(fail)?;
+        let details = Self::details(&txn, vec![bundle]).await.map_err(fail)?;
+        txn.commit().await.map_err(fail)?;
+        debug!("Bundle created: {:?}", details);
+        details.into_iter().next().ok_or_else(|| {
+            error!("Bundle for store {} vanished after insert", store_id);
+            "Failed to create bundle. Please try again later.".to_string()
+        })
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<BundleDetails>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to fetch bundle {}: {:?}", id, e);
+            "Failed to fetch bundle. Please try again later.".to_string()
+        };
+        let Some(bundle) = BundleEntity::find_by_id(id).one(db).await.map_err(fail)? else {
+            return Ok(None);
+        };
+        Ok(Self::details(db, vec![bundle])
+            .await
+            .map_err(fail)?
+            .into_iter()
+            .next())
+    }
+
+    /// A store's bundles, newest first; only active ones when `active_only`.
+    /// Empty when the store is not in `tenant_id`.
+    pub async fn list_by_store(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_id: Uuid,
+        active_only: bool,
+    ) -> Result<Vec<BundleDetails>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to list bundles for store {}: {:?}", store_id, e);
+            "Failed to list bundles. Please try again later.".to_string()
+        };
+        let tenant_stores = Query::select()
+            .column(store::Column::Id)
+            .from(store::Entity)
+            .and_where(tenant_condition::<store::Entity>(tenant_id))
+            .to_owned();
+        let mut query = BundleEntity::find()
+            .filter(product_bundle::Column::StoreId.eq(store_id))
+            .filter(product_bundle::Column::StoreId.in_subquery(tenant_stores));
+        if active_only {
+            query = query.filter(product_bundle::Column::IsActive.eq(true));
+        }
+        let bundles = query
+            .order_by_desc(product_bundle::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(fail)?;
+        Self::details(db, bundles).await.map_err(fail)
+    }
+
+    /// Replace a bundle's fields and components
+    pub async fn update(
+        db: &DatabaseConnection,
+        bundle: BundleModel,
+        name: &str,
+        description: Option<&str>,
+        price: f64,
+        is_active: bool,
+        components: &[BundleComponent],
+    ) -> Result<BundleDetails, String> {
+        let id = bundle.id;
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to update bundle {}: {:?}", id, e);
+            "Failed to update bundle. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let mut active: BundleActiveModel = bundle.into();
+        active.name = Set(name.to_owned());
+        active.description = Set(description.map(|d| d.to_owned()));
+        active.price = Set(price);
+        active.is_active = Set(is_active);
+        active.updated_at = Set(Utc::now());
+        let bundle = active.update(&txn).await.map_err(fail)?;
+        Self::replace_items(&txn, id, components)
+            .await
+            .map_err(fail)?;
+        let details = Self::details(&txn, vec![bundle]).await.map_err(fail)?;
+        txn.commit().await.map_err(fail)?;
+        details.into_iter().next().ok_or_else(|| {
+            error!("Bundle {} vanished during update", id);
+            "Failed to update bundle. Please try again later.".to_string()
+        })
+    }
+
+    /// Returns whether the bundle existed
+    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let res = BundleEntity::delete_by_id(id).exec(db).await.map_err(|e| {
+            error!("Failed to delete bundle {}: {:?}", id, e);
+            "Failed to delete bundle. Please try again later.".to_string()
+        })?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Deactivate the active bundles containing a product, returning them.
+    /// Runs inside the caller's transaction when the product is deleted.
+    pub async fn deactivate_containing<C: ConnectionTrait>(
+        conn: &C,
+        product_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<BundleModel>, String> {
+        let bundle_ids: Vec<Uuid> = BundleItemEntity::find()
+            .filter(bundle_item::Column::ProductId.eq(product_id))
+            .all(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to find bundles of product {}: {:?}", product_id, e);
+                "Failed to update bundles. Please try again later.".to_string()
+            })?
+            .into_iter()
+            .map(|item| item.bundle_id)
+            .collect();
+        if bundle_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        BundleEntity::update_many()
+            .col_expr(product_bundle::Column::IsActive, Expr::value(false))
+            .col_expr(product_bundle::Column::UpdatedAt, Expr::value(now))
+            .filter(product_bundle::Column::Id.is_in(bundle_ids))
+            .filter(product_bundle::Column::IsActive.eq(true))
+            .exec_with_returning(conn)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to deactivate bundles of product {}: {:?}",
+                    product_id, e
+                );
+                "Failed to update bundles. Please try again later.".to_string()
+            })
+    }
+
+    /// Sell `quantity` bundles: take the stock of every component in one
+    /// transaction, or none of it if any component runs short
+    pub async fn purchase(
+        db: &DatabaseConnection,
+        bundle_id: Uuid,
+        quantity: i32,
+    ) -> Result<PurchaseOutcome, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to purchase bundle {}: {:?}", bundle_id, e);
+            "Failed to purchase bundle. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let bundle = BundleEntity::find_by_id(bundle_id)
+            .one(&txn)
+            .await
+            .map_err(fail)?;
+        if !bundle.is_some_and(|b| b.is_active) {
+            return Ok(PurchaseOutcome::Unavailable);
+        }
+        let components: Vec<BundleComponent> = BundleItemEntity::find()
+            .filter(bundle_item::Column::BundleId.eq(bundle_id))
+            .all(&txn)
+            .await
+            .map_err(fail)?
+            .into_iter()
+            .map(|item| BundleComponent {
+                product_id: item.product_id,
+                quantity: item.quantity,
+            })
+            .collect();
+        if components.is_empty() {
+            return Ok(PurchaseOutcome::Unavailable);
+        }
+
+        let now = Utc::now();
+        let mut updated = Vec::with_capacity(components.len());
+        for (product_id, units) in stock_decrements(&components, quantity) {
+            let mut rows = decrement_query(product_id, units, now)
+                .exec_with_returning(&txn)
+                .await
+                .map_err(fail)?;
+            match rows.pop() {
+                Some(product) => updated.push(product),
+                // Dropping the transaction rolls back the earlier decrements
+                None => return Ok(PurchaseOutcome::OutOfStock(product_id)),
+            }
+        }
+        txn.commit().await.map_err(fail)?;
+        debug!(bundle_id = %bundle_id, quantity, "Bundle purchased");
+        Ok(PurchaseOutcome::Purchased(updated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    #[test]
+    fn test_availability_is_the_scarcest_component() {
+        // phone: 5 in stock, 1 per bundle; case: 7, 2 per bundle; charger: 10, 1
+        assert_eq!(availability(&[(1, 5), (2, 7), (1, 10)]), 3);
+        assert_eq!(availability(&[(1, 5), (3, 2)]), 0);
+        assert_eq!(availability(&[(2, -4)]), 0);
+        assert_eq!(availability(&[]), 0);
+    }
+
+    #[test]
+    fn test_purchase_decrements_every_component() {
+        let (phone, case) = (Uuid::new_v4(), Uuid::new_v4());
+        let components = [
+            BundleComponent {
+                product_id: phone,
+                quantity: 1,
+            },
+            BundleComponent {
+                product_id: case,
+                quantity: 2,
+            },
+        ];
+        assert_eq!(
+            stock_decrements(&components, 3),
+            vec![(phone, 3), (case, 6)]
+        );
+
+        let sql = decrement_query(case, 6, Utc::now())
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#"SET "quantity_available" = "quantity_available" - 6"#),
+            "{sql}"
+        );
+        // The stock check and the decrement are one statement, so concurrent
+        // purchases cannot both take the last units
+        assert!(sql.contains(r#""quantity_available" >= 6"#), "{sql}");
+    }
+}
@@ -0,0 +1,32 @@
+use crate::entity::category::{self, Entity as CategoryEntity, Model as CategoryModel};
+use sea_orm::{DatabaseConnection, EntityTrait, PaginatorTrait, QueryOrder};
+use tracing::error;
+use uuid::Uuid;
+
+pub struct Category;
+
+#[allow(dead_code)]
+impl Category {
+    pub async fn exists(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let count = CategoryEntity::find_by_id(id)
+            .count(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up category {}: {:?}", id, e);
+                "Failed to look up category. Please try again later.".to_string()
+            })?;
+        Ok(count > 0)
+    }
+
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<CategoryModel>, String> {
+        let categories = CategoryEntity::find()
+            .order_by_asc(category::Column::Name)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list categories: {:?}", e);
+                "Failed to list categories. Please try again later.".to_string()
+            })?;
+        Ok(categories)
+    }
+}
@@ -0,0 +1,523 @@
+//! Per-category attribute definitions, and the `attr.` filters that match
+//! products on the attributes they carry.
+
+use crate::entity::category_attribute::{
+    self, ActiveModel as AttributeActiveModel, Entity as AttributeEntity, Model as AttributeModel,
+};
+use crate::entity::product;
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseBackend, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Error of [`CategoryAttributes::define`] when the category already has the key
+pub const ATTRIBUTE_KEY_TAKEN: &str = "The category already defines this attribute.";
+
+/// Longest attribute key accepted
+pub const MAX_ATTRIBUTE_KEY_LEN: usize = 64;
+
+/// Filter suffixes for numeric comparisons, e.g. `attr.ram_gte=8`; keys may
+/// not end in them
+const COMPARISON_SUFFIXES: [(&str, Comparison); 4] = [
+    ("_gte", Comparison::Gte),
+    ("_lte", Comparison::Lte),
+    ("_gt", Comparison::Gt),
+    ("_lt", Comparison::Lt),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeType {
+    /// Any string
+    Text,
+    /// A JSON number
+    Number,
+    /// One of the definition's `allowed_values`
+    Enum,
+}
+
+impl AttributeType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttributeType::Text => "text",
+            AttributeType::Number => "number",
+            AttributeType::Enum => "enum",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(AttributeType::Text),
+            "number" => Some(AttributeType::Number),
+            "enum" => Some(AttributeType::Enum),
+            _ => None,
+        }
+    }
+}
+
+/// A definition as the validator needs it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    pub key: String,
+    pub attribute_type: AttributeType,
+    pub allowed_values: Vec<String>,
+    pub required: bool,
+}
+
+impl From<&AttributeModel> for AttributeDefinition {
+    fn from(model: &AttributeModel) -> Self {
+        let allowed_values = model
+            .allowed_values
+            .as_ref()
+            .and_then(|values| values.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            key: model.key.clone(),
+            // Rows are only written through `define`, which checks the type
+            attribute_type: AttributeType::parse(&model.attribute_type)
+                .unwrap_or(AttributeType::Text),
+            allowed_values,
+            required: model.required,
+        }
+    }
+}
+
+/// Lowercase letters, digits and underscores, starting with a letter, and
+/// not ending in a comparison suffix
+pub fn is_attribute_key(key: &str) -> bool {
+    key.len() <= MAX_ATTRIBUTE_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !COMPARISON_SUFFIXES
+            .iter()
+            .any(|(suffix, _)| key.ends_with(suffix))
+}
+
+pub struct CategoryAttributes;
+
+impl CategoryAttributes {
+    /// The category's definitions, by key
+    pub async fn for_category(
+        db: &DatabaseConnection,
+        category_id: Uuid,
+    ) -> Result<Vec<AttributeModel>, String> {
+        AttributeEntity::find()
+            .filter(category_attribute::Column::CategoryId.eq(category_id))
+            .order_by_asc(category_attribute::Column::Key)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to load attributes of category {}: {:?}",
+                    category_id, e
+                );
+                "Failed to load category attributes. Please try again later.".to_string()
+            })
+    }
+
+    /// Add a definition; the caller has checked the key and values
+    pub async fn define(
+        db: &DatabaseConnection,
+        category_id: Uuid,
+        definition: &AttributeDefinition,
+    ) -> Result<AttributeModel, String> {
+        let existing = AttributeEntity::find()
+            .filter(category_attribute::Column::CategoryId.eq(category_id))
+            .filter(category_attribute::Column::Key.eq(definition.key.as_str()))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up attribute {}: {:?}", definition.key, e);
+                "Failed to save category attribute. Please try again later.".to_string()
+            })?;
+        if existing.is_some() {
+            return Err(ATTRIBUTE_KEY_TAKEN.to_string());
+        }
+        let allowed_values = (definition.attribute_type == AttributeType::Enum)
+            .then(|| serde_json::json!(definition.allowed_values));
+        let id = Uuid::new_v4();
+        let attribute = AttributeActiveModel {
+            id: Set(id),
+            category_id: Set(category_id),
+            key: Set(definition.key.clone()),
+            attribute_type: Set(definition.attribute_type.as_str().to_string()),
+            allowed_values: Set(allowed_values),
+            required: Set(definition.required),
+            created_at: Set(Utc::now()),
+        };
+        AttributeEntity::insert(attribute)
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to save attribute {}: {:?}", definition.key, e);
+                "Failed to save category attribute. Please try again later.".to_string()
+            })?;
+        AttributeEntity::find_by_id(id)
+            .one(db)
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| "Failed to save category attribute. Please try again later.".to_string())
+    }
+
+    /// Drop a definition; products keep the values they carry until their
+    /// next edit, which reports them as unknown. `false` when there was none.
+    pub async fn remove(
+        db: &DatabaseConnection,
+        category_id: Uuid,
+        key: &str,
+    ) -> Result<bool, String> {
+        let res = AttributeEntity::delete_many()
+            .filter(category_attribute::Column::CategoryId.eq(category_id))
+            .filter(category_attribute::Column::Key.eq(key))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to remove attribute {}: {:?}", key, e);
+                "Failed to remove category attribute. Please try again later.".to_string()
+            })?;
+        Ok(res.rows_affected > 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Gte,
+    Lte,
+    Gt,
+    Lt,
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Gte => ">=",
+            Comparison::Lte => "<=",
+            Comparison::Gt => ">",
+            Comparison::Lt => "<",
+        }
+    }
+}
+
+/// One `attr.` query parameter of a product listing
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeFilter {
+    /// `attr.<key>=<value>`: the attribute is this text, or this number
+    Equals { key: String, value: String },
+    /// `attr.<key>_gte=<n>` and the like: the attribute is a number in range
+    Compare {
+        key: String,
+        comparison: Comparison,
+        value: f64,
+    },
+}
+
+impl AttributeFilter {
+    /// The `attr.` parameters among `params`; other parameters are ignored
+    pub fn from_query<'a>(
+        params: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Vec<Self>, String> {
+        let mut filters = Vec::new();
+        for (name, value) in params {
+            let Some(name) = name.strip_prefix("attr.") else {
+                continue;
+            };
+            filters.push(Self::parse(name, value)?);
+        }
+        // Parameters come from a map; keep the SQL stable between requests
+        filters.sort_by(|a, b| a.key().cmp(b.key()));
+        Ok(filters)
+    }
+
+    fn parse(name: &str, value: &str) -> Result<Self, String> {
+        if let Some((key, comparison)) = COMPARISON_SUFFIXES
+            .iter()
+            .find_map(|(suffix, comparison)| Some((name.strip_suffix(suffix)?, *comparison)))
+        {
+            if !is_attribute_key(key) {
+                return Err(format!("attr.{name}: unknown attribute key"));
+            }
+            let value = value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("attr.{name} must be a number"))?;
+            return Ok(AttributeFilter::Compare {
+                key: key.to_string(),
+                comparison,
+                value,
+            });
+        }
+        if !is_attribute_key(name) {
+            return Err(format!("attr.{name}: unknown attribute key"));
+        }
+        Ok(AttributeFilter::Equals {
+            key: name.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            AttributeFilter::Equals { key, .. } | AttributeFilter::Compare { key, .. } => key,
+        }
+    }
+
+    /// The filter as a condition on `products.attributes`. On Postgres
+    /// equality is a `@>` containment, which the GIN index serves. Custom
+    /// SQL placeholders differ by backend: `$n` on Postgres, `?` elsewhere.
+    pub fn condition(&self, backend: DatabaseBackend) -> Condition {
+        let column = Expr::col((product::Entity, product::Column::Attributes));
+        match (self, backend) {
+            (AttributeFilter::Equals { key, value }, DatabaseBackend::Postgres) => {
+                let contains = |value: serde_json::Value| -> SimpleExpr {
+                    let document = serde_json::json!({ key.as_str(): value }).to_string();
+                    Expr::cust_with_exprs(
+                        "$1 @> $2::jsonb",
+                        [column.clone().into(), Expr::val(document).into()],
+                    )
+                };
+                let mut any = Condition::any().add(contains(serde_json::json!(value)));
+                if let Some(number) = number(value) {
+                    any = any.add(contains(number));
+                }
+                any
+            }
+            (AttributeFilter::Equals { key, value }, _) => {
+                let extracted = || {
+                    Expr::cust_with_exprs(
+                        "json_extract(?, ?)",
+                        [column.clone().into(), Expr::val(format!("$.{key}")).into()],
+                    )
+                };
+                let mut any = Condition::any().add(Expr::expr(extracted()).eq(value.as_str()));
+                if let Some(number) = value.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                    any = any.add(Expr::expr(extracted()).eq(number));
+                }
+                any
+            }
+            (
+                AttributeFilter::Compare {
+                    key,
+                    comparison,
+                    value,
+                },
+                DatabaseBackend::Postgres,
+            ) => Condition::all().add(Expr::cust_with_exprs(
+                format!(
+                    "CASE WHEN jsonb_typeof($1 -> $2) = 'number' \
+                     THEN ($1 ->> $2)::float8 END {} $3",
+                    comparison.sql()
+                ),
+                [
+                    column.into(),
+                    Expr::val(key.as_str()).into(),
+                    Expr::val(*value).into(),
+                ],
+            )),
+            (
+                AttributeFilter::Compare {
+                    key,
+                    comparison,
+                    value,
+                },
+                _,
+            ) => Condition::all().add(Expr::cust_with_exprs(
+                format!(
+                    "CASE WHEN json_type(?, ?) IN ('integer', 'real') \
+                     THEN json_extract(?, ?) END {} ?",
+                    comparison.sql()
+                ),
+                [
+                    column.clone().into(),
+                    Expr::val(format!("$.{key}")).into(),
+                    column.into(),
+                    Expr::val(format!("$.{key}")).into(),
+                    Expr::val(*value).into(),
+                ],
+            )),
+        }
+    }
+}
+
+/// `value` as a JSON number, when it reads as one
+fn number(value: &str) -> Option<serde_json::Value> {
+    let parsed = value.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    match value.parse::<i64>() {
+        Ok(int) => Some(serde_json::json!(int)),
+        Err(_) => serde_json::Number::from_f64(parsed).map(serde_json::Value::Number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::products::{PriceFilter, Product};
+    use crate::db::testing;
+    use sea_orm::QueryTrait;
+
+    #[test]
+    fn test_filters_parse_from_query() {
+        let filters = AttributeFilter::from_query([
+            ("store_id", "x"),
+            ("attr.ram_gte", "8"),
+            ("attr.condition", "used"),
+        ])
+        .unwrap();
+        assert_eq!(
+            filters,
+            vec![
+                AttributeFilter::Equals {
+                    key: "condition".into(),
+                    value: "used".into()
+                },
+                AttributeFilter::Compare {
+                    key: "ram".into(),
+                    comparison: Comparison::Gte,
+                    value: 8.0
+                },
+            ]
+        );
+        assert!(AttributeFilter::from_query([("attr.ram_lt", "lots")]).is_err());
+        assert!(AttributeFilter::from_query([("attr.Ram", "8")]).is_err());
+        assert!(AttributeFilter::from_query([("attr._gt", "8")]).is_err());
+    }
+
+    #[test]
+    fn test_postgres_equality_is_a_containment() {
+        let filter = AttributeFilter::Equals {
+            key: "ram".into(),
+            value: "8".into(),
+        };
+        let sql = product::Entity::find()
+            .filter(filter.condition(DatabaseBackend::Postgres))
+            .build(DatabaseBackend::Postgres)
+            .to_string();
+        // Matches a stored "8" as well as 8, both through the GIN index
+        assert!(sql.contains(r#""products"."attributes" @> E'{\"ram\":\"8\"}'::jsonb"#));
+        assert!(sql.contains(r#""products"."attributes" @> E'{\"ram\":8}'::jsonb"#));
+    }
+
+    #[tokio::test]
+    async fn test_filters_match_equal_values_and_number_ranges() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let listing = |name: &'static str, attributes: serde_json::Value| {
+            let db = &db;
+            async move {
+                Product::create(
+                    db, store_id, None, name, None, 50000.0, "XAF", 1, None, None, None, None,
+                    None, false, None, None, attributes,
+                )
+                .await
+                .unwrap()
+                .id
+            }
+        };
+        let used_8 = listing("used 8", serde_json::json!({"condition": "used", "ram": 8})).await;
+        let used_4 = listing("used 4", serde_json::json!({"condition": "used", "ram": 4})).await;
+        let new_12 = listing("new 12", serde_json::json!({"condition": "new", "ram": 12})).await;
+        let odd = listing("odd", serde_json::json!({"ram": "16"})).await;
+        listing("bare", serde_json::json!({})).await;
+
+        let find = |params: &'static [(&'static str, &'static str)]| {
+            let db = &db;
+            async move {
+                let filter = PriceFilter {
+                    attributes: AttributeFilter::from_query(params.iter().copied()).unwrap(),
+                    ..PriceFilter::default()
+                };
+                let mut found: Vec<Uuid> = Product::list_by_store(db, "default", store_id, filter)
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|p| p.id)
+                    .collect();
+                found.sort();
+                found
+            }
+        };
+        let sorted = |mut ids: Vec<Uuid>| {
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            find(&[("attr.condition", "used")]).await,
+            sorted(vec![used_8, used_4])
+        );
+        // Equality on a number matches the stored number or its text
+        assert_eq!(find(&[("attr.ram", "16")]).await, vec![odd]);
+        assert_eq!(find(&[("attr.ram", "12")]).await, vec![new_12]);
+        // Ranges only consider numbers
+        assert_eq!(
+            find(&[("attr.ram_gte", "8")]).await,
+            sorted(vec![used_8, new_12])
+        );
+        assert_eq!(find(&[("attr.ram_lt", "8")]).await, vec![used_4]);
+        assert_eq!(
+            find(&[("attr.ram_gt", "8"), ("attr.ram_lte", "12")]).await,
+            vec![new_12]
+        );
+        assert_eq!(
+            find(&[("attr.condition", "used"), ("attr.ram_gte", "8")]).await,
+            vec![used_8]
+        );
+        assert!(find(&[("attr.colour", "red")]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_unique_per_category() {
+        let db = testing::sqlite().await;
+        let phones = testing::seed_category(&db, "phones").await;
+        let laptops = testing::seed_category(&db, "laptops").await;
+        let ram = AttributeDefinition {
+            key: "ram".into(),
+            attribute_type: AttributeType::Number,
+            allowed_values: Vec::new(),
+            required: true,
+        };
+        let defined = CategoryAttributes::define(&db, phones, &ram).await.unwrap();
+        assert_eq!(AttributeDefinition::from(&defined), ram);
+        assert_eq!(
+            CategoryAttributes::define(&db, phones, &ram)
+                .await
+                .unwrap_err(),
+            ATTRIBUTE_KEY_TAKEN
+        );
+        CategoryAttributes::define(&db, laptops, &ram)
+            .await
+            .unwrap();
+
+        assert!(CategoryAttributes::remove(&db, phones, "ram")
+            .await
+            .unwrap());
+        assert!(!CategoryAttributes::remove(&db, phones, "ram")
+            .await
+            .unwrap());
+        assert!(CategoryAttributes::for_category(&db, phones)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            CategoryAttributes::for_category(&db, laptops)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
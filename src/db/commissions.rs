@@ -0,0 +1,300 @@
+//! Effective-dated commission rates. A store override beats its product's
+//! category rate; within a scope the latest rate already in effect wins.
+
+use crate::entity::commission_rate::{self, ActiveModel, Entity as RateEntity, Model};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// What a rate applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateScope {
+    Category(Uuid),
+    Store(Uuid),
+}
+
+impl RateScope {
+    fn of(rate: &Model) -> Option<Self> {
+        match (rate.category_id, rate.store_id) {
+            (Some(category_id), None) => Some(RateScope::Category(category_id)),
+            (None, Some(store_id)) => Some(RateScope::Store(store_id)),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `rate` already applies at `now`; such rates are kept as they
+/// are, so every past sale can be explained by the rate in force then
+pub fn is_in_effect(rate: &Model, now: DateTime<Utc>) -> bool {
+    rate.effective_from <= now
+}
+
+pub struct CommissionRate;
+
+impl CommissionRate {
+    pub async fn create(
+        db: &DatabaseConnection,
+        scope: RateScope,
+        percent: f64,
+        effective_from: DateTime<Utc>,
+        created_by: &str,
+    ) -> Result<Model, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to create commission rate for {:?}: {:?}", scope, e);
+            "Failed to create commission rate. Please try again later.".to_string()
+        };
+        let (category_id, store_id) = match scope {
+            RateScope::Category(id) => (Some(id), None),
+            RateScope::Store(id) => (None, Some(id)),
+        };
+        let id = Uuid::new_v4();
+        let rate = ActiveModel {
+            id: Set(id),
+            category_id: Set(category_id),
+            store_id: Set(store_id),
+            percent: Set(percent),
+            effective_from: Set(effective_from),
+            created_by: Set(created_by.to_owned()),
+            created_at: Set(Utc::now()),
+        };
+        RateEntity::insert(rate)
+            .exec_without_returning(db)
+            .await
+            .map_err(fail)?;
+        let res = RateEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Failed to create commission rate".to_string())?;
+        debug!("Commission rate created: {:?}", res);
+        Ok(res)
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<Model>, String> {
+        RateEntity::find_by_id(id).one(db).await.map_err(|e| {
+            error!("Failed to fetch commission rate {}: {:?}", id, e);
+            "Failed to fetch commission rate. Please try again later.".to_string()
+        })
+    }
+
+    /// Rates for `scope`, or all of them, latest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        scope: Option<RateScope>,
+    ) -> Result<Vec<Model>, String> {
+        let mut query = RateEntity::find();
+        match scope {
+            Some(RateScope::Category(id)) => {
+                query = query.filter(commission_rate::Column::CategoryId.eq(id))
+            }
+            Some(RateScope::Store(id)) => {
+                query = query.filter(commission_rate::Column::StoreId.eq(id))
+            }
+            None => {}
+        }
+        query
+            .order_by_desc(commission_rate::Column::EffectiveFrom)
+            .order_by_desc(commission_rate::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list commission rates: {:?}", e);
+                "Failed to list commission rates. Please try again later.".to_string()
+            })
+    }
+
+    /// Reschedule or reprice a rate that is not in effect yet
+    pub async fn update(
+        db: &DatabaseConnection,
+        rate: Model,
+        percent: f64,
+        effective_from: DateTime<Utc>,
+    ) -> Result<Model, String> {
+        let id = rate.id;
+        let mut active: ActiveModel = rate.into();
+        active.percent = Set(percent);
+        active.effective_from = Set(effective_from);
+        active.update(db).await.map_err(|e| {
+            error!("Failed to update commission rate {}: {:?}", id, e);
+            "Failed to update commission rate. Please try again later.".to_string()
+        })
+    }
+
+    /// Returns false when there was no such rate
+    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let res = RateEntity::delete_by_id(id).exec(db).await.map_err(|e| {
+            error!("Failed to delete commission rate {}: {:?}", id, e);
+            "Failed to delete commission rate. Please try again later.".to_string()
+        })?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// The rate a sale in `store_id` of a product in `category_id` pays at
+    /// `at`: the store's latest rate in effect, else the category's, else
+    /// none. Equal start times go to the rate created last.
+    pub async fn effective(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        category_id: Option<Uuid>,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Model>, String> {
+        let mut scopes = Condition::any().add(commission_rate::Column::StoreId.eq(store_id));
+        if let Some(category_id) = category_id {
+            scopes = scopes.add(commission_rate::Column::CategoryId.eq(category_id));
+        }
+        let rates = RateEntity::find()
+            .filter(scopes)
+            .filter(commission_rate::Column::EffectiveFrom.lte(at))
+            .order_by_desc(commission_rate::Column::EffectiveFrom)
+            .order_by_desc(commission_rate::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to look up commission rate for store {}: {:?}",
+                    store_id, e
+                );
+                "Failed to look up commission rate. Please try again later.".to_string()
+            })?;
+        let latest = |scope: RateScope| {
+            rates
+                .iter()
+                .find(|rate| RateScope::of(rate) == Some(scope))
+                .cloned()
+        };
+        Ok(latest(RateScope::Store(store_id))
+            .or_else(|| category_id.and_then(|id| latest(RateScope::Category(id)))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::category;
+    use chrono::Duration;
+
+    async fn seed_category(db: &DatabaseConnection) -> Uuid {
+        let id = Uuid::new_v4();
+        category::Entity::insert(category::ActiveModel {
+            id: Set(id),
+            slug: Set(format!("fabrics-{id}")),
+            name: Set("Fabrics".to_string()),
+            created_at: Set(Utc::now()),
+        })
+        .exec_without_returning(db)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn percent_at(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        category_id: Option<Uuid>,
+        at: DateTime<Utc>,
+    ) -> Option<f64> {
+        CommissionRate::effective(db, store_id, category_id, at)
+            .await
+            .unwrap()
+            .map(|rate| rate.percent)
+    }
+
+    #[tokio::test]
+    async fn test_latest_rate_in_effect_wins_within_a_category() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let fabrics = seed_category(&db).await;
+        let jan = Utc::now() - Duration::days(90);
+        let mar = jan + Duration::days(60);
+        let scope = RateScope::Category(fabrics);
+        CommissionRate::create(&db, scope, 5.0, jan, "admin-1")
+            .await
+            .unwrap();
+        CommissionRate::create(&db, scope, 7.5, mar, "admin-1")
+            .await
+            .unwrap();
+        // Announced but not yet in effect
+        let next_month = Utc::now() + Duration::days(30);
+        CommissionRate::create(&db, scope, 9.0, next_month, "admin-1")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), jan - Duration::days(1)).await,
+            None
+        );
+        assert_eq!(percent_at(&db, store, Some(fabrics), jan).await, Some(5.0));
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), mar - Duration::seconds(1)).await,
+            Some(5.0)
+        );
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), Utc::now()).await,
+            Some(7.5)
+        );
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), next_month).await,
+            Some(9.0)
+        );
+        // Uncategorised products pay nothing without a store override
+        assert_eq!(percent_at(&db, store, None, Utc::now()).await, None);
+
+        // A correction starting the same instant supersedes the earlier rate
+        CommissionRate::create(&db, scope, 6.0, mar, "admin-2")
+            .await
+            .unwrap();
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), Utc::now()).await,
+            Some(6.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_override_beats_category_once_in_effect() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let other_store = testing::seed_store(&db, "seller-2").await;
+        let fabrics = seed_category(&db).await;
+        let now = Utc::now();
+        let start = now - Duration::days(30);
+        CommissionRate::create(&db, RateScope::Category(fabrics), 8.0, start, "admin-1")
+            .await
+            .unwrap();
+        // A newer category rate does not undo an older store override
+        let override_from = start + Duration::days(10);
+        CommissionRate::create(&db, RateScope::Store(store), 3.0, override_from, "admin-1")
+            .await
+            .unwrap();
+        CommissionRate::create(
+            &db,
+            RateScope::Category(fabrics),
+            10.0,
+            override_from + Duration::days(5),
+            "admin-1",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            percent_at(&db, store, Some(fabrics), start).await,
+            Some(8.0)
+        );
+        assert_eq!(percent_at(&db, store, Some(fabrics), now).await, Some(3.0));
+        assert_eq!(percent_at(&db, store, None, now).await, Some(3.0));
+        assert_eq!(
+            percent_at(&db, other_store, Some(fabrics), now).await,
+            Some(10.0)
+        );
+
+        let listed = CommissionRate::list(&db, Some(RateScope::Category(fabrics)))
+            .await
+            .unwrap();
+        let percents: Vec<f64> = listed.iter().map(|rate| rate.percent).collect();
+        assert_eq!(percents, [10.0, 8.0]);
+    }
+}
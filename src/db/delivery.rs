@@ -0,0 +1,224 @@
+//! How a product reaches the buyer. Each product carries its own options,
+//! copied from the store's defaults for whatever the seller leaves out.
+
+use crate::entity::product::Model as ProductModel;
+use crate::entity::store::Model as StoreModel;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest delivery estimate a seller may give
+pub const MAX_ESTIMATED_DAYS: i32 = 60;
+
+/// Delivery options as stored on a product or as a store default
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct DeliveryOptions {
+    pub pickup_available: bool,
+    pub delivery_available: bool,
+    pub delivery_fee: Option<f64>,
+    pub estimated_days: Option<i32>,
+}
+
+impl Default for DeliveryOptions {
+    /// Pickup only, which is how every store worked before delivery options
+    fn default() -> Self {
+        Self {
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee: None,
+            estimated_days: None,
+        }
+    }
+}
+
+impl DeliveryOptions {
+    pub fn store_default(store: &StoreModel) -> Self {
+        Self {
+            pickup_available: store.default_pickup_available,
+            delivery_available: store.default_delivery_available,
+            delivery_fee: store.default_delivery_fee,
+            estimated_days: store.default_delivery_estimated_days,
+        }
+    }
+
+    pub fn of_product(product: &ProductModel) -> Self {
+        Self {
+            pickup_available: product.pickup_available,
+            delivery_available: product.delivery_available,
+            delivery_fee: product.delivery_fee_override,
+            estimated_days: product.delivery_estimated_days,
+        }
+    }
+
+    pub fn allows(&self, method: DeliveryMethod) -> bool {
+        match method {
+            DeliveryMethod::Pickup => self.pickup_available,
+            DeliveryMethod::Delivery => self.delivery_available,
+        }
+    }
+}
+
+/// `delivery_options` as submitted; omitted fields take the store's default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryOptionsInput {
+    pub pickup_available: Option<bool>,
+    pub delivery_available: Option<bool>,
+    /// Delivery fee for this product, zero or more
+    pub delivery_fee_override: Option<f64>,
+    /// Typical days from order to delivery (0–60)
+    pub estimated_days: Option<i32>,
+}
+
+impl DeliveryOptionsInput {
+    /// Fill the fields the seller left out from `defaults`
+    pub fn fill(self, defaults: DeliveryOptions) -> DeliveryOptions {
+        DeliveryOptions {
+            pickup_available: self.pickup_available.unwrap_or(defaults.pickup_available),
+            delivery_available: self
+                .delivery_available
+                .unwrap_or(defaults.delivery_available),
+            delivery_fee: self.delivery_fee_override.or(defaults.delivery_fee),
+            estimated_days: self.estimated_days.or(defaults.estimated_days),
+        }
+    }
+}
+
+/// How the buyer wants an order handed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMethod {
+    Pickup,
+    Delivery,
+}
+
+impl DeliveryMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryMethod::Pickup => "pickup",
+            DeliveryMethod::Delivery => "delivery",
+        }
+    }
+}
+
+/// Cart items that cannot be handed over by `method`, in cart order. Order
+/// creation rejects the whole cart when any are returned.
+pub fn unavailable_items<'a>(
+    items: impl IntoIterator<Item = &'a ProductModel>,
+    method: DeliveryMethod,
+) -> Vec<Uuid> {
+    items
+        .into_iter()
+        .filter(|product| !DeliveryOptions::of_product(product).allows(method))
+        .map(|product| product.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn product(pickup_available: bool, delivery_available: bool) -> ProductModel {
+        let now = Utc::now();
+        ProductModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: "Kaba dress".to_string(),
+            description: None,
+            price: 15000.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: 3,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available,
+            delivery_available,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "product".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_cart_validation_matrix() {
+        let pickup_only = product(true, false);
+        let delivery_only = product(false, true);
+        let both = product(true, true);
+        let cases: &[(&[&ProductModel], DeliveryMethod, Vec<Uuid>)] = &[
+            (&[&both], DeliveryMethod::Pickup, vec![]),
+            (&[&both], DeliveryMethod::Delivery, vec![]),
+            (&[&pickup_only], DeliveryMethod::Pickup, vec![]),
+            (
+                &[&pickup_only],
+                DeliveryMethod::Delivery,
+                vec![pickup_only.id],
+            ),
+            (
+                &[&delivery_only],
+                DeliveryMethod::Pickup,
+                vec![delivery_only.id],
+            ),
+            (&[&delivery_only], DeliveryMethod::Delivery, vec![]),
+            (
+                &[&both, &pickup_only, &delivery_only],
+                DeliveryMethod::Delivery,
+                vec![pickup_only.id],
+            ),
+            (
+                &[&delivery_only, &both, &pickup_only],
+                DeliveryMethod::Pickup,
+                vec![delivery_only.id],
+            ),
+            (&[], DeliveryMethod::Delivery, vec![]),
+        ];
+        for (items, method, expected) in cases {
+            assert_eq!(
+                &unavailable_items(items.iter().copied(), *method),
+                expected,
+                "{} of {} items",
+                method.as_str(),
+                items.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_fields_come_from_store_defaults() {
+        let defaults = DeliveryOptions {
+            pickup_available: true,
+            delivery_available: true,
+            delivery_fee: Some(1000.0),
+            estimated_days: Some(2),
+        };
+        let input = DeliveryOptionsInput {
+            pickup_available: Some(false),
+            estimated_days: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            input.fill(defaults),
+            DeliveryOptions {
+                pickup_available: false,
+                delivery_available: true,
+                delivery_fee: Some(1000.0),
+                estimated_days: Some(5),
+            }
+        );
+        assert_eq!(DeliveryOptionsInput::default().fill(defaults), defaults);
+    }
+}
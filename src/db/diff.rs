@@ -0,0 +1,169 @@
+//! Field-level differences between two versions of a record, as shown in the
+//! change history of products and stores.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use utoipa::ToSchema;
+
+/// Bumped on every write, so never worth showing as a change
+pub const ALWAYS_CHANGED: &[&str] = &["updated_at"];
+
+/// One field that changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldChange {
+    /// Field name; nested fields are joined with dots, e.g. `options.fee`
+    pub field: String,
+    /// Previous value, `null` when unset
+    #[schema(value_type = Object)]
+    pub old: Value,
+    /// New value, `null` when cleared
+    #[schema(value_type = Object)]
+    pub new: Value,
+}
+
+/// Fields that differ between `before` and `after`, in field order.
+///
+/// Nested objects are compared field by field; anything else, arrays
+/// included, is compared as a whole. A missing field counts as `null`, so an
+/// optional field going from `None` to `Some` shows up once with `old: null`.
+/// Fields named in `ignored` are skipped at any depth.
+pub fn diff<T: Serialize>(before: &T, after: &T, ignored: &[&str]) -> Vec<FieldChange> {
+    let before = serde_json::to_value(before).unwrap_or(Value::Null);
+    let after = serde_json::to_value(after).unwrap_or(Value::Null);
+    let mut changes = Vec::new();
+    diff_values("", &before, &after, ignored, &mut changes);
+    changes
+}
+
+fn diff_values(
+    path: &str,
+    before: &Value,
+    after: &Value,
+    ignored: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            diff_objects(path, before, after, ignored, changes)
+        }
+        _ if before != after => changes.push(FieldChange {
+            field: path.to_string(),
+            old: before.clone(),
+            new: after.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+    ignored: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        if ignored.contains(&key.as_str()) {
+            continue;
+        }
+        let field = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        diff_values(
+            &field,
+            before.get(key).unwrap_or(&Value::Null),
+            after.get(key).unwrap_or(&Value::Null),
+            ignored,
+            changes,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn change(field: &str, old: Value, new: Value) -> FieldChange {
+        FieldChange {
+            field: field.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn test_only_changed_fields_are_reported() {
+        let before = json!({ "name": "Wax print", "price": 5000.0, "quantity": 3 });
+        let after = json!({ "name": "Wax print", "price": 0.0, "quantity": 2 });
+        assert_eq!(
+            diff(&before, &after, &[]),
+            vec![
+                change("price", json!(5000.0), json!(0.0)),
+                change("quantity", json!(3), json!(2)),
+            ]
+        );
+        assert!(diff(&before, &before, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_nested_fields_get_dotted_paths() {
+        let before = json!({ "delivery": { "fee": 1000, "days": 2 }, "tags": ["a"] });
+        let after = json!({ "delivery": { "fee": 1500, "days": 2 }, "tags": ["a", "b"] });
+        assert_eq!(
+            diff(&before, &after, &[]),
+            vec![
+                change("delivery.fee", json!(1000), json!(1500)),
+                change("tags", json!(["a"]), json!(["a", "b"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optional_fields_treat_missing_as_null() {
+        #[derive(Serialize)]
+        struct Listing {
+            description: Option<&'static str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            sale_price: Option<f64>,
+        }
+        let before = Listing {
+            description: None,
+            sale_price: None,
+        };
+        let after = Listing {
+            description: Some("Hand dyed"),
+            sale_price: Some(4000.0),
+        };
+        assert_eq!(
+            diff(&before, &after, &[]),
+            vec![
+                change("description", Value::Null, json!("Hand dyed")),
+                change("sale_price", Value::Null, json!(4000.0)),
+            ]
+        );
+        assert_eq!(
+            diff(&after, &before, &[]),
+            vec![
+                change("description", json!("Hand dyed"), Value::Null),
+                change("sale_price", json!(4000.0), Value::Null),
+            ]
+        );
+        // A nested object that appears is reported whole
+        assert_eq!(
+            diff(&json!({ "a": null }), &json!({ "a": { "b": 1 } }), &[]),
+            vec![change("a", Value::Null, json!({ "b": 1 }))]
+        );
+    }
+
+    #[test]
+    fn test_ignored_fields_are_skipped_at_any_depth() {
+        let before = json!({ "price": 1, "updated_at": "t1", "meta": { "updated_at": "t1" } });
+        let after = json!({ "price": 1, "updated_at": "t2", "meta": { "updated_at": "t2" } });
+        assert!(diff(&before, &after, ALWAYS_CHANGED).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! The persisted event log that analytics exports page through; rows are
+//! written by `crate::events::EventLogRecorder`.
+//!
+//! On Postgres an append takes a transaction-scoped advisory lock before
+//! drawing its `seq`, so appends commit in `seq` order: a row still being
+//! written always numbers above every row a reader can see, and paging by
+//! `seq` never steps past one. SQLite already serializes writers.
+
+use crate::entity::event_record::{
+    self, ActiveModel as EventRecordActiveModel, Entity as EventRecordEntity, Model as EventRecord,
+};
+use crate::events::Event;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, NotSet,
+    QueryFilter, QueryOrder, QuerySelect, Set, Statement, TransactionTrait,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// Error of [`EventLog::export`] for an `after` that names no recorded event
+pub const UNKNOWN_CURSOR: &str = "after names no recorded event";
+
+/// Advisory lock key held while appending to the log; any constant no other
+/// code locks on
+const APPEND_LOCK_KEY: i64 = 0x0065_7665_6e74_6c6f;
+
+pub struct EventLog;
+
+impl EventLog {
+    /// Keep `event`; returns its sequence number
+    pub async fn append(db: &DatabaseConnection, event: &Event) -> Result<i64, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to record event {}: {:?}", event.id, e);
+            "Failed to record event".to_string()
+        };
+        if db.get_database_backend() != DatabaseBackend::Postgres {
+            return Self::append_in(db, event).await;
+        }
+        let txn = db.begin().await.map_err(fail)?;
+        let seq = Self::append_in(&txn, event).await?;
+        txn.commit().await.map_err(fail)?;
+        Ok(seq)
+    }
+
+    /// [`Self::append`] inside the caller's transaction, which holds the
+    /// append lock until it ends
+    async fn append_in<C: ConnectionTrait>(conn: &C, event: &Event) -> Result<i64, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to record event {}: {:?}", event.id, e);
+            "Failed to record event".to_string()
+        };
+        if conn.get_database_backend() == DatabaseBackend::Postgres {
+            conn.execute(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                "SELECT pg_advisory_xact_lock($1)",
+                [APPEND_LOCK_KEY.into()],
+            ))
+            .await
+            .map_err(fail)?;
+        }
+        let event_type = match serde_json::to_value(&event.event_type) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => format!("{:?}", event.event_type),
+        };
+        let record = EventRecordActiveModel {
+            seq: NotSet,
+            id: Set(event.id),
+            event_type: Set(event_type),
+            entity_id: Set(event.entity_id),
+            data: Set(event.data.clone()),
+            occurred_at: Set(event.timestamp),
+        };
+        EventRecordEntity::insert(record)
+            .exec(conn)
+            .await
+            .map(|res| res.last_insert_id)
+            .map_err(fail)
+    }
+
+    /// Sequence number of the event with this ID, if it was recorded
+    pub async fn seq_of(db: &DatabaseConnection, id: Uuid) -> Result<Option<i64>, String> {
+        EventRecordEntity::find()
+            .filter(event_record::Column::Id.eq(id))
+            .select_only()
+            .column(event_record::Column::Seq)
+            .into_tuple()
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up event {}: {:?}", id, e);
+                "Failed to look up event".to_string()
+            })
+    }
+
+    /// Up to `limit` events with `seq > after`, in `seq` order
+    pub async fn page(
+        db: &DatabaseConnection,
+        after: i64,
+        limit: u64,
+    ) -> Result<Vec<EventRecord>, String> {
+        EventRecordEntity::find()
+            .filter(event_record::Column::Seq.gt(after))
+            .order_by_asc(event_record::Column::Seq)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to read the event log after {}: {:?}", after, e);
+                "Failed to read the event log".to_string()
+            })
+    }
+
+    /// The next `limit` events after the one with ID `after`, or from the
+    /// start of the log. Rows still being appended number above the page,
+    /// so they come with the next one rather than racing past the cursor.
+    pub async fn export(
+        db: &DatabaseConnection,
+        after: Option<Uuid>,
+        limit: u64,
+    ) -> Result<Vec<EventRecord>, String> {
+        let after = match after {
+            Some(id) => Self::seq_of(db, id)
+                .await?
+                .ok_or_else(|| UNKNOWN_CURSOR.to_string())?,
+            None => 0,
+        };
+        Self::page(db, after, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::events::{create_event, EventHandler, EventLogRecorder, EventType};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_paging_while_recording_covers_every_event_once() {
+        let db = testing::sqlite().await;
+        let recorder = Arc::new(EventLogRecorder::new(db.clone()));
+        let total = 120;
+        let mut writers = Vec::new();
+        for writer in 0..4 {
+            let recorder = recorder.clone();
+            writers.push(tokio::spawn(async move {
+                for _ in 0..total / 4 {
+                    let event = create_event(
+                        EventType::ProductUpdated,
+                        Uuid::new_v4(),
+                        serde_json::json!({ "writer": writer }),
+                    );
+                    recorder.handle_event(&event).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            }));
+        }
+
+        let mut exported = Vec::new();
+        let mut cursor = None;
+        loop {
+            let done = writers.iter().all(|writer| writer.is_finished());
+            let page = EventLog::export(&db, cursor, 7).await.unwrap();
+            if let Some(last) = page.last() {
+                cursor = Some(last.id);
+            }
+            let caught_up = page.is_empty();
+            exported.extend(page.into_iter().map(|record| record.seq));
+            if done && caught_up {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(exported, (1..=total as i64).collect::<Vec<_>>());
+        assert_eq!(
+            EventLog::export(&db, Some(Uuid::new_v4()), 7).await,
+            Err(UNKNOWN_CURSOR.to_string())
+        );
+    }
+
+    /// Drops and recreates `events`, so point it at a scratch database, e.g.
+    /// `TEST_DATABASE_URL=postgres://localhost/transac_test cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore = "needs a scratch Postgres database at TEST_DATABASE_URL"]
+    async fn test_export_waits_for_an_append_still_committing_on_postgres() {
+        use sea_orm::{Database, Schema};
+
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+        let db = Database::connect(url).await.unwrap();
+        let backend = db.get_database_backend();
+        db.execute_unprepared("DROP TABLE IF EXISTS events")
+            .await
+            .unwrap();
+        db.execute(
+            backend.build(&Schema::new(backend).create_table_from_entity(EventRecordEntity)),
+        )
+        .await
+        .unwrap();
+        let event = || {
+            create_event(
+                EventType::ProductUpdated,
+                Uuid::new_v4(),
+                serde_json::json!({}),
+            )
+        };
+
+        // The first append draws its seq, then stalls before committing
+        let slow = db.begin().await.unwrap();
+        assert_eq!(EventLog::append_in(&slow, &event()).await, Ok(1));
+        let fast = tokio::spawn({
+            let db = db.clone();
+            async move { EventLog::append(&db, &event()).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert!(EventLog::export(&db, None, 10).await.unwrap().is_empty());
+
+        slow.commit().await.unwrap();
+        assert_eq!(fast.await.unwrap(), Ok(2));
+        let seqs: Vec<_> = EventLog::export(&db, None, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.seq)
+            .collect();
+        assert_eq!(seqs, vec![1, 2]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Exposure log of A/B experiments; see `crate::experiments`.
+
+use crate::entity::experiment_exposure::{
+    self, ActiveModel as ExposureActiveModel, Entity as ExposureEntity,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Exposures of one variant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct VariantExposures {
+    pub variant: String,
+    /// Caller-days: each caller counts once per day they saw the variant
+    pub exposures: i64,
+    /// Distinct callers
+    pub subjects: i64,
+}
+
+/// Note that `subject` saw `variant` of `experiment`. Only the first
+/// exposure of a UTC day is kept; returns whether this one was.
+pub async fn record_exposure(
+    db: &DatabaseConnection,
+    experiment: &str,
+    variant: &str,
+    subject: &str,
+    now: DateTime<Utc>,
+) -> Result<bool, String> {
+    let exposure = ExposureActiveModel {
+        id: Set(Uuid::new_v4()),
+        experiment: Set(experiment.to_owned()),
+        variant: Set(variant.to_owned()),
+        subject: Set(subject.to_owned()),
+        exposed_on: Set(now.date_naive()),
+        created_at: Set(now),
+    };
+    let inserted = ExposureEntity::insert(exposure)
+        .on_conflict(
+            OnConflict::columns([
+                experiment_exposure::Column::Experiment,
+                experiment_exposure::Column::Subject,
+                experiment_exposure::Column::ExposedOn,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to record exposure to {}: {:?}", experiment, e);
+            "Failed to record experiment exposure.".to_string()
+        })?;
+    Ok(inserted > 0)
+}
+
+/// Exposures of each variant of `experiment` that has any, by variant name
+pub async fn summary(
+    db: &DatabaseConnection,
+    experiment: &str,
+) -> Result<Vec<VariantExposures>, String> {
+    let rows: Vec<(String, i64, i64)> = ExposureEntity::find()
+        .select_only()
+        .column(experiment_exposure::Column::Variant)
+        .column_as(
+            Expr::col(experiment_exposure::Column::Id).count(),
+            "exposures",
+        )
+        .column_as(
+            Expr::col(experiment_exposure::Column::Subject).count_distinct(),
+            "subjects",
+        )
+        .filter(experiment_exposure::Column::Experiment.eq(experiment))
+        .group_by(experiment_exposure::Column::Variant)
+        .order_by_asc(experiment_exposure::Column::Variant)
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to summarise experiment {}: {:?}", experiment, e);
+            "Failed to summarise experiment.".to_string()
+        })?;
+    Ok(rows
+        .into_iter()
+        .map(|(variant, exposures, subjects)| VariantExposures {
+            variant,
+            exposures,
+            subjects,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::sqlite;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_one_exposure_per_caller_and_day() {
+        let db = sqlite().await;
+        let now = Utc::now();
+        let record = |variant, subject, at| record_exposure(&db, "feed", variant, subject, at);
+
+        assert!(record("newest", "relay-1", now).await.unwrap());
+        assert!(!record("newest", "relay-1", now).await.unwrap());
+        assert!(record("newest", "relay-1", now + Duration::days(1))
+            .await
+            .unwrap());
+        assert!(record("newest", "relay-2", now).await.unwrap());
+        assert!(record("trust_score", "relay-3", now).await.unwrap());
+        assert!(record_exposure(&db, "other", "a", "relay-1", now)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            summary(&db, "feed").await.unwrap(),
+            [
+                VariantExposures {
+                    variant: "newest".into(),
+                    exposures: 3,
+                    subjects: 2,
+                },
+                VariantExposures {
+                    variant: "trust_score".into(),
+                    exposures: 1,
+                    subjects: 1,
+                },
+            ]
+        );
+        assert!(summary(&db, "unknown").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,485 @@
+//! Change history of products, stores and payout accounts. Writes record the
+//! fields they changed in `audit_log`; the feed owners read merges those
+//! entries with `product_price_history`, newest first.
+
+use crate::db::diff::{diff, FieldChange, ALWAYS_CHANGED};
+use crate::entity::audit_log::{self, ActiveModel as AuditActiveModel, Entity as AuditEntity};
+use crate::entity::product::Model as ProductModel;
+use crate::entity::product_price_history::{self, Entity as PriceHistoryEntity};
+use crate::entity::store::Model as StoreModel;
+use crate::entity::store_payout_account::Model as PayoutAccountModel;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// `source` of entries written by an owner's own edit
+pub const EDIT_SOURCE: &str = "edit";
+
+/// Fields whose values never reach `audit_log`, at any depth; a change to
+/// one is still recorded, with both sides shown as [`REDACTED`]
+pub const REDACTED_FIELDS: &[&str] = &["account_number", "account_number_encrypted"];
+pub const REDACTED: &str = "[redacted]";
+
+/// Who or what is writing a change
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeOrigin<'a> {
+    /// Device or API key of the caller; `None` for system writes
+    pub changed_by: Option<&'a str>,
+    pub source: &'static str,
+}
+
+impl<'a> ChangeOrigin<'a> {
+    pub fn edit(changed_by: Option<&'a str>) -> Self {
+        Self {
+            changed_by,
+            source: EDIT_SOURCE,
+        }
+    }
+
+    pub fn system(source: &'static str) -> Self {
+        Self {
+            changed_by: None,
+            source,
+        }
+    }
+}
+
+/// Kind of record an entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEntity {
+    Product,
+    Store,
+    PayoutAccount,
+}
+
+impl HistoryEntity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryEntity::Product => "product",
+            HistoryEntity::Store => "store",
+            HistoryEntity::PayoutAccount => "payout_account",
+        }
+    }
+}
+
+pub struct AuditLog;
+
+impl AuditLog {
+    /// Record a product write; nothing is stored when no field changed
+    pub async fn record_product<C: ConnectionTrait>(
+        conn: &C,
+        before: &ProductModel,
+        after: &ProductModel,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<(), String> {
+        let changes = diff(before, after, ALWAYS_CHANGED);
+        Self::insert(
+            conn,
+            HistoryEntity::Product,
+            after.id,
+            after.store_id,
+            changes,
+            origin,
+        )
+        .await
+    }
+
+    /// Record a store write; nothing is stored when no field changed
+    pub async fn record_store<C: ConnectionTrait>(
+        conn: &C,
+        before: &StoreModel,
+        after: &StoreModel,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<(), String> {
+        let changes = diff(before, after, ALWAYS_CHANGED);
+        Self::insert(
+            conn,
+            HistoryEntity::Store,
+            after.id,
+            after.id,
+            changes,
+            origin,
+        )
+        .await
+    }
+
+    /// Record a payout account write, `before` being `None` when it was
+    /// created. Account numbers are redacted.
+    pub async fn record_payout_account<C: ConnectionTrait>(
+        conn: &C,
+        before: Option<&PayoutAccountModel>,
+        after: &PayoutAccountModel,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<(), String> {
+        // A new account shows every field going from null
+        let before = before.map_or_else(
+            || serde_json::json!({}),
+            |before| serde_json::to_value(before).unwrap_or_default(),
+        );
+        let changes = diff(
+            &before,
+            &serde_json::to_value(after).unwrap_or_default(),
+            ALWAYS_CHANGED,
+        );
+        Self::insert(
+            conn,
+            HistoryEntity::PayoutAccount,
+            after.id,
+            after.store_id,
+            changes,
+            origin,
+        )
+        .await
+    }
+
+    async fn insert<C: ConnectionTrait>(
+        conn: &C,
+        entity: HistoryEntity,
+        entity_id: Uuid,
+        store_id: Uuid,
+        changes: Vec<FieldChange>,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<(), String> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let changes = redact(changes);
+        let entry = AuditActiveModel {
+            id: Set(Uuid::new_v4()),
+            entity_type: Set(entity.as_str().to_string()),
+            entity_id: Set(entity_id),
+            store_id: Set(store_id),
+            changed_by: Set(origin.changed_by.map(str::to_owned)),
+            source: Set(origin.source.to_string()),
+            changes: Set(serde_json::json!(changes)),
+            created_at: Set(Utc::now()),
+        };
+        AuditEntity::insert(entry)
+            .exec_without_returning(conn)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to record {} {} change: {:?}",
+                    entity.as_str(),
+                    entity_id,
+                    e
+                );
+                "Failed to record change history. Please try again later.".to_string()
+            })?;
+        Ok(())
+    }
+}
+
+fn redact(changes: Vec<FieldChange>) -> Vec<FieldChange> {
+    let hide = |value: serde_json::Value| match value {
+        serde_json::Value::Null => value,
+        _ => serde_json::json!(REDACTED),
+    };
+    changes
+        .into_iter()
+        .map(|change| {
+            let name = change.field.rsplit('.').next().unwrap_or_default();
+            if REDACTED_FIELDS.contains(&name) {
+                FieldChange {
+                    old: hide(change.old),
+                    new: hide(change.new),
+                    ..change
+                }
+            } else {
+                change
+            }
+        })
+        .collect()
+}
+
+/// One write in the change feed
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct HistoryEntry {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// `product`, `store` or `payout_account`
+    pub entity_type: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub entity_id: Uuid,
+    /// Device or API key that made the change, when known
+    pub changed_by: Option<String>,
+    /// What made the change: `edit`, `inventory_sync`, `bulk`, ...
+    pub source: String,
+    pub changes: Vec<FieldChange>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    fn from_audit(entry: audit_log::Model) -> Self {
+        Self {
+            id: entry.id,
+            entity_type: entry.entity_type,
+            entity_id: entry.entity_id,
+            changed_by: entry.changed_by,
+            source: entry.source,
+            changes: serde_json::from_value(entry.changes).unwrap_or_default(),
+            created_at: entry.created_at,
+        }
+    }
+
+    fn from_price_change(change: product_price_history::Model) -> Self {
+        Self {
+            id: change.id,
+            entity_type: HistoryEntity::Product.as_str().to_string(),
+            entity_id: change.product_id,
+            changed_by: None,
+            source: change.source,
+            changes: vec![FieldChange {
+                field: "price".to_string(),
+                old: serde_json::json!(change.previous_price),
+                new: serde_json::json!(change.price),
+            }],
+            created_at: change.created_at,
+        }
+    }
+
+    fn key(&self) -> (DateTime<Utc>, Uuid) {
+        (self.created_at, self.id)
+    }
+}
+
+/// Whose history to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryScope {
+    Product(Uuid),
+    /// The store itself and all of its products
+    Store(Uuid),
+}
+
+/// Position after the last entry of a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl HistoryCursor {
+    /// Opaque continuation token handed to clients
+    pub fn encode(&self) -> String {
+        let raw = format!(
+            "{}|{}",
+            self.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.id
+        );
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        let invalid = || "Invalid history cursor".to_string();
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (created_at, id) = raw.split_once('|').ok_or_else(invalid)?;
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .map_err(|_| invalid())?
+                .with_timezone(&Utc),
+            id: Uuid::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+
+    /// Rows strictly older than the cursor in `(created_at, id)` order
+    fn older<C: ColumnTrait>(&self, created_at: C, id: C) -> Condition {
+        Condition::any().add(created_at.lt(self.created_at)).add(
+            Condition::all()
+                .add(created_at.eq(self.created_at))
+                .add(id.lt(self.id)),
+        )
+    }
+}
+
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    /// Entries in the whole of `scope`'s history
+    pub total: u64,
+    pub next_cursor: Option<HistoryCursor>,
+}
+
+/// Up to `limit` entries of `scope`'s history older than `cursor`, newest
+/// first
+pub async fn history_page(
+    db: &DatabaseConnection,
+    scope: HistoryScope,
+    cursor: Option<HistoryCursor>,
+    limit: u64,
+) -> Result<HistoryPage, String> {
+    let fail = |e: sea_orm::DbErr| {
+        error!("Failed to read change history of {:?}: {:?}", scope, e);
+        "Failed to read change history. Please try again later.".to_string()
+    };
+    let limit = limit.max(1);
+
+    let mut audit = AuditEntity::find();
+    let mut prices = PriceHistoryEntity::find();
+    match scope {
+        HistoryScope::Product(id) => {
+            audit = audit
+                .filter(audit_log::Column::EntityType.eq(HistoryEntity::Product.as_str()))
+                .filter(audit_log::Column::EntityId.eq(id));
+            prices = prices.filter(product_price_history::Column::ProductId.eq(id));
+        }
+        HistoryScope::Store(id) => {
+            audit = audit.filter(audit_log::Column::StoreId.eq(id));
+            prices = prices.filter(product_price_history::Column::StoreId.eq(id));
+        }
+    }
+    let total = audit.clone().count(db).await.map_err(fail)?
+        + prices.clone().count(db).await.map_err(fail)?;
+    if let Some(cursor) = cursor {
+        audit = audit.filter(cursor.older(audit_log::Column::CreatedAt, audit_log::Column::Id));
+        prices = prices.filter(cursor.older(
+            product_price_history::Column::CreatedAt,
+            product_price_history::Column::Id,
+        ));
+    }
+
+    // Each source can fill the page on its own, so read one past it from both
+    let audit = audit
+        .order_by_desc(audit_log::Column::CreatedAt)
+        .order_by_desc(audit_log::Column::Id)
+        .limit(limit + 1)
+        .all(db)
+        .await
+        .map_err(fail)?;
+    let prices = prices
+        .order_by_desc(product_price_history::Column::CreatedAt)
+        .order_by_desc(product_price_history::Column::Id)
+        .limit(limit + 1)
+        .all(db)
+        .await
+        .map_err(fail)?;
+
+    let mut entries: Vec<HistoryEntry> = audit
+        .into_iter()
+        .map(HistoryEntry::from_audit)
+        .chain(prices.into_iter().map(HistoryEntry::from_price_change))
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.key()));
+    let more = entries.len() as u64 > limit;
+    entries.truncate(limit as usize);
+    let next_cursor = entries.last().filter(|_| more).map(|last| HistoryCursor {
+        created_at: last.created_at,
+        id: last.id,
+    });
+    Ok(HistoryPage {
+        entries,
+        total,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::entity::product::Entity as ProductEntity;
+    use crate::entity::product_price_history::ActiveModel as PriceHistoryActiveModel;
+
+    async fn price_change(db: &DatabaseConnection, product: &ProductModel, price: f64) {
+        PriceHistoryEntity::insert(PriceHistoryActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(product.id),
+            store_id: Set(product.store_id),
+            previous_price: Set(product.price),
+            price: Set(price),
+            source: Set("bulk".to_string()),
+            created_at: Set(Utc::now()),
+        })
+        .exec_without_returning(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_feed_merges_both_sources_across_pages() {
+        let db = testing::sqlite().await;
+        let id = testing::seed_product(&db, "seller-1").await;
+        let product = ProductEntity::find_by_id(id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let renamed = ProductModel {
+            name: "Renamed".to_string(),
+            ..product.clone()
+        };
+        AuditLog::record_product(&db, &product, &renamed, ChangeOrigin::edit(Some("phone-2")))
+            .await
+            .unwrap();
+        price_change(&db, &renamed, 0.0).await;
+        // A write that changes nothing leaves no entry
+        AuditLog::record_product(&db, &renamed, &renamed, ChangeOrigin::edit(Some("phone-2")))
+            .await
+            .unwrap();
+        let restocked = ProductModel {
+            quantity_available: 9,
+            updated_at: Utc::now(),
+            ..renamed.clone()
+        };
+        AuditLog::record_product(
+            &db,
+            &renamed,
+            &restocked,
+            ChangeOrigin::system("inventory_sync"),
+        )
+        .await
+        .unwrap();
+
+        let first = history_page(&db, HistoryScope::Product(id), None, 2)
+            .await
+            .unwrap();
+        let sources: Vec<&str> = first.entries.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(sources, ["inventory_sync", "bulk"]);
+        assert_eq!(first.entries[0].changes.len(), 1);
+        assert_eq!(first.entries[0].changes[0].field, "quantity_available");
+        assert_eq!(first.entries[1].changes[0].new, serde_json::json!(0.0));
+
+        let cursor = HistoryCursor::decode(&first.next_cursor.unwrap().encode()).unwrap();
+        let rest = history_page(&db, HistoryScope::Product(id), Some(cursor), 2)
+            .await
+            .unwrap();
+        assert_eq!(rest.entries.len(), 1);
+        assert_eq!((first.total, rest.total), (3, 3));
+        assert!(rest.next_cursor.is_none());
+        let edit = &rest.entries[0];
+        assert_eq!(edit.changed_by.as_deref(), Some("phone-2"));
+        assert_eq!(
+            edit.changes,
+            vec![FieldChange {
+                field: "name".to_string(),
+                old: serde_json::json!(product.name),
+                new: serde_json::json!("Renamed"),
+            }]
+        );
+
+        // The store's feed covers its products too; another store's doesn't
+        let store = history_page(&db, HistoryScope::Store(product.store_id), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(store.entries.len(), 3);
+        let other = testing::seed_store(&db, "seller-2").await;
+        assert!(history_page(&db, HistoryScope::Store(other), None, 10)
+            .await
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cursor_rejects_garbage() {
+        assert!(HistoryCursor::decode("not a cursor").is_err());
+        assert!(HistoryCursor::decode(&URL_SAFE_NO_PAD.encode("2025-01-01T00:00:00Z")).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! POS inventory uploads: stock and price changes matched to products by SKU.
+
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::db::products::active_sale;
+use crate::entity::inventory_sync::{
+    self, ActiveModel as InventorySyncActiveModel, Entity as InventorySyncEntity,
+    Model as InventorySyncModel,
+};
+use crate::entity::product::{
+    self, ActiveModel as ProductActiveModel, Entity as ProductEntity, Model as ProductModel,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use std::collections::HashMap;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// `source` of the change-history entries written by an upload
+pub const INVENTORY_SYNC_SOURCE: &str = "inventory_sync";
+
+/// One validated row of an upload
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryUpdate {
+    pub sku: String,
+    pub quantity: i32,
+    /// New regular price; left as is when None
+    pub price: Option<f64>,
+}
+
+/// What happened to one update
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkuOutcome {
+    Updated {
+        before: Box<ProductModel>,
+        after: Box<ProductModel>,
+    },
+    /// The product already had this quantity and price
+    Unchanged,
+    NotFound,
+    /// The product exists but the update would break it
+    Rejected(String),
+}
+
+/// Check an update against the product it targets
+pub fn check_update(product: &ProductModel, update: &InventoryUpdate) -> Result<bool, String> {
+    let price = update.price.unwrap_or(product.price);
+    if active_sale(product, Utc::now()).is_some_and(|sale| sale.price >= price) {
+        return Err("price must stay above the current sale price".to_string());
+    }
+    Ok(product.quantity_available != update.quantity || product.price != price)
+}
+
+/// Turns the outcomes of an upload into the report stored with it
+pub type ReportBuilder<'a> = dyn Fn(&[SkuOutcome]) -> serde_json::Value + Sync + 'a;
+
+pub struct InventorySync;
+
+impl InventorySync {
+    /// The upload recorded under `sync_id`, if it was already applied
+    pub async fn find(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        sync_id: &str,
+    ) -> Result<Option<InventorySyncModel>, String> {
+        InventorySyncEntity::find()
+            .filter(inventory_sync::Column::StoreId.eq(store_id))
+            .filter(inventory_sync::Column::SyncId.eq(sync_id))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up inventory sync {}: {:?}", sync_id, e);
+                "Failed to look up inventory sync. Please try again later.".to_string()
+            })
+    }
+
+    /// Apply `updates` to the store's products and record the upload, all in
+    /// one transaction. `report` turns the outcomes into the stored report.
+    ///
+    /// Outcomes line up with `updates`. Fails, applying nothing, if `sync_id`
+    /// was recorded concurrently.
+    pub async fn apply(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        sync_id: &str,
+        updates: &[InventoryUpdate],
+        report: &ReportBuilder<'_>,
+    ) -> Result<(Vec<SkuOutcome>, serde_json::Value), String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!(
+                "Inventory sync {} for store {} failed: {:?}",
+                sync_id, store_id, e
+            );
+            "Failed to apply inventory sync. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+
+        let skus: Vec<&str> = updates.iter().map(|u| u.sku.as_str()).collect();
+        let mut products: HashMap<String, ProductModel> = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .filter(product::Column::Sku.is_in(skus))
+            .all(&txn)
+            .await
+            .map_err(fail)?
+            .into_iter()
+            .filter_map(|p| Some((p.sku.clone()?, p)))
+            .collect();
+
+        let now = Utc::now();
+        let mut outcomes = Vec::with_capacity(updates.len());
+        for update in updates {
+            let Some(product) = products.remove(&update.sku) else {
+                outcomes.push(SkuOutcome::NotFound);
+                continue;
+            };
+            match check_update(&product, update) {
+                Err(reason) => outcomes.push(SkuOutcome::Rejected(reason)),
+                Ok(false) => outcomes.push(SkuOutcome::Unchanged),
+                Ok(true) => {
+                    let mut active: ProductActiveModel = product.clone().into();
+                    active.quantity_available = Set(update.quantity);
+                    if let Some(price) = update.price {
+                        active.price = Set(price);
+                    }
+                    active.updated_at = Set(now);
+                    let after = active.update(&txn).await.map_err(fail)?;
+                    AuditLog::record_product(
+                        &txn,
+                        &product,
+                        &after,
+                        ChangeOrigin::system(INVENTORY_SYNC_SOURCE),
+                    )
+                    .await?;
+                    outcomes.push(SkuOutcome::Updated {
+                        before: Box::new(product),
+                        after: Box::new(after),
+                    });
+                }
+            }
+        }
+
+        let report = report(&outcomes);
+        InventorySyncActiveModel {
+            id: Set(Uuid::new_v4()),
+            store_id: Set(store_id),
+            sync_id: Set(sync_id.to_owned()),
+            report: Set(report.clone()),
+            created_at: Set(now),
+        }
+        .insert(&txn)
+        .await
+        .map_err(fail)?;
+        txn.commit().await.map_err(fail)?;
+        debug!(store_id = %store_id, sync_id, "Inventory sync applied");
+        Ok((outcomes, report))
+    }
+}
+
+/// Where uploads are applied and remembered; the database in production
+#[async_trait::async_trait]
+pub trait InventoryLedger {
+    /// Report stored for an already applied `sync_id`
+    async fn recorded(
+        &self,
+        store_id: Uuid,
+        sync_id: &str,
+    ) -> Result<Option<serde_json::Value>, String>;
+
+    /// Apply `updates` and record `sync_id` with the report built from the outcomes
+    async fn apply(
+        &self,
+        store_id: Uuid,
+        sync_id: &str,
+        updates: &[InventoryUpdate],
+        report: &ReportBuilder<'_>,
+    ) -> Result<(Vec<SkuOutcome>, serde_json::Value), String>;
+}
+
+pub struct DbInventoryLedger<'a> {
+    pub db: &'a DatabaseConnection,
+}
+
+#[async_trait::async_trait]
+impl InventoryLedger for DbInventoryLedger<'_> {
+    async fn recorded(
+        &self,
+        store_id: Uuid,
+        sync_id: &str,
+    ) -> Result<Option<serde_json::Value>, String> {
+        Ok(InventorySync::find(self.db, store_id, sync_id)
+            .await?
+            .map(|sync| sync.report))
+    }
+
+    async fn apply(
+        &self,
+        store_id: Uuid,
+        sync_id: &str,
+        updates: &[InventoryUpdate],
+        report: &ReportBuilder<'_>,
+    ) -> Result<(Vec<SkuOutcome>, serde_json::Value), String> {
+        InventorySync::apply(self.db, store_id, sync_id, updates, report).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(quantity: i32, price: f64, sale_price: Option<f64>) -> ProductModel {
+        let now = Utc::now();
+        ProductModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            sku: Some("TSHIRT-M".to_string()),
+            name: "T-shirt".to_string(),
+            description: None,
+            price,
+            currency: "XAF".to_string(),
+            sale_price,
+            sale_ends_at: sale_price.map(|_| now + chrono::Duration::days(1)),
+            quantity_available: quantity,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn update(quantity: i32, price: Option<f64>) -> InventoryUpdate {
+        InventoryUpdate {
+            sku: "TSHIRT-M".to_string(),
+            quantity,
+            price,
+        }
+    }
+
+    #[test]
+    fn test_check_update() {
+        let tshirt = product(5, 3000.0, None);
+        assert_eq!(check_update(&tshirt, &update(5, None)), Ok(false));
+        assert_eq!(check_update(&tshirt, &update(5, Some(3000.0))), Ok(false));
+        assert_eq!(check_update(&tshirt, &update(4, None)), Ok(true));
+        assert_eq!(check_update(&tshirt, &update(5, Some(2800.0))), Ok(true));
+
+        let on_sale = product(5, 3000.0, Some(2500.0));
+        assert!(check_update(&on_sale, &update(5, Some(2400.0))).is_err());
+    }
+}
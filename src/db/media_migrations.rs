@@ -0,0 +1,257 @@
+//! `media_migrations` rows and the key updates of each batch; the copying
+//! itself lives in `crate::media_migration`.
+
+use crate::entity::media_migration::{self, ActiveModel, Entity as MediaMigrationEntity, Model};
+use crate::entity::product_media::{
+    self, ActiveModel as ProductMediaActiveModel, Entity as ProductMediaEntity,
+    Model as ProductMediaModel,
+};
+use crate::media_migration::{
+    MigrationFailure, MigrationStatus, MovedMedia, MAX_RECORDED_FAILURES,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// What an admin asked for
+#[derive(Debug, Clone)]
+pub struct NewMediaMigration {
+    pub requested_by: String,
+    pub source: String,
+    pub target: String,
+    pub key_prefix: Option<String>,
+    pub dry_run: bool,
+}
+
+/// Outcome of one batch, written in one transaction
+#[derive(Debug, Clone, Default)]
+pub struct BatchProgress {
+    pub last_media_id: Option<Uuid>,
+    pub media_done: i64,
+    pub objects_copied: i64,
+    pub bytes_copied: i64,
+    pub failures: Vec<MigrationFailure>,
+    /// Media now stored under new keys
+    pub moved: Vec<MovedMedia>,
+}
+
+pub struct MediaMigration;
+
+impl MediaMigration {
+    /// Record a new run, unless another one is still running. Runs not
+    /// updated since `stale_before` are taken to have died.
+    pub async fn create(
+        db: &DatabaseConnection,
+        request: &NewMediaMigration,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to start media migration: {:?}", e);
+            "Failed to start media migration".to_string()
+        };
+        let running = MediaMigrationEntity::find()
+            .filter(media_migration::Column::Status.eq(MigrationStatus::Running.as_str()))
+            .filter(media_migration::Column::UpdatedAt.gte(stale_before))
+            .count(db)
+            .await
+            .map_err(fail)?;
+        if running > 0 {
+            return Ok(None);
+        }
+
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let row = ActiveModel {
+            id: Set(id),
+            requested_by: Set(request.requested_by.clone()),
+            source: Set(request.source.clone()),
+            target: Set(request.target.clone()),
+            key_prefix: Set(request.key_prefix.clone()),
+            dry_run: Set(request.dry_run),
+            status: Set(MigrationStatus::Running.as_str().to_string()),
+            last_media_id: Set(None),
+            media_done: Set(0),
+            objects_copied: Set(0),
+            bytes_copied: Set(0),
+            media_failed: Set(0),
+            failures: Set(serde_json::json!([])),
+            error: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+            finished_at: Set(None),
+        };
+        MediaMigrationEntity::insert(row)
+            .exec_without_returning(db)
+            .await
+            .map_err(fail)?;
+        MediaMigrationEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(fail)
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<Model>, String> {
+        MediaMigrationEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to load media migration {}: {:?}", id, e);
+                "Failed to load media migration".to_string()
+            })
+    }
+
+    /// Mark a failed or stalled run running again. Returns `None` when it is
+    /// finished or still running; the status check in the update means two
+    /// callers never both resume it.
+    pub async fn resume(
+        db: &DatabaseConnection,
+        id: Uuid,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to resume media migration {}: {:?}", id, e);
+            "Failed to resume media migration".to_string()
+        };
+        let resumable = Condition::any()
+            .add(media_migration::Column::Status.eq(MigrationStatus::Failed.as_str()))
+            .add(
+                Condition::all()
+                    .add(media_migration::Column::Status.eq(MigrationStatus::Running.as_str()))
+                    .add(media_migration::Column::UpdatedAt.lt(stale_before)),
+            );
+        let claimed = MediaMigrationEntity::update_many()
+            .col_expr(
+                media_migration::Column::Status,
+                sea_orm::sea_query::Expr::value(MigrationStatus::Running.as_str()),
+            )
+            .col_expr(
+                media_migration::Column::Error,
+                sea_orm::sea_query::Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                media_migration::Column::UpdatedAt,
+                sea_orm::sea_query::Expr::value(Utc::now()),
+            )
+            .filter(media_migration::Column::Id.eq(id))
+            .filter(resumable)
+            .exec(db)
+            .await
+            .map_err(fail)?;
+        if claimed.rows_affected == 0 {
+            return Ok(None);
+        }
+        MediaMigrationEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(fail)
+    }
+
+    /// The next `limit` media rows after `after`, in id order
+    pub async fn next_batch(
+        db: &DatabaseConnection,
+        after: Option<Uuid>,
+        limit: u64,
+    ) -> Result<Vec<ProductMediaModel>, String> {
+        let mut query = ProductMediaEntity::find();
+        if let Some(after) = after {
+            query = query.filter(product_media::Column::Id.gt(after));
+        }
+        query
+            .order_by_asc(product_media::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list media to migrate: {:?}", e);
+                "Failed to list media to migrate".to_string()
+            })
+    }
+
+    /// Point moved media at their new keys and add the batch to the run's
+    /// progress, together, so a resumed run never copies a batch whose keys
+    /// were already rewritten
+    pub async fn record_batch(
+        db: &DatabaseConnection,
+        id: Uuid,
+        batch: &BatchProgress,
+    ) -> Result<(), String> {
+        let fail = |e: DbErr| {
+            error!("Failed to record media migration {} batch: {:?}", id, e);
+            "Failed to record media migration progress".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let now = Utc::now();
+        for moved in &batch.moved {
+            ProductMediaActiveModel {
+                id: Set(moved.media_id),
+                s3_key: Set(moved.s3_key.clone()),
+                webp_s3_key: Set(moved.webp_s3_key.clone()),
+                updated_at: Set(now),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(fail)?;
+        }
+
+        let run = MediaMigrationEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| format!("Media migration {id} not found"))?;
+        let mut failures: Vec<serde_json::Value> =
+            serde_json::from_value(run.failures.clone()).unwrap_or_default();
+        let room = MAX_RECORDED_FAILURES.saturating_sub(failures.len());
+        failures.extend(
+            batch
+                .failures
+                .iter()
+                .take(room)
+                .map(|failure| serde_json::json!(failure)),
+        );
+        let mut active: ActiveModel = run.clone().into();
+        active.last_media_id = Set(batch.last_media_id.or(run.last_media_id));
+        active.media_done = Set(run.media_done + batch.media_done);
+        active.objects_copied = Set(run.objects_copied + batch.objects_copied);
+        active.bytes_copied = Set(run.bytes_copied + batch.bytes_copied);
+        active.media_failed = Set(run.media_failed + batch.failures.len() as i64);
+        active.failures = Set(serde_json::json!(failures));
+        active.updated_at = Set(now);
+        active.update(&txn).await.map_err(fail)?;
+        txn.commit().await.map_err(fail)
+    }
+
+    /// End the run as `completed`, or `failed` with `error`
+    pub async fn finish(
+        db: &DatabaseConnection,
+        id: Uuid,
+        error: Option<&str>,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to finish media migration {}: {:?}", id, e);
+            "Failed to finish media migration".to_string()
+        };
+        let Some(run) = MediaMigrationEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(None);
+        };
+        let status = match error {
+            Some(_) => MigrationStatus::Failed,
+            None => MigrationStatus::Completed,
+        };
+        let now = Utc::now();
+        let mut active: ActiveModel = run.into();
+        active.status = Set(status.as_str().to_string());
+        active.error = Set(error.map(str::to_owned));
+        active.updated_at = Set(now);
+        active.finished_at = Set(Some(now));
+        active.update(db).await.map(Some).map_err(fail)
+    }
+}
@@ -0,0 +1,404 @@
+//! Per-store limits on media uploads, so one seller cannot fill the bucket.
+//!
+//! Each store has a byte quota over the originals it keeps, a cap on images
+//! per product and a daily upload count. The byte counter lives on the store
+//! row and moves in the same transaction as the media row it accounts for.
+
+use crate::entity::product::Entity as ProductEntity;
+use crate::entity::product_media::{self, Entity as ProductMediaEntity};
+use crate::entity::store::{
+    self, ActiveModel as StoreActiveModel, Entity as StoreEntity, Model as StoreModel,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, PaginatorTrait, QueryFilter, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Upload limits of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct MediaLimits {
+    pub images_per_product: u32,
+    /// Bytes of original uploads a store may keep
+    pub store_bytes: i64,
+    /// Uploads per UTC day; deleting media does not give them back
+    pub daily_uploads: u32,
+    /// Images a product needs before it can be published; 0 turns the
+    /// check off. Set per deployment, never per store.
+    pub min_images_to_publish: u32,
+}
+
+impl Default for MediaLimits {
+    fn default() -> Self {
+        Self {
+            images_per_product: 10,
+            store_bytes: 500 * 1024 * 1024,
+            daily_uploads: 200,
+            min_images_to_publish: 1,
+        }
+    }
+}
+
+impl MediaLimits {
+    /// The limits of `store`: its admin overrides where set, these otherwise
+    pub fn for_store(&self, store: &StoreModel) -> Self {
+        Self {
+            images_per_product: store
+                .media_images_per_product
+                .map_or(self.images_per_product, |n| n.max(0) as u32),
+            store_bytes: store.media_quota_bytes.unwrap_or(self.store_bytes),
+            daily_uploads: store
+                .media_daily_uploads
+                .map_or(self.daily_uploads, |n| n.max(0) as u32),
+            min_images_to_publish: self.min_images_to_publish,
+        }
+    }
+}
+
+/// Uploads counted against `today`; yesterday's count no longer applies
+fn uploads_on(store: &StoreModel, today: NaiveDate) -> i32 {
+    if store.media_uploads_day == Some(today) {
+        store.media_uploads_today
+    } else {
+        0
+    }
+}
+
+/// Result of charging an upload to its store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaCharge {
+    Charged,
+    /// The product already has `limit` images
+    TooManyImages {
+        limit: u32,
+    },
+    /// The upload would take the store past its byte quota
+    StoreFull {
+        limit: i64,
+        used: i64,
+    },
+    /// The store made `limit` uploads today
+    DailyLimit {
+        limit: u32,
+    },
+}
+
+/// A store's media usage against its limits
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct MediaUsage {
+    pub bytes_used: i64,
+    pub bytes_quota: i64,
+    pub uploads_today: i32,
+    pub daily_upload_limit: u32,
+    pub images_per_product_limit: u32,
+}
+
+impl MediaUsage {
+    pub fn new(store: &StoreModel, defaults: &MediaLimits, now: DateTime<Utc>) -> Self {
+        let limits = defaults.for_store(store);
+        Self {
+            bytes_used: store.media_bytes_used,
+            bytes_quota: limits.store_bytes,
+            uploads_today: uploads_on(store, now.date_naive()),
+            daily_upload_limit: limits.daily_uploads,
+            images_per_product_limit: limits.images_per_product,
+        }
+    }
+}
+
+/// Per-store overrides set by an admin; `None` falls back to the configured value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaQuotaOverrides {
+    pub store_bytes: Option<i64>,
+    pub daily_uploads: Option<i32>,
+    pub images_per_product: Option<i32>,
+}
+
+pub struct MediaQuota;
+
+impl MediaQuota {
+    /// Count an upload of `bytes` to `product_id` against its store's limits.
+    ///
+    /// Run it in the upload's transaction: the store row stays locked until
+    /// commit, so concurrent uploads cannot both squeeze under the quota, and
+    /// a failed upload rolls the charge back.
+    pub async fn charge_upload<C: ConnectionTrait>(
+        conn: &C,
+        defaults: &MediaLimits,
+        product_id: Uuid,
+        bytes: i64,
+        now: DateTime<Utc>,
+    ) -> Result<MediaCharge, String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to charge media upload for product {}: {:?}",
+                product_id, e
+            );
+            "Failed to check media quota. Please try again later.".to_string()
+        };
+        let product = ProductEntity::find_by_id(product_id)
+            .one(conn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Product not found.".to_string())?;
+        let store = StoreEntity::find_by_id(product.store_id)
+            .one(conn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Store not found.".to_string())?;
+        let limits = defaults.for_store(&store);
+
+        let images = ProductMediaEntity::find()
+            .filter(product_media::Column::ProductId.eq(product_id))
+            .count(conn)
+            .await
+            .map_err(fail)?;
+        if images >= u64::from(limits.images_per_product) {
+            return Ok(MediaCharge::TooManyImages {
+                limit: limits.images_per_product,
+            });
+        }
+
+        // A new day starts the upload count over
+        let today = now.date_naive();
+        StoreEntity::update_many()
+            .col_expr(store::Column::MediaUploadsDay, Expr::value(today))
+            .col_expr(store::Column::MediaUploadsToday, Expr::value(0))
+            .filter(store::Column::Id.eq(store.id))
+            .filter(
+                Condition::any()
+                    .add(store::Column::MediaUploadsDay.is_null())
+                    .add(store::Column::MediaUploadsDay.ne(today)),
+            )
+            .exec(conn)
+            .await
+            .map_err(fail)?;
+
+        // Both limits are checked by the update itself. `updated_at` is left
+        // alone: quota bookkeeping is not a change clients need to sync.
+        let charged = StoreEntity::update_many()
+            .col_expr(
+                store::Column::MediaBytesUsed,
+                Expr::col(store::Column::MediaBytesUsed).add(bytes),
+            )
+            .col_expr(
+                store::Column::MediaUploadsToday,
+                Expr::col(store::Column::MediaUploadsToday).add(1),
+            )
+            .filter(store::Column::Id.eq(store.id))
+            .filter(store::Column::MediaBytesUsed.lte(limits.store_bytes - bytes))
+            .filter(store::Column::MediaUploadsToday.lt(limits.daily_uploads))
+            .exec(conn)
+            .await
+            .map_err(fail)?;
+        if charged.rows_affected > 0 {
+            return Ok(MediaCharge::Charged);
+        }
+
+        let store = StoreEntity::find_by_id(store.id)
+            .one(conn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Store not found.".to_string())?;
+        if uploads_on(&store, today) >= limits.daily_uploads as i32 {
+            Ok(MediaCharge::DailyLimit {
+                limit: limits.daily_uploads,
+            })
+        } else {
+            Ok(MediaCharge::StoreFull {
+                limit: limits.store_bytes,
+                used: store.media_bytes_used,
+            })
+        }
+    }
+
+    /// Give back the bytes of deleted media
+    pub async fn release<C: ConnectionTrait>(
+        conn: &C,
+        store_id: Uuid,
+        bytes: i64,
+    ) -> Result<(), String> {
+        StoreEntity::update_many()
+            .col_expr(
+                store::Column::MediaBytesUsed,
+                Expr::col(store::Column::MediaBytesUsed).sub(bytes),
+            )
+            .filter(store::Column::Id.eq(store_id))
+            .exec(conn)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to release media bytes of store {}: {:?}",
+                    store_id, e
+                );
+                "Failed to update media quota. Please try again later.".to_string()
+            })?;
+        Ok(())
+    }
+
+    pub async fn set_overrides(
+        db: &DatabaseConnection,
+        store: StoreModel,
+        overrides: MediaQuotaOverrides,
+    ) -> Result<StoreModel, String> {
+        let id = store.id;
+        let mut active: StoreActiveModel = store.into();
+        active.media_quota_bytes = Set(overrides.store_bytes);
+        active.media_daily_uploads = Set(overrides.daily_uploads);
+        active.media_images_per_product = Set(overrides.images_per_product);
+        active.update(db).await.map_err(|e| {
+            error!("Failed to set media quota of store {}: {:?}", id, e);
+            "Failed to set media quota. Please try again later.".to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::product_media::ProductMedia;
+    use crate::db::testing;
+
+    const LIMITS: MediaLimits = MediaLimits {
+        images_per_product: 10,
+        store_bytes: 1_000,
+        daily_uploads: 100,
+        min_images_to_publish: 1,
+    };
+
+    async fn store_of(db: &DatabaseConnection, product_id: Uuid) -> StoreModel {
+        let product = ProductEntity::find_by_id(product_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        StoreEntity::find_by_id(product.store_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn record(db: &DatabaseConnection, product_id: Uuid, bytes: i64) -> Uuid {
+        let image_id = Uuid::new_v4();
+        let media = product_media::ActiveModel {
+            id: Set(image_id),
+            product_id: Set(product_id),
+            s3_key: Set(format!("products/{product_id}/media/{image_id}.jpg")),
+            content_type: Set("image/jpeg".to_string()),
+            size_bytes: Set(bytes),
+            webp_s3_key: Set(None),
+            webp_size_bytes: Set(None),
+            content_hash: Set(None),
+            perceptual_hash: Set(None),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+        };
+        ProductMediaEntity::insert(media)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        image_id
+    }
+
+    #[tokio::test]
+    async fn test_byte_quota_boundary_and_deletes_free_space() {
+        let db = testing::sqlite().await;
+        let product_id = testing::seed_product(&db, "seller-1").await;
+        let now = Utc::now();
+
+        let charge = |bytes| MediaQuota::charge_upload(&db, &LIMITS, product_id, bytes, now);
+        assert_eq!(charge(600).await.unwrap(), MediaCharge::Charged);
+        let first = record(&db, product_id, 600).await;
+        // Exactly filling the quota is allowed
+        assert_eq!(charge(400).await.unwrap(), MediaCharge::Charged);
+        record(&db, product_id, 400).await;
+        assert_eq!(
+            charge(1).await.unwrap(),
+            MediaCharge::StoreFull {
+                limit: 1_000,
+                used: 1_000
+            }
+        );
+
+        let media = ProductMedia::get(&db, first).await.unwrap().unwrap();
+        let store = store_of(&db, product_id).await;
+        ProductMedia::delete(&db, store.id, &media).await.unwrap();
+        assert_eq!(store_of(&db, product_id).await.media_bytes_used, 400);
+        assert_eq!(charge(600).await.unwrap(), MediaCharge::Charged);
+        assert_eq!(
+            charge(1).await.unwrap(),
+            MediaCharge::StoreFull {
+                limit: 1_000,
+                used: 1_000
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_daily_and_per_product_limits() {
+        let db = testing::sqlite().await;
+        let product_id = testing::seed_product(&db, "seller-1").await;
+        let limits = MediaLimits {
+            images_per_product: 2,
+            store_bytes: 1_000,
+            daily_uploads: 3,
+            min_images_to_publish: 1,
+        };
+        let now = Utc::now();
+
+        for _ in 0..2 {
+            assert_eq!(
+                MediaQuota::charge_upload(&db, &limits, product_id, 10, now)
+                    .await
+                    .unwrap(),
+                MediaCharge::Charged
+            );
+            record(&db, product_id, 10).await;
+        }
+        assert_eq!(
+            MediaQuota::charge_upload(&db, &limits, product_id, 10, now)
+                .await
+                .unwrap(),
+            MediaCharge::TooManyImages { limit: 2 }
+        );
+
+        // An admin raises the per-product cap; the daily count still applies
+        let store = store_of(&db, product_id).await;
+        let overrides = MediaQuotaOverrides {
+            images_per_product: Some(20),
+            ..Default::default()
+        };
+        MediaQuota::set_overrides(&db, store, overrides)
+            .await
+            .unwrap();
+        assert_eq!(
+            MediaQuota::charge_upload(&db, &limits, product_id, 10, now)
+                .await
+                .unwrap(),
+            MediaCharge::Charged
+        );
+        assert_eq!(
+            MediaQuota::charge_upload(&db, &limits, product_id, 10, now)
+                .await
+                .unwrap(),
+            MediaCharge::DailyLimit { limit: 3 }
+        );
+        // Tomorrow the count starts over
+        let tomorrow = now + chrono::Duration::days(1);
+        assert_eq!(
+            MediaQuota::charge_upload(&db, &limits, product_id, 10, tomorrow)
+                .await
+                .unwrap(),
+            MediaCharge::Charged
+        );
+        let usage = MediaUsage::new(&store_of(&db, product_id).await, &limits, tomorrow);
+        assert_eq!(usage.uploads_today, 1);
+        assert_eq!(usage.bytes_used, 40);
+        assert_eq!(usage.images_per_product_limit, 20);
+    }
+}
@@ -0,0 +1,371 @@
+//! Flags raised when an upload looks like an image another store already
+//! uses; see `moderation::image_hash`. Flags never block an upload, they
+//! only put the pair in front of an admin.
+
+use crate::db::moderation::ProductModeration;
+use crate::entity::media_similarity_flag::{
+    self, ActiveModel as FlagActiveModel, Entity as FlagEntity, Model as FlagModel,
+};
+use crate::entity::product::{self, Entity as ProductEntity};
+use crate::entity::product_media::{
+    self, Entity as ProductMediaEntity, Model as ProductMediaModel,
+};
+use crate::moderation::image_hash::{distance, from_column, MAX_DISTANCE};
+use chrono::Utc;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, JoinType, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Closest matches flagged for one upload, so a stock photo used by many
+/// stores doesn't flood the queue
+pub const MAX_FLAGS_PER_UPLOAD: usize = 20;
+
+/// Where a flag stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagStatus {
+    Pending,
+    /// Not a copy, or an acceptable one
+    Dismissed,
+    /// The uploading listing was sent to the moderation queue
+    Held,
+}
+
+impl FlagStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagStatus::Pending => "pending",
+            FlagStatus::Dismissed => "dismissed",
+            FlagStatus::Held => "held",
+        }
+    }
+}
+
+/// An image of another store close enough to an upload to flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimilarImage {
+    pub media_id: Uuid,
+    pub product_id: Uuid,
+    pub store_id: Uuid,
+    pub distance: u32,
+}
+
+/// Candidates within [`MAX_DISTANCE`] of `hash`, closest first, at most
+/// [`MAX_FLAGS_PER_UPLOAD`] of them
+pub fn closest_matches(
+    hash: u64,
+    candidates: impl IntoIterator<Item = (Uuid, Uuid, Uuid, u64)>,
+) -> Vec<SimilarImage> {
+    let mut matches: Vec<SimilarImage> = candidates
+        .into_iter()
+        .filter_map(|(media_id, product_id, store_id, other)| {
+            let distance = distance(hash, other);
+            (distance <= MAX_DISTANCE).then_some(SimilarImage {
+                media_id,
+                product_id,
+                store_id,
+                distance,
+            })
+        })
+        .collect();
+    matches.sort_by_key(|m| m.distance);
+    matches.truncate(MAX_FLAGS_PER_UPLOAD);
+    matches
+}
+
+pub struct MediaSimilarity;
+
+impl MediaSimilarity {
+    /// Compare a new upload with the hashed images of every other store in
+    /// its tenant and flag the close ones. Returns the flags raised.
+    ///
+    /// Hashes are compared in memory: one BIGINT per image is cheap to load,
+    /// and Hamming distance has no portable index.
+    pub async fn flag_matches<C: ConnectionTrait>(
+        conn: &C,
+        media: &ProductMediaModel,
+    ) -> Result<Vec<FlagModel>, String> {
+        let Some(hash) = media.perceptual_hash.map(from_column) else {
+            return Ok(Vec::new());
+        };
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to check media {} for copies: {:?}", media.id, e);
+            "Failed to check the image for copies. Please try again later.".to_string()
+        };
+        let Some((store_id, tenant_id)) = ProductEntity::find_by_id(media.product_id)
+            .select_only()
+            .column(product::Column::StoreId)
+            .column(product::Column::TenantId)
+            .into_tuple::<(Uuid, String)>()
+            .one(conn)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(Vec::new());
+        };
+
+        let candidates = ProductMediaEntity::find()
+            .select_only()
+            .column(product_media::Column::Id)
+            .column(product_media::Column::ProductId)
+            .column(product::Column::StoreId)
+            .column(product_media::Column::PerceptualHash)
+            .join(JoinType::InnerJoin, product_media::Relation::Product.def())
+            .filter(product::Column::TenantId.eq(&tenant_id))
+            .filter(product::Column::StoreId.ne(store_id))
+            .filter(product_media::Column::PerceptualHash.is_not_null())
+            .into_tuple::<(Uuid, Uuid, Uuid, i64)>()
+            .all(conn)
+            .await
+            .map_err(fail)?;
+        let matches = closest_matches(
+            hash,
+            candidates
+                .into_iter()
+                .map(|(id, product_id, store_id, hash)| {
+                    (id, product_id, store_id, from_column(hash))
+                }),
+        );
+        if matches.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Utc::now();
+        let flags: Vec<FlagModel> = matches
+            .iter()
+            .map(|m| FlagModel {
+                id: Uuid::new_v4(),
+                tenant_id: tenant_id.clone(),
+                media_id: media.id,
+                product_id: media.product_id,
+                store_id,
+                matched_media_id: m.media_id,
+                matched_product_id: m.product_id,
+                matched_store_id: m.store_id,
+                distance: m.distance as i32,
+                status: FlagStatus::Pending.as_str().to_string(),
+                created_at: now,
+                reviewed_by: None,
+                reviewed_at: None,
+            })
+            .collect();
+        FlagEntity::insert_many(flags.iter().cloned().map(FlagActiveModel::from))
+            .exec_without_returning(conn)
+            .await
+            .map_err(fail)?;
+        debug!(media_id = %media.id, flags = flags.len(), "Upload resembles other stores' images");
+        Ok(flags)
+    }
+
+    /// One tenant's flags with `status`, oldest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        status: FlagStatus,
+    ) -> Result<Vec<FlagModel>, String> {
+        FlagEntity::find()
+            .filter(media_similarity_flag::Column::TenantId.eq(tenant_id))
+            .filter(media_similarity_flag::Column::Status.eq(status.as_str()))
+            .order_by_asc(media_similarity_flag::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list media similarity flags: {:?}", e);
+                "Failed to list image flags. Please try again later.".to_string()
+            })
+    }
+
+    /// Record an admin decision. Holding sends the uploading listing to the
+    /// moderation queue, where it stays hidden until approved. `Ok(None)` if
+    /// there is no such flag.
+    pub async fn review(
+        db: &DatabaseConnection,
+        id: Uuid,
+        decision: FlagStatus,
+        reviewed_by: &str,
+    ) -> Result<Option<FlagModel>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to review media similarity flag {}: {:?}", id, e);
+            "Failed to record review. Please try again later.".to_string()
+        };
+        let Some(flag) = FlagEntity::find_by_id(id).one(db).await.map_err(fail)? else {
+            return Ok(None);
+        };
+        if decision == FlagStatus::Held {
+            let Some(product) = ProductEntity::find_by_id(flag.product_id)
+                .one(db)
+                .await
+                .map_err(fail)?
+            else {
+                return Ok(None);
+            };
+            let reason = serde_json::json!([{
+                "category": "duplicate_image",
+                "media_id": flag.media_id,
+                "matched_product_id": flag.matched_product_id,
+                "matched_media_id": flag.matched_media_id,
+            }]);
+            ProductModeration::hold_for(db, product, reason).await?;
+        }
+
+        let now = Utc::now();
+        FlagEntity::update_many()
+            .col_expr(
+                media_similarity_flag::Column::Status,
+                sea_orm::sea_query::Expr::value(decision.as_str()),
+            )
+            .col_expr(
+                media_similarity_flag::Column::ReviewedBy,
+                sea_orm::sea_query::Expr::value(reviewed_by),
+            )
+            .col_expr(
+                media_similarity_flag::Column::ReviewedAt,
+                sea_orm::sea_query::Expr::value(now),
+            )
+            .filter(media_similarity_flag::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(fail)?;
+        Ok(Some(FlagModel {
+            status: decision.as_str().to_string(),
+            reviewed_by: Some(reviewed_by.to_string()),
+            reviewed_at: Some(now),
+            ..flag
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::moderation::image_hash::to_column;
+
+    async fn seed_media(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        hash: Option<u64>,
+    ) -> ProductMediaModel {
+        let now = Utc::now();
+        let media = ProductMediaModel {
+            id: Uuid::new_v4(),
+            product_id,
+            s3_key: format!("products/{product_id}/photo.jpg"),
+            content_type: "image/jpeg".to_string(),
+            size_bytes: 1024,
+            webp_s3_key: None,
+            webp_size_bytes: None,
+            content_hash: None,
+            perceptual_hash: hash.map(to_column),
+            created_at: now,
+            updated_at: now,
+        };
+        ProductMediaEntity::insert(product_media::ActiveModel::from(media.clone()))
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        media
+    }
+
+    #[test]
+    fn test_closest_matches_keeps_near_hashes_in_order() {
+        let hash = 0xF0F0_F0F0_F0F0_F0F0u64;
+        let near = |bits: u32| hash ^ ((1u64 << bits) - 1);
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let store = Uuid::new_v4();
+        let matches = closest_matches(
+            hash,
+            [
+                (ids[0], ids[0], store, near(MAX_DISTANCE)),
+                (ids[1], ids[1], store, near(MAX_DISTANCE + 1)),
+                (ids[2], ids[2], store, hash),
+                (ids[3], ids[3], store, !hash),
+            ],
+        );
+        let found: Vec<(Uuid, u32)> = matches.iter().map(|m| (m.media_id, m.distance)).collect();
+        assert_eq!(found, vec![(ids[2], 0), (ids[0], MAX_DISTANCE)]);
+    }
+
+    #[tokio::test]
+    async fn test_flags_copies_from_other_stores_only() {
+        use crate::db::products::Product;
+        use crate::entity::product_moderation;
+
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let listing = || {
+            Product::create(
+                &db,
+                store_id,
+                None,
+                "Wax print",
+                None,
+                5000.0,
+                "XAF",
+                3,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+        };
+        let original = listing().await.unwrap();
+        let sibling = listing().await.unwrap();
+        let copier = testing::seed_product(&db, "seller-2").await;
+        let photo = 0x1234_5678_9ABC_DEF0u64;
+
+        seed_media(&db, original.id, Some(photo)).await;
+        // Uploads from before hashing never match
+        seed_media(&db, original.id, None).await;
+        // The same photo on two listings of one store is not a copy
+        let reused = seed_media(&db, sibling.id, Some(photo)).await;
+        assert!(MediaSimilarity::flag_matches(&db, &reused)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let copy = seed_media(&db, copier, Some(photo ^ 0b111)).await;
+        let flags = MediaSimilarity::flag_matches(&db, &copy).await.unwrap();
+        assert_eq!(flags.len(), 2, "{flags:?}");
+        assert!(flags
+            .iter()
+            .all(|f| f.matched_store_id == store_id && f.distance == 3 && f.product_id == copier));
+        let different = seed_media(&db, copier, Some(!photo)).await;
+        assert!(MediaSimilarity::flag_matches(&db, &different)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let dismissed = MediaSimilarity::review(&db, flags[0].id, FlagStatus::Dismissed, "admin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dismissed.status, "dismissed");
+        MediaSimilarity::review(&db, flags[1].id, FlagStatus::Held, "admin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(MediaSimilarity::list(&db, "default", FlagStatus::Pending)
+            .await
+            .unwrap()
+            .is_empty());
+        let held = product_moderation::Entity::find()
+            .filter(product_moderation::Column::ProductId.eq(copier))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(held.status, "pending");
+        assert_eq!(held.matched_terms[0]["category"], "duplicate_image");
+    }
+}
@@ -1,8 +1,48 @@
+pub mod admin_credentials;
+pub mod analytics;
+pub mod api_keys;
+pub mod bulk_prices;
+pub mod bundles;
+pub mod categories;
+pub mod category_attributes;
+pub mod commissions;
+pub mod delivery;
+pub mod diff;
+pub mod events;
+pub mod experiments;
+pub mod history;
+pub mod inventory_sync;
+pub mod media_migrations;
+pub mod media_quota;
+pub mod media_similarity;
+pub mod moderation;
+pub mod notifications;
+pub mod onboarding;
+pub mod payout_accounts;
+pub mod product_counts;
+pub mod product_media;
+pub mod product_views;
 pub mod products;
+pub mod promotions;
+pub mod public_stats;
+pub mod questions;
+pub mod referrals;
+pub mod reports;
+pub mod retention;
+pub mod return_policy;
+pub mod review_anomalies;
+pub mod schema_migrations;
+pub mod seo;
+pub mod snapshots;
+pub mod store_reviews;
 pub mod stores;
+pub mod sync;
+pub mod system_settings;
+pub mod watches;
 
 use crate::config::Config;
-use sea_orm::{Database, DatabaseConnection};
+use sea_orm::{Database, DatabaseConnection, DbErr, RuntimeErr};
+use std::future::Future;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -10,7 +50,7 @@ pub async fn create_connection(config: &Config) -> anyhow::Result<DatabaseConnec
     let max_attempts = 20u32;
     let mut attempt = 0u32;
     loop {
-        match Database::connect(&config.database_url).await {
+        match Database::connect(&config.database.url).await {
             Ok(conn) => return Ok(conn),
             Err(e) => {
                 attempt += 1;
@@ -30,3 +70,409 @@ pub async fn create_connection(config: &Config) -> anyhow::Result<DatabaseConnec
         }
     }
 }
+
+/// SQLSTATE of a serializable transaction that lost to a concurrent one
+const SERIALIZATION_FAILURE: &str = "40001";
+
+/// What a failed statement ran into, for callers that handle a constraint
+/// failure instead of reporting it. `constraint` is the index or constraint
+/// name where the backend gives it: Postgres does, SQLite doesn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbErrorKind {
+    UniqueViolation {
+        constraint: Option<String>,
+    },
+    ForeignKeyViolation {
+        constraint: Option<String>,
+    },
+    CheckViolation {
+        constraint: Option<String>,
+    },
+    /// Postgres aborted a serializable transaction; running it again is safe
+    SerializationFailure,
+    Other,
+}
+
+/// Kind of `err`, from the database's own error code rather than its message
+pub fn classify(err: &DbErr) -> DbErrorKind {
+    let (DbErr::Conn(RuntimeErr::SqlxError(err))
+    | DbErr::Exec(RuntimeErr::SqlxError(err))
+    | DbErr::Query(RuntimeErr::SqlxError(err))) = err
+    else {
+        return DbErrorKind::Other;
+    };
+    let Some(err) = err.as_database_error() else {
+        return DbErrorKind::Other;
+    };
+    let constraint = err.constraint().map(str::to_owned);
+    if err.is_unique_violation() {
+        DbErrorKind::UniqueViolation { constraint }
+    } else if err.is_foreign_key_violation() {
+        DbErrorKind::ForeignKeyViolation { constraint }
+    } else if err.is_check_violation() {
+        DbErrorKind::CheckViolation { constraint }
+    } else if err.code().as_deref() == Some(SERIALIZATION_FAILURE) {
+        DbErrorKind::SerializationFailure
+    } else {
+        DbErrorKind::Other
+    }
+}
+
+/// Whether `err` is a unique index or primary key rejecting a duplicate
+pub fn is_unique_violation(err: &DbErr) -> bool {
+    matches!(classify(err), DbErrorKind::UniqueViolation { .. })
+}
+
+/// Run `insert` until it stops failing on a unique violation, at most
+/// `attempts` times. It is passed the attempt number, from 0, to derive a
+/// fresh value from (a slug suffix, the next invoice number); when every
+/// attempt collides the last violation is returned for the caller to turn
+/// into a conflict. Any other error is returned at once.
+// Generated slugs and numbers insert through this
+#[allow(dead_code)]
+pub async fn retry_on_unique<T, F, Fut>(attempts: u32, mut insert: F) -> Result<T, DbErr>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, DbErr>>,
+{
+    let mut attempt = 0;
+    loop {
+        match insert(attempt).await {
+            Err(e) if attempt + 1 < attempts && is_unique_violation(&e) => {
+                tracing::debug!(attempt, error = %e, "Unique violation, retrying");
+                attempt += 1;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// In-memory SQLite with the catalogue tables, for tests that need real rows
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::entity::{
+        admin_credential, audit_log, bundle_item, category, category_attribute, commission_rate,
+        event_record, experiment_exposure, inventory_sync, media_migration, media_similarity_flag,
+        notification_digest_item, notification_preference, product, product_bundle, product_count,
+        product_media, product_moderation, product_price_history, product_question, product_view,
+        product_watch, profanity_term, prohibited_term, public_stat, referral_click, referral_link,
+        report_job, review_anomaly, snapshot_product, store, store_api_key, store_payout_account,
+        store_promotion, store_review, store_snapshot, system_setting, tombstone,
+    };
+    use chrono::Utc;
+    use sea_orm::{
+        ConnectOptions, ConnectionTrait, Database, DatabaseConnection, EntityTrait, Schema, Set,
+    };
+    use uuid::Uuid;
+
+    pub async fn sqlite() -> DatabaseConnection {
+        // One connection, so every query sees the same in-memory database
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1).sqlx_logging(false);
+        let db = Database::connect(options).await.unwrap();
+        create(&db, store::Entity).await;
+        create(&db, category::Entity).await;
+        create(&db, category_attribute::Entity).await;
+        create(&db, commission_rate::Entity).await;
+        create(&db, product::Entity).await;
+        create(&db, product_bundle::Entity).await;
+        create(&db, bundle_item::Entity).await;
+        create(&db, product_media::Entity).await;
+        create(&db, media_similarity_flag::Entity).await;
+        create(&db, product_moderation::Entity).await;
+        create(&db, product_question::Entity).await;
+        create(&db, product_price_history::Entity).await;
+        create(&db, inventory_sync::Entity).await;
+        create(&db, tombstone::Entity).await;
+        create(&db, admin_credential::Entity).await;
+        create(&db, product_count::Entity).await;
+        create(&db, report_job::Entity).await;
+        create(&db, audit_log::Entity).await;
+        create(&db, media_migration::Entity).await;
+        create(&db, store_payout_account::Entity).await;
+        create(&db, product_watch::Entity).await;
+        create(&db, store_review::Entity).await;
+        create(&db, experiment_exposure::Entity).await;
+        create(&db, review_anomaly::Entity).await;
+        create(&db, notification_preference::Entity).await;
+        create(&db, notification_digest_item::Entity).await;
+        create(&db, event_record::Entity).await;
+        create(&db, prohibited_term::Entity).await;
+        create(&db, profanity_term::Entity).await;
+        create(&db, store_api_key::Entity).await;
+        create(&db, store_promotion::Entity).await;
+        create(&db, system_setting::Entity).await;
+        create(&db, store_snapshot::Entity).await;
+        create(&db, snapshot_product::Entity).await;
+        create(&db, referral_link::Entity).await;
+        create(&db, referral_click::Entity).await;
+        create(&db, public_stat::Entity).await;
+        create(&db, product_view::Entity).await;
+        // Composite unique indexes aren't derived from the entity
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX idx_store_reviews_store_reviewer \
+             ON store_reviews (store_id, reviewer_id)",
+        )
+        .await
+        .unwrap();
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX idx_products_store_sku ON products (store_id, sku)",
+        )
+        .await
+        .unwrap();
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX idx_experiment_exposures_daily \
+             ON experiment_exposures (experiment, subject, exposed_on)",
+        )
+        .await
+        .unwrap();
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX idx_referral_clicks_daily \
+             ON referral_clicks (link_id, subject, clicked_on)",
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn create<E: EntityTrait>(db: &DatabaseConnection, entity: E) {
+        let backend = db.get_database_backend();
+        let table = backend.build(&Schema::new(backend).create_table_from_entity(entity));
+        // Entities leave `auto_increment` at its default on their UUID keys,
+        // which SQLite only accepts on integer keys
+        let sql = table.to_string().replace(" AUTOINCREMENT", "");
+        db.execute_unprepared(&sql).await.unwrap();
+    }
+
+    /// A new store owned by `owner`
+    pub async fn seed_store(db: &DatabaseConnection, owner: &str) -> Uuid {
+        let now = Utc::now();
+        let store_id = Uuid::new_v4();
+        // Inserted without RETURNING, which this SQLite backend can't read back
+        let store = store::ActiveModel {
+            id: Set(store_id),
+            tenant_id: Set("default".to_string()),
+            name: Set("Mama Ngono".to_string()),
+            description: Set(None),
+            logo_url: Set(None),
+            location: Set(None),
+            contact_phone: Set(None),
+            contact_email: Set(None),
+            contact_whatsapp: Set(None),
+            show_phone: Set(true),
+            show_whatsapp: Set(true),
+            show_email: Set(true),
+            embed_enabled: Set(false),
+            media_bytes_used: Set(0),
+            media_uploads_today: Set(0),
+            media_uploads_day: Set(None),
+            media_quota_bytes: Set(None),
+            media_daily_uploads: Set(None),
+            media_images_per_product: Set(None),
+            owner_device_id: Set(Some(owner.to_string())),
+            is_verified: Set(false),
+            rating: Set(None),
+            store_rating: Set(None),
+            store_review_count: Set(0),
+            total_products: Set(1),
+            default_return_policy: Set(None),
+            default_pickup_available: Set(true),
+            default_delivery_available: Set(false),
+            default_delivery_fee: Set(None),
+            default_delivery_estimated_days: Set(None),
+            is_paused: Set(false),
+            paused_until: Set(None),
+            pause_message: Set(None),
+            auto_response_enabled: Set(false),
+            auto_response_message: Set(None),
+            auto_response_trigger: Set(None),
+            trust_score: Set(None),
+            first_shared_at: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        store::Entity::insert(store)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        store_id
+    }
+
+    /// A new category named after its slug
+    pub async fn seed_category(db: &DatabaseConnection, slug: &str) -> Uuid {
+        let id = Uuid::new_v4();
+        let row = category::ActiveModel {
+            id: Set(id),
+            slug: Set(slug.to_string()),
+            name: Set(slug.to_string()),
+            created_at: Set(Utc::now()),
+        };
+        category::Entity::insert(row)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        id
+    }
+
+    /// A published product in a new store owned by `owner`
+    pub async fn seed_product(db: &DatabaseConnection, owner: &str) -> Uuid {
+        let store_id = seed_store(db, owner).await;
+        let now = Utc::now();
+        let product_id = Uuid::new_v4();
+        let product = product::ActiveModel {
+            id: Set(product_id),
+            store_id: Set(store_id),
+            tenant_id: Set("default".to_string()),
+            sku: Set(None),
+            name: Set("Wax print".to_string()),
+            description: Set(None),
+            price: Set(5000.0),
+            currency: Set("XAF".to_string()),
+            sale_price: Set(None),
+            sale_ends_at: Set(None),
+            quantity_available: Set(3),
+            low_stock_threshold: Set(None),
+            image_id: Set(None),
+            category_id: Set(None),
+            attributes: Set(serde_json::json!({})),
+            return_policy: Set(None),
+            return_policy_source: Set("store".to_string()),
+            returns_accepted: Set(false),
+            return_window_days: Set(None),
+            return_conditions: Set(None),
+            pickup_available: Set(true),
+            delivery_available: Set(false),
+            delivery_fee_override: Set(None),
+            delivery_estimated_days: Set(None),
+            delivery_options_source: Set("store_default".to_string()),
+            publish_at: Set(None),
+            is_published: Set(true),
+            archived_at: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        product::Entity::insert(product)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        product_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::ConnectionTrait;
+
+    /// A table with one constraint of each kind SQLite enforces
+    async fn constrained() -> DatabaseConnection {
+        let db = testing::sqlite().await;
+        db.execute_unprepared(
+            "CREATE TABLE slugs (\
+                 id INTEGER PRIMARY KEY, \
+                 parent_id INTEGER REFERENCES slugs (id), \
+                 slug TEXT NOT NULL UNIQUE, \
+                 position INTEGER CHECK (position >= 0))",
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn insert(db: &DatabaseConnection, slug: &str) -> Result<(), DbErr> {
+        db.execute_unprepared(&format!("INSERT INTO slugs (slug) VALUES ('{slug}')"))
+            .await
+            .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn test_constraint_failures_are_classified() {
+        let db = constrained().await;
+        insert(&db, "wax-print").await.unwrap();
+
+        for (sql, expected) in [
+            (
+                "INSERT INTO slugs (slug) VALUES ('wax-print')".to_string(),
+                DbErrorKind::UniqueViolation { constraint: None },
+            ),
+            (
+                "INSERT INTO slugs (id, slug) VALUES (1, 'kente')".to_string(),
+                DbErrorKind::UniqueViolation { constraint: None },
+            ),
+            (
+                "INSERT INTO slugs (parent_id, slug) VALUES (9, 'kente')".to_string(),
+                DbErrorKind::ForeignKeyViolation { constraint: None },
+            ),
+            (
+                "INSERT INTO slugs (slug, position) VALUES ('kente', -1)".to_string(),
+                DbErrorKind::CheckViolation { constraint: None },
+            ),
+            (
+                "INSERT INTO slugs (position) VALUES (1)".to_string(),
+                DbErrorKind::Other,
+            ),
+            ("SELECT * FROM missing".to_string(), DbErrorKind::Other),
+        ] {
+            let err = db.execute_unprepared(&sql).await.unwrap_err();
+            assert_eq!(classify(&err), expected, "{sql}");
+        }
+        assert_eq!(
+            classify(&DbErr::RecordNotFound("slug".into())),
+            DbErrorKind::Other
+        );
+
+        // Each row above failed on its one broken constraint alone
+        db.execute_unprepared(
+            "INSERT INTO slugs (parent_id, slug, position) VALUES (1, 'kente', 0)",
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_unique_takes_the_next_free_value() {
+        let db = constrained().await;
+        insert(&db, "wax-print").await.unwrap();
+        insert(&db, "wax-print-1").await.unwrap();
+        let candidate = |attempt: u32| match attempt {
+            0 => "wax-print".to_string(),
+            n => format!("wax-print-{n}"),
+        };
+
+        let mut tried = Vec::new();
+        let slug = retry_on_unique(5, |attempt| {
+            tried.push(attempt);
+            let slug = candidate(attempt);
+            let db = &db;
+            async move { insert(db, &slug).await.map(|()| slug) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(slug, "wax-print-2");
+        assert_eq!(tried, [0, 1, 2]);
+
+        // Out of attempts: the collision is handed back
+        let err = retry_on_unique(2, |attempt| {
+            let slug = candidate(attempt);
+            let db = &db;
+            async move { insert(db, &slug).await }
+        })
+        .await
+        .unwrap_err();
+        assert!(is_unique_violation(&err));
+
+        // Anything else isn't retried
+        let mut calls = 0;
+        let err = retry_on_unique(5, |_| {
+            calls += 1;
+            db.execute_unprepared("INSERT INTO slugs (slug, position) VALUES ('kente', -1)")
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            classify(&err),
+            DbErrorKind::CheckViolation { constraint: None }
+        );
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,373 @@
+//! Prohibited terms and the review queue for listings they flag, and the
+//! profanity list screened against user-written text.
+
+use crate::db::product_counts::ProductCounts;
+use crate::entity::product::{self, ActiveModel as ProductActiveModel, Model as ProductModel};
+use crate::entity::product_moderation::{
+    self, ActiveModel as ModerationActiveModel, Entity as ModerationEntity,
+    Model as ModerationModel,
+};
+use crate::entity::profanity_term::{
+    self, ActiveModel as ProfanityActiveModel, Entity as ProfanityEntity, Model as ProfanityModel,
+};
+use crate::entity::prohibited_term::{
+    self, ActiveModel as TermActiveModel, Entity as TermEntity, Model as TermModel,
+};
+use crate::moderation::text::ProfanityRule;
+use crate::moderation::{TermAction, TermRule};
+use crate::tenant::tenant_condition;
+use chrono::Utc;
+use sea_orm::{
+    sea_query::{Query, SimpleExpr},
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Where a held listing stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ModerationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationStatus::Pending => "pending",
+            ModerationStatus::Approved => "approved",
+            ModerationStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// Products not waiting on, or refused by, moderation
+pub(crate) fn not_held_condition() -> SimpleExpr {
+    product::Column::Id.not_in_subquery(
+        Query::select()
+            .column(product_moderation::Column::ProductId)
+            .from(ModerationEntity)
+            .and_where(product_moderation::Column::Status.ne(ModerationStatus::Approved.as_str()))
+            .to_owned(),
+    )
+}
+
+/// Matcher form of a stored term
+pub fn term_rule(term: &TermModel) -> Result<TermRule, String> {
+    Ok(TermRule {
+        id: term.id,
+        term: term.term.clone(),
+        category: term.category.clone(),
+        action: term.action.parse()?,
+    })
+}
+
+pub struct ProhibitedTerm;
+
+impl ProhibitedTerm {
+    /// All terms, alphabetically
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<TermModel>, String> {
+        TermEntity::find()
+            .order_by_asc(prohibited_term::Column::Term)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list prohibited terms: {:?}", e);
+                "Failed to list prohibited terms. Please try again later.".to_string()
+            })
+    }
+
+    /// The stored term with this normalized form, if any
+    pub async fn find(db: &DatabaseConnection, term: &str) -> Result<Option<TermModel>, String> {
+        TermEntity::find()
+            .filter(prohibited_term::Column::Term.eq(term))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up prohibited term {}: {:?}", term, e);
+                "Failed to look up prohibited term. Please try again later.".to_string()
+            })
+    }
+
+    /// Store a term; `term` must already be normalized
+    pub async fn create(
+        db: &DatabaseConnection,
+        term: &str,
+        category: &str,
+        action: TermAction,
+        created_by: &str,
+    ) -> Result<TermModel, String> {
+        let res = TermActiveModel {
+            id: Set(Uuid::new_v4()),
+            term: Set(term.to_owned()),
+            category: Set(category.to_owned()),
+            action: Set(action.as_str().to_owned()),
+            created_by: Set(created_by.to_owned()),
+            created_at: Set(Utc::now()),
+        }
+        .insert(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to create prohibited term {}: {:?}", term, e);
+            "Failed to create prohibited term. Please try again later.".to_string()
+        })?;
+        debug!("Prohibited term created: {:?}", res);
+        Ok(res)
+    }
+
+    /// Returns whether the term existed
+    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let res = TermEntity::delete_by_id(id).exec(db).await.map_err(|e| {
+            error!("Failed to delete prohibited term {}: {:?}", id, e);
+            "Failed to delete prohibited term. Please try again later.".to_string()
+        })?;
+        Ok(res.rows_affected > 0)
+    }
+}
+
+/// Matcher form of a stored profanity term
+pub fn profanity_rule(term: &ProfanityModel) -> ProfanityRule {
+    ProfanityRule {
+        id: term.id,
+        phrase: term.phrase.clone(),
+        category: term.category.clone(),
+    }
+}
+
+pub struct ProfanityTerm;
+
+impl ProfanityTerm {
+    /// All phrases, alphabetically
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<ProfanityModel>, String> {
+        ProfanityEntity::find()
+            .order_by_asc(profanity_term::Column::Phrase)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list profanity terms: {:?}", e);
+                "Failed to list profanity terms. Please try again later.".to_string()
+            })
+    }
+
+    /// The stored phrase with this normalized form, if any
+    pub async fn find(
+        db: &DatabaseConnection,
+        phrase: &str,
+    ) -> Result<Option<ProfanityModel>, String> {
+        ProfanityEntity::find()
+            .filter(profanity_term::Column::Phrase.eq(phrase))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up profanity term {}: {:?}", phrase, e);
+                "Failed to look up profanity term. Please try again later.".to_string()
+            })
+    }
+
+    /// Store a phrase; `phrase` must already be normalized
+    pub async fn create(
+        db: &DatabaseConnection,
+        phrase: &str,
+        category: &str,
+        created_by: &str,
+    ) -> Result<ProfanityModel, String> {
+        let term = ProfanityModel {
+            id: Uuid::new_v4(),
+            phrase: phrase.to_owned(),
+            category: category.to_owned(),
+            created_by: created_by.to_owned(),
+            created_at: Utc::now(),
+        };
+        ProfanityEntity::insert(ProfanityActiveModel::from(term.clone()))
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to create profanity term {}: {:?}", phrase, e);
+                "Failed to create profanity term. Please try again later.".to_string()
+            })?;
+        debug!("Profanity term created: {:?}", term);
+        Ok(term)
+    }
+
+    /// Returns whether the phrase existed
+    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let res = ProfanityEntity::delete_by_id(id)
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to delete profanity term {}: {:?}", id, e);
+                "Failed to delete profanity term. Please try again later.".to_string()
+            })?;
+        Ok(res.rows_affected > 0)
+    }
+}
+
+pub struct ProductModeration;
+
+impl ProductModeration {
+    /// Unpublish a product and queue it for review with the flagged terms it
+    /// matched. A product already in the queue goes back to pending.
+    pub async fn hold(
+        db: &DatabaseConnection,
+        product: ProductModel,
+        matched: &[TermRule],
+    ) -> Result<ModerationModel, String> {
+        let matched_terms = serde_json::json!(matched
+            .iter()
+            .map(|rule| serde_json::json!({ "term": rule.term, "category": rule.category }))
+            .collect::<Vec<_>>());
+        Self::hold_for(db, product, matched_terms).await
+    }
+
+    /// Like [`Self::hold`], recording `matched_terms` as given
+    pub async fn hold_for(
+        db: &DatabaseConnection,
+        product: ProductModel,
+        matched_terms: serde_json::Value,
+    ) -> Result<ModerationModel, String> {
+        let product_id = product.id;
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to hold product {} for review: {:?}", product_id, e);
+            "Failed to queue product for review. Please try again later.".to_string()
+        };
+        let now = Utc::now();
+
+        let txn = db.begin().await.map_err(fail)?;
+        let mut active: ProductActiveModel = product.clone().into();
+        active.is_published = Set(false);
+        let held = active.update(&txn).await.map_err(fail)?;
+        ProductCounts::record(&txn, Some(&product), Some(&held)).await?;
+
+        let existing = ModerationEntity::find()
+            .filter(product_moderation::Column::ProductId.eq(product_id))
+            .one(&txn)
+            .await
+            .map_err(fail)?;
+        let entry = match existing {
+            Some(entry) => {
+                let mut entry: ModerationActiveModel = entry.into();
+                entry.matched_terms = Set(matched_terms);
+                entry.status = Set(ModerationStatus::Pending.as_str().to_owned());
+                entry.created_at = Set(now);
+                entry.reviewed_by = Set(None);
+                entry.reviewed_at = Set(None);
+                entry.update(&txn).await
+            }
+            None => {
+                let entry = ModerationModel {
+                    id: Uuid::new_v4(),
+                    product_id,
+                    matched_terms,
+                    status: ModerationStatus::Pending.as_str().to_owned(),
+                    created_at: now,
+                    reviewed_by: None,
+                    reviewed_at: None,
+                };
+                ModerationEntity::insert(ModerationActiveModel::from(entry.clone()))
+                    .exec_without_returning(&txn)
+                    .await
+                    .map(|_| entry)
+            }
+        }
+        .map_err(fail)?;
+        txn.commit().await.map_err(fail)?;
+        debug!(product_id = %product_id, "Product held for review");
+        Ok(entry)
+    }
+
+    /// Queue entries with `status`, oldest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        status: ModerationStatus,
+    ) -> Result<Vec<ModerationModel>, String> {
+        let tenant_products = Query::select()
+            .column(product::Column::Id)
+            .from(product::Entity)
+            .and_where(tenant_condition::<product::Entity>(tenant_id))
+            .to_owned();
+        ModerationEntity::find()
+            .filter(product_moderation::Column::Status.eq(status.as_str()))
+            .filter(product_moderation::Column::ProductId.in_subquery(tenant_products))
+            .order_by_asc(product_moderation::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list moderation queue: {:?}", e);
+                "Failed to list moderation queue. Please try again later.".to_string()
+            })
+    }
+
+    /// Record an admin decision on a held product. Approval publishes the
+    /// product, or leaves it to the scheduler when `publish_at` is still ahead.
+    /// `Ok(None)` if the product is not in the queue.
+    pub async fn review(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        decision: ModerationStatus,
+        reviewed_by: &str,
+    ) -> Result<Option<(ModerationModel, ProductModel)>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to review product {}: {:?}", product_id, e);
+            "Failed to record review. Please try again later.".to_string()
+        };
+        let now = Utc::now();
+        let txn = db.begin().await.map_err(fail)?;
+        let Some(entry) = ModerationEntity::find()
+            .filter(product_moderation::Column::ProductId.eq(product_id))
+            .one(&txn)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(None);
+        };
+        let Some(product) = product::Entity::find_by_id(product_id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(None);
+        };
+
+        let mut entry: ModerationActiveModel = entry.into();
+        entry.status = Set(decision.as_str().to_owned());
+        entry.reviewed_by = Set(Some(reviewed_by.to_owned()));
+        entry.reviewed_at = Set(Some(now));
+        let entry = entry.update(&txn).await.map_err(fail)?;
+
+        let mut active: ProductActiveModel = product.clone().into();
+        active.is_published =
+            Set(decision == ModerationStatus::Approved
+                && product.publish_at.is_none_or(|at| at <= now));
+        // Bumped so sync clients pick up the change in visibility
+        active.updated_at = Set(now);
+        let reviewed = active.update(&txn).await.map_err(fail)?;
+        ProductCounts::record(&txn, Some(&product), Some(&reviewed)).await?;
+        txn.commit().await.map_err(fail)?;
+        Ok(Some((entry, reviewed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DbBackend, EntityTrait, QueryTrait};
+
+    #[test]
+    fn test_held_products_are_excluded() {
+        let sql = product::Entity::find()
+            .filter(not_held_condition())
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#""id" NOT IN (SELECT "product_id" FROM "product_moderation""#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#""status" <> 'approved'"#), "{sql}");
+    }
+}
@@ -0,0 +1,237 @@
+//! Notification preferences and the digest queue; routing lives in
+//! `crate::notifications`.
+
+use crate::entity::notification_digest_item::{
+    self, ActiveModel as DigestItemActiveModel, Entity as DigestItemEntity, Model as DigestItem,
+};
+use crate::entity::notification_preference::{
+    self, ActiveModel as PreferenceActiveModel, Entity as PreferenceEntity,
+};
+use crate::events::Event;
+use crate::notifications::{DeliveryMode, NotificationKind};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use std::collections::BTreeMap;
+use tracing::error;
+use uuid::Uuid;
+
+pub struct NotificationPreferences;
+
+impl NotificationPreferences {
+    /// The user's mode for every kind, `immediate` where none was set
+    pub async fn for_user(
+        db: &DatabaseConnection,
+        user_id: &str,
+    ) -> Result<BTreeMap<NotificationKind, DeliveryMode>, String> {
+        let rows = PreferenceEntity::find()
+            .filter(notification_preference::Column::UserId.eq(user_id))
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to load notification preferences of {}: {:?}",
+                    user_id, e
+                );
+                "Failed to load notification preferences".to_string()
+            })?;
+        let mut modes: BTreeMap<NotificationKind, DeliveryMode> = NotificationKind::ALL
+            .into_iter()
+            .map(|kind| (kind, DeliveryMode::default()))
+            .collect();
+        for row in rows {
+            if let (Some(kind), Some(mode)) = (
+                NotificationKind::parse(&row.kind),
+                DeliveryMode::parse(&row.mode),
+            ) {
+                modes.insert(kind, mode);
+            }
+        }
+        Ok(modes)
+    }
+
+    /// How the user wants `kind` delivered
+    pub async fn mode(
+        db: &DatabaseConnection,
+        user_id: &str,
+        kind: NotificationKind,
+    ) -> Result<DeliveryMode, String> {
+        let row = Self::find(db, user_id, kind).await.map_err(|e| {
+            error!(
+                "Failed to load notification preference of {}: {:?}",
+                user_id, e
+            );
+            "Failed to load notification preferences".to_string()
+        })?;
+        Ok(row
+            .and_then(|row| DeliveryMode::parse(&row.mode))
+            .unwrap_or_default())
+    }
+
+    /// Set the mode of each kind listed; other kinds keep theirs
+    pub async fn set(
+        db: &DatabaseConnection,
+        user_id: &str,
+        modes: &[(NotificationKind, DeliveryMode)],
+    ) -> Result<(), String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to save notification preferences of {}: {:?}",
+                user_id, e
+            );
+            "Failed to save notification preferences".to_string()
+        };
+        let now = Utc::now();
+        for &(kind, mode) in modes {
+            if let Some(existing) = Self::find(db, user_id, kind).await.map_err(fail)? {
+                let mut active: PreferenceActiveModel = existing.into();
+                active.mode = Set(mode.as_str().to_string());
+                active.updated_at = Set(now);
+                active.update(db).await.map_err(fail)?;
+                continue;
+            }
+            let preference = PreferenceActiveModel {
+                id: Set(Uuid::new_v4()),
+                user_id: Set(user_id.to_string()),
+                kind: Set(kind.as_str().to_string()),
+                mode: Set(mode.as_str().to_string()),
+                updated_at: Set(now),
+            };
+            PreferenceEntity::insert(preference)
+                .exec_without_returning(db)
+                .await
+                .map_err(fail)?;
+        }
+        Ok(())
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        user_id: &str,
+        kind: NotificationKind,
+    ) -> Result<Option<notification_preference::Model>, DbErr> {
+        PreferenceEntity::find()
+            .filter(notification_preference::Column::UserId.eq(user_id))
+            .filter(notification_preference::Column::Kind.eq(kind.as_str()))
+            .one(db)
+            .await
+    }
+}
+
+pub struct NotificationDigestQueue;
+
+impl NotificationDigestQueue {
+    /// Hold `event` for the user's digest closing at `due_at`
+    pub async fn enqueue(
+        db: &DatabaseConnection,
+        user_id: &str,
+        kind: NotificationKind,
+        mode: DeliveryMode,
+        event: &Event,
+        queued_at: DateTime<Utc>,
+        due_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let item = DigestItemActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id.to_string()),
+            kind: Set(kind.as_str().to_string()),
+            mode: Set(mode.as_str().to_string()),
+            event_id: Set(event.id),
+            entity_id: Set(event.entity_id),
+            payload: Set(event.data.clone()),
+            queued_at: Set(queued_at),
+            due_at: Set(due_at),
+        };
+        DigestItemEntity::insert(item)
+            .exec_without_returning(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to queue notification for {}: {:?}", user_id, e);
+                "Failed to queue notification".to_string()
+            })
+    }
+
+    /// Items whose digest window closed by `now`, oldest first
+    pub async fn due(
+        db: &DatabaseConnection,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<DigestItem>, String> {
+        DigestItemEntity::find()
+            .filter(notification_digest_item::Column::DueAt.lte(now))
+            .order_by_asc(notification_digest_item::Column::QueuedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to load due notification digests: {:?}", e);
+                "Failed to load notification digests".to_string()
+            })
+    }
+
+    /// Remove the items of a digest about to be sent; `false` when some were
+    /// already gone, i.e. another run sent them
+    pub async fn claim(db: &DatabaseConnection, ids: &[Uuid]) -> Result<bool, String> {
+        let res = DigestItemEntity::delete_many()
+            .filter(notification_digest_item::Column::Id.is_in(ids.iter().copied()))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to clear notification digest items: {:?}", e);
+                "Failed to clear notification digest".to_string()
+            })?;
+        Ok(res.rows_affected == ids.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+
+    #[tokio::test]
+    async fn test_preferences_default_to_immediate_and_update_in_place() {
+        let db = testing::sqlite().await;
+        let modes = NotificationPreferences::for_user(&db, "seller-1")
+            .await
+            .unwrap();
+        assert_eq!(modes.len(), NotificationKind::ALL.len());
+        assert!(modes.values().all(|mode| *mode == DeliveryMode::Immediate));
+
+        NotificationPreferences::set(
+            &db,
+            "seller-1",
+            &[(NotificationKind::NewQuestion, DeliveryMode::HourlyDigest)],
+        )
+        .await
+        .unwrap();
+        NotificationPreferences::set(
+            &db,
+            "seller-1",
+            &[(NotificationKind::NewQuestion, DeliveryMode::DailyDigest)],
+        )
+        .await
+        .unwrap();
+
+        let modes = NotificationPreferences::for_user(&db, "seller-1")
+            .await
+            .unwrap();
+        assert_eq!(
+            modes[&NotificationKind::NewQuestion],
+            DeliveryMode::DailyDigest
+        );
+        assert_eq!(
+            modes[&NotificationKind::NewStoreReview],
+            DeliveryMode::Immediate
+        );
+        // Someone else's preferences are their own
+        assert_eq!(
+            NotificationPreferences::mode(&db, "seller-2", NotificationKind::NewQuestion)
+                .await
+                .unwrap(),
+            DeliveryMode::Immediate
+        );
+        assert_eq!(PreferenceEntity::find().all(&db).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,93 @@
+//! A new seller's setup checklist, read from what the store already has:
+//! its logo, its products, and whether the owner has shared it yet.
+
+use crate::db::product_counts::ProductCounts;
+use crate::entity::store::{self, Entity as StoreEntity, Model as StoreModel};
+use chrono::{DateTime, Utc};
+use sea_orm::{sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// One step of the checklist. More may be added; clients skip steps they
+/// don't know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum OnboardingStep {
+    StoreCreated,
+    LogoUploaded,
+    FirstProductAdded,
+    FirstShareLink,
+}
+
+impl OnboardingStep {
+    /// Every step, in the order the seller is asked to do them
+    pub const ALL: [OnboardingStep; 4] = [
+        OnboardingStep::StoreCreated,
+        OnboardingStep::LogoUploaded,
+        OnboardingStep::FirstProductAdded,
+        OnboardingStep::FirstShareLink,
+    ];
+
+    /// What the seller should do to complete the step
+    pub fn action(self) -> &'static str {
+        match self {
+            OnboardingStep::StoreCreated => "Create your store",
+            OnboardingStep::LogoUploaded => "Add a logo to your store",
+            OnboardingStep::FirstProductAdded => "Add your first product",
+            OnboardingStep::FirstShareLink => "Share your store link with customers",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct StepStatus {
+    pub step: OnboardingStep,
+    pub completed: bool,
+}
+
+/// Every step of `store`'s checklist and whether it is done
+pub async fn progress(
+    db: &DatabaseConnection,
+    store: &StoreModel,
+) -> Result<Vec<StepStatus>, String> {
+    let counts = ProductCounts::for_store(db, store.id).await?;
+    let has_logo = store
+        .logo_url
+        .as_deref()
+        .is_some_and(|url| !url.trim().is_empty());
+    Ok(OnboardingStep::ALL
+        .into_iter()
+        .map(|step| StepStatus {
+            step,
+            completed: match step {
+                OnboardingStep::StoreCreated => true,
+                OnboardingStep::LogoUploaded => has_logo,
+                OnboardingStep::FirstProductAdded => counts.published + counts.unpublished > 0,
+                OnboardingStep::FirstShareLink => store.first_shared_at.is_some(),
+            },
+        })
+        .collect())
+}
+
+/// Note that the owner generated the store's share links; only the first
+/// time is kept
+pub async fn record_share(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    StoreEntity::update_many()
+        .col_expr(store::Column::FirstSharedAt, Expr::value(now))
+        .filter(store::Column::Id.eq(store_id))
+        .filter(store::Column::FirstSharedAt.is_null())
+        .exec(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to record share of store {}: {:?}", store_id, e);
+            "Failed to record store share.".to_string()
+        })?;
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! Where a store is paid out. Account numbers are sealed with
+//! `crate::crypto::field` before they reach the database and opened only to
+//! be masked for display.
+
+use crate::crypto::field::FieldCipher;
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::entity::store_payout_account::{
+    self, ActiveModel, Entity as PayoutAccountEntity, Model,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// `source` of audit entries written when an admin (un)verifies an account
+pub const VERIFICATION_SOURCE: &str = "payout_verification";
+
+/// Mobile-money operators stores can be paid out through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutProvider {
+    MtnMomo,
+    OrangeMoney,
+}
+
+impl PayoutProvider {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PayoutProvider::MtnMomo => "mtn_momo",
+            PayoutProvider::OrangeMoney => "orange_money",
+        }
+    }
+}
+
+/// Account details as the owner entered them
+#[derive(Clone)]
+pub struct PayoutAccountInput {
+    pub provider: PayoutProvider,
+    /// Plaintext; sealed before it is stored
+    pub account_number: String,
+    pub account_name: String,
+}
+
+// Account numbers stay out of logs and panic messages
+impl fmt::Debug for PayoutAccountInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayoutAccountInput")
+            .field("provider", &self.provider)
+            .field("account_number", &"***")
+            .field("account_name", &self.account_name)
+            .finish()
+    }
+}
+
+/// Every character but the last four replaced with `*`
+pub fn mask_account_number(number: &str) -> String {
+    let len = number.chars().count();
+    number
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i + 4 < len { '*' } else { c })
+        .collect()
+}
+
+pub struct PayoutAccount;
+
+impl PayoutAccount {
+    pub async fn get(db: &DatabaseConnection, store_id: Uuid) -> Result<Option<Model>, String> {
+        PayoutAccountEntity::find()
+            .filter(store_payout_account::Column::StoreId.eq(store_id))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to load payout account of store {}: {:?}",
+                    store_id, e
+                );
+                "Failed to load payout account. Please try again later.".to_string()
+            })
+    }
+
+    /// Create or replace a store's account. Changing the provider, number
+    /// or name clears its verification; a number sealed with a retired key
+    /// is resealed with the current one.
+    pub async fn set(
+        db: &DatabaseConnection,
+        cipher: &FieldCipher,
+        store_id: Uuid,
+        input: &PayoutAccountInput,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<Model, String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to save payout account of store {}: {:?}",
+                store_id, e
+            );
+            "Failed to save payout account. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let existing = PayoutAccountEntity::find()
+            .filter(store_payout_account::Column::StoreId.eq(store_id))
+            .one(&txn)
+            .await
+            .map_err(fail)?;
+        let now = Utc::now();
+
+        let saved = match &existing {
+            None => {
+                let id = Uuid::new_v4();
+                let row = ActiveModel {
+                    id: Set(id),
+                    store_id: Set(store_id),
+                    provider: Set(input.provider.as_str().to_string()),
+                    account_number_encrypted: Set(cipher.encrypt(&input.account_number)?),
+                    account_name: Set(input.account_name.clone()),
+                    verified: Set(false),
+                    verified_by: Set(None),
+                    verified_at: Set(None),
+                    created_at: Set(now),
+                    updated_at: Set(now),
+                };
+                PayoutAccountEntity::insert(row)
+                    .exec_without_returning(&txn)
+                    .await
+                    .map_err(fail)?;
+                PayoutAccountEntity::find_by_id(id)
+                    .one(&txn)
+                    .await
+                    .map_err(fail)?
+                    .ok_or_else(|| "Failed to save payout account".to_string())?
+            }
+            Some(current) => {
+                // A number that no longer opens counts as changed
+                let same_number = cipher
+                    .decrypt(&current.account_number_encrypted)
+                    .is_ok_and(|number| number == input.account_number);
+                let unchanged = same_number
+                    && current.provider == input.provider.as_str()
+                    && current.account_name == input.account_name;
+                let reseal =
+                    !same_number || cipher.needs_rotation(&current.account_number_encrypted);
+                if unchanged && !reseal {
+                    txn.commit().await.map_err(fail)?;
+                    return Ok(current.clone());
+                }
+                let mut active: ActiveModel = current.clone().into();
+                active.provider = Set(input.provider.as_str().to_string());
+                active.account_name = Set(input.account_name.clone());
+                if reseal {
+                    active.account_number_encrypted = Set(cipher.encrypt(&input.account_number)?);
+                }
+                if !unchanged {
+                    active.verified = Set(false);
+                    active.verified_by = Set(None);
+                    active.verified_at = Set(None);
+                }
+                active.updated_at = Set(now);
+                active.update(&txn).await.map_err(fail)?
+            }
+        };
+        AuditLog::record_payout_account(&txn, existing.as_ref(), &saved, origin).await?;
+        txn.commit().await.map_err(fail)?;
+        info!(
+            store_id = %store_id,
+            provider = %saved.provider,
+            "Payout account saved"
+        );
+        Ok(saved)
+    }
+
+    /// Mark a store's account verified or not; `None` when it has none
+    pub async fn set_verified(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        verified: bool,
+        admin: &str,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to update payout account verification of store {}: {:?}",
+                store_id, e
+            );
+            "Failed to update payout account. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let Some(current) = PayoutAccountEntity::find()
+            .filter(store_payout_account::Column::StoreId.eq(store_id))
+            .one(&txn)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(None);
+        };
+        if current.verified == verified {
+            txn.commit().await.map_err(fail)?;
+            return Ok(Some(current));
+        }
+        let now = Utc::now();
+        let mut active: ActiveModel = current.clone().into();
+        active.verified = Set(verified);
+        active.verified_by = Set(verified.then(|| admin.to_string()));
+        active.verified_at = Set(verified.then_some(now));
+        active.updated_at = Set(now);
+        let saved = active.update(&txn).await.map_err(fail)?;
+        let origin = ChangeOrigin {
+            changed_by: Some(admin),
+            source: VERIFICATION_SOURCE,
+        };
+        AuditLog::record_payout_account(&txn, Some(&current), &saved, origin).await?;
+        txn.commit().await.map_err(fail)?;
+        Ok(Some(saved))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::field::{FieldKey, KEY_LEN};
+    use crate::db::history::{history_page, HistoryScope, REDACTED};
+    use crate::db::testing;
+
+    fn cipher(keys: &[(&str, u8)]) -> FieldCipher {
+        FieldCipher::new(
+            keys.iter()
+                .map(|(id, byte)| FieldKey::new(*id, [*byte; KEY_LEN]))
+                .collect(),
+        )
+    }
+
+    fn input(number: &str) -> PayoutAccountInput {
+        PayoutAccountInput {
+            provider: PayoutProvider::MtnMomo,
+            account_number: number.to_string(),
+            account_name: "Ngono Marie".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_mask_keeps_last_four() {
+        assert_eq!(mask_account_number("677123456"), "*****3456");
+        assert_eq!(mask_account_number("3456"), "3456");
+        assert_eq!(mask_account_number(""), "");
+    }
+
+    #[tokio::test]
+    async fn test_changes_clear_verification_and_audit_is_redacted() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let cipher = cipher(&[("k1", 1)]);
+        let origin = ChangeOrigin::edit(Some("seller-1"));
+
+        let created = PayoutAccount::set(&db, &cipher, store_id, &input("677123456"), origin)
+            .await
+            .unwrap();
+        assert!(!created.account_number_encrypted.contains("677123456"));
+        assert_eq!(
+            cipher.decrypt(&created.account_number_encrypted).unwrap(),
+            "677123456"
+        );
+        let verified = PayoutAccount::set_verified(&db, store_id, true, "admin-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(verified.verified);
+        assert_eq!(verified.verified_by.as_deref(), Some("admin-1"));
+
+        // Saving the same details keeps the verification and writes nothing
+        let same = PayoutAccount::set(&db, &cipher, store_id, &input("677123456"), origin)
+            .await
+            .unwrap();
+        assert!(same.verified);
+        assert_eq!(
+            same.account_number_encrypted,
+            verified.account_number_encrypted
+        );
+
+        let changed = PayoutAccount::set(&db, &cipher, store_id, &input("699000111"), origin)
+            .await
+            .unwrap();
+        assert!(!changed.verified);
+        assert!(changed.verified_at.is_none());
+
+        let feed = history_page(&db, HistoryScope::Store(store_id), None, 10)
+            .await
+            .unwrap();
+        let sources: Vec<&str> = feed.entries.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(sources, ["edit", VERIFICATION_SOURCE, "edit"]);
+        let raw =
+            serde_json::to_string(&feed.entries.iter().map(|e| &e.changes).collect::<Vec<_>>())
+                .unwrap();
+        assert!(!raw.contains("677123456") && !raw.contains("699000111"));
+        assert!(!raw.contains("k1:"));
+        let number = feed.entries[0]
+            .changes
+            .iter()
+            .find(|c| c.field == "account_number_encrypted")
+            .unwrap();
+        assert_eq!(number.old, serde_json::json!(REDACTED));
+        assert_eq!(number.new, serde_json::json!(REDACTED));
+
+        let created_entry = feed.entries[2]
+            .changes
+            .iter()
+            .find(|c| c.field == "account_number_encrypted")
+            .unwrap();
+        assert_eq!(created_entry.old, serde_json::Value::Null);
+        assert_eq!(created_entry.new, serde_json::json!(REDACTED));
+    }
+
+    #[tokio::test]
+    async fn test_old_records_open_after_key_rotation() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let origin = ChangeOrigin::edit(Some("seller-1"));
+        let old = cipher(&[("k1", 1)]);
+        PayoutAccount::set(&db, &old, store_id, &input("677123456"), origin)
+            .await
+            .unwrap();
+        PayoutAccount::set_verified(&db, store_id, true, "admin-1")
+            .await
+            .unwrap();
+
+        let rotated = cipher(&[("k2", 2), ("k1", 1)]);
+        let stored = PayoutAccount::get(&db, store_id).await.unwrap().unwrap();
+        assert_eq!(
+            rotated.decrypt(&stored.account_number_encrypted).unwrap(),
+            "677123456"
+        );
+
+        // Resaving the same number moves it to the current key without
+        // costing the verification
+        let resealed = PayoutAccount::set(&db, &rotated, store_id, &input("677123456"), origin)
+            .await
+            .unwrap();
+        assert!(resealed.account_number_encrypted.starts_with("k2:"));
+        assert!(resealed.verified);
+        assert_eq!(
+            cipher(&[("k2", 2)])
+                .decrypt(&resealed.account_number_encrypted)
+                .unwrap(),
+            "677123456"
+        );
+    }
+}
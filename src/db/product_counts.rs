@@ -0,0 +1,591 @@
+//! `product_counts` read model: how many products each store has per category
+//! and publication status, so category filters and store pages can show
+//! counts without a COUNT(*) per request.
+//!
+//! Product writes call [`ProductCounts::record`] in the transaction that
+//! changes the product. Anything that bypasses them, such as hand-edited
+//! rows, is fixed by [`ProductCounts::rebuild`].
+
+use crate::entity::product::{self, Entity as ProductEntity, Model as ProductModel};
+use crate::entity::product_count::{
+    self, ActiveModel as ProductCountActiveModel, Entity as ProductCountEntity,
+};
+use crate::entity::store::{self, Entity as StoreEntity};
+use crate::tenant::{ForTenant, TenantScoped};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, SimpleExpr},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const PUBLISHED: &str = "published";
+pub const UNPUBLISHED: &str = "unpublished";
+
+/// Stores recounted per transaction by a rebuild
+pub const REBUILD_BATCH_SIZE: u64 = 200;
+
+impl TenantScoped for ProductCountEntity {
+    fn tenant_column() -> Self::Column {
+        product_count::Column::TenantId
+    }
+}
+
+/// The projection row a product is counted in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CountKey {
+    tenant_id: String,
+    store_id: Uuid,
+    category_id: Option<Uuid>,
+    status: &'static str,
+}
+
+fn status(is_published: bool) -> &'static str {
+    if is_published {
+        PUBLISHED
+    } else {
+        UNPUBLISHED
+    }
+}
+
+impl CountKey {
+    fn of(product: &ProductModel) -> Self {
+        Self {
+            tenant_id: product.tenant_id.clone(),
+            store_id: product.store_id,
+            category_id: product.category_id,
+            status: status(product.is_published),
+        }
+    }
+}
+
+/// A store's products in one category; no category means uncategorised
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CategoryProductCount {
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    pub published: i64,
+    pub unpublished: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct StoreProductCounts {
+    pub published: i64,
+    pub unpublished: i64,
+    pub by_category: Vec<CategoryProductCount>,
+}
+
+/// What a rebuild recounted
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RebuildSummary {
+    pub stores: u64,
+    /// Projection rows written
+    pub rows: u64,
+}
+
+/// `SUM(product_count)`, cast back to a BIGINT as Postgres sums to NUMERIC
+fn count_sum() -> SimpleExpr {
+    Func::cast_as(
+        Func::sum(Expr::col(product_count::Column::ProductCount)),
+        Alias::new("BIGINT"),
+    )
+    .into()
+}
+
+pub struct ProductCounts;
+
+impl ProductCounts {
+    /// Move a product between projection rows. `before` is `None` for a new
+    /// product and `after` is `None` for a deleted one; archived products
+    /// are not counted. Call it with the connection or transaction that
+    /// writes the product.
+    pub async fn record<C: ConnectionTrait>(
+        db: &C,
+        before: Option<&ProductModel>,
+        after: Option<&ProductModel>,
+    ) -> Result<(), String> {
+        let counted = |product: &&ProductModel| product.archived_at.is_none();
+        let before = before.filter(counted).map(CountKey::of);
+        let after = after.filter(counted).map(CountKey::of);
+        if before == after {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let changes = before
+            .iter()
+            .map(|k| (k, -1))
+            .chain(after.iter().map(|k| (k, 1)));
+        for (key, delta) in changes {
+            Self::adjust(db, key, delta, now).await.map_err(|e| {
+                error!(
+                    "Failed to update product counts of store {}: {:?}",
+                    key.store_id, e
+                );
+                "Failed to update product counts. Please try again later.".to_string()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Add `delta` to one row of the key. Two writers racing on a new key may
+    /// each insert a row; readers sum them, so the total stays right.
+    async fn adjust<C: ConnectionTrait>(
+        db: &C,
+        key: &CountKey,
+        delta: i64,
+        now: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let category = match key.category_id {
+            Some(id) => product_count::Column::CategoryId.eq(id),
+            None => product_count::Column::CategoryId.is_null(),
+        };
+        let existing: Option<Uuid> = ProductCountEntity::find()
+            .select_only()
+            .column(product_count::Column::Id)
+            .filter(product_count::Column::StoreId.eq(key.store_id))
+            .filter(category)
+            .filter(product_count::Column::Status.eq(key.status))
+            .into_tuple()
+            .one(db)
+            .await?;
+        match existing {
+            Some(id) => {
+                ProductCountEntity::update_many()
+                    .col_expr(
+                        product_count::Column::ProductCount,
+                        Expr::col(product_count::Column::ProductCount).add(delta),
+                    )
+                    .col_expr(product_count::Column::UpdatedAt, Expr::value(now))
+                    .filter(product_count::Column::Id.eq(id))
+                    .exec(db)
+                    .await?;
+            }
+            None => {
+                let row = ProductCountActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    tenant_id: Set(key.tenant_id.clone()),
+                    store_id: Set(key.store_id),
+                    category_id: Set(key.category_id),
+                    status: Set(key.status.to_string()),
+                    product_count: Set(delta),
+                    updated_at: Set(now),
+                };
+                ProductCountEntity::insert(row)
+                    .exec_without_returning(db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A store's counts, with categories in id order and uncategorised first
+    pub async fn for_store(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+    ) -> Result<StoreProductCounts, String> {
+        let rows: Vec<(Option<Uuid>, String, i64)> = ProductCountEntity::find()
+            .select_only()
+            .column(product_count::Column::CategoryId)
+            .column(product_count::Column::Status)
+            .column_as(count_sum(), "product_count")
+            .filter(product_count::Column::StoreId.eq(store_id))
+            .group_by(product_count::Column::CategoryId)
+            .group_by(product_count::Column::Status)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to read product counts of store {}: {:?}",
+                    store_id, e
+                );
+                "Failed to read product counts. Please try again later.".to_string()
+            })?;
+
+        let mut counts = StoreProductCounts::default();
+        let mut by_category: BTreeMap<Option<Uuid>, CategoryProductCount> = BTreeMap::new();
+        for (category_id, status, count) in rows {
+            let entry = by_category
+                .entry(category_id)
+                .or_insert(CategoryProductCount {
+                    category_id,
+                    published: 0,
+                    unpublished: 0,
+                });
+            if status == PUBLISHED {
+                entry.published += count;
+                counts.published += count;
+            } else {
+                entry.unpublished += count;
+                counts.unpublished += count;
+            }
+        }
+        counts.by_category = by_category
+            .into_values()
+            .filter(|c| c.published != 0 || c.unpublished != 0)
+            .collect();
+        Ok(counts)
+    }
+
+    /// Published products per category in a tenant, or in one of its stores.
+    /// Categories without products are left out.
+    pub async fn published_by_category(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_id: Option<Uuid>,
+    ) -> Result<HashMap<Uuid, i64>, String> {
+        let mut query = ProductCountEntity::find()
+            .select_only()
+            .column(product_count::Column::CategoryId)
+            .column_as(count_sum(), "product_count")
+            .for_tenant(tenant_id)
+            .filter(product_count::Column::Status.eq(PUBLISHED))
+            .filter(product_count::Column::CategoryId.is_not_null());
+        if let Some(store_id) = store_id {
+            query = query.filter(product_count::Column::StoreId.eq(store_id));
+        }
+        let rows: Vec<(Uuid, i64)> = query
+            .group_by(product_count::Column::CategoryId)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to read category product counts: {:?}", e);
+                "Failed to read product counts. Please try again later.".to_string()
+            })?;
+        Ok(rows.into_iter().filter(|(_, count)| *count > 0).collect())
+    }
+
+    /// Recount every store from its products, `batch_size` stores per
+    /// transaction, replacing their projection rows
+    pub async fn rebuild(
+        db: &DatabaseConnection,
+        batch_size: u64,
+    ) -> Result<RebuildSummary, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to rebuild product counts: {:?}", e);
+            "Failed to rebuild product counts. Please try again later.".to_string()
+        };
+        let mut summary = RebuildSummary { stores: 0, rows: 0 };
+        let mut after: Option<Uuid> = None;
+        loop {
+            let mut stores = StoreEntity::find()
+                .select_only()
+                .column(store::Column::Id)
+                .order_by_asc(store::Column::Id)
+                .limit(batch_size);
+            if let Some(last) = after {
+                stores = stores.filter(store::Column::Id.gt(last));
+            }
+            let store_ids: Vec<Uuid> = stores.into_tuple().all(db).await.map_err(fail)?;
+            let Some(&last) = store_ids.last() else {
+                break;
+            };
+
+            let txn = db.begin().await.map_err(fail)?;
+            ProductCountEntity::delete_many()
+                .filter(product_count::Column::StoreId.is_in(store_ids.iter().copied()))
+                .exec(&txn)
+                .await
+                .map_err(fail)?;
+            let counts: Vec<(String, Uuid, Option<Uuid>, bool, i64)> = ProductEntity::find()
+                .select_only()
+                .column(product::Column::TenantId)
+                .column(product::Column::StoreId)
+                .column(product::Column::CategoryId)
+                .column(product::Column::IsPublished)
+                .column_as(
+                    SimpleExpr::from(Func::count(Expr::col(product::Column::Id))),
+                    "product_count",
+                )
+                .filter(product::Column::StoreId.is_in(store_ids.iter().copied()))
+                .filter(product::Column::ArchivedAt.is_null())
+                .group_by(product::Column::TenantId)
+                .group_by(product::Column::StoreId)
+                .group_by(product::Column::CategoryId)
+                .group_by(product::Column::IsPublished)
+                .into_tuple()
+                .all(&txn)
+                .await
+                .map_err(fail)?;
+            let now = Utc::now();
+            let rows: Vec<ProductCountActiveModel> = counts
+                .into_iter()
+                .map(|(tenant_id, store_id, category_id, is_published, count)| {
+                    ProductCountActiveModel {
+                        id: Set(Uuid::new_v4()),
+                        tenant_id: Set(tenant_id),
+                        store_id: Set(store_id),
+                        category_id: Set(category_id),
+                        status: Set(status(is_published).to_string()),
+                        product_count: Set(count),
+                        updated_at: Set(now),
+                    }
+                })
+                .collect();
+            summary.rows += rows.len() as u64;
+            if !rows.is_empty() {
+                ProductCountEntity::insert_many(rows)
+                    .exec_without_returning(&txn)
+                    .await
+                    .map_err(fail)?;
+            }
+            txn.commit().await.map_err(fail)?;
+
+            summary.stores += store_ids.len() as u64;
+            if (store_ids.len() as u64) < batch_size {
+                break;
+            }
+            after = Some(last);
+        }
+        debug!(
+            stores = summary.stores,
+            rows = summary.rows,
+            "Product counts rebuilt"
+        );
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::history::ChangeOrigin;
+    use crate::db::moderation::{ModerationStatus, ProductModeration};
+    use crate::db::products::Product;
+    use crate::db::testing;
+    use crate::entity::{category, product_moderation};
+    use chrono::Duration;
+
+    async fn seed_category(db: &DatabaseConnection, slug: &str) -> Uuid {
+        let id = Uuid::new_v4();
+        let row = category::ActiveModel {
+            id: Set(id),
+            slug: Set(slug.to_string()),
+            name: Set(slug.to_string()),
+            created_at: Set(Utc::now()),
+        };
+        category::Entity::insert(row)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+        id
+    }
+
+    async fn create(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        category_id: Option<Uuid>,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> ProductModel {
+        Product::create(
+            db,
+            store_id,
+            None,
+            "Okok leaves",
+            None,
+            1500.0,
+            "XAF",
+            4,
+            None,
+            None,
+            None,
+            None,
+            publish_at,
+            false,
+            None,
+            category_id,
+            serde_json::json!({}),
+        )
+        .await
+        .unwrap()
+    }
+
+    /// The projection summed per key, next to a COUNT(*) over the products
+    async fn projected_and_counted(
+        db: &DatabaseConnection,
+    ) -> (
+        BTreeMap<(Uuid, Option<Uuid>, String), i64>,
+        BTreeMap<(Uuid, Option<Uuid>, String), i64>,
+    ) {
+        let mut projected = BTreeMap::new();
+        for row in ProductCountEntity::find().all(db).await.unwrap() {
+            *projected
+                .entry((row.store_id, row.category_id, row.status))
+                .or_insert(0) += row.product_count;
+        }
+        projected.retain(|_, count| *count != 0);
+        let mut counted = BTreeMap::new();
+        // Archived products are out of the projection
+        for product in ProductEntity::find()
+            .filter(product::Column::ArchivedAt.is_null())
+            .all(db)
+            .await
+            .unwrap()
+        {
+            let key = CountKey::of(&product);
+            *counted
+                .entry((key.store_id, key.category_id, key.status.to_string()))
+                .or_insert(0) += 1;
+        }
+        (projected, counted)
+    }
+
+    async fn assert_in_step(db: &DatabaseConnection) {
+        let (projected, counted) = projected_and_counted(db).await;
+        assert_eq!(projected, counted);
+    }
+
+    #[tokio::test]
+    async fn test_counts_follow_product_writes() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let other_store = testing::seed_store(&db, "seller-2").await;
+        let food = seed_category(&db, "food").await;
+        let fashion = seed_category(&db, "fashion").await;
+
+        let soup = create(&db, store_id, Some(food), None).await;
+        let spice = create(&db, store_id, Some(food), None).await;
+        let scheduled = create(
+            &db,
+            store_id,
+            Some(fashion),
+            Some(Utc::now() + Duration::days(2)),
+        )
+        .await;
+        let loose = create(&db, store_id, None, None).await;
+        create(&db, other_store, Some(food), None).await;
+        assert_in_step(&db).await;
+
+        let counts = ProductCounts::for_store(&db, store_id).await.unwrap();
+        assert_eq!((counts.published, counts.unpublished), (3, 1));
+        assert_eq!(
+            ProductCounts::published_by_category(&db, "default", None)
+                .await
+                .unwrap(),
+            HashMap::from([(food, 3)])
+        );
+        assert_eq!(
+            ProductCounts::published_by_category(&db, "default", Some(store_id))
+                .await
+                .unwrap(),
+            HashMap::from([(food, 2)])
+        );
+        assert!(ProductCounts::published_by_category(&db, "acme", None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Moving a product to another category
+        Product::update(
+            &db,
+            spice.id,
+            None,
+            &spice.name,
+            None,
+            spice.price,
+            None,
+            spice.quantity_available,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(fashion),
+            serde_json::json!({}),
+            ChangeOrigin::edit(None),
+        )
+        .await
+        .unwrap();
+        assert_in_step(&db).await;
+
+        // Held for review again after an earlier approval, then approved
+        let earlier_review = product_moderation::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(soup.id),
+            matched_terms: Set(serde_json::json!([])),
+            status: Set(ModerationStatus::Approved.as_str().to_owned()),
+            created_at: Set(Utc::now()),
+            reviewed_by: Set(Some("admin".to_string())),
+            reviewed_at: Set(Some(Utc::now())),
+        };
+        product_moderation::Entity::insert(earlier_review)
+            .exec_without_returning(&db)
+            .await
+            .unwrap();
+        ProductModeration::hold(&db, soup.clone(), &[])
+            .await
+            .unwrap();
+        assert_in_step(&db).await;
+        assert_eq!(
+            ProductCounts::published_by_category(&db, "default", Some(store_id))
+                .await
+                .unwrap(),
+            HashMap::from([(fashion, 1)])
+        );
+        ProductModeration::review(&db, soup.id, ModerationStatus::Approved, "admin")
+            .await
+            .unwrap();
+        assert_in_step(&db).await;
+
+        Product::delete(&db, loose.id).await.unwrap();
+        Product::delete(&db, scheduled.id).await.unwrap();
+        assert_in_step(&db).await;
+
+        let counts = ProductCounts::for_store(&db, store_id).await.unwrap();
+        assert_eq!((counts.published, counts.unpublished), (2, 0));
+        let by_category: Vec<_> = counts
+            .by_category
+            .iter()
+            .map(|c| (c.category_id, c.published, c.unpublished))
+            .collect();
+        let mut expected = vec![(Some(food), 1, 0), (Some(fashion), 1, 0)];
+        expected.sort();
+        assert_eq!(by_category, expected);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_recounts_from_products() {
+        let db = testing::sqlite().await;
+        let food = seed_category(&db, "food").await;
+        let mut stores = Vec::new();
+        for n in 0..5 {
+            let store_id = testing::seed_store(&db, &format!("seller-{n}")).await;
+            for _ in 0..n {
+                create(&db, store_id, Some(food), None).await;
+            }
+            stores.push(store_id);
+        }
+        create(&db, stores[4], None, Some(Utc::now() + Duration::days(1))).await;
+
+        // Drift: a lost row and a bogus one
+        ProductCountEntity::delete_many()
+            .filter(product_count::Column::StoreId.eq(stores[3]))
+            .exec(&db)
+            .await
+            .unwrap();
+        ProductCounts::record(
+            &db,
+            None,
+            Some(&ProductModel {
+                store_id: stores[0],
+                ..ProductEntity::find().one(&db).await.unwrap().unwrap()
+            }),
+        )
+        .await
+        .unwrap();
+        let (projected, counted) = projected_and_counted(&db).await;
+        assert_ne!(projected, counted);
+
+        let summary = ProductCounts::rebuild(&db, 2).await.unwrap();
+        assert_eq!(summary, RebuildSummary { stores: 5, rows: 5 });
+        assert_in_step(&db).await;
+        // Rebuilt rows are one per key
+        assert_eq!(ProductCountEntity::find().all(&db).await.unwrap().len(), 5);
+    }
+}
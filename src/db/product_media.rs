@@ -0,0 +1,146 @@
+use crate::db::media_quota::MediaQuota;
+use crate::entity::product_media::{
+    self, ActiveModel as ProductMediaActiveModel, Entity as ProductMediaEntity,
+    Model as ProductMediaModel,
+};
+use crate::moderation::image_hash::to_column;
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use sha2::{Digest, Sha256};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+pub struct ProductMedia;
+
+/// Storage details of a stored WebP variant
+pub struct WebpVariant {
+    pub s3_key: String,
+    pub size_bytes: i64,
+}
+
+/// Hex SHA-256 of an upload, so clients can tell when image bytes changed
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl ProductMedia {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        image_id: Uuid,
+        product_id: Uuid,
+        s3_key: &str,
+        content_type: &str,
+        size_bytes: i64,
+        content_hash: &str,
+        perceptual_hash: Option<u64>,
+        webp: Option<WebpVariant>,
+    ) -> Result<ProductMediaModel, String> {
+        let (webp_s3_key, webp_size_bytes) = match webp {
+            Some(variant) => (Some(variant.s3_key), Some(variant.size_bytes)),
+            None => (None, None),
+        };
+        let now = Utc::now();
+        let media = ProductMediaActiveModel {
+            id: Set(image_id),
+            product_id: Set(product_id),
+            s3_key: Set(s3_key.to_owned()),
+            content_type: Set(content_type.to_owned()),
+            size_bytes: Set(size_bytes),
+            webp_s3_key: Set(webp_s3_key),
+            webp_size_bytes: Set(webp_size_bytes),
+            content_hash: Set(Some(content_hash.to_owned())),
+            perceptual_hash: Set(perceptual_hash.map(to_column)),
+            created_at: Set(now),
+            updated_at: Set(now),
+        };
+        let res = media.insert(db).await.map_err(|e| {
+            error!("Failed to record product media: {:?}", e);
+            "Failed to record product media. Please try again later.".to_string()
+        })?;
+        debug!("Product media recorded: {:?}", res);
+        Ok(res)
+    }
+
+    /// Remove a media row and give its bytes back to the store's quota
+    pub async fn delete<C: ConnectionTrait>(
+        conn: &C,
+        store_id: Uuid,
+        media: &ProductMediaModel,
+    ) -> Result<(), String> {
+        ProductMediaEntity::delete_by_id(media.id)
+            .exec(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to delete product media {}: {:?}", media.id, e);
+                "Failed to delete product media. Please try again later.".to_string()
+            })?;
+        MediaQuota::release(conn, store_id, media.size_bytes).await
+    }
+
+    pub async fn get(
+        db: &DatabaseConnection,
+        image_id: Uuid,
+    ) -> Result<Option<ProductMediaModel>, String> {
+        ProductMediaEntity::find_by_id(image_id)
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch product media {}: {:?}", image_id, e);
+                "Failed to fetch product media. Please try again later.".to_string()
+            })
+    }
+
+    /// Media rows of a product
+    pub async fn count_for_product<C: ConnectionTrait>(
+        conn: &C,
+        product_id: Uuid,
+    ) -> Result<u64, String> {
+        ProductMediaEntity::find()
+            .filter(product_media::Column::ProductId.eq(product_id))
+            .count(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to count media for product {}: {:?}", product_id, e);
+                "Failed to count product media. Please try again later.".to_string()
+            })
+    }
+
+    pub async fn list_by_product(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+    ) -> Result<Vec<ProductMediaModel>, String> {
+        ProductMediaEntity::find()
+            .filter(product_media::Column::ProductId.eq(product_id))
+            .order_by_asc(product_media::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list media for product {}: {:?}", product_id, e);
+                "Failed to list product media. Please try again later.".to_string()
+            })
+    }
+
+    /// Media of several products in one query, oldest first
+    #[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+    pub async fn list_by_products(
+        db: &DatabaseConnection,
+        product_ids: &[Uuid],
+    ) -> Result<Vec<ProductMediaModel>, String> {
+        ProductMediaEntity::find()
+            .filter(product_media::Column::ProductId.is_in(product_ids.iter().copied()))
+            .order_by_asc(product_media::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list media for products: {:?}", e);
+                "Failed to list product media. Please try again later.".to_string()
+            })
+    }
+}
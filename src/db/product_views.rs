@@ -0,0 +1,143 @@
+//! Views of product pages, written in batches by the view writer; see
+//! `crate::product_views`.
+
+use crate::entity::product;
+use crate::entity::product_view::{self, ActiveModel as ViewActiveModel, Entity as ViewEntity};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect, Set,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A view waiting to be written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewProductView {
+    pub product_id: Uuid,
+    pub viewer_hash: String,
+    pub viewed_at: DateTime<Utc>,
+}
+
+/// How often a product was viewed, all time and lately
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ViewCounts {
+    pub total: u64,
+    pub last_7_days: u64,
+    pub last_30_days: u64,
+}
+
+pub struct ProductViews;
+
+impl ProductViews {
+    /// Write `views`, leaving out those of products deleted since they were
+    /// seen; returns how many were written
+    pub async fn insert_batch(
+        db: &DatabaseConnection,
+        views: &[NewProductView],
+    ) -> Result<u64, String> {
+        let failed = |e| {
+            error!("Failed to write {} product views: {:?}", views.len(), e);
+            "Failed to record product views.".to_string()
+        };
+        if views.is_empty() {
+            return Ok(0);
+        }
+        let ids: HashSet<Uuid> = views.iter().map(|view| view.product_id).collect();
+        let existing: HashSet<Uuid> = product::Entity::find()
+            .select_only()
+            .column(product::Column::Id)
+            .filter(product::Column::Id.is_in(ids))
+            .into_tuple::<Uuid>()
+            .all(db)
+            .await
+            .map_err(failed)?
+            .into_iter()
+            .collect();
+        let rows: Vec<ViewActiveModel> = views
+            .iter()
+            .filter(|view| existing.contains(&view.product_id))
+            .map(|view| ViewActiveModel {
+                id: Set(Uuid::new_v4()),
+                product_id: Set(view.product_id),
+                viewer_hash: Set(view.viewer_hash.clone()),
+                viewed_at: Set(view.viewed_at),
+            })
+            .collect();
+        let written = rows.len() as u64;
+        if written > 0 {
+            ViewEntity::insert_many(rows)
+                .exec_without_returning(db)
+                .await
+                .map_err(failed)?;
+        }
+        Ok(written)
+    }
+
+    /// Views of `product_id` written so far
+    pub async fn counts(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<ViewCounts, String> {
+        let failed = |e| {
+            error!("Failed to count views of product {}: {:?}", product_id, e);
+            "Failed to count product views.".to_string()
+        };
+        let since = |days| {
+            ViewEntity::find()
+                .filter(product_view::Column::ProductId.eq(product_id))
+                .filter(product_view::Column::ViewedAt.gte(now - Duration::days(days)))
+        };
+        Ok(ViewCounts {
+            total: ViewEntity::find()
+                .filter(product_view::Column::ProductId.eq(product_id))
+                .count(db)
+                .await
+                .map_err(failed)?,
+            last_7_days: since(7).count(db).await.map_err(failed)?,
+            last_30_days: since(30).count(db).await.map_err(failed)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{seed_product, sqlite};
+
+    fn view(product_id: Uuid, viewer: &str, viewed_at: DateTime<Utc>) -> NewProductView {
+        NewProductView {
+            product_id,
+            viewer_hash: viewer.to_string(),
+            viewed_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_views_are_counted_over_their_windows() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller").await;
+        let now = Utc::now();
+        let views = [
+            view(product_id, "a", now),
+            view(product_id, "b", now - Duration::days(3)),
+            view(product_id, "a", now - Duration::days(10)),
+            view(product_id, "c", now - Duration::days(45)),
+            // Deleted since it was seen
+            view(Uuid::new_v4(), "a", now),
+        ];
+        assert_eq!(ProductViews::insert_batch(&db, &views).await, Ok(4));
+        assert_eq!(
+            ProductViews::counts(&db, product_id, now).await,
+            Ok(ViewCounts {
+                total: 4,
+                last_7_days: 2,
+                last_30_days: 3,
+            })
+        );
+        assert_eq!(ProductViews::insert_batch(&db, &[]).await, Ok(0));
+    }
+}
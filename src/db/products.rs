@@ -1,14 +1,592 @@
+use crate::currency::is_iso_4217;
+use crate::db::bundles::Bundle;
+use crate::db::category_attributes::AttributeFilter;
+use crate::db::delivery::{DeliveryOptions, DeliveryOptionsInput};
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::db::is_unique_violation;
+use crate::db::moderation::not_held_condition;
+use crate::db::product_counts::ProductCounts;
+use crate::db::product_media::ProductMedia;
+use crate::db::return_policy::ReturnTerms;
+use crate::db::stores::paused_condition;
+use crate::db::sync::{Tombstone, TombstoneKind};
+use crate::entity::category;
 use crate::entity::product::{
     self, ActiveModel as ProductActiveModel, Entity as ProductEntity, Model as ProductModel,
 };
+use crate::entity::product_bundle::Model as BundleModel;
+use crate::entity::product_media::{self, Entity as ProductMediaEntity};
+use crate::entity::store::{self, Entity as StoreEntity};
+use crate::tenant::ForTenant;
+use chrono::{DateTime, Utc};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    sea_query::{extension::postgres::PgExpr, Expr, Func, LikeExpr, Query, SimpleExpr},
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseBackend, DatabaseConnection,
+    EntityTrait, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set,
+    TransactionTrait, UpdateMany,
 };
-use tracing::{debug, error};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Refusal of a SKU another product in the store already uses
+pub const SKU_TAKEN: &str = "Another product in this store already uses this SKU.";
+
+/// Highest price a product may have; anything above is taken for a typo
+pub const MAX_PRICE: f64 = 100_000_000.0;
+
+/// Why `price` can't be stored in `field`, if it can't
+pub fn price_error(field: &str, price: f64) -> Option<String> {
+    if !price.is_finite() {
+        Some(format!("{field} must be a finite number"))
+    } else if price < 0.0 {
+        Some(format!("{field} must not be negative"))
+    } else if price > MAX_PRICE {
+        Some(format!("{field} must be at most {MAX_PRICE}"))
+    } else {
+        None
+    }
+}
+
+/// Why `quantity` can't be stored as stock, if it can't
+pub fn quantity_error(quantity: i32) -> Option<String> {
+    (quantity < 0).then(|| "quantity_available must not be negative".to_string())
+}
+
+/// Why `threshold` can't be stored as a low-stock threshold, if it can't
+pub fn low_stock_threshold_error(threshold: i32) -> Option<String> {
+    (threshold < 0).then(|| "low_stock_threshold must not be negative".to_string())
+}
+
+/// The threshold `product` just fell to, if its stock went from above
+/// `low_stock_threshold` to at or below it. Later decrements stay quiet
+/// until a restock lifts the stock back above the threshold, so a seller is
+/// warned once per crossing.
+pub fn low_stock_crossing(previous_quantity: i32, product: &ProductModel) -> Option<i32> {
+    product.low_stock_threshold.filter(|&threshold| {
+        previous_quantity > threshold && product.quantity_available <= threshold
+    })
+}
+
+/// Why `code` can't be stored as the currency in `field`, if it can't
+pub fn currency_error(field: &str, code: &str) -> Option<String> {
+    (!is_iso_4217(code))
+        .then(|| format!("{field} must be an ISO 4217 code such as XAF, NGN or USD"))
+}
+
+/// Last guard before a price and stock level are written; the handlers
+/// report the same problems as form errors first
+fn check_amounts(price: f64, quantity_available: i32) -> Result<(), String> {
+    match price_error("price", price).or_else(|| quantity_error(quantity_available)) {
+        Some(problem) => Err(problem),
+        None => Ok(()),
+    }
+}
+
 pub struct Product;
 
+/// Publication state shown to the owning seller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicationStatus {
+    Published,
+    /// `publish_at` is still in the future; hidden from public listings
+    Scheduled,
+    /// Saved without publishing; hidden until the seller publishes it
+    Draft,
+    /// Deleted by the seller; hidden everywhere until restored
+    Archived,
+}
+
+impl PublicationStatus {
+    pub fn of(product: &ProductModel, now: DateTime<Utc>) -> Self {
+        if product.archived_at.is_some() {
+            return PublicationStatus::Archived;
+        }
+        match product.publish_at {
+            Some(publish_at) if publish_at > now => PublicationStatus::Scheduled,
+            None if !product.is_published => PublicationStatus::Draft,
+            _ => PublicationStatus::Published,
+        }
+    }
+}
+
+/// What came of asking to publish a product
+#[derive(Debug, Clone, PartialEq)]
+pub enum PublishOutcome {
+    Published(ProductModel),
+    /// Nothing to do; the product was already live
+    AlreadyPublished(ProductModel),
+    /// Waiting on, or refused by, moderation
+    Held,
+    /// Fewer images than the deployment requires
+    MissingMedia {
+        required: u32,
+        found: u64,
+    },
+}
+
+/// Products visible to buyers: published, with no schedule or a schedule
+/// that has passed, not archived and not held back by moderation. Drafts
+/// stay hidden.
+pub(crate) fn visible_condition(now: DateTime<Utc>) -> Condition {
+    Condition::all()
+        .add(product::Column::IsPublished.eq(true))
+        .add(product::Column::ArchivedAt.is_null())
+        .add(
+            Condition::any()
+                .add(product::Column::PublishAt.is_null())
+                .add(product::Column::PublishAt.lte(now)),
+        )
+        .add(not_held_condition())
+}
+
+/// Products of stores that are not paused. Paused stores keep their direct
+/// links working but drop out of browse-wide listings.
+pub(crate) fn open_store_condition(now: DateTime<Utc>) -> SimpleExpr {
+    product::Column::StoreId.not_in_subquery(
+        Query::select()
+            .column(store::Column::Id)
+            .from(StoreEntity)
+            .cond_where(paused_condition(now))
+            .to_owned(),
+    )
+}
+
+/// `text` for use inside a LIKE pattern escaped by `\`, so `%` and `_`
+/// typed by a buyer match themselves
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Name or description contains `text`, ignoring case: ILIKE on Postgres,
+/// where the trigram indexes serve it, and LIKE elsewhere, SQLite's being
+/// case-insensitive already
+fn text_condition(text: &str, backend: DatabaseBackend) -> Condition {
+    let pattern = format!("%{}%", escape_like(text));
+    let contains = |column: product::Column| -> SimpleExpr {
+        let like = LikeExpr::new(pattern.clone()).escape('\\');
+        match backend {
+            DatabaseBackend::Postgres => Expr::col((ProductEntity, column)).ilike(like),
+            _ => Expr::col((ProductEntity, column)).like(like),
+        }
+    };
+    Condition::any()
+        .add(contains(product::Column::Name))
+        .add(contains(product::Column::Description))
+}
+
+/// Products with at least `min_images` media rows. A media row is only
+/// written once its object is stored, so the rows are trusted as they are.
+fn has_media_condition(min_images: u32) -> Condition {
+    if min_images == 0 {
+        return Condition::all();
+    }
+    Condition::all().add(
+        product::Column::Id.in_subquery(
+            Query::select()
+                .column(product_media::Column::ProductId)
+                .from(ProductMediaEntity)
+                .group_by_col(product_media::Column::ProductId)
+                .and_having(
+                    Expr::expr(Func::count(Expr::col(product_media::Column::Id))).gte(min_images),
+                )
+                .to_owned(),
+        ),
+    )
+}
+
+/// Marks due, not-yet-published products with enough images as published.
+///
+/// Filtering on `is_published = false` makes the promotion idempotent: a product
+/// is returned by exactly one run, even if the scheduler restarts mid-way.
+/// Products held by moderation wait for an admin instead.
+fn publish_due_query(now: DateTime<Utc>, min_images: u32) -> UpdateMany<ProductEntity> {
+    ProductEntity::update_many()
+        .col_expr(product::Column::IsPublished, Expr::value(true))
+        .col_expr(product::Column::UpdatedAt, Expr::value(now))
+        .filter(product::Column::IsPublished.eq(false))
+        .filter(product::Column::PublishAt.lte(now))
+        .filter(not_held_condition())
+        .filter(has_media_condition(min_images))
+}
+
+/// Turns due products that still lack images into drafts, so they wait for
+/// the seller instead of being retried on every run
+fn shelve_due_query(now: DateTime<Utc>, min_images: u32) -> UpdateMany<ProductEntity> {
+    ProductEntity::update_many()
+        .col_expr(
+            product::Column::PublishAt,
+            Expr::value(Option::<DateTime<Utc>>::None),
+        )
+        .col_expr(product::Column::UpdatedAt, Expr::value(now))
+        .filter(product::Column::IsPublished.eq(false))
+        .filter(product::Column::PublishAt.lte(now))
+        .filter(not_held_condition())
+        .filter(has_media_condition(min_images).not())
+}
+
+/// A promotional price that applies until `ends_at`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sale {
+    pub price: f64,
+    pub ends_at: DateTime<Utc>,
+}
+
+/// Fields to change on a product; `None` leaves a field as it is, and
+/// `Some(None)` clears a nullable one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProductPatch {
+    pub sku: Option<Option<String>>,
+    pub name: Option<String>,
+    pub description: Option<Option<String>>,
+    pub price: Option<f64>,
+    pub currency: Option<String>,
+    pub quantity_available: Option<i32>,
+    pub low_stock_threshold: Option<Option<i32>>,
+    pub image_id: Option<Option<Uuid>>,
+    pub category_id: Option<Option<Uuid>>,
+    pub attributes: Option<serde_json::Value>,
+    pub return_policy: Option<ReturnTerms>,
+    pub delivery: Option<DeliveryOptionsInput>,
+    /// `Some(None)` ends a running sale
+    pub sale: Option<Option<Sale>>,
+}
+
+/// The sale currently in effect; expired sales are ignored at read time
+pub fn active_sale(product: &ProductModel, now: DateTime<Utc>) -> Option<Sale> {
+    match (product.sale_price, product.sale_ends_at) {
+        (Some(price), Some(ends_at)) if ends_at > now => Some(Sale { price, ends_at }),
+        _ => None,
+    }
+}
+
+/// Price a buyer pays right now
+pub fn effective_price(product: &ProductModel, now: DateTime<Utc>) -> f64 {
+    active_sale(product, now).map_or(product.price, |sale| sale.price)
+}
+
+/// Whole-number discount of the active sale, if any
+pub fn discount_percent(product: &ProductModel, now: DateTime<Utc>) -> Option<i32> {
+    let sale = active_sale(product, now)?;
+    if product.price <= 0.0 {
+        return None;
+    }
+    Some(((product.price - sale.price) / product.price * 100.0).round() as i32)
+}
+
+/// Which of a store's products a catalog export lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CatalogFilter {
+    /// Also list scheduled, unpublished and held products
+    pub include_unpublished: bool,
+    pub include_out_of_stock: bool,
+}
+
+/// SQL counterpart of [`effective_price`], for filtering and sorting
+fn effective_price_expr(now: DateTime<Utc>) -> SimpleExpr {
+    Expr::case(
+        Condition::all()
+            .add(product::Column::SalePrice.is_not_null())
+            .add(product::Column::SaleEndsAt.gt(now)),
+        Expr::col(product::Column::SalePrice),
+    )
+    .finally(Expr::col(product::Column::Price))
+    .into()
+}
+
+/// Ordering for product listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSort {
+    #[default]
+    Newest,
+    PriceAsc,
+    PriceDesc,
+}
+
+impl std::str::FromStr for ProductSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(ProductSort::Newest),
+            "price_asc" => Ok(ProductSort::PriceAsc),
+            "price_desc" => Ok(ProductSort::PriceDesc),
+            _ => Err("sort must be one of newest, price_asc, price_desc".to_string()),
+        }
+    }
+}
+
+/// Optional filters and ordering applied to listings; prices are effective prices
+#[derive(Debug, Clone, Default)]
+pub struct PriceFilter {
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub returns_accepted: Option<bool>,
+    pub delivery_available: Option<bool>,
+    /// Only products priced in this ISO 4217 currency
+    pub currency: Option<String>,
+    /// Category attribute filters, all of which must match
+    pub attributes: Vec<AttributeFilter>,
+    /// Keep archived products in the listing; only honoured for the owning
+    /// seller, as public listings never show them
+    pub include_archived: bool,
+    pub sort: ProductSort,
+}
+
+impl PriceFilter {
+    /// Whether an item at `price` without a return policy or delivery options
+    /// of its own, such as a bundle, passes the filter
+    pub fn admits(&self, price: f64) -> bool {
+        self.min_price.is_none_or(|min| price >= min)
+            && self.max_price.is_none_or(|max| price <= max)
+            && self.returns_accepted.is_none()
+            && self.delivery_available.is_none()
+            && self.currency.is_none()
+            && self.attributes.is_empty()
+    }
+
+    fn apply(
+        &self,
+        query: Select<ProductEntity>,
+        now: DateTime<Utc>,
+        backend: DatabaseBackend,
+    ) -> Select<ProductEntity> {
+        let mut query = query;
+        if let Some(min) = self.min_price {
+            query = query.filter(Expr::expr(effective_price_expr(now)).gte(min));
+        }
+        if let Some(max) = self.max_price {
+            query = query.filter(Expr::expr(effective_price_expr(now)).lte(max));
+        }
+        if let Some(returns_accepted) = self.returns_accepted {
+            query = query.filter(product::Column::ReturnsAccepted.eq(returns_accepted));
+        }
+        if let Some(delivery_available) = self.delivery_available {
+            query = query.filter(product::Column::DeliveryAvailable.eq(delivery_available));
+        }
+        if let Some(currency) = &self.currency {
+            query = query.filter(product::Column::Currency.eq(currency.as_str()));
+        }
+        for attribute in &self.attributes {
+            query = query.filter(attribute.condition(backend));
+        }
+        if !self.include_archived {
+            query = query.filter(product::Column::ArchivedAt.is_null());
+        }
+        let query = match self.sort {
+            ProductSort::Newest => query.order_by_desc(product::Column::CreatedAt),
+            ProductSort::PriceAsc => query
+                .order_by(effective_price_expr(now), Order::Asc)
+                .order_by_desc(product::Column::CreatedAt),
+            ProductSort::PriceDesc => query
+                .order_by(effective_price_expr(now), Order::Desc)
+                .order_by_desc(product::Column::CreatedAt),
+        };
+        // Ties broken by ID, so pages cut in the database neither skip nor
+        // repeat products created together
+        query.order_by_asc(product::Column::Id)
+    }
+}
+
+/// Where a product's return policy came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnPolicySource {
+    /// Set explicitly on the product
+    Product,
+    /// Inherited from the store's default return policy
+    StoreDefault,
+    /// Neither the product nor the store had a policy
+    None,
+}
+
+impl ReturnPolicySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReturnPolicySource::Product => "product",
+            ReturnPolicySource::StoreDefault => "store_default",
+            ReturnPolicySource::None => "none",
+        }
+    }
+}
+
+/// Pick the effective return policy: the product's own text wins, then the store
+/// default; blank strings count as omitted.
+pub fn resolve_return_policy(
+    requested: Option<&str>,
+    store_default: Option<&str>,
+) -> (Option<String>, ReturnPolicySource) {
+    let non_blank = |policy: Option<&str>| {
+        policy
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_owned)
+    };
+    if let Some(policy) = non_blank(requested) {
+        return (Some(policy), ReturnPolicySource::Product);
+    }
+    if let Some(policy) = non_blank(store_default) {
+        return (Some(policy), ReturnPolicySource::StoreDefault);
+    }
+    (None, ReturnPolicySource::None)
+}
+
+/// A product's resolved return policy, as stored on the product row
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveReturnPolicy {
+    /// Summary for older clients: computed from the product's own terms, or
+    /// the store default text as the seller wrote it
+    pub text: Option<String>,
+    pub terms: ReturnTerms,
+    pub source: ReturnPolicySource,
+}
+
+impl EffectiveReturnPolicy {
+    /// The product's own terms win, then the store's default text, read into terms
+    pub fn resolve(requested: Option<ReturnTerms>, store_default: Option<&str>) -> Self {
+        let summary = requested.as_ref().map(ReturnTerms::describe);
+        let (text, source) = resolve_return_policy(summary.as_deref(), store_default);
+        let terms = match (source, requested) {
+            (ReturnPolicySource::Product, Some(terms)) => terms,
+            (ReturnPolicySource::StoreDefault, _) => text
+                .as_deref()
+                .map(ReturnTerms::from_text)
+                .unwrap_or_default(),
+            _ => ReturnTerms::default(),
+        };
+        Self {
+            text,
+            terms,
+            source,
+        }
+    }
+
+    fn apply(self, active: &mut ProductActiveModel) {
+        active.return_policy = Set(self.text);
+        active.return_policy_source = Set(self.source.as_str().to_owned());
+        active.returns_accepted = Set(self.terms.returns_accepted);
+        active.return_window_days = Set(self.terms.window_days);
+        active.return_conditions = Set(self.terms.conditions);
+    }
+}
+
+/// Products take the tenant of the store they are listed in
+async fn store_tenant(db: &DatabaseConnection, store_id: Uuid) -> Result<String, String> {
+    StoreEntity::find_by_id(store_id)
+        .select_only()
+        .column(store::Column::TenantId)
+        .into_tuple::<String>()
+        .one(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch store {}: {:?}", store_id, e);
+            "Failed to create product. Please try again later.".to_string()
+        })?
+        .ok_or_else(|| "Store not found.".to_string())
+}
+
+/// Fetch the store default only when the product doesn't provide its own policy
+async fn effective_return_policy(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    requested: Option<ReturnTerms>,
+) -> Result<EffectiveReturnPolicy, String> {
+    if requested.is_some() {
+        return Ok(EffectiveReturnPolicy::resolve(requested, None));
+    }
+    let store_default = StoreEntity::find_by_id(store_id)
+        .one(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch store {}: {:?}", store_id, e);
+            "Failed to resolve return policy. Please try again later.".to_string()
+        })?
+        .and_then(|store| store.default_return_policy);
+    Ok(EffectiveReturnPolicy::resolve(
+        requested,
+        store_default.as_deref(),
+    ))
+}
+
+/// Where a product's delivery options came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOptionsSource {
+    /// The seller set at least one option on the product
+    Product,
+    /// Copied whole from the store's defaults, and kept in step with them
+    StoreDefault,
+}
+
+impl DeliveryOptionsSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryOptionsSource::Product => "product",
+            DeliveryOptionsSource::StoreDefault => "store_default",
+        }
+    }
+}
+
+/// The product's own options, with the store's defaults for the rest
+async fn effective_delivery_options(
+    db: &DatabaseConnection,
+    store_id: Uuid,
+    requested: Option<DeliveryOptionsInput>,
+) -> Result<(DeliveryOptions, DeliveryOptionsSource), String> {
+    let store = StoreEntity::find_by_id(store_id)
+        .one(db)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch store {}: {:?}", store_id, e);
+            "Failed to resolve delivery options. Please try again later.".to_string()
+        })?
+        .ok_or_else(|| "Store not found.".to_string())?;
+    let defaults = DeliveryOptions::store_default(&store);
+    Ok(match requested {
+        Some(input) => (input.fill(defaults), DeliveryOptionsSource::Product),
+        None => (defaults, DeliveryOptionsSource::StoreDefault),
+    })
+}
+
+fn apply_delivery_options(
+    active: &mut ProductActiveModel,
+    (options, source): (DeliveryOptions, DeliveryOptionsSource),
+) {
+    active.pickup_available = Set(options.pickup_available);
+    active.delivery_available = Set(options.delivery_available);
+    active.delivery_fee_override = Set(options.delivery_fee);
+    active.delivery_estimated_days = Set(options.estimated_days);
+    active.delivery_options_source = Set(source.as_str().to_owned());
+}
+
+/// Page `page` (from 1) of `query`, `per_page` long, and the rows in all
+/// pages; a page past the end is empty
+async fn fetch_page(
+    db: &DatabaseConnection,
+    query: Select<ProductEntity>,
+    page: u64,
+    per_page: u64,
+) -> Result<(Vec<ProductModel>, u64), sea_orm::DbErr> {
+    let per_page = per_page.max(1);
+    let page = page.saturating_sub(1);
+    let paginator = query.paginate(db, per_page);
+    let total = paginator.num_items().await?;
+    // Past the end: nothing to read, and an offset that may not fit in SQL
+    if page.saturating_mul(per_page) >= total {
+        return Ok((Vec::new(), total));
+    }
+    let products = paginator.fetch_page(page).await?;
+    Ok((products, total))
+}
+
 #[allow(clippy::too_many_arguments)]
 impl Product {
     pub async fn create(
@@ -18,31 +596,82 @@ impl Product {
         name: &str,
         description: Option<&str>,
         price: f64,
+        currency: &str,
         quantity_available: i32,
+        low_stock_threshold: Option<i32>,
         image_id: Option<Uuid>,
+        return_policy: Option<ReturnTerms>,
+        delivery: Option<DeliveryOptionsInput>,
+        publish_at: Option<DateTime<Utc>>,
+        draft: bool,
+        sale: Option<Sale>,
+        category_id: Option<Uuid>,
+        attributes: serde_json::Value,
     ) -> Result<ProductModel, String> {
         debug!(
             "Creating product with: store_id={}, name={}",
             store_id, name
         );
+        check_amounts(price, quantity_available)?;
+        if let Some(problem) = currency_error("currency", currency) {
+            return Err(problem);
+        }
 
-        let product = ProductActiveModel {
-            id: Set(Uuid::new_v4()),
+        let return_policy = effective_return_policy(db, store_id, return_policy).await?;
+        let delivery = effective_delivery_options(db, store_id, delivery).await?;
+        let tenant_id = store_tenant(db, store_id).await?;
+
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let mut product = ProductActiveModel {
+            id: Set(id),
             store_id: Set(store_id),
+            tenant_id: Set(tenant_id),
             sku: Set(sku.map(|s| s.to_owned())),
             name: Set(name.to_owned()),
             description: Set(description.map(|d| d.to_owned())),
             price: Set(price),
+            currency: Set(currency.to_owned()),
             quantity_available: Set(quantity_available),
+            low_stock_threshold: Set(low_stock_threshold),
             image_id: Set(image_id),
+            publish_at: Set(publish_at),
+            sale_price: Set(sale.map(|s| s.price)),
+            sale_ends_at: Set(sale.map(|s| s.ends_at)),
+            category_id: Set(category_id),
+            attributes: Set(attributes),
+            is_published: Set(!draft && publish_at.is_none_or(|at| at <= now)),
+            created_at: Set(now),
+            updated_at: Set(now),
             ..Default::default()
         };
+        return_policy.apply(&mut product);
+        apply_delivery_options(&mut product, delivery);
 
         debug!("Product ActiveModel created: {:?}", product);
-        let res = product.insert(db).await.map_err(|e| {
+        let fail = |e: sea_orm::DbErr| {
             error!("Failed to create product: {:?}", e);
             "Failed to create product. Please try again later.".to_string()
-        })?;
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        // The form checks the SKU first; the unique index settles two
+        // creates racing past that check
+        if let Err(e) = ProductEntity::insert(product)
+            .exec_without_returning(&txn)
+            .await
+        {
+            if sku.is_some() && is_unique_violation(&e) {
+                return Err(SKU_TAKEN.to_string());
+            }
+            return Err(fail(e));
+        }
+        let res = ProductEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Failed to create product. Please try again later.".to_string())?;
+        ProductCounts::record(&txn, None, Some(&res)).await?;
+        txn.commit().await.map_err(fail)?;
         debug!("Product created: {:?}", res);
         Ok(res)
     }
@@ -61,11 +690,15 @@ impl Product {
 
     pub async fn list_by_store(
         db: &DatabaseConnection,
+        tenant_id: &str,
         store_id: Uuid,
+        price_filter: PriceFilter,
     ) -> Result<Vec<ProductModel>, String> {
-        let products = ProductEntity::find()
+        let query = ProductEntity::find()
             .filter(product::Column::StoreId.eq(store_id))
-            .order_by_desc(product::Column::CreatedAt)
+            .for_tenant(tenant_id);
+        let products = price_filter
+            .apply(query, Utc::now(), db.get_database_backend())
             .all(db)
             .await
             .map_err(|e| {
@@ -75,19 +708,268 @@ impl Product {
         Ok(products)
     }
 
-    /// List all products (no store filter), newest first
-    #[allow(dead_code)]
-    pub async fn list_all(db: &DatabaseConnection) -> Result<Vec<ProductModel>, String> {
-        let products = ProductEntity::find()
+    /// Page `page` (from 1) of a store's products, `per_page` long, and how
+    /// many there are in all; only publicly visible ones if `visible_only`
+    pub async fn page_by_store(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_id: Uuid,
+        price_filter: PriceFilter,
+        visible_only: bool,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<ProductModel>, u64), String> {
+        let now = Utc::now();
+        let mut query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .for_tenant(tenant_id);
+        if visible_only {
+            query = query.filter(visible_condition(now));
+        }
+        fetch_page(
+            db,
+            price_filter.apply(query, now, db.get_database_backend()),
+            page,
+            per_page,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list products for store {}: {:?}", store_id, e);
+            "Failed to list products. Please try again later.".to_string()
+        })
+    }
+
+    /// Page `page` (from 1) of a store's publicly visible products, only
+    /// those in the category with slug `category` if given, and how many
+    /// match in all. An unknown slug matches nothing.
+    pub async fn list_filtered(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_id: Uuid,
+        category: Option<&str>,
+        price_filter: PriceFilter,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<ProductModel>, u64), String> {
+        let now = Utc::now();
+        let mut query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .for_tenant(tenant_id)
+            .filter(visible_condition(now));
+        if let Some(slug) = category {
+            query = query.filter(
+                product::Column::CategoryId.in_subquery(
+                    Query::select()
+                        .column(category::Column::Id)
+                        .from(category::Entity)
+                        .and_where(category::Column::Slug.eq(slug))
+                        .to_owned(),
+                ),
+            );
+        }
+        fetch_page(
+            db,
+            price_filter.apply(query, now, db.get_database_backend()),
+            page,
+            per_page,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list products for store {}: {:?}", store_id, e);
+            "Failed to list products. Please try again later.".to_string()
+        })
+    }
+
+    /// Page `page` (from 1) of the publicly visible products whose name or
+    /// description contains `text`, and how many match in all. Products of
+    /// paused stores are left out, as from other browse-wide listings.
+    pub async fn search(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        text: &str,
+        store_id: Option<Uuid>,
+        category_id: Option<Uuid>,
+        price_filter: PriceFilter,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<ProductModel>, u64), String> {
+        let now = Utc::now();
+        let mut query = ProductEntity::find()
+            .for_tenant(tenant_id)
+            .filter(text_condition(text, db.get_database_backend()))
+            .filter(visible_condition(now))
+            .filter(open_store_condition(now));
+        if let Some(store_id) = store_id {
+            query = query.filter(product::Column::StoreId.eq(store_id));
+        }
+        if let Some(category_id) = category_id {
+            query = query.filter(product::Column::CategoryId.eq(category_id));
+        }
+        fetch_page(
+            db,
+            price_filter.apply(query, now, db.get_database_backend()),
+            page,
+            per_page,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to search products: {:?}", e);
+            "Failed to search products. Please try again later.".to_string()
+        })
+    }
+
+    /// Whether another product in the store already uses this SKU
+    pub async fn sku_taken(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        sku: &str,
+        exclude_product_id: Option<Uuid>,
+    ) -> Result<bool, String> {
+        let mut query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .filter(product::Column::Sku.eq(sku));
+        if let Some(id) = exclude_product_id {
+            query = query.filter(product::Column::Id.ne(id));
+        }
+        let count = query.count(db).await.map_err(|e| {
+            error!("Failed to check SKU for store {}: {:?}", store_id, e);
+            "Failed to validate SKU. Please try again later.".to_string()
+        })?;
+        Ok(count > 0)
+    }
+
+    /// Fetch a product only if it is publicly visible
+    pub async fn get_visible(db: &DatabaseConnection, id: Uuid) -> Result<ProductModel, String> {
+        let product = ProductEntity::find_by_id(id)
+            .filter(visible_condition(Utc::now()))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch product {}: {:?}", id, e);
+                "Product not found.".to_string()
+            })?
+            .ok_or_else(|| "Product not found.".to_string())?;
+        Ok(product)
+    }
+
+    /// Publicly visible products of `tenant_id` among `ids`, in one query
+    #[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+    pub async fn get_visible_many(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        ids: &[Uuid],
+    ) -> Result<Vec<ProductModel>, String> {
+        ProductEntity::find()
+            .filter(product::Column::Id.is_in(ids.iter().copied()))
+            .for_tenant(tenant_id)
+            .filter(visible_condition(Utc::now()))
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch products: {:?}", e);
+                "Failed to fetch products. Please try again later.".to_string()
+            })
+    }
+
+    /// Publicly visible products of several stores in one query, newest first
+    #[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+    pub async fn list_visible_by_stores(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_ids: &[Uuid],
+    ) -> Result<Vec<ProductModel>, String> {
+        ProductEntity::find()
+            .filter(product::Column::StoreId.is_in(store_ids.iter().copied()))
+            .for_tenant(tenant_id)
+            .filter(visible_condition(Utc::now()))
             .order_by_desc(product::Column::CreatedAt)
             .all(db)
             .await
             .map_err(|e| {
-                error!("Failed to list products: {:?}", e);
+                error!("Failed to list products for stores: {:?}", e);
+                "Failed to list products. Please try again later.".to_string()
+            })
+    }
+
+    /// List a store's publicly visible products (scheduled ones are hidden)
+    pub async fn list_visible_by_store(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        store_id: Uuid,
+        price_filter: PriceFilter,
+    ) -> Result<Vec<ProductModel>, String> {
+        let now = Utc::now();
+        let query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .for_tenant(tenant_id)
+            .filter(visible_condition(now));
+        let products = price_filter
+            .apply(query, now, db.get_database_backend())
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list products for store {}: {:?}", store_id, e);
                 "Failed to list products. Please try again later.".to_string()
             })?;
         Ok(products)
     }
+
+    /// One page of a store's catalog export, by name. Published, in-stock
+    /// products only unless `filter` says otherwise.
+    pub async fn catalog_page(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        filter: CatalogFilter,
+        now: DateTime<Utc>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<ProductModel>, String> {
+        let mut query = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .filter(product::Column::ArchivedAt.is_null());
+        if !filter.include_unpublished {
+            query = query
+                .filter(product::Column::IsPublished.eq(true))
+                .filter(visible_condition(now));
+        }
+        if !filter.include_out_of_stock {
+            query = query.filter(product::Column::QuantityAvailable.gt(0));
+        }
+        query
+            .order_by_asc(product::Column::Name)
+            .order_by_asc(product::Column::Id)
+            .offset(offset)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list catalog of store {}: {:?}", store_id, e);
+                "Failed to build catalog. Please try again later.".to_string()
+            })
+    }
+
+    /// Page `page` (from 1) of all publicly visible products (no store
+    /// filter), newest first, and how many there are in all. Products of
+    /// paused stores are left out.
+    #[allow(dead_code)]
+    pub async fn list_all(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<ProductModel>, u64), String> {
+        let now = Utc::now();
+        let query = ProductEntity::find()
+            .for_tenant(tenant_id)
+            .filter(visible_condition(now))
+            .filter(open_store_condition(now))
+            .order_by_desc(product::Column::CreatedAt)
+            .order_by_asc(product::Column::Id);
+        fetch_page(db, query, page, per_page).await.map_err(|e| {
+            error!("Failed to list products: {:?}", e);
+            "Failed to list products. Please try again later.".to_string()
+        })
+    }
     pub async fn update(
         db: &DatabaseConnection,
         id: Uuid,
@@ -95,9 +977,21 @@ impl Product {
         name: &str,
         description: Option<&str>,
         price: f64,
+        currency: Option<&str>,
         quantity_available: i32,
+        low_stock_threshold: Option<i32>,
         image_id: Option<Uuid>,
+        return_policy: Option<ReturnTerms>,
+        delivery: Option<DeliveryOptionsInput>,
+        sale: Option<Sale>,
+        category_id: Option<Uuid>,
+        attributes: serde_json::Value,
+        origin: ChangeOrigin<'_>,
     ) -> Result<ProductModel, String> {
+        check_amounts(price, quantity_available)?;
+        if let Some(problem) = currency.and_then(|code| currency_error("currency", code)) {
+            return Err(problem);
+        }
         let product = ProductEntity::find_by_id(id)
             .one(db)
             .await
@@ -107,23 +1001,198 @@ impl Product {
             })?
             .ok_or_else(|| "Product not found.".to_string())?;
 
-        let mut active: ProductActiveModel = product.into();
+        let return_policy = effective_return_policy(db, product.store_id, return_policy).await?;
+        let delivery = effective_delivery_options(db, product.store_id, delivery).await?;
+
+        let mut active: ProductActiveModel = product.clone().into();
+        return_policy.apply(&mut active);
+        apply_delivery_options(&mut active, delivery);
         active.sku = Set(sku.map(|s| s.to_owned()));
         active.name = Set(name.to_owned());
         active.description = Set(description.map(|d| d.to_owned()));
         active.price = Set(price);
+        if let Some(currency) = currency {
+            active.currency = Set(currency.to_owned());
+        }
         active.quantity_available = Set(quantity_available);
+        active.low_stock_threshold = Set(low_stock_threshold);
         active.image_id = Set(image_id);
+        active.sale_price = Set(sale.map(|s| s.price));
+        active.sale_ends_at = Set(sale.map(|s| s.ends_at));
+        active.category_id = Set(category_id);
+        active.attributes = Set(attributes);
+        active.updated_at = Set(Utc::now());
 
-        let res = active.update(db).await.map_err(|e| {
+        let fail = |e: sea_orm::DbErr| {
             error!("Failed to update product {}: {:?}", id, e);
             "Failed to update product. Please try again later.".to_string()
-        })?;
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let res = active.update(&txn).await.map_err(fail)?;
+        ProductCounts::record(&txn, Some(&product), Some(&res)).await?;
+        AuditLog::record_product(&txn, &product, &res, origin).await?;
+        txn.commit().await.map_err(fail)?;
         debug!("Product updated: {:?}", res);
         Ok(res)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<(), String> {
+    /// Change only the fields set in `patch`. Return terms and delivery
+    /// options, when given, are resolved against the store's defaults as
+    /// [`Product::update`] does.
+    pub async fn patch(
+        db: &DatabaseConnection,
+        id: Uuid,
+        patch: ProductPatch,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<ProductModel, String> {
+        let product = ProductEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch product {}: {:?}", id, e);
+                "Failed to update product. Please try again later.".to_string()
+            })?
+            .ok_or_else(|| "Product not found.".to_string())?;
+
+        let mut active: ProductActiveModel = product.clone().into();
+        if let Some(terms) = patch.return_policy {
+            effective_return_policy(db, product.store_id, Some(terms))
+                .await?
+                .apply(&mut active);
+        }
+        if let Some(delivery) = patch.delivery {
+            let delivery = effective_delivery_options(db, product.store_id, Some(delivery)).await?;
+            apply_delivery_options(&mut active, delivery);
+        }
+        if let Some(sku) = patch.sku {
+            active.sku = Set(sku);
+        }
+        if let Some(name) = patch.name {
+            active.name = Set(name);
+        }
+        if let Some(description) = patch.description {
+            active.description = Set(description);
+        }
+        if let Some(price) = patch.price {
+            active.price = Set(price);
+        }
+        if let Some(currency) = patch.currency {
+            active.currency = Set(currency);
+        }
+        if let Some(quantity_available) = patch.quantity_available {
+            active.quantity_available = Set(quantity_available);
+        }
+        if let Some(low_stock_threshold) = patch.low_stock_threshold {
+            active.low_stock_threshold = Set(low_stock_threshold);
+        }
+        if let Some(image_id) = patch.image_id {
+            active.image_id = Set(image_id);
+        }
+        if let Some(category_id) = patch.category_id {
+            active.category_id = Set(category_id);
+        }
+        if let Some(attributes) = patch.attributes {
+            active.attributes = Set(attributes);
+        }
+        if let Some(sale) = patch.sale {
+            active.sale_price = Set(sale.map(|s| s.price));
+            active.sale_ends_at = Set(sale.map(|s| s.ends_at));
+        }
+        if !active.is_changed() {
+            return Ok(product);
+        }
+        active.updated_at = Set(Utc::now());
+
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to update product {}: {:?}", id, e);
+            "Failed to update product. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let res = match active.update(&txn).await {
+            Ok(res) => res,
+            Err(e) if is_unique_violation(&e) => return Err(SKU_TAKEN.to_string()),
+            Err(e) => return Err(fail(e)),
+        };
+        ProductCounts::record(&txn, Some(&product), Some(&res)).await?;
+        AuditLog::record_product(&txn, &product, &res, origin).await?;
+        txn.commit().await.map_err(fail)?;
+        debug!("Product patched: {:?}", res);
+        Ok(res)
+    }
+
+    /// Archive a product, the seller's delete: it leaves every listing, the
+    /// bundles it was part of are deactivated and sync clients get a
+    /// tombstone, but the row stays with its reviews and history until
+    /// [`Product::restore`] or [`Product::delete_permanently`]. Returns the
+    /// archived product and the bundles deactivated; archiving an archived
+    /// product changes nothing.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<(ProductModel, Vec<BundleModel>), String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to archive product {}: {:?}", id, e);
+            "Failed to delete product. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let product = ProductEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Product not found.".to_string())?;
+        if product.archived_at.is_some() {
+            return Ok((product, Vec::new()));
+        }
+
+        let now = Utc::now();
+        let deactivated = Bundle::deactivate_containing(&txn, id, now).await?;
+        let mut active: ProductActiveModel = product.clone().into();
+        active.archived_at = Set(Some(now));
+        active.updated_at = Set(now);
+        let res = active.update(&txn).await.map_err(fail)?;
+        Tombstone::record(&txn, TombstoneKind::Product, id, Some(res.store_id)).await?;
+        ProductCounts::record(&txn, Some(&product), Some(&res)).await?;
+        txn.commit().await.map_err(fail)?;
+        debug!("Product archived: {}", id);
+        Ok((res, deactivated))
+    }
+
+    /// Bring an archived product back into the listings it qualifies for.
+    /// Bundles deactivated by the archive stay inactive until their seller
+    /// turns them back on. Restoring a product that isn't archived changes
+    /// nothing.
+    pub async fn restore(db: &DatabaseConnection, id: Uuid) -> Result<ProductModel, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to restore product {}: {:?}", id, e);
+            "Failed to restore product. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let product = ProductEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Product not found.".to_string())?;
+        if product.archived_at.is_none() {
+            return Ok(product);
+        }
+
+        let mut active: ProductActiveModel = product.clone().into();
+        active.archived_at = Set(None);
+        // A newer `updated_at` than the tombstone puts it back on sync clients
+        active.updated_at = Set(Utc::now());
+        let res = active.update(&txn).await.map_err(fail)?;
+        ProductCounts::record(&txn, Some(&product), Some(&res)).await?;
+        txn.commit().await.map_err(fail)?;
+        debug!("Product restored: {}", id);
+        Ok(res)
+    }
+
+    /// Remove a product's row for good, archived or not, deactivating the
+    /// bundles it was part of. Returns the bundles deactivated.
+    pub async fn delete_permanently(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Vec<BundleModel>, String> {
         let product = ProductEntity::find_by_id(id)
             .one(db)
             .await
@@ -133,17 +1202,141 @@ impl Product {
             })?
             .ok_or_else(|| "Product not found.".to_string())?;
 
-        let active: ProductActiveModel = product.into();
-        active.delete(db).await.map_err(|e| {
+        let store_id = product.store_id;
+        let active: ProductActiveModel = product.clone().into();
+        let txn = db.begin().await.map_err(|e| {
+            error!("Failed to start transaction for product {}: {:?}", id, e);
+            "Failed to delete product. Please try again later.".to_string()
+        })?;
+        let deactivated = Bundle::deactivate_containing(&txn, id, Utc::now()).await?;
+        active.delete(&txn).await.map_err(|e| {
             error!("Failed to delete product {}: {:?}", id, e);
             "Failed to delete product. Please try again later.".to_string()
         })?;
+        Tombstone::record(&txn, TombstoneKind::Product, id, Some(store_id)).await?;
+        ProductCounts::record(&txn, Some(&product), None).await?;
+        txn.commit().await.map_err(|e| {
+            error!("Failed to commit deletion of product {}: {:?}", id, e);
+            "Failed to delete product. Please try again later.".to_string()
+        })?;
         debug!("Product deleted: {}", id);
-        Ok(())
+        Ok(deactivated)
+    }
+
+    /// Publish every scheduled product whose time has come, returning the
+    /// products that went live in this call. Due products with fewer than
+    /// `min_images` images become drafts instead.
+    pub async fn publish_due(
+        db: &DatabaseConnection,
+        now: DateTime<Utc>,
+        min_images: u32,
+    ) -> Result<Vec<ProductModel>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to publish scheduled products: {:?}", e);
+            "Failed to publish scheduled products.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let published = publish_due_query(now, min_images)
+            .exec_with_returning(&txn)
+            .await
+            .map_err(fail)?;
+        if min_images > 0 {
+            let shelved = shelve_due_query(now, min_images)
+                .exec(&txn)
+                .await
+                .map_err(fail)?;
+            if shelved.rows_affected > 0 {
+                warn!(
+                    count = shelved.rows_affected,
+                    min_images, "Scheduled products lacked images and were kept as drafts"
+                );
+            }
+        }
+        for product in &published {
+            let before = ProductModel {
+                is_published: false,
+                ..product.clone()
+            };
+            ProductCounts::record(&txn, Some(&before), Some(product)).await?;
+        }
+        txn.commit().await.map_err(fail)?;
+        Ok(published)
+    }
+
+    /// Publish a draft now, if it has the images the deployment requires and
+    /// is not held by moderation. A pending schedule is dropped.
+    pub async fn publish(
+        db: &DatabaseConnection,
+        id: Uuid,
+        min_images: u32,
+        origin: ChangeOrigin<'_>,
+    ) -> Result<PublishOutcome, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to publish product {}: {:?}", id, e);
+            "Failed to publish product. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let product = ProductEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Product not found.".to_string())?;
+        if product.is_published {
+            return Ok(PublishOutcome::AlreadyPublished(product));
+        }
+        let held = ProductEntity::find_by_id(id)
+            .filter(not_held_condition())
+            .count(&txn)
+            .await
+            .map_err(fail)?
+            == 0;
+        if held {
+            return Ok(PublishOutcome::Held);
+        }
+        let found = ProductMedia::count_for_product(&txn, id).await?;
+        if found < u64::from(min_images) {
+            return Ok(PublishOutcome::MissingMedia {
+                required: min_images,
+                found,
+            });
+        }
+
+        let mut active: ProductActiveModel = product.clone().into();
+        active.is_published = Set(true);
+        active.publish_at = Set(None);
+        active.updated_at = Set(Utc::now());
+        let res = active.update(&txn).await.map_err(fail)?;
+        ProductCounts::record(&txn, Some(&product), Some(&res)).await?;
+        AuditLog::record_product(&txn, &product, &res, origin).await?;
+        txn.commit().await.map_err(fail)?;
+        Ok(PublishOutcome::Published(res))
     }
 
-    pub async fn update_image(
+    /// Null out sale fields whose end time has passed. Reads already ignore
+    /// expired sales; this only keeps the table tidy.
+    pub async fn clear_expired_sales(
         db: &DatabaseConnection,
+        now: DateTime<Utc>,
+    ) -> Result<u64, String> {
+        let res = ProductEntity::update_many()
+            .col_expr(product::Column::SalePrice, Expr::value(Option::<f64>::None))
+            .col_expr(
+                product::Column::SaleEndsAt,
+                Expr::value(Option::<DateTime<Utc>>::None),
+            )
+            .col_expr(product::Column::UpdatedAt, Expr::value(now))
+            .filter(product::Column::SaleEndsAt.lte(now))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to clear expired sales: {:?}", e);
+                "Failed to clear expired sales.".to_string()
+            })?;
+        Ok(res.rows_affected)
+    }
+
+    pub async fn update_image<C: ConnectionTrait>(
+        db: &C,
         id: Uuid,
         image_id: Option<Uuid>,
     ) -> Result<ProductModel, String> {
@@ -158,6 +1351,7 @@ impl Product {
 
         let mut active: ProductActiveModel = product.into();
         active.image_id = Set(image_id);
+        active.updated_at = Set(Utc::now());
 
         let res = active.update(db).await.map_err(|e| {
             error!("Failed to update product image {}: {:?}", id, e);
@@ -167,3 +1361,789 @@ impl Product {
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn scheduled_product(publish_at: Option<DateTime<Utc>>) -> ProductModel {
+        ProductModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: "Wax print".to_string(),
+            description: None,
+            price: 5000.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: 3,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at,
+            is_published: publish_at.is_none(),
+            archived_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_publication_status() {
+        let now = Utc::now();
+        let future = scheduled_product(Some(now + Duration::hours(1)));
+        assert_eq!(
+            PublicationStatus::of(&future, now),
+            PublicationStatus::Scheduled
+        );
+        assert_eq!(
+            PublicationStatus::of(&future, now + Duration::hours(2)),
+            PublicationStatus::Published
+        );
+        assert_eq!(
+            PublicationStatus::of(&scheduled_product(None), now),
+            PublicationStatus::Published
+        );
+    }
+
+    #[test]
+    fn test_effective_price_and_discount() {
+        let now = Utc::now();
+        let mut product = scheduled_product(None);
+        assert_eq!(effective_price(&product, now), 5000.0);
+        assert_eq!(discount_percent(&product, now), None);
+
+        product.sale_price = Some(3750.0);
+        product.sale_ends_at = Some(now + Duration::hours(1));
+        assert_eq!(effective_price(&product, now), 3750.0);
+        assert_eq!(discount_percent(&product, now), Some(25));
+
+        // Expired sales are ignored without any cleanup having run
+        let after = now + Duration::hours(2);
+        assert_eq!(effective_price(&product, after), 5000.0);
+        assert_eq!(discount_percent(&product, after), None);
+    }
+
+    #[test]
+    fn test_price_sort_uses_effective_price() {
+        let filter = PriceFilter {
+            min_price: Some(1000.0),
+            max_price: None,
+            returns_accepted: None,
+            delivery_available: None,
+            sort: ProductSort::PriceAsc,
+            attributes: Vec::new(),
+            include_archived: false,
+            currency: None,
+        };
+        let sql = filter
+            .apply(ProductEntity::find(), Utc::now(), DbBackend::Postgres)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#"ORDER BY (CASE WHEN ("products"."sale_price" IS NOT NULL"#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#"ELSE "price" END) >= 1000"#), "{sql}");
+    }
+
+    #[test]
+    fn test_publish_due_only_promotes_unpublished_products() {
+        let sql = publish_due_query(Utc::now(), 0)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#"SET "is_published" = TRUE"#), "{sql}");
+        // The guard is what keeps a restarted scheduler from re-publishing
+        assert!(sql.contains(r#""is_published" = FALSE"#), "{sql}");
+        assert!(sql.contains(r#""publish_at" <="#), "{sql}");
+    }
+
+    #[test]
+    fn test_browse_listing_skips_paused_stores() {
+        let sql = ProductEntity::find()
+            .filter(open_store_condition(Utc::now()))
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#""store_id" NOT IN (SELECT "id" FROM "stores""#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#""is_paused" = TRUE"#), "{sql}");
+    }
+
+    #[test]
+    fn test_resolve_return_policy_prefers_product() {
+        let (policy, source) = resolve_return_policy(Some("7 days"), Some("No returns"));
+        assert_eq!(policy.as_deref(), Some("7 days"));
+        assert_eq!(source, ReturnPolicySource::Product);
+    }
+
+    #[test]
+    fn test_resolve_return_policy_falls_back_to_store_default() {
+        for requested in [None, Some(""), Some("   ")] {
+            let (policy, source) = resolve_return_policy(requested, Some("No returns"));
+            assert_eq!(policy.as_deref(), Some("No returns"));
+            assert_eq!(source, ReturnPolicySource::StoreDefault);
+        }
+    }
+
+    #[test]
+    fn test_effective_policy_reads_store_default_into_terms() {
+        let own = ReturnTerms {
+            returns_accepted: true,
+            window_days: Some(14),
+            conditions: None,
+        };
+        let policy = EffectiveReturnPolicy::resolve(Some(own.clone()), Some("No returns"));
+        assert_eq!(
+            policy.text.as_deref(),
+            Some("Returns accepted within 14 days.")
+        );
+        assert_eq!(policy.terms, own);
+        assert_eq!(policy.source, ReturnPolicySource::Product);
+
+        let inherited = EffectiveReturnPolicy::resolve(None, Some("7 days"));
+        assert_eq!(inherited.text.as_deref(), Some("7 days"));
+        assert_eq!(inherited.terms.window_days, Some(7));
+        assert_eq!(inherited.source, ReturnPolicySource::StoreDefault);
+
+        let none = EffectiveReturnPolicy::resolve(None, None);
+        assert_eq!(none.terms, ReturnTerms::default());
+        assert_eq!(none.source, ReturnPolicySource::None);
+    }
+
+    #[test]
+    fn test_returns_accepted_filter() {
+        let filter = PriceFilter {
+            returns_accepted: Some(true),
+            ..Default::default()
+        };
+        let sql = filter
+            .apply(ProductEntity::find(), Utc::now(), DbBackend::Postgres)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#""returns_accepted" = TRUE"#), "{sql}");
+    }
+
+    #[test]
+    fn test_delivery_available_filter() {
+        let filter = PriceFilter {
+            delivery_available: Some(true),
+            ..Default::default()
+        };
+        let sql = filter
+            .apply(ProductEntity::find(), Utc::now(), DbBackend::Postgres)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#""delivery_available" = TRUE"#), "{sql}");
+        // Bundles have no delivery options to match on
+        assert!(!filter.admits(5000.0));
+    }
+
+    #[test]
+    fn test_scheduler_only_publishes_products_with_enough_images() {
+        let now = Utc::now();
+        let sql = publish_due_query(now, 2)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#""id" IN (SELECT "product_id" FROM "product_media""#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#"HAVING COUNT("id") >= 2"#), "{sql}");
+        let sql = shelve_due_query(now, 2)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#"SET "publish_at" = NULL"#), "{sql}");
+        assert!(
+            sql.contains(r#"NOT "products"."id" IN (SELECT "product_id" FROM "product_media""#),
+            "{sql}"
+        );
+    }
+
+    async fn draft(db: &DatabaseConnection) -> ProductModel {
+        let store_id = crate::db::testing::seed_store(db, "seller-1").await;
+        Product::create(
+            db,
+            store_id,
+            None,
+            "Wax print",
+            None,
+            5000.0,
+            "XAF",
+            3,
+            None,
+            None,
+            None,
+            None,
+            None,
+            true,
+            None,
+            None,
+            serde_json::json!({}),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn add_image(db: &DatabaseConnection, product_id: Uuid) {
+        ProductMediaEntity::insert(product_media::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(product_id),
+            s3_key: Set(format!("products/{product_id}/media/photo.jpg")),
+            content_type: Set("image/jpeg".to_string()),
+            size_bytes: Set(100),
+            webp_s3_key: Set(None),
+            webp_size_bytes: Set(None),
+            content_hash: Set(None),
+            perceptual_hash: Set(None),
+            created_at: Set(Utc::now()),
+            updated_at: Set(Utc::now()),
+        })
+        .exec_without_returning(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_draft_publishes_once_it_has_images() {
+        let db = crate::db::testing::sqlite().await;
+        let product = draft(&db).await;
+        assert!(!product.is_published);
+        assert_eq!(
+            PublicationStatus::of(&product, Utc::now()),
+            PublicationStatus::Draft
+        );
+        assert!(Product::get_visible(&db, product.id).await.is_err());
+
+        let publish = || Product::publish(&db, product.id, 1, ChangeOrigin::edit(Some("phone-1")));
+        assert_eq!(
+            publish().await.unwrap(),
+            PublishOutcome::MissingMedia {
+                required: 1,
+                found: 0
+            }
+        );
+        add_image(&db, product.id).await;
+        let PublishOutcome::Published(live) = publish().await.unwrap() else {
+            panic!("draft with an image was not published");
+        };
+        assert!(live.is_published);
+        assert_eq!(
+            Product::get_visible(&db, product.id).await.unwrap().id,
+            live.id
+        );
+        assert!(matches!(
+            publish().await.unwrap(),
+            PublishOutcome::AlreadyPublished(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_held_draft_cannot_be_published() {
+        let db = crate::db::testing::sqlite().await;
+        let product = draft(&db).await;
+        crate::db::moderation::ProductModeration::hold_for(
+            &db,
+            product.clone(),
+            serde_json::json!([]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            Product::publish(&db, product.id, 0, ChangeOrigin::edit(None))
+                .await
+                .unwrap(),
+            PublishOutcome::Held
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bad_amounts_are_refused_before_writing() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let create = |price, quantity| {
+            Product::create(
+                &db,
+                store_id,
+                None,
+                "Wax print",
+                None,
+                price,
+                "XAF",
+                quantity,
+                None,
+                None,
+                None,
+                None,
+                None,
+                true,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+        };
+
+        for (price, quantity, problem) in [
+            (-5.0, 3, "price must not be negative"),
+            (f64::NAN, 3, "price must be a finite number"),
+            (f64::INFINITY, 3, "price must be a finite number"),
+            (MAX_PRICE * 10.0, 3, "price must be at most 100000000"),
+            (5000.0, -3, "quantity_available must not be negative"),
+        ] {
+            assert_eq!(create(price, quantity).await.unwrap_err(), problem);
+        }
+        assert_eq!(ProductEntity::find().count(&db).await.unwrap(), 0);
+
+        let product = create(MAX_PRICE, 0).await.unwrap();
+        let update = |price, quantity| {
+            Product::update(
+                &db,
+                product.id,
+                None,
+                "Wax print",
+                None,
+                price,
+                None,
+                quantity,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                serde_json::json!({}),
+                ChangeOrigin::edit(None),
+            )
+        };
+        assert_eq!(
+            update(-1.0, 3).await.unwrap_err(),
+            "price must not be negative"
+        );
+        assert_eq!(
+            update(5000.0, -1).await.unwrap_err(),
+            "quantity_available must not be negative"
+        );
+        let stored = Product::get(&db, product.id).await.unwrap();
+        assert_eq!((stored.price, stored.quantity_available), (MAX_PRICE, 0));
+    }
+
+    #[tokio::test]
+    async fn test_currency_is_checked_kept_and_filtered() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let create = |name, currency| {
+            Product::create(
+                &db,
+                store_id,
+                None,
+                name,
+                None,
+                5000.0,
+                currency,
+                3,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+        };
+
+        for code in ["xaf", "CFA", ""] {
+            assert_eq!(
+                create("Wax print", code).await.unwrap_err(),
+                "currency must be an ISO 4217 code such as XAF, NGN or USD"
+            );
+        }
+        let wax = create("Wax print", "XAF").await.unwrap();
+        let ankara = create("Ankara", "NGN").await.unwrap();
+
+        // Leaving the currency out of an update keeps it
+        Product::update(
+            &db,
+            ankara.id,
+            None,
+            "Ankara",
+            None,
+            6000.0,
+            None,
+            3,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            serde_json::json!({}),
+            ChangeOrigin::edit(None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(Product::get(&db, ankara.id).await.unwrap().currency, "NGN");
+
+        let priced_in = |currency: &'static str| {
+            let db = &db;
+            async move {
+                let filter = PriceFilter {
+                    currency: Some(currency.to_string()),
+                    ..PriceFilter::default()
+                };
+                Product::list_by_store(db, "default", store_id, filter)
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(priced_in("XAF").await, [wax.id]);
+        assert_eq!(priced_in("NGN").await, [ankara.id]);
+        assert!(priced_in("USD").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_a_sku_is_used_once_per_store() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let other_store = crate::db::testing::seed_store(&db, "seller-2").await;
+        let create = |store_id, sku| {
+            Product::create(
+                &db,
+                store_id,
+                sku,
+                "Wax print",
+                None,
+                5000.0,
+                "XAF",
+                3,
+                None,
+                None,
+                None,
+                None,
+                None,
+                true,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+        };
+
+        create(store_id, Some("WAX-1")).await.unwrap();
+        assert_eq!(
+            create(store_id, Some("WAX-1")).await.unwrap_err(),
+            SKU_TAKEN
+        );
+        create(other_store, Some("WAX-1")).await.unwrap();
+        create(store_id, None).await.unwrap();
+        create(store_id, None).await.unwrap();
+        assert_eq!(
+            Product::list_by_store(&db, "default", store_id, PriceFilter::default())
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_pages_are_cut_in_the_database() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        for (name, draft) in [("a", false), ("b", false), ("c", false), ("d", true)] {
+            Product::create(
+                &db,
+                store_id,
+                None,
+                name,
+                None,
+                5000.0,
+                "XAF",
+                3,
+                None,
+                None,
+                None,
+                None,
+                None,
+                draft,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        }
+        let page = |visible_only, page, per_page| {
+            Product::page_by_store(
+                &db,
+                "default",
+                store_id,
+                PriceFilter::default(),
+                visible_only,
+                page,
+                per_page,
+            )
+        };
+
+        let (first, total) = page(true, 1, 2).await.unwrap();
+        let (second, _) = page(true, 2, 2).await.unwrap();
+        assert_eq!((first.len(), second.len(), total), (2, 1, 3));
+        let mut ids: Vec<Uuid> = first.iter().chain(&second).map(|p| p.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        // The owner's view counts the draft too
+        assert_eq!(page(false, 2, 2).await.unwrap().0.len(), 2);
+        assert_eq!(page(false, 1, 20).await.unwrap().1, 4);
+
+        for (number, per_page) in [(3, 2), (u64::MAX, 2), (u64::MAX, u64::MAX)] {
+            let (past_end, total) = page(true, number, per_page).await.unwrap();
+            assert!(past_end.is_empty());
+            assert_eq!(total, 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filtered_list_keeps_to_the_price_range_and_category() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let shoes = Uuid::new_v4();
+        category::Entity::insert(category::ActiveModel {
+            id: Set(shoes),
+            slug: Set("shoes".into()),
+            name: Set("Shoes".into()),
+            created_at: Set(Utc::now()),
+        })
+        .exec_without_returning(&db)
+        .await
+        .unwrap();
+        let listing = |name: &'static str, price: f64, category_id: Option<Uuid>| {
+            let db = &db;
+            async move {
+                Product::create(
+                    db,
+                    store_id,
+                    None,
+                    name,
+                    None,
+                    price,
+                    "XAF",
+                    3,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    category_id,
+                    serde_json::json!({}),
+                )
+                .await
+                .unwrap()
+                .id
+            }
+        };
+        listing("cheap", 1000.0, Some(shoes)).await;
+        let sandals = listing("sandals", 5000.0, Some(shoes)).await;
+        let belt = listing("belt", 6000.0, None).await;
+        listing("boots", 20000.0, Some(shoes)).await;
+
+        let list = |category: Option<&'static str>| {
+            let db = &db;
+            async move {
+                let filter = PriceFilter {
+                    min_price: Some(2000.0),
+                    max_price: Some(10000.0),
+                    sort: ProductSort::PriceAsc,
+                    ..PriceFilter::default()
+                };
+                let (found, total) =
+                    Product::list_filtered(db, "default", store_id, category, filter, 1, 20)
+                        .await
+                        .unwrap();
+                assert_eq!(found.len() as u64, total);
+                found.iter().map(|p| p.id).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(list(None).await, [sandals, belt]);
+        assert_eq!(list(Some("shoes")).await, [sandals]);
+        assert!(list(Some("hats")).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_archived_products_are_listed_only_on_request() {
+        use crate::db::product_counts::ProductCounts;
+
+        let db = crate::db::testing::sqlite().await;
+        let id = crate::db::testing::seed_product(&db, "seller-1").await;
+        let product = Product::get(&db, id).await.unwrap();
+        ProductCounts::record(&db, None, Some(&product))
+            .await
+            .unwrap();
+        let listed = |include_archived: bool| {
+            let db = &db;
+            async move {
+                let filter = PriceFilter {
+                    include_archived,
+                    ..PriceFilter::default()
+                };
+                Product::list_by_store(db, "default", product.store_id, filter)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        let published = || {
+            let db = &db;
+            async move {
+                ProductCounts::for_store(db, product.store_id)
+                    .await
+                    .unwrap()
+                    .published
+            }
+        };
+        assert_eq!(published().await, 1);
+
+        let (archived, _) = Product::delete(&db, id).await.unwrap();
+        assert_eq!(
+            PublicationStatus::of(&archived, Utc::now()),
+            PublicationStatus::Archived
+        );
+        // Archiving again changes nothing
+        let (again, _) = Product::delete(&db, id).await.unwrap();
+        assert_eq!(again.archived_at, archived.archived_at);
+        assert!(Product::get_visible(&db, id).await.is_err());
+        assert_eq!(listed(false).await, 0);
+        assert_eq!(listed(true).await, 1);
+        assert_eq!(published().await, 0);
+
+        let restored = Product::restore(&db, id).await.unwrap();
+        assert_eq!(restored.archived_at, None);
+        assert!(Product::get_visible(&db, id).await.is_ok());
+        assert_eq!(listed(false).await, 1);
+        assert_eq!(published().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_matches_name_or_description_ignoring_case() {
+        let db = crate::db::testing::sqlite().await;
+        let store_id = crate::db::testing::seed_store(&db, "seller-1").await;
+        let other_store = crate::db::testing::seed_store(&db, "seller-2").await;
+        let listing = |store_id, name: &'static str, description: Option<&'static str>, draft| {
+            let db = &db;
+            async move {
+                Product::create(
+                    db,
+                    store_id,
+                    None,
+                    name,
+                    description,
+                    5000.0,
+                    "XAF",
+                    3,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    draft,
+                    None,
+                    None,
+                    serde_json::json!({}),
+                )
+                .await
+                .unwrap()
+                .id
+            }
+        };
+        let wax = listing(store_id, "Wax Print Dress", None, false).await;
+        let kente = listing(store_id, "Kente scarf", Some("Woven like wax cloth"), false).await;
+        let elsewhere = listing(other_store, "WAX apron", None, false).await;
+        listing(store_id, "Wax draft", None, true).await;
+        let percent = listing(store_id, "100% cotton", None, false).await;
+
+        let search = |text: &'static str, store: Option<Uuid>| {
+            let db = &db;
+            async move {
+                let (found, total) = Product::search(
+                    db,
+                    "default",
+                    text,
+                    store,
+                    None,
+                    PriceFilter::default(),
+                    1,
+                    20,
+                )
+                .await
+                .unwrap();
+                let mut ids: Vec<Uuid> = found.iter().map(|p| p.id).collect();
+                ids.sort();
+                assert_eq!(ids.len() as u64, total);
+                ids
+            }
+        };
+        let sorted = |mut ids: Vec<Uuid>| {
+            ids.sort();
+            ids
+        };
+
+        // Drafts stay hidden
+        assert_eq!(
+            search("wax", None).await,
+            sorted(vec![wax, kente, elsewhere])
+        );
+        assert_eq!(
+            search("wAx", Some(store_id)).await,
+            sorted(vec![wax, kente])
+        );
+        assert_eq!(search("woven", None).await, [kente]);
+        // Wildcards typed by the buyer are taken literally
+        assert_eq!(search("0%", None).await, [percent]);
+        assert!(search("%", Some(other_store)).await.is_empty());
+        assert!(search("_", None).await.is_empty());
+        assert!(search("linen", None).await.is_empty());
+        assert!(Product::search(
+            &db,
+            "other",
+            "wax",
+            None,
+            None,
+            PriceFilter::default(),
+            1,
+            20
+        )
+        .await
+        .unwrap()
+        .0
+        .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_return_policy_none() {
+        let (policy, source) = resolve_return_policy(None, Some(" "));
+        assert_eq!(policy, None);
+        assert_eq!(source, ReturnPolicySource::None);
+        assert_eq!(source.as_str(), "none");
+    }
+}
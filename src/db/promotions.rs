@@ -0,0 +1,242 @@
+use crate::db::stores::paused_condition;
+use crate::entity::store::{Entity as StoreEntity, Model as StoreModel};
+use crate::entity::store_promotion::{
+    self, ActiveModel as PromotionActiveModel, Entity as PromotionEntity, Model as PromotionModel,
+};
+use crate::tenant::tenant_condition;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, Select, Set,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// A promotion runs from `starts_at` (inclusive) to `ends_at` (exclusive)
+pub fn is_active(promotion: &PromotionModel, now: DateTime<Utc>) -> bool {
+    promotion.starts_at <= now && now < promotion.ends_at
+}
+
+/// Bookings of `position` sharing any instant with the window; back-to-back
+/// windows don't overlap
+fn overlapping_query(
+    position: i32,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    exclude_id: Option<Uuid>,
+) -> Select<PromotionEntity> {
+    let mut query = PromotionEntity::find()
+        .filter(store_promotion::Column::Position.eq(position))
+        .filter(store_promotion::Column::StartsAt.lt(ends_at))
+        .filter(store_promotion::Column::EndsAt.gt(starts_at));
+    if let Some(id) = exclude_id {
+        query = query.filter(store_promotion::Column::Id.ne(id));
+    }
+    query
+}
+
+/// SQL form of [`is_active`]
+fn active_condition(now: DateTime<Utc>) -> Condition {
+    Condition::all()
+        .add(store_promotion::Column::StartsAt.lte(now))
+        .add(store_promotion::Column::EndsAt.gt(now))
+}
+
+pub struct Promotion;
+
+impl Promotion {
+    pub async fn create(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        position: i32,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        created_by: &str,
+    ) -> Result<PromotionModel, String> {
+        let promotion = PromotionActiveModel {
+            id: Set(Uuid::new_v4()),
+            store_id: Set(store_id),
+            position: Set(position),
+            starts_at: Set(starts_at),
+            ends_at: Set(ends_at),
+            created_by: Set(created_by.to_owned()),
+            expired: Set(false),
+            created_at: Set(Utc::now()),
+        };
+        let res = promotion.insert(db).await.map_err(|e| {
+            error!("Failed to create promotion for store {}: {:?}", store_id, e);
+            "Failed to create promotion. Please try again later.".to_string()
+        })?;
+        debug!("Promotion created: {:?}", res);
+        Ok(res)
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<PromotionModel>, String> {
+        PromotionEntity::find_by_id(id).one(db).await.map_err(|e| {
+            error!("Failed to fetch promotion {}: {:?}", id, e);
+            "Failed to fetch promotion. Please try again later.".to_string()
+        })
+    }
+
+    /// All promotions, upcoming and past, by slot then start time
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<PromotionModel>, String> {
+        PromotionEntity::find()
+            .order_by_asc(store_promotion::Column::Position)
+            .order_by_asc(store_promotion::Column::StartsAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list promotions: {:?}", e);
+                "Failed to list promotions. Please try again later.".to_string()
+            })
+    }
+
+    /// A promotion already booked for `position` during the window, if any
+    pub async fn find_overlapping(
+        db: &DatabaseConnection,
+        position: i32,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        exclude_id: Option<Uuid>,
+    ) -> Result<Option<PromotionModel>, String> {
+        overlapping_query(position, starts_at, ends_at, exclude_id)
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to check promotion overlap: {:?}", e);
+                "Failed to check promotion slot. Please try again later.".to_string()
+            })
+    }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        promotion: PromotionModel,
+        position: i32,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+    ) -> Result<PromotionModel, String> {
+        let id = promotion.id;
+        let mut active: PromotionActiveModel = promotion.into();
+        active.position = Set(position);
+        active.starts_at = Set(starts_at);
+        active.ends_at = Set(ends_at);
+        // Extending a finished promotion brings it back
+        active.expired = Set(ends_at <= Utc::now());
+        let res = active.update(db).await.map_err(|e| {
+            error!("Failed to update promotion {}: {:?}", id, e);
+            "Failed to update promotion. Please try again later.".to_string()
+        })?;
+        debug!("Promotion updated: {:?}", res);
+        Ok(res)
+    }
+
+    /// Returns false when there was no such promotion
+    pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<bool, String> {
+        let res = PromotionEntity::delete_by_id(id)
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to delete promotion {}: {:?}", id, e);
+                "Failed to delete promotion. Please try again later.".to_string()
+            })?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// Stores of `tenant_id` with a running promotion, in slot order
+    pub async fn featured_stores(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(PromotionModel, StoreModel)>, String> {
+        let rows = PromotionEntity::find()
+            .find_also_related(StoreEntity)
+            .filter(tenant_condition::<StoreEntity>(tenant_id))
+            .filter(active_condition(now))
+            // A paused store keeps its slot but is not advertised meanwhile
+            .filter(paused_condition(now).not())
+            .order_by_asc(store_promotion::Column::Position)
+            .order_by_asc(store_promotion::Column::StartsAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list featured stores: {:?}", e);
+                "Failed to list featured stores. Please try again later.".to_string()
+            })?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(promotion, store)| store.map(|store| (promotion, store)))
+            .collect())
+    }
+
+    /// Flag promotions whose window has closed, returning the ones flagged now
+    pub async fn expire_ended(
+        db: &DatabaseConnection,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<PromotionModel>, String> {
+        PromotionEntity::update_many()
+            .col_expr(store_promotion::Column::Expired, Expr::value(true))
+            .filter(store_promotion::Column::Expired.eq(false))
+            .filter(store_promotion::Column::EndsAt.lte(now))
+            .exec_with_returning(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to expire promotions: {:?}", e);
+                "Failed to expire promotions.".to_string()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn promotion(starts_at: DateTime<Utc>, ends_at: DateTime<Utc>) -> PromotionModel {
+        PromotionModel {
+            id: Uuid::new_v4(),
+            store_id: Uuid::new_v4(),
+            position: 1,
+            starts_at,
+            ends_at,
+            created_by: "admin-device".to_string(),
+            expired: false,
+            created_at: starts_at,
+        }
+    }
+
+    #[test]
+    fn test_active_window_boundaries() {
+        let start = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap();
+        let end = start + Duration::days(7);
+        let promo = promotion(start, end);
+        let tick = Duration::microseconds(1);
+
+        assert!(!is_active(&promo, start - tick));
+        assert!(is_active(&promo, start));
+        assert!(is_active(&promo, end - tick));
+        assert!(!is_active(&promo, end));
+    }
+
+    #[test]
+    fn test_active_condition_matches_half_open_window() {
+        let sql = PromotionEntity::find()
+            .filter(active_condition(Utc::now()))
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#""starts_at" <="#), "{sql}");
+        assert!(sql.contains(r#""ends_at" >"#), "{sql}");
+    }
+
+    #[test]
+    fn test_overlap_query_allows_back_to_back_bookings() {
+        let start = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap();
+        let sql = overlapping_query(2, start, start + Duration::days(1), None)
+            .build(DbBackend::Postgres)
+            .to_string();
+        // Strict comparisons: a booking ending exactly at our start is not a clash
+        assert!(sql.contains(r#""starts_at" < '"#), "{sql}");
+        assert!(sql.contains(r#""ends_at" > '"#), "{sql}");
+        assert!(sql.contains(r#""position" = 2"#), "{sql}");
+    }
+}
@@ -0,0 +1,178 @@
+//! Public marketplace figures, computed off the request path; see
+//! `crate::public_stats`.
+
+use crate::db::products::visible_condition;
+use crate::entity::public_stat::{
+    self, ActiveModel as PublicStatActiveModel, Entity as PublicStatEntity,
+    Model as PublicStatModel,
+};
+use crate::entity::{product, store};
+use crate::public_stats::{public_figures, PublicFigures, PublicStatsRules};
+use crate::tenant::ForTenant;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::OnConflict, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QuerySelect, Set,
+};
+use tracing::error;
+
+pub struct PublicStats;
+
+impl PublicStats {
+    /// `tenant_id`'s figures from its visible products and its stores
+    pub async fn compute(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        rules: &PublicStatsRules,
+        now: DateTime<Utc>,
+    ) -> Result<PublicFigures, String> {
+        let failed = |e| {
+            error!("Failed to compute public stats of {}: {:?}", tenant_id, e);
+            "Failed to compute public stats.".to_string()
+        };
+        let products = product::Entity::find()
+            .for_tenant(tenant_id)
+            .filter(visible_condition(now))
+            .count(db)
+            .await
+            .map_err(failed)?;
+        let locations: Vec<Option<String>> = store::Entity::find()
+            .for_tenant(tenant_id)
+            .select_only()
+            .column(store::Column::Location)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(failed)?;
+        Ok(public_figures(
+            products,
+            locations.len() as u64,
+            locations.iter().flatten().map(String::as_str),
+            rules,
+        ))
+    }
+
+    /// Recompute the figures of every tenant with a store. Returns how many
+    /// tenants were refreshed.
+    pub async fn refresh_all(
+        db: &DatabaseConnection,
+        rules: &PublicStatsRules,
+        now: DateTime<Utc>,
+    ) -> Result<usize, String> {
+        let tenants: Vec<String> = store::Entity::find()
+            .select_only()
+            .column(store::Column::TenantId)
+            .distinct()
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list tenants for public stats: {:?}", e);
+                "Failed to compute public stats.".to_string()
+            })?;
+        for tenant_id in &tenants {
+            let figures = Self::compute(db, tenant_id, rules, now).await?;
+            Self::save(db, tenant_id, &figures, now).await?;
+        }
+        Ok(tenants.len())
+    }
+
+    async fn save(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        figures: &PublicFigures,
+        now: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let row = PublicStatActiveModel {
+            tenant_id: Set(tenant_id.to_owned()),
+            products: Set(figures.products as i64),
+            stores: Set(figures.stores as i64),
+            city_count: Set(figures.city_count as i64),
+            cities: Set(serde_json::json!(figures.cities)),
+            computed_at: Set(now),
+        };
+        PublicStatEntity::insert(row)
+            .on_conflict(
+                OnConflict::column(public_stat::Column::TenantId)
+                    .update_columns([
+                        public_stat::Column::Products,
+                        public_stat::Column::Stores,
+                        public_stat::Column::CityCount,
+                        public_stat::Column::Cities,
+                        public_stat::Column::ComputedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to save public stats of {}: {:?}", tenant_id, e);
+                "Failed to save public stats.".to_string()
+            })?;
+        Ok(())
+    }
+
+    /// The last figures computed for `tenant_id`, if the job has run
+    pub async fn get(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+    ) -> Result<Option<PublicStatModel>, String> {
+        PublicStatEntity::find_by_id(tenant_id.to_owned())
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to read public stats of {}: {:?}", tenant_id, e);
+                "Failed to read public stats.".to_string()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{seed_product, seed_store, sqlite};
+    use sea_orm::{sea_query::Expr, ColumnTrait};
+
+    #[tokio::test]
+    async fn test_refresh_stores_coarsened_figures_per_tenant() {
+        let db = sqlite().await;
+        for _ in 0..3 {
+            seed_product(&db, "seller-1").await;
+        }
+        let other = seed_store(&db, "seller-2").await;
+        store::Entity::update_many()
+            .col_expr(store::Column::Location, Expr::value("Douala, Cameroon"))
+            .exec(&db)
+            .await
+            .unwrap();
+        store::Entity::update_many()
+            .col_expr(store::Column::TenantId, Expr::value("other"))
+            .filter(store::Column::Id.eq(other))
+            .exec(&db)
+            .await
+            .unwrap();
+        let rules = PublicStatsRules {
+            granularity: 2,
+            min_stores_per_city: 3,
+        };
+        let now = Utc::now();
+
+        assert!(PublicStats::get(&db, "default").await.unwrap().is_none());
+        assert_eq!(PublicStats::refresh_all(&db, &rules, now).await.unwrap(), 2);
+        let stats = PublicStats::get(&db, "default").await.unwrap().unwrap();
+        assert_eq!((stats.products, stats.stores), (2, 2));
+        assert_eq!(stats.city_count, 1);
+        assert_eq!(stats.cities, serde_json::json!(["Douala"]));
+        assert_eq!(stats.computed_at, now);
+
+        // The other tenant's single store is neither counted nor named
+        let stats = PublicStats::get(&db, "other").await.unwrap().unwrap();
+        assert_eq!((stats.products, stats.stores, stats.city_count), (0, 0, 0));
+
+        // A second run overwrites the first
+        let later = now + chrono::Duration::hours(1);
+        PublicStats::refresh_all(&db, &rules, later).await.unwrap();
+        let stats = PublicStats::get(&db, "default").await.unwrap().unwrap();
+        assert_eq!(stats.computed_at, later);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::db::stores::AutoResponseTrigger;
+use crate::entity::product_question::{
+    self, ActiveModel as QuestionActiveModel, Entity as QuestionEntity, Model as QuestionModel,
+};
+use crate::entity::{product, store};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+    Select, Set,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Which of a product's questions to list
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuestionFilter {
+    pub answered_only: bool,
+    /// Include questions held by the term filter; the store owner's view
+    pub include_held: bool,
+}
+
+/// A product's questions, newest first
+fn list_query(product_id: Uuid, filter: QuestionFilter) -> Select<QuestionEntity> {
+    let mut query =
+        QuestionEntity::find().filter(product_question::Column::ProductId.eq(product_id));
+    if filter.answered_only {
+        query = query.filter(product_question::Column::Answer.is_not_null());
+    }
+    if !filter.include_held {
+        query = query.filter(product_question::Column::IsHeld.eq(false));
+    }
+    query
+        .order_by_desc(product_question::Column::CreatedAt)
+        .order_by_desc(product_question::Column::Id)
+}
+
+/// Questions of one store that went unanswered past the reminder threshold
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct UnansweredQuestions {
+    pub store_id: Uuid,
+    pub count: i64,
+}
+
+/// An unanswered question due a `delayed_reply` auto-response
+#[derive(Debug, FromQueryResult)]
+struct OverdueQuestion {
+    id: Uuid,
+    message: Option<String>,
+}
+
+pub struct ProductQuestion;
+
+impl ProductQuestion {
+    pub async fn create(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        asked_by: &str,
+        question: &str,
+        is_held: bool,
+    ) -> Result<QuestionModel, String> {
+        let model = QuestionActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(product_id),
+            asked_by: Set(asked_by.to_owned()),
+            question: Set(question.to_owned()),
+            answer: Set(None),
+            answered_by: Set(None),
+            is_held: Set(is_held),
+            created_at: Set(Utc::now()),
+            answered_at: Set(None),
+            auto_reply: Set(None),
+            auto_replied_at: Set(None),
+        };
+        let res = model.insert(db).await.map_err(|e| {
+            error!("Failed to save question on product {}: {:?}", product_id, e);
+            "Failed to save question. Please try again later.".to_string()
+        })?;
+        debug!("Question created: {:?}", res);
+        Ok(res)
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<QuestionModel>, String> {
+        QuestionEntity::find_by_id(id).one(db).await.map_err(|e| {
+            error!("Failed to fetch question {}: {:?}", id, e);
+            "Failed to fetch question. Please try again later.".to_string()
+        })
+    }
+
+    /// Set or replace the answer
+    pub async fn answer(
+        db: &DatabaseConnection,
+        question: QuestionModel,
+        answer: &str,
+        answered_by: &str,
+    ) -> Result<QuestionModel, String> {
+        let id = question.id;
+        let mut active: QuestionActiveModel = question.into();
+        active.answer = Set(Some(answer.to_owned()));
+        active.answered_by = Set(Some(answered_by.to_owned()));
+        active.answered_at = Set(Some(Utc::now()));
+        active.update(db).await.map_err(|e| {
+            error!("Failed to answer question {}: {:?}", id, e);
+            "Failed to save answer. Please try again later.".to_string()
+        })
+    }
+
+    /// Record the store's automatic reply to `question_id`, unless it already
+    /// has one or the seller answered first; returns whether it was recorded.
+    /// The reply leaves `answer` alone, so the question still counts as
+    /// unanswered.
+    pub async fn record_auto_reply(
+        db: &DatabaseConnection,
+        question_id: Uuid,
+        message: &str,
+        now: DateTime<Utc>,
+    ) -> Result<bool, String> {
+        let res = QuestionEntity::update_many()
+            .col_expr(product_question::Column::AutoReply, Expr::value(message))
+            .col_expr(product_question::Column::AutoRepliedAt, Expr::value(now))
+            .filter(product_question::Column::Id.eq(question_id))
+            .filter(product_question::Column::AutoRepliedAt.is_null())
+            .filter(product_question::Column::Answer.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to auto-reply to question {}: {:?}", question_id, e);
+                "Failed to save automatic reply. Please try again later.".to_string()
+            })?;
+        Ok(res.rows_affected == 1)
+    }
+
+    /// Auto-reply to every visible question asked before `asked_before` and
+    /// still unanswered, on stores whose auto-response is set to
+    /// `delayed_reply`; returns the questions replied to. Each question is
+    /// replied to once, however often this runs and on however many
+    /// replicas.
+    pub async fn send_delayed_auto_replies(
+        db: &DatabaseConnection,
+        asked_before: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<QuestionModel>, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to send delayed auto-replies: {:?}", e);
+            "Failed to send automatic replies. Please try again later.".to_string()
+        };
+        let overdue = QuestionEntity::find()
+            .select_only()
+            .column(product_question::Column::Id)
+            .column_as(store::Column::AutoResponseMessage, "message")
+            .join(
+                JoinType::InnerJoin,
+                product_question::Relation::Product.def(),
+            )
+            .join(JoinType::InnerJoin, product::Relation::Store.def())
+            .filter(product_question::Column::Answer.is_null())
+            .filter(product_question::Column::AutoRepliedAt.is_null())
+            .filter(product_question::Column::IsHeld.eq(false))
+            .filter(product_question::Column::CreatedAt.lt(asked_before))
+            .filter(store::Column::AutoResponseEnabled.eq(true))
+            .filter(
+                store::Column::AutoResponseTrigger.eq(AutoResponseTrigger::DelayedReply.as_str()),
+            )
+            .into_model::<OverdueQuestion>()
+            .all(db)
+            .await
+            .map_err(fail)?;
+        let mut replied = Vec::new();
+        for question in overdue {
+            let Some(message) = question.message.filter(|m| !m.trim().is_empty()) else {
+                continue;
+            };
+            if Self::record_auto_reply(db, question.id, &message, now).await? {
+                replied.push(question.id);
+            }
+        }
+        if replied.is_empty() {
+            return Ok(Vec::new());
+        }
+        QuestionEntity::find()
+            .filter(product_question::Column::Id.is_in(replied))
+            .all(db)
+            .await
+            .map_err(fail)
+    }
+
+    /// One page of a product's questions and how many there are in all;
+    /// `page` starts at 1
+    pub async fn list(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        filter: QuestionFilter,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<QuestionModel>, u64), String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!(
+                "Failed to list questions on product {}: {:?}",
+                product_id, e
+            );
+            "Failed to list questions. Please try again later.".to_string()
+        };
+        let paginator = list_query(product_id, filter).paginate(db, per_page);
+        let total = paginator.num_items().await.map_err(fail)?;
+        let questions = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(fail)?;
+        Ok((questions, total))
+    }
+
+    /// Per store, the visible questions that crossed `asked_before` within
+    /// `[since, asked_before)` without an answer, so each one is reminded once
+    pub async fn unanswered_between(
+        db: &DatabaseConnection,
+        since: DateTime<Utc>,
+        asked_before: DateTime<Utc>,
+    ) -> Result<Vec<UnansweredQuestions>, String> {
+        QuestionEntity::find()
+            .select_only()
+            .column_as(product::Column::StoreId, "store_id")
+            .column_as(product_question::Column::Id.count(), "count")
+            .join(
+                JoinType::InnerJoin,
+                product_question::Relation::Product.def(),
+            )
+            .filter(product_question::Column::Answer.is_null())
+            .filter(product_question::Column::IsHeld.eq(false))
+            .filter(product_question::Column::CreatedAt.gte(since))
+            .filter(product_question::Column::CreatedAt.lt(asked_before))
+            .group_by(product::Column::StoreId)
+            .into_model::<UnansweredQuestions>()
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to find unanswered questions: {:?}", e);
+                "Failed to find unanswered questions. Please try again later.".to_string()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::stores::{AutoResponse, Store, AUTO_REPLY_DELAY_HOURS};
+    use crate::db::testing::{seed_product, sqlite};
+    use chrono::Duration;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn sql(filter: QuestionFilter) -> String {
+        list_query(Uuid::nil(), filter)
+            .build(DbBackend::Postgres)
+            .to_string()
+    }
+
+    #[test]
+    fn test_answered_only_filter() {
+        let all = sql(QuestionFilter::default());
+        assert!(!all.contains(r#""answer" IS NOT NULL"#), "{all}");
+        assert!(all.contains(r#""is_held" = FALSE"#), "{all}");
+
+        let answered = sql(QuestionFilter {
+            answered_only: true,
+            include_held: false,
+        });
+        assert!(answered.contains(r#""answer" IS NOT NULL"#), "{answered}");
+    }
+
+    #[test]
+    fn test_owner_view_includes_held_questions() {
+        let owner = sql(QuestionFilter {
+            answered_only: false,
+            include_held: true,
+        });
+        assert!(!owner.contains(r#""is_held" = FALSE"#), "{owner}");
+    }
+
+    /// A store set to reply to questions left waiting, and one of its products
+    async fn delayed_reply_product(db: &DatabaseConnection) -> Uuid {
+        let product_id = seed_product(db, "seller-1").await;
+        let store_id = product::Entity::find_by_id(product_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .store_id;
+        let store = Store::get(db, store_id).await.unwrap();
+        let auto_response = AutoResponse {
+            enabled: true,
+            message: Some("Back from the market soon, thanks for waiting!".to_string()),
+            trigger: Some(AutoResponseTrigger::DelayedReply),
+        };
+        Store::set_auto_response(db, store, &auto_response)
+            .await
+            .unwrap();
+        product_id
+    }
+
+    async fn asked_at(db: &DatabaseConnection, product_id: Uuid, at: DateTime<Utc>) -> Uuid {
+        let question = ProductQuestion::create(db, product_id, "buyer-1", "In blue?", false)
+            .await
+            .unwrap();
+        QuestionEntity::update_many()
+            .col_expr(product_question::Column::CreatedAt, Expr::value(at))
+            .filter(product_question::Column::Id.eq(question.id))
+            .exec(db)
+            .await
+            .unwrap();
+        question.id
+    }
+
+    #[tokio::test]
+    async fn test_delayed_auto_replies_go_out_once() {
+        let db = sqlite().await;
+        let product_id = delayed_reply_product(&db).await;
+        let now = Utc::now();
+        let due = now - Duration::hours(AUTO_REPLY_DELAY_HOURS);
+        let waiting = asked_at(&db, product_id, due - Duration::hours(1)).await;
+        let recent = asked_at(&db, product_id, due + Duration::hours(1)).await;
+        let answered = asked_at(&db, product_id, due - Duration::hours(2)).await;
+        let question = ProductQuestion::get(&db, answered).await.unwrap().unwrap();
+        ProductQuestion::answer(&db, question, "Yes", "seller-1")
+            .await
+            .unwrap();
+
+        let replied = ProductQuestion::send_delayed_auto_replies(&db, due, now)
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = replied.iter().map(|q| q.id).collect();
+        assert_eq!(ids, [waiting]);
+        assert_eq!(
+            replied[0].auto_reply.as_deref(),
+            Some("Back from the market soon, thanks for waiting!")
+        );
+        assert_eq!(replied[0].answer, None);
+        assert_eq!(
+            ProductQuestion::send_delayed_auto_replies(&db, due, now)
+                .await
+                .unwrap(),
+            []
+        );
+
+        // An automatic reply is not an answer: the seller is still reminded
+        let unanswered = ProductQuestion::unanswered_between(&db, due - Duration::days(1), due)
+            .await
+            .unwrap();
+        assert_eq!(unanswered[0].count, 1);
+
+        // Nor does a later run reply to the recent question on a store that
+        // no longer auto-responds
+        let store_id = product::Entity::find_by_id(product_id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap()
+            .store_id;
+        let store = Store::get(&db, store_id).await.unwrap();
+        let off = AutoResponse {
+            enabled: false,
+            ..AutoResponse::of(&store)
+        };
+        Store::set_auto_response(&db, store, &off).await.unwrap();
+        let later = now + Duration::hours(2);
+        let replied = ProductQuestion::send_delayed_auto_replies(
+            &db,
+            later - Duration::hours(AUTO_REPLY_DELAY_HOURS),
+            later,
+        )
+        .await
+        .unwrap();
+        assert_eq!(replied, []);
+        let recent = ProductQuestion::get(&db, recent).await.unwrap().unwrap();
+        assert_eq!(recent.auto_reply, None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Referral links one store shares to send buyers to another: the visits
+//! each link brings, one per viewer per day, and the orders those viewers
+//! place with the target store within the attribution window.
+
+use crate::db::retry_on_unique;
+use crate::entity::referral_click::{self, ActiveModel as ClickActiveModel, Entity as ClickEntity};
+use crate::entity::referral_link::{
+    self, ActiveModel as LinkActiveModel, Entity as LinkEntity, Model as LinkModel,
+};
+use crate::entity::store::Entity as StoreEntity;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::Serialize;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Characters of a link code; no 0/O or 1/l/I to misread when typed
+const CODE_ALPHABET: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+pub const CODE_LEN: usize = 8;
+/// Fresh codes tried before giving up on a collision
+const CODE_ATTEMPTS: u32 = 5;
+/// Links one viewer may be counted for in a UTC day, so a script following
+/// every link can't inflate them all
+pub const MAX_CLICKS_PER_VIEWER_PER_DAY: u64 = 50;
+
+/// What a store's referral links have brought in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ReferralTotals {
+    pub links: i64,
+    /// Viewer-days across the store's links
+    pub referred_visits: i64,
+    /// Orders attributed to the store's links
+    pub referred_sales: i64,
+}
+
+impl ReferralTotals {
+    pub fn of(links: &[LinkModel]) -> Self {
+        Self {
+            links: links.len() as i64,
+            referred_visits: links.iter().map(|link| link.click_count).sum(),
+            referred_sales: links.iter().map(|link| link.sale_count).sum(),
+        }
+    }
+}
+
+fn generate_code() -> String {
+    let mut rng = rand::rng();
+    (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[rng.random_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+pub struct ReferralLink;
+
+impl ReferralLink {
+    /// A new link crediting `store_id` for sending buyers to
+    /// `target_store_id`, or to one of its products
+    pub async fn create(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        target_store_id: Uuid,
+        target_product_id: Option<Uuid>,
+        now: DateTime<Utc>,
+    ) -> Result<LinkModel, String> {
+        let link = retry_on_unique(CODE_ATTEMPTS, |_| {
+            LinkActiveModel {
+                id: Set(Uuid::new_v4()),
+                code: Set(generate_code()),
+                store_id: Set(store_id),
+                target_store_id: Set(target_store_id),
+                target_product_id: Set(target_product_id),
+                click_count: Set(0),
+                sale_count: Set(0),
+                last_clicked_at: Set(None),
+                created_at: Set(now),
+            }
+            .insert(db)
+        })
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to create referral link for store {}: {:?}",
+                store_id, e
+            );
+            "Failed to create referral link. Please try again later.".to_string()
+        })?;
+        debug!("Referral link {} created for store {}", link.code, store_id);
+        Ok(link)
+    }
+
+    pub async fn find_by_code(
+        db: &DatabaseConnection,
+        code: &str,
+    ) -> Result<Option<LinkModel>, String> {
+        LinkEntity::find()
+            .filter(referral_link::Column::Code.eq(code))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up referral link {}: {:?}", code, e);
+                "Failed to look up referral link.".to_string()
+            })
+    }
+
+    /// A store's links, newest first
+    pub async fn list_by_store(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+    ) -> Result<Vec<LinkModel>, String> {
+        LinkEntity::find()
+            .filter(referral_link::Column::StoreId.eq(store_id))
+            .order_by_desc(referral_link::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to list referral links of store {}: {:?}",
+                    store_id, e
+                );
+                "Failed to list referral links. Please try again later.".to_string()
+            })
+    }
+
+    /// What `store_id`'s links have brought in
+    pub async fn totals(db: &DatabaseConnection, store_id: Uuid) -> Result<ReferralTotals, String> {
+        Ok(ReferralTotals::of(
+            &Self::list_by_store(db, store_id).await?,
+        ))
+    }
+
+    /// Note that `subject` followed `link`. Only their first visit of a UTC
+    /// day counts, up to [`MAX_CLICKS_PER_VIEWER_PER_DAY`] links a day, and
+    /// never the referring store's own owner; returns whether this one did.
+    pub async fn record_click(
+        db: &DatabaseConnection,
+        link: &LinkModel,
+        subject: &str,
+        now: DateTime<Utc>,
+    ) -> Result<bool, String> {
+        let failed = |e| {
+            error!(
+                "Failed to record click on referral link {}: {:?}",
+                link.code, e
+            );
+            "Failed to record referral click.".to_string()
+        };
+        let referrer = StoreEntity::find_by_id(link.store_id)
+            .one(db)
+            .await
+            .map_err(failed)?;
+        if referrer.is_some_and(|store| store.owner_device_id.as_deref() == Some(subject)) {
+            return Ok(false);
+        }
+        let today = now.date_naive();
+        let counted_today = ClickEntity::find()
+            .filter(referral_click::Column::Subject.eq(subject))
+            .filter(referral_click::Column::ClickedOn.eq(today))
+            .count(db)
+            .await
+            .map_err(failed)?;
+        if counted_today >= MAX_CLICKS_PER_VIEWER_PER_DAY {
+            return Ok(false);
+        }
+
+        let click = ClickActiveModel {
+            id: Set(Uuid::new_v4()),
+            link_id: Set(link.id),
+            subject: Set(subject.to_owned()),
+            clicked_on: Set(today),
+            created_at: Set(now),
+        };
+        let inserted = ClickEntity::insert(click)
+            .on_conflict(
+                OnConflict::columns([
+                    referral_click::Column::LinkId,
+                    referral_click::Column::Subject,
+                    referral_click::Column::ClickedOn,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(failed)?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+        LinkEntity::update_many()
+            .col_expr(
+                referral_link::Column::ClickCount,
+                Expr::col(referral_link::Column::ClickCount).add(1),
+            )
+            .col_expr(referral_link::Column::LastClickedAt, Expr::value(now))
+            .filter(referral_link::Column::Id.eq(link.id))
+            .exec(db)
+            .await
+            .map_err(failed)?;
+        Ok(true)
+    }
+
+    /// Credit an order `subject` placed with `store_id` to the link they
+    /// last followed there within `window`, returning that link for the
+    /// order to keep. Links only ever point at another seller's store, so
+    /// no seller is credited for their own sales.
+    // Order creation attributes its orders through this
+    #[allow(dead_code)]
+    pub async fn attribute_order(
+        db: &DatabaseConnection,
+        subject: &str,
+        store_id: Uuid,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Uuid>, String> {
+        let failed = |e| {
+            error!("Failed to attribute order at store {}: {:?}", store_id, e);
+            "Failed to attribute order to a referral.".to_string()
+        };
+        let links: Vec<Uuid> = LinkEntity::find()
+            .filter(referral_link::Column::TargetStoreId.eq(store_id))
+            .filter(referral_link::Column::StoreId.ne(store_id))
+            .all(db)
+            .await
+            .map_err(failed)?
+            .into_iter()
+            .map(|link| link.id)
+            .collect();
+        if links.is_empty() {
+            return Ok(None);
+        }
+        let Some(click) = ClickEntity::find()
+            .filter(referral_click::Column::LinkId.is_in(links))
+            .filter(referral_click::Column::Subject.eq(subject))
+            .filter(referral_click::Column::CreatedAt.gte(now - window))
+            .filter(referral_click::Column::CreatedAt.lte(now))
+            .order_by_desc(referral_click::Column::CreatedAt)
+            .one(db)
+            .await
+            .map_err(failed)?
+        else {
+            return Ok(None);
+        };
+        LinkEntity::update_many()
+            .col_expr(
+                referral_link::Column::SaleCount,
+                Expr::col(referral_link::Column::SaleCount).add(1),
+            )
+            .filter(referral_link::Column::Id.eq(click.link_id))
+            .exec(db)
+            .await
+            .map_err(failed)?;
+        debug!(
+            "Order at store {} attributed to link {}",
+            store_id, click.link_id
+        );
+        Ok(Some(click.link_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::{seed_store, sqlite};
+
+    async fn link(db: &DatabaseConnection, code: &str) -> LinkModel {
+        ReferralLink::find_by_code(db, code).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_clicks_count_once_per_viewer_and_day() {
+        let db = sqlite().await;
+        let referrer = seed_store(&db, "seller-1").await;
+        let target = seed_store(&db, "seller-2").await;
+        let now = Utc::now();
+        let created = ReferralLink::create(&db, referrer, target, None, now)
+            .await
+            .unwrap();
+        assert_eq!(created.code.len(), CODE_LEN);
+        let click = |subject, at| {
+            let db = db.clone();
+            let created = created.clone();
+            async move {
+                ReferralLink::record_click(&db, &created, subject, at)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert!(click("buyer-1", now).await);
+        assert!(!click("buyer-1", now).await);
+        assert!(click("anon:session-1", now).await);
+        assert!(click("buyer-1", now + Duration::days(1)).await);
+        // The referring store's owner following their own link
+        assert!(!click("seller-1", now).await);
+
+        let counted = link(&db, &created.code).await;
+        assert_eq!(counted.click_count, 3);
+        assert_eq!(counted.last_clicked_at, Some(now + Duration::days(1)));
+        assert_eq!(
+            ReferralLink::totals(&db, referrer).await.unwrap(),
+            ReferralTotals {
+                links: 1,
+                referred_visits: 3,
+                referred_sales: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_viewers_clicks_are_capped_per_day() {
+        let db = sqlite().await;
+        let referrer = seed_store(&db, "seller-1").await;
+        let target = seed_store(&db, "seller-2").await;
+        let now = Utc::now();
+        for _ in 0..MAX_CLICKS_PER_VIEWER_PER_DAY {
+            let link = ReferralLink::create(&db, referrer, target, None, now)
+                .await
+                .unwrap();
+            assert!(ReferralLink::record_click(&db, &link, "bot", now)
+                .await
+                .unwrap());
+        }
+        let link = ReferralLink::create(&db, referrer, target, None, now)
+            .await
+            .unwrap();
+        assert!(!ReferralLink::record_click(&db, &link, "bot", now)
+            .await
+            .unwrap());
+        assert!(ReferralLink::record_click(&db, &link, "buyer-1", now)
+            .await
+            .unwrap());
+        assert!(
+            ReferralLink::record_click(&db, &link, "bot", now + Duration::days(1))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_credited_to_the_last_link_within_the_window() {
+        let db = sqlite().await;
+        let first = seed_store(&db, "seller-1").await;
+        let second = seed_store(&db, "seller-2").await;
+        let target = seed_store(&db, "seller-3").await;
+        let window = Duration::days(7);
+        let start = Utc::now() - Duration::days(30);
+        let older = ReferralLink::create(&db, first, target, None, start)
+            .await
+            .unwrap();
+        let newer = ReferralLink::create(&db, second, target, None, start)
+            .await
+            .unwrap();
+        let attribute = |subject, store_id, at| {
+            ReferralLink::attribute_order(&db, subject, store_id, window, at)
+        };
+
+        ReferralLink::record_click(&db, &older, "buyer-1", start)
+            .await
+            .unwrap();
+        ReferralLink::record_click(&db, &newer, "buyer-1", start + Duration::days(2))
+            .await
+            .unwrap();
+        // Last click wins while in the window
+        let at = start + Duration::days(8);
+        assert_eq!(
+            attribute("buyer-1", target, at).await.unwrap(),
+            Some(newer.id)
+        );
+        // Another viewer, or another store, isn't credited
+        assert_eq!(attribute("buyer-2", target, at).await.unwrap(), None);
+        assert_eq!(attribute("buyer-1", first, at).await.unwrap(), None);
+        // Past the window nothing is
+        let late = start + Duration::days(10);
+        assert_eq!(attribute("buyer-1", target, late).await.unwrap(), None);
+
+        assert_eq!(link(&db, &newer.code).await.sale_count, 1);
+        assert_eq!(link(&db, &older.code).await.sale_count, 0);
+        assert_eq!(
+            ReferralLink::totals(&db, second).await.unwrap(),
+            ReferralTotals {
+                links: 1,
+                referred_visits: 1,
+                referred_sales: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! `report_jobs` rows and the queries behind each report; the runner that
+//! drives them lives in `crate::reports`.
+
+use crate::entity::product::{self, Entity as ProductEntity};
+use crate::entity::product_price_history::{self, Entity as PriceHistoryEntity};
+use crate::entity::report_job::{self, ActiveModel, Entity as ReportJobEntity, Model};
+use crate::reports::{ReportFormat, ReportStatus, ReportTable, ReportType};
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// What a seller asked for
+#[derive(Debug, Clone)]
+pub struct CreateReport {
+    pub store_id: Uuid,
+    pub requested_by: String,
+    pub report_type: ReportType,
+    pub format: ReportFormat,
+    pub range_start: DateTime<Utc>,
+    pub range_end: DateTime<Utc>,
+}
+
+pub struct ReportJob;
+
+impl ReportJob {
+    /// Queue a report, unless the store already has `max_per_store` queued
+    /// or running. Returns `None` when it has.
+    pub async fn create(
+        db: &DatabaseConnection,
+        request: &CreateReport,
+        max_per_store: u64,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to queue report for store {}: {:?}",
+                request.store_id, e
+            );
+            "Failed to queue report".to_string()
+        };
+        let active = Self::count_active(db, request.store_id)
+            .await
+            .map_err(fail)?;
+        if active >= max_per_store {
+            return Ok(None);
+        }
+
+        let id = Uuid::new_v4();
+        let job = ActiveModel {
+            id: Set(id),
+            store_id: Set(request.store_id),
+            requested_by: Set(request.requested_by.clone()),
+            report_type: Set(request.report_type.as_str().to_string()),
+            format: Set(request.format.as_str().to_string()),
+            range_start: Set(request.range_start),
+            range_end: Set(request.range_end),
+            status: Set(ReportStatus::Queued.as_str().to_string()),
+            media_key: Set(None),
+            error: Set(None),
+            created_at: Set(Utc::now()),
+            started_at: Set(None),
+            finished_at: Set(None),
+            expires_at: Set(None),
+        };
+        ReportJobEntity::insert(job)
+            .exec_without_returning(db)
+            .await
+            .map_err(fail)?;
+        ReportJobEntity::find_by_id(id).one(db).await.map_err(fail)
+    }
+
+    /// How many of the store's reports are queued or running
+    pub async fn active_count(db: &DatabaseConnection, store_id: Uuid) -> Result<u64, String> {
+        Self::count_active(db, store_id).await.map_err(|e| {
+            error!("Failed to count reports of store {}: {:?}", store_id, e);
+            "Failed to count reports".to_string()
+        })
+    }
+
+    async fn count_active(db: &DatabaseConnection, store_id: Uuid) -> Result<u64, DbErr> {
+        ReportJobEntity::find()
+            .filter(report_job::Column::StoreId.eq(store_id))
+            .filter(report_job::Column::Status.is_in([
+                ReportStatus::Queued.as_str(),
+                ReportStatus::Running.as_str(),
+            ]))
+            .count(db)
+            .await
+    }
+
+    /// One of the store's reports
+    pub async fn get(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Model>, String> {
+        ReportJobEntity::find_by_id(id)
+            .filter(report_job::Column::StoreId.eq(store_id))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to load report {}: {:?}", id, e);
+                "Failed to load report".to_string()
+            })
+    }
+
+    /// Mark the oldest queued job running, unless `max_running` already are.
+    ///
+    /// The status check in the update means two replicas never claim the
+    /// same job; the one that loses gets `None` and tries on its next run.
+    pub async fn claim_next(
+        db: &DatabaseConnection,
+        max_running: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Model>, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to claim a report job: {:?}", e);
+            "Failed to claim a report job".to_string()
+        };
+        let running = ReportJobEntity::find()
+            .filter(report_job::Column::Status.eq(ReportStatus::Running.as_str()))
+            .count(db)
+            .await
+            .map_err(fail)?;
+        if running >= max_running {
+            return Ok(None);
+        }
+        let Some(job) = ReportJobEntity::find()
+            .filter(report_job::Column::Status.eq(ReportStatus::Queued.as_str()))
+            .order_by_asc(report_job::Column::CreatedAt)
+            .one(db)
+            .await
+            .map_err(fail)?
+        else {
+            return Ok(None);
+        };
+        let claimed = ReportJobEntity::update_many()
+            .col_expr(
+                report_job::Column::Status,
+                Expr::value(ReportStatus::Running.as_str()),
+            )
+            .col_expr(report_job::Column::StartedAt, Expr::value(now))
+            .filter(report_job::Column::Id.eq(job.id))
+            .filter(report_job::Column::Status.eq(ReportStatus::Queued.as_str()))
+            .exec(db)
+            .await
+            .map_err(fail)?;
+        if claimed.rows_affected == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Model {
+            status: ReportStatus::Running.as_str().to_string(),
+            started_at: Some(now),
+            ..job
+        }))
+    }
+
+    pub async fn finish(
+        db: &DatabaseConnection,
+        id: Uuid,
+        media_key: &str,
+        finished_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        ReportJobEntity::update_many()
+            .col_expr(
+                report_job::Column::Status,
+                Expr::value(ReportStatus::Ready.as_str()),
+            )
+            .col_expr(report_job::Column::MediaKey, Expr::value(media_key))
+            .col_expr(report_job::Column::FinishedAt, Expr::value(finished_at))
+            .col_expr(report_job::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(report_job::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to finish report {}: {:?}", id, e);
+                "Failed to finish report".to_string()
+            })
+    }
+
+    pub async fn fail(
+        db: &DatabaseConnection,
+        id: Uuid,
+        reason: &str,
+        finished_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        ReportJobEntity::update_many()
+            .col_expr(
+                report_job::Column::Status,
+                Expr::value(ReportStatus::Failed.as_str()),
+            )
+            .col_expr(report_job::Column::Error, Expr::value(reason))
+            .col_expr(report_job::Column::FinishedAt, Expr::value(finished_at))
+            .filter(report_job::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to record failure of report {}: {:?}", id, e);
+                "Failed to record report failure".to_string()
+            })
+    }
+
+    /// Fail jobs that started before `started_before` and never finished,
+    /// so a replica that died mid-report does not hold its slot forever
+    pub async fn fail_stale(
+        db: &DatabaseConnection,
+        started_before: DateTime<Utc>,
+    ) -> Result<u64, String> {
+        ReportJobEntity::update_many()
+            .col_expr(
+                report_job::Column::Status,
+                Expr::value(ReportStatus::Failed.as_str()),
+            )
+            .col_expr(
+                report_job::Column::Error,
+                Expr::value("Report generation did not finish"),
+            )
+            .filter(report_job::Column::Status.eq(ReportStatus::Running.as_str()))
+            .filter(report_job::Column::StartedAt.lt(started_before))
+            .exec(db)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                error!("Failed to fail stale report jobs: {:?}", e);
+                "Failed to fail stale report jobs".to_string()
+            })
+    }
+
+    /// Ready reports whose download window has closed
+    pub async fn expiring(
+        db: &DatabaseConnection,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Model>, String> {
+        ReportJobEntity::find()
+            .filter(report_job::Column::Status.eq(ReportStatus::Ready.as_str()))
+            .filter(report_job::Column::ExpiresAt.lte(now))
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list expiring reports: {:?}", e);
+                "Failed to list expiring reports".to_string()
+            })
+    }
+
+    /// Record that a report's file is gone
+    pub async fn mark_expired(db: &DatabaseConnection, id: Uuid) -> Result<(), String> {
+        ReportJobEntity::update_many()
+            .col_expr(
+                report_job::Column::Status,
+                Expr::value(ReportStatus::Expired.as_str()),
+            )
+            .col_expr(report_job::Column::MediaKey, Expr::value(None::<String>))
+            .filter(report_job::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Failed to expire report {}: {:?}", id, e);
+                "Failed to expire report".to_string()
+            })
+    }
+
+    /// The rows of one report over `[from, to)`
+    pub async fn table(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        report_type: ReportType,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<ReportTable, String> {
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to build {} report for store {}: {:?}",
+                report_type.as_str(),
+                store_id,
+                e
+            );
+            "Failed to build report".to_string()
+        };
+        match report_type {
+            ReportType::PriceChanges => {
+                let changes = PriceHistoryEntity::find()
+                    .find_also_related(ProductEntity)
+                    .filter(product_price_history::Column::StoreId.eq(store_id))
+                    .filter(product_price_history::Column::CreatedAt.gte(from))
+                    .filter(product_price_history::Column::CreatedAt.lt(to))
+                    .order_by_asc(product_price_history::Column::CreatedAt)
+                    .all(db)
+                    .await
+                    .map_err(fail)?;
+                Ok(ReportTable {
+                    columns: &[
+                        "changed_at",
+                        "product_id",
+                        "sku",
+                        "name",
+                        "previous_price",
+                        "price",
+                        "source",
+                    ],
+                    rows: changes
+                        .into_iter()
+                        .map(|(change, product)| {
+                            vec![
+                                change.created_at.to_rfc3339().into(),
+                                change.product_id.to_string().into(),
+                                product.as_ref().and_then(|p| p.sku.clone()).into(),
+                                product.map(|p| p.name).into(),
+                                change.previous_price.into(),
+                                change.price.into(),
+                                change.source.into(),
+                            ]
+                        })
+                        .collect(),
+                })
+            }
+            ReportType::NewProducts => {
+                let products = ProductEntity::find()
+                    .filter(product::Column::StoreId.eq(store_id))
+                    .filter(product::Column::CreatedAt.gte(from))
+                    .filter(product::Column::CreatedAt.lt(to))
+                    .order_by_asc(product::Column::CreatedAt)
+                    .all(db)
+                    .await
+                    .map_err(fail)?;
+                Ok(ReportTable {
+                    columns: &[
+                        "created_at",
+                        "product_id",
+                        "sku",
+                        "name",
+                        "price",
+                        "quantity_available",
+                        "is_published",
+                    ],
+                    rows: products
+                        .into_iter()
+                        .map(|product| {
+                            vec![
+                                product.created_at.to_rfc3339().into(),
+                                product.id.to_string().into(),
+                                product.sku.into(),
+                                product.name.into(),
+                                product.price.into(),
+                                product.quantity_available.into(),
+                                product.is_published.into(),
+                            ]
+                        })
+                        .collect(),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::entity::{inventory_sync, report_job, store_snapshot, tombstone};
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::{Expr, Query};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Serialize;
+use std::fmt;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// Tables the retention jobs may prune. Anything not listed here is kept
+/// forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrunableTable {
+    /// Deletion markers served to offline clients by `/sync`
+    Tombstones,
+    /// Replay records of applied POS inventory uploads
+    InventorySyncs,
+    /// Seller report requests; their files expire separately, see `crate::reports`
+    ReportJobs,
+    /// Copies of a store's products; their product rows go with them
+    StoreSnapshots,
+}
+
+impl PrunableTable {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PrunableTable::Tombstones => "tombstones",
+            PrunableTable::InventorySyncs => "inventory_syncs",
+            PrunableTable::ReportJobs => "report_jobs",
+            PrunableTable::StoreSnapshots => "store_snapshots",
+        }
+    }
+}
+
+impl fmt::Display for PrunableTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+pub struct Retention;
+
+impl Retention {
+    /// Delete up to `limit` rows of `table` older than `cutoff`.
+    ///
+    /// The victims are picked by id in a subquery so each statement touches
+    /// a bounded number of rows. Returns how many were deleted; fewer than
+    /// `limit` means nothing older is left.
+    pub async fn prune_batch(
+        db: &DatabaseConnection,
+        table: PrunableTable,
+        cutoff: DateTime<Utc>,
+        limit: u64,
+    ) -> Result<u64, String> {
+        let result = match table {
+            PrunableTable::Tombstones => {
+                tombstone::Entity::delete_many()
+                    .filter(
+                        tombstone::Column::Id.in_subquery(
+                            Query::select()
+                                .column(tombstone::Column::Id)
+                                .from(tombstone::Entity)
+                                .and_where(Expr::col(tombstone::Column::DeletedAt).lt(cutoff))
+                                .limit(limit)
+                                .to_owned(),
+                        ),
+                    )
+                    .exec(db)
+                    .await
+            }
+            PrunableTable::InventorySyncs => {
+                inventory_sync::Entity::delete_many()
+                    .filter(
+                        inventory_sync::Column::Id.in_subquery(
+                            Query::select()
+                                .column(inventory_sync::Column::Id)
+                                .from(inventory_sync::Entity)
+                                .and_where(Expr::col(inventory_sync::Column::CreatedAt).lt(cutoff))
+                                .limit(limit)
+                                .to_owned(),
+                        ),
+                    )
+                    .exec(db)
+                    .await
+            }
+            PrunableTable::ReportJobs => {
+                report_job::Entity::delete_many()
+                    .filter(
+                        report_job::Column::Id.in_subquery(
+                            Query::select()
+                                .column(report_job::Column::Id)
+                                .from(report_job::Entity)
+                                .and_where(Expr::col(report_job::Column::CreatedAt).lt(cutoff))
+                                .limit(limit)
+                                .to_owned(),
+                        ),
+                    )
+                    .exec(db)
+                    .await
+            }
+            PrunableTable::StoreSnapshots => {
+                store_snapshot::Entity::delete_many()
+                    .filter(
+                        store_snapshot::Column::Id.in_subquery(
+                            Query::select()
+                                .column(store_snapshot::Column::Id)
+                                .from(store_snapshot::Entity)
+                                .and_where(Expr::col(store_snapshot::Column::CreatedAt).lt(cutoff))
+                                .limit(limit)
+                                .to_owned(),
+                        ),
+                    )
+                    .exec(db)
+                    .await
+            }
+        };
+        result.map(|res| res.rows_affected).map_err(|e| {
+            error!("Failed to prune {}: {:?}", table, e);
+            format!("Failed to prune {table}")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use chrono::Duration;
+    use sea_orm::Set;
+    use uuid::Uuid;
+
+    async fn seed_tombstone(db: &DatabaseConnection, deleted_at: DateTime<Utc>) {
+        let row = tombstone::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            entity_type: Set("product".to_string()),
+            entity_id: Set(Uuid::new_v4()),
+            store_id: Set(None),
+            deleted_at: Set(deleted_at),
+        };
+        tombstone::Entity::insert(row)
+            .exec_without_returning(db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prune_batch_removes_only_old_rows() {
+        let db = testing::sqlite().await;
+        let now = Utc::now();
+        for days in [40, 35, 31] {
+            seed_tombstone(&db, now - Duration::days(days)).await;
+        }
+        seed_tombstone(&db, now - Duration::days(29)).await;
+        seed_tombstone(&db, now).await;
+
+        let cutoff = now - Duration::days(30);
+        let first = Retention::prune_batch(&db, PrunableTable::Tombstones, cutoff, 2)
+            .await
+            .unwrap();
+        assert_eq!(first, 2);
+        let second = Retention::prune_batch(&db, PrunableTable::Tombstones, cutoff, 2)
+            .await
+            .unwrap();
+        assert_eq!(second, 1);
+
+        let left = tombstone::Entity::find().all(&db).await.unwrap();
+        assert_eq!(left.len(), 2);
+        assert!(left.iter().all(|row| row.deleted_at >= cutoff));
+    }
+
+    #[tokio::test]
+    async fn test_prune_batch_keeps_recent_inventory_syncs() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let now = Utc::now();
+        for (sync_id, age) in [("old", 10), ("new", 1)] {
+            let row = inventory_sync::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                store_id: Set(store_id),
+                sync_id: Set(sync_id.to_string()),
+                report: Set(serde_json::json!([])),
+                created_at: Set(now - Duration::days(age)),
+            };
+            inventory_sync::Entity::insert(row)
+                .exec_without_returning(&db)
+                .await
+                .unwrap();
+        }
+
+        let pruned = Retention::prune_batch(
+            &db,
+            PrunableTable::InventorySyncs,
+            now - Duration::days(7),
+            100,
+        )
+        .await
+        .unwrap();
+        assert_eq!(pruned, 1);
+        let left = inventory_sync::Entity::find().all(&db).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].sync_id, "new");
+    }
+}
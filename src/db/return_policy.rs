@@ -0,0 +1,298 @@
+//! Structured return terms, and a best-effort reader for the free-text
+//! policies written before the terms existed.
+
+use tracing::warn;
+
+/// Longest return window a seller may offer
+pub const MAX_RETURN_WINDOW_DAYS: i32 = 90;
+
+/// What a buyer can expect when sending an item back
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReturnTerms {
+    pub returns_accepted: bool,
+    /// Days after purchase during which returns are taken; open-ended when None
+    pub window_days: Option<i32>,
+    pub conditions: Option<String>,
+}
+
+impl ReturnTerms {
+    /// Terms from request fields. Structured fields win; otherwise a legacy
+    /// `return_policy` text is parsed. None means "inherit the store default".
+    pub fn from_request(
+        returns_accepted: Option<bool>,
+        window_days: Option<i32>,
+        conditions: Option<&str>,
+        legacy_text: Option<&str>,
+    ) -> Option<Self> {
+        match returns_accepted {
+            Some(returns_accepted) => Some(Self {
+                returns_accepted,
+                window_days: window_days.filter(|_| returns_accepted),
+                conditions: non_blank(conditions),
+            }),
+            None => non_blank(legacy_text).map(|text| Self::from_text(&text)),
+        }
+    }
+
+    /// Parse free text, keeping unrecognised text as the conditions so
+    /// nothing the seller wrote is lost
+    pub fn from_text(text: &str) -> Self {
+        parse_return_policy(text).unwrap_or_else(|| {
+            warn!(policy = %text, "Could not interpret return policy text");
+            Self {
+                returns_accepted: false,
+                window_days: None,
+                conditions: non_blank(Some(text)),
+            }
+        })
+    }
+
+    /// Human-readable summary kept in `return_policy` for older clients
+    pub fn describe(&self) -> String {
+        let summary = match (self.returns_accepted, self.window_days) {
+            (false, _) => "No returns.".to_string(),
+            (true, Some(1)) => "Returns accepted within 1 day.".to_string(),
+            (true, Some(days)) => format!("Returns accepted within {days} days."),
+            (true, None) => "Returns accepted.".to_string(),
+        };
+        match &self.conditions {
+            Some(conditions) => format!("{summary} {conditions}"),
+            None => summary,
+        }
+    }
+}
+
+/// Phrases that rule returns out entirely
+const REFUSALS: &[&str] = &[
+    "no return",
+    "no refund",
+    "non-returnable",
+    "non returnable",
+    "non-refundable",
+    "all sales are final",
+    "final sale",
+    "cannot be returned",
+];
+
+/// Words that say returns of some kind are possible
+const ACCEPTANCES: &[&str] = &[
+    "return",
+    "returns",
+    "returned",
+    "returnable",
+    "exchange",
+    "exchanged",
+    "exchanges",
+    "refund",
+    "refunds",
+    "refunded",
+];
+
+/// Words a bare policy like "7 days return" is made of; anything else is a
+/// condition worth keeping
+const FILLER: &[&str] = &[
+    "a",
+    "accepted",
+    "allowed",
+    "day",
+    "days",
+    "hour",
+    "hours",
+    "month",
+    "months",
+    "no",
+    "of",
+    "policy",
+    "purchase",
+    "week",
+    "weeks",
+    "within",
+    "return",
+    "returns",
+    "returnable",
+    "exchange",
+    "exchanges",
+    "refund",
+    "refunds",
+];
+
+/// Read a free-text policy such as "No returns" or "7 days return".
+///
+/// Returns None when the text says nothing recognisable about returns.
+pub fn parse_return_policy(text: &str) -> Option<ReturnTerms> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let refused = REFUSALS.iter().any(|phrase| lower.contains(phrase));
+    let window = window_days(&words);
+    let mentions_returns = words.iter().any(|w| ACCEPTANCES.contains(w));
+    if !refused && window.is_none() && !mentions_returns {
+        return None;
+    }
+
+    let bare = words
+        .iter()
+        .all(|w| FILLER.contains(w) || number(w).is_some());
+    let mut conditions = (!bare).then(|| text.trim().to_string());
+    let mut window_days = window;
+    if window_days.is_some_and(|days| days > MAX_RETURN_WINDOW_DAYS) {
+        // Keep the seller's wording rather than silently shortening it
+        window_days = None;
+        conditions = Some(text.trim().to_string());
+    }
+
+    // An explicit window beats a refusal of refunds only ("exchange within 7 days, no refunds")
+    let returns_accepted = window_days.is_some() || (!refused && mentions_returns);
+    Some(ReturnTerms {
+        returns_accepted,
+        window_days: window_days.filter(|_| returns_accepted),
+        conditions,
+    })
+}
+
+/// First "<number> <unit>" pair, converted to whole days
+fn window_days(words: &[&str]) -> Option<i32> {
+    words.windows(2).find_map(|pair| {
+        let n = number(pair[0])?;
+        match pair[1] {
+            "day" | "days" => Some(n),
+            "week" | "weeks" => Some(n * 7),
+            "month" | "months" => Some(n * 30),
+            "hour" | "hours" => Some((n + 23) / 24),
+            _ => None,
+        }
+    })
+}
+
+fn number(word: &str) -> Option<i32> {
+    if let Ok(n) = word.parse::<i32>() {
+        return (0..=10_000).contains(&n).then_some(n);
+    }
+    let n = match word {
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "five" => 5,
+        "seven" => 7,
+        "ten" => 10,
+        "fourteen" => 14,
+        "thirty" => 30,
+        _ => return None,
+    };
+    Some(n)
+}
+
+fn non_blank(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::return_policies::RETURN_POLICY_TEMPLATES;
+
+    fn terms(
+        returns_accepted: bool,
+        window_days: Option<i32>,
+        conditions: Option<&str>,
+    ) -> ReturnTerms {
+        ReturnTerms {
+            returns_accepted,
+            window_days,
+            conditions: conditions.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_parses_short_policies() {
+        assert_eq!(
+            parse_return_policy("No returns"),
+            Some(terms(false, None, None))
+        );
+        assert_eq!(
+            parse_return_policy("7 days"),
+            Some(terms(true, Some(7), None))
+        );
+        assert_eq!(
+            parse_return_policy("2 weeks return policy"),
+            Some(terms(true, Some(14), None))
+        );
+        assert_eq!(
+            parse_return_policy("Returns accepted"),
+            Some(terms(true, None, None))
+        );
+    }
+
+    #[test]
+    fn test_keeps_wording_as_conditions() {
+        let text = "Exchange within 7 days if unused. No refunds.";
+        assert_eq!(
+            parse_return_policy(text),
+            Some(terms(true, Some(7), Some(text)))
+        );
+        // Longer than the maximum window: keep the text, drop the number
+        let text = "Returns within 6 months";
+        assert_eq!(
+            parse_return_policy(text),
+            Some(terms(true, None, Some(text)))
+        );
+    }
+
+    #[test]
+    fn test_unrecognised_text_is_kept_but_not_guessed() {
+        assert_eq!(parse_return_policy("Call the shop"), None);
+        assert_eq!(
+            ReturnTerms::from_text("Call the shop"),
+            terms(false, None, Some("Call the shop"))
+        );
+    }
+
+    #[test]
+    fn test_templates_parse() {
+        let parsed: Vec<(bool, Option<i32>)> = RETURN_POLICY_TEMPLATES
+            .iter()
+            .map(|t| {
+                let terms = parse_return_policy(t.text).unwrap();
+                (terms.returns_accepted, terms.window_days)
+            })
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                (false, None),
+                (true, Some(7)),
+                (true, Some(14)),
+                (true, Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structured_request_fields_win_over_text() {
+        assert_eq!(
+            ReturnTerms::from_request(Some(true), Some(14), Some("  "), Some("No returns")),
+            Some(terms(true, Some(14), None))
+        );
+        assert_eq!(
+            ReturnTerms::from_request(None, None, None, Some("No returns")),
+            Some(terms(false, None, None))
+        );
+        assert_eq!(ReturnTerms::from_request(None, None, None, Some(" ")), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(terms(false, None, None).describe(), "No returns.");
+        assert_eq!(
+            terms(true, Some(14), Some("Unused items only.")).describe(),
+            "Returns accepted within 14 days. Unused items only."
+        );
+        assert_eq!(terms(true, None, None).describe(), "Returns accepted.");
+    }
+}
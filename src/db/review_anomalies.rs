@@ -0,0 +1,315 @@
+//! Findings of the review anomaly scan, waiting for an admin; see
+//! `moderation::review_anomalies`. A store has at most one pending finding
+//! per pattern: later scans add to it rather than queueing another.
+
+use crate::db::store_reviews::StoreReview;
+use crate::entity::review_anomaly::{
+    self, ActiveModel as AnomalyActiveModel, Entity as AnomalyEntity, Model as AnomalyModel,
+};
+use crate::moderation::review_anomalies::StoreFindings;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Where a finding stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyStatus {
+    Pending,
+    /// The reviews look genuine
+    Dismissed,
+    /// The reviews were hidden and left the store's rating
+    Hidden,
+}
+
+impl AnomalyStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyStatus::Pending => "pending",
+            AnomalyStatus::Dismissed => "dismissed",
+            AnomalyStatus::Hidden => "hidden",
+        }
+    }
+}
+
+/// The reviews a finding is about
+pub fn review_ids(anomaly: &AnomalyModel) -> Vec<Uuid> {
+    serde_json::from_value(anomaly.review_ids.clone()).unwrap_or_default()
+}
+
+pub struct ReviewAnomaly;
+
+impl ReviewAnomaly {
+    /// Save what a scan found about one store. Reviews an admin already
+    /// decided on are not raised again; the rest join the store's pending
+    /// finding for the pattern, or start one. Returns the findings touched.
+    pub async fn record(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        found: &StoreFindings,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<AnomalyModel>, String> {
+        let store_id = found.store_id;
+        let fail = |e: DbErr| {
+            error!(
+                "Failed to record review anomalies of store {}: {:?}",
+                store_id, e
+            );
+            "Failed to record review anomalies.".to_string()
+        };
+        let existing = AnomalyEntity::find()
+            .filter(review_anomaly::Column::StoreId.eq(store_id))
+            .all(db)
+            .await
+            .map_err(fail)?;
+
+        let mut saved = Vec::new();
+        for finding in &found.findings {
+            let pattern = finding.pattern.as_str();
+            let of_pattern = existing.iter().filter(|a| a.pattern == pattern);
+            let decided: BTreeSet<Uuid> = of_pattern
+                .clone()
+                .filter(|a| a.status != AnomalyStatus::Pending.as_str())
+                .flat_map(review_ids)
+                .collect();
+            let pending = of_pattern
+                .clone()
+                .find(|a| a.status == AnomalyStatus::Pending.as_str());
+
+            let mut ids: BTreeSet<Uuid> = finding
+                .review_ids
+                .iter()
+                .filter(|id| !decided.contains(id))
+                .copied()
+                .collect();
+            if ids.is_empty() {
+                continue;
+            }
+            let anomaly = match pending {
+                Some(pending) => {
+                    ids.extend(review_ids(pending));
+                    AnomalyModel {
+                        review_ids: serde_json::json!(ids),
+                        score: found.score,
+                        detected_at: now,
+                        ..pending.clone()
+                    }
+                }
+                None => AnomalyModel {
+                    id: Uuid::new_v4(),
+                    tenant_id: tenant_id.to_owned(),
+                    store_id,
+                    pattern: pattern.to_owned(),
+                    review_ids: serde_json::json!(ids),
+                    score: found.score,
+                    status: AnomalyStatus::Pending.as_str().to_owned(),
+                    detected_at: now,
+                    reviewed_by: None,
+                    reviewed_at: None,
+                },
+            };
+            let mut active = AnomalyActiveModel::from(anomaly.clone());
+            if pending.is_some() {
+                active.review_ids = Set(anomaly.review_ids.clone());
+                active.score = Set(anomaly.score);
+                active.detected_at = Set(now);
+                AnomalyEntity::update(active).exec(db).await.map_err(fail)?;
+            } else {
+                AnomalyEntity::insert(active)
+                    .exec_without_returning(db)
+                    .await
+                    .map_err(fail)?;
+            }
+            saved.push(anomaly);
+        }
+        Ok(saved)
+    }
+
+    /// One tenant's findings with `status`, most suspicious store first
+    pub async fn list(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        status: AnomalyStatus,
+    ) -> Result<Vec<AnomalyModel>, String> {
+        AnomalyEntity::find()
+            .filter(review_anomaly::Column::TenantId.eq(tenant_id))
+            .filter(review_anomaly::Column::Status.eq(status.as_str()))
+            .order_by_desc(review_anomaly::Column::Score)
+            .order_by_asc(review_anomaly::Column::DetectedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list review anomalies: {:?}", e);
+                "Failed to list review anomalies. Please try again later.".to_string()
+            })
+    }
+
+    /// Record an admin decision. Hiding takes the finding's reviews off the
+    /// store and out of its rating. `Ok(None)` if there is no such finding.
+    pub async fn review(
+        db: &DatabaseConnection,
+        id: Uuid,
+        decision: AnomalyStatus,
+        reviewed_by: &str,
+    ) -> Result<Option<AnomalyModel>, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to review review anomaly {}: {:?}", id, e);
+            "Failed to record review. Please try again later.".to_string()
+        };
+        let Some(anomaly) = AnomalyEntity::find_by_id(id).one(db).await.map_err(fail)? else {
+            return Ok(None);
+        };
+        if decision == AnomalyStatus::Hidden {
+            StoreReview::hide(db, &review_ids(&anomaly)).await?;
+        }
+
+        let now = Utc::now();
+        AnomalyEntity::update_many()
+            .col_expr(
+                review_anomaly::Column::Status,
+                Expr::value(decision.as_str()),
+            )
+            .col_expr(review_anomaly::Column::ReviewedBy, Expr::value(reviewed_by))
+            .col_expr(review_anomaly::Column::ReviewedAt, Expr::value(now))
+            .filter(review_anomaly::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(fail)?;
+        Ok(Some(AnomalyModel {
+            status: decision.as_str().to_string(),
+            reviewed_by: Some(reviewed_by.to_string()),
+            reviewed_at: Some(now),
+            ..anomaly
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::store_reviews::{NewStoreReview, PostedReview};
+    use crate::db::stores::Store;
+    use crate::db::testing;
+    use crate::moderation::review_anomalies::{AnomalyPattern, Finding};
+
+    async fn post(db: &DatabaseConnection, store_id: Uuid, reviewer: &str, rating: i32) -> Uuid {
+        let review = NewStoreReview {
+            store_id,
+            reviewer_id: reviewer.to_string(),
+            rating,
+            comment: None,
+            order_id: None,
+            is_held: false,
+        };
+        match StoreReview::create(db, review, Utc::now()).await.unwrap() {
+            PostedReview::Posted(posted) => posted.id,
+            other => panic!("review not posted: {other:?}"),
+        }
+    }
+
+    fn found(store_id: Uuid, pattern: AnomalyPattern, ids: &[Uuid]) -> StoreFindings {
+        StoreFindings {
+            store_id,
+            score: 50.0,
+            findings: vec![Finding {
+                pattern,
+                review_ids: ids.to_vec(),
+                strength: 1.0,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_later_scans_add_to_the_pending_finding() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let mut ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        ids.sort();
+        let burst = |ids: &[Uuid]| found(store, AnomalyPattern::Burst, ids);
+
+        let first = ReviewAnomaly::record(&db, "default", &burst(&ids[..2]), Utc::now())
+            .await
+            .unwrap();
+        let again = ReviewAnomaly::record(&db, "default", &burst(&ids[1..3]), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(first[0].id, again[0].id);
+        let pending = ReviewAnomaly::list(&db, "default", AnomalyStatus::Pending)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(review_ids(&pending[0]), ids[..3]);
+        assert!(ReviewAnomaly::list(&db, "other", AnomalyStatus::Pending)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Once dismissed, the same reviews aren't raised again; new ones are
+        ReviewAnomaly::review(&db, first[0].id, AnomalyStatus::Dismissed, "ops-1")
+            .await
+            .unwrap();
+        assert!(
+            ReviewAnomaly::record(&db, "default", &burst(&ids[..3]), Utc::now())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let next = ReviewAnomaly::record(&db, "default", &burst(&ids), Utc::now())
+            .await
+            .unwrap();
+        assert_ne!(next[0].id, first[0].id);
+        assert_eq!(review_ids(&next[0]), [ids[3]]);
+    }
+
+    #[tokio::test]
+    async fn test_hiding_a_finding_takes_its_reviews_out_of_the_rating() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let farmed = [
+            post(&db, store, "farm-1", 5).await,
+            post(&db, store, "farm-2", 5).await,
+        ];
+        post(&db, store, "buyer-1", 2).await;
+        let anomaly = ReviewAnomaly::record(
+            &db,
+            "default",
+            &found(store, AnomalyPattern::DuplicateText, &farmed),
+            Utc::now(),
+        )
+        .await
+        .unwrap()
+        .remove(0);
+
+        let reviewed = ReviewAnomaly::review(&db, anomaly.id, AnomalyStatus::Hidden, "ops-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reviewed.status, "hidden");
+        assert_eq!(reviewed.reviewed_by.as_deref(), Some("ops-1"));
+        let store = Store::get(&db, store).await.unwrap();
+        assert_eq!(
+            (store.store_rating, store.store_review_count),
+            (Some(2.0), 1)
+        );
+        for id in farmed {
+            assert!(StoreReview::get(&db, id).await.unwrap().unwrap().is_held);
+        }
+        assert!(ReviewAnomaly::list(&db, "default", AnomalyStatus::Pending)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(
+            ReviewAnomaly::review(&db, Uuid::new_v4(), AnomalyStatus::Hidden, "ops-1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
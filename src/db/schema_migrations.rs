@@ -0,0 +1,230 @@
+//! Schema migration status and on-demand runs, for operators who can't
+//! shell into the container to run `migrate`.
+//!
+//! A run takes an in-process lock and, on Postgres, a transaction-scoped
+//! advisory lock, so two admins (or two replicas) pressing "run" at once
+//! apply each migration once. On Postgres the whole run is one transaction:
+//! a failing migration leaves the schema as it was.
+
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, DbErr, Statement, TransactionTrait,
+};
+use sea_orm_migration::{IntoSchemaManagerConnection, MigrationStatus, MigratorTrait};
+use serde::Serialize;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// Advisory lock key held by a migration run; any constant no other code
+/// locks on
+const MIGRATION_LOCK_KEY: i64 = 0x0074_7261_6e73_6163;
+
+/// Held for the whole of a run on this replica
+static RUNNING: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MigrationEntry {
+    pub name: String,
+    pub applied: bool,
+}
+
+/// One migration applied by a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AppliedMigration {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RunError {
+    /// Another run holds the lock
+    Busy,
+    /// A migration failed; on Postgres nothing from this run was kept
+    Failed(String),
+}
+
+/// Every migration `M` knows, in order, and whether it has been applied
+pub async fn status<M: MigratorTrait>(
+    db: &DatabaseConnection,
+) -> Result<Vec<MigrationEntry>, String> {
+    let migrations = M::get_migration_with_status(db).await.map_err(|e| {
+        error!("Failed to read migration status: {:?}", e);
+        "Failed to read migration status.".to_string()
+    })?;
+    Ok(migrations
+        .iter()
+        .map(|migration| MigrationEntry {
+            name: migration.name().to_string(),
+            applied: migration.status() == MigrationStatus::Applied,
+        })
+        .collect())
+}
+
+/// Apply every pending migration of `M`, one at a time, returning what was
+/// applied; empty when the schema was already up to date
+pub async fn run_pending<M: MigratorTrait>(
+    db: &DatabaseConnection,
+) -> Result<Vec<AppliedMigration>, RunError> {
+    let Ok(_running) = RUNNING.try_lock() else {
+        return Err(RunError::Busy);
+    };
+    let fail = |e: DbErr| {
+        error!("Migration run failed: {:?}", e);
+        RunError::Failed(format!("Migration run failed: {e}"))
+    };
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return apply::<M, _>(db).await;
+    }
+
+    let txn = db.begin().await.map_err(fail)?;
+    let locked = txn
+        .query_one(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "SELECT pg_try_advisory_xact_lock($1) AS locked",
+            [MIGRATION_LOCK_KEY.into()],
+        ))
+        .await
+        .map_err(fail)?
+        .map(|row| row.try_get::<bool>("", "locked"))
+        .transpose()
+        .map_err(fail)?;
+    if locked != Some(true) {
+        return Err(RunError::Busy);
+    }
+    let applied = apply::<M, _>(&txn).await?;
+    txn.commit().await.map_err(fail)?;
+    Ok(applied)
+}
+
+async fn apply<'c, M, C>(conn: &'c C) -> Result<Vec<AppliedMigration>, RunError>
+where
+    M: MigratorTrait,
+    C: ConnectionTrait,
+    &'c C: IntoSchemaManagerConnection<'c>,
+{
+    let pending = M::get_pending_migrations(conn).await.map_err(|e| {
+        error!("Failed to list pending migrations: {:?}", e);
+        RunError::Failed("Failed to list pending migrations.".to_string())
+    })?;
+    let mut applied = Vec::with_capacity(pending.len());
+    for migration in pending {
+        let name = migration.name().to_string();
+        let started = Instant::now();
+        if let Err(e) = M::up(conn, Some(1)).await {
+            error!(migration = %name, "Migration failed: {:?}", e);
+            return Err(RunError::Failed(format!("Migration {name} failed: {e}")));
+        }
+        let duration_ms = started.elapsed().as_millis() as u64;
+        info!(migration = %name, duration_ms, "Migration applied");
+        applied.push(AppliedMigration { name, duration_ms });
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{ConnectOptions, Database};
+    use sea_orm_migration::prelude::*;
+
+    struct TestMigrator;
+
+    #[async_trait::async_trait]
+    impl MigratorTrait for TestMigrator {
+        fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+            vec![
+                Box::new(m20250101_create_first::Migration),
+                Box::new(m20250102_create_second::Migration),
+            ]
+        }
+    }
+
+    /// Creates `table`; fails if it already exists, so running it twice
+    /// can't go unnoticed
+    async fn create_table(manager: &SchemaManager<'_>, table: &'static str) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new(table))
+                    .col(ColumnDef::new(Alias::new("id")).integer().primary_key())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    mod m20250101_create_first {
+        use super::*;
+
+        pub struct Migration;
+
+        impl MigrationName for Migration {
+            fn name(&self) -> &str {
+                "m20250101_create_first"
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl MigrationTrait for Migration {
+            async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+                create_table(manager, "first").await
+            }
+        }
+    }
+
+    mod m20250102_create_second {
+        use super::*;
+
+        pub struct Migration;
+
+        impl MigrationName for Migration {
+            fn name(&self) -> &str {
+                "m20250102_create_second"
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl MigrationTrait for Migration {
+            async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+                create_table(manager, "second").await
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_runs_apply_a_pending_migration_once() {
+        // One connection, so every query sees the same in-memory database
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1).sqlx_logging(false);
+        let db = Database::connect(options).await.unwrap();
+        TestMigrator::up(&db, Some(1)).await.unwrap();
+        let pending = |entries: Vec<MigrationEntry>| {
+            entries
+                .into_iter()
+                .filter(|entry| !entry.applied)
+                .map(|entry| entry.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            pending(status::<TestMigrator>(&db).await.unwrap()),
+            ["m20250102_create_second"]
+        );
+
+        let (a, b) = tokio::join!(
+            run_pending::<TestMigrator>(&db),
+            run_pending::<TestMigrator>(&db)
+        );
+        let applied: Vec<String> = [a, b]
+            .into_iter()
+            .map(|outcome| match outcome {
+                Ok(applied) => applied,
+                Err(RunError::Busy) => Vec::new(),
+                Err(err) => panic!("run failed: {err:?}"),
+            })
+            .flat_map(|applied| applied.into_iter().map(|migration| migration.name))
+            .collect();
+        assert_eq!(applied, ["m20250102_create_second"]);
+        assert!(pending(status::<TestMigrator>(&db).await.unwrap()).is_empty());
+        assert_eq!(run_pending::<TestMigrator>(&db).await, Ok(Vec::new()));
+    }
+}
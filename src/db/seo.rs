@@ -0,0 +1,112 @@
+//! Queries behind the sitemap and the product feed.
+
+use crate::db::products::{open_store_condition, visible_condition};
+use crate::db::stores::paused_condition;
+use crate::entity::product::{self, Entity as ProductEntity, Model as ProductModel};
+use crate::entity::store::{self, Entity as StoreEntity, Model as StoreModel};
+use crate::tenant::ForTenant;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Select,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// Stores a crawler may index: not paused
+fn listed_stores(tenant_id: &str, now: DateTime<Utc>) -> Select<StoreEntity> {
+    StoreEntity::find()
+        .for_tenant(tenant_id)
+        .filter(paused_condition(now).not())
+        .order_by_asc(store::Column::Id)
+}
+
+/// Products a crawler may index: published, visible, and in an open store
+fn listed_products(tenant_id: &str, now: DateTime<Utc>) -> Select<ProductEntity> {
+    ProductEntity::find()
+        .for_tenant(tenant_id)
+        .filter(product::Column::IsPublished.eq(true))
+        .filter(visible_condition(now))
+        .filter(open_store_condition(now))
+        .order_by_asc(product::Column::Id)
+}
+
+pub struct Seo;
+
+impl Seo {
+    /// How many stores and products the sitemap lists
+    pub async fn count_listed(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(u64, u64), String> {
+        let count_error = |e| {
+            error!("Failed to count sitemap entries: {:?}", e);
+            "Failed to build sitemap. Please try again later.".to_string()
+        };
+        let stores = listed_stores(tenant_id, now)
+            .count(db)
+            .await
+            .map_err(count_error)?;
+        let products = listed_products(tenant_id, now)
+            .count(db)
+            .await
+            .map_err(count_error)?;
+        Ok((stores, products))
+    }
+
+    pub async fn stores(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<StoreModel>, String> {
+        listed_stores(tenant_id, now)
+            .offset(offset)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list sitemap stores: {:?}", e);
+                "Failed to build sitemap. Please try again later.".to_string()
+            })
+    }
+
+    pub async fn products(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<ProductModel>, String> {
+        listed_products(tenant_id, now)
+            .offset(offset)
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list sitemap products: {:?}", e);
+                "Failed to build sitemap. Please try again later.".to_string()
+            })
+    }
+
+    /// Next page of in-stock listed products after `after`, by id
+    pub async fn feed_products(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        now: DateTime<Utc>,
+        after: Option<Uuid>,
+        limit: u64,
+    ) -> Result<Vec<ProductModel>, String> {
+        let mut query =
+            listed_products(tenant_id, now).filter(product::Column::QuantityAvailable.gt(0));
+        if let Some(after) = after {
+            query = query.filter(product::Column::Id.gt(after));
+        }
+        query.limit(limit).all(db).await.map_err(|e| {
+            error!("Failed to list feed products: {:?}", e);
+            "Failed to build product feed. Please try again later.".to_string()
+        })
+    }
+}
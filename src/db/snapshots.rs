@@ -0,0 +1,488 @@
+//! Store snapshots: a copy of every product of a store, taken on demand and
+//! before bulk operations, that the seller can restore when an edit goes
+//! wrong.
+//!
+//! A restore puts back the catalog fields in [`RESTORED_FIELDS`]. Stock,
+//! visibility and archive state are left as they are: stock has moved with
+//! sales since, and visibility goes through publishing and moderation.
+
+use crate::db::diff::{diff, FieldChange, ALWAYS_CHANGED};
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::db::is_unique_violation;
+use crate::db::product_counts::ProductCounts;
+use crate::db::products::SKU_TAKEN;
+use crate::entity::product::{
+    self, ActiveModel as ProductActiveModel, Entity as ProductEntity, Model as ProductModel,
+};
+use crate::entity::snapshot_product::{
+    self, ActiveModel as SnapshotProductActiveModel, Entity as SnapshotProductEntity,
+};
+use crate::entity::store_snapshot::{
+    self, ActiveModel as SnapshotActiveModel, Entity as SnapshotEntity, Model as SnapshotModel,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Snapshots kept per store; taking another drops the oldest
+pub const MAX_SNAPSHOTS_PER_STORE: u64 = 20;
+
+/// `source` of the history entries a restore writes
+pub const RESTORE_SOURCE: &str = "snapshot_restore";
+
+/// Product fields a restore puts back
+pub const RESTORED_FIELDS: &[&str] = &[
+    "sku",
+    "name",
+    "description",
+    "price",
+    "currency",
+    "sale_price",
+    "sale_ends_at",
+    "image_id",
+    "category_id",
+    "attributes",
+    "return_policy",
+    "return_policy_source",
+    "returns_accepted",
+    "return_window_days",
+    "return_conditions",
+    "pickup_available",
+    "delivery_available",
+    "delivery_fee_override",
+    "delivery_estimated_days",
+    "delivery_options_source",
+];
+
+/// Rows per insert, well under Postgres' bind parameter limit
+const INSERT_CHUNK: usize = 1000;
+
+/// Why a snapshot was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotReason {
+    /// Asked for by the seller
+    Manual,
+    /// Taken before a POS inventory upload
+    InventorySync,
+    /// Taken before a store-wide price change
+    BulkPrice,
+    /// Taken before a bulk price and stock edit
+    BulkEdit,
+}
+
+impl SnapshotReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotReason::Manual => "manual",
+            SnapshotReason::InventorySync => "inventory_sync",
+            SnapshotReason::BulkPrice => "bulk_price",
+            SnapshotReason::BulkEdit => "bulk_edit",
+        }
+    }
+}
+
+/// One product a restore changed back
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RestoredProduct {
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    /// `old` is the value before the restore, `new` the snapshot's
+    pub changes: Vec<FieldChange>,
+}
+
+/// What a restore did
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RestoreReport {
+    #[schema(value_type = String, format = "uuid")]
+    pub snapshot_id: Uuid,
+    pub restored: Vec<RestoredProduct>,
+    /// Products already as they were in the snapshot
+    pub unchanged: usize,
+    /// Products in the snapshot that have since been deleted for good
+    #[schema(value_type = Vec<String>)]
+    pub missing: Vec<Uuid>,
+}
+
+/// A restore's report, with each changed product before and after
+#[derive(Debug)]
+pub struct Restore {
+    pub report: RestoreReport,
+    pub changed: Vec<(ProductModel, ProductModel)>,
+}
+
+/// `current` with the [`RESTORED_FIELDS`] of a snapshotted product. Fields
+/// the snapshot lacks, e.g. ones added since, keep their current value.
+pub fn restored(current: &ProductModel, data: &serde_json::Value) -> Result<ProductModel, String> {
+    let mut merged = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    for field in RESTORED_FIELDS {
+        if let Some(value) = data.get(*field) {
+            merged[*field] = value.clone();
+        }
+    }
+    serde_json::from_value(merged).map_err(|e| {
+        error!("Unreadable snapshot of product {}: {:?}", current.id, e);
+        "Snapshot could not be read.".to_string()
+    })
+}
+
+pub struct StoreSnapshot;
+
+impl StoreSnapshot {
+    /// Copy every product of the store, archived ones included, then drop
+    /// the store's snapshots past [`MAX_SNAPSHOTS_PER_STORE`]. Runs inside
+    /// `conn`'s transaction when it has one.
+    pub async fn capture<C: ConnectionTrait + TransactionTrait>(
+        conn: &C,
+        store_id: Uuid,
+        reason: SnapshotReason,
+        created_by: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<SnapshotModel, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to snapshot store {}: {:?}", store_id, e);
+            "Failed to take snapshot. Please try again later.".to_string()
+        };
+        let txn = conn.begin().await.map_err(fail)?;
+        let products = ProductEntity::find()
+            .filter(product::Column::StoreId.eq(store_id))
+            .order_by_asc(product::Column::CreatedAt)
+            .order_by_asc(product::Column::Id)
+            .all(&txn)
+            .await
+            .map_err(fail)?;
+
+        let snapshot = SnapshotModel {
+            id: Uuid::new_v4(),
+            store_id,
+            reason: reason.as_str().to_owned(),
+            created_by: created_by.map(str::to_owned),
+            product_count: i32::try_from(products.len()).unwrap_or(i32::MAX),
+            created_at: now,
+        };
+        SnapshotEntity::insert(SnapshotActiveModel::from(snapshot.clone()))
+            .exec_without_returning(&txn)
+            .await
+            .map_err(fail)?;
+        for chunk in products.chunks(INSERT_CHUNK) {
+            let rows = chunk.iter().map(|product| SnapshotProductActiveModel {
+                id: Set(Uuid::new_v4()),
+                snapshot_id: Set(snapshot.id),
+                product_id: Set(product.id),
+                data: Set(serde_json::to_value(product).unwrap_or_default()),
+            });
+            SnapshotProductEntity::insert_many(rows)
+                .exec_without_returning(&txn)
+                .await
+                .map_err(fail)?;
+        }
+
+        let kept: Vec<Uuid> = SnapshotEntity::find()
+            .select_only()
+            .column(store_snapshot::Column::Id)
+            .filter(store_snapshot::Column::StoreId.eq(store_id))
+            .order_by_desc(store_snapshot::Column::CreatedAt)
+            .order_by_desc(store_snapshot::Column::Id)
+            .into_tuple()
+            .all(&txn)
+            .await
+            .map_err(fail)?;
+        // At most one past the cap, so no need for OFFSET
+        let stale: Vec<Uuid> = kept
+            .into_iter()
+            .skip(MAX_SNAPSHOTS_PER_STORE as usize)
+            .collect();
+        if !stale.is_empty() {
+            SnapshotProductEntity::delete_many()
+                .filter(snapshot_product::Column::SnapshotId.is_in(stale.clone()))
+                .exec(&txn)
+                .await
+                .map_err(fail)?;
+            SnapshotEntity::delete_many()
+                .filter(store_snapshot::Column::Id.is_in(stale))
+                .exec(&txn)
+                .await
+                .map_err(fail)?;
+        }
+        txn.commit().await.map_err(fail)?;
+        debug!(store_id = %store_id, snapshot_id = %snapshot.id, "Store snapshot taken");
+        Ok(snapshot)
+    }
+
+    /// The store's snapshots, newest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+    ) -> Result<Vec<SnapshotModel>, String> {
+        SnapshotEntity::find()
+            .filter(store_snapshot::Column::StoreId.eq(store_id))
+            .order_by_desc(store_snapshot::Column::CreatedAt)
+            .order_by_desc(store_snapshot::Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list snapshots of store {}: {:?}", store_id, e);
+                "Failed to list snapshots. Please try again later.".to_string()
+            })
+    }
+
+    /// The snapshot, if it exists and was taken of `store_id`
+    pub async fn get(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<SnapshotModel>, String> {
+        SnapshotEntity::find_by_id(id)
+            .filter(store_snapshot::Column::StoreId.eq(store_id))
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch snapshot {}: {:?}", id, e);
+                "Failed to fetch snapshot. Please try again later.".to_string()
+            })
+    }
+
+    /// Put the snapshot's products back as they were, in one transaction.
+    /// Only products whose [`RESTORED_FIELDS`] differ are written, each with
+    /// a history entry.
+    pub async fn restore(
+        db: &DatabaseConnection,
+        snapshot: &SnapshotModel,
+        changed_by: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Restore, String> {
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to restore snapshot {}: {:?}", snapshot.id, e);
+            "Failed to restore snapshot. Please try again later.".to_string()
+        };
+        let origin = ChangeOrigin {
+            changed_by,
+            source: RESTORE_SOURCE,
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let rows = SnapshotProductEntity::find()
+            .filter(snapshot_product::Column::SnapshotId.eq(snapshot.id))
+            .order_by_asc(snapshot_product::Column::Id)
+            .all(&txn)
+            .await
+            .map_err(fail)?;
+        let mut current = HashMap::with_capacity(rows.len());
+        for chunk in rows.chunks(INSERT_CHUNK) {
+            let ids: Vec<Uuid> = chunk.iter().map(|row| row.product_id).collect();
+            let products = ProductEntity::find()
+                .filter(product::Column::Id.is_in(ids))
+                .filter(product::Column::StoreId.eq(snapshot.store_id))
+                .all(&txn)
+                .await
+                .map_err(fail)?;
+            current.extend(products.into_iter().map(|product| (product.id, product)));
+        }
+
+        let mut report = RestoreReport {
+            snapshot_id: snapshot.id,
+            restored: Vec::new(),
+            unchanged: 0,
+            missing: Vec::new(),
+        };
+        let mut changed = Vec::new();
+        for row in &rows {
+            let Some(before) = current.remove(&row.product_id) else {
+                report.missing.push(row.product_id);
+                continue;
+            };
+            let after = restored(&before, &row.data)?;
+            let changes = diff(&before, &after, ALWAYS_CHANGED);
+            if changes.is_empty() {
+                report.unchanged += 1;
+                continue;
+            }
+            let mut active = ProductActiveModel::from(after).reset_all();
+            active.updated_at = Set(now);
+            let after = match active.update(&txn).await {
+                Ok(after) => after,
+                // Another product took the SKU since the snapshot
+                Err(e) if is_unique_violation(&e) => return Err(SKU_TAKEN.to_string()),
+                Err(e) => return Err(fail(e)),
+            };
+            ProductCounts::record(&txn, Some(&before), Some(&after)).await?;
+            AuditLog::record_product(&txn, &before, &after, origin).await?;
+            report.restored.push(RestoredProduct {
+                product_id: after.id,
+                changes,
+            });
+            changed.push((before, after));
+        }
+        txn.commit().await.map_err(fail)?;
+        debug!(
+            snapshot_id = %snapshot.id,
+            restored = report.restored.len(),
+            "Store snapshot restored"
+        );
+        Ok(Restore { report, changed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::bulk_prices::{BulkPrice, ProductEdit};
+    use crate::db::products::Product;
+    use crate::db::testing;
+    use crate::entity::audit_log;
+    use sea_orm::PaginatorTrait;
+
+    async fn import(db: &DatabaseConnection, store_id: Uuid, sku: &str, price: f64) -> Uuid {
+        Product::create(
+            db,
+            store_id,
+            Some(sku),
+            &format!("Wax print {sku}"),
+            Some("Six yards, cotton"),
+            price,
+            "XAF",
+            10,
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            None,
+            serde_json::json!({}),
+        )
+        .await
+        .unwrap()
+        .id
+    }
+
+    #[tokio::test]
+    async fn test_restore_recovers_fields_after_a_bad_bulk_edit() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let first = import(&db, store_id, "WAX-1", 5000.0).await;
+        let second = import(&db, store_id, "WAX-2", 7500.0).await;
+        let third = import(&db, store_id, "WAX-3", 9000.0).await;
+        let snapshot = StoreSnapshot::capture(
+            &db,
+            store_id,
+            SnapshotReason::BulkEdit,
+            Some("seller-1"),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(snapshot.product_count, 3);
+
+        // A bulk edit with the price column shifted, a rename, and a deletion
+        let products = BulkPrice::products(&db, &[first, second]).await.unwrap();
+        let edits =
+            [(first, 4, 1.0), (second, 0, 2.0)].map(|(product_id, quantity, price)| ProductEdit {
+                product_id,
+                price: Some(price),
+                quantity_available: Some(quantity),
+            });
+        BulkPrice::edit(
+            &db,
+            &products,
+            &edits,
+            ChangeOrigin::edit(Some("seller-1")),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        let renamed = Product::get(&db, second).await.unwrap();
+        Product::update(
+            &db,
+            second,
+            None,
+            "oops",
+            None,
+            renamed.price,
+            None,
+            renamed.quantity_available,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            serde_json::json!({}),
+            ChangeOrigin::edit(Some("seller-1")),
+        )
+        .await
+        .unwrap();
+        Product::delete_permanently(&db, third).await.unwrap();
+
+        let restore = StoreSnapshot::restore(&db, &snapshot, Some("seller-1"), Utc::now())
+            .await
+            .unwrap();
+        let report = restore.report;
+        assert_eq!(report.unchanged, 0);
+        assert_eq!(report.missing, vec![third]);
+        let fields = |product_id: Uuid| -> Vec<String> {
+            let restored = report
+                .restored
+                .iter()
+                .find(|r| r.product_id == product_id)
+                .unwrap();
+            restored.changes.iter().map(|c| c.field.clone()).collect()
+        };
+        assert_eq!(fields(first), vec!["price"]);
+        assert_eq!(fields(second), vec!["description", "name", "price", "sku"]);
+
+        let first = Product::get(&db, first).await.unwrap();
+        assert_eq!(first.price, 5000.0);
+        // Stock is left as the edit set it
+        assert_eq!(first.quantity_available, 4);
+        let second = Product::get(&db, second).await.unwrap();
+        assert_eq!(second.name, "Wax print WAX-2");
+        assert_eq!(second.sku.as_deref(), Some("WAX-2"));
+        assert_eq!(second.description.as_deref(), Some("Six yards, cotton"));
+        assert_eq!(second.price, 7500.0);
+        assert_eq!(second.quantity_available, 0);
+
+        let logged = audit_log::Entity::find()
+            .filter(audit_log::Column::Source.eq(RESTORE_SOURCE))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(logged, 2);
+
+        // Restoring again finds nothing left to change
+        let again = StoreSnapshot::restore(&db, &snapshot, None, Utc::now())
+            .await
+            .unwrap();
+        assert!(again.report.restored.is_empty());
+        assert_eq!(again.report.unchanged, 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_are_capped_per_store() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        import(&db, store_id, "WAX-1", 5000.0).await;
+        let start = Utc::now();
+        let mut taken = Vec::new();
+        for minutes in 0..MAX_SNAPSHOTS_PER_STORE + 2 {
+            let at = start + chrono::Duration::minutes(minutes as i64);
+            let snapshot = StoreSnapshot::capture(&db, store_id, SnapshotReason::Manual, None, at)
+                .await
+                .unwrap();
+            taken.push(snapshot.id);
+        }
+
+        let kept = StoreSnapshot::list(&db, store_id).await.unwrap();
+        assert_eq!(kept.len() as u64, MAX_SNAPSHOTS_PER_STORE);
+        assert_eq!(kept[0].id, *taken.last().unwrap());
+        assert!(kept.iter().all(|s| s.id != taken[0] && s.id != taken[1]));
+        let rows = SnapshotProductEntity::find().count(&db).await.unwrap();
+        assert_eq!(rows, MAX_SNAPSHOTS_PER_STORE);
+    }
+}
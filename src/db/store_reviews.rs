@@ -0,0 +1,461 @@
+//! Buyers' reviews of a store as a whole, and the `store_rating` and
+//! `store_review_count` they keep on the store. Product ratings stay in
+//! the store's `rating`; the two are never blended.
+
+use crate::db::is_unique_violation;
+use crate::entity::store::{self, Entity as StoreEntity};
+use crate::entity::store_review::{self, ActiveModel, Entity as ReviewEntity, Model};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set,
+    TransactionTrait,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Store reviews one buyer may post in 24 hours
+pub const MAX_REVIEWS_PER_DAY: u64 = 10;
+
+/// Order of a store's reviews
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSort {
+    #[default]
+    Newest,
+    Highest,
+    Lowest,
+}
+
+/// A review as the buyer wrote it, already validated and screened
+#[derive(Debug, Clone)]
+pub struct NewStoreReview {
+    pub store_id: Uuid,
+    pub reviewer_id: String,
+    pub rating: i32,
+    pub comment: Option<String>,
+    pub order_id: Option<Uuid>,
+    pub is_held: bool,
+}
+
+/// What became of a review
+#[derive(Debug)]
+pub enum PostedReview {
+    Posted(Model),
+    /// The buyer already reviewed this store
+    AlreadyReviewed,
+    /// The buyer reached [`MAX_REVIEWS_PER_DAY`]
+    DailyLimit,
+}
+
+/// A store's reviews in `sort` order, newest first among equals
+fn list_query(store_id: Uuid, sort: ReviewSort, include_held: bool) -> Select<ReviewEntity> {
+    let mut query = ReviewEntity::find().filter(store_review::Column::StoreId.eq(store_id));
+    if !include_held {
+        query = query.filter(store_review::Column::IsHeld.eq(false));
+    }
+    query = match sort {
+        ReviewSort::Newest => query,
+        ReviewSort::Highest => query.order_by_desc(store_review::Column::Rating),
+        ReviewSort::Lowest => query.order_by_asc(store_review::Column::Rating),
+    };
+    query
+        .order_by_desc(store_review::Column::CreatedAt)
+        .order_by_desc(store_review::Column::Id)
+}
+
+/// Recompute the store's rating from its published reviews
+async fn refresh_store_rating<C: ConnectionTrait>(db: &C, store_id: Uuid) -> Result<(), DbErr> {
+    let (total, count) = ReviewEntity::find()
+        .select_only()
+        .column_as(store_review::Column::Rating.sum(), "total")
+        .column_as(store_review::Column::Id.count(), "count")
+        .filter(store_review::Column::StoreId.eq(store_id))
+        .filter(store_review::Column::IsHeld.eq(false))
+        .into_tuple::<(Option<i64>, i64)>()
+        .one(db)
+        .await?
+        .unwrap_or_default();
+    let rating = total
+        .filter(|_| count > 0)
+        .map(|total| (total as f64 / count as f64) as f32);
+    StoreEntity::update_many()
+        .col_expr(store::Column::StoreRating, Expr::value(rating))
+        .col_expr(store::Column::StoreReviewCount, Expr::value(count as i32))
+        .filter(store::Column::Id.eq(store_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+pub struct StoreReview;
+
+impl StoreReview {
+    /// Save a review and update the store's rating with it, unless the
+    /// buyer already reviewed the store or posted too many reviews today
+    pub async fn create(
+        db: &DatabaseConnection,
+        review: NewStoreReview,
+        now: DateTime<Utc>,
+    ) -> Result<PostedReview, String> {
+        let store_id = review.store_id;
+        let fail = |e: DbErr| {
+            error!("Failed to save review of store {}: {:?}", store_id, e);
+            "Failed to save review. Please try again later.".to_string()
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let today = ReviewEntity::find()
+            .filter(store_review::Column::ReviewerId.eq(&review.reviewer_id))
+            .filter(store_review::Column::CreatedAt.gt(now - Duration::hours(24)))
+            .count(&txn)
+            .await
+            .map_err(fail)?;
+        if today >= MAX_REVIEWS_PER_DAY {
+            return Ok(PostedReview::DailyLimit);
+        }
+
+        let id = Uuid::new_v4();
+        let is_held = review.is_held;
+        let model = ActiveModel {
+            id: Set(id),
+            store_id: Set(store_id),
+            reviewer_id: Set(review.reviewer_id),
+            rating: Set(review.rating),
+            comment: Set(review.comment),
+            order_id: Set(review.order_id),
+            // Left for the order checks to confirm
+            is_verified: Set(false),
+            reply: Set(None),
+            replied_by: Set(None),
+            replied_at: Set(None),
+            is_held: Set(is_held),
+            created_at: Set(now),
+        };
+        // The unique index decides between two reviews racing in
+        if let Err(e) = ReviewEntity::insert(model)
+            .exec_without_returning(&txn)
+            .await
+        {
+            if is_unique_violation(&e) {
+                return Ok(PostedReview::AlreadyReviewed);
+            }
+            return Err(fail(e));
+        }
+        if !is_held {
+            refresh_store_rating(&txn, store_id).await.map_err(fail)?;
+        }
+        let res = ReviewEntity::find_by_id(id)
+            .one(&txn)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Failed to save review".to_string())?;
+        txn.commit().await.map_err(fail)?;
+        debug!("Store review created: {:?}", res);
+        Ok(PostedReview::Posted(res))
+    }
+
+    pub async fn get(db: &DatabaseConnection, id: Uuid) -> Result<Option<Model>, String> {
+        ReviewEntity::find_by_id(id).one(db).await.map_err(|e| {
+            error!("Failed to fetch store review {}: {:?}", id, e);
+            "Failed to fetch review. Please try again later.".to_string()
+        })
+    }
+
+    /// Set or replace the store owner's reply
+    pub async fn reply(
+        db: &DatabaseConnection,
+        review: Model,
+        reply: &str,
+        replied_by: &str,
+    ) -> Result<Model, String> {
+        let id = review.id;
+        let mut active: ActiveModel = review.into();
+        active.reply = Set(Some(reply.to_owned()));
+        active.replied_by = Set(Some(replied_by.to_owned()));
+        active.replied_at = Set(Some(Utc::now()));
+        active.update(db).await.map_err(|e| {
+            error!("Failed to reply to store review {}: {:?}", id, e);
+            "Failed to save reply. Please try again later.".to_string()
+        })
+    }
+
+    /// One page of a store's reviews and how many there are in all; `page`
+    /// starts at 1
+    pub async fn list(
+        db: &DatabaseConnection,
+        store_id: Uuid,
+        sort: ReviewSort,
+        include_held: bool,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<Model>, u64), String> {
+        let fail = |e: DbErr| {
+            error!("Failed to list reviews of store {}: {:?}", store_id, e);
+            "Failed to list reviews. Please try again later.".to_string()
+        };
+        let paginator = list_query(store_id, sort, include_held).paginate(db, per_page);
+        let total = paginator.num_items().await.map_err(fail)?;
+        let reviews = paginator
+            .fetch_page(page.saturating_sub(1))
+            .await
+            .map_err(fail)?;
+        Ok((reviews, total))
+    }
+
+    /// Published reviews of every store posted at or after `from`
+    pub async fn since(db: &DatabaseConnection, from: DateTime<Utc>) -> Result<Vec<Model>, String> {
+        ReviewEntity::find()
+            .filter(store_review::Column::CreatedAt.gte(from))
+            .filter(store_review::Column::IsHeld.eq(false))
+            .order_by_asc(store_review::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list recent store reviews: {:?}", e);
+                "Failed to list reviews. Please try again later.".to_string()
+            })
+    }
+
+    /// Published reviews each store got in `[from, until)`; stores with none
+    /// are left out
+    pub async fn counts_by_store(
+        db: &DatabaseConnection,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<BTreeMap<Uuid, u64>, String> {
+        let counts = ReviewEntity::find()
+            .select_only()
+            .column(store_review::Column::StoreId)
+            .column_as(store_review::Column::Id.count(), "count")
+            .filter(store_review::Column::CreatedAt.gte(from))
+            .filter(store_review::Column::CreatedAt.lt(until))
+            .filter(store_review::Column::IsHeld.eq(false))
+            .group_by(store_review::Column::StoreId)
+            .into_tuple::<(Uuid, i64)>()
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to count store reviews: {:?}", e);
+                "Failed to count reviews. Please try again later.".to_string()
+            })?;
+        Ok(counts
+            .into_iter()
+            .map(|(store_id, count)| (store_id, count as u64))
+            .collect())
+    }
+
+    /// Hide reviews from the public and take them out of their stores'
+    /// ratings, as a flagged term would have. Returns how many were still
+    /// published.
+    pub async fn hide(db: &DatabaseConnection, ids: &[Uuid]) -> Result<u64, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to hide store reviews: {:?}", e);
+            "Failed to hide reviews. Please try again later.".to_string()
+        };
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let txn = db.begin().await.map_err(fail)?;
+        let stores: Vec<Uuid> = ReviewEntity::find()
+            .select_only()
+            .column(store_review::Column::StoreId)
+            .distinct()
+            .filter(store_review::Column::Id.is_in(ids.iter().copied()))
+            .filter(store_review::Column::IsHeld.eq(false))
+            .into_tuple()
+            .all(&txn)
+            .await
+            .map_err(fail)?;
+        let hidden = ReviewEntity::update_many()
+            .col_expr(store_review::Column::IsHeld, Expr::value(true))
+            .filter(store_review::Column::Id.is_in(ids.iter().copied()))
+            .filter(store_review::Column::IsHeld.eq(false))
+            .exec(&txn)
+            .await
+            .map_err(fail)?
+            .rows_affected;
+        for store_id in stores {
+            refresh_store_rating(&txn, store_id).await.map_err(fail)?;
+        }
+        txn.commit().await.map_err(fail)?;
+        Ok(hidden)
+    }
+
+    /// Recompute every store's rating from its published reviews, for when
+    /// reviews were changed behind the API's back. Returns how many stores
+    /// were refreshed.
+    pub async fn recompute_ratings(db: &DatabaseConnection) -> Result<usize, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to recompute store ratings: {:?}", e);
+            "Failed to recompute store ratings. Please try again later.".to_string()
+        };
+        let stores: Vec<Uuid> = StoreEntity::find()
+            .select_only()
+            .column(store::Column::Id)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(fail)?;
+        for store_id in &stores {
+            refresh_store_rating(db, *store_id).await.map_err(fail)?;
+        }
+        Ok(stores.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::stores::Store;
+    use crate::db::testing;
+
+    fn review(store_id: Uuid, reviewer: &str, rating: i32) -> NewStoreReview {
+        NewStoreReview {
+            store_id,
+            reviewer_id: reviewer.to_string(),
+            rating,
+            comment: None,
+            order_id: None,
+            is_held: false,
+        }
+    }
+
+    async fn post(db: &DatabaseConnection, review: NewStoreReview) -> PostedReview {
+        StoreReview::create(db, review, Utc::now()).await.unwrap()
+    }
+
+    async fn store_rating(db: &DatabaseConnection, store_id: Uuid) -> (Option<f32>, i32) {
+        let store = Store::get(db, store_id).await.unwrap();
+        (store.store_rating, store.store_review_count)
+    }
+
+    #[tokio::test]
+    async fn test_one_review_per_buyer_and_store() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let other_store = testing::seed_store(&db, "seller-2").await;
+
+        assert!(matches!(
+            post(&db, review(store, "buyer-1", 4)).await,
+            PostedReview::Posted(_)
+        ));
+        assert!(matches!(
+            post(&db, review(store, "buyer-1", 1)).await,
+            PostedReview::AlreadyReviewed
+        ));
+        // The same buyer may review another store, and others this one
+        assert!(matches!(
+            post(&db, review(other_store, "buyer-1", 5)).await,
+            PostedReview::Posted(_)
+        ));
+        assert!(matches!(
+            post(&db, review(store, "buyer-2", 2)).await,
+            PostedReview::Posted(_)
+        ));
+        // The refused duplicate left the rating alone
+        assert_eq!(store_rating(&db, store).await, (Some(3.0), 2));
+    }
+
+    #[tokio::test]
+    async fn test_store_rating_follows_published_reviews_only() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        assert_eq!(store_rating(&db, store).await, (None, 0));
+
+        post(&db, review(store, "buyer-1", 5)).await;
+        assert_eq!(store_rating(&db, store).await, (Some(5.0), 1));
+        post(&db, review(store, "buyer-2", 4)).await;
+        post(&db, review(store, "buyer-3", 4)).await;
+        let (rating, count) = store_rating(&db, store).await;
+        assert!((rating.unwrap() - 4.333).abs() < 0.01, "{rating:?}");
+        assert_eq!(count, 3);
+
+        // Held reviews stay out of the rating
+        let held = NewStoreReview {
+            is_held: true,
+            ..review(store, "buyer-4", 1)
+        };
+        post(&db, held).await;
+        assert_eq!(store_rating(&db, store).await.1, 3);
+        // ...and the product-derived rating is untouched throughout
+        assert_eq!(Store::get(&db, store).await.unwrap().rating, None);
+
+        let (lowest, total) = StoreReview::list(&db, store, ReviewSort::Lowest, false, 1, 20)
+            .await
+            .unwrap();
+        assert_eq!(total, 3);
+        let ratings: Vec<i32> = lowest.iter().map(|review| review.rating).collect();
+        assert_eq!(ratings, [4, 4, 5]);
+        let (owner_view, _) = StoreReview::list(&db, store, ReviewSort::Lowest, true, 1, 20)
+            .await
+            .unwrap();
+        assert_eq!(owner_view[0].rating, 1);
+    }
+
+    #[tokio::test]
+    async fn test_daily_limit_per_buyer() {
+        let db = testing::sqlite().await;
+        for _ in 0..MAX_REVIEWS_PER_DAY {
+            let store = testing::seed_store(&db, "seller-1").await;
+            post(&db, review(store, "buyer-1", 5)).await;
+        }
+        let store = testing::seed_store(&db, "seller-1").await;
+        assert!(matches!(
+            post(&db, review(store, "buyer-1", 5)).await,
+            PostedReview::DailyLimit
+        ));
+        let tomorrow = Utc::now() + Duration::hours(25);
+        assert!(matches!(
+            StoreReview::create(&db, review(store, "buyer-1", 5), tomorrow)
+                .await
+                .unwrap(),
+            PostedReview::Posted(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hidden_reviews_leave_the_rating() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let mut ids = Vec::new();
+        for (buyer, rating) in [("buyer-1", 5), ("buyer-2", 5), ("buyer-3", 2)] {
+            if let PostedReview::Posted(posted) = post(&db, review(store, buyer, rating)).await {
+                ids.push(posted.id);
+            }
+        }
+        assert_eq!(store_rating(&db, store).await, (Some(4.0), 3));
+
+        assert_eq!(StoreReview::hide(&db, &ids[..2]).await.unwrap(), 2);
+        assert_eq!(store_rating(&db, store).await, (Some(2.0), 1));
+        // Hiding again changes nothing
+        assert_eq!(StoreReview::hide(&db, &ids[..2]).await.unwrap(), 0);
+        assert_eq!(StoreReview::hide(&db, &[]).await.unwrap(), 0);
+
+        let recent = StoreReview::since(&db, Utc::now() - Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(recent.iter().map(|r| r.id).collect::<Vec<_>>(), [ids[2]]);
+        let counts = StoreReview::counts_by_store(
+            &db,
+            Utc::now() - Duration::hours(1),
+            Utc::now() + Duration::hours(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(counts, BTreeMap::from([(store, 1)]));
+
+        // A rating knocked out of step is put right by a recompute
+        StoreEntity::update_many()
+            .col_expr(store::Column::StoreReviewCount, Expr::value(7))
+            .filter(store::Column::Id.eq(store))
+            .exec(&db)
+            .await
+            .unwrap();
+        let other = testing::seed_store(&db, "seller-2").await;
+        assert_eq!(StoreReview::recompute_ratings(&db).await.unwrap(), 2);
+        assert_eq!(store_rating(&db, store).await, (Some(2.0), 1));
+        assert_eq!(store_rating(&db, other).await, (None, 0));
+    }
+}
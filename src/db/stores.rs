@@ -1,16 +1,150 @@
+use crate::db::delivery::DeliveryOptions;
+use crate::db::history::{AuditLog, ChangeOrigin};
+use crate::db::return_policy::ReturnTerms;
+use crate::db::sync::{Tombstone, TombstoneKind};
+use crate::entity::product::{self, Entity as ProductEntity};
 use crate::entity::store::{
     self, ActiveModel as StoreActiveModel, Entity as StoreEntity, Model as StoreModel,
 };
-use chrono::Utc;
+use crate::tenant::ForTenant;
+use chrono::{DateTime, Utc};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set, TransactionTrait,
 };
+use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// How long a question waits for the seller before a `delayed_reply`
+/// auto-response goes out
+pub const AUTO_REPLY_DELAY_HOURS: i64 = 48;
+
 #[allow(dead_code)]
 pub struct Store;
 
+/// Page `page` (from 1) of `query`, `per_page` long, and the rows in all
+/// pages; a page past the end is empty
+async fn fetch_page(
+    db: &DatabaseConnection,
+    query: Select<StoreEntity>,
+    page: u64,
+    per_page: u64,
+) -> Result<(Vec<StoreModel>, u64), sea_orm::DbErr> {
+    let per_page = per_page.max(1);
+    let page = page.saturating_sub(1);
+    let paginator = query.paginate(db, per_page);
+    let total = paginator.num_items().await?;
+    // Past the end: nothing to read, and an offset that may not fit in SQL
+    if page.saturating_mul(per_page) >= total {
+        return Ok((Vec::new(), total));
+    }
+    let stores = paginator.fetch_page(page).await?;
+    Ok((stores, total))
+}
+
+/// Whether the store is paused at `now`. A pause whose `paused_until` has
+/// passed no longer counts, even before the resume job has cleared it.
+pub fn is_paused(store: &StoreModel, now: DateTime<Utc>) -> bool {
+    store.is_paused && store.paused_until.is_none_or(|until| until > now)
+}
+
+/// Stores paused at `now`, matching [`is_paused`]
+pub(crate) fn paused_condition(now: DateTime<Utc>) -> Condition {
+    Condition::all().add(store::Column::IsPaused.eq(true)).add(
+        Condition::any()
+            .add(store::Column::PausedUntil.is_null())
+            .add(store::Column::PausedUntil.gt(now)),
+    )
+}
+
+/// Contact fields a seller publishes to buyers; `None` keeps the current setting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContactVisibility {
+    pub show_phone: Option<bool>,
+    pub show_whatsapp: Option<bool>,
+    pub show_email: Option<bool>,
+}
+
+/// Ordering for store listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreSort {
+    #[default]
+    Newest,
+    /// Most trusted first; stores not yet scored come last
+    TrustScore,
+}
+
+impl std::str::FromStr for StoreSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(StoreSort::Newest),
+            "trust_score" => Ok(StoreSort::TrustScore),
+            _ => Err("sort must be one of newest, trust_score".to_string()),
+        }
+    }
+}
+
+/// What sets off a store's automatic reply to buyers' questions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoResponseTrigger {
+    /// Right away, to questions asked while the store is paused
+    Paused,
+    /// To questions still unanswered after [`AUTO_REPLY_DELAY_HOURS`]
+    DelayedReply,
+}
+
+impl AutoResponseTrigger {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutoResponseTrigger::Paused => "paused",
+            AutoResponseTrigger::DelayedReply => "delayed_reply",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "paused" => Some(AutoResponseTrigger::Paused),
+            "delayed_reply" => Some(AutoResponseTrigger::DelayedReply),
+            _ => None,
+        }
+    }
+}
+
+/// A store's automatic reply to buyers' questions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AutoResponse {
+    pub enabled: bool,
+    pub message: Option<String>,
+    pub trigger: Option<AutoResponseTrigger>,
+}
+
+impl AutoResponse {
+    pub fn of(store: &StoreModel) -> Self {
+        Self {
+            enabled: store.auto_response_enabled,
+            message: store.auto_response_message.clone(),
+            trigger: store
+                .auto_response_trigger
+                .as_deref()
+                .and_then(AutoResponseTrigger::parse),
+        }
+    }
+
+    /// The reply to send on `trigger`, if the store has one set up for it
+    pub fn message_for(&self, trigger: AutoResponseTrigger) -> Option<&str> {
+        if !self.enabled || self.trigger != Some(trigger) {
+            return None;
+        }
+        self.message.as_deref().filter(|m| !m.trim().is_empty())
+    }
+}
+
 #[allow(dead_code)]
 impl Store {
     #[allow(clippy::too_many_arguments)]
@@ -24,10 +158,13 @@ impl Store {
         contact_email: Option<&str>,
         contact_whatsapp: Option<&str>,
         owner_device_id: Option<&str>,
+        default_return_policy: Option<&str>,
+        tenant_id: &str,
     ) -> Result<StoreModel, String> {
         let now = Utc::now();
         let store = StoreActiveModel {
             id: Set(Uuid::new_v4()),
+            tenant_id: Set(tenant_id.to_owned()),
             name: Set(name.to_owned()),
             description: Set(description.map(|d| d.to_owned())),
             logo_url: Set(logo_url.map(|l| l.to_owned())),
@@ -35,10 +172,35 @@ impl Store {
             contact_phone: Set(contact_phone.map(|p| p.to_owned())),
             contact_email: Set(contact_email.map(|e| e.to_owned())),
             contact_whatsapp: Set(contact_whatsapp.map(|w| w.to_owned())),
+            show_phone: Set(true),
+            show_whatsapp: Set(true),
+            show_email: Set(true),
+            embed_enabled: Set(false),
+            media_bytes_used: Set(0),
+            media_uploads_today: Set(0),
+            media_uploads_day: Set(None),
+            media_quota_bytes: Set(None),
+            media_daily_uploads: Set(None),
+            media_images_per_product: Set(None),
             owner_device_id: Set(owner_device_id.map(|o| o.to_owned())),
             is_verified: Set(false),
             rating: Set(None),
+            store_rating: Set(None),
+            store_review_count: Set(0),
             total_products: Set(0),
+            default_return_policy: Set(non_blank(default_return_policy)),
+            default_pickup_available: Set(true),
+            default_delivery_available: Set(false),
+            default_delivery_fee: Set(None),
+            default_delivery_estimated_days: Set(None),
+            is_paused: Set(false),
+            paused_until: Set(None),
+            pause_message: Set(None),
+            auto_response_enabled: Set(false),
+            auto_response_message: Set(None),
+            auto_response_trigger: Set(None),
+            trust_score: Set(None),
+            first_shared_at: Set(None),
             created_at: Set(now),
             updated_at: Set(now),
         };
@@ -62,25 +224,71 @@ impl Store {
         Ok(store)
     }
 
-    pub async fn list(db: &DatabaseConnection) -> Result<Vec<StoreModel>, String> {
-        let stores = StoreEntity::find()
+    /// Stores of `tenant_id` among `ids`, in one query; other ids are skipped
+    pub async fn get_many(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        ids: &[Uuid],
+    ) -> Result<Vec<StoreModel>, String> {
+        StoreEntity::find()
+            .filter(store::Column::Id.is_in(ids.iter().copied()))
+            .for_tenant(tenant_id)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch stores: {:?}", e);
+                "Failed to fetch stores. Please try again later.".to_string()
+            })
+    }
+
+    /// One page of the tenant's stores in `sort` order and how many there
+    /// are in all; `page` starts at 1
+    pub async fn list(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        sort: StoreSort,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<StoreModel>, u64), String> {
+        let query = match sort {
+            StoreSort::Newest => StoreEntity::find(),
+            // Unscored stores (NULL) last, on every backend
+            StoreSort::TrustScore => StoreEntity::find()
+                .order_by_asc(Expr::col(store::Column::TrustScore).is_null())
+                .order_by_desc(store::Column::TrustScore),
+        };
+        let query = query
+            .for_tenant(tenant_id)
+            .order_by_desc(store::Column::CreatedAt)
+            // Ties in the sort would otherwise shift between pages
+            .order_by_asc(store::Column::Id);
+        fetch_page(db, query, page, per_page).await.map_err(|e| {
+            error!("Failed to list stores: {:?}", e);
+            "Failed to list stores. Please try again later.".to_string()
+        })
+    }
+
+    /// Every store of every tenant, for background jobs
+    pub async fn list_all_tenants(db: &DatabaseConnection) -> Result<Vec<StoreModel>, String> {
+        StoreEntity::find()
             .order_by_desc(store::Column::CreatedAt)
             .all(db)
             .await
             .map_err(|e| {
                 error!("Failed to list stores: {:?}", e);
                 "Failed to list stores. Please try again later.".to_string()
-            })?;
-        Ok(stores)
+            })
     }
 
     /// List stores owned by a specific device (seller flow)
     pub async fn list_by_owner(
         db: &DatabaseConnection,
+        tenant_id: &str,
         owner_device_id: &str,
     ) -> Result<Vec<StoreModel>, String> {
         let stores = StoreEntity::find()
             .filter(store::Column::OwnerDeviceId.eq(owner_device_id.to_owned()))
+            .for_tenant(tenant_id)
             .order_by_desc(store::Column::CreatedAt)
             .all(db)
             .await
@@ -94,6 +302,29 @@ impl Store {
         Ok(stores)
     }
 
+    /// One page of [`Store::list_by_owner`] and how many there are in all;
+    /// `page` starts at 1
+    pub async fn list_page_by_owner(
+        db: &DatabaseConnection,
+        tenant_id: &str,
+        owner_device_id: &str,
+        page: u64,
+        per_page: u64,
+    ) -> Result<(Vec<StoreModel>, u64), String> {
+        let query = StoreEntity::find()
+            .filter(store::Column::OwnerDeviceId.eq(owner_device_id.to_owned()))
+            .for_tenant(tenant_id)
+            .order_by_desc(store::Column::CreatedAt)
+            .order_by_asc(store::Column::Id);
+        fetch_page(db, query, page, per_page).await.map_err(|e| {
+            error!(
+                "Failed to list stores for owner {}: {:?}",
+                owner_device_id, e
+            );
+            "Failed to list stores. Please try again later.".to_string()
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn update(
         db: &DatabaseConnection,
@@ -105,6 +336,9 @@ impl Store {
         contact_phone: Option<&str>,
         contact_email: Option<&str>,
         contact_whatsapp: Option<&str>,
+        default_return_policy: Option<&str>,
+        visibility: ContactVisibility,
+        origin: ChangeOrigin<'_>,
     ) -> Result<StoreModel, String> {
         let store = StoreEntity::find_by_id(id)
             .one(db)
@@ -115,7 +349,7 @@ impl Store {
             })?
             .ok_or_else(|| "Store not found.".to_string())?;
 
-        let mut active: StoreActiveModel = store.into();
+        let mut active: StoreActiveModel = store.clone().into();
         active.name = Set(name.to_owned());
         active.description = Set(description.map(|d| d.to_owned()));
         active.logo_url = Set(logo_url.map(|l| l.to_owned()));
@@ -123,16 +357,251 @@ impl Store {
         active.contact_phone = Set(contact_phone.map(|p| p.to_owned()));
         active.contact_email = Set(contact_email.map(|e| e.to_owned()));
         active.contact_whatsapp = Set(contact_whatsapp.map(|w| w.to_owned()));
+        active.default_return_policy = Set(non_blank(default_return_policy));
+        if let Some(show) = visibility.show_phone {
+            active.show_phone = Set(show);
+        }
+        if let Some(show) = visibility.show_whatsapp {
+            active.show_whatsapp = Set(show);
+        }
+        if let Some(show) = visibility.show_email {
+            active.show_email = Set(show);
+        }
         active.updated_at = Set(Utc::now());
 
-        let res = active.update(db).await.map_err(|e| {
+        let fail = |e: sea_orm::DbErr| {
             error!("Failed to update store {}: {:?}", id, e);
             "Failed to update store. Please try again later.".to_string()
-        })?;
+        };
+        let txn = db.begin().await.map_err(fail)?;
+        let res = active.update(&txn).await.map_err(fail)?;
+        AuditLog::record_store(&txn, &store, &res, origin).await?;
+        txn.commit().await.map_err(fail)?;
         debug!("Store updated: {:?}", res);
         Ok(res)
     }
 
+    /// Put the store in vacation mode until `until`, or until resumed by hand
+    pub async fn pause(
+        db: &DatabaseConnection,
+        store: StoreModel,
+        until: Option<DateTime<Utc>>,
+        message: Option<&str>,
+    ) -> Result<StoreModel, String> {
+        let id = store.id;
+        let mut active: StoreActiveModel = store.into();
+        active.is_paused = Set(true);
+        active.paused_until = Set(until);
+        active.pause_message = Set(non_blank(message));
+        active.updated_at = Set(Utc::now());
+        let res = active.update(db).await.map_err(|e| {
+            error!("Failed to pause store {}: {:?}", id, e);
+            "Failed to pause store. Please try again later.".to_string()
+        })?;
+        debug!("Store paused: {:?}", res);
+        Ok(res)
+    }
+
+    pub async fn resume(db: &DatabaseConnection, store: StoreModel) -> Result<StoreModel, String> {
+        let id = store.id;
+        let mut active: StoreActiveModel = store.into();
+        active.is_paused = Set(false);
+        active.paused_until = Set(None);
+        active.pause_message = Set(None);
+        active.updated_at = Set(Utc::now());
+        let res = active.update(db).await.map_err(|e| {
+            error!("Failed to resume store {}: {:?}", id, e);
+            "Failed to resume store. Please try again later.".to_string()
+        })?;
+        debug!("Store resumed: {:?}", res);
+        Ok(res)
+    }
+
+    pub async fn set_auto_response(
+        db: &DatabaseConnection,
+        store: StoreModel,
+        auto_response: &AutoResponse,
+    ) -> Result<StoreModel, String> {
+        let id = store.id;
+        let mut active: StoreActiveModel = store.into();
+        active.auto_response_enabled = Set(auto_response.enabled);
+        active.auto_response_message = Set(non_blank(auto_response.message.as_deref()));
+        active.auto_response_trigger = Set(auto_response.trigger.map(|t| t.as_str().to_string()));
+        active.updated_at = Set(Utc::now());
+        active.update(db).await.map_err(|e| {
+            error!("Failed to update auto-response of store {}: {:?}", id, e);
+            "Failed to update store. Please try again later.".to_string()
+        })
+    }
+
+    pub async fn set_embed_enabled(
+        db: &DatabaseConnection,
+        store: StoreModel,
+        enabled: bool,
+    ) -> Result<StoreModel, String> {
+        let id = store.id;
+        let mut active: StoreActiveModel = store.into();
+        active.embed_enabled = Set(enabled);
+        active.updated_at = Set(Utc::now());
+        active.update(db).await.map_err(|e| {
+            error!("Failed to update embed setting of store {}: {:?}", id, e);
+            "Failed to update store. Please try again later.".to_string()
+        })
+    }
+
+    /// Resume every store whose `paused_until` has passed, returning them
+    pub async fn resume_due(
+        db: &DatabaseConnection,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<StoreModel>, String> {
+        StoreEntity::update_many()
+            .col_expr(store::Column::IsPaused, Expr::value(false))
+            .col_expr(
+                store::Column::PausedUntil,
+                Expr::value(Option::<DateTime<Utc>>::None),
+            )
+            .col_expr(
+                store::Column::PauseMessage,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(store::Column::UpdatedAt, Expr::value(now))
+            .filter(store::Column::IsPaused.eq(true))
+            .filter(store::Column::PausedUntil.lte(now))
+            .exec_with_returning(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to resume paused stores: {:?}", e);
+                "Failed to resume paused stores.".to_string()
+            })
+    }
+
+    /// Store a recomputed trust score. Derived data, so `updated_at` is left alone.
+    pub async fn set_trust_score(
+        db: &DatabaseConnection,
+        id: Uuid,
+        score: f64,
+    ) -> Result<(), String> {
+        StoreEntity::update_many()
+            .col_expr(store::Column::TrustScore, Expr::value(score))
+            .filter(store::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to update trust score of store {}: {:?}", id, e);
+                "Failed to update trust score.".to_string()
+            })?;
+        Ok(())
+    }
+
+    /// Push the store's current default policy to every product that inherits it.
+    ///
+    /// Products with their own policy are left untouched. Returns the number of
+    /// products updated.
+    pub async fn apply_default_return_policy(
+        db: &DatabaseConnection,
+        store: &StoreModel,
+    ) -> Result<u64, String> {
+        let source = if store.default_return_policy.is_some() {
+            "store_default"
+        } else {
+            "none"
+        };
+        let terms = store
+            .default_return_policy
+            .as_deref()
+            .map(ReturnTerms::from_text)
+            .unwrap_or_default();
+        let res = ProductEntity::update_many()
+            .col_expr(
+                product::Column::ReturnPolicy,
+                Expr::value(store.default_return_policy.clone()),
+            )
+            .col_expr(product::Column::ReturnPolicySource, Expr::value(source))
+            .col_expr(
+                product::Column::ReturnsAccepted,
+                Expr::value(terms.returns_accepted),
+            )
+            .col_expr(
+                product::Column::ReturnWindowDays,
+                Expr::value(terms.window_days),
+            )
+            .col_expr(
+                product::Column::ReturnConditions,
+                Expr::value(terms.conditions),
+            )
+            .col_expr(product::Column::UpdatedAt, Expr::value(Utc::now()))
+            .filter(product::Column::StoreId.eq(store.id))
+            .filter(product::Column::ReturnPolicySource.is_in(["store_default", "none"]))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to apply default return policy for store {}: {:?}",
+                    store.id, e
+                );
+                "Failed to update product return policies. Please try again later.".to_string()
+            })?;
+        debug!(
+            "Applied default return policy of store {} to {} products",
+            store.id, res.rows_affected
+        );
+        Ok(res.rows_affected)
+    }
+
+    /// Change the store's default delivery options and copy them to every
+    /// product that inherits them, in one transaction. Products with options
+    /// of their own are left untouched.
+    pub async fn set_delivery_defaults(
+        db: &DatabaseConnection,
+        store: StoreModel,
+        defaults: DeliveryOptions,
+    ) -> Result<StoreModel, String> {
+        let id = store.id;
+        let fail = |e: sea_orm::DbErr| {
+            error!("Failed to set delivery defaults of store {}: {:?}", id, e);
+            "Failed to update delivery options. Please try again later.".to_string()
+        };
+        let now = Utc::now();
+        let mut active: StoreActiveModel = store.into();
+        active.default_pickup_available = Set(defaults.pickup_available);
+        active.default_delivery_available = Set(defaults.delivery_available);
+        active.default_delivery_fee = Set(defaults.delivery_fee);
+        active.default_delivery_estimated_days = Set(defaults.estimated_days);
+        active.updated_at = Set(now);
+
+        let txn = db.begin().await.map_err(fail)?;
+        let res = active.update(&txn).await.map_err(fail)?;
+        let products = ProductEntity::update_many()
+            .col_expr(
+                product::Column::PickupAvailable,
+                Expr::value(defaults.pickup_available),
+            )
+            .col_expr(
+                product::Column::DeliveryAvailable,
+                Expr::value(defaults.delivery_available),
+            )
+            .col_expr(
+                product::Column::DeliveryFeeOverride,
+                Expr::value(defaults.delivery_fee),
+            )
+            .col_expr(
+                product::Column::DeliveryEstimatedDays,
+                Expr::value(defaults.estimated_days),
+            )
+            .col_expr(product::Column::UpdatedAt, Expr::value(now))
+            .filter(product::Column::StoreId.eq(id))
+            .filter(product::Column::DeliveryOptionsSource.eq("store_default"))
+            .exec(&txn)
+            .await
+            .map_err(fail)?;
+        txn.commit().await.map_err(fail)?;
+        debug!(
+            "Applied delivery defaults of store {} to {} products",
+            id, products.rows_affected
+        );
+        Ok(res)
+    }
+
     pub async fn delete(db: &DatabaseConnection, id: Uuid) -> Result<(), String> {
         let store = StoreEntity::find_by_id(id)
             .one(db)
@@ -143,12 +612,218 @@ impl Store {
             })?
             .ok_or_else(|| "Store not found.".to_string())?;
 
+        let txn = db.begin().await.map_err(|e| {
+            error!("Failed to start transaction for store {}: {:?}", id, e);
+            "Failed to delete store. Please try again later.".to_string()
+        })?;
+        // Products go with the store (ON DELETE CASCADE), so they need tombstones too
+        let product_ids: Vec<Uuid> = ProductEntity::find()
+            .select_only()
+            .column(product::Column::Id)
+            .filter(product::Column::StoreId.eq(id))
+            .into_tuple()
+            .all(&txn)
+            .await
+            .map_err(|e| {
+                error!("Failed to list products of store {}: {:?}", id, e);
+                "Failed to delete store. Please try again later.".to_string()
+            })?;
+
         let active: StoreActiveModel = store.into();
-        active.delete(db).await.map_err(|e| {
+        active.delete(&txn).await.map_err(|e| {
             error!("Failed to delete store {}: {:?}", id, e);
             "Failed to delete store. Please try again later.".to_string()
         })?;
+        for product_id in product_ids {
+            Tombstone::record(&txn, TombstoneKind::Product, product_id, Some(id)).await?;
+        }
+        Tombstone::record(&txn, TombstoneKind::Store, id, Some(id)).await?;
+        txn.commit().await.map_err(|e| {
+            error!("Failed to commit deletion of store {}: {:?}", id, e);
+            "Failed to delete store. Please try again later.".to_string()
+        })?;
         debug!("Store deleted: {}", id);
         Ok(())
     }
 }
+
+fn non_blank(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn store(is_paused: bool, paused_until: Option<DateTime<Utc>>) -> StoreModel {
+        let now = Utc::now();
+        StoreModel {
+            id: Uuid::new_v4(),
+            tenant_id: "default".to_string(),
+            name: "Mama Ngono".to_string(),
+            description: None,
+            logo_url: None,
+            location: None,
+            contact_phone: None,
+            contact_email: None,
+            contact_whatsapp: None,
+            show_phone: true,
+            show_whatsapp: true,
+            show_email: true,
+            embed_enabled: false,
+            media_bytes_used: 0,
+            media_uploads_today: 0,
+            media_uploads_day: None,
+            media_quota_bytes: None,
+            media_daily_uploads: None,
+            media_images_per_product: None,
+            owner_device_id: None,
+            is_verified: false,
+            rating: None,
+            store_rating: None,
+            store_review_count: 0,
+            total_products: 0,
+            default_return_policy: None,
+            default_pickup_available: true,
+            default_delivery_available: false,
+            default_delivery_fee: None,
+            default_delivery_estimated_days: None,
+            is_paused,
+            paused_until,
+            pause_message: None,
+            auto_response_enabled: false,
+            auto_response_message: None,
+            auto_response_trigger: None,
+            trust_score: None,
+            first_shared_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_pause_ends_at_paused_until() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 20, 9, 0, 0).unwrap();
+        let back = now + Duration::days(10);
+
+        assert!(!is_paused(&store(false, None), now));
+        assert!(is_paused(&store(true, None), now));
+        assert!(is_paused(&store(true, Some(back)), now));
+        assert!(!is_paused(&store(true, Some(back)), back));
+    }
+
+    #[test]
+    fn test_paused_condition_matches_is_paused() {
+        let sql = StoreEntity::find()
+            .filter(paused_condition(Utc::now()))
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#""is_paused" = TRUE"#), "{sql}");
+        assert!(sql.contains(r#""paused_until" IS NULL OR"#), "{sql}");
+        assert!(sql.contains(r#""paused_until" > '"#), "{sql}");
+    }
+
+    #[tokio::test]
+    async fn test_delivery_defaults_reach_inheriting_products_only() {
+        use crate::db::delivery::DeliveryOptionsInput;
+        use crate::db::products::Product;
+        use crate::db::testing;
+
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let create = |delivery: Option<DeliveryOptionsInput>| {
+            Product::create(
+                &db,
+                store_id,
+                None,
+                "Okok leaves",
+                None,
+                1500.0,
+                "XAF",
+                4,
+                None,
+                None,
+                None,
+                delivery,
+                None,
+                false,
+                None,
+                None,
+                serde_json::json!({}),
+            )
+        };
+        let inherits = create(None).await.unwrap();
+        let own = create(Some(DeliveryOptionsInput {
+            estimated_days: Some(1),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        assert_eq!(
+            DeliveryOptions::of_product(&inherits),
+            DeliveryOptions::default()
+        );
+        assert_eq!(own.delivery_options_source, "product");
+
+        let defaults = DeliveryOptions {
+            pickup_available: false,
+            delivery_available: true,
+            delivery_fee: Some(1000.0),
+            estimated_days: Some(3),
+        };
+        let store = Store::get(&db, store_id).await.unwrap();
+        let store = Store::set_delivery_defaults(&db, store, defaults)
+            .await
+            .unwrap();
+        assert_eq!(DeliveryOptions::store_default(&store), defaults);
+
+        let inherits = Product::get(&db, inherits.id).await.unwrap();
+        assert_eq!(DeliveryOptions::of_product(&inherits), defaults);
+        let own = Product::get(&db, own.id).await.unwrap();
+        assert_eq!(
+            DeliveryOptions::of_product(&own),
+            DeliveryOptions {
+                estimated_days: Some(1),
+                ..DeliveryOptions::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_lists_are_paged_in_the_query() {
+        use crate::db::testing;
+
+        let db = testing::sqlite().await;
+        for owner in ["seller-1", "seller-1", "seller-1", "seller-2"] {
+            testing::seed_store(&db, owner).await;
+        }
+
+        let (first, total) = Store::list(&db, "default", StoreSort::Newest, 1, 3)
+            .await
+            .unwrap();
+        assert_eq!((first.len(), total), (3, 4));
+        let (second, _) = Store::list(&db, "default", StoreSort::Newest, 2, 3)
+            .await
+            .unwrap();
+        assert_eq!(second.len(), 1);
+        assert!(first.iter().all(|store| store.id != second[0].id));
+        let (past_end, total) = Store::list(&db, "default", StoreSort::Newest, u64::MAX, 3)
+            .await
+            .unwrap();
+        assert_eq!((past_end.len(), total), (0, 4));
+
+        let (mine, total) = Store::list_page_by_owner(&db, "default", "seller-1", 1, 2)
+            .await
+            .unwrap();
+        assert_eq!((mine.len(), total), (2, 3));
+        let (others, total) = Store::list(&db, "elsewhere", StoreSort::Newest, 1, 3)
+            .await
+            .unwrap();
+        assert_eq!((others.len(), total), (0, 0));
+    }
+}
@@ -0,0 +1,642 @@
+use crate::db::products::visible_condition;
+use crate::entity::product::{self, Entity as ProductEntity, Model as ProductModel};
+use crate::entity::store::{self, Entity as StoreEntity, Model as StoreModel};
+use crate::entity::tombstone::{
+    self, ActiveModel as TombstoneActiveModel, Entity as TombstoneEntity, Model as TombstoneModel,
+};
+use crate::tenant::ForTenant;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// Kind of hard-deleted row a tombstone stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TombstoneKind {
+    Product,
+    Store,
+}
+
+impl TombstoneKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TombstoneKind::Product => "product",
+            TombstoneKind::Store => "store",
+        }
+    }
+}
+
+pub struct Tombstone;
+
+impl Tombstone {
+    /// Remember a deletion; call inside the transaction that deletes the row
+    pub async fn record<C: ConnectionTrait>(
+        conn: &C,
+        kind: TombstoneKind,
+        entity_id: Uuid,
+        store_id: Option<Uuid>,
+    ) -> Result<(), String> {
+        let tombstone = TombstoneActiveModel {
+            id: Set(Uuid::new_v4()),
+            entity_type: Set(kind.as_str().to_owned()),
+            entity_id: Set(entity_id),
+            store_id: Set(store_id),
+            deleted_at: Set(Utc::now()),
+        };
+        TombstoneEntity::insert(tombstone)
+            .exec_without_returning(conn)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to record tombstone for {} {}: {:?}",
+                    kind.as_str(),
+                    entity_id,
+                    e
+                );
+                "Failed to record deletion. Please try again later.".to_string()
+            })?;
+        Ok(())
+    }
+}
+
+/// Change streams, in the order a sync pass walks them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SyncStream {
+    Stores,
+    Products,
+    Tombstones,
+}
+
+const SYNC_STREAMS: [SyncStream; 3] = [
+    SyncStream::Stores,
+    SyncStream::Products,
+    SyncStream::Tombstones,
+];
+
+impl SyncStream {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyncStream::Stores => "stores",
+            SyncStream::Products => "products",
+            SyncStream::Tombstones => "tombstones",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        SYNC_STREAMS.into_iter().find(|s| s.as_str() == raw)
+    }
+}
+
+/// Where a paged sync pass stopped.
+///
+/// `until` is fixed by the first page so every page of a pass sees the same
+/// upper bound; rows changed mid-pass are picked up by the next pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncCursor {
+    pub until: DateTime<Utc>,
+    pub stream: SyncStream,
+    /// Last `(changed_at, id)` already returned from `stream`
+    pub after: Option<(DateTime<Utc>, Uuid)>,
+}
+
+impl SyncCursor {
+    /// Opaque continuation token handed to clients
+    pub fn encode(&self) -> String {
+        let ts = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        let after = match self.after {
+            Some((changed_at, id)) => format!("{}|{}", ts(changed_at), id),
+            None => "|".to_string(),
+        };
+        let raw = format!("{}|{}|{}", ts(self.until), self.stream.as_str(), after);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Result<Self, String> {
+        let invalid = || "Invalid sync cursor".to_string();
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let ts = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| invalid())
+        };
+        let parts: Vec<&str> = raw.split('|').collect();
+        let [until, stream, changed_at, id] = parts[..] else {
+            return Err(invalid());
+        };
+        let after = match (changed_at, id) {
+            ("", "") => None,
+            (changed_at, id) => {
+                Some((ts(changed_at)?, Uuid::parse_str(id).map_err(|_| invalid())?))
+            }
+        };
+        Ok(Self {
+            until: ts(until)?,
+            stream: SyncStream::parse(stream).ok_or_else(invalid)?,
+            after,
+        })
+    }
+}
+
+/// A changed row; deletions arrive as tombstones
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncRecord {
+    Store(StoreModel),
+    Product(ProductModel),
+    Tombstone(TombstoneModel),
+}
+
+impl SyncRecord {
+    /// Ordering key within the record's stream
+    fn key(&self) -> (DateTime<Utc>, Uuid) {
+        match self {
+            SyncRecord::Store(s) => (s.updated_at, s.id),
+            SyncRecord::Product(p) => (p.updated_at, p.id),
+            SyncRecord::Tombstone(t) => (t.deleted_at, t.id),
+        }
+    }
+}
+
+/// Bounds of one stream query: changed in `(since, until]`, strictly after `after`
+#[derive(Debug, Clone, Copy)]
+pub struct SyncWindow {
+    pub since: Option<DateTime<Utc>>,
+    pub until: DateTime<Utc>,
+    pub store_id: Option<Uuid>,
+    pub after: Option<(DateTime<Utc>, Uuid)>,
+}
+
+/// Something that can list changed rows of a stream, ordered by their key
+#[async_trait::async_trait]
+pub trait ChangeSource {
+    async fn changes(
+        &self,
+        stream: SyncStream,
+        window: &SyncWindow,
+        limit: u64,
+    ) -> Result<Vec<SyncRecord>, String>;
+}
+
+/// One page of a sync pass
+#[derive(Debug, Default)]
+pub struct SyncPage {
+    pub stores: Vec<StoreModel>,
+    pub products: Vec<ProductModel>,
+    pub tombstones: Vec<TombstoneModel>,
+    /// Watermark to send as `since` once the pass is complete
+    pub server_time: DateTime<Utc>,
+    /// Set when more changes remain in this pass
+    pub next_cursor: Option<SyncCursor>,
+}
+
+impl SyncPage {
+    fn push(&mut self, record: SyncRecord) {
+        match record {
+            SyncRecord::Store(s) => self.stores.push(s),
+            SyncRecord::Product(p) => self.products.push(p),
+            SyncRecord::Tombstone(t) => self.tombstones.push(t),
+        }
+    }
+}
+
+/// Collect up to `limit` changes, walking stores, products then tombstones
+pub async fn sync_page<S: ChangeSource + Sync>(
+    source: &S,
+    since: Option<DateTime<Utc>>,
+    store_id: Option<Uuid>,
+    cursor: Option<SyncCursor>,
+    limit: u64,
+    now: DateTime<Utc>,
+) -> Result<SyncPage, String> {
+    let until = cursor.map_or(now, |c| c.until);
+    let mut page = SyncPage {
+        server_time: until,
+        ..Default::default()
+    };
+    let mut remaining = limit.max(1);
+
+    for stream in SYNC_STREAMS {
+        if cursor.is_some_and(|c| stream < c.stream) {
+            continue;
+        }
+        let window = SyncWindow {
+            since,
+            until,
+            store_id,
+            after: cursor.filter(|c| c.stream == stream).and_then(|c| c.after),
+        };
+        // One extra row tells us whether the stream continues past this page
+        let mut batch = source.changes(stream, &window, remaining + 1).await?;
+        if batch.len() as u64 > remaining {
+            batch.truncate(remaining as usize);
+            page.next_cursor = Some(SyncCursor {
+                until,
+                stream,
+                after: batch.last().map(SyncRecord::key).or(window.after),
+            });
+        }
+        remaining -= batch.len() as u64;
+        for record in batch {
+            page.push(record);
+        }
+        if page.next_cursor.is_some() {
+            break;
+        }
+    }
+    Ok(page)
+}
+
+/// Change source backed by the database. Stores and products are limited to
+/// `tenant_id`; tombstones only carry ids and are not.
+pub struct DbChangeSource<'a> {
+    pub db: &'a DatabaseConnection,
+    pub tenant_id: &'a str,
+}
+
+/// `(changed_at, id)` lies inside `window`, as a SQL condition
+fn window_condition<T: ColumnTrait, I: ColumnTrait>(
+    changed_at: T,
+    id: I,
+    window: &SyncWindow,
+) -> Condition {
+    let mut condition = Condition::all().add(changed_at.lte(window.until));
+    if let Some(since) = window.since {
+        condition = condition.add(changed_at.gt(since));
+    }
+    if let Some((after_at, after_id)) = window.after {
+        condition = condition.add(
+            Condition::any().add(changed_at.gt(after_at)).add(
+                Condition::all()
+                    .add(changed_at.eq(after_at))
+                    .add(id.gt(after_id)),
+            ),
+        );
+    }
+    condition
+}
+
+#[async_trait::async_trait]
+impl ChangeSource for DbChangeSource<'_> {
+    async fn changes(
+        &self,
+        stream: SyncStream,
+        window: &SyncWindow,
+        limit: u64,
+    ) -> Result<Vec<SyncRecord>, String> {
+        let log = |e: sea_orm::DbErr| {
+            error!("Failed to load {} changes: {:?}", stream.as_str(), e);
+            "Failed to load changes. Please try again later.".to_string()
+        };
+        let records = match stream {
+            SyncStream::Stores => {
+                let mut query = StoreEntity::find()
+                    .filter(window_condition(
+                        store::Column::UpdatedAt,
+                        store::Column::Id,
+                        window,
+                    ))
+                    .for_tenant(self.tenant_id);
+                if let Some(store_id) = window.store_id {
+                    query = query.filter(store::Column::Id.eq(store_id));
+                }
+                query
+                    .order_by_asc(store::Column::UpdatedAt)
+                    .order_by_asc(store::Column::Id)
+                    .limit(limit)
+                    .all(self.db)
+                    .await
+                    .map_err(log)?
+                    .into_iter()
+                    .map(SyncRecord::Store)
+                    .collect()
+            }
+            SyncStream::Products => {
+                // Scheduled products stay out until the publisher bumps `updated_at`
+                let mut query = ProductEntity::find()
+                    .filter(window_condition(
+                        product::Column::UpdatedAt,
+                        product::Column::Id,
+                        window,
+                    ))
+                    .filter(visible_condition(window.until))
+                    .for_tenant(self.tenant_id);
+                if let Some(store_id) = window.store_id {
+                    query = query.filter(product::Column::StoreId.eq(store_id));
+                }
+                query
+                    .order_by_asc(product::Column::UpdatedAt)
+                    .order_by_asc(product::Column::Id)
+                    .limit(limit)
+                    .all(self.db)
+                    .await
+                    .map_err(log)?
+                    .into_iter()
+                    .map(SyncRecord::Product)
+                    .collect()
+            }
+            SyncStream::Tombstones => {
+                let mut query = TombstoneEntity::find().filter(window_condition(
+                    tombstone::Column::DeletedAt,
+                    tombstone::Column::Id,
+                    window,
+                ));
+                if let Some(store_id) = window.store_id {
+                    query = query.filter(tombstone::Column::StoreId.eq(store_id));
+                }
+                query
+                    .order_by_asc(tombstone::Column::DeletedAt)
+                    .order_by_asc(tombstone::Column::Id)
+                    .limit(limit)
+                    .all(self.db)
+                    .await
+                    .map_err(log)?
+                    .into_iter()
+                    .map(SyncRecord::Tombstone)
+                    .collect()
+            }
+        };
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use std::sync::Mutex;
+
+    /// In-memory tables mirroring what `DbChangeSource` queries
+    #[derive(Default)]
+    struct MemorySource {
+        records: Mutex<Vec<SyncRecord>>,
+    }
+
+    impl MemorySource {
+        fn upsert(&self, record: SyncRecord) {
+            let mut records = self.records.lock().unwrap();
+            let id = entity_id(&record);
+            records.retain(|r| entity_id(r) != id);
+            records.push(record);
+        }
+
+        fn edit_product(&self, id: Uuid, at: DateTime<Utc>) {
+            let mut records = self.records.lock().unwrap();
+            for record in records.iter_mut() {
+                if let SyncRecord::Product(p) = record {
+                    if p.id == id {
+                        p.updated_at = at;
+                    }
+                }
+            }
+        }
+
+        fn delete_product(&self, id: Uuid, store_id: Uuid, at: DateTime<Utc>) {
+            let mut records = self.records.lock().unwrap();
+            records.retain(|r| entity_id(r) != id);
+            records.push(SyncRecord::Tombstone(TombstoneModel {
+                id: Uuid::new_v4(),
+                entity_type: TombstoneKind::Product.as_str().to_string(),
+                entity_id: id,
+                store_id: Some(store_id),
+                deleted_at: at,
+            }));
+        }
+    }
+
+    fn entity_id(record: &SyncRecord) -> Uuid {
+        match record {
+            SyncRecord::Store(s) => s.id,
+            SyncRecord::Product(p) => p.id,
+            SyncRecord::Tombstone(t) => t.id,
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ChangeSource for MemorySource {
+        async fn changes(
+            &self,
+            stream: SyncStream,
+            window: &SyncWindow,
+            limit: u64,
+        ) -> Result<Vec<SyncRecord>, String> {
+            let mut matching: Vec<SyncRecord> = self
+                .records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r| {
+                    let (in_stream, store_id) = match r {
+                        SyncRecord::Store(s) => (stream == SyncStream::Stores, Some(s.id)),
+                        SyncRecord::Product(p) => {
+                            (stream == SyncStream::Products, Some(p.store_id))
+                        }
+                        SyncRecord::Tombstone(t) => (stream == SyncStream::Tombstones, t.store_id),
+                    };
+                    let key = r.key();
+                    in_stream
+                        && window.store_id.is_none_or(|id| store_id == Some(id))
+                        && window.since.is_none_or(|since| key.0 > since)
+                        && key.0 <= window.until
+                        && window.after.is_none_or(|after| key > after)
+                })
+                .cloned()
+                .collect();
+            matching.sort_by_key(SyncRecord::key);
+            matching.truncate(limit as usize);
+            Ok(matching)
+        }
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 1, 12, 0, 0).unwrap() + Duration::seconds(seconds)
+    }
+
+    fn store(id: Uuid, updated_at: DateTime<Utc>) -> SyncRecord {
+        SyncRecord::Store(StoreModel {
+            id,
+            tenant_id: "default".to_string(),
+            name: "Mama Ngono".to_string(),
+            description: None,
+            logo_url: None,
+            location: None,
+            contact_phone: None,
+            contact_email: None,
+            contact_whatsapp: None,
+            show_phone: true,
+            show_whatsapp: true,
+            show_email: true,
+            embed_enabled: false,
+            media_bytes_used: 0,
+            media_uploads_today: 0,
+            media_uploads_day: None,
+            media_quota_bytes: None,
+            media_daily_uploads: None,
+            media_images_per_product: None,
+            owner_device_id: None,
+            is_verified: false,
+            rating: None,
+            store_rating: None,
+            store_review_count: 0,
+            total_products: 0,
+            default_return_policy: None,
+            default_pickup_available: true,
+            default_delivery_available: false,
+            default_delivery_fee: None,
+            default_delivery_estimated_days: None,
+            is_paused: false,
+            paused_until: None,
+            pause_message: None,
+            auto_response_enabled: false,
+            auto_response_message: None,
+            auto_response_trigger: None,
+            trust_score: None,
+            first_shared_at: None,
+            created_at: updated_at,
+            updated_at,
+        })
+    }
+
+    fn product(id: Uuid, store_id: Uuid, updated_at: DateTime<Utc>) -> SyncRecord {
+        SyncRecord::Product(ProductModel {
+            id,
+            store_id,
+            tenant_id: "default".to_string(),
+            sku: None,
+            name: "Wax print".to_string(),
+            description: None,
+            price: 5000.0,
+            currency: "XAF".to_string(),
+            sale_price: None,
+            sale_ends_at: None,
+            quantity_available: 3,
+            low_stock_threshold: None,
+            image_id: None,
+            category_id: None,
+            attributes: serde_json::json!({}),
+            return_policy: None,
+            return_policy_source: "none".to_string(),
+            returns_accepted: false,
+            return_window_days: None,
+            return_conditions: None,
+            pickup_available: true,
+            delivery_available: false,
+            delivery_fee_override: None,
+            delivery_estimated_days: None,
+            delivery_options_source: "store_default".to_string(),
+            publish_at: None,
+            is_published: true,
+            archived_at: None,
+            created_at: updated_at,
+            updated_at,
+        })
+    }
+
+    fn product_ids(page: &SyncPage) -> Vec<Uuid> {
+        page.products.iter().map(|p| p.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_two_sync_cycles_with_interleaved_edits_and_deletions() {
+        let source = MemorySource::default();
+        let store_id = Uuid::new_v4();
+        let (p1, p2, p3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        source.upsert(store(store_id, at(0)));
+        source.upsert(product(p1, store_id, at(1)));
+        source.upsert(product(p2, store_id, at(2)));
+        source.upsert(product(p3, store_id, at(3)));
+
+        // Cycle 1, first page: the store and one product fill the limit
+        let first = sync_page(&source, None, None, None, 2, at(10))
+            .await
+            .unwrap();
+        assert_eq!(first.stores.len(), 1);
+        assert_eq!(product_ids(&first), vec![p1]);
+        let cursor = first.next_cursor.expect("more changes pending");
+        let cursor = SyncCursor::decode(&cursor.encode()).unwrap();
+
+        // p2 is edited while the client is still paging
+        source.edit_product(p2, at(11));
+
+        let second = sync_page(&source, None, None, Some(cursor), 2, at(12))
+            .await
+            .unwrap();
+        // The watermark is pinned to the first page, so p2's new version waits
+        assert_eq!(second.server_time, at(10));
+        assert_eq!(product_ids(&second), vec![p3]);
+        assert!(second.tombstones.is_empty());
+        assert!(second.next_cursor.is_none());
+        let watermark = second.server_time;
+
+        // Between cycles p3 is deleted
+        source.delete_product(p3, store_id, at(13));
+
+        // Cycle 2 only sees what happened after the watermark
+        let delta = sync_page(&source, Some(watermark), None, None, 50, at(20))
+            .await
+            .unwrap();
+        assert!(delta.stores.is_empty());
+        assert_eq!(product_ids(&delta), vec![p2]);
+        assert_eq!(delta.tombstones.len(), 1);
+        assert_eq!(delta.tombstones[0].entity_id, p3);
+        assert_eq!(delta.tombstones[0].entity_type, "product");
+        assert_eq!(delta.server_time, at(20));
+        assert!(delta.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_page_scoped_to_store() {
+        let source = MemorySource::default();
+        let (mine, other) = (Uuid::new_v4(), Uuid::new_v4());
+        source.upsert(store(mine, at(0)));
+        source.upsert(store(other, at(0)));
+        source.upsert(product(Uuid::new_v4(), mine, at(1)));
+        source.upsert(product(Uuid::new_v4(), other, at(1)));
+        source.delete_product(Uuid::new_v4(), other, at(2));
+
+        let page = sync_page(&source, None, Some(mine), None, 50, at(5))
+            .await
+            .unwrap();
+        assert_eq!(page.stores.len(), 1);
+        assert_eq!(page.products.len(), 1);
+        assert!(page.products.iter().all(|p| p.store_id == mine));
+        assert!(page.tombstones.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_page_boundary_at_end_of_stream_continues_with_next_stream() {
+        let source = MemorySource::default();
+        let store_id = Uuid::new_v4();
+        source.upsert(store(store_id, at(0)));
+        source.upsert(product(Uuid::new_v4(), store_id, at(1)));
+
+        let first = sync_page(&source, None, None, None, 1, at(5))
+            .await
+            .unwrap();
+        assert_eq!(first.stores.len(), 1);
+        let cursor = first.next_cursor.expect("products still pending");
+        assert_eq!(cursor.stream, SyncStream::Products);
+        assert_eq!(cursor.after, None);
+
+        let second = sync_page(&source, None, None, Some(cursor), 1, at(6))
+            .await
+            .unwrap();
+        assert_eq!(second.products.len(), 1);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_cursor_round_trip_and_rejects_garbage() {
+        let cursor = SyncCursor {
+            until: at(0) + Duration::microseconds(123),
+            stream: SyncStream::Tombstones,
+            after: Some((at(-5), Uuid::new_v4())),
+        };
+        assert_eq!(SyncCursor::decode(&cursor.encode()), Ok(cursor));
+        assert!(SyncCursor::decode("not-a-cursor").is_err());
+        assert!(SyncCursor::decode(&URL_SAFE_NO_PAD.encode("x|stores||")).is_err());
+    }
+}
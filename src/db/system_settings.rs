@@ -0,0 +1,59 @@
+//! Key/value settings shared by every replica.
+
+use crate::entity::system_setting::{self, ActiveModel, Entity, Model};
+use chrono::Utc;
+use sea_orm::{sea_query::OnConflict, DatabaseConnection, EntityTrait, Set};
+use tracing::{debug, error};
+
+pub struct SystemSetting;
+
+impl SystemSetting {
+    pub async fn get(db: &DatabaseConnection, key: &str) -> Result<Option<Model>, String> {
+        Entity::find_by_id(key.to_owned())
+            .one(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to read system setting {}: {:?}", key, e);
+                "Failed to read system setting. Please try again later.".to_string()
+            })
+    }
+
+    /// Create or overwrite a setting
+    pub async fn put(
+        db: &DatabaseConnection,
+        key: &str,
+        value: serde_json::Value,
+        updated_by: &str,
+    ) -> Result<Model, String> {
+        let model = Model {
+            key: key.to_owned(),
+            value,
+            updated_by: Some(updated_by.to_owned()),
+            updated_at: Utc::now(),
+        };
+        let active = ActiveModel {
+            key: Set(model.key.clone()),
+            value: Set(model.value.clone()),
+            updated_by: Set(model.updated_by.clone()),
+            updated_at: Set(model.updated_at),
+        };
+        Entity::insert(active)
+            .on_conflict(
+                OnConflict::column(system_setting::Column::Key)
+                    .update_columns([
+                        system_setting::Column::Value,
+                        system_setting::Column::UpdatedBy,
+                        system_setting::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to save system setting {}: {:?}", key, e);
+                "Failed to save system setting. Please try again later.".to_string()
+            })?;
+        debug!(key, "System setting saved");
+        Ok(model)
+    }
+}
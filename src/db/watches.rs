@@ -0,0 +1,272 @@
+//! Buyers watching products for a price drop
+
+use crate::entity::product::{Entity as ProductEntity, Model as ProductModel};
+use crate::entity::product_watch::{self, ActiveModel, Entity as WatchEntity, Model};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// A watcher hears about one product at most this often
+pub const ALERT_COOLDOWN_HOURS: i64 = 24;
+
+pub struct ProductWatch;
+
+impl ProductWatch {
+    /// Start watching `product_id`, or change the target of an existing watch
+    pub async fn watch(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        watcher_id: &str,
+        target_price: Option<f64>,
+    ) -> Result<Model, String> {
+        let fail = |e: DbErr| {
+            error!("Failed to watch product {}: {:?}", product_id, e);
+            "Failed to watch product. Please try again later.".to_string()
+        };
+        if let Some(existing) = Self::find(db, product_id, watcher_id).await.map_err(fail)? {
+            let mut active: ActiveModel = existing.into();
+            active.target_price = Set(target_price);
+            return active.update(db).await.map_err(fail);
+        }
+        let id = Uuid::new_v4();
+        let watch = ActiveModel {
+            id: Set(id),
+            product_id: Set(product_id),
+            watcher_id: Set(watcher_id.to_string()),
+            target_price: Set(target_price),
+            last_notified_at: Set(None),
+            created_at: Set(Utc::now()),
+        };
+        WatchEntity::insert(watch)
+            .exec_without_returning(db)
+            .await
+            .map_err(fail)?;
+        WatchEntity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(fail)?
+            .ok_or_else(|| "Failed to watch product".to_string())
+    }
+
+    /// `false` when the buyer wasn't watching
+    pub async fn unwatch(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        watcher_id: &str,
+    ) -> Result<bool, String> {
+        let res = WatchEntity::delete_many()
+            .filter(product_watch::Column::ProductId.eq(product_id))
+            .filter(product_watch::Column::WatcherId.eq(watcher_id))
+            .exec(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to unwatch product {}: {:?}", product_id, e);
+                "Failed to unwatch product. Please try again later.".to_string()
+            })?;
+        Ok(res.rows_affected > 0)
+    }
+
+    pub async fn is_watching(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        watcher_id: &str,
+    ) -> Result<bool, String> {
+        WatchEntity::find()
+            .filter(product_watch::Column::ProductId.eq(product_id))
+            .filter(product_watch::Column::WatcherId.eq(watcher_id))
+            .count(db)
+            .await
+            .map(|count| count > 0)
+            .map_err(|e| {
+                error!("Failed to check watch on product {}: {:?}", product_id, e);
+                "Failed to load product".to_string()
+            })
+    }
+
+    /// A buyer's watches with their products, newest first
+    pub async fn list_for_watcher(
+        db: &DatabaseConnection,
+        watcher_id: &str,
+    ) -> Result<Vec<(Model, ProductModel)>, String> {
+        let rows = WatchEntity::find()
+            .filter(product_watch::Column::WatcherId.eq(watcher_id))
+            .order_by_desc(product_watch::Column::CreatedAt)
+            .find_also_related(ProductEntity)
+            .all(db)
+            .await
+            .map_err(|e| {
+                error!("Failed to list watches of {}: {:?}", watcher_id, e);
+                "Failed to list watched products. Please try again later.".to_string()
+            })?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(watch, product)| Some((watch, product?)))
+            .collect())
+    }
+
+    /// Watches to alert now that `product_id` went from `previous_price` to
+    /// `price`, marked as alerted. A rise or unchanged price alerts nobody;
+    /// a drop alerts watchers whose target it reached, or who set none,
+    /// unless they were alerted within [`ALERT_COOLDOWN_HOURS`].
+    pub async fn claim_alerts(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        previous_price: f64,
+        price: f64,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Model>, String> {
+        if price >= previous_price {
+            return Ok(Vec::new());
+        }
+        let fail = |e: DbErr| {
+            error!("Failed to claim price alerts for {}: {:?}", product_id, e);
+            "Failed to claim price alerts".to_string()
+        };
+        let due = Condition::all()
+            .add(product_watch::Column::ProductId.eq(product_id))
+            .add(
+                Condition::any()
+                    .add(product_watch::Column::TargetPrice.is_null())
+                    .add(product_watch::Column::TargetPrice.gte(price)),
+            )
+            .add(
+                Condition::any()
+                    .add(product_watch::Column::LastNotifiedAt.is_null())
+                    .add(
+                        product_watch::Column::LastNotifiedAt
+                            .lte(now - Duration::hours(ALERT_COOLDOWN_HOURS)),
+                    ),
+            );
+        let watches = WatchEntity::find()
+            .filter(due.clone())
+            .all(db)
+            .await
+            .map_err(fail)?;
+        // Each watch is claimed with the due check repeated, so concurrent
+        // price changes alert a watcher once
+        let mut claimed = Vec::with_capacity(watches.len());
+        for watch in watches {
+            let res = WatchEntity::update_many()
+                .col_expr(product_watch::Column::LastNotifiedAt, Expr::value(now))
+                .filter(product_watch::Column::Id.eq(watch.id))
+                .filter(due.clone())
+                .exec(db)
+                .await
+                .map_err(fail)?;
+            if res.rows_affected == 1 {
+                claimed.push(Model {
+                    last_notified_at: Some(now),
+                    ..watch
+                });
+            }
+        }
+        Ok(claimed)
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        watcher_id: &str,
+    ) -> Result<Option<Model>, DbErr> {
+        WatchEntity::find()
+            .filter(product_watch::Column::ProductId.eq(product_id))
+            .filter(product_watch::Column::WatcherId.eq(watcher_id))
+            .one(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+
+    #[tokio::test]
+    async fn test_alerts_follow_targets_and_cooldown() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        ProductWatch::watch(&db, product, "any-drop", None)
+            .await
+            .unwrap();
+        ProductWatch::watch(&db, product, "bargain", Some(4000.0))
+            .await
+            .unwrap();
+        let now = Utc::now();
+        let watchers = |watches: Vec<Model>| {
+            let mut ids: Vec<String> = watches.into_iter().map(|w| w.watcher_id).collect();
+            ids.sort();
+            ids
+        };
+
+        // A rise alerts nobody
+        let alerts = ProductWatch::claim_alerts(&db, product, 5000.0, 5500.0, now)
+            .await
+            .unwrap();
+        assert!(alerts.is_empty());
+
+        // A drop above the target only alerts the watcher without one
+        let alerts = ProductWatch::claim_alerts(&db, product, 5500.0, 4500.0, now)
+            .await
+            .unwrap();
+        assert_eq!(watchers(alerts), ["any-drop"]);
+
+        // Crossing the target alerts the other; the first is cooling down
+        let later = now + Duration::hours(1);
+        let alerts = ProductWatch::claim_alerts(&db, product, 4500.0, 3900.0, later)
+            .await
+            .unwrap();
+        assert_eq!(watchers(alerts), ["bargain"]);
+        let alerts = ProductWatch::claim_alerts(&db, product, 3900.0, 3500.0, later)
+            .await
+            .unwrap();
+        assert!(alerts.is_empty());
+
+        // A day on, both hear about the next drop
+        let next_day = later + Duration::hours(ALERT_COOLDOWN_HOURS);
+        let alerts = ProductWatch::claim_alerts(&db, product, 3500.0, 3400.0, next_day)
+            .await
+            .unwrap();
+        assert_eq!(watchers(alerts), ["any-drop", "bargain"]);
+    }
+
+    #[tokio::test]
+    async fn test_watch_updates_target_and_unwatch() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        let first = ProductWatch::watch(&db, product, "buyer-1", None)
+            .await
+            .unwrap();
+        let again = ProductWatch::watch(&db, product, "buyer-1", Some(3000.0))
+            .await
+            .unwrap();
+        assert_eq!(first.id, again.id);
+        assert_eq!(again.target_price, Some(3000.0));
+
+        let listed = ProductWatch::list_for_watcher(&db, "buyer-1")
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].1.id, product);
+        assert!(ProductWatch::is_watching(&db, product, "buyer-1")
+            .await
+            .unwrap());
+        assert!(!ProductWatch::is_watching(&db, product, "buyer-2")
+            .await
+            .unwrap());
+
+        assert!(ProductWatch::unwatch(&db, product, "buyer-1")
+            .await
+            .unwrap());
+        assert!(!ProductWatch::unwatch(&db, product, "buyer-1")
+            .await
+            .unwrap());
+        assert!(ProductWatch::list_for_watcher(&db, "buyer-1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Signing credential of an admin device; the secret itself is never kept
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "admin_credentials")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    /// Device ID of the admin the credential belongs to
+    pub relay_id: String,
+    pub label: String,
+    /// HMAC key derived from the secret, sealed with the field cipher; see
+    /// `crate::auth::signing`
+    #[serde(skip_serializing)]
+    pub signing_key_encrypted: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One write to a product or store, with the fields it changed
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    /// `product` or `store`
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    /// Store the entity belongs to, so a store's history covers its products
+    pub store_id: Uuid,
+    /// Device or API key that made the change; `None` for system writes
+    pub changed_by: Option<String>,
+    /// What made the change, e.g. "edit" or "inventory_sync"
+    pub source: String,
+    /// `[{field, old, new}]`, computed when the change was written
+    #[sea_orm(column_type = "JsonBinary")]
+    pub changes: Json,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// One component of a bundle: `quantity` units of a product per bundle sold
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "bundle_items")]
+#[schema(as = BundleItem)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub bundle_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub quantity: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product_bundle::Entity",
+        from = "Column::BundleId",
+        to = "crate::entity::product_bundle::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Bundle,
+    #[sea_orm(
+        belongs_to = "crate::entity::product::Entity",
+        from = "Column::ProductId",
+        to = "crate::entity::product::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Product,
+}
+
+impl Related<crate::entity::product_bundle::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Bundle.def()
+    }
+}
+
+impl Related<crate::entity::product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One attribute products of a category may carry, e.g. `condition` or `ram`
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "category_attributes")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub category_id: Uuid,
+    /// Key under `products.attributes`; unique within the category
+    pub key: String,
+    /// `text`, `number` or `enum`; see `crate::db::category_attributes::AttributeType`
+    pub attribute_type: String,
+    /// The values an `enum` attribute may take; `null` for other types
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub allowed_values: Option<Json>,
+    /// Products of the category must set it
+    pub required: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::category::Entity",
+        from = "Column::CategoryId",
+        to = "crate::entity::category::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Category,
+}
+
+impl Related<crate::entity::category::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Category.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Marketplace commission on sales, for one category or, as an override,
+/// one store. Exactly one of `category_id` and `store_id` is set. A rate
+/// applies from `effective_from` until a later rate for the same scope takes
+/// over.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "commission_rates")]
+#[schema(as = RateModel)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+    /// Share of the sale price taken, 0 to 100
+    pub percent: f64,
+    pub effective_from: DateTime<Utc>,
+    /// Device ID of the admin who set the rate
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::category::Entity",
+        from = "Column::CategoryId",
+        to = "crate::entity::category::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Category,
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
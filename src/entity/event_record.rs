@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A dispatched event, kept for export to analytics. `seq` is assigned on
+/// insert and only ever grows, so it orders the log without gaps in meaning.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "events")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub seq: i64,
+    #[sea_orm(unique)]
+    pub id: Uuid,
+    /// `crate::events::EventType` variant name, e.g. `ProductCreated`
+    pub event_type: String,
+    pub entity_id: Uuid,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub data: Json,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
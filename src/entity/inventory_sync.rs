@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One applied POS inventory upload, kept so a retried upload with the same
+/// `sync_id` returns the original report instead of applying twice
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "inventory_syncs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub store_id: Uuid,
+    /// Client-chosen id, unique per store
+    pub sync_id: String,
+    /// Per-row report returned when the upload was applied
+    #[sea_orm(column_type = "JsonBinary")]
+    pub report: Json,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A copy of every media object from one storage backend to another; see
+/// `crate::media_migration`
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "media_migrations")]
+#[schema(as = MediaMigrationModel)]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// Relay id of the admin who started it
+    pub requested_by: String,
+    /// Endpoint and bucket copied from; credentials are never stored
+    pub source: String,
+    /// Endpoint and bucket copied to
+    pub target: String,
+    /// Prepended to every key on the target, if set
+    pub key_prefix: Option<String>,
+    /// Only count what would be copied
+    pub dry_run: bool,
+    /// `running`, `completed` or `failed`
+    pub status: String,
+    /// Last `product_media` row handled; a resumed run continues after it
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub last_media_id: Option<Uuid>,
+    pub media_done: i64,
+    pub objects_copied: i64,
+    pub bytes_copied: i64,
+    /// Media whose objects could not be copied after every retry
+    pub media_failed: i64,
+    /// The first failures as `{media_id, key, error}` objects
+    #[sea_orm(column_type = "JsonBinary")]
+    #[schema(value_type = Object)]
+    pub failures: Json,
+    /// Why the run stopped, when it failed as a whole
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Bumped after every batch, so a stalled run can be told apart
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// An uploaded image that looks like one already used by another store's
+/// listing. The upload went through; admins decide what to do about it.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "media_similarity_flags")]
+#[schema(as = FlagModel)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub tenant_id: String,
+    /// The new upload
+    #[schema(value_type = String, format = "uuid")]
+    pub media_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// The earlier image it resembles
+    #[schema(value_type = String, format = "uuid")]
+    pub matched_media_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub matched_product_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub matched_store_id: Uuid,
+    /// Bits the two perceptual hashes differ in; 0 is a straight copy
+    pub distance: i32,
+    /// `pending`, `dismissed` or `held`
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product_media::Entity",
+        from = "Column::MediaId",
+        to = "crate::entity::product_media::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Media,
+    #[sea_orm(
+        belongs_to = "crate::entity::product_media::Entity",
+        from = "Column::MatchedMediaId",
+        to = "crate::entity::product_media::Column::Id",
+        on_delete = "Cascade"
+    )]
+    MatchedMedia,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
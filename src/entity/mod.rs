@@ -1,2 +1,38 @@
+pub mod admin_credential;
+pub mod audit_log;
+pub mod bundle_item;
+pub mod category;
+pub mod category_attribute;
+pub mod commission_rate;
+pub mod event_record;
+pub mod experiment_exposure;
+pub mod inventory_sync;
+pub mod media_migration;
+pub mod media_similarity_flag;
+pub mod notification_digest_item;
+pub mod notification_preference;
 pub mod product;
+pub mod product_bundle;
+pub mod product_count;
+pub mod product_media;
+pub mod product_moderation;
+pub mod product_price_history;
+pub mod product_question;
+pub mod product_view;
+pub mod product_watch;
+pub mod profanity_term;
+pub mod prohibited_term;
+pub mod public_stat;
+pub mod referral_click;
+pub mod referral_link;
+pub mod report_job;
+pub mod review_anomaly;
+pub mod snapshot_product;
 pub mod store;
+pub mod store_api_key;
+pub mod store_payout_account;
+pub mod store_promotion;
+pub mod store_review;
+pub mod store_snapshot;
+pub mod system_setting;
+pub mod tombstone;
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A notification held back for its recipient's next digest. Rows are
+/// deleted once the digest that covers them is sent.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "notification_digest_queue")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    /// Device ID of the recipient
+    pub user_id: String,
+    /// See `crate::notifications::NotificationKind`
+    pub kind: String,
+    /// `hourly_digest` or `daily_digest`, as preferred when queued
+    pub mode: String,
+    /// The event the notification came from
+    pub event_id: Uuid,
+    pub entity_id: Uuid,
+    /// The event's data
+    #[sea_orm(column_type = "JsonBinary")]
+    pub payload: Json,
+    pub queued_at: DateTime<Utc>,
+    /// End of the digest window the item falls in
+    pub due_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -12,14 +12,58 @@ pub struct Model {
     pub id: Uuid,
     #[schema(value_type = String, format = "uuid")]
     pub store_id: Uuid,
+    /// Copied from the store; see `crate::tenant`
+    pub tenant_id: String,
     pub sku: Option<String>,
     pub name: String,
     pub description: Option<String>,
     pub price: f64,
+    /// ISO 4217 code of `price`, `sale_price` and `delivery_fee_override`
+    #[sea_orm(column_type = "Char(Some(3))")]
+    pub currency: String,
+    /// Promotional price, only applied until `sale_ends_at`
+    pub sale_price: Option<f64>,
+    pub sale_ends_at: Option<DateTime<Utc>>,
     pub quantity_available: i32,
-    #[schema(value_type = String, format = "uuid")]
+    /// Stock level at or below which the seller is warned, by a
+    /// `ProductLowStock` event, that the product is running out
+    pub low_stock_threshold: Option<i32>,
+    #[schema(value_type = Option<String>, format = "uuid")]
     pub image_id: Option<Uuid>,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub category_id: Option<Uuid>,
+    /// Category-specific details, e.g. `{"condition": "used", "ram": 8}`;
+    /// checked against the category's attribute definitions
+    #[sea_orm(column_type = "JsonBinary")]
+    #[schema(value_type = Object)]
+    pub attributes: Json,
+    /// Human-readable policy, kept for clients that predate the structured fields
+    pub return_policy: Option<String>,
+    /// Where the return policy came from: "product", "store_default" or "none"
+    pub return_policy_source: String,
+    pub returns_accepted: bool,
+    /// Days after purchase during which returns are taken (0–90)
+    pub return_window_days: Option<i32>,
+    pub return_conditions: Option<String>,
+    /// The buyer can collect the product from the seller
+    pub pickup_available: bool,
+    /// The seller can deliver the product
+    pub delivery_available: bool,
+    /// Delivery fee for this product; the store's default fee unless set
+    pub delivery_fee_override: Option<f64>,
+    /// Typical days from order to delivery
+    pub delivery_estimated_days: Option<i32>,
+    /// Where the delivery options came from: "product" or "store_default"
+    pub delivery_options_source: String,
+    /// When set in the future the product stays hidden from public listings until then
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Flipped by the publish scheduler once a scheduled product goes live
+    pub is_published: bool,
+    /// Set when the seller deletes the product; it leaves every listing
+    /// until restored
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -30,6 +74,13 @@ pub enum Relation {
         to = "crate::entity::store::Column::Id"
     )]
     Store,
+    #[sea_orm(
+        belongs_to = "crate::entity::category::Entity",
+        from = "Column::CategoryId",
+        to = "crate::entity::category::Column::Id",
+        on_delete = "SetNull"
+    )]
+    Category,
 }
 
 impl Related<crate::entity::store::Entity> for Entity {
@@ -38,4 +89,10 @@ impl Related<crate::entity::store::Entity> for Entity {
     }
 }
 
+impl Related<crate::entity::category::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Category.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Number of products of a store in one category and publication status.
+/// A read model kept by `crate::db::product_counts`; a key may span several
+/// rows, so readers sum them.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "product_counts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    /// Copied from the store; see `crate::tenant`
+    pub tenant_id: String,
+    pub store_id: Uuid,
+    pub category_id: Option<Uuid>,
+    /// "published" or "unpublished"
+    pub status: String,
+    pub product_count: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "product_media")]
+pub struct Model {
+    /// Same value as the product's `image_id` for this upload
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    pub s3_key: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub webp_s3_key: Option<String>,
+    pub webp_size_bytes: Option<i64>,
+    /// Hex SHA-256 of the original upload; missing for older uploads
+    pub content_hash: Option<String>,
+    /// 64-bit dHash of the image, stored bit for bit; see
+    /// `moderation::image_hash`. Missing for older uploads and non-images
+    #[serde(skip)]
+    pub perceptual_hash: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product::Entity",
+        from = "Column::ProductId",
+        to = "crate::entity::product::Column::Id"
+    )]
+    Product,
+}
+
+impl Related<crate::entity::product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
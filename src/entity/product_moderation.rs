@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Review state of a listing held back by a flagged term. While `status` is
+/// not `approved` the product stays hidden from buyers.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "product_moderation")]
+#[schema(as = ModerationModel)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[sea_orm(unique)]
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    /// Why the listing was held: the flagged terms it matched as
+    /// `{term, category}` objects, or a `duplicate_image` entry naming the
+    /// listing its photo was copied from
+    #[sea_orm(column_type = "JsonBinary")]
+    #[schema(value_type = Object)]
+    pub matched_terms: Json,
+    /// `pending`, `approved` or `rejected`
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product::Entity",
+        from = "Column::ProductId",
+        to = "crate::entity::product::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Product,
+}
+
+impl Related<crate::entity::product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One change of a product's regular price
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "product_price_history")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub product_id: Uuid,
+    pub store_id: Uuid,
+    pub previous_price: f64,
+    pub price: f64,
+    /// What changed the price, e.g. "bulk"
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product::Entity",
+        from = "Column::ProductId",
+        to = "crate::entity::product::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Product,
+}
+
+impl Related<crate::entity::product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Public question on a product page, answered by the store owner
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "product_questions")]
+#[schema(as = ProductQuestion)]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub product_id: Uuid,
+    /// Device ID of the buyer who asked
+    pub asked_by: String,
+    pub question: String,
+    pub answer: Option<String>,
+    /// Device ID of the store owner who answered
+    pub answered_by: Option<String>,
+    /// Matched a flagged term; shown to the store owner only
+    pub is_held: bool,
+    pub created_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+    /// The store's automatic reply; never counts as an answer
+    pub auto_reply: Option<String>,
+    pub auto_replied_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::product::Entity",
+        from = "Column::ProductId",
+        to = "crate::entity::product::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Product,
+}
+
+impl Related<crate::entity::product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
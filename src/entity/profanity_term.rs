@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A word or phrase masked or refused in user-written text, managed by admins
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "profanity_terms")]
+#[schema(as = ProfanityTerm)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// Normalized: see `crate::moderation::text::normalize_text`
+    #[sea_orm(unique)]
+    pub phrase: String,
+    /// Reason given when text is refused, e.g. "profanity" or "insult"
+    pub category: String,
+    /// Relay id of the admin who added the phrase; "seed" for the starter list
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A word or phrase listings may not use, managed by admins
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "prohibited_terms")]
+#[schema(as = TermModel)]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// Normalized: lowercase, no diacritics, single spaces
+    #[sea_orm(unique)]
+    pub term: String,
+    /// Reason shown to sellers, e.g. "counterfeit" or "wildlife"
+    pub category: String,
+    /// `block` or `flag`
+    pub action: String,
+    /// Relay id of the admin who added the term
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A tenant's marketplace figures as shown to the public, already rounded
+/// and suppressed; see `crate::public_stats`
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "public_stats")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub tenant_id: String,
+    pub products: i64,
+    pub stores: i64,
+    pub city_count: i64,
+    /// Names of the busiest cities, as a JSON array
+    #[sea_orm(column_type = "JsonBinary")]
+    pub cities: Json,
+    pub computed_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
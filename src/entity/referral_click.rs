@@ -0,0 +1,37 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A viewer following a referral link; at most one row per link, viewer and
+/// UTC day
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "referral_clicks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub link_id: Uuid,
+    /// Relay ID, or `anon:` and the client's session ID
+    pub subject: String,
+    pub clicked_on: NaiveDate,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::referral_link::Entity",
+        from = "Column::LinkId",
+        to = "crate::entity::referral_link::Column::Id",
+        on_delete = "Cascade"
+    )]
+    ReferralLink,
+}
+
+impl Related<crate::entity::referral_link::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReferralLink.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A short link one store shares to send buyers to another store or one of
+/// its products, crediting the visits and sales it brings
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "referral_links")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    #[sea_orm(unique)]
+    pub code: String,
+    /// The store credited with what the link brings
+    pub store_id: Uuid,
+    /// The store the link sends buyers to; never one of the referring
+    /// store owner's
+    pub target_store_id: Uuid,
+    /// A product of the target store; its storefront when unset
+    pub target_product_id: Option<Uuid>,
+    /// Viewer-days: each viewer counts once per day they followed the link
+    pub click_count: i64,
+    /// Orders attributed to the link
+    pub sale_count: i64,
+    pub last_clicked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A seller report, generated in the background; see `crate::reports`
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "report_jobs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub store_id: Uuid,
+    /// Relay id of the seller who asked for it
+    pub requested_by: String,
+    /// See `crate::reports::ReportType`
+    pub report_type: String,
+    /// "csv" or "json"
+    pub format: String,
+    /// Covers `[range_start, range_end)`
+    pub range_start: DateTime<Utc>,
+    pub range_end: DateTime<Utc>,
+    /// See `crate::reports::ReportStatus`
+    pub status: String,
+    /// Storage key of the generated file, cleared once it expires
+    pub media_key: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// When the file is deleted and the download link stops working
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A pattern in a store's recent reviews that looks like farming, found by
+/// the review anomaly scan. Reviews stay up until an admin hides them.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "review_anomalies")]
+#[schema(as = AnomalyModel)]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    pub tenant_id: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// `burst`, `duplicate_text` or `prolific_reviewer`
+    pub pattern: String,
+    /// IDs of the store reviews that make up the pattern
+    #[sea_orm(column_type = "JsonBinary")]
+    #[schema(value_type = Vec<String>)]
+    pub review_ids: Json,
+    /// The store's 0-100 score when last scanned; higher is more suspicious
+    pub score: f64,
+    /// `pending`, `dismissed` or `hidden`
+    pub status: String,
+    pub detected_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One product as it stood when its store snapshot was taken
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "snapshot_products")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub snapshot_id: Uuid,
+    /// Not a foreign key: the product may since have been deleted
+    pub product_id: Uuid,
+    /// The product row, serialized
+    #[sea_orm(column_type = "JsonBinary")]
+    pub data: Json,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store_snapshot::Entity",
+        from = "Column::SnapshotId",
+        to = "crate::entity::store_snapshot::Column::Id",
+        on_delete = "Cascade"
+    )]
+    StoreSnapshot,
+}
+
+impl Related<crate::entity::store_snapshot::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::StoreSnapshot.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
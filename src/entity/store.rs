@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -6,10 +6,13 @@ use uuid::Uuid;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
 #[sea_orm(table_name = "stores")]
+#[schema(as = StoreModel)]
 pub struct Model {
     #[sea_orm(primary_key)]
     #[schema(value_type = String, format = "uuid")]
     pub id: Uuid,
+    /// Marketplace the store belongs to; see `crate::tenant`
+    pub tenant_id: String,
     pub name: String,
     pub description: Option<String>,
     pub logo_url: Option<String>,
@@ -17,11 +20,73 @@ pub struct Model {
     pub contact_phone: Option<String>,
     pub contact_email: Option<String>,
     pub contact_whatsapp: Option<String>,
+    /// Publish `contact_phone` to buyers; masked when false
+    pub show_phone: bool,
+    /// Publish `contact_whatsapp` to buyers; masked when false
+    pub show_whatsapp: bool,
+    /// Publish `contact_email` to buyers; omitted when false
+    pub show_email: bool,
+    /// Let other websites show the store's products; see `api::embed`
+    pub embed_enabled: bool,
     #[schema(value_type = String, format = "uuid")]
     pub owner_device_id: Option<String>, // Device certificate ID of the owner
     pub is_verified: bool,
+    /// Average of the store's product ratings
     pub rating: Option<f32>,
+    /// Average of the store's own reviews, see `db::store_reviews`; kept
+    /// apart from `rating`, which rates what was bought rather than the seller
+    pub store_rating: Option<f32>,
+    /// Published store reviews behind `store_rating`
+    pub store_review_count: i32,
     pub total_products: i32,
+    pub default_return_policy: Option<String>,
+    // Delivery options products inherit unless they set their own; see
+    // `db::delivery`
+    pub default_pickup_available: bool,
+    pub default_delivery_available: bool,
+    pub default_delivery_fee: Option<f64>,
+    pub default_delivery_estimated_days: Option<i32>,
+    /// Seller is away: the store stays visible but takes no orders
+    pub is_paused: bool,
+    /// When the pause ends on its own; open-ended when null
+    pub paused_until: Option<DateTime<Utc>>,
+    /// Banner shown on the store page while paused
+    pub pause_message: Option<String>,
+    // Automatic replies to buyers' questions, see `db::stores::AutoResponse`.
+    // Shown to the owner only, through the auto-response endpoint.
+    #[serde(skip)]
+    pub auto_response_enabled: bool,
+    #[serde(skip)]
+    pub auto_response_message: Option<String>,
+    /// `paused` or `delayed_reply`
+    #[serde(skip)]
+    pub auto_response_trigger: Option<String>,
+    // Media quota bookkeeping, see `db::media_quota`. Reported by the store
+    // stats endpoint rather than with the store.
+    /// Bytes of original media uploads the store keeps
+    #[serde(skip)]
+    pub media_bytes_used: i64,
+    /// Uploads made on `media_uploads_day`
+    #[serde(skip)]
+    pub media_uploads_today: i32,
+    /// UTC day `media_uploads_today` counts
+    #[serde(skip)]
+    pub media_uploads_day: Option<NaiveDate>,
+    /// Admin override of the configured byte quota
+    #[serde(skip)]
+    pub media_quota_bytes: Option<i64>,
+    /// Admin override of the configured daily upload limit
+    #[serde(skip)]
+    pub media_daily_uploads: Option<i32>,
+    /// Admin override of the configured images per product
+    #[serde(skip)]
+    pub media_images_per_product: Option<i32>,
+    /// 0-100 buyer trust signal from `trust::trust_score`; null until first scored
+    pub trust_score: Option<f64>,
+    /// When the owner first generated the store's share links; see
+    /// `db::onboarding`
+    #[serde(skip)]
+    pub first_shared_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
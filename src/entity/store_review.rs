@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A buyer's rating of a store as a whole: communication, packaging,
+/// delivery. Kept apart from product reviews; one per buyer and store.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "store_reviews")]
+#[schema(as = StoreReview)]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// Device ID of the buyer who wrote the review
+    pub reviewer_id: String,
+    /// 1 to 5 stars
+    pub rating: i32,
+    pub comment: Option<String>,
+    /// Order the review is about; the review shows as verified once the
+    /// order is confirmed to be the reviewer's
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub order_id: Option<Uuid>,
+    pub is_verified: bool,
+    /// The store owner's public reply
+    pub reply: Option<String>,
+    /// Device ID of the store owner who replied
+    pub replied_by: Option<String>,
+    pub replied_at: Option<DateTime<Utc>>,
+    /// Matched a flagged term; shown to the store owner only and left out
+    /// of the store's rating
+    pub is_held: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Copy of a store's products at one moment, which the seller can restore
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "store_snapshots")]
+#[schema(as = StoreSnapshot)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
+    pub store_id: Uuid,
+    /// `manual`, or the bulk operation it was taken before: `inventory_sync`,
+    /// `bulk_price` or `bulk_edit`
+    pub reason: String,
+    /// Relay id of the seller who took or triggered it
+    pub created_by: Option<String>,
+    pub product_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::entity::store::Entity",
+        from = "Column::StoreId",
+        to = "crate::entity::store::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Store,
+    #[sea_orm(has_many = "crate::entity::snapshot_product::Entity")]
+    SnapshotProduct,
+}
+
+impl Related<crate::entity::store::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Store.def()
+    }
+}
+
+impl Related<crate::entity::snapshot_product::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SnapshotProduct.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
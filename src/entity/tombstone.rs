@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Record of a hard-deleted row, kept so offline clients can drop their copy
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "tombstones")]
+#[schema(as = TombstoneModel)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// "product" or "store"
+    pub entity_type: String,
+    #[schema(value_type = String, format = "uuid")]
+    pub entity_id: Uuid,
+    #[schema(value_type = Option<String>, format = "uuid")]
+    pub store_id: Option<Uuid>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,12 @@
+use crate::db::events::EventLog;
+use crate::shutdown::ShutdownHook;
 use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex as AsyncMutex};
+use tokio::task::JoinHandle;
 use tracing::{info, warn};
 use uuid::Uuid;
 
@@ -9,10 +16,67 @@ use uuid::Uuid;
 pub enum EventType {
     ProductCreated,
     ProductUpdated,
+    /// A product's row was removed for good
     ProductDeleted,
+    /// The seller deleted a product, which keeps its row until restored or
+    /// deleted permanently
+    ProductArchived,
+    /// An archived product is back in listings
+    ProductRestored,
+    /// A scheduled product reached its `publish_at` and became publicly visible
+    ProductPublished,
+    /// A sale price was set on a product
+    ProductOnSale,
+    /// Stock level changed outside a regular product edit, e.g. a POS sync
+    ProductStockChanged,
+    /// Stock fell to the product's `low_stock_threshold`; sent once per
+    /// crossing, not again until a restock lifts it back above
+    ProductLowStock,
+    /// Regular price changed, by an edit or a POS sync
+    ProductPriceChanged,
+    /// A seller repriced many products at once; one event for the whole batch
+    ProductPricesBulkUpdated,
     ProductMediaUploaded,
     ProductMediaReplaced,
     ProductMediaDeleted,
+    /// A store's homepage promotion reached its `ends_at`
+    StorePromotionEnded,
+    /// A store's trust score moved past the alert threshold or fell into low trust
+    StoreTrustScoreChanged,
+    /// An admin added a term to the prohibited list; `data` is the term rule
+    ProhibitedTermAdded,
+    /// An admin removed a term from the prohibited list
+    ProhibitedTermRemoved,
+    /// An admin added a phrase to the profanity list; `data` is the rule
+    ProfanityTermAdded,
+    /// An admin removed a phrase from the profanity list
+    ProfanityTermRemoved,
+    /// A listing matched a flagged term and waits, unpublished, for review
+    ProductHeldForReview,
+    /// A bundle was deactivated because one of its component products was deleted
+    BundleDeactivated,
+    /// A buyer asked a public question on a product; sent to the store owner
+    ProductQuestionAsked,
+    /// The store owner answered a product question; sent to the asker
+    ProductQuestionAnswered,
+    /// Questions on a store's products have waited past the reminder threshold
+    ProductQuestionsUnanswered,
+    /// A store's auto-response replied to a question left waiting; sent to
+    /// the asker
+    ProductQuestionAutoReplied,
+    /// A watched product dropped in price, to the buyer's target if they set
+    /// one; sent to the watcher
+    ProductPriceDropped,
+    /// A buyer reviewed a store; sent to the store owner
+    StoreReviewPosted,
+    /// The store owner replied to a store review; sent to the reviewer
+    StoreReviewReplied,
+    /// One of the events above, for a recipient who wants it right away;
+    /// `data` names the recipient and kind and carries the source event's data
+    NotificationSent,
+    /// A user's queued notifications of one hourly or daily window, counted
+    /// by kind; see `crate::notifications`
+    NotificationDigestSent,
 }
 
 /// Event data structure
@@ -34,12 +98,15 @@ pub trait EventHandler: Send + Sync {
 /// Event dispatcher for managing and dispatching events
 pub struct EventDispatcher {
     handlers: Vec<Box<dyn EventHandler>>,
+    /// Events whose handlers are still running, so shutdown can wait for them
+    in_flight: watch::Sender<HashMap<Uuid, Event>>,
 }
 
 impl EventDispatcher {
     pub fn new() -> Self {
         Self {
             handlers: Vec::new(),
+            in_flight: watch::channel(HashMap::new()).0,
         }
     }
 
@@ -49,6 +116,7 @@ impl EventDispatcher {
 
     pub async fn dispatch(&self, event: Event) -> Result<(), String> {
         info!("Dispatching event: {:?}", event);
+        let _delivering = Delivering::start(&self.in_flight, &event);
 
         for handler in &self.handlers {
             if let Err(e) = handler.handle_event(&event).await {
@@ -59,6 +127,53 @@ impl EventDispatcher {
 
         Ok(())
     }
+
+    /// Events still being delivered
+    pub fn in_flight(&self) -> Vec<Event> {
+        self.in_flight.borrow().values().cloned().collect()
+    }
+
+    /// Resolves once no event is being delivered
+    pub async fn idle(&self) {
+        let mut in_flight = self.in_flight.subscribe();
+        let _ = in_flight.wait_for(HashMap::is_empty).await;
+    }
+}
+
+/// Keeps an event in [`EventDispatcher::in_flight`] until dropped, even if
+/// the dispatching task is cancelled
+struct Delivering<'a> {
+    in_flight: &'a watch::Sender<HashMap<Uuid, Event>>,
+    id: Uuid,
+}
+
+impl<'a> Delivering<'a> {
+    fn start(in_flight: &'a watch::Sender<HashMap<Uuid, Event>>, event: &Event) -> Self {
+        in_flight.send_modify(|events| {
+            events.insert(event.id, event.clone());
+        });
+        Self {
+            in_flight,
+            id: event.id,
+        }
+    }
+}
+
+impl Drop for Delivering<'_> {
+    fn drop(&mut self) {
+        self.in_flight.send_modify(|events| {
+            events.remove(&self.id);
+        });
+    }
+}
+
+/// Hold the dispatcher's shutdown hook until shutdown is requested and the
+/// events being delivered at that point are through
+pub fn spawn_drain(events: Arc<EventDispatcher>, hook: ShutdownHook) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        hook.requested().await;
+        events.idle().await;
+    })
 }
 
 impl Default for EventDispatcher {
@@ -100,3 +215,109 @@ impl EventHandler for WebSocketEventHandler {
         Ok(())
     }
 }
+
+/// Writes every event to the `events` table for analytics export; see
+/// `crate::db::events`
+pub struct EventLogRecorder {
+    db: DatabaseConnection,
+    /// Inserts go one at a time, so rows commit in `seq` order and an
+    /// exporter reading up to the highest `seq` never passes one that is
+    /// still to commit
+    writing: AsyncMutex<()>,
+}
+
+impl EventLogRecorder {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            db,
+            writing: AsyncMutex::new(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for EventLogRecorder {
+    async fn handle_event(&self, event: &Event) -> Result<(), String> {
+        let _writing = self.writing.lock().await;
+        EventLog::append(&self.db, event).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shutdown::{HookStatus, ShutdownCoordinator};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Takes `delay` per event, then records it
+    struct SlowHandler {
+        delay: Duration,
+        delivered: Arc<Mutex<Vec<Uuid>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EventHandler for SlowHandler {
+        async fn handle_event(&self, event: &Event) -> Result<(), String> {
+            tokio::time::sleep(self.delay).await;
+            self.delivered.lock().unwrap().push(event.id);
+            Ok(())
+        }
+    }
+
+    async fn shut_down_with_events_in_flight(
+        delay: Duration,
+        deadline: Duration,
+    ) -> (HookStatus, Vec<Uuid>, Vec<Uuid>, Vec<Uuid>) {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_handler(Box::new(SlowHandler {
+            delay,
+            delivered: delivered.clone(),
+        }));
+        let dispatcher = Arc::new(dispatcher);
+        let coordinator = ShutdownCoordinator::new();
+        spawn_drain(dispatcher.clone(), coordinator.register("event dispatcher"));
+
+        let mut sent = Vec::new();
+        for _ in 0..3 {
+            let event = create_event(EventType::ProductUpdated, Uuid::new_v4(), json!({}));
+            sent.push(event.id);
+            let dispatcher = dispatcher.clone();
+            tokio::spawn(async move { dispatcher.dispatch(event).await });
+        }
+        while dispatcher.in_flight().len() < sent.len() {
+            tokio::task::yield_now().await;
+        }
+
+        coordinator.trigger();
+        let outcome = coordinator.drain(deadline).await.remove(0);
+        let mut left: Vec<Uuid> = dispatcher.in_flight().iter().map(|e| e.id).collect();
+        let mut delivered = delivered.lock().unwrap().clone();
+        sent.sort();
+        left.sort();
+        delivered.sort();
+        (outcome.status, sent, delivered, left)
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_events_in_flight() {
+        let (status, sent, delivered, left) =
+            shut_down_with_events_in_flight(Duration::from_millis(20), Duration::from_secs(5))
+                .await;
+        assert_eq!(status, HookStatus::Finished);
+        assert_eq!(delivered, sent);
+        assert!(left.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_events_past_the_deadline_are_still_accounted_for() {
+        let (status, sent, delivered, left) =
+            shut_down_with_events_in_flight(Duration::from_secs(5), Duration::from_millis(20))
+                .await;
+        assert_eq!(status, HookStatus::TimedOut);
+        assert!(delivered.is_empty());
+        assert_eq!(left, sent);
+    }
+}
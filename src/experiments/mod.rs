@@ -0,0 +1,227 @@
+//! A/B experiments: split callers between variants of a behaviour and log
+//! who saw which, so analysts can join outcomes against exposures.
+//!
+//! An experiment is set in config as `name:variant=weight,variant=weight`.
+//! A caller is assigned by hashing the experiment name with their relay ID,
+//! or with the `X-Session-Id` an anonymous client sends, so they see the
+//! same variant on every request and every replica. Callers with neither
+//! are left out of the experiment and see the default behaviour.
+
+use crate::auth::claims_from_headers;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Header an anonymous client sends to keep its variant between requests
+pub const SESSION_HEADER: &str = "x-session-id";
+
+const MAX_SESSION_ID_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    /// Share of callers, relative to the other variants' weights
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Experiment {
+    pub name: String,
+    pub variants: Vec<Variant>,
+}
+
+impl FromStr for Experiment {
+    type Err = String;
+
+    /// `name:variant=weight,variant=weight`
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (name, variants) = raw
+            .split_once(':')
+            .ok_or("expected name:variant=weight,...")?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("experiment name is empty".to_string());
+        }
+        let mut parsed: Vec<Variant> = Vec::new();
+        for variant in variants.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let (variant, weight) = variant
+                .split_once('=')
+                .ok_or_else(|| format!("variant '{variant}' has no weight"))?;
+            let variant = variant.trim();
+            let weight = weight
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("weight of '{variant}' must be a whole number"))?;
+            if parsed.iter().any(|v| v.name == variant) {
+                return Err(format!("variant '{variant}' is listed twice"));
+            }
+            parsed.push(Variant {
+                name: variant.to_string(),
+                weight,
+            });
+        }
+        if parsed.len() < 2 {
+            return Err("an experiment needs at least two variants".to_string());
+        }
+        if parsed.iter().map(|v| u64::from(v.weight)).sum::<u64>() == 0 {
+            return Err("at least one variant needs a positive weight".to_string());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            variants: parsed,
+        })
+    }
+}
+
+impl fmt::Display for Experiment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        for (index, variant) in self.variants.iter().enumerate() {
+            let sep = if index == 0 { "" } else { "," };
+            write!(f, "{sep}{}={}", variant.name, variant.weight)?;
+        }
+        Ok(())
+    }
+}
+
+impl Experiment {
+    /// The variant `subject` is in; the same for the same subject until the
+    /// experiment's name or variants change
+    pub fn assign(&self, subject: &str) -> &Variant {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        let mut bucket = bucket(&self.name, subject) % total;
+        for variant in &self.variants {
+            let weight = u64::from(variant.weight);
+            if bucket < weight {
+                return variant;
+            }
+            bucket -= weight;
+        }
+        unreachable!("bucket is below the sum of the weights")
+    }
+}
+
+/// Position of `subject` in `experiment`, from a hash that is stable across
+/// builds and platforms, unlike `std`'s hasher
+fn bucket(experiment: &str, subject: &str) -> u64 {
+    let digest = Sha256::new()
+        .chain_update(experiment.as_bytes())
+        .chain_update(b":")
+        .chain_update(subject.as_bytes())
+        .finalize();
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(first)
+}
+
+/// Who is being assigned: the caller's relay ID, else their anonymous
+/// session, else nobody
+pub fn subject(headers: &HeaderMap) -> Option<String> {
+    if let Some(claims) = claims_from_headers(headers) {
+        return Some(claims.relay_id);
+    }
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|session| !session.is_empty() && session.len() <= MAX_SESSION_ID_LEN)
+        .map(|session| format!("anon:{session}"))
+}
+
+/// Experiments configured on this replica
+#[derive(Debug, Clone, Default)]
+pub struct Experiments {
+    /// Ordering of the public store list; variants are `StoreSort` names
+    pub feed: Option<Experiment>,
+}
+
+impl Experiments {
+    pub fn get(&self, name: &str) -> Option<&Experiment> {
+        self.feed
+            .as_ref()
+            .filter(|experiment| experiment.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment(raw: &str) -> Experiment {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn test_config_is_parsed_and_checked() {
+        let parsed = experiment(" feed_ranking : newest=50, trust_score=50 ");
+        assert_eq!(parsed.to_string(), "feed_ranking:newest=50,trust_score=50");
+        for bad in [
+            "newest=50,trust_score=50",
+            ":newest=50,trust_score=50",
+            "feed:newest=50",
+            "feed:newest=50,newest=50",
+            "feed:newest,trust_score=50",
+            "feed:newest=-1,trust_score=50",
+            "feed:newest=0,trust_score=0",
+        ] {
+            assert!(bad.parse::<Experiment>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_assignment_is_stable() {
+        let feed = experiment("feed_ranking:newest=50,trust_score=50");
+        // Pinned so a change to the hashing, which would reshuffle everyone
+        // mid-experiment, fails here first
+        assert_eq!(bucket("feed_ranking", "relay-1"), 0x7405_60cb_560e_a0ee);
+        assert_eq!(feed.assign("relay-1").name, "newest");
+        let first: Vec<&str> = (0..100)
+            .map(|i| feed.assign(&format!("relay-{i}")).name.as_str())
+            .collect();
+        let again: Vec<&str> = (0..100)
+            .map(|i| feed.assign(&format!("relay-{i}")).name.as_str())
+            .collect();
+        assert_eq!(first, again);
+        // Another experiment splits the same callers independently
+        let other = experiment("other:newest=50,trust_score=50");
+        assert!((0..100).any(|i| {
+            let subject = format!("relay-{i}");
+            other.assign(&subject).name != feed.assign(&subject).name
+        }));
+    }
+
+    #[test]
+    fn test_callers_split_in_the_configured_proportions() {
+        const USERS: usize = 100_000;
+        for (raw, expected) in [
+            ("feed:a=50,b=50", [0.5, 0.5, 0.0]),
+            ("feed:a=10,b=90", [0.1, 0.9, 0.0]),
+            ("feed:a=1,b=1,c=2", [0.25, 0.25, 0.5]),
+            ("feed:a=0,b=3,c=1", [0.0, 0.75, 0.25]),
+        ] {
+            let parsed = experiment(raw);
+            let mut counts = [0usize; 3];
+            for user in 0..USERS {
+                let variant = parsed.assign(&format!("user-{user}"));
+                let index = parsed.variants.iter().position(|v| v == variant).unwrap();
+                counts[index] += 1;
+            }
+            for (count, share) in counts.iter().zip(expected) {
+                let actual = *count as f64 / USERS as f64;
+                assert!((actual - share).abs() < 0.01, "{raw}: {counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_subject_prefers_the_token_then_the_session() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(subject(&headers), None);
+        headers.insert(SESSION_HEADER, "  ".parse().unwrap());
+        assert_eq!(subject(&headers), None);
+        headers.insert(SESSION_HEADER, "abc".parse().unwrap());
+        assert_eq!(subject(&headers).as_deref(), Some("anon:abc"));
+    }
+}
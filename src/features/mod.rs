@@ -0,0 +1,217 @@
+//! Feature flags: ship endpoints dark and turn them on per environment.
+//!
+//! `ENABLED_FEATURES` sets which [`KNOWN_FEATURES`] are on at boot. When
+//! runtime overrides are allowed, admins can flip a feature with
+//! `PUT /admin/features/{name}`; the override is saved in `system_settings`
+//! and every replica picks it up on its next poll, without a restart. Routes
+//! of a disabled feature stay mounted but answer 404 with
+//! [`FEATURE_DISABLED`], so clients can tell "off" from "never existed".
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
+use utoipa::ToSchema;
+
+/// Error code of the 404 sent for a disabled feature's routes
+pub const FEATURE_DISABLED: &str = "FEATURE_DISABLED";
+
+/// `system_settings` key the runtime overrides are stored under
+pub const SETTING_KEY: &str = "feature_overrides";
+
+/// Features that can be gated
+pub const KNOWN_FEATURES: &[&str] = &["questions"];
+
+/// Enabled when `ENABLED_FEATURES` is unset
+pub const DEFAULT_FEATURES: &[&str] = &["questions"];
+
+/// Which features are on for this replica
+pub struct FeatureFlags {
+    configured: BTreeSet<String>,
+    runtime_overrides: bool,
+    overrides: RwLock<BTreeMap<String, bool>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FeatureStatus {
+    pub name: String,
+    pub enabled: bool,
+    /// Enabled by `ENABLED_FEATURES`
+    pub configured: bool,
+    /// Runtime override in effect, if any
+    #[serde(rename = "override")]
+    pub override_enabled: Option<bool>,
+}
+
+impl FeatureFlags {
+    pub fn new(configured: impl IntoIterator<Item = String>, runtime_overrides: bool) -> Self {
+        Self {
+            configured: configured.into_iter().collect(),
+            runtime_overrides,
+            overrides: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn runtime_overrides(&self) -> bool {
+        self.runtime_overrides
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.overrides
+            .read()
+            .ok()
+            .and_then(|overrides| overrides.get(name).copied())
+            .unwrap_or_else(|| self.configured.contains(name))
+    }
+
+    /// Replace the runtime overrides; ignored unless they are allowed.
+    /// Returns whether anything changed.
+    pub fn set_overrides(&self, new: BTreeMap<String, bool>) -> bool {
+        if !self.runtime_overrides {
+            return false;
+        }
+        let Ok(mut overrides) = self.overrides.write() else {
+            return false;
+        };
+        let changed = *overrides != new;
+        *overrides = new;
+        changed
+    }
+
+    pub fn overrides(&self) -> BTreeMap<String, bool> {
+        self.overrides
+            .read()
+            .map(|overrides| overrides.clone())
+            .unwrap_or_default()
+    }
+
+    /// Every known feature and why it is on or off
+    pub fn snapshot(&self) -> Vec<FeatureStatus> {
+        let overrides = self.overrides();
+        KNOWN_FEATURES
+            .iter()
+            .map(|name| FeatureStatus {
+                name: name.to_string(),
+                enabled: self.is_enabled(name),
+                configured: self.configured.contains(*name),
+                override_enabled: overrides.get(*name).copied(),
+            })
+            .collect()
+    }
+}
+
+/// Body of the 404 sent for a disabled feature
+#[derive(Serialize, ToSchema)]
+pub struct FeatureDisabled {
+    pub code: &'static str,
+    pub feature: &'static str,
+    pub message: &'static str,
+}
+
+impl IntoResponse for FeatureDisabled {
+    fn into_response(self) -> Response {
+        (StatusCode::NOT_FOUND, Json(self)).into_response()
+    }
+}
+
+/// Reject the request unless `feature` is on
+pub fn require_feature(flags: &FeatureFlags, feature: &'static str) -> Result<(), FeatureDisabled> {
+    if flags.is_enabled(feature) {
+        Ok(())
+    } else {
+        Err(FeatureDisabled {
+            code: FEATURE_DISABLED,
+            feature,
+            message: "This feature is not available.",
+        })
+    }
+}
+
+/// Gate every route of a router behind one feature
+pub async fn feature_middleware(
+    State((flags, feature)): State<(Arc<FeatureFlags>, &'static str)>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if let Err(disabled) = require_feature(&flags, feature) {
+        return disabled.into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app(flags: Arc<FeatureFlags>) -> Router {
+        Router::new()
+            .route("/questions", get(|| async { "questions" }))
+            .route_layer(middleware::from_fn_with_state(
+                (flags, "questions"),
+                feature_middleware,
+            ))
+            .route("/products", get(|| async { "products" }))
+    }
+
+    async fn status(flags: &Arc<FeatureFlags>, path: &str) -> StatusCode {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        app(flags.clone()).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_override_toggles_routes_without_restart() {
+        let flags = Arc::new(FeatureFlags::new(["questions".to_string()], true));
+        assert_eq!(status(&flags, "/questions").await, StatusCode::OK);
+
+        assert!(flags.set_overrides(BTreeMap::from([("questions".to_string(), false)])));
+        let request = Request::builder()
+            .uri("/questions")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(flags.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], FEATURE_DISABLED);
+        assert_eq!(body["feature"], "questions");
+        // Ungated routes are unaffected
+        assert_eq!(status(&flags, "/products").await, StatusCode::OK);
+
+        assert!(flags.set_overrides(BTreeMap::new()));
+        assert_eq!(status(&flags, "/questions").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_overrides_ignored_when_not_allowed() {
+        let flags = Arc::new(FeatureFlags::new([], false));
+        assert_eq!(status(&flags, "/questions").await, StatusCode::NOT_FOUND);
+        assert!(!flags.set_overrides(BTreeMap::from([("questions".to_string(), true)])));
+        assert_eq!(status(&flags, "/questions").await, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_snapshot_reports_configured_and_override() {
+        let flags = FeatureFlags::new(["questions".to_string()], true);
+        flags.set_overrides(BTreeMap::from([("questions".to_string(), false)]));
+        assert_eq!(
+            flags.snapshot(),
+            [FeatureStatus {
+                name: "questions".to_string(),
+                enabled: false,
+                configured: true,
+                override_enabled: Some(false),
+            }]
+        );
+    }
+}
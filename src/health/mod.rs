@@ -0,0 +1,30 @@
+//! Dependency checks shared by `/healthz` and the `preflight` binary.
+//!
+//! [`dependency_health`] only reads state the process already has, so the
+//! health endpoint stays cheap; [`preflight`] actively exercises each
+//! dependency before a deployment takes traffic.
+
+use crate::api::media_storage::{self, BreakerState, BucketStatus};
+use serde::Serialize;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+// Only the preflight binary runs the active checks
+#[allow(dead_code)]
+pub mod preflight;
+
+/// State of the external services the API relies on
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyHealth {
+    pub media_storage: BreakerState,
+    pub media_bucket: BucketStatus,
+}
+
+/// What this process currently knows about its dependencies, without
+/// touching the network
+pub fn dependency_health() -> DependencyHealth {
+    DependencyHealth {
+        media_storage: media_storage::S3_BREAKER.state(Instant::now()),
+        media_bucket: media_storage::bucket_status(),
+    }
+}
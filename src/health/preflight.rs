@@ -0,0 +1,469 @@
+//! Everything a new deployment needs before it takes traffic, checked in
+//! one pass: configuration, database and migrations, media storage, JWT
+//! signing and the event bus. Nothing is changed except a probe object
+//! written to and deleted from the media bucket.
+
+use crate::api::media_storage::{MediaStorage, S3BackendConfig, S3MediaStorage};
+use crate::auth::JwtService;
+use crate::config::{jwt_secret_problem, Config};
+use crate::migrator::Migrator;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, EntityTrait};
+use sea_orm_migration::{seaql_migrations, MigratorTrait, SchemaManager};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Prefix of the objects written by the storage probe
+pub const PROBE_PREFIX: &str = "preflight/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not run, because an earlier check failed or there is nothing to check
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What the operator should do about a failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, remediation: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreflightReport {
+    /// No check failed
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl PreflightReport {
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+}
+
+/// Run every check against the variables `lookup` returns, always with the
+/// production rules. `timeout` bounds each check that goes over the network.
+pub async fn preflight(
+    lookup: impl Fn(&str) -> Option<String>,
+    timeout: Duration,
+) -> PreflightReport {
+    let (config_check, config) = check_config(lookup);
+    let mut checks = vec![config_check];
+    match &config {
+        Some(config) => {
+            checks.push(with_timeout("database", timeout, check_database(config, timeout)).await);
+            checks.push(with_timeout("media_storage", timeout, check_media_storage(config)).await);
+            checks.push(check_jwt(&config.auth.jwt_secret));
+        }
+        None => {
+            for name in ["database", "media_storage", "jwt"] {
+                checks.push(Check::skip(name, "Configuration did not load"));
+            }
+        }
+    }
+    checks.push(check_event_bus());
+    PreflightReport {
+        ok: checks.iter().all(|check| check.status != CheckStatus::Fail),
+        checks,
+    }
+}
+
+/// The configuration, if it loads at all; production problems fail the
+/// check without stopping the others
+pub fn check_config(lookup: impl Fn(&str) -> Option<String>) -> (Check, Option<Config>) {
+    const NAME: &str = "config";
+    const REMEDIATION: &str = "Fix the listed variables; env.example documents each one";
+    let config = match Config::from_lookup(lookup) {
+        Ok(config) => config,
+        Err(e) => return (Check::fail(NAME, e.problems.join("; "), REMEDIATION), None),
+    };
+    let check = match config.validate_for_production() {
+        Ok(()) => Check::pass(NAME, "Loaded and valid for production"),
+        Err(e) => Check::fail(NAME, e.problems.join("; "), REMEDIATION),
+    };
+    (check, Some(config))
+}
+
+/// Connect once, without the startup retries, and list the migrations
+/// that have not been applied
+pub async fn check_database(config: &Config, timeout: Duration) -> Check {
+    const NAME: &str = "database";
+    // Half the budget, so a refused connection reports its own error
+    // before the caller's timeout hides it
+    let connect_timeout = timeout / 2;
+    let mut options = ConnectOptions::new(config.database.url.clone());
+    options
+        .max_connections(1)
+        .connect_timeout(connect_timeout)
+        .acquire_timeout(connect_timeout)
+        .sqlx_logging(false);
+    let db = match Database::connect(options).await {
+        Ok(db) => db,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("Cannot connect: {e}"),
+                "Check DATABASE_URL and that the database accepts connections from this host",
+            )
+        }
+    };
+    let pending = match pending_migrations(&db).await {
+        Ok(pending) => pending,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("Cannot read applied migrations: {e}"),
+                "Grant the configured user read access to seaql_migrations",
+            )
+        }
+    };
+    if pending.is_empty() {
+        Check::pass(NAME, "Connected; all migrations applied")
+    } else if config.database.run_migrations_on_start {
+        Check::pass(
+            NAME,
+            format!(
+                "Connected; {} pending migration(s) will run on start: {}",
+                pending.len(),
+                pending.join(", ")
+            ),
+        )
+    } else {
+        Check::fail(
+            NAME,
+            format!(
+                "{} pending migration(s): {}",
+                pending.len(),
+                pending.join(", ")
+            ),
+            "Run the migrate binary, or set RUN_MIGRATIONS_ON_START=true",
+        )
+    }
+}
+
+/// Names of the migrations not yet applied to `db`, oldest first. Unlike
+/// `Migrator::get_pending_migrations` this never creates the bookkeeping
+/// table, so a fresh database is left untouched.
+pub async fn pending_migrations(db: &DatabaseConnection) -> Result<Vec<String>, sea_orm::DbErr> {
+    let applied: HashSet<String> = if SchemaManager::new(db).has_table("seaql_migrations").await? {
+        seaql_migrations::Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| row.version)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    Ok(Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .filter(|name| !applied.contains(name))
+        .collect())
+}
+
+/// Credentials, bucket, then a write, read and delete of a probe object
+pub async fn check_media_storage(config: &Config) -> Check {
+    const NAME: &str = "media_storage";
+    let (Some(access_key_id), Some(secret_access_key)) = (
+        config.media.access_key_id.clone(),
+        config.media.secret_access_key.clone(),
+    ) else {
+        return Check::fail(
+            NAME,
+            "Storage credentials are not configured; uploads would be disabled",
+            "Set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY",
+        );
+    };
+    let backend = S3BackendConfig {
+        endpoint_url: config.media.endpoint_url.clone(),
+        region: Some(config.media.region.clone()),
+        bucket: config.media.bucket.clone(),
+        access_key_id,
+        secret_access_key,
+    };
+    let storage = S3MediaStorage::from_config(&backend).await;
+    if let Err(e) = storage.ensure_bucket().await {
+        return Check::fail(
+            NAME,
+            format!("Bucket {} is not reachable: {e}", backend.describe()),
+            "Check AWS_ENDPOINT_URL, the credentials and that S3_BUCKET_NAME exists or may be created",
+        );
+    }
+    match probe_storage(&storage).await {
+        Ok(()) => Check::pass(
+            NAME,
+            format!("Wrote, read and deleted a probe in {}", backend.describe()),
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("Probe failed in {}: {e}", backend.describe()),
+            "Grant the credentials PutObject, GetObject and DeleteObject on the bucket",
+        ),
+    }
+}
+
+/// Write, read back and delete an object under [`PROBE_PREFIX`]
+pub async fn probe_storage<S: MediaStorage + Sync>(storage: &S) -> Result<(), String> {
+    let key = format!("{PROBE_PREFIX}{}", Uuid::new_v4());
+    let body = key.as_bytes();
+    storage
+        .put_object(&key, body, "text/plain")
+        .await
+        .map_err(|e| format!("write: {e}"))?;
+    let read = storage.get_object(&key).await;
+    // Clean up even when the read went wrong
+    let deleted = storage.delete_media(&key).await;
+    match read {
+        Ok(data) if data == body => {}
+        Ok(_) => return Err("read: the probe came back altered".to_string()),
+        Err(e) => return Err(format!("read: {e}")),
+    }
+    deleted.map_err(|e| format!("delete: {e}"))
+}
+
+/// The secret is strong enough and a token signed with it verifies
+pub fn check_jwt(secret: &str) -> Check {
+    const NAME: &str = "jwt";
+    if let Some(problem) = jwt_secret_problem(secret) {
+        return Check::fail(
+            NAME,
+            problem,
+            "Set JWT_SECRET to a random value, e.g. `openssl rand -base64 48`",
+        );
+    }
+    let jwt = JwtService::with_secret(secret);
+    let round_trip = jwt
+        .generate_token_with_role(
+            "preflight".to_string(),
+            "preflight".to_string(),
+            "seller".to_string(),
+        )
+        .and_then(|token| jwt.validate_token(&token));
+    match round_trip {
+        Ok(_) => Check::pass(NAME, "Signed and verified a token"),
+        Err(e) => Check::fail(NAME, e, "Set JWT_SECRET to a valid value"),
+    }
+}
+
+/// Events are dispatched in-process, so there is no broker to reach yet
+pub fn check_event_bus() -> Check {
+    Check::skip(
+        "event_bus",
+        "Events are dispatched in-process; no external bus is configured",
+    )
+}
+
+async fn with_timeout(
+    name: &'static str,
+    timeout: Duration,
+    check: impl Future<Output = Check>,
+) -> Check {
+    tokio::time::timeout(timeout, check)
+        .await
+        .unwrap_or_else(|_| {
+            Check::fail(
+                name,
+                format!("No answer within {}s", timeout.as_secs_f32()),
+                "Check the service is up and reachable from this host",
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use async_trait::async_trait;
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Bucket kept in memory; `altered` corrupts what it hands back
+    #[derive(Default)]
+    struct MemoryBucket {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+        altered: bool,
+    }
+
+    #[async_trait]
+    impl MediaStorage for MemoryBucket {
+        async fn upload_media_data(
+            &self,
+            _: Uuid,
+            _: &str,
+            _: &[u8],
+            _: &str,
+            _: Option<Uuid>,
+        ) -> Result<String, String> {
+            unreachable!("the probe writes exact keys")
+        }
+
+        async fn delete_media(&self, media_key: &str) -> Result<(), String> {
+            self.objects.lock().unwrap().remove(media_key);
+            Ok(())
+        }
+
+        async fn put_object(&self, key: &str, data: &[u8], _: &str) -> Result<(), String> {
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), data.to_vec());
+            Ok(())
+        }
+
+        async fn presigned_url(&self, key: &str, _: Duration) -> Result<String, String> {
+            Ok(format!("memory://{key}"))
+        }
+
+        async fn get_object(&self, key: &str) -> Result<Vec<u8>, String> {
+            let mut data = self
+                .objects
+                .lock()
+                .unwrap()
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("{key} not found"))?;
+            if self.altered {
+                data.reverse();
+            }
+            Ok(data)
+        }
+    }
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[tokio::test]
+    async fn test_broken_config_reports_each_failure() {
+        let report = preflight(
+            lookup(&[
+                // Nothing listens on port 1
+                ("DATABASE_URL", "postgres://user:pw@127.0.0.1:1/transac"),
+                ("JWT_SECRET", "too-short"),
+            ]),
+            Duration::from_secs(2),
+        )
+        .await;
+        assert!(!report.ok);
+        let status = |name: &str| report.check(name).unwrap().status;
+        assert_eq!(status("config"), CheckStatus::Fail);
+        assert_eq!(status("database"), CheckStatus::Fail);
+        assert_eq!(status("media_storage"), CheckStatus::Fail);
+        assert_eq!(status("jwt"), CheckStatus::Fail);
+        assert_eq!(status("event_bus"), CheckStatus::Skip);
+        assert!(report
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .all(|check| check.remediation.is_some()));
+        assert!(report.check("jwt").unwrap().detail.contains("32 bytes"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["checks"][0]["name"], "config");
+        assert_eq!(json["checks"][0]["status"], "fail");
+    }
+
+    #[tokio::test]
+    async fn test_config_that_does_not_load_skips_dependent_checks() {
+        let report = preflight(
+            lookup(&[("POW_DIFFICULTY", "lots")]),
+            Duration::from_secs(2),
+        )
+        .await;
+        let config = report.check("config").unwrap();
+        assert_eq!(config.status, CheckStatus::Fail);
+        assert!(config.detail.contains("DATABASE_URL"));
+        assert!(config.detail.contains("POW_DIFFICULTY"));
+        for name in ["database", "media_storage", "jwt"] {
+            assert_eq!(report.check(name).unwrap().status, CheckStatus::Skip);
+        }
+    }
+
+    #[test]
+    fn test_strong_jwt_secret_passes() {
+        let check = check_jwt(&"s".repeat(48));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.remediation.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_probe_cleans_up_and_catches_altered_reads() {
+        let bucket = MemoryBucket::default();
+        probe_storage(&bucket).await.unwrap();
+        assert!(bucket.objects.lock().unwrap().is_empty());
+
+        let altered = MemoryBucket {
+            altered: true,
+            ..Default::default()
+        };
+        let err = probe_storage(&altered).await.unwrap_err();
+        assert!(err.starts_with("read:"));
+        assert!(altered.objects.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pending_migrations_without_touching_a_fresh_database() {
+        let db = testing::sqlite().await;
+        let all = pending_migrations(&db).await.unwrap();
+        assert_eq!(all.len(), Migrator::migrations().len());
+        assert!(!SchemaManager::new(&db)
+            .has_table("seaql_migrations")
+            .await
+            .unwrap());
+
+        Migrator::install(&db).await.unwrap();
+        seaql_migrations::ActiveModel {
+            version: Set(all[0].clone()),
+            applied_at: Set(0),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        assert_eq!(pending_migrations(&db).await.unwrap(), all[1..]);
+    }
+}
@@ -0,0 +1,546 @@
+//! Listing fields from a shared page's meta tags.
+//!
+//! Shared links from WhatsApp catalogs, Instagram and most shops carry
+//! OpenGraph tags, but each site fills them its own way. A
+//! [`SiteStrategy`] per site knows where the name, description, image and
+//! price are; [`OpenGraph`] handles everything else. Whatever a strategy
+//! can't find is left empty with a warning for the seller.
+
+use reqwest::Url;
+use std::collections::HashMap;
+
+/// What a page yielded; every field may be missing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extracted {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Absolute URL of the main image
+    pub image_url: Option<String>,
+    pub price: Option<f64>,
+    /// ISO code when the page states one
+    pub currency: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// `<meta>` tags by `property`, `name` or `itemprop`, lowercased, plus the
+/// `<title>`. The first of repeated tags wins, as it does for link previews.
+#[derive(Debug, Default)]
+pub struct MetaTags {
+    tags: HashMap<String, String>,
+    title: Option<String>,
+}
+
+impl MetaTags {
+    pub fn parse(html: &str) -> Self {
+        // ASCII lowercasing keeps byte offsets, so positions found in
+        // `lower` index `html` too
+        let lower = html.to_ascii_lowercase();
+        let mut tags = HashMap::new();
+        let mut from = 0;
+        while let Some(start) = lower[from..].find("<meta").map(|i| from + i) {
+            let body_start = start + "<meta".len();
+            let end = tag_end(html, body_start);
+            let attrs = attributes(&html[body_start..end]);
+            let key = ["property", "name", "itemprop"]
+                .iter()
+                .find_map(|name| attrs.get(*name));
+            if let (Some(key), Some(content)) = (key, attrs.get("content")) {
+                tags.entry(key.to_ascii_lowercase())
+                    .or_insert_with(|| content.clone());
+            }
+            from = end;
+        }
+        let title = lower.find("<title").and_then(|start| {
+            let open = start + lower[start..].find('>')? + 1;
+            let close = open + lower[open..].find("</title")?;
+            Some(decode_entities(&html[open..close]))
+        });
+        Self { tags, title }
+    }
+
+    /// Trimmed, non-empty content of the first of `keys` present
+    pub fn get(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .filter_map(|key| self.tags.get(*key))
+            .map(|value| value.trim())
+            .find(|value| !value.is_empty())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title
+            .as_deref()
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+    }
+}
+
+/// Offset of the `>` closing a tag whose attributes start at `from`,
+/// skipping any inside quoted values
+fn tag_end(html: &str, from: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in html[from..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return from + i,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// A tag's attributes, names lowercased and values decoded
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            return attrs;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        rest = rest[name_len..].trim_start();
+        let Some(after_eq) = rest.strip_prefix('=') else {
+            attrs.entry(name).or_default();
+            continue;
+        };
+        rest = after_eq.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &rest[1..];
+                let len = inner.find(quote).unwrap_or(inner.len());
+                rest = inner.get(len + 1..).unwrap_or_default();
+                &inner[..len]
+            }
+            _ => {
+                let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let value = &rest[..len];
+                rest = &rest[len..];
+                value
+            }
+        };
+        attrs.entry(name).or_insert_with(|| decode_entities(value));
+    }
+}
+
+/// Decode the character references meta tags use in practice
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                _ => {
+                    let code = match entity.strip_prefix('#') {
+                        Some(hex) if hex.starts_with(['x', 'X']) => {
+                            u32::from_str_radix(&hex[1..], 16).ok()
+                        }
+                        Some(dec) => dec.parse().ok(),
+                        None => None,
+                    };
+                    char::from_u32(code?)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Words marking a nearby number as a price in CFA francs
+const XAF_MARKERS: [&str; 6] = ["fcfa", "xaf", "cfa", "frs", "francs", "f"];
+/// Words announcing a price
+const PRICE_LABELS: [&str; 3] = ["prix", "price", "tarif"];
+
+/// The first amount in free text that reads as a price: next to a CFA
+/// franc marker ("15 000 FCFA", "XAF 15,000", "15000F") or after a label
+/// ("Prix : 15000")
+pub fn parse_price(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let chars: Vec<(usize, char)> = lower.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].1.is_ascii_digit() || (i > 0 && chars[i - 1].1.is_alphanumeric()) {
+            i += 1;
+            continue;
+        }
+        // Digits, with single separators between digit groups
+        let start = i;
+        let mut end = i;
+        while end < chars.len() {
+            let c = chars[end].1;
+            let separated = matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '.' | ',')
+                && chars
+                    .get(end + 1)
+                    .is_some_and(|(_, next)| next.is_ascii_digit());
+            if c.is_ascii_digit() || (separated && end > start) {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        let byte_start = chars[start].0;
+        let byte_end = chars.get(end).map_or(lower.len(), |(at, _)| *at);
+        let before = lower[..byte_start].trim_end_matches([' ', ':', '\u{a0}']);
+        let after = lower[byte_end..].trim_start_matches([' ', '\u{a0}', '\u{202f}']);
+        let marked_after = XAF_MARKERS.iter().any(|marker| {
+            after
+                .strip_prefix(marker)
+                .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric()))
+        });
+        let marked_before = XAF_MARKERS[..3]
+            .iter()
+            .chain(PRICE_LABELS.iter())
+            .any(|marker| {
+                before
+                    .strip_suffix(marker)
+                    .is_some_and(|rest| !rest.ends_with(|c: char| c.is_alphanumeric()))
+            });
+        if marked_after || marked_before {
+            if let Some(amount) = amount(&lower[byte_start..byte_end]) {
+                return Some(amount);
+            }
+        }
+        i = end.max(i + 1);
+    }
+    None
+}
+
+/// "15 000", "15,000", "15.000" and "15000.50" as numbers. A final group
+/// of three digits is read as thousands, one or two digits as decimals.
+fn amount(digits: &str) -> Option<f64> {
+    let groups: Vec<&str> = digits
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|group| !group.is_empty())
+        .collect();
+    let (whole, fraction) = match groups.split_last() {
+        Some((last, init)) if !init.is_empty() && last.len() <= 2 => (init.concat(), *last),
+        _ => (groups.concat(), "0"),
+    };
+    format!("{whole}.{fraction}")
+        .parse()
+        .ok()
+        .filter(|amount: &f64| *amount > 0.0)
+}
+
+/// How to read one site's pages
+pub trait SiteStrategy: Send + Sync {
+    /// Reported with the draft, so sellers can tell why fields were missed
+    fn name(&self) -> &'static str;
+    fn handles(&self, url: &Url) -> bool;
+    fn extract(&self, url: &Url, meta: &MetaTags) -> Extracted;
+}
+
+fn host_is(url: &Url, domains: &[&str]) -> bool {
+    url.host_str().is_some_and(|host| {
+        domains
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{domain}")))
+    })
+}
+
+/// Absolute form of a possibly relative image link
+fn absolute(url: &Url, link: Option<&str>) -> Option<String> {
+    url.join(link?).ok().map(String::from)
+}
+
+/// Any page with OpenGraph, Twitter card or plain meta tags
+pub struct OpenGraph;
+
+impl SiteStrategy for OpenGraph {
+    fn name(&self) -> &'static str {
+        "opengraph"
+    }
+
+    fn handles(&self, _url: &Url) -> bool {
+        true
+    }
+
+    fn extract(&self, url: &Url, meta: &MetaTags) -> Extracted {
+        let description = meta.get(&["og:description", "twitter:description", "description"]);
+        let price = meta
+            .get(&["product:price:amount", "og:price:amount", "price"])
+            .and_then(amount)
+            .or_else(|| description.and_then(parse_price));
+        Extracted {
+            title: meta
+                .get(&["og:title", "twitter:title"])
+                .or(meta.title())
+                .map(str::to_owned),
+            description: description.map(str::to_owned),
+            image_url: absolute(
+                url,
+                meta.get(&["og:image:secure_url", "og:image", "twitter:image"]),
+            ),
+            price,
+            currency: meta
+                .get(&[
+                    "product:price:currency",
+                    "og:price:currency",
+                    "pricecurrency",
+                ])
+                .map(str::to_uppercase),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Instagram posts: the caption is the listing, quoted inside
+/// "<shop> on Instagram: \"...\""
+pub struct Instagram;
+
+impl Instagram {
+    fn caption(text: &str) -> Option<&str> {
+        let start = text.find(": \"")? + 3;
+        let end = text.rfind('"').filter(|end| *end >= start)?;
+        Some(text[start..end].trim()).filter(|caption| !caption.is_empty())
+    }
+}
+
+impl SiteStrategy for Instagram {
+    fn name(&self) -> &'static str {
+        "instagram"
+    }
+
+    fn handles(&self, url: &Url) -> bool {
+        host_is(url, &["instagram.com", "instagr.am"])
+    }
+
+    fn extract(&self, url: &Url, meta: &MetaTags) -> Extracted {
+        let mut extracted = OpenGraph.extract(url, meta);
+        let caption = [
+            meta.get(&["og:description"]),
+            meta.get(&["og:title"]),
+            meta.title(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(Instagram::caption);
+        match caption {
+            Some(caption) => {
+                // The caption's first line names the product
+                let first_line = caption
+                    .split(['\n', '.', '!'])
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or(caption);
+                extracted.title = Some(first_line.to_owned());
+                extracted.description = Some(caption.to_owned());
+                extracted.price = parse_price(caption);
+            }
+            None => {
+                // Without a caption the tags only name the account
+                extracted.title = None;
+                extracted
+                    .warnings
+                    .push("The post's caption could not be read".to_string());
+            }
+        }
+        extracted
+    }
+}
+
+/// WhatsApp Business catalog links (`wa.me/p/...`): the description opens
+/// with the price, e.g. "XAF 15,000 · Robe en pagne"
+pub struct WhatsAppCatalog;
+
+impl SiteStrategy for WhatsAppCatalog {
+    fn name(&self) -> &'static str {
+        "whatsapp_catalog"
+    }
+
+    fn handles(&self, url: &Url) -> bool {
+        host_is(url, &["wa.me", "whatsapp.com"])
+    }
+
+    fn extract(&self, url: &Url, meta: &MetaTags) -> Extracted {
+        let mut extracted = OpenGraph.extract(url, meta);
+        if let Some(description) = extracted.description.take() {
+            let (head, rest) = description
+                .split_once('·')
+                .map_or((description.as_str(), ""), |(head, rest)| {
+                    (head.trim(), rest.trim())
+                });
+            match parse_price(head) {
+                Some(price) => {
+                    extracted.price = Some(price);
+                    extracted.currency = Some("XAF".to_string());
+                    extracted.description = Some(rest.to_owned()).filter(|rest| !rest.is_empty());
+                }
+                None => extracted.description = Some(description),
+            }
+        }
+        extracted
+    }
+}
+
+static STRATEGIES: [&dyn SiteStrategy; 3] = [&Instagram, &WhatsAppCatalog, &OpenGraph];
+
+/// Listing fields from `html`, fetched from `url`, with the strategy used.
+/// Missing fields come back empty with a warning each.
+pub fn extract(url: &Url, html: &str) -> (&'static str, Extracted) {
+    let meta = MetaTags::parse(html);
+    let strategy = STRATEGIES
+        .iter()
+        .find(|strategy| strategy.handles(url))
+        .unwrap_or(&STRATEGIES[STRATEGIES.len() - 1]);
+    let mut extracted = strategy.extract(url, &meta);
+    if extracted.title.is_none() {
+        extracted
+            .warnings
+            .push("No product name found; enter one before saving".to_string());
+    }
+    if extracted.price.is_none() {
+        extracted
+            .warnings
+            .push("No price found; enter one before saving".to_string());
+    }
+    if extracted.image_url.is_none() {
+        extracted.warnings.push("No image found".to_string());
+    }
+    (strategy.name(), extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOP_PAGE: &str = r#"<!DOCTYPE html>
+<html><head>
+<title>Ignored when og:title is set</title>
+<meta charset="utf-8">
+<meta property="og:title" content="Sac en cuir &amp; raphia">
+<meta property="og:description" content='Fait main &#224; Douala. Livraison 48h'>
+<meta property="og:image" content="/media/sac.jpg">
+<meta property="og:image" content="/media/second.jpg">
+<meta property="product:price:amount" content="25000">
+<meta property="product:price:currency" content="xaf">
+</head><body></body></html>"#;
+
+    const INSTAGRAM_POST: &str = r#"<html><head>
+<meta property="og:title" content="Mama Ngono Couture on Instagram: &quot;Robe en pagne wax, tailles S a XL. Prix : 18 500 FCFA&quot;" />
+<meta property="og:description" content="120 likes, 8 comments - mamangono on March 3, 2025: &quot;Robe en pagne wax, tailles S a XL. Prix : 18 500 FCFA&quot;" />
+<meta property="og:image" content="https://scontent.cdninstagram.com/v/t51/robe.jpg?stp=1&amp;x=2" />
+</head></html>"#;
+
+    const WHATSAPP_PRODUCT: &str = r#"<html><head>
+<meta property="og:title" content="Panier garni" />
+<meta property="og:description" content="XAF 12,500 · Panier de fruits de saison" />
+<meta property="og:image" content="https://pps.whatsapp.net/v/panier.jpg" />
+</head></html>"#;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn test_opengraph_fields() {
+        let (strategy, extracted) = extract(&url("https://shop.example.cm/p/sac"), SHOP_PAGE);
+        assert_eq!(strategy, "opengraph");
+        assert_eq!(extracted.title.as_deref(), Some("Sac en cuir & raphia"));
+        assert_eq!(
+            extracted.description.as_deref(),
+            Some("Fait main à Douala. Livraison 48h")
+        );
+        assert_eq!(
+            extracted.image_url.as_deref(),
+            Some("https://shop.example.cm/media/sac.jpg")
+        );
+        assert_eq!(extracted.price, Some(25000.0));
+        assert_eq!(extracted.currency.as_deref(), Some("XAF"));
+        assert!(extracted.warnings.is_empty(), "{:?}", extracted.warnings);
+    }
+
+    #[test]
+    fn test_instagram_caption_becomes_the_listing() {
+        let (strategy, extracted) =
+            extract(&url("https://www.instagram.com/p/C4xyz/"), INSTAGRAM_POST);
+        assert_eq!(strategy, "instagram");
+        assert_eq!(
+            extracted.title.as_deref(),
+            Some("Robe en pagne wax, tailles S a XL")
+        );
+        assert_eq!(
+            extracted.description.as_deref(),
+            Some("Robe en pagne wax, tailles S a XL. Prix : 18 500 FCFA")
+        );
+        assert_eq!(extracted.price, Some(18500.0));
+        assert_eq!(
+            extracted.image_url.as_deref(),
+            Some("https://scontent.cdninstagram.com/v/t51/robe.jpg?stp=1&x=2")
+        );
+    }
+
+    #[test]
+    fn test_whatsapp_catalog_price_is_split_from_description() {
+        let (strategy, extracted) = extract(
+            &url("https://wa.me/p/7012345678901234/237690000000"),
+            WHATSAPP_PRODUCT,
+        );
+        assert_eq!(strategy, "whatsapp_catalog");
+        assert_eq!(extracted.title.as_deref(), Some("Panier garni"));
+        assert_eq!(extracted.price, Some(12500.0));
+        assert_eq!(extracted.currency.as_deref(), Some("XAF"));
+        assert_eq!(
+            extracted.description.as_deref(),
+            Some("Panier de fruits de saison")
+        );
+    }
+
+    #[test]
+    fn test_bare_page_returns_partial_fields_with_warnings() {
+        let html = "<html><head><TITLE>Chaussures Bata</TITLE></head><body>Hello</body></html>";
+        let (_, extracted) = extract(&url("https://example.cm/"), html);
+        assert_eq!(extracted.title.as_deref(), Some("Chaussures Bata"));
+        assert_eq!(extracted.price, None);
+        assert_eq!(extracted.warnings.len(), 2, "{:?}", extracted.warnings);
+
+        let (_, nothing) = extract(&url("https://example.cm/"), "not html at all");
+        assert_eq!((nothing.title, nothing.description), (None, None));
+        assert_eq!(nothing.warnings.len(), 3);
+
+        // An Instagram page without a caption only names the account
+        let (_, post) = extract(
+            &url("https://instagram.com/p/abc"),
+            r#"<meta property="og:title" content="Instagram">"#,
+        );
+        assert_eq!(post.title, None);
+        assert!(post.warnings.iter().any(|w| w.contains("caption")));
+    }
+
+    #[test]
+    fn test_prices_in_free_text() {
+        assert_eq!(parse_price("Seulement 15 000 FCFA !"), Some(15000.0));
+        assert_eq!(parse_price("XAF 15,000"), Some(15000.0));
+        assert_eq!(parse_price("à 7500F la pièce"), Some(7500.0));
+        assert_eq!(parse_price("Prix: 2.500"), Some(2500.0));
+        assert_eq!(parse_price("price 99.50"), Some(99.5));
+        // Sizes, phone numbers and years are not prices
+        assert_eq!(parse_price("Taille 42, appelez le 690 00 00 00"), None);
+        assert_eq!(parse_price("Collection 2025"), None);
+        assert_eq!(parse_price("3 fois"), None);
+    }
+}
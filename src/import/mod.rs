@@ -0,0 +1,72 @@
+//! Prefilling a product from a link the seller already shared on social
+//! media. The page is fetched through [`fetch`], which keeps requests off
+//! internal addresses, and read by a per-site strategy from [`extract`].
+//! Nothing is saved: the seller gets a draft to confirm.
+
+pub mod extract;
+pub mod fetch;
+
+use extract::Extracted;
+use fetch::{FetchError, Fetched, MAX_PAGE_BYTES};
+use reqwest::Url;
+
+/// A shared page read into listing fields
+#[derive(Debug)]
+pub struct ImportedPage {
+    /// Where the page was found, after redirects
+    pub url: Url,
+    /// Name of the [`extract::SiteStrategy`] that read it
+    pub strategy: &'static str,
+    pub extracted: Extracted,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Fetch(FetchError),
+    /// The link leads to something other than a web page
+    NotHtml(String),
+}
+
+fn is_html(content_type: Option<&str>) -> bool {
+    // Sites that leave the type out are given the benefit of the doubt
+    content_type.is_none_or(|content_type| {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        essence.eq_ignore_ascii_case("text/html")
+            || essence.eq_ignore_ascii_case("application/xhtml+xml")
+    })
+}
+
+/// Fetch `url` and read a listing out of it
+pub async fn import_page(url: &str) -> Result<ImportedPage, ImportError> {
+    let Fetched {
+        url,
+        content_type,
+        body,
+    } = fetch::fetch(url, MAX_PAGE_BYTES)
+        .await
+        .map_err(ImportError::Fetch)?;
+    if !is_html(content_type.as_deref()) {
+        return Err(ImportError::NotHtml(content_type.unwrap_or_default()));
+    }
+    let html = String::from_utf8_lossy(&body);
+    let (strategy, extracted) = extract::extract(&url, &html);
+    Ok(ImportedPage {
+        url,
+        strategy,
+        extracted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_web_pages_are_read() {
+        assert!(is_html(Some("text/html; charset=utf-8")));
+        assert!(is_html(Some("TEXT/HTML")));
+        assert!(is_html(None));
+        assert!(!is_html(Some("image/jpeg")));
+        assert!(!is_html(Some("application/json")));
+    }
+}
@@ -0,0 +1,649 @@
+//! Background jobs spawned once at startup.
+
+use crate::api::media_storage::{
+    connect_s3_storage, MediaStorage, StorageConnectError, StubMediaStorage,
+};
+use crate::db::product_views::NewProductView;
+use crate::db::products::Product;
+use crate::db::promotions::Promotion;
+use crate::db::public_stats::PublicStats;
+use crate::db::questions::ProductQuestion;
+use crate::db::retention::Retention;
+use crate::db::review_anomalies::ReviewAnomaly;
+use crate::db::store_reviews::StoreReview;
+use crate::db::stores::{Store, AUTO_REPLY_DELAY_HOURS};
+use crate::db::system_settings::SystemSetting;
+use crate::events::{create_event, EventDispatcher, EventType};
+use crate::features::{self, FeatureFlags};
+use crate::maintenance::{self, MaintenanceState, MaintenanceSwitch};
+use crate::moderation::review_anomalies::{
+    detect, AnomalyThresholds, BASELINE_DAYS, SCAN_WINDOW_HOURS,
+};
+use crate::notifications;
+use crate::product_views::{self, ViewSettings};
+use crate::public_stats::PublicStatsRules;
+use crate::reports::{self, ReportLimits};
+use crate::retention::{self, PruneRun, RetentionPolicy, PRUNE_LOG};
+use crate::shutdown::{ShutdownCoordinator, ShutdownHook};
+use crate::trust::{should_alert, trust_score, TrustInputs, TrustWeights, LOW_TRUST_SCORE};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Interval;
+use tracing::{error, info};
+
+/// Wait for a job's next run; `false` once shutdown is requested. A run
+/// already under way is never interrupted, only the next one is skipped.
+async fn next_tick(interval: &mut Interval, hook: &ShutdownHook) -> bool {
+    tokio::select! {
+        biased;
+        () = hook.requested() => false,
+        _ = interval.tick() => true,
+    }
+}
+
+/// Publish every product whose `publish_at` has passed and announce it.
+/// Products with fewer than `min_images` images become drafts instead.
+///
+/// Returns how many products went live in this run.
+pub async fn publish_scheduled_products(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+    min_images: u32,
+) -> Result<usize, String> {
+    let published = Product::publish_due(db, Utc::now(), min_images).await?;
+    for product in &published {
+        let event = create_event(
+            EventType::ProductPublished,
+            product.id,
+            serde_json::json!({
+                "store_id": product.store_id,
+                "name": product.name,
+                "price": product.price,
+                "publish_at": product.publish_at,
+            }),
+        );
+        let _ = dispatcher.dispatch(event).await;
+    }
+    if !published.is_empty() {
+        info!(count = published.len(), "Published scheduled products");
+    }
+    Ok(published.len())
+}
+
+/// Run [`publish_scheduled_products`] on a fixed interval.
+///
+/// The first tick fires immediately, so products that became due while the
+/// server was down are published right after a restart.
+pub fn spawn_publish_scheduler(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    min_images: u32,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("publish scheduler");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = publish_scheduled_products(&db, &dispatcher, min_images).await {
+                error!(error = %e, "Publish scheduler run failed");
+            }
+        }
+    })
+}
+
+/// Periodically null out sale fields whose end time has passed.
+///
+/// Purely housekeeping: reads already ignore expired sales.
+pub fn spawn_sale_cleanup(
+    db: DatabaseConnection,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("sale cleanup");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            match Product::clear_expired_sales(&db, Utc::now()).await {
+                Ok(0) => {}
+                Ok(count) => info!(count, "Cleared expired sales"),
+                Err(e) => error!(error = %e, "Sale cleanup run failed"),
+            }
+        }
+    })
+}
+
+/// Flag promotions whose window has closed and announce each one, so caches
+/// of the landing page can be dropped and the seller notified.
+pub async fn expire_promotions(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+) -> Result<usize, String> {
+    let expired = Promotion::expire_ended(db, Utc::now()).await?;
+    for promotion in &expired {
+        let event = create_event(
+            EventType::StorePromotionEnded,
+            promotion.id,
+            serde_json::json!({
+                "store_id": promotion.store_id,
+                "position": promotion.position,
+                "ends_at": promotion.ends_at,
+            }),
+        );
+        let _ = dispatcher.dispatch(event).await;
+    }
+    if !expired.is_empty() {
+        info!(count = expired.len(), "Expired store promotions");
+    }
+    Ok(expired.len())
+}
+
+/// Tell sellers about questions that have waited `after` without an answer.
+///
+/// Each run covers questions that crossed the threshold during the last
+/// `every`, so a question is reminded about once.
+pub async fn remind_unanswered_questions(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+    after: chrono::Duration,
+    every: chrono::Duration,
+) -> Result<usize, String> {
+    let asked_before = Utc::now() - after;
+    let stores =
+        ProductQuestion::unanswered_between(db, asked_before - every, asked_before).await?;
+    for store in &stores {
+        let event = create_event(
+            EventType::ProductQuestionsUnanswered,
+            store.store_id,
+            serde_json::json!({
+                "count": store.count,
+                "waiting_days": after.num_days(),
+            }),
+        );
+        let _ = dispatcher.dispatch(event).await;
+    }
+    if !stores.is_empty() {
+        info!(
+            stores = stores.len(),
+            "Reminded sellers of unanswered questions"
+        );
+    }
+    Ok(stores.len())
+}
+
+/// Run [`remind_unanswered_questions`] on a fixed interval
+pub fn spawn_question_reminders(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    after_days: u32,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("question reminders");
+    tokio::spawn(async move {
+        let after = chrono::Duration::days(after_days.into());
+        let window = chrono::Duration::from_std(every).unwrap_or(chrono::Duration::hours(1));
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = remind_unanswered_questions(&db, &dispatcher, after, window).await {
+                error!(error = %e, "Question reminder run failed");
+            }
+        }
+    })
+}
+
+/// Send the `delayed_reply` auto-responses of questions that have waited
+/// [`AUTO_REPLY_DELAY_HOURS`] for the seller, and announce each one.
+///
+/// Returns how many went out in this run; a question gets one at most.
+pub async fn send_delayed_auto_replies(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+) -> Result<usize, String> {
+    let now = Utc::now();
+    let asked_before = now - chrono::Duration::hours(AUTO_REPLY_DELAY_HOURS);
+    let replied = ProductQuestion::send_delayed_auto_replies(db, asked_before, now).await?;
+    for question in &replied {
+        let event = create_event(
+            EventType::ProductQuestionAutoReplied,
+            question.id,
+            serde_json::json!({
+                "product_id": question.product_id,
+                "asked_by": question.asked_by,
+                "auto_reply": question.auto_reply,
+            }),
+        );
+        let _ = dispatcher.dispatch(event).await;
+    }
+    if !replied.is_empty() {
+        info!(count = replied.len(), "Sent delayed auto-replies");
+    }
+    Ok(replied.len())
+}
+
+/// Run [`send_delayed_auto_replies`] on a fixed interval
+pub fn spawn_auto_replies(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("auto-replies");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = send_delayed_auto_replies(&db, &dispatcher).await {
+                error!(error = %e, "Auto-reply run failed");
+            }
+        }
+    })
+}
+
+/// Send the notification digests whose window has closed, on a fixed
+/// interval. A digest goes out on the first run after its window ends.
+pub fn spawn_notification_digests(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("notification digests");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = notifications::send_due_digests(&db, &dispatcher, Utc::now()).await {
+                error!(error = %e, "Notification digest run failed");
+            }
+        }
+    })
+}
+
+/// Run queued reports and expire old report files on a fixed interval.
+///
+/// Ticks are skipped while the storage breaker is open; without storage
+/// credentials reports go to the stub storage, as media uploads do.
+pub fn spawn_report_runner(
+    db: DatabaseConnection,
+    limits: ReportLimits,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("report runner");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            let now = Utc::now();
+            let result = match connect_s3_storage().await {
+                Ok(storage) => run_reports(&db, &storage, limits, now).await,
+                Err(StorageConnectError::Unavailable) => continue,
+                Err(StorageConnectError::Failed(_)) => {
+                    run_reports(&db, &StubMediaStorage, limits, now).await
+                }
+            };
+            match result {
+                Ok(0) => {}
+                Ok(count) => info!(count, "Ran report jobs"),
+                Err(e) => error!(error = %e, "Report runner run failed"),
+            }
+        }
+    })
+}
+
+/// One report runner tick: expire old files, then run queued jobs
+async fn run_reports<S: MediaStorage + Sync>(
+    db: &DatabaseConnection,
+    storage: &S,
+    limits: ReportLimits,
+    now: chrono::DateTime<Utc>,
+) -> Result<usize, String> {
+    let expired = reports::expire_reports(db, storage, now).await?;
+    if expired > 0 {
+        info!(count = expired, "Expired report files");
+    }
+    reports::run_due_reports(db, storage, limits, now).await
+}
+
+/// Delete the rows of `policy.table` that are past its retention window.
+///
+/// Rows go `batch` at a time with `pause` between statements, so no single
+/// delete holds its locks for long. Stops at the first failed batch.
+pub async fn prune_table(
+    db: &DatabaseConnection,
+    policy: RetentionPolicy,
+    batch: u64,
+    pause: Duration,
+) -> PruneRun {
+    let cutoff = policy.cutoff(Utc::now());
+    let mut run = PruneRun::default();
+    loop {
+        match Retention::prune_batch(db, policy.table, cutoff, batch).await {
+            Ok(pruned) => {
+                run.rows_pruned += pruned;
+                retention::pruned_counter(policy.table).inc_by(pruned);
+                if pruned < batch {
+                    break;
+                }
+            }
+            Err(e) => {
+                run.error = Some(e);
+                break;
+            }
+        }
+        tokio::time::sleep(pause).await;
+    }
+    if run.rows_pruned > 0 {
+        info!(
+            table = %policy.table,
+            count = run.rows_pruned,
+            "Pruned rows past retention"
+        );
+    }
+    run
+}
+
+/// Run [`prune_table`] on a fixed interval, one job per table
+pub fn spawn_retention(
+    db: DatabaseConnection,
+    policies: impl IntoIterator<Item = RetentionPolicy>,
+    batch: u64,
+    pause: Duration,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> Vec<JoinHandle<()>> {
+    policies
+        .into_iter()
+        .map(|policy| {
+            PRUNE_LOG.register(policy);
+            let db = db.clone();
+            let hook = shutdown.register(format!("retention {}", policy.table));
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(every);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                while next_tick(&mut interval, &hook).await {
+                    let started_at = Utc::now();
+                    let started = std::time::Instant::now();
+                    let run = prune_table(&db, policy, batch, pause).await;
+                    if let Some(e) = &run.error {
+                        error!(table = %policy.table, error = %e, "Retention run failed");
+                    }
+                    PRUNE_LOG.record(policy.table, started_at, started.elapsed(), run);
+                }
+            })
+        })
+        .collect()
+}
+
+/// Run [`expire_promotions`] on a fixed interval
+pub fn spawn_promotion_expiry(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("promotion expiry");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = expire_promotions(&db, &dispatcher).await {
+                error!(error = %e, "Promotion expiry run failed");
+            }
+        }
+    })
+}
+
+/// Periodically resume stores whose vacation has ended.
+///
+/// Reads already treat a store past `paused_until` as open; this clears the
+/// pause fields so the store's own record says so too.
+pub fn spawn_store_resume(
+    db: DatabaseConnection,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("store resume");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            match Store::resume_due(&db, Utc::now()).await {
+                Ok(stores) if stores.is_empty() => {}
+                Ok(stores) => info!(count = stores.len(), "Resumed paused stores"),
+                Err(e) => error!(error = %e, "Store resume run failed"),
+            }
+        }
+    })
+}
+
+/// Recompute every store's trust score, saving the ones that moved and
+/// announcing large moves so admins can review low-trust stores.
+///
+/// Returns how many scores changed.
+pub async fn recompute_trust_scores(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+    weights: &TrustWeights,
+    alert_threshold: f64,
+) -> Result<usize, String> {
+    let now = Utc::now();
+    let mut changed = 0;
+    for store in Store::list_all_tenants(db).await? {
+        let score = trust_score(&TrustInputs::for_store(&store, now), weights);
+        if store.trust_score == Some(score) {
+            continue;
+        }
+        Store::set_trust_score(db, store.id, score).await?;
+        changed += 1;
+        if should_alert(store.trust_score, score, alert_threshold) {
+            let event = create_event(
+                EventType::StoreTrustScoreChanged,
+                store.id,
+                serde_json::json!({
+                    "previous": store.trust_score,
+                    "trust_score": score,
+                    "low_trust": score < LOW_TRUST_SCORE,
+                }),
+            );
+            let _ = dispatcher.dispatch(event).await;
+        }
+    }
+    if changed > 0 {
+        info!(count = changed, "Recomputed store trust scores");
+    }
+    Ok(changed)
+}
+
+/// Run [`recompute_trust_scores`] on a fixed interval, starting right away
+pub fn spawn_trust_scoring(
+    db: DatabaseConnection,
+    dispatcher: Arc<EventDispatcher>,
+    weights: TrustWeights,
+    alert_threshold: f64,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("trust scoring");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) =
+                recompute_trust_scores(&db, &dispatcher, &weights, alert_threshold).await
+            {
+                error!(error = %e, "Trust score run failed");
+            }
+        }
+    })
+}
+
+/// Scan the last [`SCAN_WINDOW_HOURS`] of store reviews for farming and
+/// queue what looks suspicious for admins. Nothing is hidden here.
+///
+/// Returns how many findings were raised or grew.
+pub async fn scan_review_anomalies(
+    db: &DatabaseConnection,
+    thresholds: &AnomalyThresholds,
+) -> Result<usize, String> {
+    let now = Utc::now();
+    let window_start = now - chrono::Duration::hours(SCAN_WINDOW_HOURS);
+    let recent = StoreReview::since(db, window_start).await?;
+    if recent.is_empty() {
+        return Ok(0);
+    }
+    let baselines = StoreReview::counts_by_store(
+        db,
+        window_start - chrono::Duration::days(BASELINE_DAYS),
+        window_start,
+    )
+    .await?;
+    let mut raised = 0;
+    for found in detect(&recent, &baselines, thresholds) {
+        let store = Store::get(db, found.store_id).await?;
+        raised += ReviewAnomaly::record(db, &store.tenant_id, &found, now)
+            .await?
+            .len();
+    }
+    if raised > 0 {
+        info!(count = raised, "Queued review anomalies");
+    }
+    Ok(raised)
+}
+
+/// Run [`scan_review_anomalies`] on a fixed interval, starting right away
+pub fn spawn_review_anomaly_scan(
+    db: DatabaseConnection,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("review anomaly scan");
+    tokio::spawn(async move {
+        let thresholds = AnomalyThresholds::default();
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = scan_review_anomalies(&db, &thresholds).await {
+                error!(error = %e, "Review anomaly scan failed");
+            }
+        }
+    })
+}
+
+/// Recompute every tenant's public marketplace figures on a fixed interval,
+/// starting right away, so `GET /api/v1/public-stats` never counts rows
+pub fn spawn_public_stats(
+    db: DatabaseConnection,
+    rules: PublicStatsRules,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("public stats");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = PublicStats::refresh_all(&db, &rules, Utc::now()).await {
+                error!(error = %e, "Public stats run failed");
+            }
+        }
+    })
+}
+
+/// Write the product views queued on `views`; see `crate::product_views`.
+/// Views still queued at shutdown are written before the hook is released.
+pub fn spawn_view_writer(
+    db: DatabaseConnection,
+    views: tokio::sync::mpsc::Receiver<NewProductView>,
+    settings: ViewSettings,
+    shutdown: &ShutdownCoordinator,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("view writer");
+    tokio::spawn(product_views::write_views(db, views, settings, hook))
+}
+
+/// Copy the stored maintenance state into `switch`.
+///
+/// Returns the state now in effect. A missing setting means maintenance is off.
+pub async fn refresh_maintenance(
+    db: &DatabaseConnection,
+    switch: &MaintenanceSwitch,
+) -> Result<MaintenanceState, String> {
+    let state = match SystemSetting::get(db, maintenance::SETTING_KEY).await? {
+        Some(setting) => serde_json::from_value(setting.value)
+            .map_err(|e| format!("Malformed maintenance setting: {e}"))?,
+        None => MaintenanceState::default(),
+    };
+    if switch.set(state.clone()) {
+        info!(
+            enabled = state.enabled,
+            include_reads = state.include_reads,
+            "Maintenance mode updated"
+        );
+    }
+    Ok(state)
+}
+
+/// Run [`refresh_maintenance`] on a fixed interval so every replica follows
+/// a toggle made on any of them, starting right away
+pub fn spawn_maintenance_poll(
+    db: DatabaseConnection,
+    switch: &'static MaintenanceSwitch,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("maintenance poll");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = refresh_maintenance(&db, switch).await {
+                error!(error = %e, "Maintenance poll failed");
+            }
+        }
+    })
+}
+
+/// Copy the runtime feature overrides from `system_settings` into `flags`
+pub async fn refresh_feature_flags(
+    db: &DatabaseConnection,
+    flags: &FeatureFlags,
+) -> Result<BTreeMap<String, bool>, String> {
+    let overrides: BTreeMap<String, bool> =
+        match SystemSetting::get(db, features::SETTING_KEY).await? {
+            Some(setting) => serde_json::from_value(setting.value)
+                .map_err(|e| format!("Malformed feature overrides: {e}"))?,
+            None => BTreeMap::new(),
+        };
+    if flags.set_overrides(overrides.clone()) {
+        info!(?overrides, "Feature overrides updated");
+    }
+    Ok(overrides)
+}
+
+/// Run [`refresh_feature_flags`] on a fixed interval, starting right away
+pub fn spawn_feature_refresh(
+    db: DatabaseConnection,
+    flags: Arc<FeatureFlags>,
+    shutdown: &ShutdownCoordinator,
+    every: Duration,
+) -> JoinHandle<()> {
+    let hook = shutdown.register("feature refresh");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while next_tick(&mut interval, &hook).await {
+            if let Err(e) = refresh_feature_flags(&db, &flags).await {
+                error!(error = %e, "Feature flag refresh failed");
+            }
+        }
+    })
+}
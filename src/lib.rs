@@ -1,16 +1,122 @@
 pub mod api {
+    pub mod admin;
+    pub mod bulk_delete;
+    pub mod bulk_prices;
+    pub mod bundles;
+    pub mod categories;
+    pub mod checkout;
+    pub mod commissions;
+    pub mod delta;
+    pub mod embed;
+    pub mod extract;
+    pub mod fields;
+    #[cfg(feature = "graphql")]
+    pub mod graphql;
+    pub mod history;
     pub mod image_analysis;
+    pub mod image_conversion;
+    pub mod imports;
+    pub mod inventory_sync;
+    pub mod media_migration;
     pub mod media_storage;
+    pub mod moderation;
+    pub mod multipart;
+    pub mod notification_preferences;
+    pub mod onboarding;
+    pub mod pagination;
+    pub mod payout_accounts;
+    pub mod product_views;
     pub mod products;
+    pub mod promotions;
+    pub mod public_stats;
+    pub mod questions;
+    pub mod referrals;
+    pub mod reports;
+    pub mod return_policies;
+    pub mod seo;
+    pub mod snapshots;
+    pub mod store_api_keys;
+    pub mod store_reviews;
     pub mod stores;
+    pub mod sync;
+    pub mod transaction;
+    pub mod user_refs;
+    pub mod validation;
+    pub mod watches;
+    pub mod whatsapp_catalog;
 }
 
 pub mod auth;
 pub mod db; // expose entire db module including create_connection
 pub mod entity {
+    pub mod admin_credential;
+    pub mod audit_log;
+    pub mod bundle_item;
+    pub mod category;
+    pub mod category_attribute;
+    pub mod commission_rate;
+    pub mod event_record;
+    pub mod experiment_exposure;
+    pub mod inventory_sync;
+    pub mod media_migration;
+    pub mod media_similarity_flag;
+    pub mod notification_digest_item;
+    pub mod notification_preference;
     pub mod product;
+    pub mod product_bundle;
+    pub mod product_count;
+    pub mod product_media;
+    pub mod product_moderation;
+    pub mod product_price_history;
+    pub mod product_question;
+    pub mod product_view;
+    pub mod product_watch;
+    pub mod profanity_term;
+    pub mod prohibited_term;
+    pub mod public_stat;
+    pub mod referral_click;
+    pub mod referral_link;
+    pub mod report_job;
+    pub mod review_anomaly;
+    pub mod snapshot_product;
     pub mod store;
+    pub mod store_api_key;
+    pub mod store_payout_account;
+    pub mod store_promotion;
+    pub mod store_review;
+    pub mod store_snapshot;
+    pub mod system_setting;
+    pub mod tombstone;
 }
 pub mod config;
+pub mod currency;
+// The rest of `crypto` serves the binary's PoW handshake only
+pub mod crypto {
+    pub mod field;
+}
+pub mod error;
 pub mod events;
+pub mod experiments;
+pub mod features;
+pub mod health;
+pub mod import;
+pub mod jobs;
+pub mod links;
+pub mod maintenance;
+pub mod media_migration;
+pub mod metrics;
 pub mod migrator; // expose SeaORM migrator module // expose configuration loader
+pub mod moderation;
+pub mod money;
+pub mod notifications;
+pub mod price_alerts;
+pub mod product_views;
+pub mod public_stats;
+pub mod reports;
+pub mod request_middleware;
+pub mod retention;
+pub mod shutdown;
+pub mod tenant;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod trust;
@@ -0,0 +1,385 @@
+//! Links to the public website, and the share links built on them.
+//!
+//! Every URL a buyer may follow is put together here, so encoding and
+//! length rules live in one place. Store and product pages are addressed by
+//! ID: neither has a slug yet. Query values are percent-encoded byte by byte
+//! from UTF-8, leaving only RFC 3986 unreserved characters as they are, so
+//! `+`, `&`, `#` and emoji in a store name survive the trip to the app
+//! opening the link.
+//!
+//! Each share channel caps the text it prefills. A text over the cap loses
+//! the end of its message, marked with an ellipsis; the page URL is always
+//! kept whole.
+
+use std::borrow::Cow;
+use std::fmt;
+use uuid::Uuid;
+
+/// Longest WhatsApp share text, in characters. WhatsApp itself takes far
+/// more, but a `wa.me` link much past this is cut short by some browsers.
+pub const WHATSAPP_MAX_CHARS: usize = 1000;
+/// Longest SMS share text, in UTF-16 code units: one 160-character message.
+/// Characters outside the Basic Multilingual Plane, such as emoji, take two.
+pub const SMS_MAX_UNITS: usize = 160;
+/// Longest Telegram share text, in characters; the URL is sent apart from it
+pub const TELEGRAM_MAX_CHARS: usize = 1000;
+
+const ELLIPSIS: char = '…';
+
+/// The public page of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreLink<'a> {
+    base_url: &'a str,
+    id: Uuid,
+}
+
+impl<'a> StoreLink<'a> {
+    /// `base_url` is the site's public URL; a trailing slash is ignored
+    pub fn new(base_url: &'a str, id: Uuid) -> Self {
+        Self { base_url, id }
+    }
+}
+
+impl fmt::Display for StoreLink<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/store/{}", site(self.base_url), self.id)
+    }
+}
+
+/// The public page of a product
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductLink<'a> {
+    base_url: &'a str,
+    id: Uuid,
+}
+
+impl<'a> ProductLink<'a> {
+    /// `base_url` is the site's public URL; a trailing slash is ignored
+    pub fn new(base_url: &'a str, id: Uuid) -> Self {
+        Self { base_url, id }
+    }
+}
+
+impl fmt::Display for ProductLink<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/product/{}", site(self.base_url), self.id)
+    }
+}
+
+fn site(base_url: &str) -> &str {
+    base_url.trim_end_matches('/')
+}
+
+/// How a channel counts the length of a text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Measure {
+    Chars,
+    Utf16Units,
+}
+
+impl Measure {
+    fn of_char(self, c: char) -> usize {
+        match self {
+            Measure::Chars => 1,
+            Measure::Utf16Units => c.len_utf16(),
+        }
+    }
+
+    fn of(self, text: &str) -> usize {
+        text.chars().map(|c| self.of_char(c)).sum()
+    }
+}
+
+/// `text` cut to `max`, ending in an ellipsis when anything was cut. Whole
+/// characters are kept or dropped, never split.
+fn truncate(text: &str, max: usize, measure: Measure) -> Cow<'_, str> {
+    if measure.of(text) <= max {
+        return Cow::Borrowed(text);
+    }
+    let budget = max.saturating_sub(measure.of_char(ELLIPSIS));
+    let mut used = 0;
+    let mut end = 0;
+    for (at, c) in text.char_indices() {
+        used += measure.of_char(c);
+        if used > budget {
+            break;
+        }
+        end = at + c.len_utf8();
+    }
+    let kept = text[..end].trim_end();
+    if kept.is_empty() {
+        return Cow::Borrowed("");
+    }
+    Cow::Owned(format!("{kept}{ELLIPSIS}"))
+}
+
+/// `message` and `url` as one text of at most `max`, cutting the message
+/// first; just the URL when no message fits beside it
+fn with_url(message: &str, url: &str, max: usize, measure: Measure) -> String {
+    let room = max.saturating_sub(measure.of(url) + 1);
+    let message = truncate(message.trim(), room, measure);
+    if message.is_empty() {
+        url.to_string()
+    } else {
+        format!("{message} {url}")
+    }
+}
+
+/// Opens WhatsApp with `message` and the URL prefilled, for the user to
+/// pick a chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhatsAppShare<'a> {
+    message: &'a str,
+    url: &'a str,
+}
+
+impl<'a> WhatsAppShare<'a> {
+    pub fn new(message: &'a str, url: &'a str) -> Self {
+        Self { message, url }
+    }
+
+    /// The prefilled text, within [`WHATSAPP_MAX_CHARS`]
+    pub fn text(&self) -> String {
+        with_url(self.message, self.url, WHATSAPP_MAX_CHARS, Measure::Chars)
+    }
+}
+
+impl fmt::Display for WhatsAppShare<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "https://wa.me/?text={}",
+            urlencoding::encode(&self.text())
+        )
+    }
+}
+
+/// Opens the messaging app with `message` and the URL as the body, for the
+/// user to pick a recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmsShare<'a> {
+    message: &'a str,
+    url: &'a str,
+}
+
+impl<'a> SmsShare<'a> {
+    pub fn new(message: &'a str, url: &'a str) -> Self {
+        Self { message, url }
+    }
+
+    /// The prefilled body, within [`SMS_MAX_UNITS`]
+    pub fn text(&self) -> String {
+        with_url(self.message, self.url, SMS_MAX_UNITS, Measure::Utf16Units)
+    }
+}
+
+impl fmt::Display for SmsShare<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `?&` rather than `?` is what both Android and iOS read as "no
+        // recipient, then a body"
+        write!(f, "sms:?&body={}", urlencoding::encode(&self.text()))
+    }
+}
+
+/// Opens Telegram's share sheet with the URL and `message` below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelegramShare<'a> {
+    message: &'a str,
+    url: &'a str,
+}
+
+impl<'a> TelegramShare<'a> {
+    pub fn new(message: &'a str, url: &'a str) -> Self {
+        Self { message, url }
+    }
+
+    /// The prefilled text, within [`TELEGRAM_MAX_CHARS`]; the URL is not
+    /// part of it
+    pub fn text(&self) -> Cow<'a, str> {
+        truncate(self.message.trim(), TELEGRAM_MAX_CHARS, Measure::Chars)
+    }
+}
+
+impl fmt::Display for TelegramShare<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "https://t.me/share/url?url={}&text={}",
+            urlencoding::encode(self.url),
+            urlencoding::encode(&self.text())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://transac.site";
+
+    fn id() -> Uuid {
+        Uuid::parse_str("6f1c2a9e-0d4b-4c7a-9a51-3e2f8b7d1c04").unwrap()
+    }
+
+    /// The decoded value of `param` in `link`'s query
+    fn param(link: &str, param: &str) -> String {
+        let query = link.split_once('?').unwrap().1;
+        let value = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(&format!("{param}=")))
+            .unwrap();
+        urlencoding::decode(value).unwrap().into_owned()
+    }
+
+    #[test]
+    fn test_pages_are_addressed_by_id_under_the_site() {
+        let store = "https://transac.site/store/6f1c2a9e-0d4b-4c7a-9a51-3e2f8b7d1c04";
+        assert_eq!(StoreLink::new(BASE, id()).to_string(), store);
+        assert_eq!(
+            StoreLink::new("https://transac.site/", id()).to_string(),
+            store
+        );
+        assert_eq!(
+            ProductLink::new("https://shop.example//", id()).to_string(),
+            "https://shop.example/product/6f1c2a9e-0d4b-4c7a-9a51-3e2f8b7d1c04"
+        );
+    }
+
+    #[test]
+    fn test_reserved_characters_in_names_are_encoded() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        let message = "Check out my store 'Fish & Chips + Co #1 100% ?a=b' on Transac:";
+        let link = WhatsAppShare::new(message, &url).to_string();
+        let encoded = link.strip_prefix("https://wa.me/?text=").unwrap();
+        // Nothing a URL parser would split on or read as a space
+        for raw in ['&', '+', '#', '?', '=', ' ', '\''] {
+            assert!(!encoded.contains(raw), "{raw} left raw in {encoded}");
+        }
+        assert!(encoded.contains("%26"));
+        assert!(encoded.contains("%2B"));
+        assert!(encoded.contains("%23"));
+        assert!(encoded.contains("%25"));
+        assert!(encoded.contains("%20"));
+        assert_eq!(param(&link, "text"), format!("{message} {url}"));
+    }
+
+    #[test]
+    fn test_emoji_and_other_scripts_survive_the_round_trip() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        for name in [
+            "Chez Maman 😀",
+            "🇨🇲 Douala Fashion",
+            "Famille 👨‍👩‍👧 & amis",
+            "Café Noël",
+            "متجر الأناقة",
+            "𝒮𝓉𝓎𝓁𝑒",
+        ] {
+            let message = format!("Check out my store '{name}' on Transac:");
+            let whatsapp = WhatsAppShare::new(&message, &url).to_string();
+            assert!(whatsapp.is_ascii(), "{whatsapp}");
+            assert_eq!(param(&whatsapp, "text"), format!("{message} {url}"));
+
+            let telegram = TelegramShare::new(&message, &url).to_string();
+            assert!(telegram.is_ascii(), "{telegram}");
+            assert_eq!(param(&telegram, "text"), message);
+            assert_eq!(param(&telegram, "url"), url);
+        }
+        // Four UTF-8 bytes, each encoded on its own
+        let link = WhatsAppShare::new("😀", &url).to_string();
+        assert!(link.starts_with("https://wa.me/?text=%F0%9F%98%80%20https%3A%2F%2F"));
+    }
+
+    #[test]
+    fn test_sms_links_carry_the_body_without_a_recipient() {
+        let url = ProductLink::new(BASE, id()).to_string();
+        let link = SmsShare::new("Wax print, 5 000 XAF:", &url).to_string();
+        assert!(link.starts_with("sms:?&body=Wax%20print%2C%205%20000%20XAF%3A%20https"));
+        assert_eq!(param(&link, "body"), format!("Wax print, 5 000 XAF: {url}"));
+    }
+
+    #[test]
+    fn test_short_texts_are_left_alone() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        let message = "  Check out my store 'Mama Ngono' on Transac:  ";
+        let expected = format!("Check out my store 'Mama Ngono' on Transac: {url}");
+        assert_eq!(WhatsAppShare::new(message, &url).text(), expected);
+        assert_eq!(SmsShare::new(message, &url).text(), expected);
+        assert_eq!(
+            TelegramShare::new(message, &url).text(),
+            "Check out my store 'Mama Ngono' on Transac:"
+        );
+    }
+
+    #[test]
+    fn test_whatsapp_texts_are_cut_to_the_cap_keeping_the_url() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        let message = format!("Check out my store '{}' on Transac:", "a".repeat(2000));
+        let text = WhatsAppShare::new(&message, &url).text();
+        assert_eq!(text.chars().count(), WHATSAPP_MAX_CHARS);
+        assert!(text.ends_with(&format!("{ELLIPSIS} {url}")));
+        assert!(text.starts_with("Check out my store 'aaa"));
+
+        // Exactly at the cap is not cut
+        let fits = "b".repeat(WHATSAPP_MAX_CHARS - url.len() - 1);
+        assert_eq!(
+            WhatsAppShare::new(&fits, &url).text(),
+            format!("{fits} {url}")
+        );
+        let over = format!("{fits}b");
+        assert!(WhatsAppShare::new(&over, &url).text().contains(ELLIPSIS));
+    }
+
+    #[test]
+    fn test_sms_texts_count_emoji_as_two_units() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        let room = SMS_MAX_UNITS - url.len() - 1;
+
+        // As many emoji as fit in characters, but twice too many in units
+        let message = "😀".repeat(room);
+        let text = SmsShare::new(&message, &url).text();
+        let units: usize = text.encode_utf16().count();
+        assert!(units <= SMS_MAX_UNITS, "{units}");
+        assert!(text.ends_with(&format!("{ELLIPSIS} {url}")));
+        // The ellipsis takes one unit, so an odd one is left over, never half
+        // an emoji
+        let kept = text.split(ELLIPSIS).next().unwrap();
+        assert_eq!(kept.chars().count(), (room - 1) / 2);
+        assert!(kept.chars().all(|c| c == '😀'));
+
+        // The same text passes WhatsApp, which counts characters
+        assert_eq!(
+            WhatsAppShare::new(&message, &url).text(),
+            format!("{message} {url}")
+        );
+    }
+
+    #[test]
+    fn test_cuts_drop_the_space_before_the_ellipsis() {
+        let url = StoreLink::new(BASE, id()).to_string();
+        let room = SMS_MAX_UNITS - url.len() - 1;
+        // A word boundary right where the cut falls
+        let message = format!("{} {}", "a".repeat(room - 2), "b".repeat(20));
+        let text = SmsShare::new(&message, &url).text();
+        assert_eq!(text, format!("{}{ELLIPSIS} {url}", "a".repeat(room - 2)));
+    }
+
+    #[test]
+    fn test_urls_too_long_for_any_message_are_sent_alone() {
+        let url = format!("{BASE}/store/{}", "x".repeat(SMS_MAX_UNITS));
+        assert_eq!(SmsShare::new("Look at this", &url).text(), url);
+        let url = format!("{BASE}/{}", "x".repeat(SMS_MAX_UNITS - BASE.len() - 3));
+        // Room for the space and an ellipsis, but for no character before it
+        assert_eq!(SmsShare::new("Look at this", &url).text(), url);
+    }
+
+    #[test]
+    fn test_telegram_texts_are_cut_apart_from_the_url() {
+        let url = ProductLink::new(BASE, id()).to_string();
+        let message = "z".repeat(TELEGRAM_MAX_CHARS + 1);
+        let share = TelegramShare::new(&message, &url);
+        let text = share.text();
+        assert_eq!(text.chars().count(), TELEGRAM_MAX_CHARS);
+        assert!(text.ends_with(ELLIPSIS));
+        assert_eq!(param(&share.to_string(), "url"), url);
+    }
+}
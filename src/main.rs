@@ -3,7 +3,7 @@ use axum::{
     extract::Path,
     http::StatusCode,
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
 use serde::Serialize;
@@ -13,34 +13,71 @@ use utoipa::ToSchema;
 mod api;
 mod auth;
 mod config;
+#[cfg(test)]
+mod contract;
 mod crypto;
+mod currency;
 mod db;
 mod error;
 mod events;
+mod experiments;
+mod features;
+mod health;
+mod import;
+mod jobs;
+mod links;
+mod maintenance;
+mod media_migration;
+mod metrics;
 mod migrator;
+mod moderation;
+mod money;
+mod notifications;
+mod price_alerts;
+mod product_views;
+mod public_stats;
+mod reports;
 mod request_middleware;
+mod retention;
+mod shutdown;
+mod tenant;
+#[cfg(feature = "tls")]
+mod tls;
+mod trust;
 
 use crate::auth::{Claims, JwtService};
+// Named only in `#[utoipa::path]` responses
+#[allow(unused_imports)]
+use crate::crypto::pow::PowDifficultyReport;
 use crate::crypto::PowService;
 use crate::error::AppError;
 use axum::extract::State;
 use axum::middleware;
-use crypto::middleware::crypto_validation_middleware;
+use crypto::middleware::{crypto_validation_middleware, PublicAccess};
 use std::sync::Arc;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::info;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use config::Config;
+use config::{Config, Environment};
 
 // Helper: extract JWT claims from Authorization: Bearer <token>
 fn extract_claims_from_auth(headers: &axum::http::HeaderMap) -> Option<Claims> {
-    let auth_header = headers.get(axum::http::header::AUTHORIZATION)?;
-    let auth_str = auth_header.to_str().ok()?;
-    let token = auth_str.strip_prefix("Bearer ")?;
-    let jwt = JwtService::new().ok()?;
-    jwt.validate_token(token).ok()
+    auth::claims_from_headers(headers)
+}
+
+// Helper: read an optional RFC 3339 timestamp from a JSON request body
+fn timestamp_field(
+    request: &serde_json::Value,
+    key: &str,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    match request.get(key).and_then(|v| v.as_str()) {
+        Some(raw) => chrono::DateTime::parse_from_rfc3339(raw)
+            .map(|at| Some(at.with_timezone(&chrono::Utc)))
+            .map_err(|_| format!("{key} must be an RFC 3339 timestamp")),
+        None => Ok(None),
+    }
 }
 
 // Helper: extract device_id (relay_id) from Authorization header
@@ -48,11 +85,131 @@ fn extract_device_id_from_auth(headers: &axum::http::HeaderMap) -> Option<String
     extract_claims_from_auth(headers).map(|claims| claims.relay_id)
 }
 
+/// State shared by the database-backed routes
+#[derive(Clone)]
+pub struct AppState {
+    db: sea_orm::DatabaseConnection,
+    events: Arc<events::EventDispatcher>,
+    features: Arc<features::FeatureFlags>,
+    site: Arc<config::SiteConfig>,
+    media_limits: Arc<db::media_quota::MediaLimits>,
+    upload_limits: Arc<api::multipart::UploadLimits>,
+    report_limits: Arc<reports::ReportLimits>,
+    field_cipher: Arc<crypto::field::FieldCipher>,
+    database: Arc<config::DatabaseConfig>,
+    experiments: Arc<experiments::Experiments>,
+    confirmations: Arc<auth::confirmation::ConfirmationTokens>,
+    embed_limiter: Arc<api::embed::EmbedLimiter>,
+    referrals: Arc<config::ReferralConfig>,
+    views: Arc<product_views::ViewRecorder>,
+    pow_service: Arc<PowService>,
+    webp: Arc<api::image_conversion::WebpConverter>,
+}
+
+impl axum::extract::FromRef<AppState> for Arc<api::image_conversion::WebpConverter> {
+    fn from_ref(state: &AppState) -> Self {
+        state.webp.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<PowService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pow_service.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<product_views::ViewRecorder> {
+    fn from_ref(state: &AppState) -> Self {
+        state.views.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<config::ReferralConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.referrals.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<api::embed::EmbedLimiter> {
+    fn from_ref(state: &AppState) -> Self {
+        state.embed_limiter.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<auth::confirmation::ConfirmationTokens> {
+    fn from_ref(state: &AppState) -> Self {
+        state.confirmations.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for sea_orm::DatabaseConnection {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<events::EventDispatcher> {
+    fn from_ref(state: &AppState) -> Self {
+        state.events.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<features::FeatureFlags> {
+    fn from_ref(state: &AppState) -> Self {
+        state.features.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<config::DatabaseConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.database.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<experiments::Experiments> {
+    fn from_ref(state: &AppState) -> Self {
+        state.experiments.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<config::SiteConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.site.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<db::media_quota::MediaLimits> {
+    fn from_ref(state: &AppState) -> Self {
+        state.media_limits.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<api::multipart::UploadLimits> {
+    fn from_ref(state: &AppState) -> Self {
+        state.upload_limits.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<reports::ReportLimits> {
+    fn from_ref(state: &AppState) -> Self {
+        state.report_limits.clone()
+    }
+}
+
+impl axum::extract::FromRef<AppState> for Arc<crypto::field::FieldCipher> {
+    fn from_ref(state: &AppState) -> Self {
+        state.field_cipher.clone()
+    }
+}
+
 #[derive(Clone)]
 pub struct ApiContext {
     // pool: sqlx::PgPool,
     pow_service: Arc<PowService>,
     jwt_service: Arc<JwtService>,
+    /// The request gate's, so logout revokes where tokens are checked
+    tokens: Arc<auth::token_cache::TokenGate>,
+    shutdown: shutdown::ShutdownCoordinator,
 }
 
 // Search the bucket for an object whose key contains the given image_id (UUID)
@@ -126,29 +283,81 @@ async fn find_s3_key_by_image_id(image_id: uuid::Uuid) -> Result<Option<String>,
     Ok(None)
 }
 use db::create_connection;
+use health::DependencyHealth;
+use maintenance::MaintenanceState;
 
 #[derive(Serialize, ToSchema)]
 struct HealthResponse {
     message: &'static str,
+    dependencies: DependencyHealth,
+    maintenance: MaintenanceState,
 }
 
-// Uuid schema for OpenAPI - represents a UUID string
-#[derive(Serialize, ToSchema)]
-#[schema(value_type = String, format = "uuid")]
-struct UuidSchema;
-
 /// Health check endpoint
 #[utoipa::path(
     get,
+    operation_id = "healthz",
     path = "/healthz",
     responses(
-        (status = 200, description = "Service is healthy", body = HealthResponse)
+        (status = 200, description = "Service is healthy", body = HealthResponse),
+        (status = 503, description = "Shutting down; send traffic elsewhere", body = HealthResponse)
     ),
     tag = "System"
 )]
-async fn healthz() -> impl IntoResponse {
+async fn healthz(State(ctx): State<ApiContext>) -> impl IntoResponse {
     tracing::debug!("Health check requested");
-    Json(HealthResponse { message: "ok" })
+    let (status, message) = if ctx.shutdown.is_shutting_down() {
+        (StatusCode::SERVICE_UNAVAILABLE, "shutting down")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+    (
+        status,
+        Json(HealthResponse {
+            message,
+            dependencies: health::dependency_health(),
+            maintenance: maintenance::MAINTENANCE.get(),
+        }),
+    )
+}
+
+/// Prometheus metrics endpoint
+async fn metrics_endpoint() -> impl IntoResponse {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        metrics::render(),
+    )
+}
+
+/// Revoke the bearer token the request carries
+#[utoipa::path(
+    post,
+    operation_id = "logout",
+    path = "/api/v1/auth/logout",
+    tag = "POW",
+    responses(
+        (status = 204, description = "Token revoked; this instance refuses it from now on"),
+        (status = 401, description = "Missing or invalid Authorization token")
+    )
+)]
+async fn logout(
+    State(ctx): State<ApiContext>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) => {
+            ctx.tokens.revoke(token);
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::UNAUTHORIZED,
+    }
 }
 
 fn pow_routes() -> Router<ApiContext> {
@@ -159,7 +368,9 @@ fn pow_routes() -> Router<ApiContext> {
 
 #[utoipa::path(
     post,
+    operation_id = "getPowChallenge",
     path = "/api/v1/pow/challenge",
+    tag = "POW",
     responses(
         (status = 200, description = "POW challenge", body = PowChallengeResponse),
     )
@@ -184,10 +395,13 @@ async fn get_pow_challenge(
 
 #[utoipa::path(
     post,
+    operation_id = "verifyPowSolution",
     path = "/api/v1/pow/verify",
+    tag = "POW",
     request_body = VerificationRequest,
     responses(
         (status = 200, description = "POW solution verified", body = TokenResponse),
+        (status = 400, description = "Unknown or expired challenge"),
         (status = 422, description = "Invalid solution")
     )
 )]
@@ -200,23 +414,64 @@ async fn verify_pow_solution(
         "POW solution verification requested"
     );
 
+    let identity = tenant::resolve_identity(&request.relay_id, request.tenant_id.as_deref())
+        .map_err(AppError::Validation)?;
     ctx.pow_service.verify_solution(&request.solution)?;
     tracing::debug!("POW solution verified successfully");
 
     // Generate a real JWT token
     let token = ctx
         .jwt_service
-        .generate_token(request.relay_id.clone(), request.public_key.clone())
+        .generate_token(
+            identity.relay_id.clone(),
+            request.public_key.clone(),
+            identity.tenant_id.clone(),
+        )
         .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
 
     tracing::info!(
-        relay_id = %request.relay_id,
+        relay_id = %identity.relay_id,
+        tenant_id = %identity.tenant_id,
         "JWT token generated successfully"
     );
 
     Ok(Json(crypto::types::TokenResponse { token }))
 }
 
+/// Current PoW difficulty and recent solve times, for tuning `POW_DIFFICULTY`
+#[utoipa::path(
+    get,
+    operation_id = "getPowDifficulty",
+    path = "/api/v1/admin/pow/difficulty",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Effective difficulty and percentile solve times of recent accepted solutions", body = PowDifficultyReport),
+        (status = 401, description = "Missing or invalid Authorization token"),
+        (status = 403, description = "Admin role required")
+    )
+)]
+async fn get_pow_difficulty(
+    State(pow_service): State<Arc<PowService>>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = api::admin::require_admin(&headers) {
+        return err.into_response();
+    }
+    Json(pow_service.difficulty_report()).into_response()
+}
+
+/// Screen a store's name and description for offensive language
+fn moderate_store_profile(
+    name: &str,
+    description: Option<&str>,
+) -> Result<(String, Option<String>), api::moderation::TextRejectionResponse> {
+    use moderation::text::TextField;
+    Ok((
+        api::moderation::moderate_text(TextField::StoreName, name)?,
+        api::moderation::moderate_optional_text(TextField::StoreDescription, description)?,
+    ))
+}
+
 // Create store endpoint with database integration
 async fn create_store_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
@@ -246,12 +501,7 @@ async fn create_store_endpoint(
         }
     };
 
-    let name = match request.get("name").and_then(|v| v.as_str()) {
-        Some(name) => name,
-        None => {
-            return (StatusCode::BAD_REQUEST, "Name is required").into_response();
-        }
-    };
+    let name = request.get("name").and_then(|v| v.as_str()).unwrap_or("");
 
     let description = request.get("description").and_then(|v| v.as_str());
 
@@ -261,19 +511,45 @@ async fn create_store_endpoint(
 
     let contact_whatsapp = request.get("contact_whatsapp").and_then(|v| v.as_str());
 
+    let default_return_policy = request
+        .get("default_return_policy")
+        .and_then(|v| v.as_str());
+
+    let input = api::validation::StoreInput {
+        name,
+        logo_url,
+        location,
+        contact_whatsapp,
+        ..Default::default()
+    };
+    if let Err(errors) = api::validation::validate_store(&input) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(api::validation::ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+
+    let (name, description) = match moderate_store_profile(name, description) {
+        Ok(profile) => profile,
+        Err(rejection) => return rejection.into_response(),
+    };
+
     // Owner is taken from JWT claims, ignore client-sent owner_device_id
     let owner_device_id = Some(claims.relay_id.as_str());
 
     match Store::create(
         &pool,
-        name,
-        description,
+        &name,
+        description.as_deref(),
         logo_url,
         location,
         None, // contact_phone
         None, // contact_email
         contact_whatsapp,
         owner_device_id,
+        default_return_policy,
+        &claims.tenant_id,
     )
     .await
     {
@@ -292,20 +568,66 @@ async fn create_store_endpoint(
 }
 
 // List stores endpoint
+/// Ordering of the public store list for a caller in the feed experiment,
+/// logging their exposure; `None` when no experiment runs or the caller
+/// can't be identified
+async fn feed_experiment_sort(
+    db: &sea_orm::DatabaseConnection,
+    experiments: &experiments::Experiments,
+    headers: &axum::http::HeaderMap,
+) -> Option<db::stores::StoreSort> {
+    let experiment = experiments.feed.as_ref()?;
+    let subject = experiments::subject(headers)?;
+    let variant = experiment.assign(&subject);
+    if let Err(err) = db::experiments::record_exposure(
+        db,
+        &experiment.name,
+        &variant.name,
+        &subject,
+        chrono::Utc::now(),
+    )
+    .await
+    {
+        tracing::warn!(experiment = %experiment.name, error = %err, "Exposure not recorded");
+    }
+    // Variants are checked against the sorts when the config is loaded
+    variant.name.parse().ok()
+}
+
 async fn list_stores_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
+    State(experiments): State<Arc<experiments::Experiments>>,
     headers: axum::http::HeaderMap,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+    page: api::pagination::PageRequest,
 ) -> impl IntoResponse {
-    use crate::db::stores::Store;
+    use crate::api::fields::{FieldSelection, STORE_FIELDS};
+    use crate::db::stores::{Store, StoreSort};
 
     tracing::debug!("Stores list requested");
 
+    let fields = match FieldSelection::parse(params.get("fields").map(String::as_str), STORE_FIELDS)
+    {
+        Ok(fields) => fields,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let sort = match params.get("sort").map(|s| s.parse::<StoreSort>()) {
+        Some(Ok(sort)) => Some(sort),
+        Some(Err(err)) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        None => None,
+    };
+    let tenant = match tenant::tenant_from_headers(&headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+
     // If a valid Authorization token is provided, restrict to the owner's stores (seller flow)
     if let Some(device_id) = extract_device_id_from_auth(&headers) {
-        match Store::list_by_owner(&pool, &device_id).await {
-            Ok(stores) => {
-                tracing::info!(owner_device_id = %device_id, count = stores.len(), "Found stores for owner");
-                let response = serde_json::json!({ "stores": stores });
+        match Store::list_page_by_owner(&pool, &tenant, &device_id, page.page, page.per_page).await
+        {
+            Ok((stores, total)) => {
+                tracing::info!(owner_device_id = %device_id, count = total, "Found stores for owner");
+                let response = page.numbered(stores, total).project(fields.as_ref());
                 return (StatusCode::OK, Json(response)).into_response();
             }
             Err(err) => {
@@ -315,11 +637,19 @@ async fn list_stores_endpoint(
         }
         // Non-seller roles fall through to public list
     }
-    // No Authorization header or non-seller role -> public list (buyer flow)
-    match Store::list(&pool).await {
-        Ok(stores) => {
-            tracing::info!("Found {} stores (public list)", stores.len());
-            let response = serde_json::json!({ "stores": stores });
+    // No Authorization header or non-seller role -> public list (buyer flow).
+    // Callers who pick an order themselves stay out of the feed experiment.
+    let sort = match sort {
+        Some(sort) => sort,
+        None => feed_experiment_sort(&pool, &experiments, &headers)
+            .await
+            .unwrap_or_default(),
+    };
+    match Store::list(&pool, &tenant, sort, page.page, page.per_page).await {
+        Ok((stores, total)) => {
+            tracing::info!("Found {} stores (public list)", total);
+            let stores: Vec<_> = stores.into_iter().map(api::stores::public_store).collect();
+            let response = page.numbered(stores, total).project(fields.as_ref());
             (StatusCode::OK, Json(response)).into_response()
         }
         Err(err) => {
@@ -332,40 +662,42 @@ async fn list_stores_endpoint(
 // Delete store endpoint
 async fn delete_store_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
-    Path(store_id): Path<String>,
+    State(confirmations): State<Arc<auth::confirmation::ConfirmationTokens>>,
+    api::extract::UuidPath(uuid): api::extract::UuidPath<uuid::Uuid>,
     headers: axum::http::HeaderMap,
 ) -> impl IntoResponse {
     use crate::db::stores::Store;
-    use uuid::Uuid;
 
-    tracing::debug!(store_id = %store_id, "Store deletion requested");
-
-    // Parse the store_id to UUID
-    let uuid = match Uuid::parse_str(&store_id) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return (StatusCode::BAD_REQUEST, "Invalid store ID format").into_response();
-        }
-    };
+    tracing::debug!(store_id = %uuid, "Store deletion requested");
 
-    // Require valid JWT with seller role and ensure ownership before delete
-    let claims = match extract_claims_from_auth(&headers) {
-        Some(c) if c.role == "seller" => c,
-        None => {
+    // Require a seller JWT (or a stores:write API key) and ensure ownership before delete
+    let principal = match auth::authenticate(&pool, &headers).await {
+        Ok(Some(p)) if p.claims.role == "seller" => p,
+        Ok(None) => {
             return (
                 StatusCode::UNAUTHORIZED,
                 "Missing or invalid Authorization token",
             )
                 .into_response();
         }
-        Some(_) => {
+        Ok(Some(_)) => {
             return (
                 StatusCode::FORBIDDEN,
                 "Insufficient role for deleting a store",
             )
                 .into_response();
         }
+        Err(err) => return err.into_response(),
     };
+    if !principal.allows(auth::ApiScope::StoresWrite, uuid) {
+        return (
+            StatusCode::FORBIDDEN,
+            "API key lacks stores:write for this store",
+        )
+            .into_response();
+    }
+    let actor = principal.actor();
+    let claims = principal.claims;
 
     // Verify store belongs to this device
     match Store::get(&pool, uuid).await {
@@ -377,9 +709,23 @@ async fn delete_store_endpoint(
         Err(_) => return (StatusCode::NOT_FOUND, "Store not found").into_response(),
     }
 
+    // Nothing is deleted until the caller repeats the call with the token
+    let summary = match api::stores::deletion_summary(&pool, uuid).await {
+        Ok(summary) => summary,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    };
+    let target = auth::confirmation::Target {
+        action: "delete_store",
+        entity: uuid,
+        caller: &actor,
+    };
+    if let Some(response) = confirmations.confirm(&headers, target, summary, chrono::Utc::now()) {
+        return response;
+    }
+
     match Store::delete(&pool, uuid).await {
         Ok(_) => {
-            tracing::info!("Store deleted successfully: {}", store_id);
+            tracing::info!("Store deleted successfully: {}", uuid);
             StatusCode::NO_CONTENT.into_response()
         }
         Err(err) => {
@@ -392,21 +738,26 @@ async fn delete_store_endpoint(
 // Update store endpoint
 async fn update_store_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
-    Path(store_id): Path<String>,
-    _headers: axum::http::HeaderMap,
+    api::extract::UuidPath(uuid): api::extract::UuidPath<uuid::Uuid>,
+    axum::extract::Query(query): axum::extract::Query<api::stores::UpdateStoreQuery>,
+    headers: axum::http::HeaderMap,
     Json(request): Json<serde_json::Value>,
 ) -> impl IntoResponse {
     use crate::db::stores::Store;
-    use uuid::Uuid;
 
-    tracing::debug!(store_id = %store_id, "Store update requested");
+    tracing::debug!(store_id = %uuid, "Store update requested");
 
-    // Parse the store_id to UUID
-    let uuid = match Uuid::parse_str(&store_id) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return (StatusCode::BAD_REQUEST, "Invalid store ID format").into_response();
+    // An API key may only update its own store, and only with stores:write
+    let changed_by = match auth::authenticate(&pool, &headers).await {
+        Ok(Some(principal)) if !principal.allows(auth::ApiScope::StoresWrite, uuid) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "API key lacks stores:write for this store",
+            )
+                .into_response();
         }
+        Ok(principal) => principal.map(|principal| principal.actor()),
+        Err(err) => return err.into_response(),
     };
 
     let name = request.get("name").and_then(|v| v.as_str()).unwrap_or("");
@@ -419,21 +770,61 @@ async fn update_store_endpoint(
 
     let contact_whatsapp = request.get("contact_whatsapp").and_then(|v| v.as_str());
 
+    let default_return_policy = request
+        .get("default_return_policy")
+        .and_then(|v| v.as_str());
+
+    // Contact visibility is only changed when the client sends it
+    let visibility = db::stores::ContactVisibility {
+        show_phone: request.get("show_phone").and_then(|v| v.as_bool()),
+        show_whatsapp: request.get("show_whatsapp").and_then(|v| v.as_bool()),
+        show_email: request.get("show_email").and_then(|v| v.as_bool()),
+    };
+
+    let input = api::validation::StoreInput {
+        name,
+        logo_url,
+        location,
+        contact_whatsapp,
+        ..Default::default()
+    };
+    if let Err(errors) = api::validation::validate_store(&input) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(api::validation::ValidationReport::from(errors)),
+        )
+            .into_response();
+    }
+
+    let (name, description) = match moderate_store_profile(name, description) {
+        Ok(profile) => profile,
+        Err(rejection) => return rejection.into_response(),
+    };
+
     match Store::update(
         &pool,
         uuid,
-        name,
-        description,
+        &name,
+        description.as_deref(),
         logo_url,
         location,
         None, // contact_phone
         None, // contact_email
         contact_whatsapp,
+        default_return_policy,
+        visibility,
+        db::history::ChangeOrigin::edit(changed_by.as_deref()),
     )
     .await
     {
         Ok(store) => {
-            tracing::info!("Store updated successfully: {}", store_id);
+            tracing::info!("Store updated successfully: {}", uuid);
+            if query.apply_to_products.unwrap_or(false) {
+                if let Err(err) = Store::apply_default_return_policy(&pool, &store).await {
+                    tracing::error!("Failed to cascade default return policy: {}", err);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+                }
+            }
             let response = serde_json::json!({
                 "store": store
             });
@@ -449,59 +840,232 @@ async fn update_store_endpoint(
 // Simple product creation endpoint
 async fn create_product_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
+    State(events): State<Arc<events::EventDispatcher>>,
+    State(media_limits): State<Arc<db::media_quota::MediaLimits>>,
+    State(site): State<Arc<config::SiteConfig>>,
+    headers: axum::http::HeaderMap,
     Json(request): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    use crate::db::products::Product;
+    use crate::api::products::{
+        announce_sale, publishes_on_create, stored_attributes, InsufficientMedia, ProductResponse,
+    };
+    use crate::api::validation::{
+        integer_field, number_field, validate_product, ProductInput, ValidationReport,
+    };
+    use crate::db::products::{Product, SKU_TAKEN};
+    use crate::db::return_policy::ReturnTerms;
     use uuid::Uuid;
 
     tracing::debug!("Product creation requested");
 
     let name = request.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let sku = request.get("sku").and_then(|v| v.as_str());
     let description = request.get("description").and_then(|v| v.as_str());
-    let price = request.get("price").and_then(|v| v.as_f64()).unwrap_or(0.0);
-    let quantity_available = request
-        .get("quantity_available")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(0) as i32;
-    let store_id_str = request
-        .get("store_id")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-
-    tracing::debug!(name = %name, price = %price, quantity = %quantity_available, store_id = %store_id_str, "Parsed product values");
-
-    let store_id = match Uuid::parse_str(store_id_str) {
-        Ok(uuid) => {
-            tracing::debug!(store_id = %uuid, "Successfully parsed store_id");
-            uuid
+    let return_policy = request.get("return_policy").and_then(|v| v.as_str());
+    let returns_accepted = request.get("returns_accepted").and_then(|v| v.as_bool());
+    let return_conditions = request.get("return_conditions").and_then(|v| v.as_str());
+    let delivery_options = match request.get("delivery_options") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(raw) => match serde_json::from_value(raw.clone()) {
+            Ok(options) => Some(options),
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid delivery_options: {e}"),
+                )
+                    .into_response()
+            }
+        },
+    };
+    let currency = request.get("currency").and_then(|v| v.as_str());
+    let now = chrono::Utc::now();
+
+    let (publish_at, sale_ends_at) = match (
+        timestamp_field(&request, "publish_at"),
+        timestamp_field(&request, "sale_ends_at"),
+    ) {
+        (Ok(publish_at), Ok(sale_ends_at)) => (publish_at, sale_ends_at),
+        (Err(err), _) | (_, Err(err)) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    let category_id = match request.get("category_id").and_then(|v| v.as_str()) {
+        Some(raw) => match Uuid::parse_str(raw) {
+            Ok(id) => Some(id),
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Invalid category_id format").into_response()
+            }
+        },
+        None => None,
+    };
+    // Checked against the category's definitions by `validate_product`
+    let attributes = request.get("attributes");
+    // Refused rather than coerced, so "1500" is not a free product and
+    // 2^32 - 3 is not some other stock level
+    let (price, sale_price, quantity_available, low_stock_threshold, return_window_days) = match (
+        number_field(&request, "price"),
+        number_field(&request, "sale_price"),
+        integer_field(&request, "quantity_available"),
+        integer_field(&request, "low_stock_threshold"),
+        integer_field(&request, "return_window_days"),
+    ) {
+        (Ok(price), Ok(sale_price), Ok(quantity), Ok(threshold), Ok(window)) => (
+            price.unwrap_or(0.0),
+            sale_price,
+            quantity.unwrap_or(0),
+            threshold,
+            window,
+        ),
+        (price, sale_price, quantity, threshold, window) => {
+            let errors: Vec<_> = [
+                price.err(),
+                sale_price.err(),
+                quantity.err(),
+                threshold.err(),
+                window.err(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ValidationReport::from(errors)),
+            )
+                .into_response();
         }
-        Err(e) => {
-            tracing::error!("Failed to parse store_id '{}': {}", store_id_str, e);
-            return (StatusCode::BAD_REQUEST, "Invalid store_id format").into_response();
+    };
+    // Omitted, it defaults to the API key's store or the seller's only store
+    let store_id = match request.get("store_id") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(raw) => match raw.as_str().map(Uuid::parse_str) {
+            Some(Ok(uuid)) => Some(uuid),
+            _ => {
+                tracing::error!("Invalid store_id {}", raw);
+                return (StatusCode::BAD_REQUEST, "Invalid store_id format").into_response();
+            }
+        },
+    };
+
+    tracing::debug!(name = %name, price = %price, quantity = %quantity_available, store_id = ?store_id, "Parsed product values");
+
+    let store_id = match api::products::product_store(&pool, &headers, store_id).await {
+        Ok(store) => store.id,
+        Err(err) => return err.into_response(),
+    };
+
+    let input = ProductInput {
+        store_id: Some(store_id),
+        product_id: None,
+        sku,
+        name,
+        price,
+        currency,
+        quantity_available,
+        low_stock_threshold,
+        category_id,
+        attributes,
+        publish_at,
+        sale_price,
+        sale_ends_at,
+        returns_accepted,
+        return_window_days,
+        return_conditions,
+        delivery_options,
+    };
+    let sale = match validate_product(&pool, &input, now).await {
+        Ok(sale) => sale,
+        Err(errors) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ValidationReport::from(errors)),
+            )
+                .into_response()
         }
     };
 
+    let held_terms = match api::moderation::screen_listing(name, description) {
+        Ok(terms) => terms,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let draft = request
+        .get("draft")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if publishes_on_create(draft, publish_at) {
+        // A new product has no media yet
+        if let Some(refused) = InsufficientMedia::check(media_limits.min_images_to_publish, 0) {
+            return refused.into_response();
+        }
+    }
+
     tracing::debug!("About to call Product::create");
 
     match Product::create(
         &pool,
         store_id,
-        None, // sku
+        sku,
         name,
         description,
         price,
+        currency.unwrap_or(&site.currency),
         quantity_available,
+        low_stock_threshold,
         None, // image_id
+        ReturnTerms::from_request(
+            returns_accepted,
+            return_window_days,
+            return_conditions,
+            return_policy,
+        ),
+        delivery_options,
+        publish_at,
+        draft,
+        sale,
+        category_id,
+        stored_attributes(attributes.cloned().unwrap_or_default()),
     )
     .await
     {
         Ok(product) => {
             tracing::info!(product_id = %product.id, "Product created successfully");
+            let held_for_review = held_terms.is_some();
+            let product = match held_terms {
+                Some(terms) => {
+                    match api::moderation::hold_listing(&pool, &events, product.clone(), &terms)
+                        .await
+                    {
+                        Ok(product) => product,
+                        Err(err) => {
+                            // Never leave a flagged listing live
+                            let _ = Product::delete_permanently(&pool, product.id).await;
+                            return (StatusCode::INTERNAL_SERVER_ERROR, err).into_response();
+                        }
+                    }
+                }
+                None => product,
+            };
+            // Scheduled products are announced by the publish scheduler once
+            // they go live instead
+            if product.is_published {
+                let event = crate::events::create_event(
+                    crate::events::EventType::ProductCreated,
+                    product.id,
+                    serde_json::json!({
+                        "store_id": product.store_id,
+                        "name": product.name,
+                        "price": product.price
+                    }),
+                );
+                let _ = events.dispatch(event).await;
+                if sale.is_some() {
+                    announce_sale(&events, &product).await;
+                }
+            }
             let response = serde_json::json!({
-                "product": product
+                "product": ProductResponse::new(product, now),
+                "held_for_review": held_for_review
             });
             (StatusCode::CREATED, Json(response)).into_response()
         }
+        Err(err) if err == SKU_TAKEN => (StatusCode::CONFLICT, err).into_response(),
         Err(err) => {
             tracing::error!(error = %err, "Failed to create product");
             (
@@ -516,9 +1080,19 @@ async fn create_product_endpoint(
 // Simple product listing endpoint
 async fn list_products_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
+    headers: axum::http::HeaderMap,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+    page: api::pagination::PageRequest,
+    locale: money::Locale,
 ) -> impl IntoResponse {
-    use crate::db::products::Product;
+    use crate::api::bundles::{BundleResponse, Listing};
+    use crate::api::delta::{list_body, version, KnownVersions};
+    use crate::api::fields::{FieldSelection, PRODUCT_FIELDS};
+    use crate::api::products::{ProductResponse, SellerProductResponse};
+    use crate::db::bundles::Bundle;
+    use crate::db::category_attributes::AttributeFilter;
+    use crate::db::products::{currency_error, PriceFilter, Product};
+    use crate::db::stores::Store;
     use uuid::Uuid;
 
     let store_id = params.get("store_id").and_then(|s| Uuid::parse_str(s).ok());
@@ -532,13 +1106,252 @@ async fn list_products_endpoint(
     }
 
     let store_id = store_id.unwrap();
-    let result = Product::list_by_store(&pool, store_id).await;
+
+    let parse_price = |key: &str| match params.get(key) {
+        Some(raw) => raw
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| format!("{key} must be a number")),
+        None => Ok(None),
+    };
+    let returns_accepted = match params.get("returns_accepted") {
+        Some(raw) => match raw.parse::<bool>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "returns_accepted must be true or false",
+                )
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+    let include_archived = match params.get("include_archived") {
+        Some(raw) => match raw.parse::<bool>() {
+            Ok(value) => value,
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "include_archived must be true or false",
+                )
+                    .into_response()
+            }
+        },
+        None => false,
+    };
+    let delivery_available = match params.get("delivery_available") {
+        Some(raw) => match raw.parse::<bool>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "delivery_available must be true or false",
+                )
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+    let currency = match params.get("currency") {
+        Some(code) => match currency_error("currency", code) {
+            None => Some(code.clone()),
+            Some(problem) => return (StatusCode::BAD_REQUEST, problem).into_response(),
+        },
+        None => None,
+    };
+    let mut price_filter = match (
+        parse_price("min_price"),
+        parse_price("max_price"),
+        params.get("sort").map(|s| s.parse()).transpose(),
+        AttributeFilter::from_query(
+            params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        ),
+    ) {
+        (Ok(min_price), Ok(max_price), Ok(sort), Ok(attributes)) => PriceFilter {
+            min_price,
+            max_price,
+            returns_accepted,
+            delivery_available,
+            currency,
+            sort: sort.unwrap_or_default(),
+            attributes,
+            include_archived: false,
+        },
+        (Err(err), _, _, _) | (_, Err(err), _, _) | (_, _, Err(err), _) | (_, _, _, Err(err)) => {
+            return (StatusCode::BAD_REQUEST, err).into_response();
+        }
+    };
+    let fields =
+        match FieldSelection::parse(params.get("fields").map(String::as_str), PRODUCT_FIELDS) {
+            Ok(fields) => fields,
+            Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        };
+    let known = match params.get("known_ids").map(|raw| KnownVersions::parse(raw)) {
+        Some(Ok(known)) => known,
+        Some(Err(err)) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        None => None,
+    };
+
+    let principal = match auth::authenticate(&pool, &headers).await {
+        Ok(principal) => principal,
+        Err(err) => return err.into_response(),
+    };
+    let tenant = match tenant::request_tenant(principal.as_ref().map(|p| &p.claims), &headers) {
+        Ok(tenant) => tenant,
+        Err(err) => return err.into_response(),
+    };
+    // The store owner also sees scheduled products, tagged with their state
+    let is_owner = match principal {
+        Some(principal) if principal.allows(auth::ApiScope::ProductsRead, store_id) => {
+            Store::get(&pool, store_id)
+                .await
+                .map(|store| {
+                    store.owner_device_id.as_deref() == Some(principal.claims.relay_id.as_str())
+                })
+                .unwrap_or(false)
+        }
+        _ => false,
+    };
+    // Archived products are the owner's to see
+    price_filter.include_archived = include_archived && is_owner;
+    // Bundles follow the products, tagged `type: bundle`; owners also see inactive ones
+    let bundles = match Bundle::list_by_store(&pool, &tenant, store_id, !is_owner).await {
+        Ok(bundles) => bundles
+            .into_iter()
+            .filter(|details| price_filter.admits(details.bundle.price))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to list bundles");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list products").into_response();
+        }
+    };
+    let bundle_versions: Vec<_> = bundles
+        .iter()
+        .map(|b| (b.bundle.id, version(b.bundle.updated_at)))
+        .collect();
+
+    // Without known versions only one page goes out, so only that page of
+    // products is read; bundles follow the last product
+    if known.is_none() {
+        let now = chrono::Utc::now();
+        let paused = !is_owner
+            && Store::get(&pool, store_id)
+                .await
+                .is_ok_and(|store| db::stores::is_paused(&store, now));
+        let products = Product::page_by_store(
+            &pool,
+            &tenant,
+            store_id,
+            price_filter,
+            !is_owner,
+            page.page,
+            page.per_page,
+        )
+        .await;
+        let (products, total) = match products {
+            Ok(products) => products,
+            Err(err) => {
+                tracing::error!(error = %err, "Failed to list products");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list products")
+                    .into_response();
+            }
+        };
+        if is_owner {
+            let products = products
+                .into_iter()
+                .map(|product| {
+                    Listing::Product(SellerProductResponse::new(product, now).localized(locale))
+                })
+                .collect();
+            let bundles = bundles
+                .into_iter()
+                .map(|b| Listing::Bundle(BundleResponse::new(b)))
+                .collect();
+            let body = page.numbered_then(products, total, bundles);
+            return Json(body.project(fields.as_ref())).into_response();
+        }
+        let products = products
+            .into_iter()
+            .map(|product| {
+                Listing::Product(
+                    ProductResponse::new(product, now)
+                        .store_paused(paused)
+                        .localized(locale),
+                )
+            })
+            .collect();
+        let bundles = bundles
+            .into_iter()
+            .map(|b| Listing::Bundle(BundleResponse::new(b).store_paused(paused)))
+            .collect();
+        let body = page.numbered_then(products, total, bundles);
+        return Json(body.project(fields.as_ref())).into_response();
+    }
+
+    if is_owner {
+        return match Product::list_by_store(&pool, &tenant, store_id, price_filter).await {
+            Ok(products) => {
+                let now = chrono::Utc::now();
+                let versions: Vec<_> = products
+                    .iter()
+                    .map(|p| (p.id, version(p.updated_at)))
+                    .chain(bundle_versions)
+                    .collect();
+                let listings: Vec<Listing<SellerProductResponse>> = products
+                    .into_iter()
+                    .map(|product| {
+                        Listing::Product(SellerProductResponse::new(product, now).localized(locale))
+                    })
+                    .chain(
+                        bundles
+                            .into_iter()
+                            .map(|b| Listing::Bundle(BundleResponse::new(b))),
+                    )
+                    .collect();
+                let response =
+                    list_body(listings, &versions, known.as_ref(), fields.as_ref(), &page);
+                (StatusCode::OK, Json(response)).into_response()
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "Failed to list products");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list products").into_response()
+            }
+        };
+    }
+
+    // Products of a paused store stay listed on its page, flagged unavailable
+    let now = chrono::Utc::now();
+    let paused = Store::get(&pool, store_id)
+        .await
+        .is_ok_and(|store| db::stores::is_paused(&store, now));
+    let result = Product::list_visible_by_store(&pool, &tenant, store_id, price_filter).await;
 
     match result {
         Ok(products) => {
-            let response = serde_json::json!({
-                "products": products
-            });
+            let versions: Vec<_> = products
+                .iter()
+                .map(|p| (p.id, version(p.updated_at)))
+                .chain(bundle_versions)
+                .collect();
+            let listings: Vec<Listing<ProductResponse>> = products
+                .into_iter()
+                .map(|product| {
+                    Listing::Product(
+                        ProductResponse::new(product, now)
+                            .store_paused(paused)
+                            .localized(locale),
+                    )
+                })
+                .chain(
+                    bundles
+                        .into_iter()
+                        .map(|b| Listing::Bundle(BundleResponse::new(b).store_paused(paused))),
+                )
+                .collect();
+            let response = list_body(listings, &versions, known.as_ref(), fields.as_ref(), &page);
             (StatusCode::OK, Json(response)).into_response()
         }
         Err(err) => {
@@ -551,19 +1364,13 @@ async fn list_products_endpoint(
 // Simple media upload endpoint for products
 async fn upload_product_media_endpoint(
     State(pool): State<sea_orm::DatabaseConnection>,
-    Path(product_id): Path<String>,
-    mut multipart: axum::extract::Multipart,
+    State(media_limits): State<Arc<db::media_quota::MediaLimits>>,
+    State(webp_converter): State<Arc<api::image_conversion::WebpConverter>>,
+    api::extract::UuidPath(product_uuid): api::extract::UuidPath<uuid::Uuid>,
+    tx: api::transaction::Tx,
+    upload: api::multipart::MultipartFile,
 ) -> impl IntoResponse {
-    use uuid::Uuid;
-
-    tracing::info!(product_id = %product_id, "Media upload requested");
-
-    let product_uuid = match Uuid::parse_str(&product_id) {
-        Ok(uuid) => uuid,
-        Err(_) => {
-            return (StatusCode::BAD_REQUEST, "Invalid product ID format").into_response();
-        }
-    };
+    tracing::info!(product_id = %product_uuid, "Media upload requested");
 
     // Check if product exists using SeaORM
     use crate::db::products::Product;
@@ -576,102 +1383,519 @@ async fn upload_product_media_endpoint(
         }
     }
 
-    // Process the uploaded file
-    tracing::debug!("Starting multipart processing");
-    while let Some(field) = match multipart.next_field().await {
-        Ok(field) => field,
-        Err(e) => {
-            tracing::error!("Failed to get next multipart field: {}", e);
-            return (StatusCode::BAD_REQUEST, format!("Multipart error: {e}")).into_response();
-        }
-    } {
-        let name = field.name().unwrap_or("unknown").to_string();
-        let filename = field.file_name().unwrap_or("unknown").to_string();
-        let content_type = field
-            .content_type()
-            .unwrap_or("application/octet-stream")
-            .to_string();
-
-        tracing::debug!(field_name = %name, filename = %filename, content_type = %content_type, "Processing multipart field");
-
-        if name == "file" {
-            let data = match field.bytes().await {
-                Ok(data) => data,
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to read file data");
-                    return (StatusCode::BAD_REQUEST, "Failed to read file").into_response();
-                }
-            };
+    let api::multipart::UploadedFile {
+        name: filename,
+        content_type,
+        bytes: data,
+    } = upload.file;
+    tracing::debug!(filename = %filename, content_type = %content_type, size_bytes = data.len(), "File read from multipart");
 
-            tracing::debug!(size_bytes = data.len(), "File read from multipart");
+    // Refuse before anything reaches the bucket
+    if let Err(refused) =
+        api::products::charge_media_upload(&tx, &media_limits, product_uuid, data.len()).await
+    {
+        return refused;
+    }
 
-            // Upload to MinIO/S3 using the proper S3MediaStorage implementation
-            use crate::api::media_storage::{MediaStorage, S3MediaStorage};
+    // Upload to MinIO/S3 using the proper S3MediaStorage implementation
+    use crate::api::media_storage::{
+        connect_s3_storage, storage_unavailable_response, MediaStorage, StorageConnectError,
+    };
 
-            let storage = match S3MediaStorage::new().await {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to initialize S3 storage");
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to initialize storage",
-                    )
-                        .into_response();
-                }
-            };
+    let storage = match connect_s3_storage().await {
+        Ok(s) => s,
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(e)) => {
+            tracing::error!(error = %e, "Failed to initialize S3 storage");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to initialize storage",
+            )
+                .into_response();
+        }
+    };
 
-            // Generate image_id first so the S3 key contains this UUID
-            let image_id = uuid::Uuid::new_v4();
-            let s3_key = match storage
-                .upload_media_data(
-                    product_uuid,
-                    &filename,
-                    &data,
-                    &content_type,
-                    Some(image_id),
-                )
-                .await
-            {
-                Ok(key) => {
-                    tracing::info!(s3_key = %key, "File uploaded to object storage");
-                    key
-                }
-                Err(e) => {
-                    tracing::error!(error = %e, "Failed to upload to object storage");
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("MinIO upload failed: {e}"),
-                    )
-                        .into_response();
-                }
-            };
+    // Generate image_id first so the S3 key contains this UUID
+    let image_id = uuid::Uuid::new_v4();
+    let s3_key = match storage
+        .upload_media_data(
+            product_uuid,
+            &filename,
+            &data,
+            &content_type,
+            Some(image_id),
+        )
+        .await
+    {
+        Ok(key) => {
+            tracing::info!(s3_key = %key, "File uploaded to object storage");
+            key
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to upload to object storage");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("MinIO upload failed: {e}"),
+            )
+                .into_response();
+        }
+    };
 
-            // Update the product with the image_id
-            use crate::db::products::Product;
-            if let Err(e) = Product::update_image(&pool, product_uuid, Some(image_id)).await {
-                tracing::error!(error = %e, "Failed to update product with image_id");
-                // Continue anyway, as the image was uploaded successfully
-            }
+    // Store a WebP variant alongside the original when it saves bytes
+    use crate::api::image_conversion::store_webp_variant;
+    let webp = store_webp_variant(
+        &storage,
+        &webp_converter,
+        product_uuid,
+        image_id,
+        &filename,
+        &data,
+    )
+    .await;
+    let has_webp = webp.is_some();
+
+    let perceptual_hash = moderation::image_hash::dhash_async(data.to_vec()).await;
+
+    // The media row and the product's image_id are saved together or not at all
+    use crate::db::media_similarity::MediaSimilarity;
+    use crate::db::product_media::ProductMedia;
+    let media = match ProductMedia::create(
+        &*tx,
+        image_id,
+        product_uuid,
+        &s3_key,
+        &content_type,
+        data.len() as i64,
+        &crate::db::product_media::content_hash(&data),
+        perceptual_hash,
+        webp,
+    )
+    .await
+    {
+        Ok(media) => media,
+        Err(e) => {
+            tracing::error!(error = %e, s3_key = %s3_key, "Failed to record product media");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+    // A copied photo doesn't stop the upload; it is queued for an admin
+    if let Err(e) = MediaSimilarity::flag_matches(&*tx, &media).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
 
-            tracing::info!(image_id = %image_id, s3_key = %s3_key, "Image stored");
+    if let Err(e) = Product::update_image(&*tx, product_uuid, Some(image_id)).await {
+        tracing::error!(error = %e, s3_key = %s3_key, "Failed to update product with image_id");
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
 
-            let response = serde_json::json!({
-                "success": true,
-                "product_id": product_uuid,
-                "image_id": image_id,
-                "filename": filename,
-                "size": data.len(),
-                "content_type": content_type,
-                "s3_key": s3_key,
-                // UUID based serving endpoint
-                "image_url": format!("/api/v1/media/{}", image_id)
-            });
+    tracing::info!(image_id = %image_id, s3_key = %s3_key, "Image stored");
 
-            return (StatusCode::OK, Json(response)).into_response();
-        }
-    }
+    use crate::api::products::{media_url, webp_media_url};
+    let original_url = media_url(image_id);
+    let image_url = if has_webp {
+        webp_media_url(image_id)
+    } else {
+        original_url.clone()
+    };
+
+    let response = serde_json::json!({
+        "success": true,
+        "product_id": product_uuid,
+        "image_id": image_id,
+        "filename": filename,
+        "size": data.len(),
+        "content_type": content_type,
+        "s3_key": s3_key,
+        // UUID based serving endpoint, preferring the WebP variant
+        "image_url": image_url,
+        "original_url": original_url
+    });
+
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+/// Routes on [`ApiContext`]: health, metrics and proof of work
+fn context_router(public_access: Arc<PublicAccess>) -> Router<ApiContext> {
+    let pow_routes = Router::new()
+        .nest("/api/v1/pow", pow_routes())
+        .route("/api/v1/auth/logout", axum::routing::post(logout))
+        .layer(middleware::from_fn_with_state(
+            public_access,
+            crypto_validation_middleware,
+        ));
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(metrics_endpoint))
+        .merge(pow_routes)
+}
+
+/// Routes backed by the database, i.e. everything but PoW, health and docs
+fn stores_router<S: Clone + Send + Sync + 'static>(
+    state: AppState,
+    public_access: Arc<PublicAccess>,
+    admin_signatures_required: bool,
+) -> Router<S> {
+    // Shipped behind the "questions" feature flag
+    let questions_router = Router::new()
+        .route(
+            "/api/v1/products/:id/questions",
+            post(api::questions::ask_question).get(api::questions::list_questions),
+        )
+        .route(
+            "/api/v1/questions/:id/answer",
+            post(api::questions::answer_question),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            (state.features.clone(), "questions"),
+            features::feature_middleware,
+        ));
+
+    // Admin routes, with optional HMAC request signing on top of the role check
+    let admin_router = Router::new()
+        .route("/api/v1/admin/summary", get(api::admin::admin_summary))
+        .route("/api/v1/admin/pow/difficulty", get(get_pow_difficulty))
+        .route(
+            "/api/v1/admin/maintenance",
+            post(api::admin::set_maintenance),
+        )
+        .route("/api/v1/admin/retention", get(api::admin::retention_stats))
+        .route(
+            "/api/v1/admin/events/export",
+            get(api::admin::export_events),
+        )
+        .route("/api/v1/admin/features", get(api::admin::list_features))
+        .route("/api/v1/admin/features/:name", put(api::admin::set_feature))
+        .route(
+            "/api/v1/admin/stores/:id/media-quota",
+            put(api::admin::set_media_quota),
+        )
+        .route(
+            "/api/v1/admin/promotions",
+            post(api::promotions::create_promotion).get(api::promotions::list_promotions),
+        )
+        .route(
+            "/api/v1/admin/promotions/:id",
+            put(api::promotions::update_promotion).delete(api::promotions::delete_promotion),
+        )
+        .route(
+            "/api/v1/admin/commission-rates",
+            post(api::commissions::create_commission_rate)
+                .get(api::commissions::list_commission_rates),
+        )
+        .route(
+            "/api/v1/admin/commission-rates/effective",
+            get(api::commissions::effective_commission_rate),
+        )
+        .route(
+            "/api/v1/admin/commission-rates/:id",
+            put(api::commissions::update_commission_rate)
+                .delete(api::commissions::delete_commission_rate),
+        )
+        .route(
+            "/api/v1/admin/categories/:id/attributes",
+            post(api::categories::define_category_attribute),
+        )
+        .route(
+            "/api/v1/admin/categories/:id/attributes/:key",
+            delete(api::categories::remove_category_attribute),
+        )
+        .route(
+            "/api/v1/admin/prohibited-terms",
+            post(api::moderation::create_prohibited_term)
+                .get(api::moderation::list_prohibited_terms),
+        )
+        .route(
+            "/api/v1/admin/prohibited-terms/:id",
+            delete(api::moderation::delete_prohibited_term),
+        )
+        .route(
+            "/api/v1/admin/profanity-terms",
+            post(api::moderation::create_profanity_term).get(api::moderation::list_profanity_terms),
+        )
+        .route(
+            "/api/v1/admin/profanity-terms/:id",
+            delete(api::moderation::delete_profanity_term),
+        )
+        .route(
+            "/api/v1/admin/moderation",
+            get(api::moderation::list_moderation_queue),
+        )
+        .route(
+            "/api/v1/admin/moderation/:product_id",
+            post(api::moderation::review_product),
+        )
+        .route(
+            "/api/v1/admin/moderation/image-flags/:id",
+            post(api::moderation::review_image_flag),
+        )
+        .route(
+            "/api/v1/admin/moderation/review-anomalies/:id",
+            post(api::moderation::review_review_anomaly),
+        )
+        .route(
+            "/api/v1/admin/store-ratings/recompute",
+            post(api::moderation::recompute_store_ratings),
+        )
+        .route(
+            "/api/v1/admin/experiments/:name/summary",
+            get(api::admin::get_experiment_summary),
+        )
+        .route("/api/v1/admin/migrations", get(api::admin::list_migrations))
+        .route(
+            "/api/v1/admin/migrations/run",
+            post(api::admin::run_migrations),
+        )
+        .route(
+            "/api/v1/admin/product-counts/rebuild",
+            post(api::admin::rebuild_product_counts),
+        )
+        .route(
+            "/api/v1/admin/media/migrate",
+            post(api::media_migration::start_media_migration),
+        )
+        .route(
+            "/api/v1/admin/media/migrations/:id",
+            get(api::media_migration::get_media_migration),
+        )
+        .route(
+            "/api/v1/admin/stores/:id/payout-account",
+            get(api::payout_accounts::admin_get_payout_account),
+        )
+        .route(
+            "/api/v1/admin/stores/:id/payout-account/verify",
+            post(api::payout_accounts::verify_payout_account),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            auth::signing::AdminSigning {
+                db: state.db.clone(),
+                required: admin_signatures_required,
+                cipher: state.field_cipher.clone(),
+                nonces: Arc::new(auth::signing::NonceCache::default()),
+            },
+            auth::signing::admin_signature_middleware,
+        ));
 
-    (StatusCode::BAD_REQUEST, "No file uploaded").into_response()
+    let stores_router = Router::new()
+        .route(
+            "/api/v1/stores",
+            post(create_store_endpoint).get(list_stores_endpoint),
+        )
+        .route(
+            "/api/v1/stores/:id",
+            delete(delete_store_endpoint).put(update_store_endpoint),
+        )
+        .route(
+            "/api/v1/products",
+            post(create_product_endpoint).get(list_products_endpoint),
+        )
+        .route(
+            "/api/v1/products/validate",
+            post(api::products::validate_product_form),
+        )
+        .route(
+            "/api/v1/products/search",
+            get(api::products::search_products),
+        )
+        .route(
+            "/api/v1/stores/validate",
+            post(api::stores::validate_store_form),
+        )
+        .route(
+            "/api/v1/stores/:id/bundles",
+            post(api::bundles::create_bundle).get(api::bundles::list_bundles),
+        )
+        .route(
+            "/api/v1/stores/:id/bundles/:bundle_id",
+            get(api::bundles::get_bundle)
+                .put(api::bundles::update_bundle)
+                .delete(api::bundles::delete_bundle),
+        )
+        .route(
+            "/api/v1/stores/:id/bundles/:bundle_id/sales",
+            post(api::bundles::record_bundle_sale),
+        )
+        .route("/api/v1/stores/:id/pause", post(api::stores::pause_store))
+        .route("/api/v1/stores/:id/resume", post(api::stores::resume_store))
+        .route(
+            "/api/v1/stores/:id/delivery-options",
+            put(api::stores::set_delivery_options),
+        )
+        .route("/api/v1/stores/:id/embed", put(api::stores::set_embed))
+        .route(
+            "/api/v1/stores/:id/auto-response",
+            get(api::stores::get_auto_response).put(api::stores::set_auto_response),
+        )
+        .route(
+            "/api/v1/stores/:id/inventory-sync",
+            post(api::inventory_sync::inventory_sync),
+        )
+        .route(
+            "/api/v1/stores/:id/products/bulk-price",
+            post(api::bulk_prices::bulk_update_prices),
+        )
+        .route(
+            "/api/v1/stores/:id/products/bulk-delete",
+            post(api::bulk_delete::bulk_delete_products),
+        )
+        .route(
+            "/api/v1/products/bulk",
+            patch(api::bulk_prices::bulk_update_products),
+        )
+        .route(
+            "/api/v1/stores/:id/whatsapp-catalog",
+            get(api::whatsapp_catalog::whatsapp_catalog),
+        )
+        .route(
+            "/api/v1/stores/:id/reports",
+            post(api::reports::create_report),
+        )
+        .route(
+            "/api/v1/stores/:id/reports/:report_id",
+            get(api::reports::get_report),
+        )
+        .route(
+            "/api/v1/stores/:id/api-keys",
+            post(api::store_api_keys::create_api_key).get(api::store_api_keys::list_api_keys),
+        )
+        .route(
+            "/api/v1/stores/:id/api-keys/:key_id",
+            delete(api::store_api_keys::revoke_api_key),
+        )
+        .route(
+            "/api/v1/stores/:id/referral-links",
+            post(api::referrals::create_referral_link).get(api::referrals::list_referral_links),
+        )
+        .route(
+            "/api/v1/referrals/:code",
+            get(api::referrals::resolve_referral_link),
+        )
+        .route(
+            "/api/v1/stores/:id/snapshots",
+            post(api::snapshots::create_store_snapshot).get(api::snapshots::list_store_snapshots),
+        )
+        .route(
+            "/api/v1/stores/:id/snapshots/:snapshot_id/restore",
+            post(api::snapshots::restore_store_snapshot),
+        )
+        .route(
+            "/api/v1/stores/:id/payout-account",
+            put(api::payout_accounts::set_payout_account)
+                .get(api::payout_accounts::get_payout_account),
+        )
+        .route(
+            "/api/v1/stores/:id/reviews",
+            post(api::store_reviews::create_store_review)
+                .get(api::store_reviews::list_store_reviews),
+        )
+        .route(
+            "/api/v1/store-reviews/:id/reply",
+            post(api::store_reviews::reply_store_review),
+        )
+        .merge(questions_router)
+        .route("/sitemap.xml", get(api::seo::sitemap))
+        .route("/sitemaps/:file", get(api::seo::sitemap_page))
+        .route("/feeds/products.xml", get(api::seo::product_feed))
+        .route(
+            "/api/v1/products/:id/media",
+            post(upload_product_media_endpoint)
+                .layer(axum::extract::DefaultBodyLimit::max(
+                    state.upload_limits.max_body_bytes(),
+                ))
+                .get(api::products::list_product_media),
+        )
+        .route(
+            "/api/v1/products/:id/media/:image_id",
+            delete(api::products::delete_product_media_item),
+        )
+        .route(
+            "/api/v1/products/import-from-url",
+            post(api::imports::import_product_from_url),
+        )
+        .route(
+            "/api/v1/products/:id",
+            delete(api::products::delete_product).patch(api::products::patch_product),
+        )
+        .route(
+            "/api/v1/products/:id/publish",
+            post(api::products::publish_product),
+        )
+        .route(
+            "/api/v1/products/:id/restore",
+            post(api::products::restore_product),
+        )
+        .route(
+            "/api/v1/products/:id/duplicate",
+            post(api::products::duplicate_product),
+        )
+        .route(
+            "/api/v1/products/:id/watch",
+            post(api::watches::watch_product).delete(api::watches::unwatch_product),
+        )
+        .route(
+            "/api/v1/users/me/watches",
+            get(api::watches::list_my_watches),
+        )
+        .route(
+            "/api/v1/users/me/notification-preferences",
+            get(api::notification_preferences::get_notification_preferences)
+                .put(api::notification_preferences::update_notification_preferences),
+        )
+        .route("/api/v1/stores/:id/stats", get(api::stores::store_stats))
+        .route(
+            "/api/v1/stores/:id/onboarding",
+            get(api::onboarding::store_onboarding),
+        )
+        .route(
+            "/api/v1/stores/:id/share",
+            get(api::stores::get_store_share_links),
+        )
+        .route(
+            "/api/v1/stores/:id/history",
+            get(api::history::store_history),
+        )
+        .route(
+            "/api/v1/products/:id/history",
+            get(api::history::product_history),
+        )
+        .route(
+            "/api/v1/products/:id/stats",
+            get(api::product_views::product_stats),
+        )
+        .merge(admin_router)
+        .route("/api/v1/categories", get(api::categories::list_categories))
+        .route(
+            "/api/v1/categories/:id/attributes",
+            get(api::categories::list_category_attributes),
+        )
+        .route(
+            "/api/v1/featured-stores",
+            get(api::promotions::list_featured_stores),
+        )
+        .route("/api/v1/sync", get(api::sync::sync_changes))
+        .route("/api/v1/public-stats", get(api::public_stats::public_stats))
+        .route(
+            "/api/v1/return-policy-templates",
+            get(api::return_policies::list_return_policy_templates),
+        )
+        .route("/api/v1/media/*path", get(serve_media_endpoint));
+    #[cfg(feature = "graphql")]
+    let stores_router =
+        stores_router.route("/api/v1/graphql", post(api::graphql::graphql_endpoint));
+    stores_router
+        .layer(middleware::from_fn_with_state(
+            state.db.clone(),
+            api::transaction::transaction_middleware,
+        ))
+        // Views don't touch the database, so they skip the transaction
+        .merge(api::product_views::router())
+        .layer(middleware::from_fn_with_state(
+            public_access,
+            crypto_validation_middleware,
+        ))
+        // After the token check, so it doesn't apply: the widget is public
+        // and limited per embedding site instead
+        .merge(api::embed::router())
+        .with_state(state)
 }
 
 #[tokio::main]
@@ -694,25 +1918,52 @@ async fn main() -> anyhow::Result<()> {
 
     // Load configuration
     let config = Config::from_env()?;
+    if config.environment == Environment::Production {
+        config.validate_for_production()?;
+    }
+    // Fail before touching the database when the certificate is unusable
+    #[cfg(feature = "tls")]
+    let rustls_config = match &config.tls {
+        Some(settings) => Some(tls::load(settings).await?),
+        None => None,
+    };
+    #[cfg(not(feature = "tls"))]
+    if config.tls.is_some() {
+        anyhow::bail!("TLS_CERT_PATH is set but this binary was built without the tls feature");
+    }
+    info!(
+        endpoint = %config.media.endpoint_url,
+        region = %config.media.region,
+        bucket = %config.media.bucket,
+        credentials = config.media.access_key_id.is_some(),
+        webp_quality = config.media.webp_quality,
+        "Object storage settings"
+    );
 
     // Initialize database pool
     // let pool = create_pool(&config).await?;
 
+    // One service for the request gate and the PoW routes, built from config
+    let jwt_service = Arc::new(JwtService::with_secret(&config.auth.jwt_secret));
+    let tokens = Arc::new(auth::token_cache::TokenGate::new(jwt_service.clone()));
+    auth::token_cache::install(tokens.clone());
+    let shutdown = shutdown::ShutdownCoordinator::new();
+    shutdown.listen_for_signals();
+    // Shared with the admin difficulty report, which lives on AppState
+    let pow_service = Arc::new(PowService::new(
+        config.pow.difficulty,
+        config.pow.timeout_minutes,
+    ));
     let api_context = ApiContext {
         // pool: pool.clone(),
-        pow_service: Arc::new(PowService::new(
-            config.pow_difficulty,
-            config.pow_timeout_minutes,
-        )),
-        jwt_service: Arc::new(JwtService::new().unwrap_or_default()),
+        pow_service: pow_service.clone(),
+        jwt_service,
+        tokens: tokens.clone(),
+        shutdown: shutdown.clone(),
     };
 
-    let api_routes = Router::new()
-        .nest("/api/v1/pow", pow_routes())
-        .layer(middleware::from_fn(crypto_validation_middleware));
-
     let pool = create_connection(&config).await?;
-    if config.run_migrations_on_start {
+    if config.database.run_migrations_on_start {
         use sea_orm_migration::MigratorTrait;
         info!("Running database migrations at startup");
         if let Err(e) = migrator::Migrator::up(&pool, None).await {
@@ -724,32 +1975,201 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("RUN_MIGRATIONS_ON_START is false; skipping migrations");
     }
 
-    // Create a separate router for stores and products with database state
-    let stores_router = Router::new()
-        .route(
-            "/api/v1/stores",
-            post(create_store_endpoint).get(list_stores_endpoint),
-        )
-        .route(
-            "/api/v1/stores/:id",
-            delete(delete_store_endpoint).put(update_store_endpoint),
-        )
-        .route(
-            "/api/v1/products",
-            post(create_product_endpoint).get(list_products_endpoint),
-        )
-        .route(
-            "/api/v1/products/:id/media",
-            post(upload_product_media_endpoint),
-        )
-        .route("/api/v1/media/*path", get(serve_media_endpoint))
-        .with_state(pool);
+    let public_access = Arc::new(
+        PublicAccess::from_config(&config.public_access, tokens).with_api_keys(pool.clone()),
+    );
+    let api_routes = context_router(public_access.clone());
+
+    // Cyclic so handlers can raise follow-up events, e.g. price-drop alerts
+    let event_dispatcher = Arc::new_cyclic(|events| {
+        let mut event_dispatcher = events::EventDispatcher::new();
+        event_dispatcher.add_handler(Box::new(events::LoggingEventHandler));
+        event_dispatcher.add_handler(Box::new(events::WebSocketEventHandler));
+        event_dispatcher.add_handler(Box::new(events::EventLogRecorder::new(pool.clone())));
+        event_dispatcher.add_handler(Box::new(moderation::TermFilterRefresher {
+            filter: &moderation::PROHIBITED_TERMS,
+        }));
+        event_dispatcher.add_handler(Box::new(moderation::text::TextModerationRefresher {
+            moderation: &moderation::text::TEXT_MODERATION,
+        }));
+        event_dispatcher.add_handler(Box::new(price_alerts::PriceAlertNotifier {
+            db: pool.clone(),
+            events: events.clone(),
+        }));
+        event_dispatcher.add_handler(Box::new(notifications::NotificationRouter {
+            db: pool.clone(),
+            events: events.clone(),
+        }));
+        event_dispatcher
+    });
+    events::spawn_drain(
+        event_dispatcher.clone(),
+        shutdown.register("event dispatcher"),
+    );
+
+    match api::moderation::load_prohibited_terms(&pool).await {
+        Ok(count) => info!(count, "Prohibited terms loaded"),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to load prohibited terms");
+            return Err(anyhow::anyhow!(e));
+        }
+    }
+    moderation::text::TEXT_MODERATION.configure(config.text_moderation.clone());
+    match api::moderation::load_profanity_terms(&pool).await {
+        Ok(count) => info!(count, "Profanity terms loaded"),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to load profanity terms");
+            return Err(anyhow::anyhow!(e));
+        }
+    }
+
+    // Cheap: the bucket itself is checked on the first upload, so a cold or
+    // missing MinIO never holds up boot
+    if let Err(e) = api::media_storage::shared_s3_storage().await {
+        tracing::warn!(error = %e, "Media storage not configured; uploads are disabled");
+    }
+
+    let feature_flags = Arc::new(features::FeatureFlags::new(
+        config.features.enabled.clone(),
+        config.features.runtime_overrides,
+    ));
+    if config.features.runtime_overrides {
+        if let Err(e) = jobs::refresh_feature_flags(&pool, &feature_flags).await {
+            tracing::error!(error = %e, "Failed to load feature overrides");
+        }
+        jobs::spawn_feature_refresh(
+            pool.clone(),
+            feature_flags.clone(),
+            &shutdown,
+            std::time::Duration::from_secs(config.features.refresh_interval_secs),
+        );
+    }
+
+    if let Err(e) = jobs::refresh_maintenance(&pool, &maintenance::MAINTENANCE).await {
+        tracing::error!(error = %e, "Failed to load maintenance state");
+    }
+    jobs::spawn_maintenance_poll(
+        pool.clone(),
+        &maintenance::MAINTENANCE,
+        &shutdown,
+        std::time::Duration::from_secs(config.maintenance_poll_interval_secs),
+    );
+    jobs::spawn_publish_scheduler(
+        pool.clone(),
+        event_dispatcher.clone(),
+        config.media_limits.min_images_to_publish,
+        &shutdown,
+        std::time::Duration::from_secs(config.publish_scheduler_interval_secs),
+    );
+    jobs::spawn_sale_cleanup(
+        pool.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.sale_cleanup_interval_secs),
+    );
+    jobs::spawn_promotion_expiry(
+        pool.clone(),
+        event_dispatcher.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.promotion_expiry_interval_secs),
+    );
+    jobs::spawn_store_resume(
+        pool.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.store_resume_interval_secs),
+    );
+    jobs::spawn_question_reminders(
+        pool.clone(),
+        event_dispatcher.clone(),
+        config.question_reminder_after_days,
+        &shutdown,
+        std::time::Duration::from_secs(config.question_reminder_interval_secs),
+    );
+    jobs::spawn_auto_replies(
+        pool.clone(),
+        event_dispatcher.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.auto_reply_interval_secs),
+    );
+    jobs::spawn_notification_digests(
+        pool.clone(),
+        event_dispatcher.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.notification_digest_interval_secs),
+    );
+    jobs::spawn_retention(
+        pool.clone(),
+        retention::policies(&config.retention),
+        config.retention.batch_size,
+        std::time::Duration::from_millis(config.retention.batch_pause_ms),
+        &shutdown,
+        std::time::Duration::from_secs(config.retention.interval_secs),
+    );
+    jobs::spawn_report_runner(
+        pool.clone(),
+        config.reports,
+        &shutdown,
+        std::time::Duration::from_secs(config.report_runner_interval_secs),
+    );
+    jobs::spawn_trust_scoring(
+        pool.clone(),
+        event_dispatcher.clone(),
+        config.trust_weights,
+        config.trust_alert_threshold,
+        &shutdown,
+        std::time::Duration::from_secs(config.trust_score_interval_secs),
+    );
+    jobs::spawn_review_anomaly_scan(
+        pool.clone(),
+        &shutdown,
+        std::time::Duration::from_secs(config.review_anomaly_interval_secs),
+    );
+    jobs::spawn_public_stats(
+        pool.clone(),
+        config.public_stats,
+        &shutdown,
+        std::time::Duration::from_secs(config.public_stats_interval_secs),
+    );
+    let (view_recorder, view_queue) = product_views::ViewRecorder::new();
+    jobs::spawn_view_writer(pool.clone(), view_queue, config.product_views, &shutdown);
+
+    let state = AppState {
+        db: pool,
+        events: event_dispatcher.clone(),
+        features: feature_flags,
+        site: Arc::new(config.site.clone()),
+        media_limits: Arc::new(config.media_limits),
+        upload_limits: Arc::new(config.upload_limits),
+        report_limits: Arc::new(config.reports),
+        field_cipher: Arc::new(crypto::field::FieldCipher::new(
+            config.field_encryption_keys,
+        )),
+        database: Arc::new(config.database.clone()),
+        experiments: Arc::new(experiments::Experiments {
+            feed: config.feed_experiment.clone(),
+        }),
+        confirmations: Arc::new(auth::confirmation::ConfirmationTokens::new(
+            &config.auth.jwt_secret,
+        )),
+        embed_limiter: Arc::new(api::embed::EmbedLimiter::new(
+            config.public_access.embed_requests_per_minute,
+        )),
+        referrals: Arc::new(config.referrals.clone()),
+        views: Arc::new(view_recorder),
+        pow_service,
+        webp: Arc::new(api::image_conversion::WebpConverter::new(
+            config.media.webp_quality,
+        )),
+    };
+    let stores_router = stores_router(state, public_access, config.auth.admin_signatures_required);
 
     let app = Router::new()
-        .route("/healthz", get(healthz))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .merge(api_routes)
         .merge(stores_router)
+        .layer(middleware::from_fn_with_state(
+            &maintenance::MAINTENANCE,
+            maintenance::maintenance_middleware,
+        ))
         .layer(middleware::from_fn(
             request_middleware::request_logging_middleware,
         ))
@@ -792,51 +2212,412 @@ async fn main() -> anyhow::Result<()> {
         .layer(CorsLayer::permissive())
         .with_state(api_context);
 
+    let grace = std::time::Duration::from_secs(config.shutdown_grace_secs);
+    #[cfg(feature = "tls")]
+    if let (Some(rustls_config), Some(settings)) = (rustls_config, config.tls.clone()) {
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 3001));
+        tls::spawn_reloader(rustls_config.clone(), settings, tls::WATCH_INTERVAL);
+        info!("Server listening on https://0.0.0.0:3001");
+        info!("Swagger UI available at https://0.0.0.0:3001/swagger-ui");
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let (handle, shutdown) = (handle.clone(), shutdown.clone());
+            async move {
+                shutdown.triggered().await;
+                handle.graceful_shutdown(Some(grace));
+            }
+        });
+        tls::serve(app, addr, rustls_config, handle).await?;
+        finish_shutdown(&shutdown, &event_dispatcher, grace).await;
+        return Ok(());
+    }
+
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await?;
     info!("Server listening on http://0.0.0.0:3001");
     info!("Swagger UI available at http://0.0.0.0:3001/swagger-ui");
-    axum::serve(listener, app).await?;
+    // The peer address stands in for clients that come without proxy headers
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown = shutdown.clone();
+        async move { shutdown.triggered().await }
+    })
+    .await?;
+    finish_shutdown(&shutdown, &event_dispatcher, grace).await;
 
     Ok(())
 }
 
+/// Once the server has stopped: give jobs and event delivery `grace` to
+/// wrap up, and name every event that did not make it
+async fn finish_shutdown(
+    shutdown: &shutdown::ShutdownCoordinator,
+    events: &events::EventDispatcher,
+    grace: std::time::Duration,
+) {
+    shutdown.trigger();
+    let outcomes = shutdown.drain(grace).await;
+    for event in events.in_flight() {
+        tracing::error!(
+            event_id = %event.id,
+            event_type = ?event.event_type,
+            entity_id = %event.entity_id,
+            "Event not delivered before shutdown"
+        );
+    }
+    let timed_out = outcomes
+        .iter()
+        .filter(|o| o.status == shutdown::HookStatus::TimedOut)
+        .count();
+    info!(hooks = outcomes.len(), timed_out, "Shutdown complete");
+}
+
 #[derive(OpenApi)]
 #[openapi(
     paths(
         healthz,
+        api::public_stats::public_stats,
         get_pow_challenge,
         verify_pow_solution,
+        logout,
+        get_pow_difficulty,
         api::products::create_product,
         api::products::get_product,
         api::products::list_products,
+        api::products::search_products,
         api::products::update_product,
+        api::products::patch_product,
         api::products::delete_product,
+        api::products::restore_product,
+        api::products::duplicate_product,
+        api::products::list_product_media,
         api::products::upload_product_media,
         api::products::edit_product_media,
         api::products::delete_product_media,
+        api::products::delete_product_media_item,
+        api::products::publish_product,
+        api::imports::import_product_from_url,
+        api::watches::watch_product,
+        api::watches::unwatch_product,
+        api::watches::list_my_watches,
+        api::notification_preferences::get_notification_preferences,
+        api::notification_preferences::update_notification_preferences,
+        api::return_policies::list_return_policy_templates,
+        api::questions::ask_question,
+        api::questions::answer_question,
+        api::questions::list_questions,
+        api::admin::admin_summary,
+        api::admin::set_maintenance,
+        api::admin::retention_stats,
+        api::admin::export_events,
+        api::admin::list_features,
+        api::seo::sitemap,
+        api::seo::sitemap_page,
+        api::seo::product_feed,
+        api::admin::set_feature,
+        api::admin::set_media_quota,
+        api::admin::rebuild_product_counts,
+        api::admin::get_experiment_summary,
+        api::admin::list_migrations,
+        api::admin::run_migrations,
+        api::media_migration::start_media_migration,
+        api::media_migration::get_media_migration,
+        api::payout_accounts::admin_get_payout_account,
+        api::payout_accounts::verify_payout_account,
+        api::categories::list_categories,
+        api::categories::list_category_attributes,
+        api::categories::define_category_attribute,
+        api::categories::remove_category_attribute,
+        api::products::validate_product_form,
+        api::stores::validate_store_form,
+        api::stores::pause_store,
+        api::stores::resume_store,
+        api::stores::set_delivery_options,
+        api::stores::set_embed,
+        api::stores::get_auto_response,
+        api::stores::set_auto_response,
+        api::embed::embed_store,
+        api::stores::store_stats,
+        api::stores::get_store_share_links,
+        api::onboarding::store_onboarding,
+        api::history::store_history,
+        api::history::product_history,
+        api::product_views::record_product_view,
+        api::product_views::product_stats,
+        api::inventory_sync::inventory_sync,
+        api::bulk_prices::bulk_update_prices,
+        api::bulk_prices::bulk_update_products,
+        api::bulk_delete::bulk_delete_products,
+        api::whatsapp_catalog::whatsapp_catalog,
+        api::reports::create_report,
+        api::reports::get_report,
+        api::store_reviews::create_store_review,
+        api::store_reviews::list_store_reviews,
+        api::store_reviews::reply_store_review,
+        api::bundles::create_bundle,
+        api::bundles::list_bundles,
+        api::bundles::get_bundle,
+        api::bundles::update_bundle,
+        api::bundles::delete_bundle,
+        api::bundles::record_bundle_sale,
+        api::sync::sync_changes,
+        api::store_api_keys::create_api_key,
+        api::store_api_keys::list_api_keys,
+        api::store_api_keys::revoke_api_key,
+        api::referrals::create_referral_link,
+        api::referrals::list_referral_links,
+        api::referrals::resolve_referral_link,
+        api::snapshots::create_store_snapshot,
+        api::snapshots::list_store_snapshots,
+        api::snapshots::restore_store_snapshot,
+        api::payout_accounts::set_payout_account,
+        api::payout_accounts::get_payout_account,
+        api::promotions::create_promotion,
+        api::promotions::list_promotions,
+        api::promotions::update_promotion,
+        api::promotions::delete_promotion,
+        api::promotions::list_featured_stores,
+        api::commissions::create_commission_rate,
+        api::commissions::list_commission_rates,
+        api::commissions::effective_commission_rate,
+        api::commissions::update_commission_rate,
+        api::commissions::delete_commission_rate,
+        api::moderation::create_prohibited_term,
+        api::moderation::list_prohibited_terms,
+        api::moderation::delete_prohibited_term,
+        api::moderation::create_profanity_term,
+        api::moderation::list_profanity_terms,
+        api::moderation::delete_profanity_term,
+        api::moderation::list_moderation_queue,
+        api::moderation::review_product,
+        api::moderation::review_image_flag,
+        api::moderation::review_review_anomaly,
+        api::moderation::recompute_store_ratings,
     ),
     components(
         schemas(
             HealthResponse,
-            UuidSchema,
+            api::public_stats::PublicStatsResponse,
+            api::product_views::ProductStatsResponse,
+            db::product_views::ViewCounts,
+            health::DependencyHealth,
+            api::media_storage::BreakerState,
+            api::media_storage::BucketStatus,
+            api::media_storage::StorageUnavailableResponse,
+            api::multipart::UploadRejection,
+            api::extract::InvalidIdResponse,
             crypto::types::PowChallenge,
             crypto::types::PowSolution,
             crypto::types::PowCertificateRequest,
             crypto::types::PowChallengeResponse,
+            crypto::pow::PowDifficultyReport,
             crypto::types::TokenResponse,
             crypto::types::VerificationRequest,
             api::products::CreateProductRequest,
             api::products::UpdateProductRequest,
+            api::products::UpdateProductPatch,
             api::products::ListProductsQuery,
             api::products::MediaUploadResponse,
+            api::products::ProductMediaResponse,
+            api::products::ProductResponse,
+            api::products::SellerProductResponse,
+            db::products::PublicationStatus,
+            db::products::ProductSort,
+            api::products::ValidateProductRequest,
+            api::stores::CreateStoreRequest,
+            api::stores::PauseStoreRequest,
+            api::stores::EmbedSettingsRequest,
+            api::stores::AutoResponseRequest,
+            db::stores::AutoResponse,
+            db::stores::AutoResponseTrigger,
+            api::stores::EmbedSettingsResponse,
+            api::embed::EmbedStoreResponse,
+            api::embed::EmbedStore,
+            api::embed::EmbedProduct,
+            money::Money,
+            db::delivery::DeliveryOptionsInput,
+            db::delivery::DeliveryOptions,
+            db::delivery::DeliveryMethod,
+            api::products::DeliveryMethodUnavailable,
+            api::checkout::OrderLine,
+            api::checkout::LineReason,
+            api::checkout::LineError,
+            api::checkout::OrderValidationError,
+            api::stores::StoreResponse,
+            api::stores::StorePausedResponse,
+            api::stores::StoreStatsResponse,
+            auth::confirmation::ConfirmationResponse,
+            auth::confirmation::DeletionSummary,
+            api::stores::StoreShareResponse,
+            api::onboarding::OnboardingResponse,
+            db::onboarding::StepStatus,
+            db::onboarding::OnboardingStep,
+            api::pagination::ProductsPage,
+            api::pagination::StoresPage,
+            api::pagination::QuestionsPage,
+            api::pagination::StoreReviewsPage,
+            api::pagination::HistoryPage,
+            db::history::HistoryEntry,
+            db::diff::FieldChange,
+            api::products::MediaQuotaExceeded,
+            api::products::InsufficientMedia,
+            api::products::CreateProductRejection,
+            api::admin::SetMediaQuotaRequest,
+            db::media_quota::MediaUsage,
+            api::inventory_sync::InventorySyncReport,
+            api::inventory_sync::RowResult,
+            api::inventory_sync::RowStatus,
+            api::bulk_prices::BulkPriceRequest,
+            api::bulk_prices::PriceAssignment,
+            api::bulk_prices::BulkPriceSummary,
+            api::bulk_prices::BulkProductEntry,
+            api::bulk_prices::BulkProductUpdateRequest,
+            api::bulk_prices::BulkProductUpdateResponse,
+            api::bulk_delete::BulkDeleteRequest,
+            db::bulk_prices::PriceChange,
+            db::bulk_prices::PriceChangeStatus,
+            api::whatsapp_catalog::CatalogFormat,
+            entity::product_bundle::Model,
+            api::reports::CreateReportRequest,
+            api::reports::ReportResponse,
+            api::reports::ReportLimitResponse,
+            reports::ReportType,
+            reports::ReportFormat,
+            reports::ReportStatus,
+            api::bundles::BundleItemRequest,
+            api::bundles::CreateBundleRequest,
+            api::bundles::UpdateBundleRequest,
+            api::bundles::RecordBundleSaleRequest,
+            api::bundles::BundleComponentResponse,
+            api::bundles::BundleResponse,
+            api::bundles::BundlesListResponse,
+            api::validation::FieldError,
+            api::validation::ValidationReport,
+            entity::category::Model,
+            entity::tombstone::Model,
+            api::sync::SyncResponse,
+            auth::ApiScope,
+            api::store_api_keys::CreateApiKeyRequest,
+            api::store_api_keys::ApiKeyResponse,
+            api::store_api_keys::CreatedApiKeyResponse,
+            api::store_api_keys::ApiKeyListResponse,
+            api::referrals::CreateReferralLinkRequest,
+            api::referrals::ReferralLinkResponse,
+            api::referrals::ReferralLinkListResponse,
+            api::referrals::ReferralTargetResponse,
+            db::referrals::ReferralTotals,
+            api::snapshots::StoreSnapshotsResponse,
+            db::snapshots::RestoreReport,
+            db::snapshots::RestoredProduct,
+            api::watches::WatchProductRequest,
+            api::watches::WatchResponse,
+            api::watches::WatchedProduct,
+            api::watches::WatchListResponse,
+            notifications::NotificationKind,
+            notifications::DeliveryMode,
+            api::notification_preferences::NotificationPreference,
+            api::notification_preferences::UpdateNotificationPreferencesRequest,
+            api::notification_preferences::NotificationPreferencesResponse,
+            api::payout_accounts::SetPayoutAccountRequest,
+            api::payout_accounts::VerifyPayoutAccountRequest,
+            api::payout_accounts::PayoutAccountResponse,
+            db::payout_accounts::PayoutProvider,
+            entity::store_promotion::Model,
+            api::promotions::CreatePromotionRequest,
+            api::promotions::UpdatePromotionRequest,
+            api::promotions::PromotionResponse,
+            api::promotions::PromotionsListResponse,
+            api::promotions::FeaturedStore,
+            api::promotions::FeaturedStoresResponse,
+            entity::commission_rate::Model,
+            api::commissions::CreateCommissionRateRequest,
+            api::commissions::UpdateCommissionRateRequest,
+            api::commissions::CommissionRateResponse,
+            api::commissions::CommissionRatesListResponse,
+            api::commissions::EffectiveRateResponse,
+            entity::prohibited_term::Model,
+            entity::product_moderation::Model,
+            moderation::TermAction,
+            db::moderation::ModerationStatus,
+            api::moderation::ProhibitedTermRejection,
+            api::moderation::CreateProhibitedTermRequest,
+            api::moderation::ProhibitedTermsResponse,
+            entity::profanity_term::Model,
+            moderation::text::TextField,
+            api::moderation::TextRejectionResponse,
+            api::moderation::ContentRejection,
+            api::moderation::CreateProfanityTermRequest,
+            api::moderation::ProfanityTermsResponse,
+            api::moderation::ModerationQueueResponse,
+            api::moderation::ReviewProductRequest,
+            api::moderation::ImageFlagResponse,
+            api::moderation::ReviewImageFlagRequest,
+            api::moderation::ReviewAnomalyRequest,
+            api::moderation::RecomputeStoreRatingsResponse,
+            db::review_anomalies::AnomalyStatus,
+            entity::review_anomaly::Model,
+            entity::media_similarity_flag::Model,
+            db::media_similarity::FlagStatus,
+            entity::product_question::Model,
+            api::questions::AskQuestionRequest,
+            api::questions::AnswerQuestionRequest,
+            api::questions::QuestionResponse,
+            api::questions::AutomatedReply,
+            entity::store_review::Model,
+            api::store_reviews::CreateStoreReviewRequest,
+            api::store_reviews::ReplyStoreReviewRequest,
+            api::store_reviews::StoreReviewsListResponse,
+            api::store_reviews::StoreReviewResponse,
+            api::user_refs::PublicUserRef,
+            api::imports::ImportFromUrlRequest,
+            api::imports::ImportedImage,
+            api::imports::ImportDraftResponse,
+            db::analytics::AdminSummary,
+            api::admin::SetMaintenanceRequest,
+            auth::signing::SignatureRejection,
+            api::categories::CategoryResponse,
+            api::categories::AttributeDefinitionResponse,
+            api::categories::DefineAttributeRequest,
+            db::category_attributes::AttributeType,
+            db::product_counts::StoreProductCounts,
+            db::product_counts::CategoryProductCount,
+            db::product_counts::RebuildSummary,
+            api::media_migration::StartMediaMigrationRequest,
+            api::media_storage::S3BackendConfig,
+            entity::media_migration::Model,
+            maintenance::MaintenanceState,
+            maintenance::MaintenanceRejection,
+            retention::PruneStats,
+            api::admin::FeaturesResponse,
+            api::admin::SetFeatureRequest,
+            api::admin::ExperimentSummary,
+            db::experiments::VariantExposures,
+            api::admin::MigrationStatusResponse,
+            api::admin::MigrationRunResponse,
+            api::admin::ExportedEvent,
+            db::schema_migrations::MigrationEntry,
+            db::schema_migrations::AppliedMigration,
+            features::FeatureStatus,
+            features::FeatureDisabled,
+            db::retention::PrunableTable,
+            db::analytics::CountWithDelta,
+            api::return_policies::ReturnPolicyTemplate,
+            api::return_policies::ReturnPolicyTemplatesResponse,
             entity::product::Model,
+            entity::store::Model,
         )
     ),
     tags(
         (name = "System", description = "System health and status endpoints"),
         (name = "POW", description = "Proof of Work authentication endpoints"),
         (name = "Products", description = "Product management endpoints"),
-        (name = "Stores", description = "Store management endpoints")
+        (name = "Stores", description = "Store management endpoints"),
+        (name = "Admin", description = "Internal operations endpoints (admin role)"),
+        (name = "Sync", description = "Delta sync for offline-first clients"),
+        (name = "Users", description = "The caller's own settings"),
+        (name = "Seo", description = "Sitemap and product feed for crawlers")
     ),
     servers(
         (url = "http://localhost:3001", description = "Local server")
@@ -845,7 +2626,7 @@ async fn main() -> anyhow::Result<()> {
 struct ApiDoc;
 
 async fn serve_media_endpoint(
-    State(_pool): State<sea_orm::DatabaseConnection>,
+    State(pool): State<sea_orm::DatabaseConnection>,
     Path(path): Path<String>,
 ) -> impl IntoResponse {
     // no extra imports needed here
@@ -861,8 +2642,12 @@ async fn serve_media_endpoint(
         }
     }
 
-    // Try to parse the path as a UUID (image_id)
-    let image_id = match uuid::Uuid::parse_str(&path) {
+    // Try to parse the path as a UUID (image_id), optionally asking for the WebP variant
+    let (id_part, want_webp) = match path.strip_suffix(".webp") {
+        Some(id_part) => (id_part, true),
+        None => (path.as_str(), false),
+    };
+    let image_id = match uuid::Uuid::parse_str(id_part) {
         Ok(id) => id,
         Err(_) => {
             // If it's not a UUID and not base64, treat it as a direct S3 path
@@ -870,6 +2655,22 @@ async fn serve_media_endpoint(
         }
     };
 
+    // Prefer the object keys recorded at upload time
+    use crate::db::product_media::ProductMedia;
+    match ProductMedia::get(&pool, image_id).await {
+        Ok(Some(media)) => {
+            let s3_key = match media.webp_s3_key {
+                Some(webp_key) if want_webp => webp_key,
+                _ => media.s3_key,
+            };
+            return serve_direct_media_path(&s3_key).await;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!(error = %e, image_id = %image_id, "Failed to look up product media");
+        }
+    }
+
     // Try to find an S3 object whose key contains this image_id (UUID) that we embedded at upload time
     match find_s3_key_by_image_id(image_id).await {
         Ok(Some(s3_key)) => {
@@ -889,13 +2690,16 @@ async fn serve_media_endpoint(
 
 // Helper function to serve media directly by S3 path
 async fn serve_direct_media_path(s3_key: &str) -> axum::response::Response {
-    use crate::api::media_storage::S3MediaStorage;
+    use crate::api::media_storage::{
+        connect_s3_storage, storage_unavailable_response, StorageConnectError,
+    };
     use axum::response::Response;
 
     // Initialize S3 storage
-    let storage = match S3MediaStorage::new().await {
+    let storage = match connect_s3_storage().await {
         Ok(s) => s,
-        Err(e) => {
+        Err(StorageConnectError::Unavailable) => return storage_unavailable_response(),
+        Err(StorageConnectError::Failed(e)) => {
             tracing::error!("Failed to initialize S3 storage: {}", e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -906,7 +2710,7 @@ async fn serve_direct_media_path(s3_key: &str) -> axum::response::Response {
     };
 
     // Get the file from MinIO
-    match get_media_from_storage(&storage, s3_key).await {
+    match get_media_from_storage(storage.inner(), s3_key).await {
         Ok((data, content_type)) => {
             Response::builder()
                 .status(StatusCode::OK)
@@ -1003,3 +2807,77 @@ async fn get_media_from_storage(
 
     Ok((data, content_type))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[tokio::test]
+    async fn test_logged_out_tokens_are_refused() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let jwt_service = Arc::new(JwtService::with_secret(
+            "logout-test-secret-of-a-decent-length",
+        ));
+        let tokens = Arc::new(auth::token_cache::TokenGate::new(jwt_service.clone()));
+        let public_access = Arc::new(PublicAccess::from_config(
+            &config::PublicAccessConfig {
+                rules: Vec::new(),
+                anonymous_requests_per_minute: 10,
+                embed_requests_per_minute: 10,
+            },
+            tokens.clone(),
+        ));
+        let app = context_router(public_access).with_state(ApiContext {
+            pow_service: Arc::new(PowService::new(1, 5)),
+            jwt_service: jwt_service.clone(),
+            tokens,
+            shutdown: shutdown::ShutdownCoordinator::new(),
+        });
+        let token = jwt_service
+            .generate_token("device-1".into(), String::new(), "default".into())
+            .unwrap();
+        let logout = || {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/api/v1/auth/logout")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(logout().await, StatusCode::NO_CONTENT);
+        assert_eq!(logout().await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_operations_have_unique_ids_and_declared_tags() {
+        let spec = ApiDoc::openapi();
+        let declared: BTreeSet<String> = spec
+            .tags
+            .iter()
+            .flatten()
+            .map(|tag| tag.name.clone())
+            .collect();
+        let mut ids = BTreeSet::new();
+        for (path, item) in &spec.paths.paths {
+            for operation in item.operations.values() {
+                let id = operation
+                    .operation_id
+                    .clone()
+                    .unwrap_or_else(|| panic!("{path} has no operation_id"));
+                assert!(ids.insert(id.clone()), "duplicate operation_id {id}");
+                let tags = operation.tags.as_deref().unwrap_or_default();
+                assert!(!tags.is_empty(), "{id} has no tag");
+                for tag in tags {
+                    assert!(declared.contains(tag), "{id} uses undeclared tag {tag}");
+                }
+            }
+        }
+    }
+}
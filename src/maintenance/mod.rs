@@ -0,0 +1,251 @@
+//! Maintenance mode: reject writes cleanly during migrations or incidents.
+//!
+//! Admins flip the switch with `POST /admin/maintenance`. The state is saved
+//! in `system_settings` and each replica copies it into [`MAINTENANCE`] on a
+//! short poll, so the whole fleet follows within one interval. While it is
+//! on, [`maintenance_middleware`] answers 503 with `Retry-After` for every
+//! write, and for reads too when `include_reads` is set. Health, metrics and
+//! the toggle itself stay reachable.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use utoipa::ToSchema;
+
+/// Maintenance state of the running server
+pub static MAINTENANCE: MaintenanceSwitch = MaintenanceSwitch::new();
+
+/// `system_settings` key the state is stored under
+pub const SETTING_KEY: &str = "maintenance";
+
+/// Paths answered even while maintenance rejects everything else
+pub const EXEMPT_PATHS: &[&str] = &[
+    "/healthz",
+    "/metrics",
+    "/api/v1/admin/maintenance",
+    "/api/v1/admin/migrations",
+    "/api/v1/admin/migrations/run",
+];
+
+pub const DEFAULT_MESSAGE: &str =
+    "The marketplace is undergoing maintenance. Please try again shortly.";
+pub const DEFAULT_RETRY_AFTER_SECS: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MaintenanceState {
+    pub enabled: bool,
+    /// Reject GET requests as well as writes
+    #[serde(default)]
+    pub include_reads: bool,
+    /// Shown to clients in the 503 body
+    pub message: String,
+    /// Sent as `Retry-After`
+    pub retry_after_secs: u64,
+}
+
+impl Default for MaintenanceState {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            include_reads: false,
+            message: DEFAULT_MESSAGE.to_string(),
+            retry_after_secs: DEFAULT_RETRY_AFTER_SECS,
+        }
+    }
+}
+
+impl MaintenanceState {
+    /// Whether a request must be turned away
+    pub fn rejects(&self, method: &Method, path: &str) -> bool {
+        if !self.enabled || EXEMPT_PATHS.contains(&path) {
+            return false;
+        }
+        let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        !is_read || self.include_reads
+    }
+}
+
+/// Body of the 503 sent while maintenance is on
+#[derive(Serialize, ToSchema)]
+pub struct MaintenanceRejection {
+    pub message: String,
+    pub retry_after_secs: u64,
+}
+
+impl IntoResponse for MaintenanceRejection {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, self.retry_after_secs.to_string())],
+            Json(self),
+        )
+            .into_response()
+    }
+}
+
+/// The current state, swapped whole on every change
+pub struct MaintenanceSwitch {
+    state: RwLock<Option<MaintenanceState>>,
+}
+
+impl MaintenanceSwitch {
+    pub const fn new() -> Self {
+        Self {
+            state: RwLock::new(None),
+        }
+    }
+
+    pub fn get(&self) -> MaintenanceState {
+        self.state
+            .read()
+            .ok()
+            .and_then(|state| state.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the state changed
+    pub fn set(&self, new: MaintenanceState) -> bool {
+        let Ok(mut state) = self.state.write() else {
+            return false;
+        };
+        let changed = state.as_ref() != Some(&new);
+        *state = Some(new);
+        changed
+    }
+}
+
+impl Default for MaintenanceSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Turn requests away with 503 while maintenance is on
+pub async fn maintenance_middleware(
+    State(switch): State<&'static MaintenanceSwitch>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let state = switch.get();
+    if state.rejects(request.method(), request.uri().path()) {
+        return MaintenanceRejection {
+            message: state.message,
+            retry_after_secs: state.retry_after_secs,
+        }
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app(switch: &'static MaintenanceSwitch) -> Router {
+        Router::new()
+            .route(
+                "/api/v1/products",
+                get(|| async { "list" }).post(|| async { "created" }),
+            )
+            .route("/healthz", get(|| async { "ok" }))
+            .route(
+                "/api/v1/admin/maintenance",
+                axum::routing::post(|| async { "toggled" }),
+            )
+            .layer(middleware::from_fn_with_state(
+                switch,
+                maintenance_middleware,
+            ))
+    }
+
+    async fn send(switch: &'static MaintenanceSwitch, method: Method, path: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        app(switch).oneshot(request).await.unwrap()
+    }
+
+    fn on(include_reads: bool) -> MaintenanceState {
+        MaintenanceState {
+            enabled: true,
+            include_reads,
+            message: "Database upgrade in progress".to_string(),
+            retry_after_secs: 120,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_rejected_while_on_and_allowed_after_off() {
+        static SWITCH: MaintenanceSwitch = MaintenanceSwitch::new();
+        assert_eq!(
+            send(&SWITCH, Method::POST, "/api/v1/products")
+                .await
+                .status(),
+            200
+        );
+
+        assert!(SWITCH.set(on(false)));
+        let response = send(&SWITCH, Method::POST, "/api/v1/products").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "120");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Database upgrade in progress");
+
+        assert_eq!(
+            send(&SWITCH, Method::GET, "/api/v1/products")
+                .await
+                .status(),
+            200
+        );
+
+        assert!(SWITCH.set(MaintenanceState::default()));
+        assert_eq!(
+            send(&SWITCH, Method::POST, "/api/v1/products")
+                .await
+                .status(),
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reads_rejected_when_configured_but_exempt_paths_pass() {
+        static SWITCH: MaintenanceSwitch = MaintenanceSwitch::new();
+        SWITCH.set(on(true));
+        assert_eq!(
+            send(&SWITCH, Method::GET, "/api/v1/products")
+                .await
+                .status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(send(&SWITCH, Method::GET, "/healthz").await.status(), 200);
+        assert_eq!(
+            send(&SWITCH, Method::POST, "/api/v1/admin/maintenance")
+                .await
+                .status(),
+            200
+        );
+    }
+
+    #[test]
+    fn test_set_reports_only_real_changes() {
+        let switch = MaintenanceSwitch::new();
+        assert!(!switch.get().enabled);
+        assert!(switch.set(on(false)));
+        assert!(!switch.set(on(false)));
+        assert!(switch.set(on(true)));
+    }
+}
@@ -0,0 +1,598 @@
+//! Copying every media object from one storage backend to another, e.g.
+//! MinIO to R2.
+//!
+//! `POST /admin/media/migrate` records a `media_migrations` row and spawns
+//! [`run_migration`], which walks `product_media` in id order, a batch at a
+//! time. Each object is read from the source, written to the target and read
+//! back, and only counts as copied when the copy hashes the same as the
+//! original. A failed copy is retried, then recorded and skipped; it never
+//! stops the run. After each batch the new keys and the run's progress are
+//! saved together, so an interrupted run resumes after its last saved batch.
+
+use crate::api::media_storage::MediaStorage;
+use crate::db::media_migrations::{BatchProgress, MediaMigration};
+use crate::db::product_media::content_hash;
+use crate::entity::media_migration::Model as MediaMigrationModel;
+use crate::entity::product_media::Model as ProductMediaModel;
+use futures::stream::{self, StreamExt};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Media rows read per batch unless the request says otherwise
+pub const DEFAULT_BATCH_SIZE: u64 = 100;
+pub const MAX_BATCH_SIZE: u64 = 1000;
+/// Media copied at once unless the request says otherwise
+pub const DEFAULT_CONCURRENCY: usize = 4;
+pub const MAX_CONCURRENCY: usize = 16;
+/// Tries per object before it is recorded as failed
+pub const COPY_ATTEMPTS: u32 = 3;
+const COPY_BACKOFF: Duration = Duration::from_millis(200);
+/// Failures kept on the run; the count keeps going past it
+pub const MAX_RECORDED_FAILURES: usize = 100;
+/// Running migrations not updated for this long are assumed lost with their
+/// replica and may be resumed
+pub const STALE_MIGRATION_MINUTES: i64 = 15;
+
+const WEBP_CONTENT_TYPE: &str = "image/webp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStatus {
+    Running,
+    Completed,
+    /// Stopped by an error other than a failed copy; may be resumed
+    Failed,
+}
+
+impl MigrationStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MigrationStatus::Running => "running",
+            MigrationStatus::Completed => "completed",
+            MigrationStatus::Failed => "failed",
+        }
+    }
+}
+
+/// An object that could not be copied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationFailure {
+    pub media_id: Uuid,
+    pub key: String,
+    pub error: String,
+}
+
+/// A media row whose objects now live under different keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovedMedia {
+    pub media_id: Uuid,
+    pub s3_key: String,
+    pub webp_s3_key: Option<String>,
+}
+
+/// How a run copies
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+    pub batch_size: u64,
+    pub concurrency: usize,
+    /// Prepended to every key on the target
+    pub key_prefix: Option<String>,
+    /// Count objects and bytes without copying anything
+    pub dry_run: bool,
+}
+
+/// Key an object gets on the target
+pub fn target_key(prefix: Option<&str>, key: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), key),
+        None => key.to_string(),
+    }
+}
+
+/// Copy one object and read it back. Returns the bytes copied.
+///
+/// `expected_hash` is the hash recorded at upload, checked against what the
+/// source returns; the copy is always checked against the source.
+pub async fn copy_object<S, T>(
+    source: &S,
+    target: &T,
+    from: &str,
+    to: &str,
+    content_type: &str,
+    expected_hash: Option<&str>,
+) -> Result<u64, String>
+where
+    S: MediaStorage + Sync,
+    T: MediaStorage + Sync,
+{
+    let data = source.get_object(from).await?;
+    let hash = content_hash(&data);
+    if expected_hash.is_some_and(|expected| expected != hash) {
+        return Err(format!(
+            "source object {from} does not match its upload hash"
+        ));
+    }
+    target.put_object(to, &data, content_type).await?;
+    let copy = target.get_object(to).await?;
+    if content_hash(&copy) != hash {
+        return Err(format!("copy of {from} failed hash verification"));
+    }
+    Ok(data.len() as u64)
+}
+
+/// [`copy_object`] with up to [`COPY_ATTEMPTS`] tries
+async fn copy_with_retries<S, T>(
+    source: &S,
+    target: &T,
+    from: &str,
+    to: &str,
+    content_type: &str,
+    expected_hash: Option<&str>,
+) -> Result<u64, String>
+where
+    S: MediaStorage + Sync,
+    T: MediaStorage + Sync,
+{
+    let mut delay = COPY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match copy_object(source, target, from, to, content_type, expected_hash).await {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if attempt >= COPY_ATTEMPTS => return Err(e),
+            Err(e) => {
+                warn!(key = %from, attempt, error = %e, "Media copy failed; retrying");
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Copy a media row's original and WebP rendition
+async fn migrate_media<S, T>(
+    source: &S,
+    target: &T,
+    media: &ProductMediaModel,
+    prefix: Option<&str>,
+) -> Result<(MovedMedia, u64, i64), MigrationFailure>
+where
+    S: MediaStorage + Sync,
+    T: MediaStorage + Sync,
+{
+    let failed = |key: &str, error: String| MigrationFailure {
+        media_id: media.id,
+        key: key.to_string(),
+        error,
+    };
+    let s3_key = target_key(prefix, &media.s3_key);
+    let mut bytes = copy_with_retries(
+        source,
+        target,
+        &media.s3_key,
+        &s3_key,
+        &media.content_type,
+        media.content_hash.as_deref(),
+    )
+    .await
+    .map_err(|e| failed(&media.s3_key, e))?;
+    let mut objects = 1;
+    let webp_s3_key = match &media.webp_s3_key {
+        Some(key) => {
+            let to = target_key(prefix, key);
+            bytes += copy_with_retries(source, target, key, &to, WEBP_CONTENT_TYPE, None)
+                .await
+                .map_err(|e| failed(key, e))?;
+            objects += 1;
+            Some(to)
+        }
+        None => None,
+    };
+    Ok((
+        MovedMedia {
+            media_id: media.id,
+            s3_key,
+            webp_s3_key,
+        },
+        bytes,
+        objects,
+    ))
+}
+
+/// Copy one batch, `options.concurrency` media at a time
+async fn migrate_batch<S, T>(
+    source: &S,
+    target: &T,
+    batch: &[ProductMediaModel],
+    options: &MigrationOptions,
+) -> BatchProgress
+where
+    S: MediaStorage + Sync,
+    T: MediaStorage + Sync,
+{
+    let mut progress = BatchProgress {
+        last_media_id: batch.last().map(|media| media.id),
+        media_done: batch.len() as i64,
+        ..Default::default()
+    };
+    if options.dry_run {
+        for media in batch {
+            let webp = media.webp_s3_key.is_some();
+            progress.objects_copied += 1 + i64::from(webp);
+            progress.bytes_copied += media.size_bytes + media.webp_size_bytes.unwrap_or(0);
+        }
+        return progress;
+    }
+
+    let prefix = options.key_prefix.as_deref();
+    let copies: Vec<_> = batch
+        .iter()
+        .map(|media| migrate_media(source, target, media, prefix))
+        .collect();
+    let results: Vec<_> = stream::iter(copies)
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+    for result in results {
+        match result {
+            Ok((moved, bytes, objects)) => {
+                progress.objects_copied += objects;
+                progress.bytes_copied += bytes as i64;
+                if prefix.is_some() {
+                    progress.moved.push(moved);
+                }
+            }
+            Err(failure) => {
+                warn!(
+                    media_id = %failure.media_id,
+                    key = %failure.key,
+                    error = %failure.error,
+                    "Giving up on media object"
+                );
+                progress.failures.push(failure);
+            }
+        }
+    }
+    progress
+}
+
+/// Copy every media row after the run's `last_media_id`, then mark the run
+/// completed. A database error stops the run as failed, to be resumed.
+pub async fn run_migration<S, T>(
+    db: &DatabaseConnection,
+    run: &MediaMigrationModel,
+    source: &S,
+    target: &T,
+    options: &MigrationOptions,
+) -> Result<Option<MediaMigrationModel>, String>
+where
+    S: MediaStorage + Sync,
+    T: MediaStorage + Sync,
+{
+    let mut after = run.last_media_id;
+    let result = loop {
+        let batch = match MediaMigration::next_batch(db, after, options.batch_size.max(1)).await {
+            Ok(batch) if batch.is_empty() => break Ok(()),
+            Ok(batch) => batch,
+            Err(e) => break Err(e),
+        };
+        let progress = migrate_batch(source, target, &batch, options).await;
+        if let Err(e) = MediaMigration::record_batch(db, run.id, &progress).await {
+            break Err(e);
+        }
+        after = progress.last_media_id;
+    };
+    match &result {
+        Ok(()) => info!(migration_id = %run.id, "Media migration finished"),
+        Err(e) => warn!(migration_id = %run.id, error = %e, "Media migration stopped"),
+    }
+    MediaMigration::finish(db, run.id, result.err().as_deref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::media_migrations::NewMediaMigration;
+    use crate::db::testing;
+    use crate::entity::product_media::{self, ActiveModel as ProductMediaActiveModel};
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use sea_orm::{EntityTrait, Set};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Objects in a map; the first `corrupt_puts` writes flip a byte
+    #[derive(Default)]
+    struct MemoryStorage {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+        corrupt_puts: AtomicUsize,
+    }
+
+    impl MemoryStorage {
+        fn with(objects: &[(&str, &[u8])]) -> Self {
+            let storage = Self::default();
+            for (key, data) in objects {
+                storage.insert(key, data);
+            }
+            storage
+        }
+
+        fn insert(&self, key: &str, data: &[u8]) {
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), data.to_vec());
+        }
+
+        fn object(&self, key: &str) -> Option<Vec<u8>> {
+            self.objects.lock().unwrap().get(key).cloned()
+        }
+    }
+
+    #[async_trait]
+    impl MediaStorage for MemoryStorage {
+        async fn upload_media_data(
+            &self,
+            _product_id: Uuid,
+            _file_name: &str,
+            _file_data: &[u8],
+            _content_type: &str,
+            _image_id: Option<Uuid>,
+        ) -> Result<String, String> {
+            unreachable!("migrations copy exact keys")
+        }
+
+        async fn delete_media(&self, media_key: &str) -> Result<(), String> {
+            self.objects.lock().unwrap().remove(media_key);
+            Ok(())
+        }
+
+        async fn put_object(
+            &self,
+            key: &str,
+            data: &[u8],
+            _content_type: &str,
+        ) -> Result<(), String> {
+            let mut data = data.to_vec();
+            let corrupt = self
+                .corrupt_puts
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if corrupt {
+                data[0] ^= 0xff;
+            }
+            self.insert(key, &data);
+            Ok(())
+        }
+
+        async fn presigned_url(&self, key: &str, _expires_in: Duration) -> Result<String, String> {
+            Ok(format!("memory://{key}"))
+        }
+
+        async fn get_object(&self, key: &str) -> Result<Vec<u8>, String> {
+            self.object(key).ok_or_else(|| format!("{key} not found"))
+        }
+    }
+
+    async fn seed_media(
+        db: &DatabaseConnection,
+        product_id: Uuid,
+        key: &str,
+        data: &[u8],
+        webp: Option<(&str, &[u8])>,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        product_media::Entity::insert(ProductMediaActiveModel {
+            id: Set(id),
+            product_id: Set(product_id),
+            s3_key: Set(key.to_string()),
+            content_type: Set("image/jpeg".to_string()),
+            size_bytes: Set(data.len() as i64),
+            webp_s3_key: Set(webp.map(|(key, _)| key.to_string())),
+            webp_size_bytes: Set(webp.map(|(_, data)| data.len() as i64)),
+            content_hash: Set(Some(content_hash(data))),
+            perceptual_hash: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+        })
+        .exec_without_returning(db)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn start(db: &DatabaseConnection, dry_run: bool) -> MediaMigrationModel {
+        MediaMigration::create(
+            db,
+            &NewMediaMigration {
+                requested_by: "ops-1".to_string(),
+                source: "http://minio:9000/transac-media".to_string(),
+                target: "https://r2.example/transac-media".to_string(),
+                key_prefix: Some("migrated".to_string()),
+                dry_run,
+            },
+            Utc::now() - chrono::Duration::minutes(STALE_MIGRATION_MINUTES),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    fn options(dry_run: bool) -> MigrationOptions {
+        MigrationOptions {
+            batch_size: 1,
+            concurrency: 2,
+            key_prefix: Some("migrated".to_string()),
+            dry_run,
+        }
+    }
+
+    async fn media(db: &DatabaseConnection, id: Uuid) -> ProductMediaModel {
+        product_media::Entity::find_by_id(id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_copies_every_object_and_rewrites_keys() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        let photo = seed_media(&db, product, "a.jpg", b"photo", Some(("a.webp", b"wp"))).await;
+        let other = seed_media(&db, product, "b.jpg", b"other photo", None).await;
+        let source = MemoryStorage::with(&[
+            ("a.jpg", b"photo"),
+            ("a.webp", b"wp"),
+            ("b.jpg", b"other photo"),
+        ]);
+        let target = MemoryStorage::default();
+
+        let run = start(&db, false).await;
+        let done = run_migration(&db, &run, &source, &target, &options(false))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(done.status, "completed");
+        assert_eq!((done.media_done, done.media_failed), (2, 0));
+        assert_eq!(done.objects_copied, 3);
+        assert_eq!(done.bytes_copied, (5 + 2 + 11) as i64);
+
+        assert_eq!(target.object("migrated/a.jpg").unwrap(), b"photo");
+        assert_eq!(target.object("migrated/a.webp").unwrap(), b"wp");
+        let photo = media(&db, photo).await;
+        assert_eq!(photo.s3_key, "migrated/a.jpg");
+        assert_eq!(photo.webp_s3_key.as_deref(), Some("migrated/a.webp"));
+        assert_eq!(media(&db, other).await.s3_key, "migrated/b.jpg");
+    }
+
+    #[tokio::test]
+    async fn test_hash_verification_catches_a_corrupted_copy() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        let flaky = seed_media(&db, product, "a.jpg", b"photo", None).await;
+        let source = MemoryStorage::with(&[("a.jpg", b"photo")]);
+
+        // A single bad write is retried
+        let target = MemoryStorage {
+            corrupt_puts: AtomicUsize::new(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            copy_with_retries(&source, &target, "a.jpg", "a.jpg", "image/jpeg", None).await,
+            Ok(5)
+        );
+
+        // One that never comes out right is recorded and its key left alone
+        let target = MemoryStorage {
+            corrupt_puts: AtomicUsize::new(usize::MAX),
+            ..Default::default()
+        };
+        let run = start(&db, false).await;
+        let done = run_migration(&db, &run, &source, &target, &options(false))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(done.status, "completed");
+        assert_eq!((done.media_done, done.media_failed), (1, 1));
+        assert_eq!(done.objects_copied, 0);
+        assert_eq!(done.failures[0]["media_id"], flaky.to_string());
+        assert_eq!(
+            done.failures[0]["error"],
+            "copy of a.jpg failed hash verification"
+        );
+        assert_eq!(media(&db, flaky).await.s3_key, "a.jpg");
+
+        // A source object that changed since upload is caught too
+        source.insert("a.jpg", b"tampered");
+        assert_eq!(
+            copy_object(
+                &source,
+                &MemoryStorage::default(),
+                "a.jpg",
+                "a.jpg",
+                "image/jpeg",
+                Some(&content_hash(b"photo")),
+            )
+            .await,
+            Err("source object a.jpg does not match its upload hash".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resumed_run_continues_after_its_last_batch() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        let mut ids = [
+            seed_media(&db, product, "a.jpg", b"a", None).await,
+            seed_media(&db, product, "b.jpg", b"b", None).await,
+        ];
+        ids.sort();
+        let source = MemoryStorage::with(&[("a.jpg", b"a"), ("b.jpg", b"b")]);
+        let target = MemoryStorage::default();
+
+        // The first batch was saved before the replica died
+        let run = start(&db, false).await;
+        let first = MediaMigration::next_batch(&db, None, 1).await.unwrap();
+        let progress = migrate_batch(&source, &target, &first, &options(false)).await;
+        MediaMigration::record_batch(&db, run.id, &progress)
+            .await
+            .unwrap();
+
+        let fresh = Utc::now() - chrono::Duration::minutes(STALE_MIGRATION_MINUTES);
+        assert!(MediaMigration::resume(&db, run.id, fresh)
+            .await
+            .unwrap()
+            .is_none());
+        let resumed =
+            MediaMigration::resume(&db, run.id, Utc::now() + chrono::Duration::seconds(1))
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(resumed.last_media_id, Some(ids[0]));
+
+        // Anything left at the first key would be copied twice
+        target.objects.lock().unwrap().clear();
+        let done = run_migration(&db, &resumed, &source, &target, &options(false))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(done.media_done, 2);
+        assert_eq!(target.objects.lock().unwrap().len(), 1);
+        assert!(media(&db, ids[1]).await.s3_key.starts_with("migrated/"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_counts_without_copying() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        let id = seed_media(&db, product, "a.jpg", b"photo", Some(("a.webp", b"wp"))).await;
+        let source = MemoryStorage::with(&[("a.jpg", b"photo"), ("a.webp", b"wp")]);
+        let target = MemoryStorage::default();
+
+        let run = start(&db, true).await;
+        let done = run_migration(&db, &run, &source, &target, &options(true))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((done.objects_copied, done.bytes_copied), (2, 7));
+        assert!(target.objects.lock().unwrap().is_empty());
+        assert_eq!(media(&db, id).await.s3_key, "a.jpg");
+    }
+
+    #[test]
+    fn test_target_key_prefix() {
+        assert_eq!(target_key(None, "products/1/a.jpg"), "products/1/a.jpg");
+        assert_eq!(
+            target_key(Some("r2/"), "products/1/a.jpg"),
+            "r2/products/1/a.jpg"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Lightweight in-process metrics rendered in the Prometheus text exposition format.
+//!
+//! Metrics are process-wide statics so any module can record a value without
+//! threading a registry through handler state.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Monotonically increasing counter
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, amount: u64) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.value.load(Ordering::Relaxed));
+    }
+}
+
+/// Value that can go up and down
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Gauge {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set(&self, value: u64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.value.load(Ordering::Relaxed));
+    }
+}
+
+/// Counter split by one label whose values are fixed up front, so the
+/// series count stays bounded
+pub struct LabeledCounter<const N: usize> {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: [&'static str; N],
+    counts: [AtomicU64; N],
+}
+
+impl<const N: usize> LabeledCounter<N> {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label: &'static str,
+        values: [&'static str; N],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values,
+            counts: [const { AtomicU64::new(0) }; N],
+        }
+    }
+
+    /// Count one for `value`; values not declared up front are dropped
+    pub fn inc(&self, value: &str) {
+        match self.values.iter().position(|v| *v == value) {
+            Some(index) => {
+                self.counts[index].fetch_add(1, Ordering::Relaxed);
+            }
+            None => debug_assert!(false, "{} has no {} {value}", self.name, self.label),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, value: &str) -> u64 {
+        self.values
+            .iter()
+            .position(|v| *v == value)
+            .map_or(0, |index| self.counts[index].load(Ordering::Relaxed))
+    }
+}
+
+/// Distribution of observed values over fixed upper bounds
+pub struct Histogram<const N: usize> {
+    name: &'static str,
+    help: &'static str,
+    bounds: [f64; N],
+    /// Observations per bucket, not cumulative; the last slot is `+Inf`
+    buckets: [AtomicU64; N],
+    overflow: AtomicU64,
+    /// `f64` bits of the running sum
+    sum: AtomicU64,
+}
+
+impl<const N: usize> Histogram<N> {
+    /// `bounds` must be increasing
+    pub const fn new(name: &'static str, help: &'static str, bounds: [f64; N]) -> Self {
+        Self {
+            name,
+            help,
+            bounds,
+            buckets: [const { AtomicU64::new(0) }; N],
+            overflow: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        match self.bounds.iter().position(|bound| value <= *bound) {
+            Some(index) => self.buckets[index].fetch_add(1, Ordering::Relaxed),
+            None => self.overflow.fetch_add(1, Ordering::Relaxed),
+        };
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    #[allow(dead_code)]
+    pub fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .chain([&self.overflow])
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+/// Metrics other than plain counters and gauges
+trait Family: Sync {
+    fn render(&self, out: &mut String);
+}
+
+impl<const N: usize> Family for LabeledCounter<N> {
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (value, count) in self.values.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                self.name,
+                self.label,
+                value,
+                count.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+impl<const N: usize> Family for Histogram<N> {
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                self.name, bound, cumulative
+            );
+        }
+        cumulative += self.overflow.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, cumulative);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            self.name,
+            f64::from_bits(self.sum.load(Ordering::Relaxed))
+        );
+        let _ = writeln!(out, "{}_count {}", self.name, cumulative);
+    }
+}
+
+pub static WEBP_CONVERSIONS: Counter = Counter::new(
+    "transac_media_webp_conversions_total",
+    "Uploaded images stored with a WebP variant",
+);
+pub static WEBP_CONVERSIONS_SKIPPED: Counter = Counter::new(
+    "transac_media_webp_conversions_skipped_total",
+    "Uploaded images for which no WebP variant was stored",
+);
+pub static WEBP_BYTES_SAVED: Counter = Counter::new(
+    "transac_media_webp_bytes_saved_total",
+    "Bytes saved by serving the WebP variant instead of the original",
+);
+
+pub static MEDIA_STORAGE_BREAKER_OPENED: Counter = Counter::new(
+    "transac_media_storage_breaker_opened_total",
+    "Times the media storage circuit breaker tripped open",
+);
+pub static MEDIA_STORAGE_BREAKER_OPEN: Gauge = Gauge::new(
+    "transac_media_storage_breaker_open",
+    "1 while media storage is failing and the circuit breaker is not yet closed again",
+);
+
+pub static TOMBSTONES_PRUNED: Counter = Counter::new(
+    "transac_retention_tombstones_pruned_total",
+    "Sync tombstones deleted by the retention job",
+);
+pub static INVENTORY_SYNCS_PRUNED: Counter = Counter::new(
+    "transac_retention_inventory_syncs_pruned_total",
+    "Inventory sync replay records deleted by the retention job",
+);
+pub static REPORT_JOBS_PRUNED: Counter = Counter::new(
+    "transac_retention_report_jobs_pruned_total",
+    "Report job records deleted by the retention job",
+);
+pub static STORE_SNAPSHOTS_PRUNED: Counter = Counter::new(
+    "transac_retention_store_snapshots_pruned_total",
+    "Store snapshots deleted by the retention job",
+);
+
+// Hit rate is hits / (hits + misses)
+pub static TOKEN_CACHE_HITS: Counter = Counter::new(
+    "transac_token_cache_hits_total",
+    "Bearer tokens accepted from the verified-token cache",
+);
+pub static TOKEN_CACHE_MISSES: Counter = Counter::new(
+    "transac_token_cache_misses_total",
+    "Bearer tokens verified in full because they were not cached",
+);
+pub static TOKEN_CACHE_ENTRIES: Gauge = Gauge::new(
+    "transac_token_cache_entries",
+    "Verified tokens currently cached",
+);
+
+pub static POW_CHALLENGES_ISSUED: Counter = Counter::new(
+    "transac_pow_challenges_issued_total",
+    "Proof-of-work challenges handed out",
+);
+pub static POW_VERIFICATIONS: LabeledCounter<5> = LabeledCounter::new(
+    "transac_pow_verifications_total",
+    "Proof-of-work solutions checked, by outcome",
+    "outcome",
+    [
+        "success",
+        "unknown_challenge",
+        "expired",
+        "invalid_hash",
+        "insufficient_difficulty",
+    ],
+);
+// Reported by the client, so only accepted solutions are counted
+pub static POW_SOLVE_SECONDS: Histogram<9> = Histogram::new(
+    "transac_pow_solve_seconds",
+    "Client-reported time to solve an accepted proof-of-work challenge",
+    [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0],
+);
+pub static POW_DIFFICULTY_ISSUED: Histogram<8> = Histogram::new(
+    "transac_pow_difficulty_issued",
+    "Leading zero bits required by the challenges handed out",
+    [4.0, 8.0, 12.0, 16.0, 20.0, 24.0, 28.0, 32.0],
+);
+
+static COUNTERS: &[&Counter] = &[
+    &WEBP_CONVERSIONS,
+    &WEBP_CONVERSIONS_SKIPPED,
+    &WEBP_BYTES_SAVED,
+    &MEDIA_STORAGE_BREAKER_OPENED,
+    &TOMBSTONES_PRUNED,
+    &INVENTORY_SYNCS_PRUNED,
+    &REPORT_JOBS_PRUNED,
+    &STORE_SNAPSHOTS_PRUNED,
+    &TOKEN_CACHE_HITS,
+    &TOKEN_CACHE_MISSES,
+    &POW_CHALLENGES_ISSUED,
+];
+
+static GAUGES: &[&Gauge] = &[&MEDIA_STORAGE_BREAKER_OPEN, &TOKEN_CACHE_ENTRIES];
+
+static FAMILIES: &[&dyn Family] = &[
+    &POW_VERIFICATIONS,
+    &POW_SOLVE_SECONDS,
+    &POW_DIFFICULTY_ISSUED,
+];
+
+/// Render every registered metric in the Prometheus text format
+pub fn render() -> String {
+    let mut out = String::new();
+    for counter in COUNTERS {
+        counter.render(&mut out);
+    }
+    for gauge in GAUGES {
+        gauge.render(&mut out);
+    }
+    for family in FAMILIES {
+        family.render(&mut out);
+    }
+    out
+}
@@ -10,6 +10,57 @@ impl MigratorTrait for Migrator {
             Box::new(m20251001_create_stores::Migration),
             Box::new(m20251002_create_products::Migration),
             Box::new(m20251003_fix_price_type::Migration),
+            Box::new(m20251004_create_product_media::Migration),
+            Box::new(m20251005_add_return_policies::Migration),
+            Box::new(m20251006_add_product_publish_at::Migration),
+            Box::new(m20251007_add_product_sale::Migration),
+            Box::new(m20251008_create_categories::Migration),
+            Box::new(m20251009_add_sync_tracking::Migration),
+            Box::new(m20251010_create_store_api_keys::Migration),
+            Box::new(m20251011_create_store_promotions::Migration),
+            Box::new(m20251012_add_product_media_content_hash::Migration),
+            Box::new(m20251013_add_store_pause::Migration),
+            Box::new(m20251014_add_structured_return_policy::Migration),
+            Box::new(m20251015_add_store_trust_score::Migration),
+            Box::new(m20251016_create_inventory_syncs::Migration),
+            Box::new(m20251017_create_prohibited_terms::Migration),
+            Box::new(m20251018_create_product_bundles::Migration),
+            Box::new(m20251019_create_system_settings::Migration),
+            Box::new(m20251020_add_tenant_ids::Migration),
+            Box::new(m20251021_create_product_questions::Migration),
+            Box::new(m20251022_add_store_contact_visibility::Migration),
+            Box::new(m20251023_add_store_media_quota::Migration),
+            Box::new(m20251024_create_product_price_history::Migration),
+            Box::new(m20251025_create_admin_credentials::Migration),
+            Box::new(m20251026_create_product_counts::Migration),
+            Box::new(m20251027_create_report_jobs::Migration),
+            Box::new(m20251028_add_delivery_options::Migration),
+            Box::new(m20251029_create_media_similarity_flags::Migration),
+            Box::new(m20251030_create_audit_log::Migration),
+            Box::new(m20251031_create_media_migrations::Migration),
+            Box::new(m20251101_create_store_payout_accounts::Migration),
+            Box::new(m20251102_create_product_watches::Migration),
+            Box::new(m20251103_create_commission_rates::Migration),
+            Box::new(m20251104_create_store_reviews::Migration),
+            Box::new(m20251105_add_store_first_shared_at::Migration),
+            Box::new(m20251106_create_experiment_exposures::Migration),
+            Box::new(m20251107_add_product_sku_unique_index::Migration),
+            Box::new(m20251108_create_review_anomalies::Migration),
+            Box::new(m20251109_add_product_search_indexes::Migration),
+            Box::new(m20251110_create_notification_digests::Migration),
+            Box::new(m20251112_create_events::Migration),
+            Box::new(m20251113_create_category_attributes::Migration),
+            Box::new(m20251114_add_product_archived_at::Migration),
+            Box::new(m20251115_create_profanity_terms::Migration),
+            Box::new(m20251116_create_store_snapshots::Migration),
+            Box::new(m20251117_add_product_currency::Migration),
+            Box::new(m20251118_add_store_embed_enabled::Migration),
+            Box::new(m20251119_create_referral_links::Migration),
+            Box::new(m20251120_create_public_stats::Migration),
+            Box::new(m20251121_add_product_low_stock_threshold::Migration),
+            Box::new(m20251122_create_product_views::Migration),
+            Box::new(m20251123_add_auto_responses::Migration),
+            Box::new(m20251124_seal_admin_signing_keys::Migration),
         ]
     }
 }
@@ -424,3 +475,5360 @@ mod m20251002_create_products {
         CreatedAt,
     }
 }
+
+mod m20251004_create_product_media {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251004_create_product_media"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductMedia::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductMedia::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ProductMedia::ProductId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(ProductMedia::S3Key)
+                                .string_len(500)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductMedia::ContentType)
+                                .string_len(100)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductMedia::SizeBytes)
+                                .big_integer()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ProductMedia::WebpS3Key).string_len(500))
+                        .col(ColumnDef::new(ProductMedia::WebpSizeBytes).big_integer())
+                        .col(
+                            ColumnDef::new(ProductMedia::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_media_product")
+                                .from(ProductMedia::Table, ProductMedia::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_media_product_id")
+                        .table(ProductMedia::Table)
+                        .col(ProductMedia::ProductId)
+                        .to_owned(),
+                )
+                .await?;
+
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductMedia::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+
+    #[derive(Iden)]
+    enum ProductMedia {
+        Table,
+        Id,
+        ProductId,
+        S3Key,
+        ContentType,
+        SizeBytes,
+        WebpS3Key,
+        WebpSizeBytes,
+        CreatedAt,
+    }
+}
+
+mod m20251005_add_return_policies {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251005_add_return_policies"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::DefaultReturnPolicy).text(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Source records whether the policy was set on the product, inherited
+            // from the store default, or left empty
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(ColumnDef::new(Products::ReturnPolicy).text())
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::ReturnPolicySource)
+                                .string_len(20)
+                                .not_null()
+                                .default("none"),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::ReturnPolicy)
+                        .drop_column(Products::ReturnPolicySource)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::DefaultReturnPolicy)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        DefaultReturnPolicy,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        ReturnPolicy,
+        ReturnPolicySource,
+    }
+}
+
+mod m20251006_add_product_publish_at {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251006_add_product_publish_at"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Existing products are already live, so is_published defaults to true
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::PublishAt).timestamp_with_time_zone(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::IsPublished)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_products_is_published_publish_at")
+                        .table(Products::Table)
+                        .col(Products::IsPublished)
+                        .col(Products::PublishAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_products_is_published_publish_at")
+                        .table(Products::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::PublishAt)
+                        .drop_column(Products::IsPublished)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        PublishAt,
+        IsPublished,
+    }
+}
+
+mod m20251007_add_product_sale {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251007_add_product_sale"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(ColumnDef::new(Products::SalePrice).double())
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::SaleEndsAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::SalePrice)
+                        .drop_column(Products::SaleEndsAt)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        SalePrice,
+        SaleEndsAt,
+    }
+}
+
+mod m20251008_create_categories {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251008_create_categories"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(Categories::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(Categories::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(Categories::Slug)
+                                .string_len(100)
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(ColumnDef::new(Categories::Name).string_len(255).not_null())
+                        .col(
+                            ColumnDef::new(Categories::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Starter set; operators can add more directly
+            let seed_sql = r#"
+                INSERT INTO categories (id, slug, name) VALUES
+                    (gen_random_uuid(), 'electronics', 'Electronics'),
+                    (gen_random_uuid(), 'fashion', 'Fashion'),
+                    (gen_random_uuid(), 'food', 'Food & Groceries'),
+                    (gen_random_uuid(), 'home', 'Home & Garden'),
+                    (gen_random_uuid(), 'beauty', 'Health & Beauty'),
+                    (gen_random_uuid(), 'other', 'Other')
+                ON CONFLICT (slug) DO NOTHING;
+            "#;
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    seed_sql.to_string(),
+                ))
+                .await?;
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(ColumnDef::new(Products::CategoryId).uuid())
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name("fk_products_category")
+                        .from(Products::Table, Products::CategoryId)
+                        .to(Categories::Table, Categories::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_products_category_id")
+                        .table(Products::Table)
+                        .col(Products::CategoryId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::CategoryId)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(Categories::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Categories {
+        Table,
+        Id,
+        Slug,
+        Name,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        CategoryId,
+    }
+}
+
+mod m20251009_add_sync_tracking {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251009_add_sync_tracking"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Existing rows have not changed since they were created
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "UPDATE products SET updated_at = created_at;".to_string(),
+                ))
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_products_updated_at")
+                        .table(Products::Table)
+                        .col(Products::UpdatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_stores_updated_at")
+                        .table(Stores::Table)
+                        .col(Stores::UpdatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            // Hard deletes leave a tombstone so syncing clients can drop their copy
+            manager
+                .create_table(
+                    Table::create()
+                        .table(Tombstones::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(Tombstones::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(Tombstones::EntityType)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(Tombstones::EntityId).uuid().not_null())
+                        .col(ColumnDef::new(Tombstones::StoreId).uuid())
+                        .col(
+                            ColumnDef::new(Tombstones::DeletedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_tombstones_deleted_at")
+                        .table(Tombstones::Table)
+                        .col(Tombstones::DeletedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(Tombstones::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_stores_updated_at")
+                        .table(Stores::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::UpdatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Tombstones {
+        Table,
+        Id,
+        EntityType,
+        EntityId,
+        StoreId,
+        DeletedAt,
+    }
+}
+
+mod m20251010_create_store_api_keys {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251010_create_store_api_keys"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(StoreApiKeys::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(StoreApiKeys::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(StoreApiKeys::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(StoreApiKeys::KeyHash)
+                                .string_len(64)
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(StoreApiKeys::KeyPrefix)
+                                .string_len(16)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StoreApiKeys::Label)
+                                .string_len(100)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StoreApiKeys::Scopes)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(StoreApiKeys::LastUsedAt).timestamp_with_time_zone())
+                        .col(
+                            ColumnDef::new(StoreApiKeys::Revoked)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(StoreApiKeys::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_store_api_keys_store")
+                                .from(StoreApiKeys::Table, StoreApiKeys::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_api_keys_store_id")
+                        .table(StoreApiKeys::Table)
+                        .col(StoreApiKeys::StoreId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(StoreApiKeys::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum StoreApiKeys {
+        Table,
+        Id,
+        StoreId,
+        KeyHash,
+        KeyPrefix,
+        Label,
+        Scopes,
+        LastUsedAt,
+        Revoked,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251011_create_store_promotions {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251011_create_store_promotions"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(StorePromotions::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(StorePromotions::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(StorePromotions::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(StorePromotions::Position)
+                                .integer()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePromotions::StartsAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePromotions::EndsAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePromotions::CreatedBy)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePromotions::Expired)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(StorePromotions::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_store_promotions_store")
+                                .from(StorePromotions::Table, StorePromotions::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_promotions_window")
+                        .table(StorePromotions::Table)
+                        .col(StorePromotions::Position)
+                        .col(StorePromotions::StartsAt)
+                        .col(StorePromotions::EndsAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(StorePromotions::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum StorePromotions {
+        Table,
+        Id,
+        StoreId,
+        Position,
+        StartsAt,
+        EndsAt,
+        CreatedBy,
+        Expired,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251012_add_product_media_content_hash {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251012_add_product_media_content_hash"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Unknown for uploads made before hashing was introduced
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductMedia::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(ProductMedia::ContentHash).string_len(64),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(ProductMedia::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "UPDATE product_media SET updated_at = created_at;".to_string(),
+                ))
+                .await?;
+
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductMedia::Table)
+                        .drop_column(ProductMedia::ContentHash)
+                        .drop_column(ProductMedia::UpdatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductMedia {
+        Table,
+        ContentHash,
+        UpdatedAt,
+    }
+}
+
+mod m20251013_add_store_pause {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251013_add_store_pause"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::IsPaused)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::PausedUntil).timestamp_with_time_zone(),
+                        )
+                        .add_column_if_not_exists(ColumnDef::new(Stores::PauseMessage).text())
+                        .to_owned(),
+                )
+                .await?;
+
+            // The auto-resume job only looks at paused stores with an end date
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_stores_paused_until")
+                        .table(Stores::Table)
+                        .col(Stores::IsPaused)
+                        .col(Stores::PausedUntil)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_stores_paused_until")
+                        .table(Stores::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::IsPaused)
+                        .drop_column(Stores::PausedUntil)
+                        .drop_column(Stores::PauseMessage)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        IsPaused,
+        PausedUntil,
+        PauseMessage,
+    }
+}
+
+mod m20251014_add_structured_return_policy {
+    use super::*;
+    use crate::db::return_policy::parse_return_policy;
+    use sea_orm::{ConnectionTrait, Value};
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251014_add_structured_return_policy"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::ReturnsAccepted)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::ReturnWindowDays).integer(),
+                        )
+                        .add_column_if_not_exists(ColumnDef::new(Products::ReturnConditions).text())
+                        .to_owned(),
+                )
+                .await?;
+
+            // Best-effort: read the old free text into the new fields. The text
+            // itself stays in return_policy, so nothing is lost when we can't.
+            let conn = manager.get_connection();
+            let rows = conn
+                .query_all(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "SELECT id, return_policy FROM products WHERE return_policy IS NOT NULL"
+                        .to_string(),
+                ))
+                .await?;
+            let mut unparsed = 0usize;
+            for row in rows {
+                let id: uuid::Uuid = row.try_get("", "id")?;
+                let text: String = row.try_get("", "return_policy")?;
+                let terms = match parse_return_policy(&text) {
+                    Some(terms) => terms,
+                    None => {
+                        unparsed += 1;
+                        tracing::warn!(product_id = %id, policy = %text, "Unparseable return policy");
+                        continue;
+                    }
+                };
+                conn.execute(Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    "UPDATE products SET returns_accepted = $1, return_window_days = $2, \
+                     return_conditions = $3 WHERE id = $4",
+                    [
+                        Value::from(terms.returns_accepted),
+                        Value::from(terms.window_days),
+                        Value::from(terms.conditions),
+                        Value::from(id),
+                    ],
+                ))
+                .await?;
+            }
+            if unparsed > 0 {
+                tracing::warn!(
+                    count = unparsed,
+                    "Return policies left as text only; sellers should review them"
+                );
+            }
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::ReturnsAccepted)
+                        .drop_column(Products::ReturnWindowDays)
+                        .drop_column(Products::ReturnConditions)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        ReturnsAccepted,
+        ReturnWindowDays,
+        ReturnConditions,
+    }
+}
+
+mod m20251015_add_store_trust_score {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251015_add_store_trust_score"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Null until the trust job has scored the store
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(ColumnDef::new(Stores::TrustScore).double())
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_stores_trust_score")
+                        .table(Stores::Table)
+                        .col(Stores::TrustScore)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_stores_trust_score")
+                        .table(Stores::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::TrustScore)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        TrustScore,
+    }
+}
+
+mod m20251016_create_inventory_syncs {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251016_create_inventory_syncs"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(InventorySyncs::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(InventorySyncs::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(InventorySyncs::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(InventorySyncs::SyncId)
+                                .string_len(100)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(InventorySyncs::Report)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(InventorySyncs::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_inventory_syncs_store")
+                                .from(InventorySyncs::Table, InventorySyncs::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Idempotency: a sync id is applied at most once per store
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_inventory_syncs_store_sync_id")
+                        .table(InventorySyncs::Table)
+                        .col(InventorySyncs::StoreId)
+                        .col(InventorySyncs::SyncId)
+                        .unique()
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(InventorySyncs::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum InventorySyncs {
+        Table,
+        Id,
+        StoreId,
+        SyncId,
+        Report,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251017_create_prohibited_terms {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251017_create_prohibited_terms"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProhibitedTerms::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::Term)
+                                .string_len(200)
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::Category)
+                                .string_len(50)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::Action)
+                                .string_len(10)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::CreatedBy)
+                                .string()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProhibitedTerms::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductModeration::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductModeration::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductModeration::ProductId)
+                                .uuid()
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductModeration::MatchedTerms)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductModeration::Status)
+                                .string_len(20)
+                                .not_null()
+                                .default("pending"),
+                        )
+                        .col(
+                            ColumnDef::new(ProductModeration::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(ColumnDef::new(ProductModeration::ReviewedBy).string())
+                        .col(
+                            ColumnDef::new(ProductModeration::ReviewedAt)
+                                .timestamp_with_time_zone(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_moderation_product")
+                                .from(ProductModeration::Table, ProductModeration::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Visibility checks look up held products by status
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_moderation_status")
+                        .table(ProductModeration::Table)
+                        .col(ProductModeration::Status)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductModeration::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProhibitedTerms::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProhibitedTerms {
+        Table,
+        Id,
+        Term,
+        Category,
+        Action,
+        CreatedBy,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum ProductModeration {
+        Table,
+        Id,
+        ProductId,
+        MatchedTerms,
+        Status,
+        CreatedAt,
+        ReviewedBy,
+        ReviewedAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251018_create_product_bundles {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251018_create_product_bundles"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductBundles::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductBundles::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ProductBundles::StoreId).uuid().not_null())
+                        .col(ColumnDef::new(ProductBundles::Name).string().not_null())
+                        .col(ColumnDef::new(ProductBundles::Description).text())
+                        .col(ColumnDef::new(ProductBundles::Price).double().not_null())
+                        .col(
+                            ColumnDef::new(ProductBundles::IsActive)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .col(
+                            ColumnDef::new(ProductBundles::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(
+                            ColumnDef::new(ProductBundles::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_bundles_store")
+                                .from(ProductBundles::Table, ProductBundles::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_bundles_store_id")
+                        .table(ProductBundles::Table)
+                        .col(ProductBundles::StoreId)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_table(
+                    Table::create()
+                        .table(BundleItems::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(BundleItems::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(BundleItems::BundleId).uuid().not_null())
+                        .col(ColumnDef::new(BundleItems::ProductId).uuid().not_null())
+                        .col(ColumnDef::new(BundleItems::Quantity).integer().not_null())
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_bundle_items_bundle")
+                                .from(BundleItems::Table, BundleItems::BundleId)
+                                .to(ProductBundles::Table, ProductBundles::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_bundle_items_product")
+                                .from(BundleItems::Table, BundleItems::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // A component appears once per bundle, with its quantity
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_bundle_items_bundle_product")
+                        .table(BundleItems::Table)
+                        .col(BundleItems::BundleId)
+                        .col(BundleItems::ProductId)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            // Finding the bundles a product belongs to when it is deleted
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_bundle_items_product_id")
+                        .table(BundleItems::Table)
+                        .col(BundleItems::ProductId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(BundleItems::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductBundles::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductBundles {
+        Table,
+        Id,
+        StoreId,
+        Name,
+        Description,
+        Price,
+        IsActive,
+        CreatedAt,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum BundleItems {
+        Table,
+        Id,
+        BundleId,
+        ProductId,
+        Quantity,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251019_create_system_settings {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251019_create_system_settings"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(SystemSettings::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(SystemSettings::Key)
+                                .string_len(100)
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(SystemSettings::Value)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(SystemSettings::UpdatedBy).string().null())
+                        .col(
+                            ColumnDef::new(SystemSettings::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(SystemSettings::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum SystemSettings {
+        Table,
+        Key,
+        Value,
+        UpdatedBy,
+        UpdatedAt,
+    }
+}
+
+mod m20251020_add_tenant_ids {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251020_add_tenant_ids"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // The default backfills every existing row into the default tenant
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::TenantId)
+                                .string_len(32)
+                                .not_null()
+                                .default("default"),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::TenantId)
+                                .string_len(32)
+                                .not_null()
+                                .default("default"),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("idx_stores_tenant_id")
+                        .table(Stores::Table)
+                        .col(Stores::TenantId)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("idx_products_tenant_id")
+                        .table(Products::Table)
+                        .col(Products::TenantId)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::TenantId)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::TenantId)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        TenantId,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        TenantId,
+    }
+}
+
+mod m20251021_create_product_questions {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251021_create_product_questions"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductQuestions::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductQuestions::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductQuestions::ProductId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductQuestions::AskedBy)
+                                .string()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ProductQuestions::Question).text().not_null())
+                        .col(ColumnDef::new(ProductQuestions::Answer).text())
+                        .col(ColumnDef::new(ProductQuestions::AnsweredBy).string())
+                        .col(
+                            ColumnDef::new(ProductQuestions::IsHeld)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(ProductQuestions::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(
+                            ColumnDef::new(ProductQuestions::AnsweredAt).timestamp_with_time_zone(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_questions_product")
+                                .from(ProductQuestions::Table, ProductQuestions::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Product pages list a product's questions newest first
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_questions_product_created")
+                        .table(ProductQuestions::Table)
+                        .col(ProductQuestions::ProductId)
+                        .col(ProductQuestions::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductQuestions::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductQuestions {
+        Table,
+        Id,
+        ProductId,
+        AskedBy,
+        Question,
+        Answer,
+        AnsweredBy,
+        IsHeld,
+        CreatedAt,
+        AnsweredAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251022_add_store_contact_visibility {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251022_add_store_contact_visibility"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Existing stores keep publishing everything they published before
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::ShowPhone)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::ShowWhatsapp)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::ShowEmail)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::ShowPhone)
+                        .drop_column(Stores::ShowWhatsapp)
+                        .drop_column(Stores::ShowEmail)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        ShowPhone,
+        ShowWhatsapp,
+        ShowEmail,
+    }
+}
+
+mod m20251023_add_store_media_quota {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251023_add_store_media_quota"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::MediaBytesUsed)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::MediaUploadsToday)
+                                .integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .add_column_if_not_exists(ColumnDef::new(Stores::MediaUploadsDay).date())
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::MediaQuotaBytes).big_integer(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::MediaDailyUploads).integer(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::MediaImagesPerProduct).integer(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Start the counter from what the stores already keep, so deleting
+            // older media never drives it below zero
+            let backfill_sql = r#"
+                UPDATE stores SET media_bytes_used = COALESCE((
+                    SELECT SUM(pm.size_bytes)
+                    FROM product_media pm
+                    JOIN products p ON p.id = pm.product_id
+                    WHERE p.store_id = stores.id
+                ), 0);
+            "#;
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    backfill_sql.to_string(),
+                ))
+                .await?;
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::MediaBytesUsed)
+                        .drop_column(Stores::MediaUploadsToday)
+                        .drop_column(Stores::MediaUploadsDay)
+                        .drop_column(Stores::MediaQuotaBytes)
+                        .drop_column(Stores::MediaDailyUploads)
+                        .drop_column(Stores::MediaImagesPerProduct)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        MediaBytesUsed,
+        MediaUploadsToday,
+        MediaUploadsDay,
+        MediaQuotaBytes,
+        MediaDailyUploads,
+        MediaImagesPerProduct,
+    }
+}
+
+mod m20251024_create_product_price_history {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251024_create_product_price_history"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductPriceHistory::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::ProductId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::StoreId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::PreviousPrice)
+                                .double()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::Price)
+                                .double()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::Source)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductPriceHistory::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_price_history_product")
+                                .from(ProductPriceHistory::Table, ProductPriceHistory::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_price_history_product_created")
+                        .table(ProductPriceHistory::Table)
+                        .col(ProductPriceHistory::ProductId)
+                        .col(ProductPriceHistory::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductPriceHistory::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductPriceHistory {
+        Table,
+        Id,
+        ProductId,
+        StoreId,
+        PreviousPrice,
+        Price,
+        Source,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251025_create_admin_credentials {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251025_create_admin_credentials"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(AdminCredentials::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(AdminCredentials::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(AdminCredentials::RelayId)
+                                .string()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(AdminCredentials::Label)
+                                .string_len(100)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(AdminCredentials::SecretHash)
+                                .string_len(64)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(AdminCredentials::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(ColumnDef::new(AdminCredentials::RevokedAt).timestamp_with_time_zone())
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_admin_credentials_relay_id")
+                        .table(AdminCredentials::Table)
+                        .col(AdminCredentials::RelayId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(AdminCredentials::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum AdminCredentials {
+        Table,
+        Id,
+        RelayId,
+        Label,
+        SecretHash,
+        CreatedAt,
+        RevokedAt,
+    }
+}
+
+mod m20251026_create_product_counts {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251026_create_product_counts"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductCounts::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductCounts::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductCounts::TenantId)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ProductCounts::StoreId).uuid().not_null())
+                        .col(ColumnDef::new(ProductCounts::CategoryId).uuid().null())
+                        .col(
+                            ColumnDef::new(ProductCounts::Status)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductCounts::ProductCount)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(ProductCounts::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_counts_store")
+                                .from(ProductCounts::Table, ProductCounts::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_counts_store_category_status")
+                        .table(ProductCounts::Table)
+                        .col(ProductCounts::StoreId)
+                        .col(ProductCounts::CategoryId)
+                        .col(ProductCounts::Status)
+                        .to_owned(),
+                )
+                .await?;
+
+            // Initial projection; later drift is fixed by the admin rebuild
+            let backfill_sql = r#"
+                INSERT INTO product_counts (id, tenant_id, store_id, category_id, status, product_count)
+                SELECT gen_random_uuid(), tenant_id, store_id, category_id,
+                       CASE WHEN is_published THEN 'published' ELSE 'unpublished' END,
+                       COUNT(*)
+                FROM products
+                GROUP BY tenant_id, store_id, category_id, is_published;
+            "#;
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    backfill_sql.to_string(),
+                ))
+                .await?;
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductCounts::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductCounts {
+        Table,
+        Id,
+        TenantId,
+        StoreId,
+        CategoryId,
+        Status,
+        ProductCount,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251027_create_report_jobs {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251027_create_report_jobs"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ReportJobs::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ReportJobs::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ReportJobs::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(ReportJobs::RequestedBy)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReportJobs::ReportType)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReportJobs::Format).string_len(8).not_null())
+                        .col(
+                            ColumnDef::new(ReportJobs::RangeStart)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReportJobs::RangeEnd)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReportJobs::Status).string_len(16).not_null())
+                        .col(ColumnDef::new(ReportJobs::MediaKey).text().null())
+                        .col(ColumnDef::new(ReportJobs::Error).text().null())
+                        .col(
+                            ColumnDef::new(ReportJobs::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(
+                            ColumnDef::new(ReportJobs::StartedAt)
+                                .timestamp_with_time_zone()
+                                .null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReportJobs::FinishedAt)
+                                .timestamp_with_time_zone()
+                                .null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReportJobs::ExpiresAt)
+                                .timestamp_with_time_zone()
+                                .null(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_report_jobs_store")
+                                .from(ReportJobs::Table, ReportJobs::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // The runner picks the oldest queued job and counts running ones
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_report_jobs_status_created")
+                        .table(ReportJobs::Table)
+                        .col(ReportJobs::Status)
+                        .col(ReportJobs::CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_report_jobs_store_id")
+                        .table(ReportJobs::Table)
+                        .col(ReportJobs::StoreId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ReportJobs::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ReportJobs {
+        Table,
+        Id,
+        StoreId,
+        RequestedBy,
+        ReportType,
+        Format,
+        RangeStart,
+        RangeEnd,
+        Status,
+        MediaKey,
+        Error,
+        CreatedAt,
+        StartedAt,
+        FinishedAt,
+        ExpiresAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251028_add_delivery_options {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251028_add_delivery_options"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Existing stores and products keep working as before: pickup only
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::DefaultPickupAvailable)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::DefaultDeliveryAvailable)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::DefaultDeliveryFee).double(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::DefaultDeliveryEstimatedDays).integer(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::PickupAvailable)
+                                .boolean()
+                                .not_null()
+                                .default(true),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::DeliveryAvailable)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::DeliveryFeeOverride).double(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::DeliveryEstimatedDays).integer(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::DeliveryOptionsSource)
+                                .string_len(20)
+                                .not_null()
+                                .default("store_default"),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::PickupAvailable)
+                        .drop_column(Products::DeliveryAvailable)
+                        .drop_column(Products::DeliveryFeeOverride)
+                        .drop_column(Products::DeliveryEstimatedDays)
+                        .drop_column(Products::DeliveryOptionsSource)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::DefaultPickupAvailable)
+                        .drop_column(Stores::DefaultDeliveryAvailable)
+                        .drop_column(Stores::DefaultDeliveryFee)
+                        .drop_column(Stores::DefaultDeliveryEstimatedDays)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        DefaultPickupAvailable,
+        DefaultDeliveryAvailable,
+        DefaultDeliveryFee,
+        DefaultDeliveryEstimatedDays,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        PickupAvailable,
+        DeliveryAvailable,
+        DeliveryFeeOverride,
+        DeliveryEstimatedDays,
+        DeliveryOptionsSource,
+    }
+}
+
+mod m20251029_create_media_similarity_flags {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251029_create_media_similarity_flags"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Older uploads stay unhashed; they are never matched against
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductMedia::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(ProductMedia::PerceptualHash).big_integer(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_table(
+                    Table::create()
+                        .table(MediaSimilarityFlags::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::TenantId)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::MediaId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::ProductId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::StoreId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::MatchedMediaId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::MatchedProductId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::MatchedStoreId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::Distance)
+                                .integer()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::Status)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(MediaSimilarityFlags::ReviewedBy).string_len(255))
+                        .col(
+                            ColumnDef::new(MediaSimilarityFlags::ReviewedAt)
+                                .timestamp_with_time_zone(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_media_similarity_flags_media")
+                                .from(MediaSimilarityFlags::Table, MediaSimilarityFlags::MediaId)
+                                .to(ProductMedia::Table, ProductMedia::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_media_similarity_flags_matched_media")
+                                .from(
+                                    MediaSimilarityFlags::Table,
+                                    MediaSimilarityFlags::MatchedMediaId,
+                                )
+                                .to(ProductMedia::Table, ProductMedia::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // A pair is flagged once, however often the upload is retried
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_media_similarity_flags_pair")
+                        .table(MediaSimilarityFlags::Table)
+                        .col(MediaSimilarityFlags::MediaId)
+                        .col(MediaSimilarityFlags::MatchedMediaId)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            // The moderation queue lists one tenant's pending flags, oldest first
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_media_similarity_flags_queue")
+                        .table(MediaSimilarityFlags::Table)
+                        .col(MediaSimilarityFlags::TenantId)
+                        .col(MediaSimilarityFlags::Status)
+                        .col(MediaSimilarityFlags::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(MediaSimilarityFlags::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductMedia::Table)
+                        .drop_column(ProductMedia::PerceptualHash)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductMedia {
+        Table,
+        Id,
+        PerceptualHash,
+    }
+
+    #[derive(Iden)]
+    enum MediaSimilarityFlags {
+        Table,
+        Id,
+        TenantId,
+        MediaId,
+        ProductId,
+        StoreId,
+        MatchedMediaId,
+        MatchedProductId,
+        MatchedStoreId,
+        Distance,
+        Status,
+        CreatedAt,
+        ReviewedBy,
+        ReviewedAt,
+    }
+}
+
+mod m20251030_create_audit_log {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251030_create_audit_log"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // No foreign keys: the history of a product stays readable in its
+            // store's feed after the product is deleted
+            manager
+                .create_table(
+                    Table::create()
+                        .table(AuditLog::Table)
+                        .if_not_exists()
+                        .col(ColumnDef::new(AuditLog::Id).uuid().not_null().primary_key())
+                        .col(
+                            ColumnDef::new(AuditLog::EntityType)
+                                .string_len(16)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(AuditLog::EntityId).uuid().not_null())
+                        .col(ColumnDef::new(AuditLog::StoreId).uuid().not_null())
+                        .col(ColumnDef::new(AuditLog::ChangedBy).string_len(255))
+                        .col(ColumnDef::new(AuditLog::Source).string_len(32).not_null())
+                        .col(ColumnDef::new(AuditLog::Changes).json_binary().not_null())
+                        .col(
+                            ColumnDef::new(AuditLog::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_audit_log_entity_created")
+                        .table(AuditLog::Table)
+                        .col(AuditLog::EntityId)
+                        .col(AuditLog::CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_audit_log_store_created")
+                        .table(AuditLog::Table)
+                        .col(AuditLog::StoreId)
+                        .col(AuditLog::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(Table::drop().table(AuditLog::Table).if_exists().to_owned())
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum AuditLog {
+        Table,
+        Id,
+        EntityType,
+        EntityId,
+        StoreId,
+        ChangedBy,
+        Source,
+        Changes,
+        CreatedAt,
+    }
+}
+
+mod m20251031_create_media_migrations {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251031_create_media_migrations"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(MediaMigrations::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(MediaMigrations::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::RequestedBy)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(MediaMigrations::Source).text().not_null())
+                        .col(ColumnDef::new(MediaMigrations::Target).text().not_null())
+                        .col(ColumnDef::new(MediaMigrations::KeyPrefix).string_len(255))
+                        .col(
+                            ColumnDef::new(MediaMigrations::DryRun)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::Status)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(MediaMigrations::LastMediaId).uuid())
+                        .col(
+                            ColumnDef::new(MediaMigrations::MediaDone)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::ObjectsCopied)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::BytesCopied)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::MediaFailed)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::Failures)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(MediaMigrations::Error).text())
+                        .col(
+                            ColumnDef::new(MediaMigrations::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(MediaMigrations::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(MediaMigrations::FinishedAt).timestamp_with_time_zone())
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(MediaMigrations::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum MediaMigrations {
+        Table,
+        Id,
+        RequestedBy,
+        Source,
+        Target,
+        KeyPrefix,
+        DryRun,
+        Status,
+        LastMediaId,
+        MediaDone,
+        ObjectsCopied,
+        BytesCopied,
+        MediaFailed,
+        Failures,
+        Error,
+        CreatedAt,
+        UpdatedAt,
+        FinishedAt,
+    }
+}
+
+mod m20251101_create_store_payout_accounts {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251101_create_store_payout_accounts"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(StorePayoutAccounts::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        // One account per store
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::StoreId)
+                                .uuid()
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::Provider)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::AccountNumberEncrypted)
+                                .text()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::AccountName)
+                                .string_len(100)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::Verified)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(ColumnDef::new(StorePayoutAccounts::VerifiedBy).string_len(255))
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::VerifiedAt)
+                                .timestamp_with_time_zone(),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .col(
+                            ColumnDef::new(StorePayoutAccounts::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_store_payout_accounts_store")
+                                .from(StorePayoutAccounts::Table, StorePayoutAccounts::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(StorePayoutAccounts::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum StorePayoutAccounts {
+        Table,
+        Id,
+        StoreId,
+        Provider,
+        AccountNumberEncrypted,
+        AccountName,
+        Verified,
+        VerifiedBy,
+        VerifiedAt,
+        CreatedAt,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251102_create_product_watches {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251102_create_product_watches"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductWatches::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductWatches::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ProductWatches::ProductId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(ProductWatches::WatcherId)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ProductWatches::TargetPrice).double())
+                        .col(
+                            ColumnDef::new(ProductWatches::LastNotifiedAt)
+                                .timestamp_with_time_zone(),
+                        )
+                        .col(
+                            ColumnDef::new(ProductWatches::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_watches_product")
+                                .from(ProductWatches::Table, ProductWatches::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // One watch per buyer and product; also serves the price-change lookup
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_watches_product_watcher")
+                        .table(ProductWatches::Table)
+                        .col(ProductWatches::ProductId)
+                        .col(ProductWatches::WatcherId)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_watches_watcher")
+                        .table(ProductWatches::Table)
+                        .col(ProductWatches::WatcherId)
+                        .col(ProductWatches::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductWatches::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductWatches {
+        Table,
+        Id,
+        ProductId,
+        WatcherId,
+        TargetPrice,
+        LastNotifiedAt,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251103_create_commission_rates {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251103_create_commission_rates"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(CommissionRates::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(CommissionRates::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(CommissionRates::CategoryId).uuid())
+                        .col(ColumnDef::new(CommissionRates::StoreId).uuid())
+                        .col(ColumnDef::new(CommissionRates::Percent).double().not_null())
+                        .col(
+                            ColumnDef::new(CommissionRates::EffectiveFrom)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(CommissionRates::CreatedBy)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(CommissionRates::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        // A rate is for a category or a store, never both
+                        .check(Expr::cust("(category_id IS NULL) <> (store_id IS NULL)"))
+                        .check(Expr::cust("percent >= 0 AND percent <= 100"))
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_commission_rates_category")
+                                .from(CommissionRates::Table, CommissionRates::CategoryId)
+                                .to(Categories::Table, Categories::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_commission_rates_store")
+                                .from(CommissionRates::Table, CommissionRates::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Effective-rate lookups, one per scope
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_commission_rates_category_effective")
+                        .table(CommissionRates::Table)
+                        .col(CommissionRates::CategoryId)
+                        .col(CommissionRates::EffectiveFrom)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_commission_rates_store_effective")
+                        .table(CommissionRates::Table)
+                        .col(CommissionRates::StoreId)
+                        .col(CommissionRates::EffectiveFrom)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(CommissionRates::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum CommissionRates {
+        Table,
+        Id,
+        CategoryId,
+        StoreId,
+        Percent,
+        EffectiveFrom,
+        CreatedBy,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Categories {
+        Table,
+        Id,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251104_create_store_reviews {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251104_create_store_reviews"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(StoreReviews::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(StoreReviews::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(StoreReviews::StoreId).uuid().not_null())
+                        .col(ColumnDef::new(StoreReviews::ReviewerId).string().not_null())
+                        .col(ColumnDef::new(StoreReviews::Rating).integer().not_null())
+                        .col(ColumnDef::new(StoreReviews::Comment).text())
+                        .col(ColumnDef::new(StoreReviews::OrderId).uuid())
+                        .col(
+                            ColumnDef::new(StoreReviews::IsVerified)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(ColumnDef::new(StoreReviews::Reply).text())
+                        .col(ColumnDef::new(StoreReviews::RepliedBy).string())
+                        .col(ColumnDef::new(StoreReviews::RepliedAt).timestamp_with_time_zone())
+                        .col(
+                            ColumnDef::new(StoreReviews::IsHeld)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(StoreReviews::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .check(Expr::cust("rating BETWEEN 1 AND 5"))
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_store_reviews_store")
+                                .from(StoreReviews::Table, StoreReviews::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // One review per buyer and store
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_reviews_store_reviewer")
+                        .table(StoreReviews::Table)
+                        .col(StoreReviews::StoreId)
+                        .col(StoreReviews::ReviewerId)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            // The daily limit counts a buyer's recent reviews
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_reviews_reviewer_created")
+                        .table(StoreReviews::Table)
+                        .col(StoreReviews::ReviewerId)
+                        .col(StoreReviews::CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            // Null until the store's first published review
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(ColumnDef::new(Stores::StoreRating).float())
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::StoreReviewCount)
+                                .integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::StoreRating)
+                        .drop_column(Stores::StoreReviewCount)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(StoreReviews::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum StoreReviews {
+        Table,
+        Id,
+        StoreId,
+        ReviewerId,
+        Rating,
+        Comment,
+        OrderId,
+        IsVerified,
+        Reply,
+        RepliedBy,
+        RepliedAt,
+        IsHeld,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+        StoreRating,
+        StoreReviewCount,
+    }
+}
+
+mod m20251105_add_store_first_shared_at {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251105_add_store_first_shared_at"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Null until the owner generates the store's share links
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::FirstSharedAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::FirstSharedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        FirstSharedAt,
+    }
+}
+
+mod m20251106_create_experiment_exposures {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251106_create_experiment_exposures"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ExperimentExposures::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ExperimentExposures::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ExperimentExposures::Experiment)
+                                .string_len(64)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ExperimentExposures::Variant)
+                                .string_len(64)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ExperimentExposures::Subject)
+                                .string()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ExperimentExposures::ExposedOn)
+                                .date()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ExperimentExposures::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // One exposure per caller, experiment and day; also serves the
+            // per-variant summary
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_experiment_exposures_daily")
+                        .table(ExperimentExposures::Table)
+                        .col(ExperimentExposures::Experiment)
+                        .col(ExperimentExposures::Subject)
+                        .col(ExperimentExposures::ExposedOn)
+                        .unique()
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ExperimentExposures::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ExperimentExposures {
+        Table,
+        Id,
+        Experiment,
+        Variant,
+        Subject,
+        ExposedOn,
+        CreatedAt,
+    }
+}
+
+mod m20251107_add_product_sku_unique_index {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251107_add_product_sku_unique_index"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // SKUs were free text until now, so a store may already repeat
+            // one. The oldest product keeps it; the others lose theirs,
+            // logged so the seller can set new ones, rather than fail the index
+            let conn = manager.get_connection();
+            let duplicates = conn
+                .query_all(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "SELECT id, store_id, sku FROM ( \
+                         SELECT id, store_id, sku, ROW_NUMBER() OVER ( \
+                             PARTITION BY store_id, sku ORDER BY created_at, id \
+                         ) AS nth \
+                         FROM products WHERE sku IS NOT NULL \
+                     ) numbered WHERE nth > 1"
+                        .to_string(),
+                ))
+                .await?;
+            for row in &duplicates {
+                let id: uuid::Uuid = row.try_get("", "id")?;
+                let store_id: uuid::Uuid = row.try_get("", "store_id")?;
+                let sku: String = row.try_get("", "sku")?;
+                tracing::warn!(product_id = %id, store_id = %store_id, sku = %sku, "Duplicate SKU cleared");
+                conn.execute(Statement::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    "UPDATE products SET sku = NULL, updated_at = now() WHERE id = $1",
+                    [Value::from(id)],
+                ))
+                .await?;
+            }
+            if !duplicates.is_empty() {
+                tracing::warn!(
+                    count = duplicates.len(),
+                    "SKUs cleared from products repeating one in their store; sellers should set new ones"
+                );
+            }
+
+            // Backs the form's SKU check against two creates racing in;
+            // products without a SKU never collide, NULLs being distinct
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_products_store_sku")
+                        .table(Products::Table)
+                        .col(Products::StoreId)
+                        .col(Products::Sku)
+                        .unique()
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_products_store_sku")
+                        .table(Products::Table)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        StoreId,
+        Sku,
+    }
+}
+
+mod m20251108_create_review_anomalies {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251108_create_review_anomalies"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ReviewAnomalies::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::TenantId)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReviewAnomalies::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::Pattern)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::ReviewIds)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReviewAnomalies::Score).double().not_null())
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::Status)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ReviewAnomalies::DetectedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReviewAnomalies::ReviewedBy).string_len(255))
+                        .col(ColumnDef::new(ReviewAnomalies::ReviewedAt).timestamp_with_time_zone())
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_review_anomalies_store")
+                                .from(ReviewAnomalies::Table, ReviewAnomalies::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // The moderation queue lists a tenant's pending findings
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_review_anomalies_tenant_status")
+                        .table(ReviewAnomalies::Table)
+                        .col(ReviewAnomalies::TenantId)
+                        .col(ReviewAnomalies::Status)
+                        .to_owned(),
+                )
+                .await?;
+
+            // The scan reads every store's reviews of the last day
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_reviews_created")
+                        .table(StoreReviews::Table)
+                        .col(StoreReviews::CreatedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .if_exists()
+                        .name("idx_store_reviews_created")
+                        .table(StoreReviews::Table)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ReviewAnomalies::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ReviewAnomalies {
+        Table,
+        Id,
+        TenantId,
+        StoreId,
+        Pattern,
+        ReviewIds,
+        Score,
+        Status,
+        DetectedAt,
+        ReviewedBy,
+        ReviewedAt,
+    }
+
+    #[derive(Iden)]
+    enum StoreReviews {
+        Table,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251109_add_product_search_indexes {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251109_add_product_search_indexes"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            let conn = manager.get_connection();
+            // Trigram indexes serve ILIKE '%term%', which a B-tree can't
+            for sql in [
+                "CREATE EXTENSION IF NOT EXISTS pg_trgm",
+                "CREATE INDEX IF NOT EXISTS idx_products_name_trgm \
+                 ON products USING GIN (name gin_trgm_ops)",
+                "CREATE INDEX IF NOT EXISTS idx_products_description_trgm \
+                 ON products USING GIN (description gin_trgm_ops)",
+            ] {
+                conn.execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    sql.to_string(),
+                ))
+                .await?;
+            }
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            let conn = manager.get_connection();
+            // The extension stays; other objects may rely on it
+            for sql in [
+                "DROP INDEX IF EXISTS idx_products_name_trgm",
+                "DROP INDEX IF EXISTS idx_products_description_trgm",
+            ] {
+                conn.execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    sql.to_string(),
+                ))
+                .await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+mod m20251110_create_notification_digests {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251110_create_notification_digests"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(NotificationPreferences::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(NotificationPreferences::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationPreferences::UserId)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationPreferences::Kind)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationPreferences::Mode)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationPreferences::UpdatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // One preference per user and kind
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_notification_preferences_user_kind")
+                        .table(NotificationPreferences::Table)
+                        .col(NotificationPreferences::UserId)
+                        .col(NotificationPreferences::Kind)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_table(
+                    Table::create()
+                        .table(NotificationDigestQueue::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::UserId)
+                                .string_len(255)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::Kind)
+                                .string_len(32)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::Mode)
+                                .string_len(20)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::EventId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::EntityId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::Payload)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::QueuedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(NotificationDigestQueue::DueAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // The digest job reads the items whose window has closed
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_notification_digest_queue_due")
+                        .table(NotificationDigestQueue::Table)
+                        .col(NotificationDigestQueue::DueAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(NotificationDigestQueue::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(NotificationPreferences::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum NotificationPreferences {
+        Table,
+        Id,
+        UserId,
+        Kind,
+        Mode,
+        UpdatedAt,
+    }
+
+    #[derive(Iden)]
+    enum NotificationDigestQueue {
+        Table,
+        Id,
+        UserId,
+        Kind,
+        Mode,
+        EventId,
+        EntityId,
+        Payload,
+        QueuedAt,
+        DueAt,
+    }
+}
+
+mod m20251112_create_events {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251112_create_events"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // `seq` is a bigserial: the export cursor, since neither UUIDs nor
+            // timestamps order the log
+            manager
+                .create_table(
+                    Table::create()
+                        .table(Events::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(Events::Seq)
+                                .big_integer()
+                                .not_null()
+                                .auto_increment()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(Events::Id).uuid().not_null().unique_key())
+                        .col(ColumnDef::new(Events::EventType).string_len(64).not_null())
+                        .col(ColumnDef::new(Events::EntityId).uuid().not_null())
+                        .col(ColumnDef::new(Events::Data).json_binary().not_null())
+                        .col(
+                            ColumnDef::new(Events::OccurredAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(Table::drop().table(Events::Table).if_exists().to_owned())
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Events {
+        Table,
+        Seq,
+        Id,
+        EventType,
+        EntityId,
+        Data,
+        OccurredAt,
+    }
+}
+
+mod m20251113_create_category_attributes {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251113_create_category_attributes"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(CategoryAttributes::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(CategoryAttributes::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(CategoryAttributes::CategoryId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(CategoryAttributes::Key)
+                                .string_len(64)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(CategoryAttributes::AttributeType)
+                                .string_len(10)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(CategoryAttributes::AllowedValues).json_binary())
+                        .col(
+                            ColumnDef::new(CategoryAttributes::Required)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .col(
+                            ColumnDef::new(CategoryAttributes::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_category_attributes_category")
+                                .from(CategoryAttributes::Table, CategoryAttributes::CategoryId)
+                                .to(Categories::Table, Categories::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_category_attributes_category_key")
+                        .table(CategoryAttributes::Table)
+                        .col(CategoryAttributes::CategoryId)
+                        .col(CategoryAttributes::Key)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::Attributes)
+                                .json_binary()
+                                .not_null()
+                                .default(Expr::cust("'{}'::jsonb")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Serves the `@>` containment behind `attr.<key>=` filters
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "CREATE INDEX IF NOT EXISTS idx_products_attributes \
+                     ON products USING GIN (attributes)"
+                        .to_string(),
+                ))
+                .await?;
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    "DROP INDEX IF EXISTS idx_products_attributes".to_string(),
+                ))
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::Attributes)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(CategoryAttributes::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum CategoryAttributes {
+        Table,
+        Id,
+        CategoryId,
+        Key,
+        AttributeType,
+        AllowedValues,
+        Required,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Categories {
+        Table,
+        Id,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Attributes,
+    }
+}
+
+mod m20251114_add_product_archived_at {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251114_add_product_archived_at"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Set when the seller deletes a product; the row stays for its
+            // reviews, history and share links until a permanent delete
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::ArchivedAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::ArchivedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        ArchivedAt,
+    }
+}
+
+mod m20251115_create_profanity_terms {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251115_create_profanity_terms"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProfanityTerms::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProfanityTerms::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProfanityTerms::Phrase)
+                                .string_len(200)
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ProfanityTerms::Category)
+                                .string_len(50)
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProfanityTerms::CreatedBy)
+                                .string()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(ProfanityTerms::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Starter list, already normalized; admins manage the rest
+            let seed_sql = r#"
+                INSERT INTO profanity_terms (id, phrase, category, created_by) VALUES
+                    (gen_random_uuid(), 'fuck', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'fucking', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'shit', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'bullshit', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'merde', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'putain', 'profanity', 'seed'),
+                    (gen_random_uuid(), 'asshole', 'insult', 'seed'),
+                    (gen_random_uuid(), 'bastard', 'insult', 'seed'),
+                    (gen_random_uuid(), 'bitch', 'insult', 'seed'),
+                    (gen_random_uuid(), 'motherfucker', 'insult', 'seed'),
+                    (gen_random_uuid(), 'connard', 'insult', 'seed'),
+                    (gen_random_uuid(), 'connasse', 'insult', 'seed'),
+                    (gen_random_uuid(), 'salope', 'insult', 'seed'),
+                    (gen_random_uuid(), 'encule', 'insult', 'seed'),
+                    (gen_random_uuid(), 'batard', 'insult', 'seed'),
+                    (gen_random_uuid(), 'fils de pute', 'insult', 'seed')
+                ON CONFLICT (phrase) DO NOTHING;
+            "#;
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    seed_sql.to_string(),
+                ))
+                .await?;
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProfanityTerms::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProfanityTerms {
+        Table,
+        Id,
+        Phrase,
+        Category,
+        CreatedBy,
+        CreatedAt,
+    }
+}
+
+mod m20251116_create_store_snapshots {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251116_create_store_snapshots"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(StoreSnapshots::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(StoreSnapshots::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(StoreSnapshots::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(StoreSnapshots::Reason)
+                                .string_len(30)
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(StoreSnapshots::CreatedBy).string().null())
+                        .col(
+                            ColumnDef::new(StoreSnapshots::ProductCount)
+                                .integer()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(StoreSnapshots::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_store_snapshots_store")
+                                .from(StoreSnapshots::Table, StoreSnapshots::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Listing and capping walk a store's snapshots newest first
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_store_snapshots_store_created_at")
+                        .table(StoreSnapshots::Table)
+                        .col(StoreSnapshots::StoreId)
+                        .col(StoreSnapshots::CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            // No foreign key to products: a snapshot outlives the products
+            // deleted after it was taken
+            manager
+                .create_table(
+                    Table::create()
+                        .table(SnapshotProducts::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(SnapshotProducts::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(SnapshotProducts::SnapshotId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(SnapshotProducts::ProductId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(
+                            ColumnDef::new(SnapshotProducts::Data)
+                                .json_binary()
+                                .not_null(),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_snapshot_products_snapshot")
+                                .from(SnapshotProducts::Table, SnapshotProducts::SnapshotId)
+                                .to(StoreSnapshots::Table, StoreSnapshots::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_snapshot_products_snapshot")
+                        .table(SnapshotProducts::Table)
+                        .col(SnapshotProducts::SnapshotId)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(SnapshotProducts::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(StoreSnapshots::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum StoreSnapshots {
+        Table,
+        Id,
+        StoreId,
+        Reason,
+        CreatedBy,
+        ProductCount,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum SnapshotProducts {
+        Table,
+        Id,
+        SnapshotId,
+        ProductId,
+        Data,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+}
+
+mod m20251117_add_product_currency {
+    use super::*;
+    use crate::currency::{is_iso_4217, DEFAULT_CURRENCY};
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251117_add_product_currency"
+        }
+    }
+
+    /// The deployment's currency from `DEFAULT_CURRENCY` (or the older
+    /// `CURRENCY`) alone, so the rest of the config can't fail the migration
+    fn deployment_currency() -> Result<String, DbErr> {
+        let code = std::env::var("DEFAULT_CURRENCY")
+            .or_else(|_| std::env::var("CURRENCY"))
+            .map(|code| code.trim().to_ascii_uppercase())
+            .unwrap_or_else(|_| DEFAULT_CURRENCY.to_string());
+        if !is_iso_4217(&code) {
+            return Err(DbErr::Migration(format!(
+                "DEFAULT_CURRENCY must be an ISO 4217 currency code, got '{code}'"
+            )));
+        }
+        Ok(code)
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::Currency)
+                                .char_len(3)
+                                .not_null()
+                                .default(DEFAULT_CURRENCY),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            // Existing prices were all quoted in the deployment's currency
+            let currency = deployment_currency()?;
+            if currency != DEFAULT_CURRENCY {
+                manager
+                    .exec_stmt(
+                        Query::update()
+                            .table(Products::Table)
+                            .value(Products::Currency, currency)
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::Currency)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Currency,
+    }
+}
+
+mod m20251118_add_store_embed_enabled {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251118_add_store_embed_enabled"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Off until the seller turns the embed widget on
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::EmbedEnabled)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::EmbedEnabled)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        EmbedEnabled,
+    }
+}
+
+mod m20251119_create_referral_links {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251119_create_referral_links"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ReferralLinks::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ReferralLinks::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(ReferralLinks::Code)
+                                .string_len(16)
+                                .not_null()
+                                .unique_key(),
+                        )
+                        .col(ColumnDef::new(ReferralLinks::StoreId).uuid().not_null())
+                        .col(
+                            ColumnDef::new(ReferralLinks::TargetStoreId)
+                                .uuid()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(ReferralLinks::TargetProductId).uuid())
+                        .col(
+                            ColumnDef::new(ReferralLinks::ClickCount)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(ReferralLinks::SaleCount)
+                                .big_integer()
+                                .not_null()
+                                .default(0),
+                        )
+                        .col(
+                            ColumnDef::new(ReferralLinks::LastClickedAt).timestamp_with_time_zone(),
+                        )
+                        .col(
+                            ColumnDef::new(ReferralLinks::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_referral_links_store")
+                                .from(ReferralLinks::Table, ReferralLinks::StoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_referral_links_target_store")
+                                .from(ReferralLinks::Table, ReferralLinks::TargetStoreId)
+                                .to(Stores::Table, Stores::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        // A link to a deleted product falls back to its store
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_referral_links_target_product")
+                                .from(ReferralLinks::Table, ReferralLinks::TargetProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::SetNull),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_referral_links_store_created")
+                        .table(ReferralLinks::Table)
+                        .col(ReferralLinks::StoreId)
+                        .col(ReferralLinks::CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+
+            // Order attribution looks links up by the store sold from
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_referral_links_target_store")
+                        .table(ReferralLinks::Table)
+                        .col(ReferralLinks::TargetStoreId)
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ReferralClicks::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ReferralClicks::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ReferralClicks::LinkId).uuid().not_null())
+                        .col(ColumnDef::new(ReferralClicks::Subject).string().not_null())
+                        .col(ColumnDef::new(ReferralClicks::ClickedOn).date().not_null())
+                        .col(
+                            ColumnDef::new(ReferralClicks::CreatedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_referral_clicks_link")
+                                .from(ReferralClicks::Table, ReferralClicks::LinkId)
+                                .to(ReferralLinks::Table, ReferralLinks::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // One click per link, viewer and day; also serves attribution
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_referral_clicks_daily")
+                        .table(ReferralClicks::Table)
+                        .col(ReferralClicks::LinkId)
+                        .col(ReferralClicks::Subject)
+                        .col(ReferralClicks::ClickedOn)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+
+            // The per-viewer daily cap
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_referral_clicks_subject_day")
+                        .table(ReferralClicks::Table)
+                        .col(ReferralClicks::Subject)
+                        .col(ReferralClicks::ClickedOn)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ReferralClicks::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ReferralLinks::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ReferralLinks {
+        Table,
+        Id,
+        Code,
+        StoreId,
+        TargetStoreId,
+        TargetProductId,
+        ClickCount,
+        SaleCount,
+        LastClickedAt,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum ReferralClicks {
+        Table,
+        Id,
+        LinkId,
+        Subject,
+        ClickedOn,
+        CreatedAt,
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        Id,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251120_create_public_stats {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251120_create_public_stats"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // One row per tenant, already rounded; exact counts are never kept
+            manager
+                .create_table(
+                    Table::create()
+                        .table(PublicStats::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(PublicStats::TenantId)
+                                .string_len(32)
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(
+                            ColumnDef::new(PublicStats::Products)
+                                .big_integer()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(PublicStats::Stores).big_integer().not_null())
+                        .col(
+                            ColumnDef::new(PublicStats::CityCount)
+                                .big_integer()
+                                .not_null(),
+                        )
+                        .col(ColumnDef::new(PublicStats::Cities).json_binary().not_null())
+                        .col(
+                            ColumnDef::new(PublicStats::ComputedAt)
+                                .timestamp_with_time_zone()
+                                .not_null(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(PublicStats::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum PublicStats {
+        Table,
+        TenantId,
+        Products,
+        Stores,
+        CityCount,
+        Cities,
+        ComputedAt,
+    }
+}
+
+mod m20251121_add_product_low_stock_threshold {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251121_add_product_low_stock_threshold"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Null until the seller asks to be warned before a product runs out
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Products::LowStockThreshold).integer(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Products::Table)
+                        .drop_column(Products::LowStockThreshold)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        LowStockThreshold,
+    }
+}
+
+mod m20251122_create_product_views {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251122_create_product_views"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .create_table(
+                    Table::create()
+                        .table(ProductViews::Table)
+                        .if_not_exists()
+                        .col(
+                            ColumnDef::new(ProductViews::Id)
+                                .uuid()
+                                .not_null()
+                                .primary_key(),
+                        )
+                        .col(ColumnDef::new(ProductViews::ProductId).uuid().not_null())
+                        .col(ColumnDef::new(ProductViews::ViewerHash).string().not_null())
+                        .col(
+                            ColumnDef::new(ProductViews::ViewedAt)
+                                .timestamp_with_time_zone()
+                                .not_null()
+                                .default(Expr::cust("now()")),
+                        )
+                        .foreign_key(
+                            ForeignKey::create()
+                                .name("fk_product_views_product")
+                                .from(ProductViews::Table, ProductViews::ProductId)
+                                .to(Products::Table, Products::Id)
+                                .on_delete(ForeignKeyAction::Cascade),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // A product's views, counted over the last 7 and 30 days
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name("idx_product_views_product_viewed")
+                        .table(ProductViews::Table)
+                        .col(ProductViews::ProductId)
+                        .col(ProductViews::ViewedAt)
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(ProductViews::Table)
+                        .if_exists()
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum ProductViews {
+        Table,
+        Id,
+        ProductId,
+        ViewerHash,
+        ViewedAt,
+    }
+
+    #[derive(Iden)]
+    enum Products {
+        Table,
+        Id,
+    }
+}
+
+mod m20251123_add_auto_responses {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251123_add_auto_responses"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::AutoResponseEnabled)
+                                .boolean()
+                                .not_null()
+                                .default(false),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::AutoResponseMessage).text(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(Stores::AutoResponseTrigger).string(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductQuestions::Table)
+                        .add_column_if_not_exists(
+                            ColumnDef::new(ProductQuestions::AutoReply).text(),
+                        )
+                        .add_column_if_not_exists(
+                            ColumnDef::new(ProductQuestions::AutoRepliedAt)
+                                .timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ProductQuestions::Table)
+                        .drop_column(ProductQuestions::AutoReply)
+                        .drop_column(ProductQuestions::AutoRepliedAt)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Stores::Table)
+                        .drop_column(Stores::AutoResponseEnabled)
+                        .drop_column(Stores::AutoResponseMessage)
+                        .drop_column(Stores::AutoResponseTrigger)
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum Stores {
+        Table,
+        AutoResponseEnabled,
+        AutoResponseMessage,
+        AutoResponseTrigger,
+    }
+
+    #[derive(Iden)]
+    enum ProductQuestions {
+        Table,
+        AutoReply,
+        AutoRepliedAt,
+    }
+}
+
+mod m20251124_seal_admin_signing_keys {
+    use super::*;
+
+    pub struct Migration;
+
+    impl MigrationName for Migration {
+        fn name(&self) -> &str {
+            "m20251124_seal_admin_signing_keys"
+        }
+    }
+
+    /// Credentials stored before this kept their signing key in the clear,
+    /// so they are revoked; admins issue new ones with the field cipher set
+    async fn revoke_all(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(AdminCredentials::Table)
+                    .value(AdminCredentials::RevokedAt, Expr::cust("now()"))
+                    .and_where(Expr::col(AdminCredentials::RevokedAt).is_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Migration {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            revoke_all(manager).await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(AdminCredentials::Table)
+                        .rename_column(
+                            AdminCredentials::SecretHash,
+                            AdminCredentials::SigningKeyEncrypted,
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            // `<key id>:<base64>` outgrows the 64 characters of a hex hash
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(AdminCredentials::Table)
+                        .modify_column(
+                            ColumnDef::new(AdminCredentials::SigningKeyEncrypted)
+                                .text()
+                                .not_null(),
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+
+        async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+            // Sealed keys are no use as plain ones
+            revoke_all(manager).await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(AdminCredentials::Table)
+                        .rename_column(
+                            AdminCredentials::SigningKeyEncrypted,
+                            AdminCredentials::SecretHash,
+                        )
+                        .to_owned(),
+                )
+                .await
+        }
+    }
+
+    #[derive(Iden)]
+    enum AdminCredentials {
+        Table,
+        SecretHash,
+        SigningKeyEncrypted,
+        RevokedAt,
+    }
+}
@@ -0,0 +1,130 @@
+//! Perceptual hashes of uploaded images, so a photo copied from another
+//! listing is recognised after being re-encoded, resized or lightly edited.
+//!
+//! The hash is a 64-bit difference hash (dHash): the image is shrunk to 9×8
+//! grey pixels and each bit records whether a pixel is brighter than its
+//! right-hand neighbour. Hashes at most [`MAX_DISTANCE`] bits apart are taken
+//! to be the same picture.
+
+use image::imageops::FilterType;
+
+/// Most differing bits at which two images still count as copies
+pub const MAX_DISTANCE: u32 = 10;
+
+/// dHash of encoded image bytes; `None` when they don't decode
+pub fn dhash(data: &[u8]) -> Option<u64> {
+    let grey = image::load_from_memory(data).ok()?.to_luma8();
+    let small = image::imageops::resize(&grey, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+    Some(hash)
+}
+
+/// [`dhash`] on the blocking thread pool, so decoding never stalls the runtime
+pub async fn dhash_async(data: Vec<u8>) -> Option<u64> {
+    tokio::task::spawn_blocking(move || dhash(&data))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Number of bits two hashes differ in
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// The hash as stored in a signed BIGINT column, bit for bit
+pub fn to_column(hash: u64) -> i64 {
+    hash as i64
+}
+
+pub fn from_column(value: i64) -> u64 {
+    value as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    /// A product-photo stand-in: soft gradients with a bright block off-centre
+    fn photo() -> RgbImage {
+        RgbImage::from_fn(240, 180, |x, y| {
+            let inside = (60..150).contains(&x) && (40..120).contains(&y);
+            let base = if inside { 200 } else { (x / 2) as u8 };
+            Rgb([base, (y / 2) as u8, base / 2])
+        })
+    }
+
+    fn encode(img: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    fn hash(img: &RgbImage) -> u64 {
+        dhash(&encode(img, ImageFormat::Png)).unwrap()
+    }
+
+    #[test]
+    fn test_small_perturbations_stay_under_the_threshold() {
+        let original = photo();
+        let reference = hash(&original);
+
+        let recompressed = dhash(&encode(&original, ImageFormat::Jpeg)).unwrap();
+        let resized = hash(&image::imageops::resize(
+            &original,
+            120,
+            90,
+            FilterType::Triangle,
+        ));
+        let mut brightened = original.clone();
+        for pixel in brightened.pixels_mut() {
+            pixel.0 = pixel.0.map(|c| c.saturating_add(12));
+        }
+        let mut watermarked = original.clone();
+        for x in 200..236 {
+            for y in 160..176 {
+                watermarked.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+
+        for (label, other) in [
+            ("jpeg", recompressed),
+            ("resized", resized),
+            ("brightened", hash(&brightened)),
+            ("watermarked", hash(&watermarked)),
+        ] {
+            let d = distance(reference, other);
+            assert!(d <= MAX_DISTANCE, "{label} copy is {d} bits away");
+        }
+    }
+
+    #[test]
+    fn test_different_pictures_exceed_the_threshold() {
+        let original = photo();
+        let mirrored = image::imageops::flip_horizontal(&original);
+        let other = RgbImage::from_fn(240, 180, |x, y| {
+            let stripe = if (x / 30 + y / 30) % 2 == 0 { 230 } else { 20 };
+            Rgb([stripe, stripe, stripe])
+        });
+        for (label, img) in [("mirrored", mirrored), ("other", other)] {
+            let d = distance(hash(&original), hash(&img));
+            assert!(d > MAX_DISTANCE, "{label} picture is only {d} bits away");
+        }
+    }
+
+    #[test]
+    fn test_column_round_trip_keeps_every_bit() {
+        for hash in [0, 1, u64::MAX, 1 << 63] {
+            assert_eq!(from_column(to_column(hash)), hash);
+        }
+        assert_eq!(dhash(b"not an image"), None);
+    }
+}
@@ -0,0 +1,330 @@
+//! Prohibited-term screening for product listings.
+//!
+//! Admins keep a list of terms, each either blocking a listing outright or
+//! flagging it for review. Names and descriptions are matched against the
+//! whole list in one pass. Matching ignores case and diacritics and only
+//! counts whole words, so "Ivoire" matches "ivoire" but "arm" does not match
+//! "warm".
+//!
+//! The terms live in the database. The matcher built from them is kept in
+//! [`PROHIBITED_TERMS`]: it is loaded at startup and updated from
+//! `ProhibitedTermAdded` / `ProhibitedTermRemoved` events, so admin changes
+//! take effect without a restart.
+
+pub mod image_hash;
+pub mod review_anomalies;
+pub mod text;
+
+use crate::events::{Event, EventHandler, EventType};
+use aho_corasick::{AhoCorasick, MatchKind};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Terms the running server screens listings against
+pub static PROHIBITED_TERMS: TermFilter = TermFilter::new();
+
+/// What a match does to the listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TermAction {
+    /// Refuse the listing
+    Block,
+    /// Save the listing unpublished until an admin reviews it
+    Flag,
+}
+
+impl TermAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TermAction::Block => "block",
+            TermAction::Flag => "flag",
+        }
+    }
+}
+
+impl std::str::FromStr for TermAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(TermAction::Block),
+            "flag" => Ok(TermAction::Flag),
+            other => Err(format!("action must be block or flag, got '{other}'")),
+        }
+    }
+}
+
+/// One prohibited term as the matcher sees it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TermRule {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// Normalized form, see [`normalize`]
+    pub term: String,
+    pub category: String,
+    pub action: TermAction,
+}
+
+/// Lowercase, strip diacritics and turn punctuation into single spaces
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut pending_space = false;
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        if c.is_alphanumeric() {
+            if pending_space && !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            pending_space = false;
+            normalized.extend(c.to_lowercase());
+        } else {
+            pending_space = true;
+        }
+    }
+    normalized
+}
+
+/// Outcome of screening a listing
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Clear,
+    /// Matched flag terms only
+    Flag(Vec<TermRule>),
+    /// Matched at least one block term; the first one found
+    Block(TermRule),
+}
+
+/// Matches a fixed set of terms in one pass over the text
+pub struct TermMatcher {
+    rules: Vec<TermRule>,
+    automaton: Option<AhoCorasick>,
+}
+
+impl TermMatcher {
+    pub fn new(rules: Vec<TermRule>) -> Result<Self, String> {
+        let automaton = if rules.is_empty() {
+            None
+        } else {
+            let automaton = AhoCorasick::builder()
+                .match_kind(MatchKind::Standard)
+                .build(rules.iter().map(|rule| rule.term.as_str()))
+                .map_err(|e| format!("Failed to build term matcher: {e}"))?;
+            Some(automaton)
+        };
+        Ok(Self { rules, automaton })
+    }
+
+    pub fn rules(&self) -> &[TermRule] {
+        &self.rules
+    }
+
+    /// Rules whose term appears as whole words in any of `texts`, in rule order
+    pub fn matches(&self, texts: &[&str]) -> Vec<&TermRule> {
+        let Some(automaton) = &self.automaton else {
+            return Vec::new();
+        };
+        let mut hit = vec![false; self.rules.len()];
+        for text in texts {
+            let text = normalize(text);
+            let bytes = text.as_bytes();
+            for m in automaton.find_overlapping_iter(&text) {
+                let starts_word = m.start() == 0 || bytes[m.start() - 1] == b' ';
+                let ends_word = m.end() == bytes.len() || bytes[m.end()] == b' ';
+                if starts_word && ends_word {
+                    hit[m.pattern().as_usize()] = true;
+                }
+            }
+        }
+        self.rules
+            .iter()
+            .zip(hit)
+            .filter_map(|(rule, hit)| hit.then_some(rule))
+            .collect()
+    }
+
+    pub fn verdict(&self, texts: &[&str]) -> Verdict {
+        let matches = self.matches(texts);
+        if let Some(block) = matches.iter().find(|r| r.action == TermAction::Block) {
+            return Verdict::Block((*block).clone());
+        }
+        if matches.is_empty() {
+            Verdict::Clear
+        } else {
+            Verdict::Flag(matches.into_iter().cloned().collect())
+        }
+    }
+}
+
+/// The current matcher, swapped whole whenever the term list changes
+pub struct TermFilter {
+    matcher: RwLock<Option<Arc<TermMatcher>>>,
+}
+
+impl TermFilter {
+    pub const fn new() -> Self {
+        Self {
+            matcher: RwLock::new(None),
+        }
+    }
+
+    fn current(&self) -> Option<Arc<TermMatcher>> {
+        self.matcher.read().ok()?.clone()
+    }
+
+    fn replace(&self, rules: Vec<TermRule>) -> Result<(), String> {
+        let matcher = TermMatcher::new(rules)?;
+        let mut current = self
+            .matcher
+            .write()
+            .map_err(|_| "Term matcher lock poisoned".to_string())?;
+        *current = Some(Arc::new(matcher));
+        Ok(())
+    }
+
+    /// Replace the whole term list, e.g. when loading it at startup
+    pub fn load(&self, rules: Vec<TermRule>) -> Result<(), String> {
+        self.replace(rules)
+    }
+
+    /// Add a term, or replace the one with the same id
+    pub fn add(&self, rule: TermRule) -> Result<(), String> {
+        let mut rules = self.rules();
+        rules.retain(|r| r.id != rule.id);
+        rules.push(rule);
+        self.replace(rules)
+    }
+
+    pub fn remove(&self, id: Uuid) -> Result<(), String> {
+        let mut rules = self.rules();
+        rules.retain(|r| r.id != id);
+        self.replace(rules)
+    }
+
+    pub fn rules(&self) -> Vec<TermRule> {
+        self.current()
+            .map(|matcher| matcher.rules().to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn verdict(&self, texts: &[&str]) -> Verdict {
+        match self.current() {
+            Some(matcher) => matcher.verdict(texts),
+            None => Verdict::Clear,
+        }
+    }
+}
+
+impl Default for TermFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a [`TermFilter`] in step with admin changes to the term list
+pub struct TermFilterRefresher {
+    pub filter: &'static TermFilter,
+}
+
+#[async_trait::async_trait]
+impl EventHandler for TermFilterRefresher {
+    async fn handle_event(&self, event: &Event) -> Result<(), String> {
+        match event.event_type {
+            EventType::ProhibitedTermAdded => {
+                let rule: TermRule = serde_json::from_value(event.data.clone())
+                    .map_err(|e| format!("Malformed prohibited term event: {e}"))?;
+                self.filter.add(rule)
+            }
+            EventType::ProhibitedTermRemoved => self.filter.remove(event.entity_id),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{create_event, EventDispatcher};
+
+    fn rule(term: &str, category: &str, action: TermAction) -> TermRule {
+        TermRule {
+            id: Uuid::new_v4(),
+            term: normalize(term),
+            category: category.to_string(),
+            action,
+        }
+    }
+
+    fn matcher() -> TermMatcher {
+        TermMatcher::new(vec![
+            rule("ivoire", "wildlife", TermAction::Block),
+            rule("réplique", "counterfeit", TermAction::Flag),
+            rule("arme blanche", "weapons", TermAction::Flag),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_normalize_folds_case_diacritics_and_punctuation() {
+        assert_eq!(normalize("  Réplique—SAC, Été!"), "replique sac ete");
+        assert_eq!(normalize("Crème brûlée"), "creme brulee");
+        assert_eq!(normalize("..."), "");
+    }
+
+    #[test]
+    fn test_block_term_refuses_listing() {
+        let verdict = matcher().verdict(&["Statuette en IVOIRE", "réplique ancienne"]);
+        let Verdict::Block(rule) = verdict else {
+            panic!("expected a block, got {verdict:?}");
+        };
+        assert_eq!(rule.term, "ivoire");
+        assert_eq!(rule.category, "wildlife");
+    }
+
+    #[test]
+    fn test_flag_terms_hold_listing() {
+        let verdict = matcher().verdict(&["Sac", "Replique de sac, arme-blanche décorative"]);
+        let Verdict::Flag(rules) = verdict else {
+            panic!("expected a flag, got {verdict:?}");
+        };
+        let categories: Vec<&str> = rules.iter().map(|r| r.category.as_str()).collect();
+        assert_eq!(categories, ["counterfeit", "weapons"]);
+    }
+
+    #[test]
+    fn test_only_whole_words_match() {
+        let matcher = TermMatcher::new(vec![rule("arm", "weapons", TermAction::Block)]).unwrap();
+        assert_eq!(matcher.verdict(&["Warm scarf", "armchair"]), Verdict::Clear);
+        assert!(matches!(matcher.verdict(&["Arm rest"]), Verdict::Block(_)));
+    }
+
+    #[tokio::test]
+    async fn test_filter_refreshes_when_admin_adds_term() {
+        static FILTER: TermFilter = TermFilter::new();
+        FILTER.load(Vec::new()).unwrap();
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_handler(Box::new(TermFilterRefresher { filter: &FILTER }));
+        assert_eq!(FILTER.verdict(&["Pangolin scales"]), Verdict::Clear);
+
+        let added = rule("pangolin", "wildlife", TermAction::Block);
+        let event = create_event(
+            EventType::ProhibitedTermAdded,
+            added.id,
+            serde_json::to_value(&added).unwrap(),
+        );
+        dispatcher.dispatch(event).await.unwrap();
+        assert_eq!(
+            FILTER.verdict(&["Pangolin scales"]),
+            Verdict::Block(added.clone())
+        );
+
+        let event = create_event(
+            EventType::ProhibitedTermRemoved,
+            added.id,
+            serde_json::json!({}),
+        );
+        dispatcher.dispatch(event).await.unwrap();
+        assert_eq!(FILTER.verdict(&["Pangolin scales"]), Verdict::Clear);
+    }
+}
@@ -0,0 +1,418 @@
+//! Signs that a store's reviews are being farmed, found by a periodic scan
+//! of recent store reviews; see `jobs::scan_review_anomalies`.
+//!
+//! Three patterns are looked for in the reviews of the last
+//! [`SCAN_WINDOW_HOURS`]:
+//!
+//! | pattern           | what it means                                           |
+//! |-------------------|---------------------------------------------------------|
+//! | burst             | far more reviews than the store's usual rate            |
+//! | duplicate text    | several reviews of one store with the same comment      |
+//! | prolific reviewer | one reviewer rating many stores within the window       |
+//!
+//! Each finding carries the reviews that make it up, and each store gets a
+//! 0-100 score from its findings. Nothing is hidden here: findings go to the
+//! admin moderation queue, where an admin decides.
+
+use crate::entity::store_review::Model as ReviewModel;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Reviews this recent are scanned
+pub const SCAN_WINDOW_HOURS: i64 = 24;
+
+/// Days before the window a store's usual review rate is taken from
+pub const BASELINE_DAYS: i64 = 28;
+
+/// How unusual a pattern must be to be reported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyThresholds {
+    /// Fewest reviews of one store in the window that can be a burst
+    pub burst_min_reviews: usize,
+    /// Times the store's usual rate a burst must exceed; stores with no
+    /// history are taken to get one review a day
+    pub burst_baseline_multiple: f64,
+    /// Reviews of one store sharing a comment before it counts as copied
+    pub duplicate_min_reviews: usize,
+    /// Shorter comments, once normalised, are too generic to compare
+    pub duplicate_min_comment_len: usize,
+    /// Stores one reviewer rated within the window to be prolific
+    pub prolific_min_stores: usize,
+}
+
+impl Default for AnomalyThresholds {
+    fn default() -> Self {
+        Self {
+            burst_min_reviews: 10,
+            burst_baseline_multiple: 5.0,
+            duplicate_min_reviews: 3,
+            duplicate_min_comment_len: 12,
+            prolific_min_stores: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyPattern {
+    Burst,
+    DuplicateText,
+    ProlificReviewer,
+}
+
+impl AnomalyPattern {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyPattern::Burst => "burst",
+            AnomalyPattern::DuplicateText => "duplicate_text",
+            AnomalyPattern::ProlificReviewer => "prolific_reviewer",
+        }
+    }
+
+    /// Most a finding of this pattern adds to its store's score
+    fn weight(&self) -> f64 {
+        match self {
+            AnomalyPattern::Burst => 50.0,
+            AnomalyPattern::DuplicateText => 30.0,
+            AnomalyPattern::ProlificReviewer => 20.0,
+        }
+    }
+}
+
+/// One pattern seen in one store's reviews
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub pattern: AnomalyPattern,
+    /// The reviews that make up the pattern, in ID order
+    pub review_ids: Vec<Uuid>,
+    /// How far past its threshold the pattern is, 0.0-1.0
+    pub strength: f64,
+}
+
+/// Everything found about one store
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreFindings {
+    pub store_id: Uuid,
+    /// 0-100, from the weighted strength of each finding
+    pub score: f64,
+    pub findings: Vec<Finding>,
+}
+
+/// Scan `recent` (the reviews of the last [`SCAN_WINDOW_HOURS`]) against
+/// `baselines` (each store's review count over the [`BASELINE_DAYS`] before
+/// the window). Stores with findings only, highest score first.
+pub fn detect(
+    recent: &[ReviewModel],
+    baselines: &BTreeMap<Uuid, u64>,
+    thresholds: &AnomalyThresholds,
+) -> Vec<StoreFindings> {
+    let mut by_store: BTreeMap<Uuid, Vec<&ReviewModel>> = BTreeMap::new();
+    for review in recent {
+        by_store.entry(review.store_id).or_default().push(review);
+    }
+    let prolific = prolific_reviewers(recent, thresholds);
+
+    let mut stores: Vec<StoreFindings> = by_store
+        .into_iter()
+        .filter_map(|(store_id, reviews)| {
+            let baseline = baselines.get(&store_id).copied().unwrap_or(0);
+            let findings: Vec<Finding> = [
+                burst(&reviews, baseline, thresholds),
+                duplicate_text(&reviews, thresholds),
+                from_prolific(&reviews, &prolific),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            if findings.is_empty() {
+                return None;
+            }
+            let score = findings
+                .iter()
+                .map(|f| f.pattern.weight() * f.strength)
+                .sum::<f64>()
+                .min(100.0);
+            Some(StoreFindings {
+                store_id,
+                score: (score * 10.0).round() / 10.0,
+                findings,
+            })
+        })
+        .collect();
+    stores.sort_by(|a, b| b.score.total_cmp(&a.score));
+    stores
+}
+
+fn ids<'a>(reviews: impl IntoIterator<Item = &'a ReviewModel>) -> Vec<Uuid> {
+    let mut ids: Vec<Uuid> = reviews.into_iter().map(|review| review.id).collect();
+    ids.sort();
+    ids
+}
+
+/// The whole window, when the store got far more reviews than it usually does
+fn burst(
+    reviews: &[&ReviewModel],
+    baseline: u64,
+    thresholds: &AnomalyThresholds,
+) -> Option<Finding> {
+    let usual = (baseline as f64 / BASELINE_DAYS as f64 * SCAN_WINDOW_HOURS as f64 / 24.0).max(1.0);
+    let limit =
+        (usual * thresholds.burst_baseline_multiple).max(thresholds.burst_min_reviews as f64);
+    let count = reviews.len() as f64;
+    if count < thresholds.burst_min_reviews as f64
+        || count <= usual * thresholds.burst_baseline_multiple
+    {
+        return None;
+    }
+    Some(Finding {
+        pattern: AnomalyPattern::Burst,
+        review_ids: ids(reviews.iter().copied()),
+        strength: (count / (2.0 * limit)).min(1.0),
+    })
+}
+
+/// `Great Seller!!` and `great   seller` compare equal
+fn normalise(comment: &str) -> String {
+    comment
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reviews sharing a comment with enough others; every repeated comment
+/// counts towards one finding
+fn duplicate_text(reviews: &[&ReviewModel], thresholds: &AnomalyThresholds) -> Option<Finding> {
+    let mut by_comment: BTreeMap<String, Vec<&ReviewModel>> = BTreeMap::new();
+    for review in reviews {
+        let Some(comment) = review.comment.as_deref().map(normalise) else {
+            continue;
+        };
+        if comment.chars().count() >= thresholds.duplicate_min_comment_len {
+            by_comment.entry(comment).or_default().push(review);
+        }
+    }
+    let copies: Vec<&ReviewModel> = by_comment
+        .into_values()
+        .filter(|group| group.len() >= thresholds.duplicate_min_reviews)
+        .flatten()
+        .collect();
+    if copies.is_empty() {
+        return None;
+    }
+    Some(Finding {
+        pattern: AnomalyPattern::DuplicateText,
+        strength: (copies.len() as f64 / (2.0 * thresholds.duplicate_min_reviews as f64)).min(1.0),
+        review_ids: ids(copies),
+    })
+}
+
+/// Reviewers who rated at least `prolific_min_stores` stores in the window
+fn prolific_reviewers<'a>(
+    recent: &'a [ReviewModel],
+    thresholds: &AnomalyThresholds,
+) -> BTreeSet<&'a str> {
+    let mut stores: BTreeMap<&str, BTreeSet<Uuid>> = BTreeMap::new();
+    for review in recent {
+        stores
+            .entry(review.reviewer_id.as_str())
+            .or_default()
+            .insert(review.store_id);
+    }
+    stores
+        .into_iter()
+        .filter(|(_, stores)| stores.len() >= thresholds.prolific_min_stores)
+        .map(|(reviewer, _)| reviewer)
+        .collect()
+}
+
+/// The store's reviews by prolific reviewers; stronger the more of the
+/// store's window they make up
+fn from_prolific(reviews: &[&ReviewModel], prolific: &BTreeSet<&str>) -> Option<Finding> {
+    let theirs: Vec<&ReviewModel> = reviews
+        .iter()
+        .copied()
+        .filter(|review| prolific.contains(review.reviewer_id.as_str()))
+        .collect();
+    if theirs.is_empty() {
+        return None;
+    }
+    Some(Finding {
+        pattern: AnomalyPattern::ProlificReviewer,
+        strength: theirs.len() as f64 / reviews.len() as f64,
+        review_ids: ids(theirs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn review(store_id: Uuid, reviewer: &str, comment: Option<&str>) -> ReviewModel {
+        ReviewModel {
+            id: Uuid::new_v4(),
+            store_id,
+            reviewer_id: reviewer.to_string(),
+            rating: 5,
+            comment: comment.map(str::to_owned),
+            order_id: None,
+            is_verified: false,
+            reply: None,
+            replied_by: None,
+            replied_at: None,
+            is_held: false,
+            created_at: Utc::now() - Duration::minutes(30),
+        }
+    }
+
+    /// `count` reviews of `store_id`, each by a different buyer
+    fn reviews(store_id: Uuid, count: usize, prefix: &str) -> Vec<ReviewModel> {
+        (0..count)
+            .map(|i| review(store_id, &format!("{prefix}-{i}"), None))
+            .collect()
+    }
+
+    fn patterns(found: &[StoreFindings], store_id: Uuid) -> Vec<AnomalyPattern> {
+        found
+            .iter()
+            .find(|store| store.store_id == store_id)
+            .map(|store| store.findings.iter().map(|f| f.pattern).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_bursts_are_measured_against_the_stores_usual_rate() {
+        let thresholds = AnomalyThresholds::default();
+        let (new_store, busy_store, quiet_store) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut recent = reviews(new_store, 40, "farm");
+        // 280 reviews over the baseline is 10 a day; 30 today is only 3x that
+        recent.extend(reviews(busy_store, 30, "busy"));
+        recent.extend(reviews(quiet_store, 9, "quiet"));
+        let baselines = BTreeMap::from([(busy_store, 280)]);
+
+        let found = detect(&recent, &baselines, &thresholds);
+        assert_eq!(patterns(&found, new_store), [AnomalyPattern::Burst]);
+        assert!(patterns(&found, busy_store).is_empty());
+        // Below the minimum even with no history
+        assert!(patterns(&found, quiet_store).is_empty());
+        let farmed = &found[0];
+        assert_eq!(farmed.store_id, new_store);
+        assert_eq!(farmed.findings[0].review_ids.len(), 40);
+        assert_eq!(farmed.score, 50.0);
+
+        // Just past the threshold scores lower than far past it
+        let recent = reviews(new_store, 11, "farm");
+        let found = detect(&recent, &BTreeMap::new(), &thresholds);
+        assert!(found[0].score < 50.0 && found[0].score > 0.0, "{found:?}");
+    }
+
+    #[test]
+    fn test_copied_comments_are_found_despite_case_and_punctuation() {
+        let thresholds = AnomalyThresholds::default();
+        let store = Uuid::new_v4();
+        let recent = vec![
+            review(store, "a", Some("Best seller in Douala!!")),
+            review(store, "b", Some("best seller in   douala")),
+            review(store, "c", Some("BEST SELLER IN DOUALA.")),
+            review(store, "d", Some("Fast delivery, thanks")),
+            // Too short to say anything about
+            review(store, "e", Some("Great!")),
+            review(store, "f", Some("great")),
+            review(store, "g", Some("GREAT")),
+        ];
+        let found = detect(&recent, &BTreeMap::new(), &thresholds);
+        assert_eq!(patterns(&found, store), [AnomalyPattern::DuplicateText]);
+        let copied = &found[0].findings[0].review_ids;
+        assert_eq!(*copied, ids(&recent[..3]));
+
+        // Two copies aren't enough; nor are copies spread over stores
+        let other = Uuid::new_v4();
+        let recent = vec![
+            review(store, "a", Some("Best seller in Douala")),
+            review(store, "b", Some("Best seller in Douala")),
+            review(other, "c", Some("Best seller in Douala")),
+        ];
+        assert!(detect(&recent, &BTreeMap::new(), &thresholds).is_empty());
+    }
+
+    #[test]
+    fn test_prolific_reviewers_are_flagged_on_every_store_they_rated() {
+        let thresholds = AnomalyThresholds::default();
+        let stores: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let mut recent: Vec<ReviewModel> = stores
+            .iter()
+            .map(|store| review(*store, "farm-1", None))
+            .collect();
+        // An ordinary buyer reviewing four stores, and a store's real buyer
+        recent.extend(
+            stores[..4]
+                .iter()
+                .map(|store| review(*store, "buyer-1", None)),
+        );
+        recent.push(review(stores[0], "buyer-2", None));
+
+        let found = detect(&recent, &BTreeMap::new(), &thresholds);
+        assert_eq!(found.len(), 5);
+        for store in &stores {
+            assert_eq!(patterns(&found, *store), [AnomalyPattern::ProlificReviewer]);
+        }
+        // The last store's only review is the farm's; the first has two more
+        assert_eq!(found[0].store_id, stores[4]);
+        assert_eq!(found[0].score, 20.0);
+        let first = found.iter().find(|s| s.store_id == stores[0]).unwrap();
+        assert!((first.score - 20.0 / 3.0).abs() < 0.1, "{}", first.score);
+        assert_eq!(first.findings[0].review_ids, ids(&recent[..1]));
+    }
+
+    #[test]
+    fn test_patterns_add_up_to_at_most_100() {
+        let thresholds = AnomalyThresholds::default();
+        let store = Uuid::new_v4();
+        let mut recent: Vec<ReviewModel> = (0..40)
+            .map(|i| {
+                review(
+                    store,
+                    &format!("farm-{i}"),
+                    Some("Excellent store, fast delivery"),
+                )
+            })
+            .collect();
+        // One of them also rated four other stores
+        for _ in 0..4 {
+            recent.push(review(Uuid::new_v4(), "farm-0", None));
+        }
+        let found = detect(&recent, &BTreeMap::new(), &thresholds);
+        let farmed = found.iter().find(|s| s.store_id == store).unwrap();
+        assert_eq!(
+            patterns(&found, store),
+            [
+                AnomalyPattern::Burst,
+                AnomalyPattern::DuplicateText,
+                AnomalyPattern::ProlificReviewer
+            ]
+        );
+        assert!(
+            farmed.score <= 100.0 && farmed.score > 80.0,
+            "{}",
+            farmed.score
+        );
+        assert_eq!(found[0].store_id, store);
+    }
+
+    #[test]
+    fn test_quiet_day_finds_nothing() {
+        let store = Uuid::new_v4();
+        let recent = vec![
+            review(store, "buyer-1", Some("Lovely fabrics")),
+            review(store, "buyer-2", Some("Delivery took a week")),
+        ];
+        assert!(detect(&recent, &BTreeMap::new(), &AnomalyThresholds::default()).is_empty());
+        assert!(detect(&[], &BTreeMap::new(), &AnomalyThresholds::default()).is_empty());
+    }
+}
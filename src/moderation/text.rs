@@ -0,0 +1,530 @@
+//! Profanity screening for user-written text: store profiles, reviews and
+//! product Q&A.
+//!
+//! Admins keep a list of words and phrases, each with a category. Every
+//! [`TextField`] is screened in one of two [`TextMode`]s: `reject` refuses
+//! the text, naming the category but never the matched phrase, and `mask`
+//! stars the phrase out before the text is saved. Matching works on
+//! [`normalize_text`], which on top of [`super::normalize`] reads basic leetspeak,
+//! so "$h1t" matches "shit".
+//!
+//! Like [`super::PROHIBITED_TERMS`], the matcher in [`TEXT_MODERATION`] is
+//! loaded at startup and updated from `ProfanityTermAdded` /
+//! `ProfanityTermRemoved` events, so admin changes apply without a restart.
+//! Handlers go through `crate::api::moderation::moderate_text` so every
+//! endpoint applies the same rules.
+
+use crate::events::{Event, EventHandler, EventType};
+use aho_corasick::{AhoCorasick, MatchKind};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Profanity list and per-field modes the running server applies
+pub static TEXT_MODERATION: TextModeration = TextModeration::new();
+
+/// User-written text that is screened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextField {
+    StoreName,
+    StoreDescription,
+    ReviewComment,
+    ReviewReply,
+    Question,
+    Answer,
+}
+
+impl TextField {
+    pub const ALL: [TextField; 6] = [
+        TextField::StoreName,
+        TextField::StoreDescription,
+        TextField::ReviewComment,
+        TextField::ReviewReply,
+        TextField::Question,
+        TextField::Answer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextField::StoreName => "store_name",
+            TextField::StoreDescription => "store_description",
+            TextField::ReviewComment => "review_comment",
+            TextField::ReviewReply => "review_reply",
+            TextField::Question => "question",
+            TextField::Answer => "answer",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.as_str() == s)
+    }
+
+    /// How the field is screened unless configured otherwise: store
+    /// profiles are refused, conversation is masked
+    pub fn default_mode(&self) -> TextMode {
+        match self {
+            TextField::StoreName | TextField::StoreDescription => TextMode::Reject,
+            _ => TextMode::Mask,
+        }
+    }
+}
+
+/// What a match does to the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextMode {
+    /// Refuse the text with a 422
+    Reject,
+    /// Replace the matched phrase with asterisks and keep the rest
+    Mask,
+}
+
+impl std::str::FromStr for TextMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(TextMode::Reject),
+            "mask" => Ok(TextMode::Mask),
+            other => Err(format!("mode must be reject or mask, got '{other}'")),
+        }
+    }
+}
+
+/// Mode of every field, from `TEXT_MODERATION_MODES`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TextModes(BTreeMap<TextField, TextMode>);
+
+impl Default for TextModes {
+    fn default() -> Self {
+        Self(
+            TextField::ALL
+                .into_iter()
+                .map(|field| (field, field.default_mode()))
+                .collect(),
+        )
+    }
+}
+
+impl TextModes {
+    pub fn get(&self, field: TextField) -> TextMode {
+        self.0
+            .get(&field)
+            .copied()
+            .unwrap_or_else(|| field.default_mode())
+    }
+
+    pub fn set(&mut self, field: TextField, mode: TextMode) {
+        self.0.insert(field, mode);
+    }
+}
+
+impl std::str::FromStr for TextModes {
+    type Err = String;
+
+    /// Comma-separated `field=mode` overrides of the defaults, e.g.
+    /// `review_comment=reject,store_description=mask`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modes = Self::default();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (field, mode) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}' must be field=mode"))?;
+            let field = TextField::parse(field.trim()).ok_or_else(|| {
+                let fields: Vec<&str> = TextField::ALL.iter().map(|f| f.as_str()).collect();
+                format!(
+                    "unknown field '{}', expected one of {}",
+                    field.trim(),
+                    fields.join(", ")
+                )
+            })?;
+            modes.set(field, mode.trim().parse()?);
+        }
+        Ok(modes)
+    }
+}
+
+/// One listed phrase as the matcher sees it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProfanityRule {
+    #[schema(value_type = String, format = "uuid")]
+    pub id: Uuid,
+    /// Normalized form, see [`normalize_text`]
+    pub phrase: String,
+    pub category: String,
+}
+
+/// Letter a leetspeak digit or symbol stands for
+fn leet(c: char) -> Option<char> {
+    Some(match c {
+        '0' => 'o',
+        '1' | '!' | '|' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        '8' => 'b',
+        '9' => 'g',
+        _ => return None,
+    })
+}
+
+/// [`normalize_text`], with the byte range in `text` each output byte came from
+fn fold(text: &str) -> (String, Vec<Range<usize>>) {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut folded = String::with_capacity(text.len());
+    let mut origins = Vec::with_capacity(text.len());
+    let mut pending_space = false;
+    for (i, &(start, c)) in chars.iter().enumerate() {
+        let origin = start..start + c.len_utf8();
+        // A symbol only reads as a letter inside or at the start of a word,
+        // so the "!" of "shit!" stays punctuation
+        let letter = match leet(c) {
+            Some(letter) if c.is_ascii_digit() => letter,
+            Some(letter)
+                if chars[i + 1..]
+                    .iter()
+                    .find(|(_, next)| leet(*next).is_none() || next.is_ascii_digit())
+                    .is_some_and(|(_, next)| next.is_alphanumeric()) =>
+            {
+                letter
+            }
+            _ => c,
+        };
+        for c in letter.nfd().filter(|c| !is_combining_mark(*c)) {
+            if !c.is_alphanumeric() {
+                pending_space = true;
+                continue;
+            }
+            if pending_space && !folded.is_empty() {
+                folded.push(' ');
+                origins.push(origin.clone());
+            }
+            pending_space = false;
+            for lower in c.to_lowercase() {
+                folded.push(lower);
+                origins.extend(std::iter::repeat_n(origin.clone(), lower.len_utf8()));
+            }
+        }
+    }
+    (folded, origins)
+}
+
+/// [`normalize`](super::normalize), reading digits and `@ $ ! |` inside words as the letters
+/// they stand in for
+pub fn normalize_text(text: &str) -> String {
+    fold(text).0
+}
+
+/// Why text was refused; deliberately without the matched phrase
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRejection {
+    pub field: TextField,
+    pub category: String,
+}
+
+/// Matches a fixed set of phrases in one pass over the text
+pub struct ProfanityMatcher {
+    rules: Vec<ProfanityRule>,
+    automaton: Option<AhoCorasick>,
+}
+
+impl ProfanityMatcher {
+    pub fn new(rules: Vec<ProfanityRule>) -> Result<Self, String> {
+        let automaton = if rules.is_empty() {
+            None
+        } else {
+            let automaton = AhoCorasick::builder()
+                .match_kind(MatchKind::Standard)
+                .build(rules.iter().map(|rule| rule.phrase.as_str()))
+                .map_err(|e| format!("Failed to build profanity matcher: {e}"))?;
+            Some(automaton)
+        };
+        Ok(Self { rules, automaton })
+    }
+
+    pub fn rules(&self) -> &[ProfanityRule] {
+        &self.rules
+    }
+
+    /// Whole-word matches in `text`: the rule and the bytes of `text` it covers
+    fn find(&self, text: &str) -> Vec<(&ProfanityRule, Range<usize>)> {
+        let Some(automaton) = &self.automaton else {
+            return Vec::new();
+        };
+        let (folded, origins) = fold(text);
+        let bytes = folded.as_bytes();
+        automaton
+            .find_overlapping_iter(&folded)
+            .filter(|m| {
+                let starts_word = m.start() == 0 || bytes[m.start() - 1] == b' ';
+                let ends_word = m.end() == bytes.len() || bytes[m.end()] == b' ';
+                starts_word && ends_word
+            })
+            .map(|m| {
+                let span = origins[m.start()].start..origins[m.end() - 1].end;
+                (&self.rules[m.pattern().as_usize()], span)
+            })
+            .collect()
+    }
+
+    /// `text` as it may be saved in `field`: unchanged, masked, or refused
+    pub fn screen(
+        &self,
+        modes: &TextModes,
+        field: TextField,
+        text: &str,
+    ) -> Result<String, TextRejection> {
+        let matches = self.find(text);
+        let Some((first, _)) = matches.first() else {
+            return Ok(text.to_string());
+        };
+        if modes.get(field) == TextMode::Reject {
+            return Err(TextRejection {
+                field,
+                category: first.category.clone(),
+            });
+        }
+        let mut masked = String::with_capacity(text.len());
+        for (i, c) in text.char_indices() {
+            let hidden = !c.is_whitespace() && matches.iter().any(|(_, span)| span.contains(&i));
+            masked.push(if hidden { '*' } else { c });
+        }
+        Ok(masked)
+    }
+}
+
+/// The current matcher and modes; the matcher is swapped whole whenever the
+/// list changes
+pub struct TextModeration {
+    matcher: RwLock<Option<Arc<ProfanityMatcher>>>,
+    modes: RwLock<Option<TextModes>>,
+}
+
+impl TextModeration {
+    pub const fn new() -> Self {
+        Self {
+            matcher: RwLock::new(None),
+            modes: RwLock::new(None),
+        }
+    }
+
+    fn current(&self) -> Option<Arc<ProfanityMatcher>> {
+        self.matcher.read().ok()?.clone()
+    }
+
+    fn replace(&self, rules: Vec<ProfanityRule>) -> Result<(), String> {
+        let matcher = ProfanityMatcher::new(rules)?;
+        let mut current = self
+            .matcher
+            .write()
+            .map_err(|_| "Profanity matcher lock poisoned".to_string())?;
+        *current = Some(Arc::new(matcher));
+        Ok(())
+    }
+
+    /// Set the per-field modes, e.g. from `Config::text_moderation`
+    pub fn configure(&self, modes: TextModes) {
+        if let Ok(mut current) = self.modes.write() {
+            *current = Some(modes);
+        }
+    }
+
+    fn modes(&self) -> TextModes {
+        self.modes
+            .read()
+            .ok()
+            .and_then(|modes| modes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replace the whole list, e.g. when loading it at startup
+    pub fn load(&self, rules: Vec<ProfanityRule>) -> Result<(), String> {
+        self.replace(rules)
+    }
+
+    /// Add a phrase, or replace the one with the same id
+    pub fn add(&self, rule: ProfanityRule) -> Result<(), String> {
+        let mut rules = self.rules();
+        rules.retain(|r| r.id != rule.id);
+        rules.push(rule);
+        self.replace(rules)
+    }
+
+    pub fn remove(&self, id: Uuid) -> Result<(), String> {
+        let mut rules = self.rules();
+        rules.retain(|r| r.id != id);
+        self.replace(rules)
+    }
+
+    pub fn rules(&self) -> Vec<ProfanityRule> {
+        self.current()
+            .map(|matcher| matcher.rules().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// See [`ProfanityMatcher::screen`]
+    pub fn screen(&self, field: TextField, text: &str) -> Result<String, TextRejection> {
+        match self.current() {
+            Some(matcher) => matcher.screen(&self.modes(), field, text),
+            None => Ok(text.to_string()),
+        }
+    }
+}
+
+impl Default for TextModeration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a [`TextModeration`] in step with admin changes to the list
+pub struct TextModerationRefresher {
+    pub moderation: &'static TextModeration,
+}
+
+#[async_trait::async_trait]
+impl EventHandler for TextModerationRefresher {
+    async fn handle_event(&self, event: &Event) -> Result<(), String> {
+        match event.event_type {
+            EventType::ProfanityTermAdded => {
+                let rule: ProfanityRule = serde_json::from_value(event.data.clone())
+                    .map_err(|e| format!("Malformed profanity term event: {e}"))?;
+                self.moderation.add(rule)
+            }
+            EventType::ProfanityTermRemoved => self.moderation.remove(event.entity_id),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{create_event, EventDispatcher};
+
+    fn rule(phrase: &str, category: &str) -> ProfanityRule {
+        ProfanityRule {
+            id: Uuid::new_v4(),
+            phrase: normalize_text(phrase),
+            category: category.to_string(),
+        }
+    }
+
+    fn matcher() -> ProfanityMatcher {
+        ProfanityMatcher::new(vec![
+            rule("shit", "profanity"),
+            rule("connard", "insult"),
+            rule("fils de pute", "insult"),
+        ])
+        .unwrap()
+    }
+
+    fn masking() -> TextModes {
+        let mut modes = TextModes::default();
+        modes.set(TextField::ReviewComment, TextMode::Mask);
+        modes
+    }
+
+    #[test]
+    fn test_normalize_text_reads_leetspeak_inside_words() {
+        assert_eq!(normalize_text("$H1T"), "shit");
+        assert_eq!(normalize_text("sh!t, Connàrd"), "shit connard");
+        assert_eq!(normalize_text("a$$hole"), "asshole");
+        assert_eq!(normalize_text("Shit! 100 @ home"), "shit ioo home");
+        assert_eq!(normalize_text("5 stars!!"), "s stars");
+    }
+
+    #[test]
+    fn test_mask_stars_out_matches_whatever_their_spelling() {
+        let modes = masking();
+        let screen = |text| matcher().screen(&modes, TextField::ReviewComment, text);
+        assert_eq!(screen("Total $h1t, avoid"), Ok("Total ****, avoid".into()));
+        assert_eq!(
+            screen("Le vendeur: CONNÀRD!"),
+            Ok("Le vendeur: *******!".into())
+        );
+        assert_eq!(
+            screen("un fils de-pute"),
+            Ok("un **** *******".into()),
+            "phrases match across punctuation and keep their spaces"
+        );
+        assert_eq!(screen("Shitake mushrooms"), Ok("Shitake mushrooms".into()));
+    }
+
+    #[test]
+    fn test_each_field_uses_its_mode() {
+        let matcher = matcher();
+        let mut modes: TextModes = "review_comment=reject,store_description=mask"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            matcher.screen(&modes, TextField::ReviewComment, "sh1t service"),
+            Err(TextRejection {
+                field: TextField::ReviewComment,
+                category: "profanity".into()
+            })
+        );
+        assert_eq!(
+            matcher.screen(&modes, TextField::StoreDescription, "Pas de connard ici"),
+            Ok("Pas de ******* ici".into())
+        );
+        // Left at its default
+        assert!(matcher
+            .screen(&modes, TextField::StoreName, "Shit shop")
+            .is_err());
+        assert_eq!(
+            matcher.screen(&modes, TextField::Answer, "No shit"),
+            Ok("No ****".into())
+        );
+
+        modes.set(TextField::Answer, TextMode::Reject);
+        assert!(matcher
+            .screen(&modes, TextField::Answer, "No shit")
+            .is_err());
+        assert!("answer=delete".parse::<TextModes>().is_err());
+        assert!("title=mask".parse::<TextModes>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_changes_apply_without_restart() {
+        static MODERATION: TextModeration = TextModeration::new();
+        MODERATION.load(Vec::new()).unwrap();
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_handler(Box::new(TextModerationRefresher {
+            moderation: &MODERATION,
+        }));
+        assert_eq!(
+            MODERATION.screen(TextField::Question, "Is it a scam?"),
+            Ok("Is it a scam?".into())
+        );
+
+        let added = rule("scam", "fraud");
+        let event = create_event(
+            EventType::ProfanityTermAdded,
+            added.id,
+            serde_json::to_value(&added).unwrap(),
+        );
+        dispatcher.dispatch(event).await.unwrap();
+        assert_eq!(
+            MODERATION.screen(TextField::Question, "Is it a scam?"),
+            Ok("Is it a ****?".into())
+        );
+
+        let event = create_event(
+            EventType::ProfanityTermRemoved,
+            added.id,
+            serde_json::json!({}),
+        );
+        dispatcher.dispatch(event).await.unwrap();
+        assert_eq!(
+            MODERATION.screen(TextField::Question, "Is it a scam?"),
+            Ok("Is it a scam?".into())
+        );
+    }
+}
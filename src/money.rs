@@ -0,0 +1,330 @@
+//! Prices for display.
+//!
+//! Responses carry each price twice: the bare number, kept while clients
+//! move over, and next to it a `<field>_money` object with the amount
+//! rounded to the currency's minor unit, the currency, and the amount
+//! formatted for the caller's locale. The locale comes from `?locale=`,
+//! then `Accept-Language`, and defaults to English.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts},
+};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use utoipa::{IntoParams, ToSchema};
+
+/// Decimal places amounts are rounded through before their minor unit, so
+/// 2.675 rounds as written rather than as the 2.67499… it is stored as
+const ROUNDING_PLACES: u32 = 10;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LocaleParams {
+    /// Language to format prices for, e.g. `fr` or `en-NG`; overrides
+    /// `Accept-Language`. English when neither names a supported one.
+    pub locale: Option<String>,
+}
+
+/// Languages prices are formatted for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+impl Locale {
+    /// `fr`, `fr-CM` or `FR_cm` are French; unknown languages are `None`
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        if language.eq_ignore_ascii_case("en") {
+            Some(Locale::En)
+        } else if language.eq_ignore_ascii_case("fr") {
+            Some(Locale::Fr)
+        } else {
+            None
+        }
+    }
+
+    /// The supported language the `Accept-Language` header weighs highest;
+    /// ties go to the one listed first
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let Some(locale) = parts.next().and_then(Locale::parse) else {
+                continue;
+            };
+            let weight = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if weight > 0.0 && best.is_none_or(|(_, top)| weight > top) {
+                best = Some((locale, weight));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+
+    fn thousands_separator(self) -> char {
+        match self {
+            Locale::En => ',',
+            // Narrow no-break space, so an amount never wraps
+            Locale::Fr => '\u{202F}',
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::En => '.',
+            Locale::Fr => ',',
+        }
+    }
+}
+
+/// `?locale=`, else `Accept-Language`, else English; never rejects
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Locale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let requested = Query::<LocaleParams>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Query(params)| params.locale)
+            .and_then(|tag| Locale::parse(&tag));
+        let accepted = || {
+            parts
+                .headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Locale::from_accept_language)
+        };
+        Ok(requested.or_else(accepted).unwrap_or_default())
+    }
+}
+
+/// Digits after the decimal point in `currency`, per ISO 4217
+pub fn minor_units(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// `amount` in minor units of a currency with `places` of them, half away
+/// from zero
+fn to_minor(amount: f64, places: u32) -> i128 {
+    let scaled = (amount.abs() * 10f64.powi(ROUNDING_PLACES as i32)).round() as i128;
+    let unit = 10i128.pow(ROUNDING_PLACES - places);
+    let minor = (scaled + unit / 2) / unit;
+    if amount < 0.0 {
+        -minor
+    } else {
+        minor
+    }
+}
+
+/// `amount` rounded to the minor unit of `currency`
+pub fn round(amount: f64, currency: &str) -> f64 {
+    let places = minor_units(currency);
+    to_minor(amount, places) as f64 / 10f64.powi(places as i32)
+}
+
+/// How `currency` is written in `locale`; its code when it has no symbol
+/// of its own
+fn symbol(currency: &str, locale: Locale) -> &str {
+    match (currency, locale) {
+        ("USD", Locale::En) => "$",
+        ("USD", Locale::Fr) => "$US",
+        ("EUR", _) => "€",
+        ("GBP", _) => "£",
+        ("NGN", _) => "₦",
+        ("XAF", _) => "FCFA",
+        ("XOF", _) => "F CFA",
+        _ => currency,
+    }
+}
+
+/// `amount` of `currency` written for `locale`: `$1,234.50` and
+/// `5,000 FCFA` in English, `1 234,50 $US` and `5 000 FCFA` in French
+pub fn format(amount: f64, currency: &str, locale: Locale) -> String {
+    let places = minor_units(currency);
+    let minor = to_minor(amount, places);
+    let unit = 10u128.pow(places);
+    let (whole, fraction) = (minor.unsigned_abs() / unit, minor.unsigned_abs() % unit);
+
+    let digits = whole.to_string();
+    let mut number = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            number.push(locale.thousands_separator());
+        }
+        number.push(digit);
+    }
+    if places > 0 {
+        number.push(locale.decimal_separator());
+        number.push_str(&format!("{fraction:0width$}", width = places as usize));
+    }
+
+    let sign = if minor < 0 { "-" } else { "" };
+    let symbol = symbol(currency, locale);
+    // English puts a sign-like symbol before the amount and a word after it
+    if locale == Locale::En && !symbol.chars().any(char::is_alphabetic) {
+        format!("{sign}{symbol}{number}")
+    } else {
+        format!("{sign}{number}\u{00A0}{symbol}")
+    }
+}
+
+/// A price with its currency, ready to show
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Money {
+    /// Rounded to the currency's minor unit
+    pub amount: f64,
+    /// ISO 4217 code
+    pub currency: String,
+    /// For display in the requested locale
+    pub formatted: String,
+}
+
+impl Money {
+    pub fn new(amount: f64, currency: &str, locale: Locale) -> Self {
+        Self {
+            amount: round(amount, currency),
+            currency: currency.to_string(),
+            formatted: format(amount, currency, locale),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    const NBSP: char = '\u{00A0}';
+    const NNBSP: char = '\u{202F}';
+
+    #[test]
+    fn test_minor_units_follow_iso_4217() {
+        assert_eq!(minor_units("XAF"), 0);
+        assert_eq!(minor_units("XOF"), 0);
+        assert_eq!(minor_units("NGN"), 2);
+        assert_eq!(minor_units("USD"), 2);
+        assert_eq!(minor_units("EUR"), 2);
+        assert_eq!(minor_units("KWD"), 3);
+    }
+
+    #[test]
+    fn test_rounding_is_half_away_from_zero_as_written() {
+        for (amount, currency, rounded) in [
+            // Stored as 2.67499… and 1.00499…, rounded as typed
+            (2.675, "USD", 2.68),
+            (1.005, "EUR", 1.01),
+            (-2.675, "USD", -2.68),
+            (1.004, "EUR", 1.0),
+            (12500.5, "XAF", 12501.0),
+            (12499.49, "XAF", 12499.0),
+            (-0.5, "XAF", -1.0),
+            (1999.995, "NGN", 2000.0),
+            (1.0005, "KWD", 1.001),
+            (100_000_000.0, "XAF", 100_000_000.0),
+        ] {
+            assert_eq!(round(amount, currency), rounded, "{amount} {currency}");
+        }
+    }
+
+    #[test]
+    fn test_english_formatting() {
+        for (amount, currency, formatted) in [
+            (5000.0, "XAF", format!("5,000{NBSP}FCFA")),
+            (12500.5, "XAF", format!("12,501{NBSP}FCFA")),
+            (-2500.0, "XAF", format!("-2,500{NBSP}FCFA")),
+            (100_000_000.0, "XAF", format!("100,000,000{NBSP}FCFA")),
+            (1_234_567.891, "NGN", "₦1,234,567.89".to_string()),
+            (1234.5, "USD", "$1,234.50".to_string()),
+            (999.995, "USD", "$1,000.00".to_string()),
+            (0.0, "USD", "$0.00".to_string()),
+            (-0.25, "USD", "-$0.25".to_string()),
+            (12.0, "EUR", "€12.00".to_string()),
+            (100.0, "KES", format!("100.00{NBSP}KES")),
+        ] {
+            assert_eq!(format(amount, currency, Locale::En), formatted);
+        }
+    }
+
+    #[test]
+    fn test_french_formatting() {
+        for (amount, currency, formatted) in [
+            (5000.0, "XAF", format!("5{NNBSP}000{NBSP}FCFA")),
+            (999.0, "XAF", format!("999{NBSP}FCFA")),
+            (-2500.0, "XAF", format!("-2{NNBSP}500{NBSP}FCFA")),
+            (
+                1_234_567.891,
+                "NGN",
+                format!("1{NNBSP}234{NNBSP}567,89{NBSP}₦"),
+            ),
+            (1234.5, "USD", format!("1{NNBSP}234,50{NBSP}$US")),
+            (12.0, "EUR", format!("12,00{NBSP}€")),
+            (1.005, "EUR", format!("1,01{NBSP}€")),
+        ] {
+            assert_eq!(format(amount, currency, Locale::Fr), formatted);
+        }
+    }
+
+    #[test]
+    fn test_money_rounds_the_amount_it_formats() {
+        assert_eq!(
+            Money::new(2.675, "USD", Locale::En),
+            Money {
+                amount: 2.68,
+                currency: "USD".to_string(),
+                formatted: "$2.68".to_string(),
+            }
+        );
+        assert_eq!(Money::new(4999.5, "XAF", Locale::Fr).amount, 5000.0);
+    }
+
+    #[test]
+    fn test_locale_tags_and_accept_language() {
+        assert_eq!(Locale::parse("fr"), Some(Locale::Fr));
+        assert_eq!(Locale::parse("FR_cm"), Some(Locale::Fr));
+        assert_eq!(Locale::parse("en-NG"), Some(Locale::En));
+        assert_eq!(Locale::parse("de"), None);
+        assert_eq!(Locale::parse(""), None);
+
+        for (header, locale) in [
+            ("fr-CM,fr;q=0.9,en;q=0.8", Some(Locale::Fr)),
+            ("de-DE,en;q=0.5,fr;q=0.4", Some(Locale::En)),
+            ("en;q=0.2, fr;q=0.7", Some(Locale::Fr)),
+            ("en;q=0,fr;q=0.1", Some(Locale::Fr)),
+            ("en, fr", Some(Locale::En)),
+            ("de, *;q=0.5", None),
+            ("", None),
+        ] {
+            assert_eq!(Locale::from_accept_language(header), locale, "{header}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_locale_overrides_the_header() {
+        let locale = |uri: &str, accept: &str| {
+            let (mut parts, _) = Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT_LANGUAGE, accept)
+                .body(())
+                .unwrap()
+                .into_parts();
+            async move { Locale::from_request_parts(&mut parts, &()).await.unwrap() }
+        };
+        assert_eq!(locale("/products?locale=fr", "en").await, Locale::Fr);
+        assert_eq!(locale("/products?locale=de", "fr-CM").await, Locale::Fr);
+        assert_eq!(locale("/products", "fr-CM").await, Locale::Fr);
+        assert_eq!(locale("/products", "de").await, Locale::En);
+    }
+}
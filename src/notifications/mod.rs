@@ -0,0 +1,554 @@
+//! Per-user delivery of notifications: right away, or rolled into an hourly
+//! or daily digest.
+//!
+//! [`NotificationRouter`] listens for the events addressed to one user (a
+//! new store review for its owner, an answer for the asker, ...). Each one
+//! is sent on as a `NotificationSent` event, or queued in
+//! `notification_digest_queue` when the recipient prefers a digest for that
+//! kind. [`send_due_digests`] later turns every closed window's queue into
+//! one `NotificationDigestSent` event per user, e.g. "5 new questions, 3 new
+//! store reviews today". The mode is read when an item is queued, so a
+//! preference change applies to the items that come after it.
+
+use crate::db::notifications::{NotificationDigestQueue, NotificationPreferences};
+use crate::db::stores::Store;
+use crate::entity::notification_digest_item::Model as DigestItem;
+use crate::events::{create_event, Event, EventDispatcher, EventHandler, EventType};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Weak;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// What a notification is about; preferences are set per kind
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A buyer reviewed the user's store
+    NewStoreReview,
+    /// A store owner replied to the user's review
+    StoreReviewReply,
+    /// A buyer asked a question on one of the user's products
+    NewQuestion,
+    /// A store owner answered the user's question
+    QuestionAnswered,
+    /// Questions on the user's products are waiting for an answer
+    UnansweredQuestions,
+    /// A product the user watches dropped in price
+    PriceDrop,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 6] = [
+        NotificationKind::NewStoreReview,
+        NotificationKind::StoreReviewReply,
+        NotificationKind::NewQuestion,
+        NotificationKind::QuestionAnswered,
+        NotificationKind::UnansweredQuestions,
+        NotificationKind::PriceDrop,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationKind::NewStoreReview => "new_store_review",
+            NotificationKind::StoreReviewReply => "store_review_reply",
+            NotificationKind::NewQuestion => "new_question",
+            NotificationKind::QuestionAnswered => "question_answered",
+            NotificationKind::UnansweredQuestions => "unanswered_questions",
+            NotificationKind::PriceDrop => "price_drop",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    /// The kind of notification `event` raises, if it raises one
+    pub fn of(event: &EventType) -> Option<Self> {
+        match event {
+            EventType::StoreReviewPosted => Some(NotificationKind::NewStoreReview),
+            EventType::StoreReviewReplied => Some(NotificationKind::StoreReviewReply),
+            EventType::ProductQuestionAsked => Some(NotificationKind::NewQuestion),
+            EventType::ProductQuestionAnswered => Some(NotificationKind::QuestionAnswered),
+            EventType::ProductQuestionsUnanswered => Some(NotificationKind::UnansweredQuestions),
+            EventType::ProductPriceDropped => Some(NotificationKind::PriceDrop),
+            _ => None,
+        }
+    }
+
+    /// How a digest counts `count` of this kind, e.g. "3 new questions"
+    fn describe(self, count: u64) -> String {
+        let (one, many) = match self {
+            NotificationKind::NewStoreReview => ("new store review", "new store reviews"),
+            NotificationKind::StoreReviewReply => {
+                ("reply to your review", "replies to your reviews")
+            }
+            NotificationKind::NewQuestion => ("new question", "new questions"),
+            NotificationKind::QuestionAnswered => {
+                ("answer to your question", "answers to your questions")
+            }
+            NotificationKind::UnansweredQuestions => (
+                "unanswered question reminder",
+                "unanswered question reminders",
+            ),
+            NotificationKind::PriceDrop => ("price drop", "price drops"),
+        };
+        format!("{count} {}", if count == 1 { one } else { many })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    #[default]
+    Immediate,
+    /// Collected and sent once an hour, on the hour (UTC)
+    HourlyDigest,
+    /// Collected and sent once a day, at midnight UTC
+    DailyDigest,
+}
+
+impl DeliveryMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryMode::Immediate => "immediate",
+            DeliveryMode::HourlyDigest => "hourly_digest",
+            DeliveryMode::DailyDigest => "daily_digest",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "immediate" => Some(DeliveryMode::Immediate),
+            "hourly_digest" => Some(DeliveryMode::HourlyDigest),
+            "daily_digest" => Some(DeliveryMode::DailyDigest),
+            _ => None,
+        }
+    }
+
+    /// When the digest holding an item queued at `queued_at` goes out;
+    /// `None` for immediate delivery
+    pub fn due_at(self, queued_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let window = match self {
+            DeliveryMode::Immediate => return None,
+            DeliveryMode::HourlyDigest => Duration::hours(1),
+            DeliveryMode::DailyDigest => Duration::days(1),
+        };
+        // Both windows divide a day evenly, so this truncates to UTC hours
+        // and midnights
+        let start = queued_at.duration_trunc(window).unwrap_or(queued_at);
+        Some(start + window)
+    }
+
+    fn period(self) -> &'static str {
+        match self {
+            DeliveryMode::Immediate => "just now",
+            DeliveryMode::HourlyDigest => "this hour",
+            DeliveryMode::DailyDigest => "today",
+        }
+    }
+}
+
+/// The queued items of one user and window, counted by kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub user_id: String,
+    pub mode: DeliveryMode,
+    pub window_end: DateTime<Utc>,
+    pub counts: BTreeMap<NotificationKind, u64>,
+    /// Every queue row the digest covers, to clear once it is sent
+    pub item_ids: Vec<Uuid>,
+}
+
+impl Digest {
+    /// e.g. "5 new questions, 3 new store reviews today"; largest count first
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<(NotificationKind, u64)> =
+            self.counts.iter().map(|(k, c)| (*k, *c)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let parts: Vec<String> = counts
+            .into_iter()
+            .map(|(kind, count)| kind.describe(count))
+            .collect();
+        format!("{} {}", parts.join(", "), self.mode.period())
+    }
+
+    fn event(&self) -> Event {
+        let counts: BTreeMap<&str, u64> =
+            self.counts.iter().map(|(k, c)| (k.as_str(), *c)).collect();
+        create_event(
+            EventType::NotificationDigestSent,
+            Uuid::new_v4(),
+            serde_json::json!({
+                "recipient": self.user_id,
+                "mode": self.mode,
+                "window_end": self.window_end,
+                "counts": counts,
+                "summary": self.summary(),
+            }),
+        )
+    }
+}
+
+/// Group queued items into one digest per user, mode and window. Rows of a
+/// kind this build doesn't know are cleared without being counted.
+pub fn assemble(items: Vec<DigestItem>) -> Vec<Digest> {
+    let mut digests: BTreeMap<(String, &'static str, DateTime<Utc>), Digest> = BTreeMap::new();
+    for item in items {
+        let mode = DeliveryMode::parse(&item.mode).unwrap_or(DeliveryMode::DailyDigest);
+        let digest = digests
+            .entry((item.user_id.clone(), mode.as_str(), item.due_at))
+            .or_insert_with(|| Digest {
+                user_id: item.user_id.clone(),
+                mode,
+                window_end: item.due_at,
+                counts: BTreeMap::new(),
+                item_ids: Vec::new(),
+            });
+        digest.item_ids.push(item.id);
+        if let Some(kind) = NotificationKind::parse(&item.kind) {
+            *digest.counts.entry(kind).or_default() += 1;
+        }
+    }
+    digests.into_values().collect()
+}
+
+/// Send a digest for every window that closed by `now` and clear its items.
+/// Items another replica cleared first are left to it.
+///
+/// Returns how many digests went out.
+pub async fn send_due_digests(
+    db: &DatabaseConnection,
+    dispatcher: &EventDispatcher,
+    now: DateTime<Utc>,
+) -> Result<usize, String> {
+    let items = NotificationDigestQueue::due(db, now).await?;
+    let mut sent = 0;
+    for digest in assemble(items) {
+        if !NotificationDigestQueue::claim(db, &digest.item_ids).await? {
+            continue;
+        }
+        if digest.counts.is_empty() {
+            continue;
+        }
+        let _ = dispatcher.dispatch(digest.event()).await;
+        sent += 1;
+    }
+    if sent > 0 {
+        info!(count = sent, "Sent notification digests");
+    }
+    Ok(sent)
+}
+
+/// Routes user-addressed events to immediate delivery or a digest queue
+pub struct NotificationRouter {
+    pub db: DatabaseConnection,
+    /// The dispatcher this handler is registered with; weak so the two don't
+    /// keep each other alive
+    pub events: Weak<EventDispatcher>,
+}
+
+impl NotificationRouter {
+    /// Who `event` is for; `None` when nobody is to be told
+    async fn recipient(
+        &self,
+        kind: NotificationKind,
+        event: &Event,
+    ) -> Result<Option<String>, String> {
+        let field = |name: &str| event.data.get(name).and_then(|v| v.as_str());
+        let store_id = match kind {
+            NotificationKind::StoreReviewReply => {
+                return Ok(field("reviewer_id").map(str::to_owned))
+            }
+            NotificationKind::QuestionAnswered => return Ok(field("asked_by").map(str::to_owned)),
+            NotificationKind::PriceDrop => return Ok(field("watcher_id").map(str::to_owned)),
+            NotificationKind::UnansweredQuestions => Some(event.entity_id),
+            NotificationKind::NewStoreReview | NotificationKind::NewQuestion => {
+                field("store_id").and_then(|id| Uuid::parse_str(id).ok())
+            }
+        };
+        let Some(store_id) = store_id else {
+            return Ok(None);
+        };
+        Ok(Store::get(&self.db, store_id).await?.owner_device_id)
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for NotificationRouter {
+    async fn handle_event(&self, event: &Event) -> Result<(), String> {
+        let Some(kind) = NotificationKind::of(&event.event_type) else {
+            return Ok(());
+        };
+        let Some(recipient) = self.recipient(kind, event).await? else {
+            return Ok(());
+        };
+        let mode = NotificationPreferences::mode(&self.db, &recipient, kind).await?;
+        let now = Utc::now();
+        match mode.due_at(now) {
+            Some(due_at) => {
+                NotificationDigestQueue::enqueue(
+                    &self.db, &recipient, kind, mode, event, now, due_at,
+                )
+                .await
+            }
+            None => {
+                let Some(events) = self.events.upgrade() else {
+                    return Ok(());
+                };
+                let notification = create_event(
+                    EventType::NotificationSent,
+                    event.entity_id,
+                    serde_json::json!({
+                        "recipient": recipient,
+                        "kind": kind,
+                        "source_event_id": event.id,
+                        "data": event.data,
+                    }),
+                );
+                let _ = events.dispatch(notification).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use chrono::TimeZone;
+    use std::sync::{Arc, Mutex};
+
+    /// Keeps the notifications and digests sent
+    struct Inbox(Arc<Mutex<Vec<Event>>>);
+
+    #[async_trait::async_trait]
+    impl EventHandler for Inbox {
+        async fn handle_event(&self, event: &Event) -> Result<(), String> {
+            if matches!(
+                event.event_type,
+                EventType::NotificationSent | EventType::NotificationDigestSent
+            ) {
+                self.0.lock().unwrap().push(event.clone());
+            }
+            Ok(())
+        }
+    }
+
+    fn router(db: &DatabaseConnection, sent: &Arc<Mutex<Vec<Event>>>) -> Arc<EventDispatcher> {
+        Arc::new_cyclic(|events| {
+            let mut dispatcher = EventDispatcher::new();
+            dispatcher.add_handler(Box::new(NotificationRouter {
+                db: db.clone(),
+                events: events.clone(),
+            }));
+            dispatcher.add_handler(Box::new(Inbox(sent.clone())));
+            dispatcher
+        })
+    }
+
+    fn question_asked(store_id: Uuid) -> Event {
+        create_event(
+            EventType::ProductQuestionAsked,
+            Uuid::new_v4(),
+            serde_json::json!({ "store_id": store_id, "question": "Any in blue?" }),
+        )
+    }
+
+    fn review_posted(store_id: Uuid) -> Event {
+        create_event(
+            EventType::StoreReviewPosted,
+            Uuid::new_v4(),
+            serde_json::json!({ "store_id": store_id, "rating": 5 }),
+        )
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 14, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_digest_windows_close_on_the_hour_and_at_midnight() {
+        assert_eq!(DeliveryMode::Immediate.due_at(at(9, 41)), None);
+        assert_eq!(
+            DeliveryMode::HourlyDigest.due_at(at(9, 41)),
+            Some(at(10, 0))
+        );
+        assert_eq!(DeliveryMode::HourlyDigest.due_at(at(9, 0)), Some(at(10, 0)));
+        assert_eq!(
+            DeliveryMode::DailyDigest.due_at(at(9, 41)),
+            Some(Utc.with_ymd_and_hms(2026, 3, 15, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_immediate_kinds_are_sent_right_away() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = router(&db, &sent);
+
+        dispatcher.dispatch(question_asked(store)).await.unwrap();
+        let answered = create_event(
+            EventType::ProductQuestionAnswered,
+            Uuid::new_v4(),
+            serde_json::json!({ "product_id": Uuid::new_v4(), "asked_by": "buyer-1" }),
+        );
+        dispatcher.dispatch(answered).await.unwrap();
+
+        assert!(
+            NotificationDigestQueue::due(&db, Utc::now() + Duration::days(2))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].data["recipient"], "seller-1");
+        assert_eq!(sent[0].data["kind"], "new_question");
+        assert_eq!(sent[1].data["recipient"], "buyer-1");
+        assert_eq!(sent[1].data["kind"], "question_answered");
+    }
+
+    #[tokio::test]
+    async fn test_digest_kinds_are_queued_per_window() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        NotificationPreferences::set(
+            &db,
+            "seller-1",
+            &[
+                (NotificationKind::NewQuestion, DeliveryMode::HourlyDigest),
+                (NotificationKind::NewStoreReview, DeliveryMode::DailyDigest),
+            ],
+        )
+        .await
+        .unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = router(&db, &sent);
+
+        dispatcher.dispatch(question_asked(store)).await.unwrap();
+        dispatcher.dispatch(review_posted(store)).await.unwrap();
+        assert!(sent.lock().unwrap().is_empty());
+
+        let now = Utc::now();
+        let hourly = DeliveryMode::HourlyDigest.due_at(now).unwrap();
+        let daily = DeliveryMode::DailyDigest.due_at(now).unwrap();
+        let queued = NotificationDigestQueue::due(&db, daily).await.unwrap();
+        assert_eq!(queued.len(), 2);
+        // Neither window has closed yet
+        assert_eq!(send_due_digests(&db, &dispatcher, now).await.unwrap(), 0);
+
+        // Both close at midnight when queued in the day's last hour
+        let at_hour_end = send_due_digests(&db, &dispatcher, hourly).await.unwrap();
+        assert_eq!(at_hour_end, if hourly == daily { 2 } else { 1 });
+        let at_day_end = send_due_digests(&db, &dispatcher, daily).await.unwrap();
+        assert_eq!(at_hour_end + at_day_end, 2);
+        assert!(NotificationDigestQueue::due(&db, daily)
+            .await
+            .unwrap()
+            .is_empty());
+        let sent = sent.lock().unwrap();
+        let summary = |mode: &str| {
+            sent.iter()
+                .find(|event| event.data["mode"] == mode)
+                .map(|event| event.data["summary"].clone())
+        };
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            summary("hourly_digest").unwrap(),
+            "1 new question this hour"
+        );
+        assert_eq!(summary("daily_digest").unwrap(), "1 new store review today");
+    }
+
+    #[tokio::test]
+    async fn test_preference_changes_apply_to_later_items() {
+        let db = testing::sqlite().await;
+        let store = testing::seed_store(&db, "seller-1").await;
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = router(&db, &sent);
+        let question = |mode| [(NotificationKind::NewQuestion, mode)];
+
+        NotificationPreferences::set(&db, "seller-1", &question(DeliveryMode::DailyDigest))
+            .await
+            .unwrap();
+        dispatcher.dispatch(question_asked(store)).await.unwrap();
+        NotificationPreferences::set(&db, "seller-1", &question(DeliveryMode::Immediate))
+            .await
+            .unwrap();
+        dispatcher.dispatch(question_asked(store)).await.unwrap();
+
+        // The second went out; the first stays in its daily digest
+        assert_eq!(sent.lock().unwrap().len(), 1);
+        let queued = NotificationDigestQueue::due(&db, Utc::now() + Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].mode, "daily_digest");
+    }
+
+    #[tokio::test]
+    async fn test_digests_count_each_kind_per_user() {
+        let db = testing::sqlite().await;
+        let mine = testing::seed_store(&db, "seller-1").await;
+        let theirs = testing::seed_store(&db, "seller-2").await;
+        for seller in ["seller-1", "seller-2"] {
+            NotificationPreferences::set(
+                &db,
+                seller,
+                &[
+                    (NotificationKind::NewQuestion, DeliveryMode::DailyDigest),
+                    (NotificationKind::NewStoreReview, DeliveryMode::DailyDigest),
+                ],
+            )
+            .await
+            .unwrap();
+        }
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = router(&db, &sent);
+        for _ in 0..5 {
+            dispatcher.dispatch(question_asked(mine)).await.unwrap();
+        }
+        for _ in 0..3 {
+            dispatcher.dispatch(review_posted(mine)).await.unwrap();
+        }
+        dispatcher.dispatch(review_posted(theirs)).await.unwrap();
+
+        let day_end = DeliveryMode::DailyDigest.due_at(Utc::now()).unwrap();
+        assert_eq!(
+            send_due_digests(&db, &dispatcher, day_end).await.unwrap(),
+            2
+        );
+        // Everything sent is cleared
+        assert_eq!(
+            send_due_digests(&db, &dispatcher, day_end).await.unwrap(),
+            0
+        );
+
+        let sent = sent.lock().unwrap();
+        let digest = |seller: &str| {
+            sent.iter()
+                .find(|event| event.data["recipient"] == seller)
+                .unwrap()
+                .data
+                .clone()
+        };
+        let mine = digest("seller-1");
+        assert_eq!(mine["counts"]["new_question"], 5);
+        assert_eq!(mine["counts"]["new_store_review"], 3);
+        assert_eq!(
+            mine["summary"],
+            "5 new questions, 3 new store reviews today"
+        );
+        let theirs = digest("seller-2");
+        assert_eq!(theirs["counts"]["new_store_review"], 1);
+        assert!(theirs["counts"].get("new_question").is_none());
+    }
+}
@@ -0,0 +1,157 @@
+//! Price-drop alerts for buyers watching a product.
+//!
+//! [`PriceAlertNotifier`] listens for `ProductPriceChanged` and
+//! `ProductPricesBulkUpdated`, picks the watchers due an alert (see
+//! `ProductWatch::claim_alerts`) and sends each a `ProductPriceDropped`
+//! event through the same dispatcher.
+
+use crate::db::watches::ProductWatch;
+use crate::events::{create_event, Event, EventDispatcher, EventHandler, EventType};
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use std::sync::Weak;
+use uuid::Uuid;
+
+/// One product's move, as carried by the price events
+#[derive(Debug, Deserialize)]
+struct PriceChange {
+    product_id: Option<Uuid>,
+    previous_price: f64,
+    price: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkPriceChange {
+    products: Vec<PriceChange>,
+}
+
+pub struct PriceAlertNotifier {
+    pub db: DatabaseConnection,
+    /// The dispatcher this handler is registered with; weak so the two don't
+    /// keep each other alive
+    pub events: Weak<EventDispatcher>,
+}
+
+impl PriceAlertNotifier {
+    async fn alert(&self, product_id: Uuid, change: &PriceChange) -> Result<(), String> {
+        let watches = ProductWatch::claim_alerts(
+            &self.db,
+            product_id,
+            change.previous_price,
+            change.price,
+            Utc::now(),
+        )
+        .await?;
+        let Some(events) = self.events.upgrade() else {
+            return Ok(());
+        };
+        for watch in watches {
+            let event = create_event(
+                EventType::ProductPriceDropped,
+                product_id,
+                serde_json::json!({
+                    "watcher_id": watch.watcher_id,
+                    "previous_price": change.previous_price,
+                    "price": change.price,
+                    "target_price": watch.target_price,
+                }),
+            );
+            let _ = events.dispatch(event).await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for PriceAlertNotifier {
+    async fn handle_event(&self, event: &Event) -> Result<(), String> {
+        match event.event_type {
+            EventType::ProductPriceChanged => {
+                let change: PriceChange = serde_json::from_value(event.data.clone())
+                    .map_err(|e| format!("Malformed price change event: {e}"))?;
+                self.alert(event.entity_id, &change).await
+            }
+            EventType::ProductPricesBulkUpdated => {
+                let bulk: BulkPriceChange = serde_json::from_value(event.data.clone())
+                    .map_err(|e| format!("Malformed bulk price event: {e}"))?;
+                for change in &bulk.products {
+                    if let Some(product_id) = change.product_id {
+                        self.alert(product_id, change).await?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::db::watches::ProductWatch;
+    use std::sync::{Arc, Mutex};
+
+    /// Keeps the alerts sent
+    struct Inbox(Arc<Mutex<Vec<serde_json::Value>>>);
+
+    #[async_trait::async_trait]
+    impl EventHandler for Inbox {
+        async fn handle_event(&self, event: &Event) -> Result<(), String> {
+            if matches!(event.event_type, EventType::ProductPriceDropped) {
+                self.0.lock().unwrap().push(event.data.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_price_drops_alert_watchers_once_a_day() {
+        let db = testing::sqlite().await;
+        let product = testing::seed_product(&db, "seller-1").await;
+        ProductWatch::watch(&db, product, "buyer-1", Some(4000.0))
+            .await
+            .unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dispatcher = Arc::new_cyclic(|events| {
+            let mut dispatcher = EventDispatcher::new();
+            dispatcher.add_handler(Box::new(PriceAlertNotifier {
+                db: db.clone(),
+                events: events.clone(),
+            }));
+            dispatcher.add_handler(Box::new(Inbox(sent.clone())));
+            dispatcher
+        });
+        let changed = |previous: f64, price: f64| {
+            create_event(
+                EventType::ProductPriceChanged,
+                product,
+                serde_json::json!({ "previous_price": previous, "price": price }),
+            )
+        };
+
+        // Up, then down but above the target: nothing
+        dispatcher.dispatch(changed(5000.0, 5200.0)).await.unwrap();
+        dispatcher.dispatch(changed(5200.0, 4500.0)).await.unwrap();
+        assert!(sent.lock().unwrap().is_empty());
+
+        dispatcher.dispatch(changed(4500.0, 3800.0)).await.unwrap();
+        // A bulk repricing the same day doesn't alert again
+        let bulk = create_event(
+            EventType::ProductPricesBulkUpdated,
+            Uuid::new_v4(),
+            serde_json::json!({
+                "products": [{ "product_id": product, "previous_price": 3800.0, "price": 3500.0 }]
+            }),
+        );
+        dispatcher.dispatch(bulk).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["watcher_id"], "buyer-1");
+        assert_eq!(sent[0]["price"], 3800.0);
+        assert_eq!(sent[0]["target_price"], 4000.0);
+    }
+}
@@ -0,0 +1,285 @@
+//! Product views, counted off the request path.
+//!
+//! `POST /api/v1/products/{id}/view` only queues the view on a
+//! [`ViewRecorder`]. The view writer drops repeat views of a product by the
+//! same viewer within the dedup window and writes the rest in batches, every
+//! flush interval or once [`MAX_BATCH`] are waiting. Viewers are kept as a
+//! hash of their JWT subject, or of their client IP when they have no
+//! token. Repeats are told apart per replica, so a viewer whose requests
+//! land on two replicas may be counted twice.
+
+use crate::auth::claims_from_headers;
+use crate::db::product_views::{NewProductView, ProductViews};
+use crate::request_middleware::get_client_ip;
+use crate::shutdown::ShutdownHook;
+use axum::extract::Request;
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Views waiting to be written before new ones are dropped
+pub const QUEUE_CAPACITY: usize = 10_000;
+/// Views written by one insert
+pub const MAX_BATCH: usize = 500;
+
+/// How views are counted and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ViewSettings {
+    /// A viewer's views of a product within this many seconds count once
+    pub dedup_window_secs: u64,
+    /// Longest a queued view waits before it is written
+    pub flush_interval_secs: u64,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            dedup_window_secs: 30 * 60,
+            flush_interval_secs: 5,
+        }
+    }
+}
+
+/// The viewer behind `request`, hashed: the JWT subject, else the client
+/// IP as [`get_client_ip`] finds it. `None` when there is neither.
+pub fn viewer_hash(request: &Request) -> Option<String> {
+    let viewer = match claims_from_headers(request.headers()) {
+        Some(claims) => format!("sub:{}", claims.sub),
+        None => match get_client_ip(request) {
+            ip if ip == "unknown" => return None,
+            ip => format!("ip:{ip}"),
+        },
+    };
+    Some(
+        Sha256::digest(viewer.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+    )
+}
+
+/// Where handlers queue views for the writer
+#[derive(Debug, Clone)]
+pub struct ViewRecorder {
+    sender: mpsc::Sender<NewProductView>,
+}
+
+impl ViewRecorder {
+    /// A recorder and the queue the writer reads it from
+    pub fn new() -> (Self, mpsc::Receiver<NewProductView>) {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        (Self { sender }, receiver)
+    }
+
+    /// Queue a view without waiting; false when the queue is full or the
+    /// writer has stopped, and the view is dropped
+    pub fn record(&self, product_id: Uuid, viewer_hash: String, viewed_at: DateTime<Utc>) -> bool {
+        self.sender
+            .try_send(NewProductView {
+                product_id,
+                viewer_hash,
+                viewed_at,
+            })
+            .is_ok()
+    }
+}
+
+/// When each viewer was last counted on each product, within the window
+#[derive(Debug)]
+pub struct RecentViews {
+    window: Duration,
+    counted: HashMap<(Uuid, String), DateTime<Utc>>,
+}
+
+impl RecentViews {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            counted: HashMap::new(),
+        }
+    }
+
+    /// Whether `view` counts: its viewer wasn't counted on the product
+    /// within the window before it
+    pub fn admit(&mut self, view: &NewProductView) -> bool {
+        let key = (view.product_id, view.viewer_hash.clone());
+        match self.counted.get(&key) {
+            Some(&last) if view.viewed_at - last < self.window => false,
+            _ => {
+                self.counted.insert(key, view.viewed_at);
+                true
+            }
+        }
+    }
+
+    /// Forget views too old to hold back another
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        let window = self.window;
+        self.counted.retain(|_, last| now - *last < window);
+    }
+}
+
+async fn flush(db: &DatabaseConnection, pending: &mut Vec<NewProductView>) {
+    if pending.is_empty() {
+        return;
+    }
+    // Views are best effort: a batch that fails to write is dropped
+    match ProductViews::insert_batch(db, pending).await {
+        Ok(written) => debug!(written, "Product views written"),
+        Err(e) => error!(error = %e, dropped = pending.len(), "Product views lost"),
+    }
+    pending.clear();
+}
+
+/// Write the views queued on `views` until every recorder is dropped or
+/// shutdown is requested; views already queued by then are still written
+pub async fn write_views(
+    db: DatabaseConnection,
+    mut views: mpsc::Receiver<NewProductView>,
+    settings: ViewSettings,
+    hook: ShutdownHook,
+) {
+    let mut recent = RecentViews::new(Duration::seconds(settings.dedup_window_secs as i64));
+    let mut pending = Vec::new();
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(settings.flush_interval_secs));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            biased;
+            () = hook.requested() => break,
+            view = views.recv() => match view {
+                Some(view) => {
+                    if recent.admit(&view) {
+                        pending.push(view);
+                    }
+                    if pending.len() >= MAX_BATCH {
+                        flush(&db, &mut pending).await;
+                    }
+                }
+                None => break,
+            },
+            _ = interval.tick() => {
+                flush(&db, &mut pending).await;
+                recent.prune(Utc::now());
+            }
+        }
+    }
+    views.close();
+    while let Ok(view) = views.try_recv() {
+        if recent.admit(&view) {
+            pending.push(view);
+        }
+        if pending.len() >= MAX_BATCH {
+            flush(&db, &mut pending).await;
+        }
+    }
+    flush(&db, &mut pending).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::JwtService;
+    use crate::db::product_views::ViewCounts;
+    use crate::db::testing::{seed_product, sqlite};
+    use crate::shutdown::ShutdownCoordinator;
+
+    #[test]
+    fn test_viewers_are_hashed_by_subject_then_ip() {
+        use axum::body::Body;
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+
+        let request = |header: Option<(&str, String)>| {
+            let mut request = Request::builder();
+            if let Some((name, value)) = header {
+                request = request.header(name, value);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        assert_eq!(viewer_hash(&request(None)), None);
+
+        let forwarded = ("x-forwarded-for", "203.0.113.7, 10.0.0.1".to_string());
+        let by_ip = viewer_hash(&request(Some(forwarded))).unwrap();
+        assert_eq!(by_ip.len(), 64);
+        assert!(!by_ip.contains("203.0.113.7"));
+        let real_ip = ("x-real-ip", "203.0.113.7".to_string());
+        assert_eq!(viewer_hash(&request(Some(real_ip))), Some(by_ip.clone()));
+        // No proxy in front: the peer address
+        let mut direct = request(None);
+        direct
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 40123))));
+        assert_eq!(viewer_hash(&direct), Some(by_ip.clone()));
+
+        let token = JwtService::new()
+            .unwrap()
+            .generate_token_with_role("buyer".into(), String::new(), "buyer".into())
+            .unwrap();
+        let bearer = ("authorization", format!("Bearer {token}"));
+        let by_subject = viewer_hash(&request(Some(bearer))).unwrap();
+        assert_ne!(by_subject, by_ip);
+    }
+
+    #[test]
+    fn test_repeat_views_count_once_per_window() {
+        let mut recent = RecentViews::new(Duration::minutes(30));
+        let product_id = Uuid::new_v4();
+        let start = Utc::now();
+        let view = |viewer: &str, minutes| NewProductView {
+            product_id,
+            viewer_hash: viewer.to_string(),
+            viewed_at: start + Duration::minutes(minutes),
+        };
+        assert!(recent.admit(&view("a", 0)));
+        assert!(!recent.admit(&view("a", 10)));
+        assert!(recent.admit(&view("b", 10)));
+        // The window runs from the view that was counted
+        assert!(!recent.admit(&view("a", 29)));
+        assert!(recent.admit(&view("a", 30)));
+
+        recent.prune(start + Duration::minutes(45));
+        assert_eq!(recent.counted.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_queued_views_are_written_once_the_writer_stops() {
+        let db = sqlite().await;
+        let product_id = seed_product(&db, "seller").await;
+        let (recorder, queue) = ViewRecorder::new();
+        let shutdown = ShutdownCoordinator::new();
+        let settings = ViewSettings {
+            dedup_window_secs: 1800,
+            // Long enough that only stopping writes the views
+            flush_interval_secs: 3600,
+        };
+        let writer = tokio::spawn(write_views(
+            db.clone(),
+            queue,
+            settings,
+            shutdown.register("view writer"),
+        ));
+
+        let now = Utc::now();
+        for viewer in ["a", "a", "b", "a", "c"] {
+            assert!(recorder.record(product_id, viewer.to_string(), now));
+        }
+        drop(recorder);
+        writer.await.unwrap();
+
+        assert_eq!(
+            ProductViews::counts(&db, product_id, now).await,
+            Ok(ViewCounts {
+                total: 3,
+                last_7_days: 3,
+                last_30_days: 3,
+            })
+        );
+    }
+}
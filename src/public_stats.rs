@@ -0,0 +1,165 @@
+//! Marketplace figures safe to show anyone.
+//!
+//! Counts are rounded down to a multiple of `granularity`, so the widget
+//! reads "1,200+ products" and one new store or product never shows up in
+//! it. A city is named only once `min_stores_per_city` stores give it as
+//! their location, so no city points at a handful of sellers. A job writes
+//! the figures to `public_stats`; `GET /api/v1/public-stats` only reads
+//! them back.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Cities named in the figures, busiest first
+pub const MAX_PUBLIC_CITIES: usize = 20;
+
+/// How far the public figures are coarsened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct PublicStatsRules {
+    /// Counts are rounded down to a multiple of this
+    pub granularity: u64,
+    /// Stores a city needs before it is named or counted
+    pub min_stores_per_city: u64,
+}
+
+impl Default for PublicStatsRules {
+    fn default() -> Self {
+        Self {
+            granularity: 100,
+            min_stores_per_city: 5,
+        }
+    }
+}
+
+/// The marketplace as the public sees it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicFigures {
+    pub products: u64,
+    pub stores: u64,
+    /// Cities with enough stores to be shown
+    pub city_count: u64,
+    /// The busiest of them, at most [`MAX_PUBLIC_CITIES`]
+    pub cities: Vec<String>,
+}
+
+/// `count` rounded down to a multiple of `granularity`
+pub fn round_count(count: u64, granularity: u64) -> u64 {
+    let granularity = granularity.max(1);
+    count / granularity * granularity
+}
+
+/// The city of a store location: "Douala" in "Douala, Cameroon"
+pub fn city_of(location: &str) -> Option<&str> {
+    let city = location.split(',').next()?.trim();
+    (!city.is_empty()).then_some(city)
+}
+
+/// Cities with at least `min_stores` of `locations`, busiest first, then
+/// by name. Spellings differing only in case are one city, written as it
+/// was first seen.
+pub fn public_cities<'a>(
+    locations: impl IntoIterator<Item = &'a str>,
+    min_stores: u64,
+) -> Vec<String> {
+    let mut cities: HashMap<String, (&str, u64)> = HashMap::new();
+    for city in locations.into_iter().filter_map(city_of) {
+        cities.entry(city.to_lowercase()).or_insert((city, 0)).1 += 1;
+    }
+    let mut named: Vec<(&str, u64)> = cities
+        .into_values()
+        .filter(|(_, stores)| *stores >= min_stores.max(1))
+        .collect();
+    named.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    named
+        .into_iter()
+        .map(|(city, _)| city.to_string())
+        .collect()
+}
+
+/// The public figures for exact `products` and `stores` counts and the
+/// stores' `locations`
+pub fn public_figures<'a>(
+    products: u64,
+    stores: u64,
+    locations: impl IntoIterator<Item = &'a str>,
+    rules: &PublicStatsRules,
+) -> PublicFigures {
+    let mut cities = public_cities(locations, rules.min_stores_per_city);
+    let city_count = cities.len() as u64;
+    cities.truncate(MAX_PUBLIC_CITIES);
+    PublicFigures {
+        products: round_count(products, rules.granularity),
+        stores: round_count(stores, rules.granularity),
+        city_count,
+        cities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_round_down_to_the_granularity() {
+        for (count, granularity, rounded) in [
+            (0, 100, 0),
+            (99, 100, 0),
+            (100, 100, 100),
+            (1_299, 100, 1_200),
+            (1_299, 1_000, 1_000),
+            (7, 1, 7),
+            // A zero granularity is taken as no rounding
+            (7, 0, 7),
+        ] {
+            assert_eq!(round_count(count, granularity), rounded, "{count}");
+        }
+    }
+
+    #[test]
+    fn test_cities_below_the_threshold_are_suppressed() {
+        let locations = [
+            "Douala, Cameroon",
+            "douala",
+            "Douala ",
+            "Yaoundé",
+            "Yaoundé, Centre",
+            "Bafoussam",
+            "",
+            " , Littoral",
+        ];
+        assert_eq!(public_cities(locations, 2), ["Douala", "Yaoundé"]);
+        assert_eq!(public_cities(locations, 3), ["Douala"]);
+        assert!(public_cities(locations, 4).is_empty());
+        assert_eq!(
+            public_cities(locations, 1),
+            ["Douala", "Yaoundé", "Bafoussam"]
+        );
+    }
+
+    #[test]
+    fn test_figures_never_show_exact_counts_or_small_cities() {
+        let rules = PublicStatsRules {
+            granularity: 50,
+            min_stores_per_city: 3,
+        };
+        let mut locations = vec!["Douala"; 40];
+        locations.extend(["Buea"; 2]);
+        let figures = public_figures(1_234, 42, locations, &rules);
+        assert_eq!(
+            figures,
+            PublicFigures {
+                products: 1_200,
+                stores: 0,
+                city_count: 1,
+                cities: vec!["Douala".to_string()],
+            }
+        );
+
+        let many: Vec<String> = (0..30).map(|i| format!("City {i:02}")).collect();
+        let locations = many.iter().flat_map(|city| [city.as_str(); 3]);
+        let figures = public_figures(0, 90, locations, &rules);
+        assert_eq!(figures.city_count, 30);
+        assert_eq!(figures.cities.len(), MAX_PUBLIC_CITIES);
+        assert_eq!(figures.cities[0], "City 00");
+    }
+}
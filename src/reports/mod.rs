@@ -0,0 +1,472 @@
+//! Seller reports generated in the background.
+//!
+//! `POST /stores/{id}/reports` queues a `report_jobs` row; the report runner
+//! claims queued jobs, renders the report as CSV or JSON and stores it under
+//! `reports/{store_id}/`. Finished reports can be downloaded through a
+//! presigned link for [`REPORT_TTL_DAYS`], after which the runner deletes the
+//! file and the retention job eventually prunes the row.
+
+use crate::api::media_storage::MediaStorage;
+use crate::api::whatsapp_catalog::csv_field;
+use crate::db::reports::ReportJob;
+use crate::entity::report_job;
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Days a finished report stays downloadable
+pub const REPORT_TTL_DAYS: i64 = 7;
+/// Longest date range a single report may cover
+pub const MAX_REPORT_RANGE_DAYS: i64 = 366;
+/// Running jobs older than this are assumed lost with their replica
+pub const STALE_REPORT_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportType {
+    /// Every regular price change, with the old and new price
+    PriceChanges,
+    /// Products created in the range
+    NewProducts,
+}
+
+impl ReportType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportType::PriceChanges => "price_changes",
+            ReportType::NewProducts => "new_products",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "price_changes" => Some(ReportType::PriceChanges),
+            "new_products" => Some(ReportType::NewProducts),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "text/csv; charset=utf-8",
+            ReportFormat::Json => "application/json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Queued,
+    Running,
+    /// Generated and downloadable until `expires_at`
+    Ready,
+    Failed,
+    /// The file was deleted after [`REPORT_TTL_DAYS`]
+    Expired,
+}
+
+impl ReportStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportStatus::Queued => "queued",
+            ReportStatus::Running => "running",
+            ReportStatus::Ready => "ready",
+            ReportStatus::Failed => "failed",
+            ReportStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(ReportStatus::Queued),
+            "running" => Some(ReportStatus::Running),
+            "ready" => Some(ReportStatus::Ready),
+            "failed" => Some(ReportStatus::Failed),
+            "expired" => Some(ReportStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// How many report jobs may be in flight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ReportLimits {
+    /// Queued or running jobs one store may have
+    pub max_per_store: u64,
+    /// Jobs running at once across every replica
+    pub max_running: u64,
+}
+
+impl Default for ReportLimits {
+    fn default() -> Self {
+        Self {
+            max_per_store: 2,
+            max_running: 4,
+        }
+    }
+}
+
+/// Check a requested range: not reversed, not in the future, not too long
+pub fn check_range(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    if to <= from {
+        return Err("to must be after from".to_string());
+    }
+    if from > now {
+        return Err("from must not be in the future".to_string());
+    }
+    if to - from > Duration::days(MAX_REPORT_RANGE_DAYS) {
+        return Err(format!(
+            "A report may cover at most {MAX_REPORT_RANGE_DAYS} days"
+        ));
+    }
+    Ok(())
+}
+
+/// Where a report's file is stored
+pub fn report_key(store_id: Uuid, id: Uuid, format: ReportFormat) -> String {
+    format!("reports/{store_id}/{id}.{}", format.as_str())
+}
+
+/// Rows of a report, rendered to a file by [`ReportTable::render`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportTable {
+    pub columns: &'static [&'static str],
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+impl ReportTable {
+    /// CSV with a header line, or a JSON array with one object per row
+    pub fn render(&self, format: ReportFormat) -> Vec<u8> {
+        match format {
+            ReportFormat::Csv => {
+                let mut out = self.columns.join(",");
+                out.push('\n');
+                for row in &self.rows {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .map(|cell| match cell {
+                            serde_json::Value::Null => String::new(),
+                            serde_json::Value::String(text) => csv_field(text).into_owned(),
+                            other => other.to_string(),
+                        })
+                        .collect();
+                    let _ = writeln!(out, "{}", cells.join(","));
+                }
+                out.into_bytes()
+            }
+            ReportFormat::Json => {
+                let rows: Vec<serde_json::Map<String, serde_json::Value>> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        self.columns
+                            .iter()
+                            .map(|column| column.to_string())
+                            .zip(row.iter().cloned())
+                            .collect()
+                    })
+                    .collect();
+                serde_json::to_vec(&rows).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Generate one claimed job's report and store it. Returns the storage key.
+pub async fn generate_report<S: MediaStorage + Sync>(
+    db: &DatabaseConnection,
+    storage: &S,
+    job: &report_job::Model,
+) -> Result<String, String> {
+    let report_type = ReportType::parse(&job.report_type)
+        .ok_or_else(|| format!("Unknown report type {}", job.report_type))?;
+    let format = ReportFormat::parse(&job.format)
+        .ok_or_else(|| format!("Unknown report format {}", job.format))?;
+    let table = ReportJob::table(
+        db,
+        job.store_id,
+        report_type,
+        job.range_start,
+        job.range_end,
+    )
+    .await?;
+    let key = report_key(job.store_id, job.id, format);
+    storage
+        .put_object(&key, &table.render(format), format.content_type())
+        .await?;
+    Ok(key)
+}
+
+/// Run queued reports until none are left or `limits.max_running` jobs are
+/// already running. Returns how many finished, successfully or not.
+pub async fn run_due_reports<S: MediaStorage + Sync>(
+    db: &DatabaseConnection,
+    storage: &S,
+    limits: ReportLimits,
+    now: DateTime<Utc>,
+) -> Result<usize, String> {
+    let stale = ReportJob::fail_stale(db, now - Duration::minutes(STALE_REPORT_MINUTES)).await?;
+    if stale > 0 {
+        info!(count = stale, "Failed report jobs that stopped running");
+    }
+    let mut done = 0;
+    while let Some(job) = ReportJob::claim_next(db, limits.max_running, Utc::now()).await? {
+        match generate_report(db, storage, &job).await {
+            Ok(key) => {
+                let finished_at = Utc::now();
+                ReportJob::finish(
+                    db,
+                    job.id,
+                    &key,
+                    finished_at,
+                    finished_at + Duration::days(REPORT_TTL_DAYS),
+                )
+                .await?;
+            }
+            Err(e) => {
+                error!(report_id = %job.id, error = %e, "Report generation failed");
+                ReportJob::fail(db, job.id, &e, Utc::now()).await?;
+            }
+        }
+        done += 1;
+    }
+    Ok(done)
+}
+
+/// Delete the files of reports past their `expires_at`. A file that cannot
+/// be deleted keeps its job ready, so the next run tries again.
+pub async fn expire_reports<S: MediaStorage + Sync>(
+    db: &DatabaseConnection,
+    storage: &S,
+    now: DateTime<Utc>,
+) -> Result<usize, String> {
+    let mut expired = 0;
+    for job in ReportJob::expiring(db, now).await? {
+        if let Some(key) = &job.media_key {
+            if let Err(e) = storage.delete_media(key).await {
+                error!(report_id = %job.id, error = %e, "Failed to delete expired report");
+                continue;
+            }
+        }
+        ReportJob::mark_expired(db, job.id).await?;
+        expired += 1;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::media_storage::StubMediaStorage;
+    use crate::db::reports::CreateReport;
+    use crate::db::testing;
+    use crate::entity::{product, product_price_history};
+    use sea_orm::{EntityTrait, Set};
+
+    #[test]
+    fn test_check_range() {
+        let now = Utc::now();
+        assert!(check_range(now - Duration::days(30), now, now).is_ok());
+        assert!(check_range(now, now - Duration::days(1), now).is_err());
+        assert!(check_range(now + Duration::days(1), now + Duration::days(2), now).is_err());
+        assert!(check_range(now - Duration::days(400), now, now).is_err());
+    }
+
+    #[test]
+    fn test_render_csv_and_json() {
+        let table = ReportTable {
+            columns: &["name", "price"],
+            rows: vec![
+                vec![serde_json::json!("Ndolé, large"), serde_json::json!(2500.0)],
+                vec![serde_json::json!("Plantain"), serde_json::Value::Null],
+            ],
+        };
+        assert_eq!(
+            String::from_utf8(table.render(ReportFormat::Csv)).unwrap(),
+            "name,price\n\"Ndolé, large\",2500.0\nPlantain,\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_slice(&table.render(ReportFormat::Json)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"name": "Ndolé, large", "price": 2500.0},
+                {"name": "Plantain", "price": null},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_report_lifecycle() {
+        let db = testing::sqlite().await;
+        let product_id = testing::seed_product(&db, "seller-1").await;
+        let product = product::Entity::find_by_id(product_id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        let now = Utc::now();
+        product_price_history::Entity::insert(product_price_history::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            product_id: Set(product.id),
+            store_id: Set(product.store_id),
+            previous_price: Set(1000.0),
+            price: Set(1200.0),
+            source: Set("bulk".to_string()),
+            created_at: Set(now - Duration::days(2)),
+        })
+        .exec_without_returning(&db)
+        .await
+        .unwrap();
+
+        let limits = ReportLimits {
+            max_per_store: 1,
+            max_running: 1,
+        };
+        let request = CreateReport {
+            store_id: product.store_id,
+            requested_by: "seller-1".to_string(),
+            report_type: ReportType::PriceChanges,
+            format: ReportFormat::Csv,
+            range_start: now - Duration::days(7),
+            range_end: now,
+        };
+        let job = ReportJob::create(&db, &request, limits.max_per_store)
+            .await
+            .unwrap()
+            .expect("first report is accepted");
+        assert_eq!(job.status, ReportStatus::Queued.as_str());
+        // One store may not queue more than its share
+        assert!(ReportJob::create(&db, &request, limits.max_per_store)
+            .await
+            .unwrap()
+            .is_none());
+
+        let done = run_due_reports(&db, &StubMediaStorage, limits, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(done, 1);
+        let ready = ReportJob::get(&db, product.store_id, job.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ready.status, ReportStatus::Ready.as_str());
+        let key = report_key(product.store_id, job.id, ReportFormat::Csv);
+        assert_eq!(ready.media_key.as_deref(), Some(key.as_str()));
+        let expires_at = ready.expires_at.unwrap();
+        assert!(expires_at > now + Duration::days(REPORT_TTL_DAYS - 1));
+
+        let table = ReportJob::table(
+            &db,
+            product.store_id,
+            ReportType::PriceChanges,
+            request.range_start,
+            request.range_end,
+        )
+        .await
+        .unwrap();
+        assert_eq!(table.rows.len(), 1);
+
+        // Nothing expires early; afterwards the file goes and the job says so
+        assert_eq!(
+            expire_reports(&db, &StubMediaStorage, now).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            expire_reports(&db, &StubMediaStorage, expires_at)
+                .await
+                .unwrap(),
+            1
+        );
+        let expired = ReportJob::get(&db, product.store_id, job.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expired.status, ReportStatus::Expired.as_str());
+        assert!(expired.media_key.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_runner_respects_overall_limit() {
+        let db = testing::sqlite().await;
+        let store_id = testing::seed_store(&db, "seller-1").await;
+        let now = Utc::now();
+        let request = CreateReport {
+            store_id,
+            requested_by: "seller-1".to_string(),
+            report_type: ReportType::NewProducts,
+            format: ReportFormat::Json,
+            range_start: now - Duration::days(1),
+            range_end: now,
+        };
+        let job = ReportJob::create(&db, &request, 5).await.unwrap().unwrap();
+        let running = ReportJob::create(&db, &request, 5).await.unwrap().unwrap();
+        assert!(ReportJob::claim_next(&db, 1, now)
+            .await
+            .unwrap()
+            .is_some_and(|claimed| claimed.id == job.id));
+
+        // The only slot is taken until the stale run is failed
+        let limits = ReportLimits {
+            max_per_store: 5,
+            max_running: 1,
+        };
+        assert_eq!(
+            run_due_reports(&db, &StubMediaStorage, limits, now)
+                .await
+                .unwrap(),
+            0
+        );
+        let later = now + Duration::minutes(STALE_REPORT_MINUTES + 1);
+        assert_eq!(
+            run_due_reports(&db, &StubMediaStorage, limits, later)
+                .await
+                .unwrap(),
+            1
+        );
+        let lost = ReportJob::get(&db, store_id, job.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lost.status, ReportStatus::Failed.as_str());
+        let ready = ReportJob::get(&db, store_id, running.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ready.status, ReportStatus::Ready.as_str());
+    }
+}
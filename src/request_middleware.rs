@@ -1,9 +1,10 @@
 use axum::{
-    extract::MatchedPath,
+    extract::{ConnectInfo, MatchedPath},
     http::{Request, Response},
     middleware::Next,
     response::IntoResponse,
 };
+use std::net::SocketAddr;
 use std::time::Instant;
 use tracing::{info, warn};
 use uuid::Uuid;
@@ -79,7 +80,7 @@ pub async fn request_logging_middleware(
 }
 
 /// Extract client IP from request headers, considering common proxy headers
-fn get_client_ip(request: &Request<axum::body::Body>) -> String {
+pub fn get_client_ip(request: &Request<axum::body::Body>) -> String {
     let headers = request.headers();
 
     // Try various headers in order of preference
@@ -120,7 +121,12 @@ fn get_client_ip(request: &Request<axum::body::Body>) -> String {
         }
     }
 
-    // Fallback to connection info (though this might not be available in all cases)
+    // Fall back to the peer address, there when the server was started with
+    // `into_make_service_with_connect_info`
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        return peer.ip().to_string();
+    }
+
     "unknown".to_string()
 }
 
@@ -182,6 +188,17 @@ mod tests {
         assert_eq!(ip, "198.51.100.1");
     }
 
+    #[test]
+    fn test_get_client_ip_peer_address() {
+        let mut request = Request::builder().body(axum::body::Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 9], 52144))));
+
+        let ip = get_client_ip(&request);
+        assert_eq!(ip, "198.51.100.9");
+    }
+
     #[test]
     fn test_get_client_ip_unknown() {
         let request = Request::builder().body(axum::body::Body::empty()).unwrap();
@@ -0,0 +1,214 @@
+//! Data retention for append-only tables.
+//!
+//! Each [`PrunableTable`] keeps rows for a configured number of days and a
+//! background job deletes older ones in bounded batches. What each job did
+//! last is kept in [`PRUNE_LOG`] for `GET /admin/retention`. Tables that are
+//! not [`PrunableTable`]s are never pruned, whatever the configuration says.
+
+use crate::config::RetentionConfig;
+use crate::db::retention::PrunableTable;
+use crate::metrics::{self, Counter};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use utoipa::ToSchema;
+
+/// What the retention jobs last did, per table
+pub static PRUNE_LOG: PruneLog = PruneLog::new();
+
+/// How long one table keeps its rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub table: PrunableTable,
+    pub keep_days: u32,
+}
+
+impl RetentionPolicy {
+    /// Rows older than this are due for pruning
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - Duration::days(self.keep_days.into())
+    }
+}
+
+/// One policy per prunable table
+pub fn policies(config: &RetentionConfig) -> [RetentionPolicy; 4] {
+    [
+        RetentionPolicy {
+            table: PrunableTable::Tombstones,
+            keep_days: config.tombstone_days,
+        },
+        RetentionPolicy {
+            table: PrunableTable::InventorySyncs,
+            keep_days: config.inventory_sync_days,
+        },
+        RetentionPolicy {
+            table: PrunableTable::ReportJobs,
+            keep_days: config.report_days,
+        },
+        RetentionPolicy {
+            table: PrunableTable::StoreSnapshots,
+            keep_days: config.snapshot_days,
+        },
+    ]
+}
+
+/// Counter of rows deleted from `table`
+pub fn pruned_counter(table: PrunableTable) -> &'static Counter {
+    match table {
+        PrunableTable::Tombstones => &metrics::TOMBSTONES_PRUNED,
+        PrunableTable::InventorySyncs => &metrics::INVENTORY_SYNCS_PRUNED,
+        PrunableTable::ReportJobs => &metrics::REPORT_JOBS_PRUNED,
+        PrunableTable::StoreSnapshots => &metrics::STORE_SNAPSHOTS_PRUNED,
+    }
+}
+
+/// Outcome of one pruning run over a table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneRun {
+    pub rows_pruned: u64,
+    /// Set when a batch failed; rows deleted before it stay deleted
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PruneStats {
+    pub table: PrunableTable,
+    pub keep_days: u32,
+    /// `None` until the job has run once since startup
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: u64,
+    pub last_rows_pruned: u64,
+    /// Rows pruned since startup
+    pub total_rows_pruned: u64,
+    pub last_error: Option<String>,
+}
+
+pub struct PruneLog {
+    entries: RwLock<BTreeMap<PrunableTable, PruneStats>>,
+}
+
+impl PruneLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Start tracking a table, before its job first runs
+    pub fn register(&self, policy: RetentionPolicy) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.insert(
+                policy.table,
+                PruneStats {
+                    table: policy.table,
+                    keep_days: policy.keep_days,
+                    last_run_at: None,
+                    last_duration_ms: 0,
+                    last_rows_pruned: 0,
+                    total_rows_pruned: 0,
+                    last_error: None,
+                },
+            );
+        }
+    }
+
+    pub fn record(
+        &self,
+        table: PrunableTable,
+        at: DateTime<Utc>,
+        duration: std::time::Duration,
+        run: PruneRun,
+    ) {
+        let Ok(mut entries) = self.entries.write() else {
+            return;
+        };
+        if let Some(stats) = entries.get_mut(&table) {
+            stats.last_run_at = Some(at);
+            stats.last_duration_ms = duration.as_millis().try_into().unwrap_or(u64::MAX);
+            stats.last_rows_pruned = run.rows_pruned;
+            stats.total_rows_pruned += run.rows_pruned;
+            stats.last_error = run.error;
+        }
+    }
+
+    /// Oldest instant whose rows of `table` are still guaranteed to be
+    /// kept, or `None` when the table is not pruned
+    pub fn horizon(&self, table: PrunableTable, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let entries = self.entries.read().ok()?;
+        entries.get(&table).map(|stats| {
+            RetentionPolicy {
+                table,
+                keep_days: stats.keep_days,
+            }
+            .cutoff(now)
+        })
+    }
+
+    pub fn snapshot(&self) -> Vec<PruneStats> {
+        self.entries
+            .read()
+            .map(|entries| entries.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for PruneLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOMBSTONES: RetentionPolicy = RetentionPolicy {
+        table: PrunableTable::Tombstones,
+        keep_days: 90,
+    };
+
+    #[test]
+    fn test_record_accumulates_per_table() {
+        let log = PruneLog::new();
+        log.register(TOMBSTONES);
+        let now = Utc::now();
+        for rows_pruned in [5_000, 120] {
+            let run = PruneRun {
+                rows_pruned,
+                error: None,
+            };
+            log.record(
+                PrunableTable::Tombstones,
+                now,
+                std::time::Duration::from_millis(40),
+                run,
+            );
+        }
+        // Never registered, so not tracked
+        log.record(
+            PrunableTable::InventorySyncs,
+            now,
+            std::time::Duration::ZERO,
+            PruneRun::default(),
+        );
+
+        let stats = log.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].last_rows_pruned, 120);
+        assert_eq!(stats[0].total_rows_pruned, 5_120);
+        assert_eq!(stats[0].last_run_at, Some(now));
+    }
+
+    #[test]
+    fn test_horizon_only_for_pruned_tables() {
+        let log = PruneLog::new();
+        let now = Utc::now();
+        assert_eq!(log.horizon(PrunableTable::Tombstones, now), None);
+        log.register(TOMBSTONES);
+        assert_eq!(
+            log.horizon(PrunableTable::Tombstones, now),
+            Some(now - Duration::days(90))
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Orderly stop on SIGTERM or Ctrl-C.
+//!
+//! Every background task that must not be cut off mid-run registers a named
+//! [`ShutdownHook`] with the process-wide [`ShutdownCoordinator`]. On a
+//! signal the coordinator flips its watch channel: the HTTP server stops
+//! accepting connections, jobs return after their current iteration and the
+//! event dispatcher waits for the events it is delivering. `main` then
+//! [drains](ShutdownCoordinator::drain) the hooks with a deadline and logs
+//! which ones finished and which it gave up on.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use tracing::{error, info, warn};
+
+/// Hooks not yet drained, each with the channel that closes when it is dropped
+type PendingHooks = Vec<(String, oneshot::Receiver<()>)>;
+
+/// Hands out hooks and tells them when to stop. Clones share the signal.
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    signal: Arc<watch::Sender<bool>>,
+    hooks: Arc<Mutex<PendingHooks>>,
+}
+
+/// One subsystem's part in a shutdown. It is done when the hook is dropped.
+pub struct ShutdownHook {
+    signal: watch::Receiver<bool>,
+    _done: oneshot::Sender<()>,
+}
+
+/// How a hook ended during [`ShutdownCoordinator::drain`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStatus {
+    Finished,
+    /// Still running when the deadline passed
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutcome {
+    pub name: String,
+    pub status: HookStatus,
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        Self {
+            signal: Arc::new(watch::channel(false).0),
+            hooks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// A hook for the subsystem called `name`, as it appears in the logs
+    pub fn register(&self, name: impl Into<String>) -> ShutdownHook {
+        let (done, finished) = oneshot::channel();
+        self.hooks
+            .lock()
+            .expect("shutdown hooks poisoned")
+            .push((name.into(), finished));
+        ShutdownHook {
+            signal: self.signal.subscribe(),
+            _done: done,
+        }
+    }
+
+    /// Ask every hook to stop. Calling it again does nothing.
+    pub fn trigger(&self) {
+        self.signal
+            .send_if_modified(|requested| !std::mem::replace(requested, true));
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.signal.borrow()
+    }
+
+    /// Resolves once shutdown is triggered
+    pub async fn triggered(&self) {
+        let mut signal = self.signal.subscribe();
+        let _ = signal.wait_for(|requested| *requested).await;
+    }
+
+    /// Trigger on SIGTERM (what orchestrators send) or Ctrl-C
+    pub fn listen_for_signals(&self) {
+        let coordinator = self.clone();
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let terminate = async {
+                match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                    Ok(mut signal) => {
+                        signal.recv().await;
+                    }
+                    Err(e) => {
+                        error!(error = %e, "Failed to listen for SIGTERM; only Ctrl-C stops the server cleanly");
+                        std::future::pending::<()>().await;
+                    }
+                }
+            };
+            #[cfg(not(unix))]
+            let terminate = std::future::pending::<()>();
+
+            let signal = tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl-C",
+                () = terminate => "SIGTERM",
+            };
+            info!(signal, "Shutting down");
+            coordinator.trigger();
+        });
+    }
+
+    /// Wait up to `deadline` for every registered hook to finish, logging
+    /// each one. Only call it after [`trigger`](Self::trigger).
+    pub async fn drain(&self, deadline: Duration) -> Vec<HookOutcome> {
+        let hooks = std::mem::take(&mut *self.hooks.lock().expect("shutdown hooks poisoned"));
+        let until = tokio::time::Instant::now() + deadline;
+        let waits = hooks.into_iter().map(|(name, finished)| async move {
+            // A dropped hook closes the channel; that is its way of finishing
+            let status = match tokio::time::timeout_at(until, finished).await {
+                Ok(_) => {
+                    info!(hook = %name, "Shutdown hook finished");
+                    HookStatus::Finished
+                }
+                Err(_) => {
+                    warn!(hook = %name, ?deadline, "Shutdown hook timed out");
+                    HookStatus::TimedOut
+                }
+            };
+            HookOutcome { name, status }
+        });
+        futures::future::join_all(waits).await
+    }
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownHook {
+    /// Resolves once shutdown is triggered, or the coordinator is gone
+    pub async fn requested(&self) {
+        let mut signal = self.signal.clone();
+        let _ = signal.wait_for(|requested| *requested).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_reports_finished_and_stuck_hooks() {
+        let coordinator = ShutdownCoordinator::new();
+        let quick = coordinator.register("quick");
+        let _stuck = coordinator.register("stuck");
+        let worker = tokio::spawn(async move { quick.requested().await });
+        assert!(!coordinator.is_shutting_down());
+
+        coordinator.trigger();
+        coordinator.trigger();
+        coordinator.triggered().await;
+        let outcomes = coordinator.drain(Duration::from_millis(50)).await;
+        worker.await.unwrap();
+        assert!(coordinator.is_shutting_down());
+        assert_eq!(
+            outcomes,
+            [
+                HookOutcome {
+                    name: "quick".into(),
+                    status: HookStatus::Finished,
+                },
+                HookOutcome {
+                    name: "stuck".into(),
+                    status: HookStatus::TimedOut,
+                },
+            ]
+        );
+    }
+}
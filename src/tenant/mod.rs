@@ -0,0 +1,210 @@
+//! Tenants: separate marketplaces served by this backend.
+//!
+//! Every store and product belongs to one tenant, and every list, search and
+//! feed query filters on it through [`ForTenant`] so tenants never see each
+//! other's catalogue. A device picks its tenant at PoW verification, either
+//! with a `<tenant>:` prefix on its relay_id or an explicit `tenant_id`. The
+//! relay_id in the token always carries the prefix, so device ids of two
+//! tenants cannot collide in ownership checks. Rows created before tenants
+//! existed belong to [`DEFAULT_TENANT`], whose relay_ids stay unprefixed.
+
+use crate::auth::{claims_from_headers, Claims};
+use crate::entity::{product, store};
+use axum::http::{HeaderMap, StatusCode};
+use sea_orm::{sea_query::SimpleExpr, ColumnTrait, EntityTrait, QueryFilter, Select};
+
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Lets anonymous buyer apps of another tenant browse its catalogue
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+const MAX_TENANT_LEN: usize = 32;
+const PREFIX_SEPARATOR: char = ':';
+
+/// Serde default for tokens issued before tenants existed
+pub fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// 1-32 lowercase letters, digits or dashes
+pub fn validate_tenant_id(tenant_id: &str) -> Result<(), String> {
+    let well_formed = !tenant_id.is_empty()
+        && tenant_id.len() <= MAX_TENANT_LEN
+        && tenant_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if well_formed {
+        Ok(())
+    } else {
+        Err(format!(
+            "tenant_id must be 1 to {MAX_TENANT_LEN} lowercase letters, digits or dashes"
+        ))
+    }
+}
+
+/// Who a verified device is, once its tenant is settled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub tenant_id: String,
+    /// Prefixed with the tenant unless it is the default one
+    pub relay_id: String,
+}
+
+/// Settle a device's tenant from its relay_id prefix and/or explicit field.
+/// The two must agree when both are given.
+pub fn resolve_identity(relay_id: &str, explicit: Option<&str>) -> Result<Identity, String> {
+    let (prefix, device) = match relay_id.split_once(PREFIX_SEPARATOR) {
+        Some((prefix, device)) => (Some(prefix), device),
+        None => (None, relay_id),
+    };
+    if device.is_empty() {
+        return Err("relay_id must not be empty".to_string());
+    }
+    let tenant_id = match (prefix, explicit) {
+        (Some(prefix), Some(explicit)) if prefix != explicit => {
+            return Err(format!(
+                "relay_id prefix '{prefix}' does not match tenant_id '{explicit}'"
+            ))
+        }
+        (Some(tenant), _) | (None, Some(tenant)) => tenant,
+        (None, None) => DEFAULT_TENANT,
+    };
+    validate_tenant_id(tenant_id)?;
+    let relay_id = if tenant_id == DEFAULT_TENANT {
+        device.to_string()
+    } else {
+        format!("{tenant_id}{PREFIX_SEPARATOR}{device}")
+    };
+    Ok(Identity {
+        tenant_id: tenant_id.to_string(),
+        relay_id,
+    })
+}
+
+/// Tenant a request acts in: the caller's token when there is one, else the
+/// `X-Tenant-Id` header, else the default tenant
+pub fn request_tenant(
+    claims: Option<&Claims>,
+    headers: &HeaderMap,
+) -> Result<String, (StatusCode, String)> {
+    if let Some(claims) = claims {
+        return Ok(claims.tenant_id.clone());
+    }
+    match headers.get(TENANT_HEADER) {
+        Some(value) => {
+            let tenant_id = value
+                .to_str()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid X-Tenant-Id".to_string()))?;
+            validate_tenant_id(tenant_id).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+            Ok(tenant_id.to_string())
+        }
+        None => Ok(default_tenant()),
+    }
+}
+
+/// [`request_tenant`] for handlers that do not otherwise authenticate
+pub fn tenant_from_headers(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    request_tenant(claims_from_headers(headers).as_ref(), headers)
+}
+
+/// Tables whose rows belong to a tenant
+pub trait TenantScoped: EntityTrait {
+    fn tenant_column() -> Self::Column;
+}
+
+impl TenantScoped for store::Entity {
+    fn tenant_column() -> Self::Column {
+        store::Column::TenantId
+    }
+}
+
+impl TenantScoped for product::Entity {
+    fn tenant_column() -> Self::Column {
+        product::Column::TenantId
+    }
+}
+
+/// Rows of `E` in `tenant_id`, for queries that join or select other entities
+pub fn tenant_condition<E: TenantScoped>(tenant_id: &str) -> SimpleExpr {
+    E::tenant_column().eq(tenant_id)
+}
+
+/// Restrict a query to one tenant's rows
+pub trait ForTenant {
+    fn for_tenant(self, tenant_id: &str) -> Self;
+}
+
+impl<E: TenantScoped> ForTenant for Select<E> {
+    fn for_tenant(self, tenant_id: &str) -> Self {
+        self.filter(tenant_condition::<E>(tenant_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    #[test]
+    fn test_prefix_and_explicit_field_agree() {
+        let prefixed = resolve_identity("acme:device-1", None).unwrap();
+        let explicit = resolve_identity("device-1", Some("acme")).unwrap();
+        let both = resolve_identity("acme:device-1", Some("acme")).unwrap();
+        for identity in [prefixed, explicit, both] {
+            assert_eq!(identity.tenant_id, "acme");
+            assert_eq!(identity.relay_id, "acme:device-1");
+        }
+        assert!(resolve_identity("acme:device-1", Some("other")).is_err());
+        assert!(resolve_identity("Acme:device-1", None).is_err());
+        assert!(resolve_identity("acme:", None).is_err());
+    }
+
+    #[test]
+    fn test_default_tenant_keeps_relay_ids_unprefixed() {
+        for (relay_id, explicit) in [("device-1", None), ("default:device-1", Some("default"))] {
+            let identity = resolve_identity(relay_id, explicit).unwrap();
+            assert_eq!(identity.tenant_id, DEFAULT_TENANT);
+            assert_eq!(identity.relay_id, "device-1");
+        }
+    }
+
+    #[test]
+    fn test_anonymous_requests_pick_tenant_from_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_tenant(None, &headers).unwrap(), DEFAULT_TENANT);
+        headers.insert(TENANT_HEADER, "acme".parse().unwrap());
+        assert_eq!(request_tenant(None, &headers).unwrap(), "acme");
+
+        let mut claims = Claims::new("device-1".into(), String::new(), 1, "buyer".into());
+        claims.tenant_id = "other".into();
+        assert_eq!(request_tenant(Some(&claims), &headers).unwrap(), "other");
+
+        headers.insert(TENANT_HEADER, "../etc".parse().unwrap());
+        assert_eq!(
+            request_tenant(None, &headers).unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_store_and_product_queries_are_isolated() {
+        let stores = store::Entity::find()
+            .for_tenant("acme")
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            stores.contains(r#"WHERE "stores"."tenant_id" = 'acme'"#),
+            "{stores}"
+        );
+
+        let products = product::Entity::find()
+            .filter(product::Column::StoreId.is_not_null())
+            .for_tenant("acme")
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            products.contains(r#"AND "products"."tenant_id" = 'acme'"#),
+            "{products}"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! HTTPS termination for deployments without a reverse proxy.
+//!
+//! Enabled with the `tls` feature and the `TLS_CERT_PATH` / `TLS_KEY_PATH`
+//! settings. The certificate is reloaded on SIGHUP and whenever either file
+//! changes on disk, so a Let's Encrypt renewal is picked up without a
+//! restart. A reload that fails keeps serving the previous certificate.
+
+use crate::config::TlsConfig;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+/// How often the certificate files are checked for changes
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Load the certificate and key, failing when either is unreadable, not
+/// PEM, or the key does not belong to the certificate
+pub async fn load(tls: &TlsConfig) -> anyhow::Result<RustlsConfig> {
+    install_crypto_provider();
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to load TLS certificate {} with key {}: {e}",
+                tls.cert_path.display(),
+                tls.key_path.display()
+            )
+        })
+}
+
+/// Re-read the certificate and key into the running server
+pub async fn reload(config: &RustlsConfig, tls: &TlsConfig) -> anyhow::Result<()> {
+    config
+        .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to reload TLS certificate {} with key {}: {e}",
+                tls.cert_path.display(),
+                tls.key_path.display()
+            )
+        })
+}
+
+/// Reload on SIGHUP and when either file's modification time changes
+pub fn spawn_reloader(config: RustlsConfig, tls: TlsConfig, interval: Duration) {
+    tokio::spawn(async move {
+        let mut seen = modified(&tls);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        #[cfg(unix)]
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                error!(error = %e, "Failed to listen for SIGHUP; relying on file checks");
+                None
+            }
+        };
+        loop {
+            #[cfg(unix)]
+            let trigger = tokio::select! {
+                _ = ticker.tick() => "file change",
+                Some(()) = async {
+                    match hangup.as_mut() {
+                        Some(signal) => signal.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => "SIGHUP",
+            };
+            #[cfg(not(unix))]
+            let trigger = {
+                ticker.tick().await;
+                "file change"
+            };
+
+            let current = modified(&tls);
+            if trigger == "file change" && current == seen {
+                continue;
+            }
+            seen = current;
+            match reload(&config, &tls).await {
+                Ok(()) => info!(trigger, "TLS certificate reloaded"),
+                Err(e) => error!(trigger, error = %e, "Keeping the previous TLS certificate"),
+            }
+        }
+    });
+}
+
+/// Serve `app` over HTTPS until the process exits
+pub async fn serve(
+    app: Router,
+    addr: SocketAddr,
+    config: RustlsConfig,
+    handle: Handle,
+) -> std::io::Result<()> {
+    axum_server::bind_rustls(addr, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+}
+
+fn modified(tls: &TlsConfig) -> (Option<SystemTime>, Option<SystemTime>) {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    (mtime(&tls.cert_path), mtime(&tls.key_path))
+}
+
+/// rustls refuses to pick a provider when more than one is compiled in
+fn install_crypto_provider() {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+}
@@ -0,0 +1,219 @@
+//! Store trust score: one 0-100 signal for buyers.
+//!
+//! Each component maps one property of the store onto 0.0-1.0:
+//!
+//! | component    | 1.0                    | 0.0                        |
+//! |--------------|------------------------|----------------------------|
+//! | verification | store is verified      | not verified               |
+//! | rating       | 5-star average         | 0-star average             |
+//! | fulfillment  | every order fulfilled  | no order fulfilled         |
+//! | disputes     | no disputed orders     | 10% or more disputed       |
+//! | account age  | a year old or more     | created today              |
+//!
+//! The score is the weighted mean of the known components, times 100 and
+//! rounded to one decimal. Components without data (no ratings yet, no
+//! orders yet) are left out and the remaining weights renormalised, so a new
+//! store is not marked down for history it cannot have.
+
+use crate::entity::store::Model as StoreModel;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Scores below this are flagged to admins when a store drops under it
+pub const LOW_TRUST_SCORE: f64 = 40.0;
+
+/// Share of disputed orders at which the dispute component bottoms out
+const DISPUTE_RATE_FLOOR: f64 = 0.10;
+
+/// Age at which a store gets full marks for account age
+const MATURE_ACCOUNT_DAYS: f64 = 365.0;
+
+/// Relative importance of each component; only ratios matter
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TrustWeights {
+    pub verification: f64,
+    pub rating: f64,
+    pub fulfillment: f64,
+    pub disputes: f64,
+    pub account_age: f64,
+}
+
+impl Default for TrustWeights {
+    fn default() -> Self {
+        Self {
+            verification: 0.25,
+            rating: 0.25,
+            fulfillment: 0.20,
+            disputes: 0.20,
+            account_age: 0.10,
+        }
+    }
+}
+
+/// What the score is computed from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrustInputs {
+    pub is_verified: bool,
+    /// Average rating out of 5, when the store has one
+    pub rating: Option<f32>,
+    /// Fulfilled share of completed orders, when there are any
+    pub fulfillment_rate: Option<f64>,
+    /// Disputed share of orders, when there are any
+    pub dispute_rate: Option<f64>,
+    pub account_age_days: i64,
+}
+
+impl TrustInputs {
+    /// Inputs available from the store record alone. Order history is not
+    /// tracked yet, so fulfillment and disputes are unknown.
+    pub fn for_store(store: &StoreModel, now: DateTime<Utc>) -> Self {
+        Self {
+            is_verified: store.is_verified,
+            rating: store.rating,
+            fulfillment_rate: None,
+            dispute_rate: None,
+            account_age_days: (now - store.created_at).num_days().max(0),
+        }
+    }
+}
+
+pub fn verification_component(is_verified: bool) -> f64 {
+    if is_verified {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+pub fn rating_component(rating: f32) -> f64 {
+    (f64::from(rating) / 5.0).clamp(0.0, 1.0)
+}
+
+pub fn fulfillment_component(fulfillment_rate: f64) -> f64 {
+    fulfillment_rate.clamp(0.0, 1.0)
+}
+
+pub fn dispute_component(dispute_rate: f64) -> f64 {
+    1.0 - (dispute_rate / DISPUTE_RATE_FLOOR).clamp(0.0, 1.0)
+}
+
+pub fn account_age_component(account_age_days: i64) -> f64 {
+    (account_age_days as f64 / MATURE_ACCOUNT_DAYS).clamp(0.0, 1.0)
+}
+
+/// Weighted 0-100 score over the known components
+pub fn trust_score(inputs: &TrustInputs, weights: &TrustWeights) -> f64 {
+    let components = [
+        Some((
+            weights.verification,
+            verification_component(inputs.is_verified),
+        )),
+        inputs.rating.map(|r| (weights.rating, rating_component(r))),
+        inputs
+            .fulfillment_rate
+            .map(|r| (weights.fulfillment, fulfillment_component(r))),
+        inputs
+            .dispute_rate
+            .map(|r| (weights.disputes, dispute_component(r))),
+        Some((
+            weights.account_age,
+            account_age_component(inputs.account_age_days),
+        )),
+    ];
+    let (weighted, total_weight) = components
+        .iter()
+        .flatten()
+        .filter(|(weight, _)| *weight > 0.0)
+        .fold((0.0, 0.0), |(sum, total), (weight, value)| {
+            (sum + weight * value, total + weight)
+        });
+    if total_weight == 0.0 {
+        return 0.0;
+    }
+    (weighted / total_weight * 1000.0).round() / 10.0
+}
+
+/// Whether a recomputed score is worth telling admins about: a move of at
+/// least `threshold` points, or a drop below [`LOW_TRUST_SCORE`]
+pub fn should_alert(previous: Option<f64>, score: f64, threshold: f64) -> bool {
+    let Some(previous) = previous else {
+        return false;
+    };
+    (score - previous).abs() >= threshold
+        || (previous >= LOW_TRUST_SCORE && score < LOW_TRUST_SCORE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> TrustInputs {
+        TrustInputs {
+            is_verified: true,
+            rating: Some(4.0),
+            fulfillment_rate: Some(0.9),
+            dispute_rate: Some(0.02),
+            account_age_days: 730,
+        }
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(verification_component(true), 1.0);
+        assert_eq!(verification_component(false), 0.0);
+        assert_eq!(rating_component(4.0), 0.8);
+        assert_eq!(rating_component(7.0), 1.0);
+        assert_eq!(fulfillment_component(0.75), 0.75);
+        assert_eq!(dispute_component(0.0), 1.0);
+        assert!((dispute_component(0.05) - 0.5).abs() < 1e-9);
+        assert_eq!(dispute_component(0.5), 0.0);
+        assert_eq!(account_age_component(0), 0.0);
+        assert_eq!(account_age_component(73), 0.2);
+        assert_eq!(account_age_component(1000), 1.0);
+    }
+
+    #[test]
+    fn test_weighted_score() {
+        // 0.25*1 + 0.25*0.8 + 0.2*0.9 + 0.2*0.8 + 0.1*1 = 0.89
+        assert_eq!(trust_score(&inputs(), &TrustWeights::default()), 89.0);
+    }
+
+    #[test]
+    fn test_unknown_components_are_left_out() {
+        let new_store = TrustInputs {
+            is_verified: true,
+            rating: None,
+            fulfillment_rate: None,
+            dispute_rate: None,
+            account_age_days: 0,
+        };
+        // Only verification (0.25) and account age (0.1) count: 0.25 / 0.35
+        assert_eq!(trust_score(&new_store, &TrustWeights::default()), 71.4);
+    }
+
+    #[test]
+    fn test_weights_are_tunable() {
+        let verification_only = TrustWeights {
+            verification: 1.0,
+            rating: 0.0,
+            fulfillment: 0.0,
+            disputes: 0.0,
+            account_age: 0.0,
+        };
+        assert_eq!(trust_score(&inputs(), &verification_only), 100.0);
+        let nothing = TrustWeights {
+            verification: 0.0,
+            ..verification_only
+        };
+        assert_eq!(trust_score(&inputs(), &nothing), 0.0);
+    }
+
+    #[test]
+    fn test_alerts_on_big_moves_and_low_trust() {
+        assert!(!should_alert(None, 10.0, 15.0));
+        assert!(!should_alert(Some(80.0), 75.0, 15.0));
+        assert!(should_alert(Some(80.0), 60.0, 15.0));
+        assert!(should_alert(Some(45.0), 38.0, 15.0));
+        assert!(!should_alert(Some(35.0), 30.0, 15.0));
+    }
+}
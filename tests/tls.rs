@@ -0,0 +1,111 @@
+#![cfg(feature = "tls")]
+
+use axum::{routing::get, Router};
+use axum_server::Handle;
+use std::path::PathBuf;
+use std::time::Duration;
+use transac::config::TlsConfig;
+use transac::tls;
+
+struct TestCert {
+    cert_pem: String,
+    key_pem: String,
+}
+
+fn self_signed() -> TestCert {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    TestCert {
+        cert_pem: cert.cert.pem(),
+        key_pem: cert.key_pair.serialize_pem(),
+    }
+}
+
+fn write_pair(dir: &std::path::Path, cert_pem: &str, key_pem: &str) -> TlsConfig {
+    let settings = TlsConfig {
+        cert_path: dir.join("cert.pem"),
+        key_path: dir.join("key.pem"),
+    };
+    std::fs::write(&settings.cert_path, cert_pem).unwrap();
+    std::fs::write(&settings.key_path, key_pem).unwrap();
+    settings
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("transac-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn client_trusting(cert_pem: &str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+        .tls_built_in_root_certs(false)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_https_request_with_self_signed_certificate() {
+    let dir = temp_dir();
+    let first = self_signed();
+    let settings = write_pair(&dir, &first.cert_pem, &first.key_pem);
+    let config = tls::load(&settings).await.unwrap();
+
+    let app = Router::new().route("/healthz", get(|| async { "ok" }));
+    let handle = Handle::new();
+    let server = tokio::spawn(tls::serve(
+        app,
+        "127.0.0.1:0".parse().unwrap(),
+        config.clone(),
+        handle.clone(),
+    ));
+    let port = handle.listening().await.unwrap().port();
+    let url = format!("https://localhost:{port}/healthz");
+
+    let response = client_trusting(&first.cert_pem)
+        .get(&url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "ok");
+
+    // A renewed certificate is served without restarting
+    tls::spawn_reloader(config, settings, Duration::from_millis(50));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let renewed = self_signed();
+    write_pair(&dir, &renewed.cert_pem, &renewed.key_pem);
+    let client = client_trusting(&renewed.cert_pem);
+    let mut served = false;
+    for _ in 0..40 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if client.get(&url).send().await.is_ok() {
+            served = true;
+            break;
+        }
+    }
+    assert!(served, "renewed certificate was not picked up");
+
+    handle.shutdown();
+    let _ = server.await;
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn test_startup_fails_on_unreadable_or_mismatched_pair() {
+    let dir = temp_dir();
+    let missing = TlsConfig {
+        cert_path: dir.join("missing.pem"),
+        key_path: dir.join("missing-key.pem"),
+    };
+    let err = tls::load(&missing).await.unwrap_err().to_string();
+    assert!(err.contains("missing.pem"), "{err}");
+
+    let cert = self_signed();
+    let other = self_signed();
+    let mismatched = write_pair(&dir, &cert.cert_pem, &other.key_pem);
+    let err = tls::load(&mismatched).await.unwrap_err().to_string();
+    assert!(err.starts_with("Failed to load TLS certificate"), "{err}");
+
+    let _ = std::fs::remove_dir_all(dir);
+}